@@ -1,4 +1,5 @@
 use std::{
+    net::IpAddr,
     ops::Bound,
     sync::Arc,
     time::Duration,
@@ -33,6 +34,8 @@ use database::{
 use file_storage::{
     FileRangeStream,
     FileStream,
+    ImageTransform,
+    ResumableUploadProgress,
 };
 use futures::{
     future::BoxFuture,
@@ -199,6 +202,16 @@ pub trait ApplicationApi: Send + Sync {
         caller: FunctionCaller,
     ) -> anyhow::Result<Result<FunctionReturn, FunctionError>>;
 
+    /// Count `requests` requests from a client address against the
+    /// deployment's per-IP rate limit, failing with a rate limited error if
+    /// it's over.
+    async fn check_client_rate_limit(
+        &self,
+        host: &ResolvedHostname,
+        client_address: IpAddr,
+        requests: u32,
+    ) -> anyhow::Result<()>;
+
     async fn latest_timestamp(
         &self,
         host: &ResolvedHostname,
@@ -225,6 +238,38 @@ pub trait ApplicationApi: Send + Sync {
         body: BoxStream<'_, anyhow::Result<Bytes>>,
     ) -> anyhow::Result<DeveloperDocumentId>;
 
+    async fn start_resumable_upload(
+        &self,
+        host: &ResolvedHostname,
+        request_id: RequestId,
+        component: ComponentId,
+        content_type: Option<ContentType>,
+    ) -> anyhow::Result<ResumableUploadProgress>;
+
+    async fn upload_resumable_part(
+        &self,
+        host: &ResolvedHostname,
+        request_id: RequestId,
+        upload_id: String,
+        offset: u64,
+        part: Bytes,
+    ) -> anyhow::Result<ResumableUploadProgress>;
+
+    async fn resumable_upload_progress(
+        &self,
+        host: &ResolvedHostname,
+        request_id: RequestId,
+        upload_id: String,
+    ) -> anyhow::Result<ResumableUploadProgress>;
+
+    async fn finish_resumable_upload(
+        &self,
+        host: &ResolvedHostname,
+        request_id: RequestId,
+        upload_id: String,
+        expected_sha256: Option<Sha256Digest>,
+    ) -> anyhow::Result<DeveloperDocumentId>;
+
     async fn get_file_range(
         &self,
         host: &ResolvedHostname,
@@ -244,6 +289,16 @@ pub trait ApplicationApi: Send + Sync {
         file_storage_id: FileStorageId,
     ) -> anyhow::Result<FileStream>;
 
+    async fn get_transformed_image(
+        &self,
+        host: &ResolvedHostname,
+        request_id: RequestId,
+        origin: ConvexOrigin,
+        component: ComponentId,
+        file_storage_id: FileStorageId,
+        transform: ImageTransform,
+    ) -> anyhow::Result<FileStream>;
+
     // Returns a fallible subscription client. The implementation is not required to
     // recover from transient errors with the underlying connection or stream. The
     // client is responsible to Drop the client and create a new one on any system
@@ -445,6 +500,16 @@ impl<RT: Runtime> ApplicationApi for Application<RT> {
         self.any_udf(request_id, path, args, identity, caller).await
     }
 
+    async fn check_client_rate_limit(
+        &self,
+        _host: &ResolvedHostname,
+        client_address: IpAddr,
+        requests: u32,
+    ) -> anyhow::Result<()> {
+        self.runner()
+            .check_client_rate_limit(client_address, requests)
+    }
+
     async fn latest_timestamp(
         &self,
         _host: &ResolvedHostname,
@@ -504,6 +569,47 @@ impl<RT: Runtime> ApplicationApi for Application<RT> {
         .await
     }
 
+    async fn start_resumable_upload(
+        &self,
+        _host: &ResolvedHostname,
+        _request_id: RequestId,
+        component: ComponentId,
+        content_type: Option<ContentType>,
+    ) -> anyhow::Result<ResumableUploadProgress> {
+        self.start_resumable_upload(component, content_type).await
+    }
+
+    async fn upload_resumable_part(
+        &self,
+        _host: &ResolvedHostname,
+        _request_id: RequestId,
+        upload_id: String,
+        offset: u64,
+        part: Bytes,
+    ) -> anyhow::Result<ResumableUploadProgress> {
+        self.upload_resumable_part(&upload_id, offset, part).await
+    }
+
+    async fn resumable_upload_progress(
+        &self,
+        _host: &ResolvedHostname,
+        _request_id: RequestId,
+        upload_id: String,
+    ) -> anyhow::Result<ResumableUploadProgress> {
+        self.resumable_upload_progress(&upload_id).await
+    }
+
+    async fn finish_resumable_upload(
+        &self,
+        _host: &ResolvedHostname,
+        _request_id: RequestId,
+        upload_id: String,
+        expected_sha256: Option<Sha256Digest>,
+    ) -> anyhow::Result<DeveloperDocumentId> {
+        self.finish_resumable_upload(&upload_id, expected_sha256)
+            .await
+    }
+
     async fn get_file_range(
         &self,
         _host: &ResolvedHostname,
@@ -527,6 +633,19 @@ impl<RT: Runtime> ApplicationApi for Application<RT> {
         self.get_file(component, file_storage_id).await
     }
 
+    async fn get_transformed_image(
+        &self,
+        _host: &ResolvedHostname,
+        _request_id: RequestId,
+        _origin: ConvexOrigin,
+        component: ComponentId,
+        file_storage_id: FileStorageId,
+        transform: ImageTransform,
+    ) -> anyhow::Result<FileStream> {
+        self.get_transformed_image(component, file_storage_id, transform)
+            .await
+    }
+
     async fn subscription_client(
         &self,
         _host: &ResolvedHostname,
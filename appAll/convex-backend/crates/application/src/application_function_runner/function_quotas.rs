@@ -0,0 +1,467 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    net::IpAddr,
+    num::NonZeroU32,
+    sync::Arc,
+    time::Duration,
+};
+
+use common::{
+    components::PublicFunctionPath,
+    errors::rate_limited_error,
+    knobs::{
+        FUNCTION_MAX_CONCURRENT_PER_FUNCTION,
+        FUNCTION_MAX_CONCURRENT_PER_IDENTITY,
+        FUNCTION_RATE_LIMIT_MAX_TRACKED_KEYS,
+        FUNCTION_RATE_LIMIT_PER_FUNCTION,
+        FUNCTION_RATE_LIMIT_PER_IDENTITY,
+        FUNCTION_RATE_LIMIT_PER_IP,
+    },
+    runtime::{
+        new_keyed_rate_limiter,
+        KeyedRateLimiter,
+        Runtime,
+    },
+    types::{
+        FunctionCaller,
+        UdfType,
+    },
+};
+use governor::Quota;
+use keybroker::Identity;
+use parking_lot::Mutex;
+
+use super::metrics::log_function_quota_exceeded;
+
+/// Clients over a concurrency quota are asked to wait this long, since we
+/// can't know when their running calls will finish.
+const CONCURRENCY_RETRY_AFTER: Duration = Duration::from_secs(1);
+
+/// Rate limits and concurrency quotas for public functions called by clients
+/// over the HTTP API and the sync protocol, configured by the
+/// `FUNCTION_RATE_LIMIT_*` and `FUNCTION_MAX_CONCURRENT_*` knobs.
+///
+/// Admin and system callers aren't limited, nor are functions called from
+/// other functions, crons or the scheduler. Clients' query subscriptions over
+/// the sync protocol only count against the per-IP limit, when they're added.
+pub struct FunctionQuotas<RT: Runtime> {
+    runtime: RT,
+    limits: FunctionQuotaLimits,
+    per_function: Option<KeyedRateLimiter<String, RT>>,
+    per_identity: Option<KeyedRateLimiter<String, RT>>,
+    per_ip: Option<KeyedRateLimiter<IpAddr, RT>>,
+    in_flight: Arc<Mutex<HashMap<InFlightKey, usize>>>,
+}
+
+/// Each limit is disabled when it's 0.
+#[derive(Clone, Copy, Debug)]
+pub struct FunctionQuotaLimits {
+    pub rate_per_function: u32,
+    pub rate_per_identity: u32,
+    pub rate_per_ip: u32,
+    pub max_concurrent_per_function: usize,
+    pub max_concurrent_per_identity: usize,
+}
+
+impl FunctionQuotaLimits {
+    pub fn from_knobs() -> Self {
+        Self {
+            rate_per_function: *FUNCTION_RATE_LIMIT_PER_FUNCTION,
+            rate_per_identity: *FUNCTION_RATE_LIMIT_PER_IDENTITY,
+            rate_per_ip: *FUNCTION_RATE_LIMIT_PER_IP,
+            max_concurrent_per_function: *FUNCTION_MAX_CONCURRENT_PER_FUNCTION,
+            max_concurrent_per_identity: *FUNCTION_MAX_CONCURRENT_PER_IDENTITY,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum InFlightKey {
+    Function(String),
+    Identity(String),
+}
+
+/// Counts a call against the concurrency quotas until it's dropped.
+pub struct FunctionQuotaPermit {
+    in_flight: Arc<Mutex<HashMap<InFlightKey, usize>>>,
+    keys: Vec<InFlightKey>,
+}
+
+impl Drop for FunctionQuotaPermit {
+    fn drop(&mut self) {
+        let mut in_flight = self.in_flight.lock();
+        for key in self.keys.drain(..) {
+            if let Some(count) = in_flight.get_mut(&key) {
+                *count -= 1;
+                if *count == 0 {
+                    in_flight.remove(&key);
+                }
+            }
+        }
+    }
+}
+
+impl<RT: Runtime> FunctionQuotas<RT> {
+    pub fn new(runtime: RT, limits: FunctionQuotaLimits) -> Self {
+        Self {
+            per_function: per_second_limiter(&runtime, limits.rate_per_function),
+            per_identity: per_second_limiter(&runtime, limits.rate_per_identity),
+            per_ip: per_second_limiter(&runtime, limits.rate_per_ip),
+            in_flight: Arc::new(Mutex::new(HashMap::new())),
+            limits,
+            runtime,
+        }
+    }
+
+    /// Count a call to `path` against the quotas for the function and the
+    /// caller's identity. The returned permit should be held until the call
+    /// finishes. Only mutations and actions count against the concurrency
+    /// quotas, since queries are short and usually cached.
+    pub fn acquire(
+        &self,
+        path: &PublicFunctionPath,
+        udf_type: UdfType,
+        identity: &Identity,
+        caller: &FunctionCaller,
+    ) -> anyhow::Result<FunctionQuotaPermit> {
+        let from_client = match caller {
+            FunctionCaller::HttpApi(_) => true,
+            // The sync worker reruns subscribed queries itself whenever their
+            // results change, so those aren't the client's doing.
+            FunctionCaller::SyncWorker(_) => udf_type != UdfType::Query,
+            _ => false,
+        };
+        if !from_client || identity.is_admin() || identity.is_system() {
+            return Ok(FunctionQuotaPermit {
+                in_flight: self.in_flight.clone(),
+                keys: vec![],
+            });
+        }
+        let function = path.clone().debug_into_component_path().debug_str();
+        let user = match identity {
+            Identity::User(user) => Some(user.attributes.token_identifier.0.clone()),
+            _ => None,
+        };
+
+        // Check the concurrency quotas before the rate limits so a rejected
+        // call doesn't use up any of the client's rate, and hold the lock
+        // until the call is counted so concurrent calls can't both squeeze in.
+        let mut in_flight_keys = vec![];
+        if udf_type == UdfType::Mutation || udf_type == UdfType::Action {
+            if let Some(ref user) = user
+                && self.limits.max_concurrent_per_identity > 0
+            {
+                in_flight_keys.push((
+                    InFlightKey::Identity(user.clone()),
+                    self.limits.max_concurrent_per_identity,
+                ));
+            }
+            if self.limits.max_concurrent_per_function > 0 {
+                in_flight_keys.push((
+                    InFlightKey::Function(function.clone()),
+                    self.limits.max_concurrent_per_function,
+                ));
+            }
+        }
+        let mut in_flight = self.in_flight.lock();
+        for (key, max) in &in_flight_keys {
+            if in_flight.get(key).copied().unwrap_or(0) < *max {
+                continue;
+            }
+            let (quota, short_msg, msg) = match key {
+                InFlightKey::Function(_) => (
+                    "concurrent_per_function",
+                    "TooManyConcurrentFunctionCalls",
+                    format!(
+                        "{function} already has {max} calls in progress, the most this deployment \
+                         allows. Try again when some of them have finished."
+                    ),
+                ),
+                InFlightKey::Identity(_) => (
+                    "concurrent_per_identity",
+                    "TooManyConcurrentUserCalls",
+                    format!(
+                        "This user already has {max} mutations and actions in progress, the most \
+                         this deployment allows. Try again when some of them have finished."
+                    ),
+                ),
+            };
+            log_function_quota_exceeded(quota);
+            return Err(rate_limited_error(short_msg, msg, CONCURRENCY_RETRY_AFTER));
+        }
+
+        // Check the user's own limit first, so a user who keeps calling after
+        // going over it doesn't use up the function's rate for everyone else.
+        if let Some(ref limiter) = self.per_identity
+            && let Some(ref user) = user
+        {
+            self.check(limiter, user, 1, self.limits.rate_per_identity)
+                .map_err(|retry_after| {
+                    log_function_quota_exceeded("rate_per_identity");
+                    rate_limited_error(
+                        "UserRateLimited",
+                        format!(
+                            "This user made more than the {} function calls per second this \
+                             deployment allows. Try again in {}ms.",
+                            self.limits.rate_per_identity,
+                            retry_after.as_millis(),
+                        ),
+                        retry_after,
+                    )
+                })?;
+        }
+        if let Some(ref limiter) = self.per_function {
+            self.check(limiter, &function, 1, self.limits.rate_per_function)
+                .map_err(|retry_after| {
+                    log_function_quota_exceeded("rate_per_function");
+                    rate_limited_error(
+                        "FunctionRateLimited",
+                        format!(
+                            "{function} was called more than the {} times per second this \
+                             deployment allows. Try again in {}ms.",
+                            self.limits.rate_per_function,
+                            retry_after.as_millis(),
+                        ),
+                        retry_after,
+                    )
+                })?;
+        }
+
+        let keys: Vec<_> = in_flight_keys.into_iter().map(|(key, _)| key).collect();
+        for key in &keys {
+            *in_flight.entry(key.clone()).or_insert(0) += 1;
+        }
+        Ok(FunctionQuotaPermit {
+            in_flight: self.in_flight.clone(),
+            keys,
+        })
+    }
+
+    /// Count `requests` requests from a client address against its rate
+    /// limit. A batch larger than the limit's burst counts as a full burst,
+    /// so it's allowed once the client has been idle for a second.
+    pub fn check_client_address(&self, address: IpAddr, requests: u32) -> anyhow::Result<()> {
+        let Some(ref limiter) = self.per_ip else {
+            return Ok(());
+        };
+        self.check(limiter, &address, requests, self.limits.rate_per_ip)
+            .map_err(|retry_after| {
+                log_function_quota_exceeded("rate_per_ip");
+                rate_limited_error(
+                    "ClientRateLimited",
+                    format!(
+                        "This client made more than the {} requests per second this deployment \
+                         allows. Try again in {}ms.",
+                        self.limits.rate_per_ip,
+                        retry_after.as_millis(),
+                    ),
+                    retry_after,
+                )
+            })
+    }
+
+    /// Returns how long to wait if `key` is over `limiter`'s limit, which
+    /// allows bursts of up to `burst` requests.
+    fn check<K: Hash + Eq + Clone>(
+        &self,
+        limiter: &KeyedRateLimiter<K, RT>,
+        key: &K,
+        requests: u32,
+        burst: u32,
+    ) -> Result<(), Duration> {
+        if limiter.len() > *FUNCTION_RATE_LIMIT_MAX_TRACKED_KEYS {
+            limiter.retain_recent();
+        }
+        // More requests than the burst could never all be allowed at once.
+        let Some(requests) = NonZeroU32::new(requests.min(burst)) else {
+            return Ok(());
+        };
+        match limiter.check_key_n(key, requests) {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(not_until)) => {
+                Err(not_until.wait_time_from(self.runtime.monotonic_now().into()))
+            },
+            // `requests` is at most the burst, so this doesn't happen.
+            Err(_) => Ok(()),
+        }
+    }
+}
+
+fn per_second_limiter<RT: Runtime, K: Hash + Eq + Clone>(
+    runtime: &RT,
+    per_second: u32,
+) -> Option<KeyedRateLimiter<K, RT>> {
+    let per_second = NonZeroU32::new(per_second)?;
+    Some(new_keyed_rate_limiter(
+        runtime.clone(),
+        Quota::per_second(per_second),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use common::{
+        components::{
+            ExportPath,
+            PublicFunctionPath,
+        },
+        errors::RetryAfter,
+        types::{
+            FunctionCaller,
+            UdfType,
+        },
+        version::ClientVersion,
+    };
+    use errors::ErrorMetadataAnyhowExt;
+    use keybroker::{
+        testing::TestUserIdentity,
+        Identity,
+        UserIdentity,
+    };
+    use runtime::testing::TestRuntime;
+    use sync_types::{
+        UdfPath,
+        UserIdentifier,
+    };
+
+    use super::{
+        FunctionQuotaLimits,
+        FunctionQuotas,
+    };
+
+    const NO_LIMITS: FunctionQuotaLimits = FunctionQuotaLimits {
+        rate_per_function: 0,
+        rate_per_identity: 0,
+        rate_per_ip: 0,
+        max_concurrent_per_function: 0,
+        max_concurrent_per_identity: 0,
+    };
+
+    fn path(path: &str) -> anyhow::Result<PublicFunctionPath> {
+        Ok(PublicFunctionPath::RootExport(ExportPath::from(
+            path.parse::<UdfPath>()?.canonicalize(),
+        )))
+    }
+
+    #[convex_macro::test_runtime]
+    async fn test_rate_per_identity(rt: TestRuntime) -> anyhow::Result<()> {
+        let quotas = FunctionQuotas::new(
+            rt.clone(),
+            FunctionQuotaLimits {
+                rate_per_identity: 2,
+                ..NO_LIMITS
+            },
+        );
+        let user = Identity::user(UserIdentity::test());
+        let caller = FunctionCaller::HttpApi(ClientVersion::unknown());
+        let path = path("messages:send")?;
+        for _ in 0..2 {
+            quotas.acquire(&path, UdfType::Mutation, &user, &caller)?;
+        }
+        let err = quotas
+            .acquire(&path, UdfType::Mutation, &user, &caller)
+            .err()
+            .unwrap();
+        assert_eq!(err.short_msg(), "UserRateLimited");
+        let RetryAfter(retry_after) = err.downcast_ref::<RetryAfter>().unwrap();
+        assert!(*retry_after > Duration::ZERO);
+
+        // Admins and calls from other functions aren't limited.
+        quotas.acquire(&path, UdfType::Mutation, &Identity::system(), &caller)?;
+        let from_action = FunctionCaller::Action {
+            parent_scheduled_job: None,
+        };
+        quotas.acquire(&path, UdfType::Mutation, &user, &from_action)?;
+
+        rt.advance_time(Duration::from_secs(1)).await;
+        quotas.acquire(&path, UdfType::Mutation, &user, &caller)?;
+        Ok(())
+    }
+
+    #[convex_macro::test_runtime]
+    async fn test_rate_limited_user_does_not_use_function_rate(
+        rt: TestRuntime,
+    ) -> anyhow::Result<()> {
+        let quotas = FunctionQuotas::new(
+            rt,
+            FunctionQuotaLimits {
+                rate_per_function: 3,
+                rate_per_identity: 2,
+                ..NO_LIMITS
+            },
+        );
+        let abuser = Identity::user(UserIdentity::test());
+        let mut other_user = UserIdentity::test();
+        other_user.attributes.token_identifier =
+            UserIdentifier::construct("https://testauth.fake.domain", "testauth|456");
+        let other_user = Identity::user(other_user);
+        let caller = FunctionCaller::HttpApi(ClientVersion::unknown());
+        let path = path("messages:send")?;
+        for _ in 0..2 {
+            quotas.acquire(&path, UdfType::Mutation, &abuser, &caller)?;
+        }
+        // Calls rejected by the user's own limit don't count against the
+        // function's.
+        for _ in 0..10 {
+            let err = quotas
+                .acquire(&path, UdfType::Mutation, &abuser, &caller)
+                .err()
+                .unwrap();
+            assert_eq!(err.short_msg(), "UserRateLimited");
+        }
+        quotas.acquire(&path, UdfType::Mutation, &other_user, &caller)?;
+        Ok(())
+    }
+
+    #[convex_macro::test_runtime]
+    async fn test_rate_per_ip_batch(rt: TestRuntime) -> anyhow::Result<()> {
+        let quotas = FunctionQuotas::new(
+            rt.clone(),
+            FunctionQuotaLimits {
+                rate_per_ip: 2,
+                ..NO_LIMITS
+            },
+        );
+        let address = "10.0.0.1".parse()?;
+        // A batch larger than the burst uses up the whole burst.
+        quotas.check_client_address(address, 5)?;
+        let err = quotas.check_client_address(address, 1).err().unwrap();
+        assert_eq!(err.short_msg(), "ClientRateLimited");
+        let RetryAfter(retry_after) = err.downcast_ref::<RetryAfter>().unwrap();
+        assert!(*retry_after <= Duration::from_millis(500));
+
+        rt.advance_time(Duration::from_secs(1)).await;
+        quotas.check_client_address(address, 5)?;
+        Ok(())
+    }
+
+    #[convex_macro::test_runtime]
+    async fn test_max_concurrent_per_function(rt: TestRuntime) -> anyhow::Result<()> {
+        let quotas = FunctionQuotas::new(
+            rt,
+            FunctionQuotaLimits {
+                max_concurrent_per_function: 1,
+                ..NO_LIMITS
+            },
+        );
+        let caller = FunctionCaller::SyncWorker(ClientVersion::unknown());
+        let send = path("messages:send")?;
+        let list = path("messages:list")?;
+        let permit = quotas.acquire(&send, UdfType::Action, &Identity::Unknown, &caller)?;
+        let err = quotas
+            .acquire(&send, UdfType::Action, &Identity::Unknown, &caller)
+            .err()
+            .unwrap();
+        assert_eq!(err.short_msg(), "TooManyConcurrentFunctionCalls");
+        // Other functions and queries have their own quota.
+        quotas.acquire(&list, UdfType::Action, &Identity::Unknown, &caller)?;
+        quotas.acquire(&send, UdfType::Query, &Identity::Unknown, &caller)?;
+
+        drop(permit);
+        quotas.acquire(&send, UdfType::Action, &Identity::Unknown, &caller)?;
+        Ok(())
+    }
+}
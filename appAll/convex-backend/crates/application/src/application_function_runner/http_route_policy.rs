@@ -0,0 +1,568 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::Arc,
+    time::Duration,
+};
+
+use common::{
+    components::ComponentPath,
+    knobs::HTTP_ACTION_RATE_LIMIT_MAX_TRACKED_CLIENTS,
+    runtime::{
+        new_keyed_rate_limiter,
+        KeyedRateLimiter,
+        Runtime,
+    },
+    types::{
+        HttpActionRoute,
+        RoutableMethod,
+    },
+};
+use governor::Quota;
+use http::{
+    header::{
+        ACCESS_CONTROL_ALLOW_CREDENTIALS,
+        ACCESS_CONTROL_ALLOW_HEADERS,
+        ACCESS_CONTROL_ALLOW_METHODS,
+        ACCESS_CONTROL_ALLOW_ORIGIN,
+        ACCESS_CONTROL_EXPOSE_HEADERS,
+        ACCESS_CONTROL_MAX_AGE,
+        ACCESS_CONTROL_REQUEST_HEADERS,
+        ACCESS_CONTROL_REQUEST_METHOD,
+        ORIGIN,
+        RETRY_AFTER,
+        VARY,
+    },
+    HeaderMap,
+    HeaderValue,
+    Method,
+    StatusCode,
+};
+use keybroker::Identity;
+use model::modules::{
+    http_route_policy::{
+        CorsPolicy,
+        HttpRateLimit,
+        RateLimitKey,
+    },
+    module_versions::AnalyzedHttpRoute,
+};
+use parking_lot::Mutex;
+use udf::{
+    HttpActionRequestHead,
+    HttpActionResponseHead,
+    HttpActionResponsePart,
+};
+
+use super::metrics::log_http_action_rate_limited;
+
+/// The rate limiters for HTTP routes that declare a `rateLimit`, keyed by
+/// route and limit so that changing a route's limit starts it afresh.
+pub struct HttpRateLimiters<RT: Runtime> {
+    runtime: RT,
+    limiters: Mutex<
+        HashMap<(ComponentPath, HttpActionRoute, HttpRateLimit), Arc<KeyedRateLimiter<String, RT>>>,
+    >,
+}
+
+impl<RT: Runtime> HttpRateLimiters<RT> {
+    pub fn new(runtime: RT) -> Self {
+        Self {
+            runtime,
+            limiters: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Count a request against its route's rate limit. Returns how long the
+    /// client should wait before retrying if it's over the limit.
+    pub fn check(
+        &self,
+        component: &ComponentPath,
+        route: &AnalyzedHttpRoute,
+        identity: &Identity,
+        client_address: Option<IpAddr>,
+    ) -> Option<Duration> {
+        let limit = route.policy.rate_limit?;
+        let key = match (limit.key, identity) {
+            // Admin and system requests aren't limited.
+            (_, Identity::InstanceAdmin(_) | Identity::System(_)) => return None,
+            (RateLimitKey::Identity, Identity::User(user)) => {
+                format!("user:{}", user.attributes.token_identifier.0)
+            },
+            (RateLimitKey::Identity, Identity::ActingUser(_, attributes)) => {
+                format!("user:{}", attributes.token_identifier.0)
+            },
+            // Requests without a known address share one limit.
+            _ => match client_address {
+                Some(address) => format!("ip:{address}"),
+                None => "ip:unknown".to_string(),
+            },
+        };
+        let limiter = self
+            .limiters
+            .lock()
+            .entry((component.clone(), route.route.clone(), limit))
+            .or_insert_with(|| {
+                Arc::new(new_keyed_rate_limiter(
+                    self.runtime.clone(),
+                    rate_limit_quota(&limit),
+                ))
+            })
+            .clone();
+        if limiter.len() > *HTTP_ACTION_RATE_LIMIT_MAX_TRACKED_CLIENTS {
+            limiter.retain_recent();
+        }
+        match limiter.check_key(&key) {
+            Ok(()) => None,
+            Err(not_until) => {
+                log_http_action_rate_limited();
+                Some(not_until.wait_time_from(self.runtime.monotonic_now().into()))
+            },
+        }
+    }
+}
+
+fn rate_limit_quota(limit: &HttpRateLimit) -> Quota {
+    // Replenish one request every `period / requests`, allowing bursts of up
+    // to `requests`.
+    let replenish_interval = (limit.period / limit.requests).max(Duration::from_nanos(1));
+    Quota::with_period(replenish_interval)
+        .expect("replenish interval is nonzero")
+        .allow_burst(
+            limit
+                .requests
+                .try_into()
+                .expect("rate limit requests are nonzero"),
+        )
+}
+
+/// The method a CORS preflight request asks about, if `head` is one.
+pub fn preflight_method(head: &HttpActionRequestHead) -> Option<RoutableMethod> {
+    if head.method != Method::OPTIONS || !head.headers.contains_key(ORIGIN) {
+        return None;
+    }
+    let method = head
+        .headers
+        .get(ACCESS_CONTROL_REQUEST_METHOD)?
+        .to_str()
+        .ok()?;
+    method.parse().ok()
+}
+
+pub fn preflight_response(
+    cors: &CorsPolicy,
+    request_headers: &HeaderMap,
+    method: RoutableMethod,
+) -> Vec<HttpActionResponsePart> {
+    let mut headers = cors_headers(cors, request_headers);
+    if headers.contains_key(ACCESS_CONTROL_ALLOW_ORIGIN) {
+        if let Ok(method) = HeaderValue::from_str(&method.to_string()) {
+            headers.insert(ACCESS_CONTROL_ALLOW_METHODS, method);
+        }
+        let allowed_headers = if cors.allowed_headers.is_empty() {
+            request_headers.get(ACCESS_CONTROL_REQUEST_HEADERS).cloned()
+        } else {
+            HeaderValue::from_str(&cors.allowed_headers.join(", ")).ok()
+        };
+        if let Some(allowed_headers) = allowed_headers {
+            headers.insert(ACCESS_CONTROL_ALLOW_HEADERS, allowed_headers);
+        }
+        if let Some(max_age_secs) = cors.max_age_secs {
+            headers.insert(ACCESS_CONTROL_MAX_AGE, max_age_secs.into());
+        }
+    }
+    vec![HttpActionResponsePart::Head(HttpActionResponseHead {
+        status: StatusCode::NO_CONTENT,
+        headers,
+    })]
+}
+
+/// Add the headers `cors` calls for to the response head of a request with
+/// `request_headers`.
+pub fn with_cors_headers(
+    mut head: HttpActionResponseHead,
+    cors: Option<&CorsPolicy>,
+    request_headers: &HeaderMap,
+) -> HttpActionResponseHead {
+    if let Some(cors) = cors {
+        for (name, value) in &cors_headers(cors, request_headers) {
+            if name == VARY {
+                head.headers.append(name, value.clone());
+            } else {
+                head.headers.insert(name, value.clone());
+            }
+        }
+    }
+    head
+}
+
+pub fn rate_limited_response(
+    retry_after: Duration,
+    cors: Option<&CorsPolicy>,
+    request_headers: &HeaderMap,
+) -> Vec<HttpActionResponsePart> {
+    let mut parts = HttpActionResponsePart::from_text(
+        StatusCode::TOO_MANY_REQUESTS,
+        "Too many requests, try again later.".to_string(),
+    );
+    if let Some(HttpActionResponsePart::Head(head)) = parts.first_mut() {
+        // `Retry-After` is in whole seconds, so round up.
+        let retry_after_secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+        head.headers.insert(RETRY_AFTER, retry_after_secs.into());
+        *head = with_cors_headers(head.clone(), cors, request_headers);
+    }
+    parts
+}
+
+fn cors_headers(cors: &CorsPolicy, request_headers: &HeaderMap) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    let Some(origin) = request_headers.get(ORIGIN) else {
+        return headers;
+    };
+    if cors.allowed_origins.is_none() && !cors.allow_credentials {
+        headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue::from_static("*"));
+    } else {
+        // The response depends on the origin, so caches need to know.
+        headers.insert(VARY, HeaderValue::from_static("Origin"));
+        let allowed = origin
+            .to_str()
+            .map(|origin| cors.allows_origin(origin))
+            .unwrap_or(false);
+        if !allowed {
+            return headers;
+        }
+        headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, origin.clone());
+    }
+    if cors.allow_credentials {
+        headers.insert(
+            ACCESS_CONTROL_ALLOW_CREDENTIALS,
+            HeaderValue::from_static("true"),
+        );
+    }
+    if !cors.exposed_headers.is_empty()
+        && let Ok(exposed_headers) = HeaderValue::from_str(&cors.exposed_headers.join(", "))
+    {
+        headers.insert(ACCESS_CONTROL_EXPOSE_HEADERS, exposed_headers);
+    }
+    headers
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use common::{
+        components::ComponentPath,
+        types::{
+            HttpActionRoute,
+            RoutableMethod,
+        },
+    };
+    use http::{
+        header::{
+            ACCESS_CONTROL_ALLOW_CREDENTIALS,
+            ACCESS_CONTROL_ALLOW_HEADERS,
+            ACCESS_CONTROL_ALLOW_METHODS,
+            ACCESS_CONTROL_ALLOW_ORIGIN,
+            ACCESS_CONTROL_EXPOSE_HEADERS,
+            ACCESS_CONTROL_MAX_AGE,
+            ACCESS_CONTROL_REQUEST_HEADERS,
+            ACCESS_CONTROL_REQUEST_METHOD,
+            CONTENT_TYPE,
+            ORIGIN,
+            RETRY_AFTER,
+            VARY,
+        },
+        HeaderMap,
+        HeaderValue,
+        Method,
+        StatusCode,
+    };
+    use keybroker::{
+        testing::TestUserIdentity,
+        Identity,
+        UserIdentity,
+    };
+    use model::modules::{
+        http_route_policy::{
+            CorsPolicy,
+            HttpRateLimit,
+            HttpRoutePolicy,
+            RateLimitKey,
+        },
+        module_versions::AnalyzedHttpRoute,
+    };
+    use runtime::testing::TestRuntime;
+    use sync_types::UserIdentifier;
+    use udf::{
+        HttpActionRequestHead,
+        HttpActionResponseHead,
+        HttpActionResponsePart,
+    };
+
+    use super::{
+        preflight_method,
+        preflight_response,
+        rate_limited_response,
+        with_cors_headers,
+        HttpRateLimiters,
+    };
+
+    const ORIGIN_URL: &str = "https://example.com";
+
+    fn cors(allowed_origins: Option<&[&str]>, allow_credentials: bool) -> CorsPolicy {
+        CorsPolicy {
+            allowed_origins: allowed_origins
+                .map(|origins| origins.iter().map(|o| o.to_string()).collect()),
+            allowed_headers: vec![],
+            exposed_headers: vec![],
+            allow_credentials,
+            max_age_secs: None,
+        }
+    }
+
+    fn request_headers(origin: Option<&str>) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Some(origin) = origin {
+            headers.insert(ORIGIN, HeaderValue::from_str(origin).unwrap());
+        }
+        headers
+    }
+
+    fn head(parts: &[HttpActionResponsePart]) -> &HttpActionResponseHead {
+        let Some(HttpActionResponsePart::Head(head)) = parts.first() else {
+            panic!("Response doesn't start with a head");
+        };
+        head
+    }
+
+    fn route(key: RateLimitKey) -> AnalyzedHttpRoute {
+        AnalyzedHttpRoute {
+            route: HttpActionRoute {
+                method: RoutableMethod::Post,
+                path: "/messages".to_string(),
+            },
+            pos: None,
+            policy: HttpRoutePolicy {
+                cors: None,
+                rate_limit: Some(HttpRateLimit {
+                    key,
+                    requests: 2,
+                    period: Duration::from_secs(2),
+                }),
+            },
+        }
+    }
+
+    fn user(token_identifier: &str) -> Identity {
+        let mut user = UserIdentity::test();
+        user.attributes.token_identifier = UserIdentifier(token_identifier.to_string());
+        Identity::user(user)
+    }
+
+    #[test]
+    fn test_preflight_method() -> anyhow::Result<()> {
+        let mut head = HttpActionRequestHead {
+            headers: request_headers(Some(ORIGIN_URL)),
+            url: "https://carnitas.convex.site/messages".parse()?,
+            method: Method::OPTIONS,
+        };
+        // Without `Access-Control-Request-Method` it's a plain OPTIONS request.
+        assert_eq!(preflight_method(&head), None);
+        head.headers.insert(
+            ACCESS_CONTROL_REQUEST_METHOD,
+            HeaderValue::from_static("POST"),
+        );
+        assert_eq!(preflight_method(&head), Some(RoutableMethod::Post));
+        head.headers.remove(ORIGIN);
+        assert_eq!(preflight_method(&head), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_preflight_response() {
+        let mut headers = request_headers(Some(ORIGIN_URL));
+        headers.insert(
+            ACCESS_CONTROL_REQUEST_HEADERS,
+            HeaderValue::from_static("content-type"),
+        );
+
+        // Any origin, echoing the requested headers.
+        let parts = preflight_response(&cors(None, false), &headers, RoutableMethod::Post);
+        let response = head(&parts);
+        assert_eq!(response.status, StatusCode::NO_CONTENT);
+        assert_eq!(response.headers[ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+        assert_eq!(response.headers[ACCESS_CONTROL_ALLOW_METHODS], "POST");
+        assert_eq!(
+            response.headers[ACCESS_CONTROL_ALLOW_HEADERS],
+            "content-type"
+        );
+        assert!(!response.headers.contains_key(ACCESS_CONTROL_MAX_AGE));
+
+        // Listed origins and headers, with credentials.
+        let policy = CorsPolicy {
+            allowed_headers: vec!["authorization".to_string(), "content-type".to_string()],
+            max_age_secs: Some(600),
+            ..cors(Some(&[ORIGIN_URL]), true)
+        };
+        let parts = preflight_response(&policy, &headers, RoutableMethod::Post);
+        let response = head(&parts);
+        assert_eq!(response.headers[ACCESS_CONTROL_ALLOW_ORIGIN], ORIGIN_URL);
+        assert_eq!(response.headers[ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+        assert_eq!(
+            response.headers[ACCESS_CONTROL_ALLOW_HEADERS],
+            "authorization, content-type"
+        );
+        assert_eq!(response.headers[ACCESS_CONTROL_MAX_AGE], "600");
+        assert_eq!(response.headers[VARY], "Origin");
+
+        // Other origins get no CORS headers besides `Vary`.
+        let parts = preflight_response(
+            &policy,
+            &request_headers(Some("https://evil.com")),
+            RoutableMethod::Post,
+        );
+        let response = head(&parts);
+        assert_eq!(response.status, StatusCode::NO_CONTENT);
+        assert!(!response.headers.contains_key(ACCESS_CONTROL_ALLOW_ORIGIN));
+        assert!(!response.headers.contains_key(ACCESS_CONTROL_ALLOW_METHODS));
+        assert_eq!(response.headers[VARY], "Origin");
+    }
+
+    #[test]
+    fn test_with_cors_headers() {
+        let mut action_headers = HeaderMap::new();
+        action_headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue::from_static("*"));
+        action_headers.insert(VARY, HeaderValue::from_static("Accept-Encoding"));
+        action_headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+        let action_head = HttpActionResponseHead {
+            status: StatusCode::OK,
+            headers: action_headers,
+        };
+        let policy = CorsPolicy {
+            exposed_headers: vec!["x-request-id".to_string()],
+            ..cors(Some(&[ORIGIN_URL]), false)
+        };
+
+        // The policy's headers replace the action's, except `Vary` which is
+        // merged.
+        let merged = with_cors_headers(
+            action_head.clone(),
+            Some(&policy),
+            &request_headers(Some(ORIGIN_URL)),
+        );
+        assert_eq!(merged.status, StatusCode::OK);
+        assert_eq!(merged.headers[ACCESS_CONTROL_ALLOW_ORIGIN], ORIGIN_URL);
+        assert_eq!(
+            merged.headers[ACCESS_CONTROL_EXPOSE_HEADERS],
+            "x-request-id"
+        );
+        assert_eq!(merged.headers[CONTENT_TYPE], "text/plain");
+        assert_eq!(
+            merged.headers.get_all(VARY).iter().collect::<Vec<_>>(),
+            vec!["Accept-Encoding", "Origin"]
+        );
+
+        // Requests without an `Origin` and routes without a policy are left
+        // alone.
+        assert_eq!(
+            with_cors_headers(action_head.clone(), Some(&policy), &request_headers(None)).headers,
+            action_head.headers
+        );
+        assert_eq!(
+            with_cors_headers(
+                action_head.clone(),
+                None,
+                &request_headers(Some(ORIGIN_URL))
+            )
+            .headers,
+            action_head.headers
+        );
+    }
+
+    #[test]
+    fn test_rate_limited_response() {
+        let parts = rate_limited_response(
+            Duration::from_millis(1500),
+            Some(&cors(None, false)),
+            &request_headers(Some(ORIGIN_URL)),
+        );
+        let response = head(&parts);
+        assert_eq!(response.status, StatusCode::TOO_MANY_REQUESTS);
+        // Rounded up to whole seconds.
+        assert_eq!(response.headers[RETRY_AFTER], "2");
+        // Browsers can only read the error if it has CORS headers too.
+        assert_eq!(response.headers[ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+
+        let parts = rate_limited_response(Duration::from_secs(3), None, &HeaderMap::new());
+        assert_eq!(head(&parts).headers[RETRY_AFTER], "3");
+    }
+
+    #[convex_macro::test_runtime]
+    async fn test_rate_limit_by_ip(rt: TestRuntime) -> anyhow::Result<()> {
+        let limiters = HttpRateLimiters::new(rt.clone());
+        let component = ComponentPath::root();
+        let route = route(RateLimitKey::Ip);
+        let first = Some("10.0.0.1".parse()?);
+        let second = Some("10.0.0.2".parse()?);
+        for _ in 0..2 {
+            assert_eq!(
+                limiters.check(&component, &route, &Identity::Unknown, first),
+                None
+            );
+        }
+        let retry_after = limiters
+            .check(&component, &route, &Identity::Unknown, first)
+            .expect("should be rate limited");
+        assert!(retry_after > Duration::ZERO && retry_after <= Duration::from_secs(1));
+
+        // Other addresses have their own limit, even for the same user.
+        assert_eq!(limiters.check(&component, &route, &user("a"), second), None);
+        // Admins aren't limited.
+        assert_eq!(
+            limiters.check(&component, &route, &Identity::system(), first),
+            None
+        );
+
+        rt.advance_time(Duration::from_secs(1)).await;
+        assert_eq!(
+            limiters.check(&component, &route, &Identity::Unknown, first),
+            None
+        );
+        Ok(())
+    }
+
+    #[convex_macro::test_runtime]
+    async fn test_rate_limit_by_identity(rt: TestRuntime) -> anyhow::Result<()> {
+        let limiters = HttpRateLimiters::new(rt);
+        let component = ComponentPath::root();
+        let route = route(RateLimitKey::Identity);
+        let address = Some("10.0.0.1".parse()?);
+        for _ in 0..2 {
+            assert_eq!(
+                limiters.check(&component, &route, &user("a"), address),
+                None
+            );
+        }
+        assert!(limiters
+            .check(&component, &route, &user("a"), address)
+            .is_some());
+
+        // Other users have their own limit, even from the same address.
+        assert_eq!(
+            limiters.check(&component, &route, &user("b"), address),
+            None
+        );
+        // Unauthenticated requests are limited by address.
+        for _ in 0..2 {
+            assert_eq!(
+                limiters.check(&component, &route, &Identity::Unknown, address),
+                None
+            );
+        }
+        assert!(limiters
+            .check(&component, &route, &Identity::Unknown, address)
+            .is_some());
+        Ok(())
+    }
+}
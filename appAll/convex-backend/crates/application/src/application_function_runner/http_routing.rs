@@ -37,6 +37,7 @@ use futures::{
 use http::StatusCode;
 use keybroker::Identity;
 use model::modules::{
+    module_versions::AnalyzedHttpRoute,
     ModuleModel,
     HTTP_MODULE_PATH,
 };
@@ -50,14 +51,21 @@ use udf::{
     validation::ValidatedHttpPath,
     HttpActionOutcome,
     HttpActionRequest,
-    HttpActionRequestHead,
     HttpActionResponsePart,
     HttpActionResponseStreamer,
     HttpActionResult,
 };
 use usage_tracking::FunctionUsageTracker;
 
-use super::ApplicationFunctionRunner;
+use super::{
+    http_route_policy::{
+        preflight_method,
+        preflight_response,
+        rate_limited_response,
+        with_cors_headers,
+    },
+    ApplicationFunctionRunner,
+};
 use crate::function_log::HttpActionStatusCode;
 
 impl<RT: Runtime> ApplicationFunctionRunner<RT> {
@@ -78,21 +86,56 @@ impl<RT: Runtime> ApplicationFunctionRunner<RT> {
             .begin_with_usage(identity.clone(), usage_tracker.clone())
             .await?;
 
-        let (component_path, routed_path) =
-            match self.route_http_action(&mut tx, &http_request.head).await? {
-                Some(r) => r,
-                None => {
-                    drop(tx);
-                    let response_parts = udf::HttpActionResponsePart::from_text(
-                        StatusCode::NOT_FOUND,
-                        "This Convex deployment does not have HTTP actions enabled.".to_string(),
-                    );
-                    for part in response_parts {
-                        response_streamer.send_part(part)?;
-                    }
-                    return Ok(udf::HttpActionResult::Streamed);
-                },
-            };
+        // Answer CORS preflight requests for routes with a CORS policy without
+        // running their HTTP action.
+        if let Some(method) = preflight_method(&http_request.head)
+            && let Some((_, _, Some(route))) = self
+                .route_http_action(&mut tx, http_request.head.url.path(), method)
+                .await?
+            && let Some(ref cors) = route.policy.cors
+        {
+            drop(tx);
+            for part in preflight_response(cors, &http_request.head.headers, method) {
+                response_streamer.send_part(part)?;
+            }
+            return Ok(udf::HttpActionResult::Streamed);
+        }
+
+        let method = RoutableMethod::try_from(http_request.head.method.clone())?;
+        let (component_path, routed_path, route) = match self
+            .route_http_action(&mut tx, http_request.head.url.path(), method)
+            .await?
+        {
+            Some(r) => r,
+            None => {
+                drop(tx);
+                let response_parts = udf::HttpActionResponsePart::from_text(
+                    StatusCode::NOT_FOUND,
+                    "This Convex deployment does not have HTTP actions enabled.".to_string(),
+                );
+                for part in response_parts {
+                    response_streamer.send_part(part)?;
+                }
+                return Ok(udf::HttpActionResult::Streamed);
+            },
+        };
+        let cors = route.as_ref().and_then(|route| route.policy.cors.clone());
+        if let Some(ref route) = route
+            && let Some(retry_after) = self.http_rate_limiters.check(
+                &component_path,
+                route,
+                &identity,
+                http_request.client_address,
+            )
+        {
+            drop(tx);
+            for part in
+                rate_limited_response(retry_after, cors.as_ref(), &http_request.head.headers)
+            {
+                response_streamer.send_part(part)?;
+            }
+            return Ok(udf::HttpActionResult::Streamed);
+        }
         let path = CanonicalizedComponentFunctionPath {
             component: component_path,
             udf_path: CanonicalizedUdfPath::new(
@@ -154,6 +197,7 @@ impl<RT: Runtime> ApplicationFunctionRunner<RT> {
                 Some(result) = response_stream.next(), if !response_stream.is_terminated() => {
                     match result {
                         HttpActionResponsePart::Head(h) => {
+                            let h = with_cors_headers(h, cors.as_ref(), &request_head.headers);
                             result_for_logging = Some(Ok(HttpActionStatusCode(h.status)));
                             response_streamer.send_part(HttpActionResponsePart::Head(h))?;
                         },
@@ -171,6 +215,7 @@ impl<RT: Runtime> ApplicationFunctionRunner<RT> {
         while let Some(part) = response_stream.next().await {
             match part {
                 HttpActionResponsePart::Head(h) => {
+                    let h = with_cors_headers(h, cors.as_ref(), &request_head.headers);
                     result_for_logging = Some(Ok(HttpActionStatusCode(h.status)));
                     response_streamer.send_part(HttpActionResponsePart::Head(h))?;
                 },
@@ -279,15 +324,17 @@ impl<RT: Runtime> ApplicationFunctionRunner<RT> {
         }
     }
 
+    /// Find the component whose `http.js` handles a request, the path
+    /// within it, and the route it matched if it matched one.
     async fn route_http_action(
         &self,
         tx: &mut Transaction<RT>,
-        head: &HttpActionRequestHead,
-    ) -> anyhow::Result<Option<(ComponentPath, RoutedHttpPath)>> {
+        path: &str,
+        method: RoutableMethod,
+    ) -> anyhow::Result<Option<(ComponentPath, RoutedHttpPath, Option<AnalyzedHttpRoute>)>> {
         let mut model = BootstrapComponentsModel::new(tx);
         let mut current_component_path = ComponentPath::root();
-        let mut routed_path = RoutedHttpPath(head.url.path().to_string());
-        let method = RoutableMethod::try_from(head.method.clone())?;
+        let mut routed_path = RoutedHttpPath(path.to_string());
         loop {
             let (definition_id, current_id) =
                 model.must_component_path_to_ids(&current_component_path)?;
@@ -311,22 +358,27 @@ impl<RT: Runtime> ApplicationFunctionRunner<RT> {
             // First, try matching an exact path from `http.js`, which will always
             // be the most specific match.
             if let Some(ref http_routes) = http_routes {
-                if http_routes.route_exact(&routed_path[..], method) {
-                    return Ok(Some((current_component_path, routed_path)));
+                if let Some(route) = http_routes.route_exact(&routed_path[..], method) {
+                    return Ok(Some((
+                        current_component_path,
+                        routed_path,
+                        Some(route.clone()),
+                    )));
                 }
             }
 
             // Next, try finding the most specific prefix match from both `http.js`
             // and the component-level mounts.
             enum CurrentMatch<'a> {
-                CurrentHttpJs,
+                CurrentHttpJs(&'a AnalyzedHttpRoute),
                 MountedComponent(&'a Reference),
             }
             let mut longest_match = None;
 
             if let Some(ref http_routes) = http_routes {
-                if let Some(match_suffix) = http_routes.route_prefix(&routed_path, method) {
-                    longest_match = Some((match_suffix, CurrentMatch::CurrentHttpJs));
+                if let Some((match_suffix, route)) = http_routes.route_prefix(&routed_path, method)
+                {
+                    longest_match = Some((match_suffix, CurrentMatch::CurrentHttpJs(route)));
                 }
             }
             for (mount_path, reference) in &definition.http_mounts {
@@ -352,15 +404,17 @@ impl<RT: Runtime> ApplicationFunctionRunner<RT> {
                         return Ok(Some((
                             current_component_path,
                             RoutedHttpPath(routed_path.to_string()),
+                            None,
                         )));
                     } else {
                         return Ok(None);
                     }
                 },
-                Some((_, CurrentMatch::CurrentHttpJs)) => {
+                Some((_, CurrentMatch::CurrentHttpJs(route))) => {
                     return Ok(Some((
                         current_component_path,
                         RoutedHttpPath(routed_path.to_string()),
+                        Some(route.clone()),
                     )));
                 },
                 Some((match_suffix, CurrentMatch::MountedComponent(reference))) => {
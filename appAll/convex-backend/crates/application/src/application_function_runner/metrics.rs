@@ -49,6 +49,27 @@ pub fn log_mutation_already_committed() {
     log_counter(&APPLICATION_MUTATION_ALREADY_COMMITTED_TOTAL, 1);
 }
 
+register_convex_counter!(
+    APPLICATION_HTTP_ACTION_RATE_LIMITED_TOTAL,
+    "Number of HTTP action requests rejected by their route's rate limit"
+);
+pub fn log_http_action_rate_limited() {
+    log_counter(&APPLICATION_HTTP_ACTION_RATE_LIMITED_TOTAL, 1);
+}
+
+register_convex_counter!(
+    APPLICATION_FUNCTION_QUOTA_EXCEEDED_TOTAL,
+    "Number of client requests rejected by a function rate limit or concurrency quota",
+    &["quota"]
+);
+pub fn log_function_quota_exceeded(quota: &'static str) {
+    log_counter_with_labels(
+        &APPLICATION_FUNCTION_QUOTA_EXCEEDED_TOTAL,
+        1,
+        vec![StaticMetricLabel::new("quota", quota)],
+    );
+}
+
 register_convex_histogram!(OCC_RETRIES_TOTAL, "Number of OCC retries for a commit");
 pub fn log_occ_retries(count: usize) {
     log_distribution(&OCC_RETRIES_TOTAL, count as f64);
@@ -4,6 +4,7 @@ use std::{
         BTreeMap,
         BTreeSet,
     },
+    net::IpAddr,
     sync::{
         atomic::AtomicUsize,
         Arc,
@@ -67,7 +68,9 @@ use common::{
         FunctionCaller,
         ModuleEnvironment,
         NodeDependency,
+        NodeVersion,
         Timestamp,
+        UdfIdentifier,
         UdfType,
     },
     value::ConvexArray,
@@ -188,14 +191,19 @@ use vector::{
     VectorSearch,
 };
 
-use self::metrics::{
-    function_waiter_timer,
-    log_occ_retries,
-    log_outstanding_functions,
-    log_udf_executor_result,
-    mutation_timer,
-    OutstandingFunctionState,
-    UdfExecutorResult,
+pub use self::function_quotas::FunctionQuotaLimits;
+use self::{
+    function_quotas::FunctionQuotas,
+    http_route_policy::HttpRateLimiters,
+    metrics::{
+        function_waiter_timer,
+        log_occ_retries,
+        log_outstanding_functions,
+        log_udf_executor_result,
+        mutation_timer,
+        OutstandingFunctionState,
+        UdfExecutorResult,
+    },
 };
 use crate::{
     application_function_runner::metrics::{
@@ -212,6 +220,7 @@ use crate::{
         ActionCompletion,
         FunctionExecutionLog,
     },
+    metrics::log_function_occ_retry,
     ActionError,
     ActionReturn,
     MutationError,
@@ -219,6 +228,8 @@ use crate::{
     QueryReturn,
 };
 
+mod function_quotas;
+mod http_route_policy;
 mod http_routing;
 mod metrics;
 
@@ -579,6 +590,8 @@ pub struct ApplicationFunctionRunner<RT: Runtime> {
     cache_manager: CacheManager<RT>,
     system_env_vars: BTreeMap<EnvVarName, EnvVarValue>,
     node_action_limiter: Limiter,
+    http_rate_limiters: HttpRateLimiters<RT>,
+    function_quotas: FunctionQuotas<RT>,
 }
 
 impl<RT: Runtime> ApplicationFunctionRunner<RT> {
@@ -594,6 +607,7 @@ impl<RT: Runtime> ApplicationFunctionRunner<RT> {
         function_log: FunctionExecutionLog<RT>,
         system_env_vars: BTreeMap<EnvVarName, EnvVarValue>,
         cache: QueryCache,
+        function_quota_limits: FunctionQuotaLimits,
     ) -> Self {
         let isolate_functions = FunctionRouter::new(
             function_runner,
@@ -610,6 +624,8 @@ impl<RT: Runtime> ApplicationFunctionRunner<RT> {
         );
 
         Self {
+            http_rate_limiters: HttpRateLimiters::new(runtime.clone()),
+            function_quotas: FunctionQuotas::new(runtime.clone(), function_quota_limits),
             runtime,
             database,
             key_broker,
@@ -629,6 +645,12 @@ impl<RT: Runtime> ApplicationFunctionRunner<RT> {
         }
     }
 
+    /// Count `requests` requests from a client address against the
+    /// deployment's per-IP rate limit.
+    pub fn check_client_rate_limit(&self, address: IpAddr, requests: u32) -> anyhow::Result<()> {
+        self.function_quotas.check_client_address(address, requests)
+    }
+
     pub(crate) async fn shutdown(&self) -> anyhow::Result<()> {
         self.node_actions.shutdown();
         Ok(())
@@ -746,6 +768,9 @@ impl<RT: Runtime> ApplicationFunctionRunner<RT> {
         if path.is_system() && !(identity.is_admin() || identity.is_system()) {
             anyhow::bail!(unauthorized_error("mutation"));
         }
+        let _quota_permit =
+            self.function_quotas
+                .acquire(&path, UdfType::Mutation, &identity, &caller)?;
         let arguments = match parse_udf_args(path.udf_path(), arguments) {
             Ok(arguments) => arguments,
             Err(error) => {
@@ -873,6 +898,13 @@ impl<RT: Runtime> ApplicationFunctionRunner<RT> {
                                  {udf_path_string:?} after {sleep:?}",
                             );
                             self.runtime.wait(sleep).await;
+                            if !outcome.path.udf_path.is_system() {
+                                log_function_occ_retry(
+                                    &UdfIdentifier::Function(outcome.path.clone()),
+                                    &context.request_id,
+                                    self.runtime.system_time(),
+                                );
+                            }
                             let (table_name, document_id, write_source) =
                                 e.occ_info().unwrap_or((None, None, None));
                             self.function_log.log_mutation_occ_error(
@@ -1047,6 +1079,9 @@ impl<RT: Runtime> ApplicationFunctionRunner<RT> {
         if path.is_system() && !(identity.is_admin() || identity.is_system()) {
             anyhow::bail!(unauthorized_error("action"));
         }
+        let _quota_permit =
+            self.function_quotas
+                .acquire(&path, UdfType::Action, &identity, &caller)?;
         let arguments = match parse_udf_args(path.udf_path(), arguments) {
             Ok(arguments) => arguments,
             Err(error) => {
@@ -1330,6 +1365,7 @@ impl<RT: Runtime> ApplicationFunctionRunner<RT> {
                             sha256: source_package.sha256,
                         },
                         external_deps: external_deps_package,
+                        node_version: source_package.node_version.unwrap_or_default(),
                     },
                     source_package_id,
                     user_identity: tx.user_identity(),
@@ -1438,6 +1474,7 @@ impl<RT: Runtime> ApplicationFunctionRunner<RT> {
     pub async fn build_deps(
         &self,
         deps: Vec<NodeDependency>,
+        node_version: NodeVersion,
     ) -> anyhow::Result<Result<ExternalDepsPackage, JsError>> {
         let (object_key, upload_uri) = self
             .modules_storage
@@ -1446,6 +1483,7 @@ impl<RT: Runtime> ApplicationFunctionRunner<RT> {
         let request = BuildDepsRequest {
             deps: deps.clone(),
             upload_url: upload_uri,
+            node_version,
         };
         let build_deps_res = self.node_actions.build_deps(request).await?;
         Ok(
@@ -1454,6 +1492,7 @@ impl<RT: Runtime> ApplicationFunctionRunner<RT> {
                 sha256: digest,
                 deps,
                 package_size,
+                node_version: Some(node_version),
             }),
         )
     }
@@ -1574,6 +1613,7 @@ impl<RT: Runtime> ApplicationFunctionRunner<RT> {
                         sha256: source_package.sha256,
                     },
                     external_deps: external_deps_package,
+                    node_version: source_package.node_version.unwrap_or_default(),
                 },
                 environment_variables,
             };
@@ -1687,6 +1727,10 @@ impl<RT: Runtime> ApplicationFunctionRunner<RT> {
         self.node_actions.enable()
     }
 
+    pub fn check_node_version(&self, node_version: NodeVersion) -> anyhow::Result<()> {
+        self.node_actions.check_node_version(node_version)
+    }
+
     #[fastrace::trace]
     pub async fn run_query_at_ts(
         &self,
@@ -1734,6 +1778,9 @@ impl<RT: Runtime> ApplicationFunctionRunner<RT> {
         if path.is_system() && !(identity.is_admin() || identity.is_system()) {
             anyhow::bail!(unauthorized_error("query"));
         }
+        let _quota_permit =
+            self.function_quotas
+                .acquire(&path, UdfType::Query, &identity, &caller)?;
         let args = match parse_udf_args(path.udf_path(), args) {
             Ok(arguments) => arguments,
             Err(js_error) => {
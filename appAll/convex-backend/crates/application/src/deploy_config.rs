@@ -34,6 +34,7 @@ use common::{
         EnvVarValue,
         ModuleEnvironment,
         NodeDependency,
+        NodeVersion,
     },
 };
 use database::{
@@ -81,6 +82,7 @@ use model::{
         ConfigFile,
         ConfigMetadata,
         ModuleConfig,
+        AUTH_CONFIG_FILE_NAME,
     },
     deployment_audit_log::types::{
         DeploymentAuditLogEvent,
@@ -93,6 +95,15 @@ use model::{
         ModuleSource,
         SourceMap,
     },
+    push_history::{
+        types::{
+            PushHistoryEntry,
+            PushedComponentDefinition,
+        },
+        PushHistoryModel,
+        COMPONENT_DEFINITION_FILE_NAME,
+        SCHEMA_FILE_NAME,
+    },
     source_packages::{
         types::SourcePackage,
         upload_download::download_package,
@@ -126,10 +137,25 @@ impl<RT: Runtime> Application<RT> {
         config: &ProjectConfig,
         dry_run: bool,
     ) -> anyhow::Result<StartPushResponse> {
-        let unix_timestamp = self.runtime.unix_timestamp();
         let (external_deps_id, component_definition_packages) =
             self.upload_packages(config).await?;
+        self.start_push_with_packages(
+            config,
+            external_deps_id,
+            component_definition_packages,
+            dry_run,
+        )
+        .await
+    }
 
+    async fn start_push_with_packages(
+        &self,
+        config: &ProjectConfig,
+        external_deps_id: Option<ExternalDepsPackageId>,
+        component_definition_packages: BTreeMap<ComponentDefinitionPath, SourcePackage>,
+        dry_run: bool,
+    ) -> anyhow::Result<StartPushResponse> {
+        let unix_timestamp = self.runtime.unix_timestamp();
         let app_udf_config = UdfConfig {
             server_version: config.app_definition.udf_server_version.clone(),
             import_phase_rng_seed: self.runtime.rng().random(),
@@ -165,6 +191,7 @@ impl<RT: Runtime> Application<RT> {
                 } else {
                     Some(config.config.auth_info.clone())
                 },
+                node_version: config.config.node_version,
             },
         )
         .await?;
@@ -526,9 +553,155 @@ impl<RT: Runtime> Application<RT> {
 
     #[fastrace::trace]
     pub async fn finish_push(
+        &self,
+        identity: Identity,
+        start_push: StartPushResponse,
+    ) -> anyhow::Result<FinishPushDiff> {
+        self.apply_push(identity, start_push, None).await
+    }
+
+    /// Push the configuration recorded as `push_id` in the push history
+    /// again. Waits up to `schema_timeout` for any indexes the old schema
+    /// needs, then switches over to the old configuration in one transaction.
+    #[fastrace::trace]
+    pub async fn rollback_push(
+        &self,
+        identity: Identity,
+        push_id: DeveloperDocumentId,
+        schema_timeout: Duration,
+    ) -> anyhow::Result<FinishPushDiff> {
+        let entry = {
+            let mut tx = self.begin(identity.clone()).await?;
+            PushHistoryModel::new(&mut tx)
+                .get(push_id)
+                .await?
+                .ok_or_else(|| {
+                    ErrorMetadata::not_found(
+                        "PushNotFound",
+                        format!("Push {push_id} isn't in the push history"),
+                    )
+                })?
+                .into_value()
+        };
+        let config = self.project_config_from_history(&entry).await?;
+        let component_definition_packages = entry
+            .definitions
+            .into_iter()
+            .map(|(path, definition)| (path, definition.source_package))
+            .collect();
+        let start_push = self
+            .start_push_with_packages(
+                &config,
+                entry.external_deps_id,
+                component_definition_packages,
+                false,
+            )
+            .await?;
+        let schema_status = self
+            .wait_for_schema(
+                identity.clone(),
+                start_push.schema_change.clone(),
+                schema_timeout,
+            )
+            .await?;
+        match schema_status {
+            SchemaStatus::Complete => (),
+            SchemaStatus::InProgress { .. } => anyhow::bail!(ErrorMetadata::bad_request(
+                "RollbackSchemaInProgress",
+                "Indexes for the schema being rolled back to are still backfilling. Retry the \
+                 rollback once they're done."
+            )),
+            SchemaStatus::Failed { error, .. } => anyhow::bail!(ErrorMetadata::bad_request(
+                "RollbackSchemaFailed",
+                format!("The schema being rolled back to doesn't match the data: {error}")
+            )),
+            SchemaStatus::RaceDetected => anyhow::bail!(ErrorMetadata::bad_request(
+                "RaceDetected",
+                "The schema changed during the rollback"
+            )),
+        }
+        self.apply_push(identity, start_push, Some(push_id)).await
+    }
+
+    /// Rebuild the project config for a push in the history from its source
+    /// packages.
+    async fn project_config_from_history(
+        &self,
+        entry: &PushHistoryEntry,
+    ) -> anyhow::Result<ProjectConfig> {
+        let mut app_definition = None;
+        let mut component_definitions = vec![];
+        let mut has_auth_config = false;
+        for (definition_path, definition) in &entry.definitions {
+            let modules = download_package(
+                self.modules_storage().clone(),
+                definition.source_package.storage_key.clone(),
+                definition.source_package.sha256.clone(),
+            )
+            .await?;
+            let mut definition_module = None;
+            let mut schema = None;
+            let mut functions = vec![];
+            for (path, module) in modules {
+                match path.as_str() {
+                    COMPONENT_DEFINITION_FILE_NAME => definition_module = Some(module),
+                    SCHEMA_FILE_NAME => schema = Some(module),
+                    AUTH_CONFIG_FILE_NAME => {
+                        has_auth_config |= definition_path.is_root();
+                        functions.push(module);
+                    },
+                    _ => functions.push(module),
+                }
+            }
+            if definition_path.is_root() {
+                app_definition = Some(AppDefinitionConfig {
+                    definition: definition_module,
+                    dependencies: definition.dependencies.clone(),
+                    schema,
+                    functions,
+                    udf_server_version: definition.udf_server_version.clone(),
+                });
+            } else {
+                component_definitions.push(ComponentDefinitionConfig {
+                    definition_path: definition_path.clone(),
+                    definition: definition_module
+                        .context("Missing component definition in source package")?,
+                    dependencies: definition.dependencies.clone(),
+                    schema,
+                    functions,
+                    udf_server_version: definition.udf_server_version.clone(),
+                });
+            }
+        }
+        let app_source_package = &entry
+            .definitions
+            .get(&ComponentDefinitionPath::root())
+            .context("Missing app definition in push history")?
+            .source_package;
+        Ok(ProjectConfig {
+            config: ConfigMetadata {
+                // Auth configured in `auth.config.js` is evaluated again like in a regular
+                // push. Older configs set it directly.
+                auth_info: if has_auth_config {
+                    vec![]
+                } else {
+                    entry.auth_info.clone()
+                },
+                node_version: app_source_package.node_version,
+                ..ConfigMetadata::new()
+            },
+            app_definition: app_definition.context("Missing app definition in push history")?,
+            component_definitions,
+            // The external deps package is reused as is.
+            node_dependencies: vec![],
+        })
+    }
+
+    async fn apply_push(
         &self,
         identity: Identity,
         mut start_push: StartPushResponse,
+        rollback_of: Option<DeveloperDocumentId>,
     ) -> anyhow::Result<FinishPushDiff> {
         // Download all source packages. We can remove this once we don't store source
         // in the database.
@@ -590,12 +763,19 @@ impl<RT: Runtime> Application<RT> {
                         )
                         .await?;
 
+                    PushHistoryModel::new(tx)
+                        .record(push_history_entry(start_push, rollback_of)?)
+                        .await?;
+
                     let diffs = PushComponentDiffs {
                         auth_diff: auth_diff.clone(),
                         component_diffs: component_diffs.clone(),
                     };
-                    let audit_log_events =
-                        vec![DeploymentAuditLogEvent::PushConfigWithComponents { diffs }];
+                    let audit_log_event = match rollback_of {
+                        Some(push_id) => DeploymentAuditLogEvent::RollbackPush { push_id, diffs },
+                        None => DeploymentAuditLogEvent::PushConfigWithComponents { diffs },
+                    };
+                    let audit_log_events = vec![audit_log_event];
                     let diff = FinishPushDiff {
                         auth_diff,
                         definition_diffs,
@@ -612,6 +792,40 @@ impl<RT: Runtime> Application<RT> {
     }
 }
 
+fn push_history_entry(
+    start_push: &StartPushResponse,
+    rollback_of: Option<DeveloperDocumentId>,
+) -> anyhow::Result<PushHistoryEntry> {
+    let definitions = start_push
+        .analysis
+        .iter()
+        .map(|(definition_path, evaluated)| {
+            let source_package = start_push
+                .component_definition_packages
+                .get(definition_path)
+                .context("Missing source package for component")?
+                .clone();
+            let definition = PushedComponentDefinition {
+                source_package,
+                dependencies: evaluated
+                    .definition
+                    .child_components
+                    .iter()
+                    .map(|child| child.path.clone())
+                    .collect(),
+                udf_server_version: evaluated.udf_config.server_version.clone(),
+            };
+            anyhow::Ok((definition_path.clone(), definition))
+        })
+        .try_collect()?;
+    Ok(PushHistoryEntry {
+        rollback_of,
+        external_deps_id: start_push.external_deps_id.clone(),
+        definitions,
+        auth_info: start_push.app_auth.clone(),
+    })
+}
+
 struct ApplicationInitializerEvaluator<'a, RT: Runtime> {
     application: &'a Application<RT>,
     component_definitions: BTreeMap<ComponentDefinitionPath, ModuleConfig>,
@@ -679,6 +893,7 @@ pub struct StartPushRequest {
     pub component_definitions: Vec<ComponentDefinitionConfigJson>,
 
     pub node_dependencies: Vec<NodeDependencyJson>,
+    pub node_version: Option<NodeVersion>,
 }
 
 impl StartPushRequest {
@@ -687,6 +902,7 @@ impl StartPushRequest {
             config: ConfigMetadata {
                 functions: self.functions,
                 auth_info: vec![],
+                node_version: self.node_version,
             },
             app_definition: self.app_definition.try_into()?,
             component_definitions: self
@@ -101,6 +101,8 @@ use value::{
     ConvexArray,
 };
 
+use crate::metrics::log_function_execution;
+
 /// A function's execution is summarized by this structure and stored in the
 /// UdfExecutionLog
 #[derive(Debug, Clone)]
@@ -1553,6 +1555,14 @@ impl<RT: Runtime> Inner<RT> {
             let name = udf_errors_metric(&identifier);
             self.metrics.add_counter(&name, ts, 1.0)?;
         }
+        log_function_execution(
+            &identifier,
+            execution.udf_type,
+            is_err,
+            Duration::from_secs_f64(execution.execution_time),
+            &execution.context.request_id,
+            ts,
+        );
         if execution.udf_type == UdfType::Query {
             if execution.cached_result {
                 let name = udf_cache_hits_metric(&identifier);
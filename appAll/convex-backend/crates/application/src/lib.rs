@@ -109,6 +109,7 @@ use common::{
         IndexName,
         ModuleEnvironment,
         NodeDependency,
+        NodeVersion,
         ObjectKey,
         RepeatableTimestamp,
         TableName,
@@ -161,6 +162,8 @@ use file_storage::{
     FileRangeStream,
     FileStorage,
     FileStream,
+    ImageTransform,
+    ResumableUploadProgress,
 };
 use function_log::{
     FunctionExecution,
@@ -292,6 +295,7 @@ use storage::{
     StorageGetStream,
     Upload,
 };
+use storage_reconciler::StorageReconciler;
 use sync_types::{
     AuthenticationToken,
     CanonicalizedModulePath,
@@ -347,7 +351,10 @@ use vector::{
 };
 
 use crate::{
-    application_function_runner::ApplicationFunctionRunner,
+    application_function_runner::{
+        ApplicationFunctionRunner,
+        FunctionQuotaLimits,
+    },
     exports::worker::ExportWorker,
     function_log::{
         FunctionExecutionLog,
@@ -379,6 +386,7 @@ pub mod redaction;
 pub mod scheduled_jobs;
 mod schema_worker;
 pub mod snapshot_import;
+mod storage_reconciler;
 mod system_table_cleanup;
 mod table_summary_worker;
 pub mod valid_identifier;
@@ -528,6 +536,7 @@ pub struct Application<RT: Runtime> {
     snapshot_import_worker: Arc<Mutex<Box<dyn SpawnHandle>>>,
     export_worker: Arc<Mutex<Box<dyn SpawnHandle>>>,
     system_table_cleanup_worker: Arc<Mutex<Box<dyn SpawnHandle>>>,
+    storage_reconciler: Arc<Mutex<Box<dyn SpawnHandle>>>,
     migration_worker: Arc<Mutex<Option<Box<dyn SpawnHandle>>>>,
     log_sender: Arc<dyn LogSender>,
     log_visibility: Arc<dyn LogVisibility<RT>>,
@@ -563,6 +572,7 @@ impl<RT: Runtime> Clone for Application<RT> {
             snapshot_import_worker: self.snapshot_import_worker.clone(),
             export_worker: self.export_worker.clone(),
             system_table_cleanup_worker: self.system_table_cleanup_worker.clone(),
+            storage_reconciler: self.storage_reconciler.clone(),
             migration_worker: self.migration_worker.clone(),
             log_sender: self.log_sender.clone(),
             log_visibility: self.log_visibility.clone(),
@@ -597,6 +607,7 @@ impl<RT: Runtime> Application<RT> {
         log_visibility: Arc<dyn LogVisibility<RT>>,
         app_auth: Arc<ApplicationAuth>,
         cache: QueryCache,
+        function_quota_limits: FunctionQuotaLimits,
     ) -> anyhow::Result<Self> {
         let module_cache = ModuleCache::new(runtime.clone(), modules_storage.clone()).await;
         let module_loader = Arc::new(module_cache.clone());
@@ -640,10 +651,22 @@ impl<RT: Runtime> Application<RT> {
             runtime.clone(),
             database.clone(),
             exports_storage.clone(),
+            files_storage.clone(),
         );
         let system_table_cleanup_worker = Arc::new(Mutex::new(
             runtime.spawn("system_table_cleanup_worker", system_table_cleanup_worker),
         ));
+        let storage_reconciler = StorageReconciler::new(
+            runtime.clone(),
+            database.clone(),
+            files_storage.clone(),
+            exports_storage.clone(),
+            snapshot_imports_storage.clone(),
+            search_storage.clone(),
+        );
+        let storage_reconciler = Arc::new(Mutex::new(
+            runtime.spawn("storage_reconciler", storage_reconciler.start()),
+        ));
 
         let function_log = FunctionExecutionLog::new(
             runtime.clone(),
@@ -662,6 +685,7 @@ impl<RT: Runtime> Application<RT> {
             function_log.clone(),
             system_env_vars.clone(),
             cache,
+            function_quota_limits,
         ));
         function_runner.set_action_callbacks(runner.clone());
 
@@ -740,6 +764,7 @@ impl<RT: Runtime> Application<RT> {
             export_worker,
             snapshot_import_worker,
             system_table_cleanup_worker,
+            storage_reconciler,
             migration_worker,
             log_sender,
             log_visibility,
@@ -1908,11 +1933,12 @@ impl<RT: Runtime> Application<RT> {
     )> {
         let upload_limit = Arc::new(Semaphore::new(*APPLICATION_MAX_CONCURRENT_UPLOADS));
 
+        let node_version = config.config.node_version.unwrap_or_default();
         let root_future = async {
             let permit = upload_limit.acquire().await?;
             let external_deps_id_and_pkg = if !config.node_dependencies.is_empty() {
                 let deps = self
-                    .build_external_node_deps(config.node_dependencies.clone())
+                    .build_external_node_deps(config.node_dependencies.clone(), node_version)
                     .await?;
                 Some(deps)
             } else {
@@ -1920,7 +1946,7 @@ impl<RT: Runtime> Application<RT> {
             };
             let app_modules = config.app_definition.modules().cloned().collect();
             let app_pkg = self
-                .upload_package(&app_modules, external_deps_id_and_pkg.clone())
+                .upload_package(&app_modules, external_deps_id_and_pkg.clone(), node_version)
                 .await?;
             drop(permit);
             Ok((external_deps_id_and_pkg, app_pkg))
@@ -1934,7 +1960,9 @@ impl<RT: Runtime> Application<RT> {
             let upload_limit = upload_limit.clone();
             let component_pkg_future = async move {
                 let permit = upload_limit.acquire().await?;
-                let component_pkg = app.upload_package(&component_modules, None).await?;
+                let component_pkg = app
+                    .upload_package(&component_modules, None, node_version)
+                    .await?;
                 drop(permit);
                 anyhow::Ok((definition_path, component_pkg))
             };
@@ -2115,6 +2143,7 @@ impl<RT: Runtime> Application<RT> {
         &self,
         modules: &Vec<ModuleConfig>,
         external_deps_id_and_pkg: Option<(ExternalDepsPackageId, ExternalDepsPackage)>,
+        node_version: NodeVersion,
     ) -> anyhow::Result<SourcePackage> {
         // If there are any node actions, turn on the lambdas.
         if modules
@@ -2122,6 +2151,7 @@ impl<RT: Runtime> Application<RT> {
             .any(|m| m.environment == ModuleEnvironment::Node)
         {
             self.runner().enable_actions()?;
+            self.runner().check_node_version(node_version)?;
         }
 
         tracing::info!(
@@ -2162,6 +2192,7 @@ impl<RT: Runtime> Application<RT> {
             sha256,
             external_deps_package_id,
             package_size,
+            node_version: Some(node_version),
         })
     }
 
@@ -2197,7 +2228,9 @@ impl<RT: Runtime> Application<RT> {
         // Write (and commit) the module source to S3.
         // This will become a dangling reference since the _modules entry won't
         // be committed to the database, but we have to deal with those anyway.
-        let source_package = self.upload_package(&vec![module.clone()], None).await?;
+        let source_package = self
+            .upload_package(&vec![module.clone()], None, NodeVersion::default())
+            .await?;
 
         let mut tx = self.begin(identity.clone()).await?;
         let environment_variables = if component.is_root() {
@@ -2336,11 +2369,15 @@ impl<RT: Runtime> Application<RT> {
     pub async fn build_external_node_deps(
         &self,
         deps: Vec<NodeDependency>,
+        node_version: NodeVersion,
     ) -> anyhow::Result<(ExternalDepsPackageId, ExternalDepsPackage)> {
+        self.runner().check_node_version(node_version)?;
         // Check cache to see if we've built this package recently
         let mut tx = self.begin(Identity::system()).await?;
         let mut model = ExternalPackagesModel::new(&mut tx);
-        let cached_match = model.get_cached_package_match(deps.clone()).await?;
+        let cached_match = model
+            .get_cached_package_match(deps.clone(), node_version)
+            .await?;
         if let Some((cached_id, cached_pkg)) = cached_match {
             tracing::info!("Cache hit for external deps package!");
             log_external_deps_package(true);
@@ -2350,7 +2387,7 @@ impl<RT: Runtime> Application<RT> {
             tracing::info!("Cache miss for external deps package, running build_deps...");
         }
 
-        let result = self.runner().build_deps(deps).await?;
+        let result = self.runner().build_deps(deps, node_version).await?;
         let pkg = match result {
             Ok(pkg) => pkg,
             Err(js_error) => {
@@ -2494,6 +2531,47 @@ impl<RT: Runtime> Application<RT> {
         Ok(storage_id)
     }
 
+    pub async fn start_resumable_upload(
+        &self,
+        component: ComponentId,
+        content_type: Option<ContentType>,
+    ) -> anyhow::Result<ResumableUploadProgress> {
+        self.bail_if_not_running().await?;
+        self.file_storage
+            .start_resumable_upload(component, content_type)
+            .await
+    }
+
+    pub async fn upload_resumable_part(
+        &self,
+        upload_id: &str,
+        offset: u64,
+        part: Bytes,
+    ) -> anyhow::Result<ResumableUploadProgress> {
+        self.bail_if_not_running().await?;
+        self.file_storage
+            .upload_resumable_part(upload_id, offset, part)
+            .await
+    }
+
+    pub async fn resumable_upload_progress(
+        &self,
+        upload_id: &str,
+    ) -> anyhow::Result<ResumableUploadProgress> {
+        self.file_storage.resumable_upload_progress(upload_id).await
+    }
+
+    pub async fn finish_resumable_upload(
+        &self,
+        upload_id: &str,
+        expected_sha256: Option<Sha256Digest>,
+    ) -> anyhow::Result<DeveloperDocumentId> {
+        self.bail_if_not_running().await?;
+        self.file_storage
+            .finish_resumable_upload(upload_id, expected_sha256, &self.usage_tracking)
+            .await
+    }
+
     pub async fn store_file_entry(
         &self,
         component: ComponentId,
@@ -2607,6 +2685,19 @@ impl<RT: Runtime> Application<RT> {
             .await
     }
 
+    pub async fn get_transformed_image(
+        &self,
+        component: ComponentId,
+        storage_id: FileStorageId,
+        transform: ImageTransform,
+    ) -> anyhow::Result<FileStream> {
+        self.bail_if_not_running().await?;
+        self.file_storage
+            // The transaction is not part of UDF so use the global usage counters.
+            .get_transformed_image(component, storage_id, transform, self.usage_tracking.clone())
+            .await
+    }
+
     pub async fn authenticate(
         &self,
         token: AuthenticationToken,
@@ -3261,6 +3352,7 @@ impl<RT: Runtime> Application<RT> {
         self.log_sender.shutdown()?;
         self.table_summary_worker.shutdown().await?;
         self.system_table_cleanup_worker.lock().shutdown();
+        self.storage_reconciler.lock().shutdown();
         self.schema_worker.lock().shutdown();
         self.index_worker.lock().shutdown();
         self.search_worker.lock().shutdown();
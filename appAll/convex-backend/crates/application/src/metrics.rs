@@ -1,13 +1,38 @@
+use std::{
+    collections::HashSet,
+    sync::LazyLock,
+    time::{
+        Duration,
+        SystemTime,
+    },
+};
+
+use common::{
+    knobs::FUNCTION_METRICS_MAX_FUNCTIONS,
+    types::{
+        UdfIdentifier,
+        UdfType,
+    },
+    RequestId,
+};
+use fastrace::prelude::SpanContext;
 use metrics::{
+    log_counter_with_exemplar,
     log_counter_with_labels,
+    log_distribution_with_exemplar,
     log_distribution_with_labels,
     register_convex_counter,
+    register_convex_exemplar_counter,
+    register_convex_exemplar_histogram,
     register_convex_histogram,
+    Exemplar,
+    Labels,
     StaticMetricLabel,
     StatusTimer,
     STATUS_LABEL,
 };
 use model::source_packages::types::PackageSize;
+use parking_lot::Mutex;
 
 register_convex_counter!(
     EXTERNAL_DEPS_PACKAGES_TOTAL,
@@ -84,3 +109,120 @@ register_convex_histogram!(
 pub fn table_summary_bootstrap_timer() -> StatusTimer {
     StatusTimer::new(&TABLE_SUMMARY_BOOTSTRAP_SECONDS)
 }
+
+const FUNCTION_LABELS: &[&str] = &["component", "function", "udf_type"];
+
+/// Execution time buckets in seconds, up to the action time limit.
+const FUNCTION_EXECUTION_BUCKETS: &[f64] = &[
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1., 2.5, 5., 10., 30., 60., 300., 600.,
+];
+
+/// The `function` label for functions beyond `FUNCTION_METRICS_MAX_FUNCTIONS`.
+const OTHER_FUNCTIONS_LABEL: &str = "_other";
+
+/// The functions that have their own series, keyed by component path and
+/// function.
+static FUNCTIONS_WITH_METRICS: LazyLock<Mutex<HashSet<(String, String)>>> =
+    LazyLock::new(Default::default);
+
+register_convex_exemplar_counter!(
+    FUNCTION_CALLS_TOTAL,
+    "Number of function calls",
+    FUNCTION_LABELS,
+);
+register_convex_exemplar_counter!(
+    FUNCTION_ERRORS_TOTAL,
+    "Number of function calls that failed",
+    FUNCTION_LABELS,
+);
+register_convex_exemplar_counter!(
+    FUNCTION_OCC_RETRIES_TOTAL,
+    "Number of mutation runs retried because of a write conflict",
+    FUNCTION_LABELS,
+);
+register_convex_exemplar_histogram!(
+    FUNCTION_EXECUTION_SECONDS,
+    "Function execution time",
+    FUNCTION_LABELS,
+    FUNCTION_EXECUTION_BUCKETS,
+);
+
+pub fn log_function_execution(
+    identifier: &UdfIdentifier,
+    udf_type: UdfType,
+    is_err: bool,
+    execution_time: Duration,
+    request_id: &RequestId,
+    timestamp: SystemTime,
+) {
+    let labels = function_labels(identifier, udf_type);
+    let exemplar = function_exemplar(request_id, timestamp);
+    log_counter_with_exemplar(
+        &FUNCTION_CALLS_TOTAL,
+        1,
+        labels.clone(),
+        Some(exemplar.clone()),
+    );
+    if is_err {
+        log_counter_with_exemplar(
+            &FUNCTION_ERRORS_TOTAL,
+            1,
+            labels.clone(),
+            Some(exemplar.clone()),
+        );
+    }
+    log_distribution_with_exemplar(
+        &FUNCTION_EXECUTION_SECONDS,
+        execution_time.as_secs_f64(),
+        labels,
+        Some(exemplar),
+    );
+}
+
+pub fn log_function_occ_retry(
+    identifier: &UdfIdentifier,
+    request_id: &RequestId,
+    timestamp: SystemTime,
+) {
+    log_counter_with_exemplar(
+        &FUNCTION_OCC_RETRIES_TOTAL,
+        1,
+        function_labels(identifier, UdfType::Mutation),
+        Some(function_exemplar(request_id, timestamp)),
+    );
+}
+
+fn function_labels(identifier: &UdfIdentifier, udf_type: UdfType) -> Labels<'static> {
+    let (component, function) = match identifier {
+        UdfIdentifier::Function(path) => (
+            path.component.clone().serialize().unwrap_or_default(),
+            path.udf_path.clone().strip().to_string(),
+        ),
+        UdfIdentifier::Http(_) | UdfIdentifier::SystemJob(_) => {
+            (String::new(), identifier.to_string())
+        },
+    };
+    let key = (component, function);
+    let mut functions = FUNCTIONS_WITH_METRICS.lock();
+    let function = if functions.contains(&key) || functions.len() < *FUNCTION_METRICS_MAX_FUNCTIONS
+    {
+        functions.insert(key.clone());
+        key.1
+    } else {
+        OTHER_FUNCTIONS_LABEL.to_string()
+    };
+    vec![
+        StaticMetricLabel::new("component", key.0),
+        StaticMetricLabel::new("function", function),
+        udf_type.metric_label(),
+    ]
+}
+
+fn function_exemplar(request_id: &RequestId, timestamp: SystemTime) -> Exemplar {
+    let mut labels = vec![];
+    if let Some(span) = SpanContext::current_local_parent() {
+        labels.push(("trace_id", format!("{:032x}", span.trace_id.0)));
+    }
+    labels.push(("request_id", request_id.to_string()));
+    Exemplar::new(labels, timestamp)
+}
@@ -39,6 +39,7 @@ use headers::{
 use keybroker::Identity;
 use model::{
     file_storage::{
+        blobs::FileStorageBlobsModel,
         FILE_STORAGE_TABLE,
         FILE_STORAGE_VIRTUAL_TABLE,
     },
@@ -186,8 +187,11 @@ pub async fn import_storage_table<RT: Runtime>(
                 "snapshot_import_storage_table",
                 |tx| {
                     async {
-                        let mut entry_object_map =
-                            BTreeMap::from(ConvexObject::try_from(entry.clone())?);
+                        let mut entry = entry.clone();
+                        FileStorageBlobsModel::new(tx)
+                            .add_reference(&mut entry)
+                            .await?;
+                        let mut entry_object_map = BTreeMap::from(ConvexObject::try_from(entry)?);
                         entry_object_map.insert(ID_FIELD.clone().into(), val!(id));
                         if let Some(creation_time) = creation_time {
                             entry_object_map.insert(
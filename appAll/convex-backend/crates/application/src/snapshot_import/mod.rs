@@ -73,6 +73,7 @@ use model::{
         DeploymentAuditLogModel,
     },
     file_storage::{
+        FileStorageModel,
         FILE_STORAGE_TABLE,
         FILE_STORAGE_VIRTUAL_TABLE,
     },
@@ -891,6 +892,12 @@ async fn finalize_import<RT: Runtime>(
                     for tablet_id in table_mapping_for_import.to_delete.keys() {
                         let namespace = tx.table_mapping().tablet_namespace(*tablet_id)?;
                         let table_name = tx.table_mapping().tablet_name(*tablet_id)?;
+                        if table_name == *FILE_STORAGE_TABLE {
+                            // The replaced entries no longer hold their blobs.
+                            FileStorageModel::new(tx, namespace)
+                                .remove_all_references()
+                                .await?;
+                        }
                         let mut table_model = TableModel::new(tx);
                         documents_deleted += table_model
                             .count(namespace, &table_name)
@@ -0,0 +1,26 @@
+use metrics::{
+    log_counter_with_labels,
+    register_convex_counter,
+    StaticMetricLabel,
+};
+use storage::StorageUseCase;
+
+register_convex_counter!(
+    STORAGE_RECONCILER_OBJECTS_DELETED_TOTAL,
+    "Number of unreferenced objects deleted from storage by the storage reconciler",
+    &["use_case"]
+);
+register_convex_counter!(
+    STORAGE_RECONCILER_BYTES_DELETED_TOTAL,
+    "Number of bytes reclaimed from storage by the storage reconciler",
+    &["use_case"]
+);
+pub fn log_storage_reconciler_deleted(use_case: StorageUseCase, num_objects: usize, bytes: u64) {
+    let labels = vec![StaticMetricLabel::new("use_case", use_case.to_string())];
+    log_counter_with_labels(
+        &STORAGE_RECONCILER_OBJECTS_DELETED_TOTAL,
+        num_objects as u64,
+        labels.clone(),
+    );
+    log_counter_with_labels(&STORAGE_RECONCILER_BYTES_DELETED_TOTAL, bytes, labels);
+}
@@ -0,0 +1,420 @@
+//! Garbage collection for objects in `Storage` that nothing references.
+//!
+//! Objects are normally deleted by whatever removes their last reference, but
+//! a crash between committing that change and deleting the object (or between
+//! uploading an object and recording it) leaves the bytes behind forever. The
+//! reconciler periodically lists every object per use case, compares the
+//! listing with the references in the database and deletes the difference.
+//!
+//! To avoid racing with writers, an object is only deleted once it is older
+//! than `STORAGE_ORPHAN_GRACE_PERIOD` and has been found unreferenced on two
+//! consecutive passes. The second pass gives readers at a slightly older
+//! snapshot (e.g. a search that started before a compaction committed) time to
+//! finish with it.
+
+use std::{
+    collections::{
+        BTreeMap,
+        BTreeSet,
+    },
+    sync::Arc,
+    time::{
+        Duration,
+        SystemTime,
+    },
+};
+
+use common::{
+    bootstrap_model::index::{
+        text_index::{
+            TextIndexSnapshotData,
+            TextIndexState,
+        },
+        vector_index::{
+            VectorIndexSnapshotData,
+            VectorIndexState,
+        },
+        IndexConfig,
+    },
+    document::{
+        ParsedDocument,
+        ResolvedDocument,
+    },
+    errors::report_error,
+    knobs::{
+        ENABLE_STORAGE_RECONCILER,
+        STORAGE_ORPHAN_GRACE_PERIOD,
+        STORAGE_RECONCILE_FREQUENCY,
+    },
+    query::{
+        Order,
+        Query,
+    },
+    runtime::Runtime,
+    types::{
+        FullyQualifiedObjectKey,
+        ObjectKey,
+        TableName,
+    },
+};
+use database::{
+    query::{
+        PaginationOptions,
+        TableFilter,
+    },
+    Database,
+    IndexModel,
+    ResolvedQuery,
+    TableModel,
+};
+use futures::{
+    Future,
+    TryStreamExt,
+};
+use keybroker::Identity;
+use model::{
+    exports::{
+        types::Export,
+        ExportsModel,
+    },
+    file_storage::{
+        blobs::FILE_STORAGE_BLOBS_TABLE,
+        image_variants::FILE_STORAGE_IMAGE_VARIANTS_TABLE,
+        types::{
+            FileStorageBlob,
+            FileStorageEntry,
+            FileStorageImageVariant,
+        },
+        FILE_STORAGE_TABLE,
+    },
+    snapshot_imports::SnapshotImportModel,
+};
+use rand::Rng;
+use storage::{
+    Storage,
+    StorageUseCase,
+};
+use value::TableNamespace;
+
+use self::metrics::log_storage_reconciler_deleted;
+
+mod metrics;
+
+/// What a single pass of the reconciler did for one use case.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ReconcileReport {
+    pub objects_listed: usize,
+    /// Unreferenced objects that will be deleted on the next pass if they're
+    /// still unreferenced then.
+    pub objects_suspected: usize,
+    pub objects_deleted: usize,
+    pub bytes_deleted: u64,
+}
+
+pub struct StorageReconciler<RT: Runtime> {
+    runtime: RT,
+    database: Database<RT>,
+    storages: Vec<(StorageUseCase, Arc<dyn Storage>)>,
+    grace_period: Duration,
+    /// Objects found unreferenced on the previous pass.
+    suspected: BTreeMap<StorageUseCase, BTreeSet<ObjectKey>>,
+}
+
+impl<RT: Runtime> StorageReconciler<RT> {
+    pub fn new(
+        runtime: RT,
+        database: Database<RT>,
+        files_storage: Arc<dyn Storage>,
+        exports_storage: Arc<dyn Storage>,
+        snapshot_imports_storage: Arc<dyn Storage>,
+        search_storage: Arc<dyn Storage>,
+    ) -> Self {
+        Self {
+            runtime,
+            database,
+            storages: vec![
+                (StorageUseCase::Files, files_storage),
+                (StorageUseCase::Exports, exports_storage),
+                (StorageUseCase::SnapshotImports, snapshot_imports_storage),
+                (StorageUseCase::SearchIndexes, search_storage),
+            ],
+            grace_period: *STORAGE_ORPHAN_GRACE_PERIOD,
+            suspected: BTreeMap::new(),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn with_grace_period(mut self, grace_period: Duration) -> Self {
+        self.grace_period = grace_period;
+        self
+    }
+
+    pub fn start(self) -> impl Future<Output = ()> + Send {
+        let mut worker = self;
+        async move {
+            if !*ENABLE_STORAGE_RECONCILER {
+                tracing::info!("Storage reconciler is disabled");
+                return;
+            }
+            loop {
+                // Always wait the full interval between passes, plus up to 10% of
+                // jitter so a fleet of backends doesn't list their buckets at the
+                // same time.
+                let jitter = STORAGE_RECONCILE_FREQUENCY
+                    .mul_f32(worker.runtime.rng().random_range(0.0..=0.1));
+                worker
+                    .runtime
+                    .wait(*STORAGE_RECONCILE_FREQUENCY + jitter)
+                    .await;
+                if let Err(e) = worker.reconcile().await {
+                    report_error(&mut e.context("StorageReconciler failed")).await;
+                }
+            }
+        }
+    }
+
+    /// Run a single pass over every storage use case.
+    pub async fn reconcile(&mut self) -> anyhow::Result<BTreeMap<StorageUseCase, ReconcileReport>> {
+        let mut reports = BTreeMap::new();
+        for (use_case, storage) in self.storages.clone() {
+            let report = self.reconcile_storage(use_case, &storage).await?;
+            if report.objects_deleted > 0 {
+                tracing::info!(
+                    "Deleted {} unreferenced objects ({} bytes) from {use_case} storage",
+                    report.objects_deleted,
+                    report.bytes_deleted,
+                );
+                log_storage_reconciler_deleted(
+                    use_case,
+                    report.objects_deleted,
+                    report.bytes_deleted,
+                );
+            }
+            reports.insert(use_case, report);
+        }
+        Ok(reports)
+    }
+
+    async fn reconcile_storage(
+        &mut self,
+        use_case: StorageUseCase,
+        storage: &Arc<dyn Storage>,
+    ) -> anyhow::Result<ReconcileReport> {
+        // List before reading references: anything recorded after we read them
+        // was uploaded recently enough to be within the grace period.
+        let listed: Vec<_> = storage.list_objects().try_collect().await?;
+        let mut report = ReconcileReport {
+            objects_listed: listed.len(),
+            ..Default::default()
+        };
+        let Some(referenced) = self.referenced_keys(use_case, storage).await? else {
+            tracing::warn!("Skipping {use_case} storage: can't determine which objects are live");
+            self.suspected.remove(&use_case);
+            return Ok(report);
+        };
+        // Modification times come from the storage backend, so compare them
+        // against the wall clock rather than the runtime's.
+        let now = SystemTime::now();
+        let previously_suspected = self.suspected.remove(&use_case).unwrap_or_default();
+        let mut suspected = BTreeSet::new();
+        for object in listed {
+            let age = now
+                .duration_since(object.last_modified)
+                .unwrap_or(Duration::ZERO);
+            if age < self.grace_period
+                || referenced.contains(&storage.fully_qualified_key(&object.key))
+            {
+                continue;
+            }
+            if !previously_suspected.contains(&object.key) {
+                suspected.insert(object.key);
+                continue;
+            }
+            storage.delete_object(&object.key).await?;
+            report.objects_deleted += 1;
+            report.bytes_deleted += object.size;
+        }
+        report.objects_suspected = suspected.len();
+        self.suspected.insert(use_case, suspected);
+        Ok(report)
+    }
+
+    /// Returns the fully qualified keys of every object in `storage` that the
+    /// database still references, or `None` if that can't be determined.
+    async fn referenced_keys(
+        &self,
+        use_case: StorageUseCase,
+        storage: &Arc<dyn Storage>,
+    ) -> anyhow::Result<Option<BTreeSet<FullyQualifiedObjectKey>>> {
+        let keys = match use_case {
+            StorageUseCase::Files => self.file_references().await?,
+            StorageUseCase::Exports => self.export_references().await?,
+            StorageUseCase::SnapshotImports => {
+                let mut tx = self.database.begin(Identity::system()).await?;
+                let imports = SnapshotImportModel::new(&mut tx).list().await?;
+                // Imports may point at objects in other storages, so compare
+                // fully qualified keys.
+                return Ok(Some(
+                    imports
+                        .into_iter()
+                        .map(|import| match import.into_value().object_key {
+                            Ok(fq_key) => fq_key,
+                            Err(key) => storage.fully_qualified_key(&key),
+                        })
+                        .collect(),
+                ));
+            },
+            StorageUseCase::SearchIndexes => match self.search_index_references().await? {
+                Some(keys) => keys,
+                None => return Ok(None),
+            },
+            // Module sources are cached by content and shared between pushes, so
+            // they aren't reconciled.
+            StorageUseCase::Modules => return Ok(None),
+        };
+        Ok(Some(
+            keys.iter()
+                .map(|key| storage.fully_qualified_key(key))
+                .collect(),
+        ))
+    }
+
+    async fn file_references(&self) -> anyhow::Result<BTreeSet<ObjectKey>> {
+        let mut keys = BTreeSet::new();
+        let namespaces = {
+            let mut tx = self.database.begin(Identity::system()).await?;
+            tx.table_mapping().namespaces_for_name(&FILE_STORAGE_TABLE)
+        };
+        for namespace in namespaces {
+            self.for_each_document(namespace, &FILE_STORAGE_TABLE, |document| {
+                let entry: ParsedDocument<FileStorageEntry> = document.try_into()?;
+                keys.insert(entry.into_value().storage_key);
+                Ok(())
+            })
+            .await?;
+        }
+        // Blobs with no references are deleted by `SystemTableCleanupWorker`,
+        // so leave them to it.
+        self.for_each_document(
+            TableNamespace::Global,
+            &FILE_STORAGE_BLOBS_TABLE,
+            |document| {
+                let blob: ParsedDocument<FileStorageBlob> = document.try_into()?;
+                keys.insert(blob.into_value().storage_key);
+                Ok(())
+            },
+        )
+        .await?;
+        self.for_each_document(
+            TableNamespace::Global,
+            &FILE_STORAGE_IMAGE_VARIANTS_TABLE,
+            |document| {
+                let variant: ParsedDocument<FileStorageImageVariant> = document.try_into()?;
+                keys.insert(variant.into_value().storage_key);
+                Ok(())
+            },
+        )
+        .await?;
+        Ok(keys)
+    }
+
+    async fn export_references(&self) -> anyhow::Result<BTreeSet<ObjectKey>> {
+        let mut tx = self.database.begin(Identity::system()).await?;
+        let exports = ExportsModel::new(&mut tx).list().await?;
+        Ok(exports
+            .into_iter()
+            .filter_map(|export| match export.into_value() {
+                Export::Completed { zip_object_key, .. } => Some(zip_object_key),
+                Export::Requested { .. }
+                | Export::InProgress { .. }
+                | Export::Failed { .. }
+                | Export::Canceled { .. } => None,
+            })
+            .collect())
+    }
+
+    /// Segments referenced by text and vector indexes in any state. Returns
+    /// `None` if an index has a snapshot in a format we don't understand.
+    async fn search_index_references(&self) -> anyhow::Result<Option<BTreeSet<ObjectKey>>> {
+        let mut tx = self.database.begin(Identity::system()).await?;
+        let indexes = IndexModel::new(&mut tx).get_all_indexes().await?;
+        let mut keys = BTreeSet::new();
+        for index in indexes {
+            match &index.config {
+                IndexConfig::Database { .. } => {},
+                IndexConfig::Text { on_disk_state, .. } => {
+                    let segments = match on_disk_state {
+                        TextIndexState::Backfilling(backfill_state) => &backfill_state.segments,
+                        TextIndexState::Backfilled(snapshot)
+                        | TextIndexState::SnapshottedAt(snapshot) => match &snapshot.data {
+                            TextIndexSnapshotData::MultiSegment(segments) => segments,
+                            TextIndexSnapshotData::Unknown(_) => return Ok(None),
+                        },
+                    };
+                    for segment in segments {
+                        keys.extend([
+                            segment.segment_key.clone(),
+                            segment.id_tracker_key.clone(),
+                            segment.deleted_terms_table_key.clone(),
+                            segment.alive_bitset_key.clone(),
+                        ]);
+                    }
+                },
+                IndexConfig::Vector { on_disk_state, .. } => {
+                    let segments = match on_disk_state {
+                        VectorIndexState::Backfilling(backfill_state) => &backfill_state.segments,
+                        VectorIndexState::Backfilled(snapshot)
+                        | VectorIndexState::SnapshottedAt(snapshot) => match &snapshot.data {
+                            VectorIndexSnapshotData::MultiSegment(segments) => segments,
+                            VectorIndexSnapshotData::Unknown(_) => return Ok(None),
+                        },
+                    };
+                    for segment in segments {
+                        keys.extend([
+                            segment.segment_key.clone(),
+                            segment.id_tracker_key.clone(),
+                            segment.deleted_bitset_key.clone(),
+                        ]);
+                    }
+                },
+            }
+        }
+        Ok(Some(keys))
+    }
+
+    /// Call `f` on every document in `table`, reading in as many transactions
+    /// as it takes to stay under the transaction limits.
+    async fn for_each_document(
+        &self,
+        namespace: TableNamespace,
+        table: &TableName,
+        mut f: impl FnMut(ResolvedDocument) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let mut tx = self.database.begin(Identity::system()).await?;
+        if !TableModel::new(&mut tx).table_exists(namespace, table) {
+            return Ok(());
+        }
+        let query = Query::full_table_scan(table.clone(), Order::Asc);
+        let mut query_stream = ResolvedQuery::new(&mut tx, namespace, query.clone())?;
+        while let Some(document) = query_stream.next(&mut tx, None).await? {
+            f(document)?;
+            if query_stream.is_approaching_data_limit() {
+                let cursor = query_stream.cursor();
+                tx = self.database.begin(Identity::system()).await?;
+                query_stream = ResolvedQuery::new_bounded(
+                    &mut tx,
+                    namespace,
+                    query.clone(),
+                    PaginationOptions::ManualPagination {
+                        start_cursor: cursor,
+                        maximum_rows_read: None,
+                        maximum_bytes_read: None,
+                    },
+                    None,
+                    TableFilter::IncludePrivateSystemTables,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -37,3 +37,11 @@ register_convex_counter!(
 pub fn log_exports_s3_cleanup() {
     log_counter(&EXPORT_TABLE_CLEANUP_ROWS_TOTAL, 1)
 }
+
+register_convex_counter!(
+    FILE_STORAGE_BLOBS_CLEANUP_TOTAL,
+    "Number of unreferenced file storage blobs deleted from storage",
+);
+pub fn log_file_storage_blobs_cleanup(num_deleted: usize) {
+    log_counter(&FILE_STORAGE_BLOBS_CLEANUP_TOTAL, num_deleted as u64)
+}
@@ -48,21 +48,36 @@ use database::{
     ResolvedQuery,
     SystemMetadataModel,
     TableModel,
+    Transaction,
+};
+use futures::{
+    Future,
+    TryStreamExt,
 };
-use futures::Future;
 use governor::Quota;
 use keybroker::Identity;
 use metrics::{
     log_exports_s3_cleanup,
+    log_file_storage_blobs_cleanup,
     log_system_table_cleanup_rows,
     system_table_cleanup_timer,
 };
 use model::{
     exports::ExportsModel,
+    file_storage::{
+        blobs::FileStorageBlobsModel,
+        types::FileStorageEntry,
+        uploads::FileStorageUploadsModel,
+        FileStorageModel,
+        FILE_STORAGE_TABLE,
+    },
     session_requests::SESSION_REQUESTS_TABLE,
 };
 use rand::Rng;
-use storage::Storage;
+use storage::{
+    ClientDrivenUploadToken,
+    Storage,
+};
 use value::{
     TableNamespace,
     TabletId,
@@ -76,6 +91,7 @@ pub struct SystemTableCleanupWorker<RT: Runtime> {
     database: Database<RT>,
     runtime: RT,
     exports_storage: Arc<dyn Storage>,
+    files_storage: Arc<dyn Storage>,
 }
 
 impl<RT: Runtime> SystemTableCleanupWorker<RT> {
@@ -84,11 +100,13 @@ impl<RT: Runtime> SystemTableCleanupWorker<RT> {
         runtime: RT,
         database: Database<RT>,
         exports_storage: Arc<dyn Storage>,
+        files_storage: Arc<dyn Storage>,
     ) -> impl Future<Output = ()> + Send {
         let mut worker = SystemTableCleanupWorker {
             database,
             runtime,
             exports_storage,
+            files_storage,
         };
         async move {
             if MAX_SESSION_CLEANUP_DURATION.is_none() {
@@ -119,6 +137,8 @@ impl<RT: Runtime> SystemTableCleanupWorker<RT> {
             self.cleanup_hidden_tables().await?;
             self.cleanup_orphaned_table_namespaces().await?;
             self.cleanup_expired_exports().await?;
+            self.cleanup_unreferenced_file_blobs().await?;
+            self.cleanup_expired_file_uploads().await?;
 
             // _session_requests are used to make mutations idempotent.
             // We can delete them after they are old enough that the client that
@@ -184,6 +204,10 @@ impl<RT: Runtime> SystemTableCleanupWorker<RT> {
                         if age > 2 * (*MAX_IMPORT_AGE) {
                             let table_id = TabletId(table.id().internal_id());
                             tracing::info!("Deleting hidden table: {table_id:?}");
+                            if table.name == *FILE_STORAGE_TABLE {
+                                self.remove_hidden_file_storage_references(&mut tx, table_id)
+                                    .await?;
+                            }
                             TableModel::new(&mut tx)
                                 .delete_hidden_table(table_id)
                                 .await?;
@@ -204,6 +228,24 @@ impl<RT: Runtime> SystemTableCleanupWorker<RT> {
         Ok(())
     }
 
+    /// A failed import already counted blob references for the `_file_storage`
+    /// entries it wrote, so they have to be dropped along with its hidden
+    /// table. Nothing writes to hidden tables, so scanning the latest
+    /// snapshot outside of `tx` sees the same entries.
+    async fn remove_hidden_file_storage_references(
+        &self,
+        tx: &mut Transaction<RT>,
+        tablet_id: TabletId,
+    ) -> anyhow::Result<()> {
+        let mut documents = self.database.full_table_scan(tablet_id).await?;
+        let mut blobs_model = FileStorageBlobsModel::new(tx);
+        while let Some(document) = documents.try_next().await? {
+            let entry: ParsedDocument<FileStorageEntry> = document.value.try_into()?;
+            blobs_model.remove_reference(&entry.storage_key).await?;
+        }
+        Ok(())
+    }
+
     /// Delete table namespaces that are not associated with any component.
     /// This can occur when a push does not complete successfully, where
     /// `start_push` initializes component system tables in a new namespace
@@ -213,7 +255,6 @@ impl<RT: Runtime> SystemTableCleanupWorker<RT> {
         let ts = tx.begin_timestamp();
         let table_mapping = tx.table_mapping().clone();
         let component_paths = BootstrapComponentsModel::new(&mut tx).all_component_paths();
-        let mut table_model = TableModel::new(&mut tx);
         for (namespace, map) in table_mapping.iter_active_namespaces() {
             let component_id = ComponentId::from(*namespace);
             if component_paths.contains_key(&component_id) {
@@ -222,14 +263,18 @@ impl<RT: Runtime> SystemTableCleanupWorker<RT> {
             for (table_name, tablet_id) in map.iter() {
                 // Ensure user tables are empty before deleting.
                 if !table_name.is_system() {
-                    let count = table_model.must_count(*namespace, table_name).await?;
+                    let count = TableModel::new(&mut tx)
+                        .must_count(*namespace, table_name)
+                        .await?;
                     anyhow::ensure!(
                         count == 0,
                         "Non-system table {table_name} found with {count} documents in orphaned \
                          table namespace component id: {component_id:?}"
                     );
                 }
-                let table_metadata = table_model.get_table_metadata(*tablet_id).await?;
+                let table_metadata = TableModel::new(&mut tx)
+                    .get_table_metadata(*tablet_id)
+                    .await?;
                 let now = CreationTime::try_from(*ts)?;
                 let creation_time = table_metadata
                     .creation_time()
@@ -240,7 +285,12 @@ impl<RT: Runtime> SystemTableCleanupWorker<RT> {
                         "Deleting orphaned table {table_name:?} in non-existent component \
                          {component_id:?}"
                     );
-                    table_model
+                    if *table_name == *FILE_STORAGE_TABLE {
+                        FileStorageModel::new(&mut tx, *namespace)
+                            .remove_all_references()
+                            .await?;
+                    }
+                    TableModel::new(&mut tx)
                         .delete_table(*namespace, table_name.clone())
                         .await?;
                 }
@@ -358,6 +408,57 @@ impl<RT: Runtime> SystemTableCleanupWorker<RT> {
         }
         Ok(())
     }
+
+    /// Remove objects from file storage that are no longer referenced by any
+    /// `_storage` entry.
+    async fn cleanup_unreferenced_file_blobs(&self) -> anyhow::Result<()> {
+        loop {
+            let mut tx = self.database.begin(Identity::system()).await?;
+            let object_keys_to_del = FileStorageBlobsModel::new(&mut tx)
+                .cleanup_unreferenced(*SYSTEM_TABLE_CLEANUP_CHUNK_SIZE)
+                .await?;
+            if object_keys_to_del.is_empty() {
+                return Ok(());
+            }
+            // Commit before deleting the objects: once the rows are gone no new
+            // `_storage` entry can start referencing these objects.
+            self.database
+                .commit_with_write_source(tx, "system_table_cleanup")
+                .await?;
+            let num_deleted = object_keys_to_del.len();
+            for object_key in object_keys_to_del {
+                self.files_storage.delete_object(&object_key).await?;
+            }
+            log_file_storage_blobs_cleanup(num_deleted);
+            tracing::info!("Deleted {num_deleted} unreferenced file storage blobs");
+        }
+    }
+
+    /// Forget resumable uploads that were never finished and discard the parts
+    /// they staged in file storage.
+    async fn cleanup_expired_file_uploads(&self) -> anyhow::Result<()> {
+        loop {
+            let mut tx = self.database.begin(Identity::system()).await?;
+            let upload_tokens = FileStorageUploadsModel::new(&mut tx)
+                .cleanup_expired(*SYSTEM_TABLE_CLEANUP_CHUNK_SIZE)
+                .await?;
+            if upload_tokens.is_empty() {
+                return Ok(());
+            }
+            // Commit first so a racing `upload_resumable_part` can't record a
+            // part for an upload that has already been aborted.
+            self.database
+                .commit_with_write_source(tx, "system_table_cleanup")
+                .await?;
+            let num_deleted = upload_tokens.len();
+            for upload_token in upload_tokens {
+                self.files_storage
+                    .abort_client_driven_upload(ClientDrivenUploadToken(upload_token))
+                    .await?;
+            }
+            tracing::info!("Deleted {num_deleted} expired resumable uploads");
+        }
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -34,6 +34,7 @@ use common::{
     types::{
         ConvexOrigin,
         FullyQualifiedObjectKey,
+        NodeVersion,
     },
 };
 use database::{
@@ -88,6 +89,7 @@ use model::{
 use node_executor::{
     local::LocalNodeExecutor,
     Actions,
+    NodeSandboxConfig,
 };
 use storage::{
     LocalDirStorage,
@@ -101,6 +103,7 @@ use value::{
 };
 
 use crate::{
+    application_function_runner::FunctionQuotaLimits,
     cache::QueryCache,
     cron_jobs::CronJobExecutor,
     deploy_config::{
@@ -120,6 +123,7 @@ pub static OBJECTS_TABLE_COMPONENT: ComponentId = ComponentId::test_user();
 pub struct ApplicationFixtureArgs {
     pub tp: Option<TestPersistence>,
     pub event_logger: Option<Arc<dyn UsageEventLogger>>,
+    pub function_quota_limits: Option<FunctionQuotaLimits>,
 }
 
 impl ApplicationFixtureArgs {
@@ -247,7 +251,11 @@ impl<RT: Runtime> ApplicationTestExt<RT> for Application<RT> {
         };
 
         let node_process_timeout = *ACTION_USER_TIMEOUT + Duration::from_secs(5);
-        let node_executor = Arc::new(LocalNodeExecutor::new(node_process_timeout)?);
+        let node_executor = Arc::new(LocalNodeExecutor::new(
+            node_process_timeout,
+            vec![],
+            NodeSandboxConfig::default(),
+        )?);
         let actions = Actions::new(
             node_executor,
             convex_origin.clone(),
@@ -281,6 +289,8 @@ impl<RT: Runtime> ApplicationTestExt<RT> for Application<RT> {
                 Arc::new(NullAccessTokenAuth),
             )),
             QueryCache::new(*UDF_CACHE_MAX_SIZE),
+            args.function_quota_limits
+                .unwrap_or_else(FunctionQuotaLimits::from_knobs),
         )
         .await?;
 
@@ -423,7 +433,9 @@ impl<RT: Runtime> Application<RT> {
         let mut tx = self.begin(Identity::system()).await?;
         let udf_config = UdfConfig::new_for_test(&self.runtime(), "1000.0.0".parse()?);
         // TODO(rakeeb): add external packages to udf test modules
-        let source_package = self.upload_package(&test_source, None).await?;
+        let source_package = self
+            .upload_package(&test_source, None, NodeVersion::default())
+            .await?;
         let analyze_results = self
             .analyze(
                 udf_config.clone(),
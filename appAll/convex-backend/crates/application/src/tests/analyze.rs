@@ -2,6 +2,7 @@ use std::collections::BTreeMap;
 
 use common::types::{
     ModuleEnvironment,
+    NodeVersion,
     UdfType,
 };
 use model::{
@@ -71,7 +72,9 @@ async fn test_analyze(rt: ProdRuntime) -> anyhow::Result<()> {
             environment: ModuleEnvironment::Node,
         },
     ];
-    let source_package = application.upload_package(&modules, None).await?;
+    let source_package = application
+        .upload_package(&modules, None, NodeVersion::default())
+        .await?;
     let udf_config = UdfConfig::new_for_test(&rt, "1000.0.0".parse()?);
     let modules = application
         .analyze(udf_config, modules, source_package, BTreeMap::new())
@@ -172,7 +175,9 @@ export { hello, internalHello };
         },
     ];
 
-    let source_package = application.upload_package(&modules, None).await?;
+    let source_package = application
+        .upload_package(&modules, None, NodeVersion::default())
+        .await?;
     let udf_config = UdfConfig::new_for_test(&rt, "1000.0.0".parse()?);
     let modules = application
         .analyze(udf_config.clone(), modules, source_package, BTreeMap::new())
@@ -227,7 +232,9 @@ async fn test_analyze_crons(rt: ProdRuntime) -> anyhow::Result<()> {
             environment: ModuleEnvironment::Isolate,
         },
     ];
-    let source_package = application.upload_package(&modules, None).await?;
+    let source_package = application
+        .upload_package(&modules, None, NodeVersion::default())
+        .await?;
     let udf_config = UdfConfig::new_for_test(&rt, "1000.0.0".parse()?);
     let modules = application
         .analyze(udf_config.clone(), modules, source_package, BTreeMap::new())
@@ -255,7 +262,9 @@ async fn test_analyze_crons(rt: ProdRuntime) -> anyhow::Result<()> {
         source_map: None,
         environment: ModuleEnvironment::Isolate,
     }];
-    let source_package = application.upload_package(&modules, None).await?;
+    let source_package = application
+        .upload_package(&modules, None, NodeVersion::default())
+        .await?;
     let result = application
         .analyze(udf_config.clone(), modules, source_package, BTreeMap::new())
         .await;
@@ -275,7 +284,9 @@ async fn test_analyze_crons(rt: ProdRuntime) -> anyhow::Result<()> {
         source_map: None,
         environment: ModuleEnvironment::Isolate,
     }];
-    let source_package = application.upload_package(&modules, None).await?;
+    let source_package = application
+        .upload_package(&modules, None, NodeVersion::default())
+        .await?;
     let result = application
         .analyze(udf_config, modules, source_package, BTreeMap::new())
         .await;
@@ -42,6 +42,7 @@ export default {
         &ConfigFile {
             functions: "convex".to_owned(),
             auth_info: None,
+            node_version: None,
         },
     )
     .await
@@ -9,6 +9,7 @@ mod fivetran_import;
 mod indexes;
 mod mutation;
 mod occ_retries;
+mod push_history;
 mod query_cache;
 mod returns_validation;
 mod scheduled_jobs;
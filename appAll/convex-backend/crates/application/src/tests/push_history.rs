@@ -0,0 +1,49 @@
+use std::time::Duration;
+
+use common::components::ComponentId;
+use keybroker::Identity;
+use model::{
+    modules::ModuleModel,
+    push_history::PushHistoryModel,
+};
+use runtime::testing::TestRuntime;
+
+use crate::{
+    test_helpers::ApplicationTestExt,
+    Application,
+};
+
+#[convex_macro::test_runtime]
+async fn test_rollback_push(rt: TestRuntime) -> anyhow::Result<()> {
+    let application = Application::new_for_tests(&rt).await?;
+    application.load_component_tests_modules("basic").await?;
+    application.load_component_tests_modules("empty").await?;
+
+    let mut tx = application.begin(Identity::system()).await?;
+    let pushes = PushHistoryModel::new(&mut tx).list().await?;
+    assert_eq!(pushes.len(), 2);
+    let basic_push_id = pushes[1].id().developer_id;
+    assert!(ModuleModel::new(&mut tx)
+        .get_application_metadata(ComponentId::Root)
+        .await?
+        .is_empty());
+    drop(tx);
+
+    application
+        .rollback_push(Identity::system(), basic_push_id, Duration::from_secs(10))
+        .await?;
+
+    let mut tx = application.begin(Identity::system()).await?;
+    let pushes = PushHistoryModel::new(&mut tx).list().await?;
+    assert_eq!(pushes.len(), 3);
+    assert_eq!(pushes[0].rollback_of, Some(basic_push_id));
+    assert_eq!(
+        pushes[0].definitions.keys().collect::<Vec<_>>(),
+        pushes[2].definitions.keys().collect::<Vec<_>>(),
+    );
+    assert!(!ModuleModel::new(&mut tx)
+        .get_application_metadata(ComponentId::Root)
+        .await?
+        .is_empty());
+    Ok(())
+}
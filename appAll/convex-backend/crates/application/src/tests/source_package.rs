@@ -3,7 +3,10 @@ use std::collections::BTreeMap;
 use common::{
     components::ComponentId,
     runtime::Runtime,
-    types::ModuleEnvironment,
+    types::{
+        ModuleEnvironment,
+        NodeVersion,
+    },
 };
 use database::Transaction;
 use keybroker::Identity;
@@ -48,7 +51,9 @@ async fn test_source_package(rt: ProdRuntime) -> anyhow::Result<()> {
         storage_key,
         sha256,
         ..
-    } = application.upload_package(&package, None).await?;
+    } = application
+        .upload_package(&package, None, NodeVersion::default())
+        .await?;
 
     let result =
         download_package(application.modules_storage().clone(), storage_key, sha256).await?;
@@ -1,14 +1,25 @@
+use std::time::Duration;
+
 use common::{
     components::ComponentId,
     types::BackendState,
 };
 use errors::ErrorMetadataAnyhowExt;
-use futures::stream;
+use futures::{
+    stream,
+    TryStreamExt,
+};
 use keybroker::Identity;
 use model::backend_state::BackendStateModel;
 use runtime::testing::TestRuntime;
+use storage::{
+    StorageExt,
+    StorageUseCase,
+    Upload,
+};
 
 use crate::{
+    storage_reconciler::StorageReconciler,
     test_helpers::ApplicationTestExt,
     Application,
 };
@@ -44,3 +55,49 @@ pub(crate) async fn test_backend_not_running_cannot_store_file(
     assert_eq!(error.short_msg(), "BackendIsNotRunning");
     Ok(())
 }
+
+#[convex_macro::test_runtime]
+pub(crate) async fn test_storage_reconciler_deletes_orphans(rt: TestRuntime) -> anyhow::Result<()> {
+    let app = Application::new_for_tests(&rt).await?;
+    let file_body = Box::pin(stream::once(async {
+        Ok(bytes::Bytes::from(vec![55; 1024 + 1]))
+    }));
+    app.store_file(ComponentId::Root, None, None, None, file_body)
+        .await?;
+
+    // An object that was uploaded but never recorded, e.g. because the backend
+    // crashed before committing its `_storage` entry.
+    let mut upload = app.files_storage.start_upload().await?;
+    upload.write(vec![1; 16].into()).await?;
+    let orphan = upload.complete().await?;
+
+    let mut reconciler = StorageReconciler::new(
+        rt.clone(),
+        app.database.clone(),
+        app.files_storage.clone(),
+        app.exports_storage.clone(),
+        app.snapshot_imports_storage.clone(),
+        app.search_storage.clone(),
+    )
+    .with_grace_period(Duration::ZERO);
+
+    // The first pass only notes the orphan.
+    let reports = reconciler.reconcile().await?;
+    let report = &reports[&StorageUseCase::Files];
+    assert_eq!(report.objects_listed, 2);
+    assert_eq!(report.objects_suspected, 1);
+    assert_eq!(report.objects_deleted, 0);
+    assert!(app.files_storage.get(&orphan).await?.is_some());
+
+    let reports = reconciler.reconcile().await?;
+    let report = &reports[&StorageUseCase::Files];
+    assert_eq!(report.objects_deleted, 1);
+    assert_eq!(report.bytes_deleted, 16);
+    assert!(app.files_storage.get(&orphan).await?.is_none());
+
+    // The stored file is untouched.
+    let remaining: Vec<_> = app.files_storage.list_objects().try_collect().await?;
+    assert_eq!(remaining.len(), 1);
+    assert_eq!(remaining[0].size, 1024 + 1);
+    Ok(())
+}
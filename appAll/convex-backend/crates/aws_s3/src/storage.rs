@@ -45,7 +45,10 @@ use futures::{
         BoxFuture,
         Either,
     },
-    stream,
+    stream::{
+        self,
+        BoxStream,
+    },
     Future,
     FutureExt,
     Stream,
@@ -66,6 +69,7 @@ use storage::{
     StorageCacheKey,
     StorageGetStream,
     StorageUseCase,
+    StoredObject,
     Upload,
     UploadId,
     MAXIMUM_PARALLEL_UPLOADS,
@@ -327,6 +331,28 @@ impl<RT: Runtime> Storage for S3Storage<RT> {
         s3_upload.complete().await
     }
 
+    async fn abort_client_driven_upload(
+        &self,
+        token: ClientDrivenUploadToken,
+    ) -> anyhow::Result<()> {
+        let ClientDrivenUpload {
+            object_key,
+            upload_id,
+        } = token.try_into()?;
+        let s3_key = S3Key(self.key_prefix.clone() + &object_key);
+        let s3_upload = Box::new(S3Upload::new_client_driven(
+            self.client.clone(),
+            self.bucket.clone(),
+            upload_id.to_string().into(),
+            object_key,
+            s3_key,
+            self.runtime.clone(),
+            vec![],
+            1,
+        )?);
+        s3_upload.abort().await
+    }
+
     async fn signed_url(&self, key: ObjectKey, expires_in: Duration) -> anyhow::Result<Uri> {
         let timer = sign_url_timer();
         let s3_key = S3Key(self.key_prefix.clone() + &key);
@@ -483,6 +509,46 @@ impl<RT: Runtime> Storage for S3Storage<RT> {
             .context(format!("Failed to delete object {key:?}"))?;
         Ok(())
     }
+
+    fn list_objects(&self) -> BoxStream<'static, anyhow::Result<StoredObject>> {
+        let pages = self
+            .client
+            .list_objects_v2()
+            .bucket(self.bucket.clone())
+            .prefix(self.key_prefix.clone())
+            .into_paginator()
+            .send();
+        let key_prefix = self.key_prefix.clone();
+        stream::unfold(pages, |mut pages| async move {
+            let page = pages.next().await?;
+            Some((page, pages))
+        })
+        .map(move |page| {
+            let page = page.context("Failed to list objects")?;
+            let objects = page
+                .contents
+                .unwrap_or_default()
+                .into_iter()
+                .map(|object| {
+                    let s3_key = object.key.context("Listed object is missing a key")?;
+                    let key = s3_key
+                        .strip_prefix(&key_prefix)
+                        .with_context(|| format!("Listed object {s3_key} is outside prefix"))?;
+                    Ok(StoredObject {
+                        key: key.to_string().try_into()?,
+                        size: object.size.unwrap_or_default() as u64,
+                        last_modified: object
+                            .last_modified
+                            .context("Listed object is missing its modification time")?
+                            .try_into()?,
+                    })
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            anyhow::Ok(stream::iter(objects.into_iter().map(Ok)))
+        })
+        .try_flatten()
+        .boxed()
+    }
 }
 
 struct S3Key(String);
@@ -5,6 +5,7 @@ use common::types::PersistenceVersion;
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum DbDriverTag {
     Sqlite,
+    RocksDb,
     Postgres(PersistenceVersion),
     PostgresAwsIam(PersistenceVersion),
     MySql(PersistenceVersion),
@@ -15,6 +16,7 @@ impl clap::ValueEnum for DbDriverTag {
     fn value_variants<'a>() -> &'a [Self] {
         &[
             DbDriverTag::Sqlite,
+            DbDriverTag::RocksDb,
             DbDriverTag::MySql(PersistenceVersion::V5),
             DbDriverTag::MySqlAwsIam(PersistenceVersion::V5),
             DbDriverTag::Postgres(PersistenceVersion::V5),
@@ -35,12 +37,14 @@ impl DbDriverTag {
             | Self::MySql(version)
             | Self::MySqlAwsIam(version) => Ok(*version),
             Self::Sqlite => anyhow::bail!("sqlite has no persistence version"),
+            Self::RocksDb => anyhow::bail!("rocksdb has no persistence version"),
         }
     }
 
     pub fn as_str(&self) -> &'static str {
         match self {
             DbDriverTag::Sqlite => "sqlite",
+            DbDriverTag::RocksDb => "rocksdb",
             DbDriverTag::Postgres(PersistenceVersion::V5) => "postgres-v5",
             DbDriverTag::PostgresAwsIam(PersistenceVersion::V5) => "postgres-v5-aws-iam",
             DbDriverTag::MySql(PersistenceVersion::V5) => "mysql-v5",
@@ -55,6 +59,7 @@ impl FromStr for DbDriverTag {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "sqlite" => Ok(Self::Sqlite),
+            "rocksdb" => Ok(Self::RocksDb),
             "postgres-v5" => Ok(DbDriverTag::Postgres(PersistenceVersion::V5)),
             "postgres-v5-aws-iam" => Ok(DbDriverTag::PostgresAwsIam(PersistenceVersion::V5)),
             "mysql-v5" => Ok(DbDriverTag::MySql(PersistenceVersion::V5)),
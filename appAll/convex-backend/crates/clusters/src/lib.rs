@@ -73,6 +73,7 @@ pub fn persistence_args_from_cluster_url(
                 .append_pair("verify_ca", "false");
         },
         DbDriverTag::Sqlite => anyhow::bail!("no url for sqlite"),
+        DbDriverTag::RocksDb => anyhow::bail!("no url for rocksdb"),
     };
     Ok(PersistenceArgs {
         url: cluster_url,
@@ -6,6 +6,7 @@ use std::{
     },
     fmt,
     sync::LazyLock,
+    time::Duration,
 };
 
 use errors::{
@@ -658,6 +659,19 @@ pub fn lease_lost_error() -> anyhow::Error {
     anyhow::anyhow!(LeaseLostError).context(ErrorMetadata::operational_internal_server_error())
 }
 
+/// How long a client should wait before retrying a rate limited request.
+/// HTTP responses for errors carrying this send it as `Retry-After`.
+#[derive(thiserror::Error, Debug, Clone, Copy)]
+#[error("Retry after {0:?}")]
+pub struct RetryAfter(pub Duration);
+pub fn rate_limited_error(
+    short_msg: &'static str,
+    msg: String,
+    retry_after: Duration,
+) -> anyhow::Error {
+    anyhow::anyhow!(RetryAfter(retry_after)).context(ErrorMetadata::rate_limited(short_msg, msg))
+}
+
 pub const AUTH_ERROR: &str = "AuthError";
 pub const TIMEOUT_ERROR_MESSAGE: &str = "Your request timed out.";
 
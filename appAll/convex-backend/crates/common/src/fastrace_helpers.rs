@@ -21,6 +21,13 @@ use serde::Deserialize;
 
 use crate::knobs::REQUEST_TRACE_SAMPLE_CONFIG;
 
+/// Property recording a span's OpenTelemetry kind: [`SPAN_KIND_SERVER`] on the
+/// root of a request this server is handling, or [`SPAN_KIND_CLIENT`] around a
+/// request it's making. Spans without it are internal.
+pub const SPAN_KIND_PROPERTY: &str = "span.kind";
+pub const SPAN_KIND_SERVER: &str = "server";
+pub const SPAN_KIND_CLIENT: &str = "client";
+
 static SAMPLING_CONFIG_FROM_LOADER: LazyLock<Mutex<Option<SamplingConfig>>> =
     LazyLock::new(|| Mutex::new(None));
 
@@ -3,7 +3,10 @@ use std::{
     convert::Infallible,
     fmt,
     future::Future,
-    net::SocketAddr,
+    net::{
+        IpAddr,
+        SocketAddr,
+    },
     ops::Deref,
     pin::Pin,
     str::{
@@ -21,6 +24,8 @@ use std::{
 };
 
 use ::metrics::{
+    encode_metrics,
+    ExpositionFormat,
     CONVEX_METRICS_REGISTRY,
     SERVER_VERSION_STR,
     SERVICE_NAME,
@@ -32,6 +37,7 @@ use axum::{
     error_handling::HandleErrorLayer,
     extract::{
         connect_info::IntoMakeServiceWithConnectInfo,
+        ConnectInfo,
         FromRequestParts,
         Host,
         State,
@@ -69,6 +75,7 @@ use http::{
         AUTHORIZATION,
         CONTENT_TYPE,
         REFERER,
+        RETRY_AFTER,
         USER_AGENT,
     },
     request::Parts,
@@ -79,10 +86,7 @@ use http::{
 };
 use http_body_util::BodyExt;
 use itertools::Itertools;
-use prometheus::{
-    PullingGauge,
-    TextEncoder,
-};
+use prometheus::PullingGauge;
 use regex::Regex;
 use sentry::integrations::tower as sentry_tower;
 use serde::{
@@ -106,8 +110,19 @@ use utoipa::ToSchema;
 
 use self::metrics::log_http_request;
 use crate::{
-    errors::report_error_sync,
-    knobs::HTTP_SERVER_TCP_BACKLOG,
+    errors::{
+        report_error_sync,
+        RetryAfter,
+    },
+    fastrace_helpers::{
+        SPAN_KIND_PROPERTY,
+        SPAN_KIND_SERVER,
+    },
+    knobs::{
+        HTTP_SERVER_TCP_BACKLOG,
+        HTTP_TRUST_X_FORWARDED_FOR,
+        HTTP_X_FORWARDED_FOR_TRUSTED_HOPS,
+    },
     metrics::log_client_version_unsupported,
     runtime::TaskManager,
     version::{
@@ -527,6 +542,7 @@ impl HttpError {
 pub struct HttpResponseError {
     trace: anyhow::Error,
     http_error: HttpError,
+    retry_after: Option<Duration>,
 }
 
 impl From<Infallible> for HttpResponseError {
@@ -546,7 +562,16 @@ impl IntoResponse for HttpResponseError {
         // This is the only place we capture errors to sentry because it is the exit
         // point of the HTTP layer
         report_error_sync(&mut self.trace);
-        self.http_error.into_response()
+        let mut response = self.http_error.into_response();
+        if let Some(retry_after) = self.retry_after {
+            // `Retry-After` is in whole seconds, so round up.
+            let retry_after_secs =
+                retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, retry_after_secs.into());
+        }
+        response
     }
 }
 
@@ -557,9 +582,13 @@ impl From<anyhow::Error> for HttpResponseError {
             error_code: err.short_msg().to_string().into(),
             msg: err.msg().to_string().into(),
         };
+        let retry_after = err
+            .downcast_ref::<RetryAfter>()
+            .map(|RetryAfter(retry_after)| *retry_after);
         Self {
             trace: err,
             http_error,
+            retry_after,
         }
     }
 }
@@ -827,7 +856,8 @@ pub async fn stats_middleware<RM: RouteMapper>(
 
     // Sampling isn't done here, and should be done upstream
     let root = match traceparent {
-        Some(span_ctx) => Span::root(route.to_owned(), span_ctx),
+        Some(span_ctx) => Span::root(route.to_owned(), span_ctx)
+            .with_property(|| (SPAN_KIND_PROPERTY, SPAN_KIND_SERVER)),
         None => Span::noop(),
     };
     let resp = next.run(req).in_span(root).await;
@@ -1023,6 +1053,60 @@ where
     }
 }
 
+/// The address of the client that made a request, if known.
+pub struct ExtractClientAddress(pub Option<IpAddr>);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ExtractClientAddress
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut axum::http::request::Parts,
+        _state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        Ok(Self(client_address(&parts.headers, &parts.extensions)))
+    }
+}
+
+/// The address of the client that made a request, taken from
+/// `X-Forwarded-For` if [`HTTP_TRUST_X_FORWARDED_FOR`] is set.
+pub fn client_address(headers: &HeaderMap, extensions: &http::Extensions) -> Option<IpAddr> {
+    if *HTTP_TRUST_X_FORWARDED_FOR {
+        let forwarded_for = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .map(|h| h.to_str().ok())
+            .collect::<Option<Vec<_>>>()
+            .and_then(|h| forwarded_client_address(&h, *HTTP_X_FORWARDED_FOR_TRUSTED_HOPS));
+        if forwarded_for.is_some() {
+            return forwarded_for;
+        }
+    }
+    extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(address)| address.ip())
+}
+
+/// The entry `trusted_hops` from the right of the `X-Forwarded-For` header
+/// values, which is the address the outermost trusted proxy got the request
+/// from. If the request passed through fewer proxies, the leftmost entry was
+/// still added by one of them.
+fn forwarded_client_address(values: &[&str], trusted_hops: usize) -> Option<IpAddr> {
+    if trusted_hops == 0 {
+        return None;
+    }
+    let entries: Vec<_> = values
+        .iter()
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .collect();
+    let index = entries.len().saturating_sub(trusted_hops);
+    entries.get(index)?.parse().ok()
+}
+
 #[allow(clippy::declare_interior_mutable_const)]
 pub const CONVEX_REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("convex-request-id");
 
@@ -1218,17 +1302,16 @@ pub fn cli_cors() -> CorsLayer {
         .max_age(Duration::from_secs(86400))
 }
 
-/// Collects metrics and returns them in the Prometheus exposition format.
+/// Collects metrics and returns them in the Prometheus exposition format, or
+/// in the OpenMetrics format with exemplars if the scraper asks for it.
 /// Returns an empty response if no metrics have been recorded yet.
 /// Note that registered metrics will not show here until recorded at least
 /// once.
-pub async fn metrics() -> Result<impl IntoResponse, HttpResponseError> {
-    let encoder = TextEncoder::new();
-    let metrics = CONVEX_METRICS_REGISTRY.gather();
-    let output = encoder
-        .encode_to_string(&metrics)
-        .map_err(anyhow::Error::from)?;
-    Ok(output)
+pub async fn metrics(headers: HeaderMap) -> Result<impl IntoResponse, HttpResponseError> {
+    let accept = headers.get(ACCEPT).and_then(|accept| accept.to_str().ok());
+    let format = ExpositionFormat::from_accept_header(accept);
+    let output = encode_metrics(format)?;
+    Ok(([(CONTENT_TYPE, format.content_type())], output))
 }
 
 /// Converts a [`HeaderMap`] into an iterator of key-value tuples, handling
@@ -1264,9 +1347,29 @@ mod tests {
     };
     use http::StatusCode;
 
-    use super::HttpResponseError;
+    use super::{
+        forwarded_client_address,
+        HttpResponseError,
+    };
     use crate::http::HttpError;
 
+    #[test]
+    fn test_forwarded_client_address() {
+        let address = |values: &[&str], hops| {
+            forwarded_client_address(values, hops).map(|address| address.to_string())
+        };
+        // The client can send its own header, so only the entries appended by
+        // the trusted proxies count.
+        let values = ["1.1.1.1, 2.2.2.2", "3.3.3.3"];
+        assert_eq!(address(&values, 1).as_deref(), Some("3.3.3.3"));
+        assert_eq!(address(&values, 2).as_deref(), Some("2.2.2.2"));
+        assert_eq!(address(&values, 3).as_deref(), Some("1.1.1.1"));
+        assert_eq!(address(&values, 4).as_deref(), Some("1.1.1.1"));
+        assert_eq!(address(&values, 0), None);
+        assert_eq!(address(&["not-an-address"], 1), None);
+        assert_eq!(address(&["::1"], 1).as_deref(), Some("::1"));
+    }
+
     #[tokio::test]
     async fn test_http_response_error_internal_server_error() -> anyhow::Result<()> {
         let err_text = "some random error";
@@ -91,6 +91,12 @@ pub static UDF_METRICS_MAX_DURATION: LazyLock<Duration> = LazyLock::new(|| {
 pub static UDF_METRICS_SIGNIFICANT_FIGURES: LazyLock<u8> =
     LazyLock::new(|| env_config("UDF_METRICS_SIGNIFICANT_FIGURES", 2));
 
+/// How many functions get their own series in the per-function Prometheus
+/// metrics. Calls to functions beyond this are counted under `_other`, so
+/// deployments with many functions don't overwhelm the metrics backend.
+pub static FUNCTION_METRICS_MAX_FUNCTIONS: LazyLock<usize> =
+    LazyLock::new(|| env_config("FUNCTION_METRICS_MAX_FUNCTIONS", 500));
+
 /// How often to flush function activity reports to analytics (in seconds).
 pub static UDF_ANALYTICS_POLL_TIME: LazyLock<u64> =
     LazyLock::new(|| env_config("UDF_ANALYTICS_POLL_TIME", 60));
@@ -781,6 +787,56 @@ pub static APPLICATION_MAX_CONCURRENT_HTTP_ACTIONS: LazyLock<usize> = LazyLock::
     )
 });
 
+/// Whether to take a request's client address from its `X-Forwarded-For`
+/// header, see [`HTTP_X_FORWARDED_FOR_TRUSTED_HOPS`]. Only enable this behind
+/// a proxy that appends to the header, since clients can otherwise pick their
+/// own address and get around per-IP rate limits.
+pub static HTTP_TRUST_X_FORWARDED_FOR: LazyLock<bool> =
+    LazyLock::new(|| env_config("HTTP_TRUST_X_FORWARDED_FOR", false));
+
+/// Number of proxies in front of the backend that append to
+/// `X-Forwarded-For`. Each proxy appends the address it received the request
+/// from, so the client address is this many entries from the right. Entries
+/// further left were sent by the client and can't be trusted.
+pub static HTTP_X_FORWARDED_FOR_TRUSTED_HOPS: LazyLock<usize> =
+    LazyLock::new(|| env_config("HTTP_X_FORWARDED_FOR_TRUSTED_HOPS", 1));
+
+/// Once a rate-limited HTTP route is tracking this many clients, forget the
+/// ones that are no longer limited.
+pub static HTTP_ACTION_RATE_LIMIT_MAX_TRACKED_CLIENTS: LazyLock<usize> =
+    LazyLock::new(|| env_config("HTTP_ACTION_RATE_LIMIT_MAX_TRACKED_CLIENTS", 10_000));
+
+/// Calls per second each public function can receive from clients across all
+/// callers, with bursts of up to a second's worth. 0 means no limit.
+pub static FUNCTION_RATE_LIMIT_PER_FUNCTION: LazyLock<u32> =
+    LazyLock::new(|| env_config("FUNCTION_RATE_LIMIT_PER_FUNCTION", 0));
+
+/// Public function calls per second each authenticated user can make, with
+/// bursts of up to a second's worth. 0 means no limit.
+pub static FUNCTION_RATE_LIMIT_PER_IDENTITY: LazyLock<u32> =
+    LazyLock::new(|| env_config("FUNCTION_RATE_LIMIT_PER_IDENTITY", 0));
+
+/// Requests per second each client address can make to the public function
+/// API and sync protocol, with bursts of up to a second's worth. 0 means no
+/// limit.
+pub static FUNCTION_RATE_LIMIT_PER_IP: LazyLock<u32> =
+    LazyLock::new(|| env_config("FUNCTION_RATE_LIMIT_PER_IP", 0));
+
+/// Mutations and actions each public function can be running at once for
+/// clients. 0 means no limit.
+pub static FUNCTION_MAX_CONCURRENT_PER_FUNCTION: LazyLock<usize> =
+    LazyLock::new(|| env_config("FUNCTION_MAX_CONCURRENT_PER_FUNCTION", 0));
+
+/// Mutations and actions each authenticated user can be running at once. 0
+/// means no limit.
+pub static FUNCTION_MAX_CONCURRENT_PER_IDENTITY: LazyLock<usize> =
+    LazyLock::new(|| env_config("FUNCTION_MAX_CONCURRENT_PER_IDENTITY", 0));
+
+/// Once a function rate limiter is tracking this many keys, forget the ones
+/// that are no longer limited.
+pub static FUNCTION_RATE_LIMIT_MAX_TRACKED_KEYS: LazyLock<usize> =
+    LazyLock::new(|| env_config("FUNCTION_RATE_LIMIT_MAX_TRACKED_KEYS", 100_000));
+
 /// The maximum number of concurrent package uploads during
 /// `/api/deploy2/start_push`.
 pub static APPLICATION_MAX_CONCURRENT_UPLOADS: LazyLock<usize> =
@@ -1006,6 +1062,27 @@ pub static AWS_LAMBDA_CLIENT_MAX_CONCURRENT_REQUESTS: LazyLock<usize> =
 pub static NODE_ANALYZE_MAX_RETRIES: LazyLock<usize> =
     LazyLock::new(|| env_config("NODE_ANALYZE_MAX_RETRIES", 3));
 
+/// The maximum number of idle Node.js worker processes the local node executor
+/// keeps warm between "use node" action invocations. Busy workers aren't
+/// limited here: APPLICATION_MAX_CONCURRENT_NODE_ACTIONS bounds those.
+pub static NODE_WORKER_POOL_MAX_IDLE: LazyLock<usize> =
+    LazyLock::new(|| env_config("NODE_WORKER_POOL_MAX_IDLE", 4));
+
+/// The number of requests a local Node.js worker process serves before it is
+/// replaced by a fresh one.
+pub static NODE_WORKER_MAX_REQUESTS: LazyLock<usize> =
+    LazyLock::new(|| env_config("NODE_WORKER_MAX_REQUESTS", 100));
+
+/// A local Node.js worker process whose resident memory is above this after a
+/// request is replaced by a fresh one.
+pub static NODE_WORKER_MAX_RSS_MB: LazyLock<u64> =
+    LazyLock::new(|| env_config("NODE_WORKER_MAX_RSS_MB", 1024));
+
+/// Number of pushed configurations kept for rollbacks. Older ones are
+/// deleted when a new push is recorded.
+pub static PUSH_HISTORY_MAX_ENTRIES: LazyLock<usize> =
+    LazyLock::new(|| env_config("PUSH_HISTORY_MAX_ENTRIES", 20));
+
 /// The number of seconds backend should wait for requests to drain before
 /// shutting down after SIGINT.
 pub static BACKEND_REQUEST_DRAIN_TIMEOUT: LazyLock<Duration> =
@@ -1271,3 +1348,95 @@ pub static EXPORT_MAX_INFLIGHT_PREFETCH_BYTES: LazyLock<usize> = LazyLock::new(|
     )
     .clamp(1, u32::MAX as usize)
 });
+
+/// How long a resumable file storage upload can stay unfinished before it is
+/// abandoned. Clients must finish the upload within this window.
+pub static RESUMABLE_UPLOAD_MAX_AGE: LazyLock<Duration> = LazyLock::new(|| {
+    Duration::from_secs(env_config("RESUMABLE_UPLOAD_MAX_AGE_SECONDS", 24 * 60 * 60))
+});
+
+/// How long a request may hold a resumable upload while writing a part or
+/// finishing it. Other requests for the upload are rejected until then, so
+/// this bounds how long a client waits after a request dies mid-part.
+pub static RESUMABLE_UPLOAD_CLAIM_TIMEOUT: LazyLock<Duration> = LazyLock::new(|| {
+    Duration::from_secs(env_config("RESUMABLE_UPLOAD_CLAIM_TIMEOUT_SECONDS", 5 * 60))
+});
+
+/// Enables the worker that deletes objects in storage that nothing references
+/// anymore.
+pub static ENABLE_STORAGE_RECONCILER: LazyLock<bool> =
+    LazyLock::new(|| env_config("ENABLE_STORAGE_RECONCILER", true));
+
+/// How often to look for objects in storage that nothing references anymore.
+pub static STORAGE_RECONCILE_FREQUENCY: LazyLock<Duration> = LazyLock::new(|| {
+    Duration::from_secs(env_config(
+        "STORAGE_RECONCILE_FREQUENCY_SECONDS",
+        6 * 60 * 60,
+    ))
+});
+
+/// Unreferenced objects younger than this are never deleted by the storage
+/// reconciler, since they may belong to an upload that hasn't been recorded
+/// yet. This must be longer than the slowest upload, including resumable
+/// uploads (see `RESUMABLE_UPLOAD_MAX_AGE`).
+pub static STORAGE_ORPHAN_GRACE_PERIOD: LazyLock<Duration> = LazyLock::new(|| {
+    Duration::from_secs(env_config(
+        "STORAGE_ORPHAN_GRACE_PERIOD_SECONDS",
+        2 * 24 * 60 * 60,
+    ))
+});
+
+/// Largest stored file the image transform endpoint will decode. Transforms are
+/// done in memory, so this bounds the memory used by a single request.
+pub static IMAGE_TRANSFORM_MAX_SOURCE_BYTES: LazyLock<usize> =
+    LazyLock::new(|| env_config("IMAGE_TRANSFORM_MAX_SOURCE_BYTES", 32 << 20));
+
+/// Most distinct transformed variants kept for a single stored image. The
+/// image endpoint is unauthenticated, so this bounds how much storage requests
+/// for arbitrary sizes can create.
+pub static IMAGE_TRANSFORM_MAX_VARIANTS_PER_FILE: LazyLock<usize> =
+    LazyLock::new(|| env_config("IMAGE_TRANSFORM_MAX_VARIANTS_PER_FILE", 16));
+
+/// Store new document revisions zstd-compressed in persistence. Compressed and
+/// uncompressed revisions can be read either way, so this can be turned on and
+/// off at any time.
+pub static PERSISTENCE_COMPRESS_DOCUMENTS: LazyLock<bool> =
+    LazyLock::new(|| env_config("PERSISTENCE_COMPRESS_DOCUMENTS", false));
+
+/// zstd compression level for documents in persistence.
+pub static PERSISTENCE_DOCUMENT_COMPRESSION_LEVEL: LazyLock<i32> =
+    LazyLock::new(|| env_config("PERSISTENCE_DOCUMENT_COMPRESSION_LEVEL", 3));
+
+/// Documents smaller than this are stored uncompressed, since the header and
+/// frame overhead would outweigh the savings.
+pub static PERSISTENCE_DOCUMENT_COMPRESSION_MIN_BYTES: LazyLock<usize> =
+    LazyLock::new(|| env_config("PERSISTENCE_DOCUMENT_COMPRESSION_MIN_BYTES", 64));
+
+/// Number of recent document revisions to train the deployment's compression
+/// dictionary on. The dictionary is trained once there are at least 1000
+/// revisions, and documents are compressed without one until then.
+pub static PERSISTENCE_DOCUMENT_COMPRESSION_TRAINING_SAMPLES: LazyLock<usize> =
+    LazyLock::new(|| env_config("PERSISTENCE_DOCUMENT_COMPRESSION_TRAINING_SAMPLES", 10000));
+
+/// How often a deployment without a compression dictionary checks whether it
+/// has enough document revisions to train one.
+pub static PERSISTENCE_DOCUMENT_COMPRESSION_TRAINING_INTERVAL: LazyLock<Duration> =
+    LazyLock::new(|| {
+        Duration::from_secs(env_config(
+            "PERSISTENCE_DOCUMENT_COMPRESSION_TRAINING_INTERVAL_SECONDS",
+            600,
+        ))
+    });
+
+/// How often the self-hosted backend writes its hourly usage rollups to disk.
+/// Usage recorded since the last write is lost if the process crashes.
+pub static LOCAL_USAGE_FLUSH_INTERVAL: LazyLock<Duration> =
+    LazyLock::new(|| Duration::from_secs(env_config("LOCAL_USAGE_FLUSH_INTERVAL_SECONDS", 60)));
+
+/// How long the self-hosted backend keeps hourly usage rollups.
+pub static LOCAL_USAGE_RETENTION: LazyLock<Duration> = LazyLock::new(|| {
+    Duration::from_secs(env_config(
+        "LOCAL_USAGE_RETENTION_SECONDS",
+        400 * 24 * 60 * 60,
+    ))
+});
@@ -55,6 +55,7 @@ pub mod numeric;
 pub mod paths;
 pub mod pause;
 pub mod persistence;
+pub mod persistence_compression;
 pub mod persistence_helpers;
 pub mod pii;
 pub mod pool_stats;
@@ -37,6 +37,7 @@ use crate::{
     interval::Interval,
     knobs::DEFAULT_DOCUMENTS_PAGE_SIZE,
     metrics::static_repeatable_ts_timer,
+    persistence_compression::DocumentCompressor,
     persistence_helpers::RevisionPair,
     query::Order,
     runtime::Runtime,
@@ -123,6 +124,10 @@ pub enum PersistenceGlobalKey {
     IndexByIdIndex,
     /// Internal id of _index table, for bootstrapping.
     IndexTabletId,
+
+    /// zstd dictionaries for compressed documents, see
+    /// `persistence_compression`.
+    DocumentCompressionDictionaries,
 }
 
 impl From<PersistenceGlobalKey> for String {
@@ -145,6 +150,9 @@ impl From<PersistenceGlobalKey> for String {
             // NB: For compatibility, these are referred to as "table_id"s, not "tablet_id"s.
             PersistenceGlobalKey::TablesTabletId => "tables_table_id".to_string(),
             PersistenceGlobalKey::IndexTabletId => "index_table_id".to_string(),
+            PersistenceGlobalKey::DocumentCompressionDictionaries => {
+                "document_compression_dictionaries".to_string()
+            },
         }
     }
 }
@@ -163,6 +171,7 @@ impl FromStr for PersistenceGlobalKey {
             "tables_table_id" => Ok(Self::TablesTabletId),
             "index_by_id" => Ok(Self::IndexByIdIndex),
             "index_table_id" => Ok(Self::IndexTabletId),
+            "document_compression_dictionaries" => Ok(Self::DocumentCompressionDictionaries),
             _ => anyhow::bail!("unrecognized persistence global key"),
         }
     }
@@ -218,6 +227,12 @@ pub trait Persistence: Sync + Send + 'static {
     async fn shutdown(&self) -> anyhow::Result<()> {
         Ok(())
     }
+
+    /// Compression applied to stored documents, for implementations that
+    /// support it.
+    fn document_compressor(&self) -> Option<Arc<DocumentCompressor>> {
+        None
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -804,6 +819,10 @@ pub struct PersistenceTableSize {
     pub data_bytes: u64,
     pub index_bytes: u64,
     pub row_count: u64,
+    /// Number of rows stored compressed, see `persistence_compression`. Only
+    /// set for the documents table, and only where it can be estimated
+    /// without scanning the table.
+    pub compressed_row_count: Option<u64>,
 }
 
 #[cfg(test)]
@@ -0,0 +1,464 @@
+//! Opt-in compression of the document payloads stored in persistence.
+//!
+//! Persistence implementations store each document revision as JSON. With
+//! `PERSISTENCE_COMPRESS_DOCUMENTS` set, new revisions are stored as a zstd
+//! frame behind a small header instead:
+//!
+//! | byte 0 | byte 1 | bytes 2..6                | rest       |
+//! |--------|--------|---------------------------|------------|
+//! | `0xC0` | format | dictionary id, big endian | zstd frame |
+//!
+//! `0xC0` can't appear in UTF-8 text, so a JSON payload never starts with it.
+//! That keeps revisions written before compression was turned on (or after it
+//! was turned off again) readable as they are, and both kinds of rows can live
+//! in the same table.
+//!
+//! Dictionary id 0 means the frame doesn't use a dictionary. Other ids refer to
+//! dictionaries trained on the deployment's own documents, which are kept in
+//! the `DocumentCompressionDictionaries` persistence global. Dictionaries are
+//! never removed from it, since old revisions keep referring to them.
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    io::{
+        Read,
+        Write,
+    },
+    sync::Arc,
+};
+
+use anyhow::Context;
+use futures::{
+    StreamExt,
+    TryStreamExt,
+};
+use parking_lot::RwLock;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use serde_json::Value as JsonValue;
+use zstd::{
+    dict::{
+        DecoderDictionary,
+        EncoderDictionary,
+    },
+    stream::{
+        read::Decoder,
+        write::Encoder,
+    },
+};
+
+use crate::{
+    knobs::{
+        PERSISTENCE_COMPRESS_DOCUMENTS,
+        PERSISTENCE_DOCUMENT_COMPRESSION_LEVEL,
+        PERSISTENCE_DOCUMENT_COMPRESSION_MIN_BYTES,
+        PERSISTENCE_DOCUMENT_COMPRESSION_TRAINING_SAMPLES,
+    },
+    persistence::{
+        NoopRetentionValidator,
+        Persistence,
+        PersistenceGlobalKey,
+        PersistenceReader,
+        TimestampRange,
+    },
+    query::Order,
+};
+
+const COMPRESSED_MARKER: u8 = 0xC0;
+/// A single zstd frame, optionally compressed with a dictionary.
+const FORMAT_ZSTD: u8 = 1;
+const HEADER_LEN: usize = 6;
+const NO_DICTIONARY: u32 = 0;
+
+/// zstd's recommended dictionary size.
+const DICTIONARY_MAX_BYTES: usize = 112 * 1024;
+/// With fewer samples than this the dictionary isn't worth having. A new
+/// deployment compresses without one until it has enough documents, see
+/// `DocumentCompressor::train_dictionary`.
+const MIN_TRAINING_SAMPLES: usize = 1000;
+
+struct Dictionary {
+    encoder: EncoderDictionary<'static>,
+    decoder: DecoderDictionary<'static>,
+}
+
+impl Dictionary {
+    fn new(bytes: &[u8], level: i32) -> Self {
+        Self {
+            encoder: EncoderDictionary::copy(bytes, level),
+            decoder: DecoderDictionary::copy(bytes),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredDictionary {
+    id: u32,
+    /// Hex encoded, since persistence globals are JSON.
+    dictionary: String,
+}
+
+/// Encodes document JSON for storage and decodes it again. Persistence
+/// implementations share one of these between their writer and readers.
+pub struct DocumentCompressor {
+    compress: bool,
+    level: i32,
+    dictionaries: RwLock<BTreeMap<u32, Arc<Dictionary>>>,
+}
+
+impl DocumentCompressor {
+    pub fn new() -> Self {
+        Self::with_options(
+            *PERSISTENCE_COMPRESS_DOCUMENTS,
+            *PERSISTENCE_DOCUMENT_COMPRESSION_LEVEL,
+        )
+    }
+
+    pub fn with_options(compress: bool, level: i32) -> Self {
+        Self {
+            compress,
+            level,
+            dictionaries: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    /// Returns the bytes to store for a document's JSON. This is the JSON
+    /// itself unless compression is enabled and makes it smaller.
+    pub fn encode(&self, json: Vec<u8>) -> anyhow::Result<Vec<u8>> {
+        if !self.compress || json.len() < *PERSISTENCE_DOCUMENT_COMPRESSION_MIN_BYTES {
+            return Ok(json);
+        }
+        let dictionary = self
+            .dictionaries
+            .read()
+            .last_key_value()
+            .map(|(id, dictionary)| (*id, dictionary.clone()));
+        let mut header = Vec::with_capacity(HEADER_LEN + json.len() / 2);
+        header.push(COMPRESSED_MARKER);
+        header.push(FORMAT_ZSTD);
+        let dictionary_id = dictionary.as_ref().map_or(NO_DICTIONARY, |(id, _)| *id);
+        header.extend_from_slice(&dictionary_id.to_be_bytes());
+        let mut encoder = match &dictionary {
+            Some((_, dictionary)) => {
+                Encoder::with_prepared_dictionary(header, &dictionary.encoder)?
+            },
+            None => Encoder::new(header, self.level)?,
+        };
+        encoder.write_all(&json)?;
+        let compressed = encoder.finish()?;
+        if compressed.len() >= json.len() {
+            return Ok(json);
+        }
+        Ok(compressed)
+    }
+
+    /// Returns the JSON for stored bytes, whether or not they were compressed.
+    pub fn decode<'a>(&self, bytes: &'a [u8]) -> anyhow::Result<Cow<'a, [u8]>> {
+        let Some((dictionary_id, frame)) = parse_header(bytes)? else {
+            return Ok(Cow::Borrowed(bytes));
+        };
+        let mut json = Vec::new();
+        if dictionary_id == NO_DICTIONARY {
+            Decoder::with_buffer(frame)?.read_to_end(&mut json)?;
+        } else {
+            let dictionary = self
+                .dictionaries
+                .read()
+                .get(&dictionary_id)
+                .cloned()
+                .with_context(|| {
+                    format!("Document compressed with unknown dictionary {dictionary_id}")
+                })?;
+            Decoder::with_prepared_dictionary(frame, &dictionary.decoder)?
+                .read_to_end(&mut json)?;
+        }
+        Ok(Cow::Owned(json))
+    }
+
+    /// Same as `decode`, but first reloads the dictionaries from `reader` if
+    /// `bytes` were compressed with one that was trained after they were
+    /// loaded, e.g. by another process.
+    pub async fn decode_with_reader<'a>(
+        &self,
+        reader: &dyn PersistenceReader,
+        bytes: &'a [u8],
+    ) -> anyhow::Result<Cow<'a, [u8]>> {
+        let missing_dictionary = match parse_header(bytes)? {
+            Some((dictionary_id, _)) if dictionary_id != NO_DICTIONARY => {
+                !self.dictionaries.read().contains_key(&dictionary_id)
+            },
+            _ => false,
+        };
+        if missing_dictionary {
+            self.load_dictionaries(reader).await?;
+        }
+        self.decode(bytes)
+    }
+
+    /// Whether stored bytes are compressed, for implementations that store
+    /// JSON and compressed payloads differently.
+    pub fn is_compressed(bytes: &[u8]) -> bool {
+        bytes.first() == Some(&COMPRESSED_MARKER)
+    }
+
+    pub async fn load_dictionaries(&self, reader: &dyn PersistenceReader) -> anyhow::Result<()> {
+        let value = reader
+            .get_persistence_global(PersistenceGlobalKey::DocumentCompressionDictionaries)
+            .await?;
+        self.set_dictionaries(value)
+    }
+
+    /// Installs the dictionaries in a `DocumentCompressionDictionaries`
+    /// persistence global.
+    pub fn set_dictionaries(&self, value: Option<JsonValue>) -> anyhow::Result<()> {
+        let Some(value) = value else {
+            return Ok(());
+        };
+        let stored: Vec<StoredDictionary> = serde_json::from_value(value)?;
+        let mut dictionaries = self.dictionaries.write();
+        for StoredDictionary { id, dictionary } in stored {
+            if dictionaries.contains_key(&id) {
+                continue;
+            }
+            let bytes = hex::decode(dictionary)?;
+            dictionaries.insert(id, Arc::new(Dictionary::new(&bytes, self.level)));
+        }
+        Ok(())
+    }
+
+    /// Loads the deployment's dictionaries, and if compression is enabled but
+    /// there's no dictionary yet, tries to train one on the latest document
+    /// revisions. Run this once the persistence has been opened for writing.
+    pub async fn initialize(&self, persistence: &dyn Persistence) -> anyhow::Result<()> {
+        self.load_dictionaries(persistence.reader().as_ref())
+            .await?;
+        if self.needs_dictionary() && !self.train_dictionary(persistence).await? {
+            tracing::warn!(
+                "Compressing documents without a dictionary until there are \
+                 {MIN_TRAINING_SAMPLES} to train one on"
+            );
+        }
+        Ok(())
+    }
+
+    /// Whether compression is enabled but there's no dictionary to compress
+    /// with yet.
+    pub fn needs_dictionary(&self) -> bool {
+        self.compress && self.dictionaries.read().is_empty()
+    }
+
+    /// Trains a dictionary on the latest document revisions and stores it, for
+    /// deployments that didn't have enough documents when they started.
+    /// Returns false if there still are fewer than `MIN_TRAINING_SAMPLES`
+    /// revisions to train on.
+    pub async fn train_dictionary(&self, persistence: &dyn Persistence) -> anyhow::Result<bool> {
+        if !self.needs_dictionary() {
+            return Ok(true);
+        }
+        let samples = sample_documents(
+            persistence.reader().as_ref(),
+            *PERSISTENCE_DOCUMENT_COMPRESSION_TRAINING_SAMPLES,
+        )
+        .await?;
+        if samples.len() < MIN_TRAINING_SAMPLES {
+            return Ok(false);
+        }
+        let dictionary = zstd::dict::from_samples(&samples, DICTIONARY_MAX_BYTES)?;
+        let id = NO_DICTIONARY + 1;
+        let stored = vec![StoredDictionary {
+            id,
+            dictionary: hex::encode(&dictionary),
+        }];
+        persistence
+            .write_persistence_global(
+                PersistenceGlobalKey::DocumentCompressionDictionaries,
+                serde_json::to_value(stored)?,
+            )
+            .await?;
+        self.dictionaries
+            .write()
+            .insert(id, Arc::new(Dictionary::new(&dictionary, self.level)));
+        tracing::info!(
+            "Trained a {} byte document compression dictionary on {} documents",
+            dictionary.len(),
+            samples.len()
+        );
+        Ok(true)
+    }
+}
+
+impl Default for DocumentCompressor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn parse_header(bytes: &[u8]) -> anyhow::Result<Option<(u32, &[u8])>> {
+    if !DocumentCompressor::is_compressed(bytes) {
+        return Ok(None);
+    }
+    anyhow::ensure!(bytes.len() >= HEADER_LEN, "Truncated compressed document");
+    anyhow::ensure!(
+        bytes[1] == FORMAT_ZSTD,
+        "Unknown document compression format {}",
+        bytes[1]
+    );
+    let dictionary_id = u32::from_be_bytes(bytes[2..HEADER_LEN].try_into()?);
+    Ok(Some((dictionary_id, &bytes[HEADER_LEN..])))
+}
+
+/// Returns the JSON of up to `limit` of the latest document revisions.
+async fn sample_documents(
+    reader: &dyn PersistenceReader,
+    limit: usize,
+) -> anyhow::Result<Vec<Vec<u8>>> {
+    reader
+        .load_documents(
+            TimestampRange::all(),
+            Order::Desc,
+            limit.clamp(1, 1000) as u32,
+            Arc::new(NoopRetentionValidator),
+        )
+        .try_filter_map(|entry| async move {
+            let Some(document) = entry.value else {
+                return Ok(None);
+            };
+            let json = JsonValue::from(document.value().0.clone());
+            anyhow::Ok(Some(serde_json::to_vec(&json)?))
+        })
+        .take(limit)
+        .try_collect()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::DocumentCompressor;
+    use crate::{
+        assert_obj,
+        document::{
+            CreationTime,
+            ResolvedDocument,
+        },
+        persistence::{
+            ConflictStrategy,
+            DocumentLogEntry,
+            Persistence,
+        },
+        testing::{
+            TestIdGenerator,
+            TestPersistence,
+        },
+        types::{
+            TableName,
+            Timestamp,
+        },
+    };
+
+    fn document(i: usize) -> Vec<u8> {
+        let json = serde_json::json!({
+            "_id": format!("{i:08}"),
+            "_creationTime": i,
+            "author": format!("user{}", i % 17),
+            "body": format!("Message number {i} in the channel"),
+            "channel": "general",
+            "reactions": [],
+        });
+        serde_json::to_vec(&json).unwrap()
+    }
+
+    #[test]
+    fn test_uncompressed_rows_pass_through() -> anyhow::Result<()> {
+        let compressor = DocumentCompressor::with_options(true, 3);
+        let json = br#"{"a":1}"#.to_vec();
+        assert_eq!(compressor.encode(json.clone())?, json);
+        assert_eq!(&*compressor.decode(&json)?, &json[..]);
+        assert!(!DocumentCompressor::is_compressed(&json));
+        Ok(())
+    }
+
+    #[test]
+    fn test_disabled_compression_still_decodes() -> anyhow::Result<()> {
+        let json = document(0).repeat(4);
+        let compressed = DocumentCompressor::with_options(true, 3).encode(json.clone())?;
+        assert!(DocumentCompressor::is_compressed(&compressed));
+        assert!(compressed.len() < json.len());
+
+        let disabled = DocumentCompressor::with_options(false, 3);
+        assert_eq!(disabled.encode(json.clone())?, json);
+        assert_eq!(&*disabled.decode(&compressed)?, &json[..]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_dictionary_round_trip() -> anyhow::Result<()> {
+        let samples: Vec<_> = (0..2000).map(document).collect();
+        let dictionary = zstd::dict::from_samples(&samples, super::DICTIONARY_MAX_BYTES)?;
+        let stored = serde_json::json!([{ "id": 1, "dictionary": hex::encode(&dictionary) }]);
+
+        let compressor = DocumentCompressor::with_options(true, 3);
+        compressor.set_dictionaries(Some(stored.clone()))?;
+        let json = document(5000);
+        let compressed = compressor.encode(json.clone())?;
+        assert!(DocumentCompressor::is_compressed(&compressed));
+        assert_eq!(&*compressor.decode(&compressed)?, &json[..]);
+
+        // A reader without the dictionary can't decode it until it's loaded.
+        let reader = DocumentCompressor::with_options(false, 3);
+        assert!(reader.decode(&compressed).is_err());
+        reader.set_dictionaries(Some(stored))?;
+        assert_eq!(&*reader.decode(&compressed)?, &json[..]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_train_dictionary_once_there_are_enough_documents() -> anyhow::Result<()> {
+        let persistence = TestPersistence::new();
+        let compressor = DocumentCompressor::with_options(true, 3);
+        let mut id_generator = TestIdGenerator::new();
+        let table: TableName = "messages".parse()?;
+        let mut write_documents = async |range: std::ops::Range<i32>| {
+            let documents = range
+                .map(|i| {
+                    let document = ResolvedDocument::new(
+                        id_generator.user_generate(&table),
+                        CreationTime::ONE,
+                        assert_obj!(
+                            "author" => format!("user{}", i % 17),
+                            "body" => format!("Message number {i} in the channel"),
+                            "channel" => "general",
+                        ),
+                    )?;
+                    anyhow::Ok(DocumentLogEntry {
+                        ts: Timestamp::must(i),
+                        id: document.id_with_table_id(),
+                        value: Some(document),
+                        prev_ts: None,
+                    })
+                })
+                .collect::<anyhow::Result<_>>()?;
+            persistence
+                .write(documents, BTreeSet::new(), ConflictStrategy::Error)
+                .await
+        };
+
+        write_documents(0..500).await?;
+        compressor.initialize(&persistence).await?;
+        assert!(compressor.needs_dictionary());
+        assert!(!compressor.train_dictionary(&persistence).await?);
+
+        write_documents(500..2500).await?;
+        assert!(compressor.train_dictionary(&persistence).await?);
+        assert!(!compressor.needs_dictionary());
+
+        // The dictionary is stored, so a restarted backend picks it up.
+        let restarted = DocumentCompressor::with_options(true, 3);
+        restarted.initialize(&persistence).await?;
+        assert!(!restarted.needs_dictionary());
+        Ok(())
+    }
+}
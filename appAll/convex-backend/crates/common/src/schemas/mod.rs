@@ -55,6 +55,7 @@ use crate::{
 };
 
 pub mod json;
+mod schema_ts;
 #[cfg(test)]
 mod tests;
 pub mod validator;
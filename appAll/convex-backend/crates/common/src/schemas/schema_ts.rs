@@ -0,0 +1,86 @@
+use std::fmt::Write;
+
+use itertools::Itertools;
+
+use super::{
+    DatabaseSchema,
+    DocumentSchema,
+    TableDefinition,
+};
+
+impl DatabaseSchema {
+    /// Render the schema as the source of a `convex/schema.ts` file.
+    pub fn to_schema_ts(&self) -> String {
+        let mut out = String::new();
+        out.push_str("import { defineSchema, defineTable } from \"convex/server\";\n");
+        out.push_str("import { v } from \"convex/values\";\n\n");
+        out.push_str("export default defineSchema(\n  {\n");
+        for table in self.tables.values() {
+            write_table(&mut out, table);
+        }
+        out.push_str("  },\n");
+        if !self.schema_validation {
+            out.push_str("  { schemaValidation: false },\n");
+        }
+        out.push_str(");\n");
+        out
+    }
+}
+
+fn write_table(out: &mut String, table: &TableDefinition) {
+    let document_type = match &table.document_type {
+        Some(DocumentSchema::Union(objects)) if objects.len() == 1 => {
+            let fields = &objects[0].0;
+            if fields.is_empty() {
+                "{}".to_string()
+            } else {
+                let fields = fields
+                    .iter()
+                    .map(|(field_name, validator)| format!("      {field_name}: {validator},\n"))
+                    .join("");
+                format!("{{\n{fields}    }}")
+            }
+        },
+        Some(DocumentSchema::Union(objects)) => {
+            format!("v.union({})", objects.iter().join(", "))
+        },
+        Some(DocumentSchema::Any) | None => "v.any()".to_string(),
+    };
+    write!(
+        out,
+        "    {}: defineTable({document_type})",
+        table.table_name
+    )
+    .unwrap();
+    for index in table.indexes.values() {
+        write!(
+            out,
+            "\n      .index(\"{}\", {})",
+            index.index_descriptor, index.fields
+        )
+        .unwrap();
+    }
+    for index in table.search_indexes.values() {
+        write!(
+            out,
+            "\n      .searchIndex(\"{}\", {{ searchField: {}, filterFields: [{}] }})",
+            index.index_descriptor,
+            index.search_field,
+            index.filter_fields.iter().join(", "),
+        )
+        .unwrap();
+    }
+    for index in table.vector_indexes.values() {
+        write!(
+            out,
+            "\n      .vectorIndex(\"{}\", {{ vectorField: {}, dimensions: {}, filterFields: [{}] \
+             }})",
+            index.index_descriptor,
+            index.vector_field,
+            u32::from(index.dimension),
+            index.filter_fields.iter().join(", "),
+        )
+        .unwrap();
+    }
+    out.push_str(",\n");
+}
@@ -656,6 +656,52 @@ fn test_json_backwards_compatibility() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_to_schema_ts() -> anyhow::Result<()> {
+    let schema = DatabaseSchema::try_from(json!({
+        "tables": [
+            {
+                "tableName": "messages",
+                "indexes": [{"indexDescriptor": "by_author", "fields": ["author", "meta.sent"]}],
+                "searchIndexes": [],
+                "documentType": {"type": "object", "value": {
+                    "author": {"fieldType": {"type": "id", "tableName": "users"}, "optional": false},
+                    "body": {"fieldType": {"type": "union", "value": [
+                        {"type": "null"},
+                        {"type": "string"},
+                    ]}, "optional": true},
+                }},
+            },
+            {
+                "tableName": "users",
+                "indexes": [],
+                "searchIndexes": [],
+                "documentType": {"type": "any"},
+            },
+        ],
+        "schemaValidation": false,
+    }))?;
+    assert_eq!(
+        schema.to_schema_ts(),
+        r#"import { defineSchema, defineTable } from "convex/server";
+import { v } from "convex/values";
+
+export default defineSchema(
+  {
+    messages: defineTable({
+      author: v.id("users"),
+      body: v.optional(v.union(v.null(), v.string())),
+    })
+      .index("by_author", ["author", "meta.sent"]),
+    users: defineTable(v.any()),
+  },
+  { schemaValidation: false },
+);
+"#
+    );
+    Ok(())
+}
+
 fn empty_table_mapping() -> NamespacedTableMapping {
     TableMapping::new().namespace(TableNamespace::test_user())
 }
@@ -12,7 +12,15 @@
 
 mod dashboard;
 pub mod reduced;
+mod schema;
 #[cfg(test)]
 mod tests;
 
-pub use self::dashboard::dashboard_shape_json;
+pub use self::{
+    dashboard::dashboard_shape_json,
+    schema::{
+        inferred_database_schema,
+        inferred_document_schema,
+        inferred_validator,
+    },
+};
@@ -0,0 +1,344 @@
+//! Propose a schema for existing data from its inferred shapes.
+//!
+//! The result is a starting point for adopting schemas on a deployment that
+//! doesn't have one yet: every existing document conforms to it, but it is
+//! usually worth tightening by hand (e.g. replacing `v.string()` with a union
+//! of literals).
+use std::collections::{
+    BTreeMap,
+    BTreeSet,
+};
+
+use value::{
+    IdentifierFieldName,
+    NamespacedTableMapping,
+    TableName,
+};
+
+use super::reduced::{
+    ReducedField,
+    ReducedShape,
+};
+use crate::{
+    schemas::{
+        validator::{
+            FieldValidator,
+            ObjectValidator,
+            Validator,
+        },
+        DatabaseSchema,
+        DocumentSchema,
+        TableDefinition,
+    },
+    virtual_system_mapping::{
+        all_tables_number_to_name,
+        VirtualSystemMapping,
+    },
+};
+
+/// Propose a [`DatabaseSchema`] with schema validation enabled that accepts
+/// every document currently in the given tables.
+pub fn inferred_database_schema(
+    table_shapes: BTreeMap<TableName, ReducedShape>,
+    mapping: &NamespacedTableMapping,
+    virtual_mapping: &VirtualSystemMapping,
+) -> DatabaseSchema {
+    let tables = table_shapes
+        .into_iter()
+        .map(|(table_name, shape)| {
+            let table_definition = TableDefinition {
+                table_name: table_name.clone(),
+                indexes: Default::default(),
+                search_indexes: Default::default(),
+                vector_indexes: Default::default(),
+                document_type: Some(inferred_document_schema(&shape, mapping, virtual_mapping)),
+            };
+            (table_name, table_definition)
+        })
+        .collect();
+    DatabaseSchema {
+        tables,
+        schema_validation: true,
+    }
+}
+
+/// Propose a [`DocumentSchema`] for a table whose documents have the given
+/// shape. Tables whose documents come in several object shapes get a union
+/// of them. Tables that are empty or whose shape isn't known yet get
+/// [`DocumentSchema::Any`].
+pub fn inferred_document_schema(
+    shape: &ReducedShape,
+    mapping: &NamespacedTableMapping,
+    virtual_mapping: &VirtualSystemMapping,
+) -> DocumentSchema {
+    let objects = match inferred_validator(shape, mapping, virtual_mapping) {
+        Validator::Object(object) => vec![object],
+        Validator::Union(validators) => {
+            let objects = validators
+                .into_iter()
+                .map(|validator| match validator {
+                    Validator::Object(object) => Some(object),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>();
+            match objects {
+                Some(objects) => objects,
+                None => return DocumentSchema::Any,
+            }
+        },
+        _ => return DocumentSchema::Any,
+    };
+    // Objects that only differed in their system fields collapse together.
+    let objects: BTreeSet<_> = objects
+        .into_iter()
+        .map(ObjectValidator::filter_system_fields)
+        .collect();
+    DocumentSchema::Union(objects.into_iter().collect())
+}
+
+/// Convert a [`ReducedShape`] into the narrowest [`Validator`] that accepts
+/// all values of the shape.
+///
+/// Strings that are IDs of existing tables become `v.id(...)` and unions are
+/// preserved. `Never` (e.g. the elements of arrays that are always empty)
+/// becomes `v.any()` rather than an empty union, since rejecting every
+/// future value is never a useful proposal.
+pub fn inferred_validator(
+    shape: &ReducedShape,
+    mapping: &NamespacedTableMapping,
+    virtual_mapping: &VirtualSystemMapping,
+) -> Validator {
+    let infer = |shape: &ReducedShape| inferred_validator(shape, mapping, virtual_mapping);
+    match shape {
+        ReducedShape::Unknown | ReducedShape::Never => Validator::Any,
+        ReducedShape::Id(table_number) => {
+            match all_tables_number_to_name(mapping, virtual_mapping)(*table_number) {
+                Ok(table_name) => Validator::Id(table_name),
+                Err(_) => Validator::String,
+            }
+        },
+        ReducedShape::Null => Validator::Null,
+        ReducedShape::Int64 => Validator::Int64,
+        ReducedShape::Float64(_) => Validator::Float64,
+        ReducedShape::Boolean => Validator::Boolean,
+        ReducedShape::String => Validator::String,
+        ReducedShape::Bytes => Validator::Bytes,
+        ReducedShape::Object(fields) => {
+            let identifier_fields = fields
+                .iter()
+                .map(|(field_name, field)| {
+                    let field_name = IdentifierFieldName::try_from(field_name.clone()).ok()?;
+                    Some((
+                        field_name,
+                        FieldValidator {
+                            validator: infer(&field.shape),
+                            optional: field.optional,
+                        },
+                    ))
+                })
+                .collect::<Option<BTreeMap<_, _>>>();
+            match identifier_fields {
+                Some(fields) => Validator::Object(ObjectValidator(fields)),
+                // `v.object` only supports identifier field names, so fall back to a record
+                // over all of the field values.
+                None => Validator::Record(
+                    Box::new(Validator::String),
+                    Box::new(union(
+                        fields
+                            .values()
+                            .map(|ReducedField { shape, .. }| infer(shape)),
+                    )),
+                ),
+            }
+        },
+        ReducedShape::Array(element) => Validator::Array(Box::new(infer(element))),
+        ReducedShape::Set(element) => Validator::Set(Box::new(infer(element))),
+        ReducedShape::Map {
+            key_shape,
+            value_shape,
+        } => Validator::Map(Box::new(infer(key_shape)), Box::new(infer(value_shape))),
+        ReducedShape::Record {
+            key_shape,
+            value_shape,
+        } => Validator::Record(
+            Box::new(infer(key_shape)),
+            Box::new(infer(&value_shape.shape)),
+        ),
+        ReducedShape::Union(shapes) => union(shapes.iter().map(infer)),
+    }
+}
+
+fn union(validators: impl Iterator<Item = Validator>) -> Validator {
+    let mut validators: BTreeSet<_> = validators.collect();
+    if validators.contains(&Validator::Any) {
+        return Validator::Any;
+    }
+    match validators.len() {
+        0 => Validator::Any,
+        1 => validators.pop_first().unwrap(),
+        _ => Validator::Union(validators.into_iter().collect()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use maplit::{
+        btreemap,
+        btreeset,
+    };
+    use value::TableNamespace;
+
+    use super::{
+        inferred_database_schema,
+        inferred_validator,
+    };
+    use crate::{
+        object_validator,
+        schemas::{
+            validator::{
+                FieldValidator,
+                Validator,
+            },
+            DocumentSchema,
+        },
+        shapes::reduced::{
+            ReducedField,
+            ReducedFloatRange,
+            ReducedShape,
+        },
+        testing::TestIdGenerator,
+    };
+
+    fn required(shape: ReducedShape) -> ReducedField {
+        ReducedField {
+            optional: false,
+            shape,
+        }
+    }
+
+    #[test]
+    fn test_inferred_database_schema() -> anyhow::Result<()> {
+        let mut id_generator = TestIdGenerator::new();
+        let users_table = id_generator.user_table_id(&"users".parse()?).table_number;
+        let messages_table = id_generator
+            .user_table_id(&"messages".parse()?)
+            .table_number;
+        let mapping = id_generator.namespace(TableNamespace::test_user());
+
+        let messages_shape = ReducedShape::Object(btreemap! {
+            "_id".parse()? => required(ReducedShape::Id(messages_table)),
+            "_creationTime".parse()? => required(ReducedShape::Float64(ReducedFloatRange::new(false))),
+            "author".parse()? => required(ReducedShape::Id(users_table)),
+            "body".parse()? => required(ReducedShape::Union(btreeset! {
+                ReducedShape::String,
+                ReducedShape::Null,
+            })),
+            "reactions".parse()? => ReducedField {
+                optional: true,
+                shape: ReducedShape::Array(Box::new(ReducedShape::Never)),
+            },
+        });
+        let schema = inferred_database_schema(
+            btreemap! {
+                "messages".parse()? => messages_shape,
+                "users".parse()? => ReducedShape::Never,
+            },
+            &mapping,
+            &id_generator.virtual_system_mapping,
+        );
+        assert!(schema.schema_validation);
+        assert_eq!(
+            schema.tables[&"messages".parse()?].document_type,
+            Some(DocumentSchema::Union(vec![object_validator!(
+                "author" => FieldValidator::required_field_type(Validator::Id("users".parse()?)),
+                "body" => FieldValidator::required_field_type(Validator::Union(vec![
+                    Validator::Null,
+                    Validator::String,
+                ])),
+                "reactions" => FieldValidator::optional_field_type(
+                    Validator::Array(Box::new(Validator::Any)),
+                ),
+            )]))
+        );
+        assert_eq!(
+            schema.tables[&"users".parse()?].document_type,
+            Some(DocumentSchema::Any)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_union_of_objects() -> anyhow::Result<()> {
+        let mut id_generator = TestIdGenerator::new();
+        let events_table = id_generator.user_table_id(&"events".parse()?).table_number;
+        let mapping = id_generator.namespace(TableNamespace::test_user());
+        let system_fields = || -> anyhow::Result<_> {
+            Ok(btreemap! {
+                "_id".parse()? => required(ReducedShape::Id(events_table)),
+                "_creationTime".parse()? => required(ReducedShape::Float64(ReducedFloatRange::new(false))),
+            })
+        };
+        let mut click = system_fields()?;
+        click.insert("x".parse()?, required(ReducedShape::Int64));
+        let mut key = system_fields()?;
+        key.insert("key".parse()?, required(ReducedShape::String));
+        let shape = ReducedShape::Union(btreeset! {
+            ReducedShape::Object(click),
+            ReducedShape::Object(key),
+        });
+        let schema = inferred_database_schema(
+            btreemap! { "events".parse()? => shape.clone() },
+            &mapping,
+            &id_generator.virtual_system_mapping,
+        );
+        let DocumentSchema::Union(mut objects) = schema.tables[&"events".parse()?]
+            .document_type
+            .clone()
+            .unwrap()
+        else {
+            anyhow::bail!("Expected a union of objects");
+        };
+        objects.sort();
+        let mut expected = vec![
+            object_validator!("x" => FieldValidator::required_field_type(Validator::Int64)),
+            object_validator!("key" => FieldValidator::required_field_type(Validator::String)),
+        ];
+        expected.sort();
+        assert_eq!(objects, expected);
+
+        // Documents that aren't all objects can't be described by a document schema.
+        let schema = inferred_database_schema(
+            btreemap! {
+                "events".parse()? => ReducedShape::Union(btreeset! {shape, ReducedShape::String}),
+            },
+            &mapping,
+            &id_generator.virtual_system_mapping,
+        );
+        assert_eq!(
+            schema.tables[&"events".parse()?].document_type,
+            Some(DocumentSchema::Any)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_non_identifier_fields_become_record() -> anyhow::Result<()> {
+        let id_generator = TestIdGenerator::new();
+        let mapping = id_generator.namespace(TableNamespace::test_user());
+        let shape = ReducedShape::Object(btreemap! {
+            "with space".parse()? => required(ReducedShape::Float64(ReducedFloatRange::new(false))),
+            "ok".parse()? => required(ReducedShape::String),
+        });
+        assert_eq!(
+            inferred_validator(&shape, &mapping, &id_generator.virtual_system_mapping),
+            Validator::Record(
+                Box::new(Validator::String),
+                Box::new(Validator::Union(vec![
+                    Validator::Float64,
+                    Validator::String
+                ])),
+            )
+        );
+        Ok(())
+    }
+}
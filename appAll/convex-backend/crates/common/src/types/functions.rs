@@ -7,6 +7,8 @@ use std::{
 };
 
 use anyhow::Context;
+use errors::ErrorMetadata;
+use itertools::Itertools;
 use metrics::StaticMetricLabel;
 use pb::common::UdfType as UdfTypeProto;
 use serde::{
@@ -408,6 +410,90 @@ impl ModuleEnvironment {
     }
 }
 
+/// Major version of Node.js that runs a project's "use node" actions.
+///
+/// Serializes as the bare major version, e.g. "20", and deserializes through
+/// [`FromStr`] so configs may also say "v20" or "20.x".
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, Default, PartialOrd, Ord, PartialEq, Eq, Hash,
+)]
+#[serde(try_from = "String", into = "String")]
+#[cfg_attr(any(test, feature = "testing"), derive(proptest_derive::Arbitrary))]
+pub enum NodeVersion {
+    /// Source packages pushed before the version was configurable ran on
+    /// Node.js 18.
+    #[default]
+    V18,
+    V20,
+    V22,
+}
+
+impl NodeVersion {
+    pub const ALL: [NodeVersion; 3] = [NodeVersion::V18, NodeVersion::V20, NodeVersion::V22];
+
+    pub fn major(&self) -> u32 {
+        match self {
+            NodeVersion::V18 => 18,
+            NodeVersion::V20 => 20,
+            NodeVersion::V22 => 22,
+        }
+    }
+
+    /// The AWS Lambda runtime identifier for this version, e.g. `nodejs20.x`.
+    pub fn aws_lambda_runtime(&self) -> String {
+        format!("nodejs{}.x", self.major())
+    }
+
+    pub fn from_aws_lambda_runtime(runtime: &str) -> Option<Self> {
+        let major = runtime.strip_prefix("nodejs")?.strip_suffix(".x")?;
+        major.parse().ok()
+    }
+}
+
+impl FromStr for NodeVersion {
+    type Err = anyhow::Error;
+
+    /// Accepts a major version with an optional `v` prefix and `.x` suffix,
+    /// e.g. "20", "v20" or "20.x".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let major = s.strip_prefix('v').unwrap_or(s);
+        let major = major.strip_suffix(".x").unwrap_or(major);
+        let Some(version) = Self::ALL
+            .into_iter()
+            .find(|version| major == version.major().to_string())
+        else {
+            anyhow::bail!(ErrorMetadata::bad_request(
+                "InvalidNodeVersion",
+                format!(
+                    "Unsupported Node.js version {s:?}. Supported versions are {}.",
+                    Self::ALL.iter().map(|v| v.to_string()).join(", ")
+                ),
+            ));
+        };
+        Ok(version)
+    }
+}
+
+impl fmt::Display for NodeVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.major())
+    }
+}
+
+impl TryFrom<String> for NodeVersion {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<NodeVersion> for String {
+    fn from(version: NodeVersion) -> Self {
+        version.to_string()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use cmd_util::env::env_config;
@@ -415,6 +501,7 @@ mod tests {
     use sync_types::testing::assert_roundtrips;
 
     use super::{
+        NodeVersion,
         UdfType,
         UdfTypeProto,
     };
@@ -434,5 +521,32 @@ mod tests {
         fn test_function_caller_roundtrips(u in any::<FunctionCaller>()) {
             assert_roundtrips::<FunctionCaller, pb::common::FunctionCaller>(u);
         }
+
+        #[test]
+        fn test_node_version_roundtrips(v in any::<NodeVersion>()) {
+            assert_eq!(v.to_string().parse::<NodeVersion>().unwrap(), v);
+            assert_eq!(NodeVersion::from_aws_lambda_runtime(&v.aws_lambda_runtime()), Some(v));
+        }
+    }
+
+    #[test]
+    fn test_parse_node_version() {
+        assert_eq!("v20".parse::<NodeVersion>().unwrap(), NodeVersion::V20);
+        assert_eq!("22.x".parse::<NodeVersion>().unwrap(), NodeVersion::V22);
+        assert!("16".parse::<NodeVersion>().is_err());
+    }
+
+    #[test]
+    fn test_deserialize_node_version() -> anyhow::Result<()> {
+        for s in ["20", "v20", "20.x"] {
+            let version: NodeVersion = serde_json::from_value(serde_json::json!(s))?;
+            assert_eq!(version, NodeVersion::V20);
+        }
+        assert_eq!(
+            serde_json::to_value(NodeVersion::V22)?,
+            serde_json::json!("22")
+        );
+        assert!(serde_json::from_value::<NodeVersion>(serde_json::json!("16")).is_err());
+        Ok(())
     }
 }
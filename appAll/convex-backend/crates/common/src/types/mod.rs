@@ -65,6 +65,7 @@ pub use functions::{
     AllowedVisibility,
     FunctionCaller,
     ModuleEnvironment,
+    NodeVersion,
     UdfIdentifier,
     UdfType,
 };
@@ -12,6 +12,7 @@ use std::{
     },
 };
 
+use anyhow::Context;
 use convex_sync_types::{
     AuthenticationToken,
     CanonicalizedUdfPath,
@@ -51,6 +52,17 @@ pub use query_result::{
     FunctionResult,
     QueryResults,
 };
+mod optimistic_update;
+pub use optimistic_update::{
+    OptimisticLocalStore,
+    OptimisticUpdate,
+};
+mod outbox;
+pub use outbox::{
+    FileMutationOutbox,
+    MutationOutbox,
+    OutboxMutation,
+};
 
 use self::request_manager::RequestType;
 
@@ -65,13 +77,6 @@ struct LocalQuery {
     num_subscribers: usize, // TODO: remove
 }
 
-#[derive(Clone, Debug)]
-struct Query {
-    result: FunctionResult,
-    _udf_path: CanonicalizedUdfPath,
-    _args: BTreeMap<String, Value>,
-}
-
 /// An identifier for a single subscriber to a query.
 #[derive(Copy, Clone, Debug, Default, Eq, PartialEq, PartialOrd, Ord, Hash)]
 #[cfg_attr(test, derive(proptest_derive::Arbitrary))]
@@ -197,13 +202,8 @@ impl LocalSyncState {
         self.query_id_to_token.get(&query_id).cloned()
     }
 
-    fn query_args(&self, query_id: QueryId) -> Option<BTreeMap<String, Value>> {
-        Some(
-            self.query_set
-                .get(&self.query_token(query_id)?)?
-                .args
-                .clone(),
-        )
+    fn queries(&self) -> Vec<&LocalQuery> {
+        self.query_set.values().collect()
     }
 
     fn query_path(&self, query_id: QueryId) -> Option<CanonicalizedUdfPath> {
@@ -333,36 +333,80 @@ impl RemoteQuerySet {
     }
 }
 
-#[derive(Default, Debug)]
+/// Query results as seen by the client: the server's results with all
+/// pending optimistic updates applied on top, in the order their mutations
+/// were made.
+#[derive(Default)]
 struct OptimisticQueryResults {
-    query_results: BTreeMap<QueryId, Query>,
+    server_query_results: BTreeMap<QueryId, FunctionResult>,
+    optimistic_updates: Vec<(RequestId, OptimisticUpdate)>,
+    query_results: BTreeMap<QueryId, FunctionResult>,
 }
 
 impl OptimisticQueryResults {
     fn ingest_query_results_from_server(
         &mut self,
-        server_query_results: BTreeMap<QueryId, Query>,
-        _optimistic_updates_to_drop: BTreeSet<RequestId>,
-    ) -> BTreeMap<QueryId, FunctionResult> {
-        // TODO: use optimistic_updates_to_drop
-        let old_query_results = self.query_results.clone();
-        self.query_results = server_query_results;
+        server_query_results: BTreeMap<QueryId, FunctionResult>,
+        optimistic_updates_to_drop: BTreeSet<RequestId>,
+        queries: &[&LocalQuery],
+    ) -> BTreeMap<QueryId, Option<FunctionResult>> {
+        self.server_query_results = server_query_results;
+        self.optimistic_updates
+            .retain(|(request_id, _)| !optimistic_updates_to_drop.contains(request_id));
+        self.recompute(queries)
+    }
+
+    fn apply_optimistic_update(
+        &mut self,
+        request_id: RequestId,
+        update: OptimisticUpdate,
+        queries: &[&LocalQuery],
+    ) -> BTreeMap<QueryId, Option<FunctionResult>> {
+        self.optimistic_updates.push((request_id, update));
+        self.recompute(queries)
+    }
+
+    fn drop_optimistic_update(
+        &mut self,
+        request_id: RequestId,
+        queries: &[&LocalQuery],
+    ) -> BTreeMap<QueryId, Option<FunctionResult>> {
+        let num_updates = self.optimistic_updates.len();
+        self.optimistic_updates
+            .retain(|(update_request_id, _)| *update_request_id != request_id);
+        if self.optimistic_updates.len() == num_updates {
+            return BTreeMap::new();
+        }
+        self.recompute(queries)
+    }
+
+    /// Rebuild the query results from the server's results and return the
+    /// queries whose results changed (`None` meaning the query is now
+    /// loading).
+    fn recompute(&mut self, queries: &[&LocalQuery]) -> BTreeMap<QueryId, Option<FunctionResult>> {
+        let mut query_results = self.server_query_results.clone();
+        let mut store = OptimisticLocalStore::new(queries, &mut query_results);
+        for (_, update) in self.optimistic_updates.iter() {
+            update(&mut store);
+        }
+
         let mut changed_queries = BTreeMap::new();
-        for (query_id, query) in self.query_results.iter() {
-            let old_query = old_query_results.get(query_id);
-            if match old_query {
-                Some(old_query) => old_query.result != query.result,
-                None => true,
-            } {
-                let result = query.result.clone();
-                changed_queries.insert(*query_id, result);
+        for (query_id, result) in query_results.iter() {
+            if self.query_results.get(query_id) != Some(result) {
+                changed_queries.insert(*query_id, Some(result.clone()));
             }
         }
+        for query_id in self.query_results.keys() {
+            if !query_results.contains_key(query_id) {
+                changed_queries.insert(*query_id, None);
+            }
+        }
+        self.query_results = query_results;
         changed_queries
     }
 
     fn query_result(&self, query_id: QueryId) -> Option<FunctionResult> {
-        self.query_results.get(&query_id).map(|q| q.result.clone())
+        self.query_results.get(&query_id).cloned()
     }
 }
 
@@ -442,6 +486,9 @@ pub struct BaseConvexClient {
     next_request_id: SessionRequestSeqNumber,
     outgoing_message_queue: VecDeque<ClientMessage>,
     max_observed_timestamp: Option<Timestamp>,
+    outbox: Option<Box<dyn MutationOutbox>>,
+    outbox_mutations: BTreeMap<RequestId, u64>,
+    next_outbox_id: u64,
 }
 
 impl BaseConvexClient {
@@ -461,9 +508,35 @@ impl BaseConvexClient {
             next_request_id,
             outgoing_message_queue: VecDeque::new(),
             max_observed_timestamp: None,
+            outbox: None,
+            outbox_mutations: BTreeMap::new(),
+            next_outbox_id: 0,
         }
     }
 
+    /// Construct a new [`BaseConvexClient`] that records mutations in
+    /// `outbox` until the server responds to them.
+    ///
+    /// Mutations left in the outbox by a previous process are queued to be
+    /// sent, in their original order, before any new mutations. Their results
+    /// aren't reported to anyone.
+    pub fn with_outbox(mut outbox: Box<dyn MutationOutbox>) -> anyhow::Result<Self> {
+        let pending = outbox.load()?;
+        let mut client = Self::new();
+        for mutation in pending {
+            let udf_path: UdfPath = mutation.udf_path.parse()?;
+            let Value::Object(args) = Value::try_from(mutation.args)? else {
+                anyhow::bail!("Outbox mutation {} has non-object args", mutation.id);
+            };
+            tracing::info!("Replaying mutation {udf_path} from the outbox");
+            let (request_id, _result_receiver) = client.start_mutation(udf_path, args);
+            client.outbox_mutations.insert(request_id, mutation.id);
+            client.next_outbox_id = mutation.id + 1;
+        }
+        client.outbox = Some(outbox);
+        Ok(client)
+    }
+
     /// Update state to be subscribed to a query and add subscription request to
     /// the outgoing message queue.
     ///
@@ -499,6 +572,9 @@ impl BaseConvexClient {
 
     /// Track mutation and add mutation request to the outgoing message queue.
     ///
+    /// If the client has an outbox, the mutation is recorded in it first, and
+    /// isn't sent if that fails.
+    ///
     /// After calling this, it is highly recommended to loop on
     /// [`pop_next_message`](Self::pop_next_message()) to flush websocket
     /// messages to the server.
@@ -506,7 +582,53 @@ impl BaseConvexClient {
         &mut self,
         udf_path: UdfPath,
         args: BTreeMap<String, Value>,
-    ) -> oneshot::Receiver<FunctionResult> {
+    ) -> anyhow::Result<oneshot::Receiver<FunctionResult>> {
+        let Some(outbox) = &mut self.outbox else {
+            return Ok(self.start_mutation(udf_path, args).1);
+        };
+        let mutation = OutboxMutation {
+            id: self.next_outbox_id,
+            udf_path: String::from(udf_path.clone().canonicalize()),
+            args: Value::Object(args.clone()).into(),
+        };
+        outbox
+            .push(&mutation)
+            .with_context(|| format!("Failed to record mutation {udf_path} in the outbox"))?;
+        self.next_outbox_id += 1;
+        let (request_id, result_receiver) = self.start_mutation(udf_path, args);
+        self.outbox_mutations.insert(request_id, mutation.id);
+        Ok(result_receiver)
+    }
+
+    /// Like [`mutation`](Self::mutation()), but also apply `update` to the
+    /// local query results until the mutation's effects are reflected in a
+    /// server transition, or the mutation fails.
+    ///
+    /// The optimistic results are visible immediately through
+    /// [`latest_results`](Self::latest_results()) and
+    /// [`get_query`](Self::get_query()).
+    pub fn mutation_with_optimistic_update(
+        &mut self,
+        udf_path: UdfPath,
+        args: BTreeMap<String, Value>,
+        update: OptimisticUpdate,
+    ) -> anyhow::Result<oneshot::Receiver<FunctionResult>> {
+        let request_id = RequestId::new(self.next_request_id);
+        let result_receiver = self.mutation(udf_path, args)?;
+        let changed_queries = self.optimistic_query_results.apply_optimistic_update(
+            request_id,
+            update,
+            &self.state.queries(),
+        );
+        self.update_latest_results(changed_queries);
+        Ok(result_receiver)
+    }
+
+    fn start_mutation(
+        &mut self,
+        udf_path: UdfPath,
+        args: BTreeMap<String, Value>,
+    ) -> (RequestId, oneshot::Receiver<FunctionResult>) {
         let request_id = self.next_request_id;
         self.next_request_id = request_id + 1;
         tracing::info!("Starting mutation {udf_path} with id {request_id}");
@@ -517,13 +639,12 @@ impl BaseConvexClient {
             component_path: None,
         };
 
-        let result_receiver = self.request_manager.track_request(
-            &message,
-            RequestId::new(request_id),
-            RequestType::Mutation,
-        );
+        let request_id = RequestId::new(request_id);
+        let result_receiver =
+            self.request_manager
+                .track_request(&message, request_id, RequestType::Mutation);
         self.outgoing_message_queue.push_back(message);
-        result_receiver
+        (request_id, result_receiver)
     }
 
     /// Track action and add action request to the outgoing message queue.
@@ -596,9 +717,7 @@ impl BaseConvexClient {
                     .request_manager
                     .remove_and_notify_completed(end_version.ts);
                 let changed_query_ids = self.on_query_result_changes(completed_requests)?;
-                for (id, result) in changed_query_ids {
-                    self.state.latest_results.results.insert(id, result);
-                }
+                self.update_latest_results(changed_query_ids);
                 return Ok(Some(self.state.latest_results.clone()));
             },
             ServerMessage::MutationResponse {
@@ -615,12 +734,35 @@ impl BaseConvexClient {
                     self.observe_timestamp(ts);
                 }
                 let request_id = RequestId::new(request_id);
+                let result: FunctionResult = result.into();
+                let failed = !matches!(result, FunctionResult::Value(_));
                 self.request_manager.update_request(
                     &request_id,
                     RequestType::Mutation,
-                    result.into(),
+                    result,
                     ts,
                 )?;
+                // The server has run the mutation, so it must not be replayed.
+                if let Some(outbox_id) = self.outbox_mutations.remove(&request_id) {
+                    if let Some(outbox) = &mut self.outbox {
+                        if let Err(e) = outbox.remove(outbox_id) {
+                            tracing::error!(
+                                "Failed to remove mutation {outbox_id} from the outbox: {e:?}"
+                            );
+                        }
+                    }
+                }
+                // A failed mutation won't show up in a transition, so roll back its
+                // optimistic update now.
+                if failed {
+                    let changed_queries = self
+                        .optimistic_query_results
+                        .drop_optimistic_update(request_id, &self.state.queries());
+                    if !changed_queries.is_empty() {
+                        self.update_latest_results(changed_queries);
+                        return Ok(Some(self.state.latest_results.clone()));
+                    }
+                }
             },
             ServerMessage::AuthError {
                 error_message,
@@ -684,31 +826,40 @@ impl BaseConvexClient {
     fn on_query_result_changes(
         &mut self,
         completed_requests: BTreeSet<RequestId>,
-    ) -> Result<BTreeMap<QueryId, FunctionResult>, ReconnectProtocolReason> {
+    ) -> Result<BTreeMap<QueryId, Option<FunctionResult>>, ReconnectProtocolReason> {
         let remote_query_results = &self.remote_query_set.remote_query_set;
         let mut query_id_to_value = BTreeMap::new();
         for (query_id, result) in remote_query_results.iter() {
-            let Some(_udf_path) = self.state.query_path(*query_id) else {
+            if self.state.query_path(*query_id).is_none() {
                 // It's possible that we've already unsubscribed to this query but
                 // the server hasn't learned about that yet. If so, ignore this one.
                 continue;
             };
-            let _args = self
-                .state
-                .query_args(*query_id)
-                .expect("INTERNAL BUG: Query args exist, but not query path.");
-            query_id_to_value.insert(
-                *query_id,
-                Query {
-                    result: result.clone(),
-                    _udf_path,
-                    _args,
-                },
-            );
+            query_id_to_value.insert(*query_id, result.clone());
         }
         Ok(self
             .optimistic_query_results
-            .ingest_query_results_from_server(query_id_to_value, completed_requests))
+            .ingest_query_results_from_server(
+                query_id_to_value,
+                completed_requests,
+                &self.state.queries(),
+            ))
+    }
+
+    fn update_latest_results(
+        &mut self,
+        changed_queries: BTreeMap<QueryId, Option<FunctionResult>>,
+    ) {
+        for (id, result) in changed_queries {
+            match result {
+                Some(result) => {
+                    self.state.latest_results.results.insert(id, result);
+                },
+                None => {
+                    self.state.latest_results.results.remove(&id);
+                },
+            }
+        }
     }
 
     fn local_query_result(&self, query_id: QueryId) -> Option<FunctionResult> {
@@ -0,0 +1,100 @@
+use std::collections::BTreeMap;
+
+use convex_sync_types::{
+    CanonicalizedUdfPath,
+    QueryId,
+    UdfPath,
+};
+
+use super::LocalQuery;
+use crate::{
+    value::Value,
+    FunctionResult,
+};
+
+/// A local change to query results made on behalf of a mutation before the
+/// server has confirmed it.
+///
+/// The update is applied on top of the server's results every time they
+/// change, until the mutation's effects show up in a server transition (or the
+/// mutation fails). It should therefore be a pure function of the store's
+/// current contents.
+pub type OptimisticUpdate = Box<dyn Fn(&mut OptimisticLocalStore<'_>) + Send + Sync>;
+
+/// A view of the client's subscribed queries that an [`OptimisticUpdate`] can
+/// read and modify.
+///
+/// Queries are identified by function name and args, like in
+/// [`ConvexClient::subscribe`](crate::ConvexClient::subscribe). Only queries
+/// that are currently subscribed can be read or set.
+pub struct OptimisticLocalStore<'a> {
+    queries: &'a [&'a LocalQuery],
+    results: &'a mut BTreeMap<QueryId, FunctionResult>,
+}
+
+impl<'a> OptimisticLocalStore<'a> {
+    pub(super) fn new(
+        queries: &'a [&'a LocalQuery],
+        results: &'a mut BTreeMap<QueryId, FunctionResult>,
+    ) -> Self {
+        Self { queries, results }
+    }
+
+    fn find(&self, name: &str, args: &BTreeMap<String, Value>) -> Option<QueryId> {
+        let udf_path = canonicalize(name)?;
+        self.queries
+            .iter()
+            .find(|query| query.canonicalized_udf_path == udf_path && &query.args == args)
+            .map(|query| query.id)
+    }
+
+    /// Return the current value of the query `name` with `args`, or `None` if
+    /// it isn't subscribed, is still loading, or failed.
+    pub fn get_query(&self, name: &str, args: &BTreeMap<String, Value>) -> Option<Value> {
+        let query_id = self.find(name, args)?;
+        match self.results.get(&query_id)? {
+            FunctionResult::Value(value) => Some(value.clone()),
+            _ => None,
+        }
+    }
+
+    /// Return the args and current value of every subscribed query for the
+    /// function `name`.
+    pub fn get_all_queries(&self, name: &str) -> Vec<(BTreeMap<String, Value>, Option<Value>)> {
+        let Some(udf_path) = canonicalize(name) else {
+            return vec![];
+        };
+        self.queries
+            .iter()
+            .filter(|query| query.canonicalized_udf_path == udf_path)
+            .map(|query| {
+                let value = match self.results.get(&query.id) {
+                    Some(FunctionResult::Value(value)) => Some(value.clone()),
+                    _ => None,
+                };
+                (query.args.clone(), value)
+            })
+            .collect()
+    }
+
+    /// Set the value of the query `name` with `args`. Setting `None` makes the
+    /// query appear to be loading. Does nothing if the query isn't
+    /// subscribed.
+    pub fn set_query(&mut self, name: &str, args: &BTreeMap<String, Value>, value: Option<Value>) {
+        let Some(query_id) = self.find(name, args) else {
+            return;
+        };
+        match value {
+            Some(value) => {
+                self.results.insert(query_id, FunctionResult::Value(value));
+            },
+            None => {
+                self.results.remove(&query_id);
+            },
+        }
+    }
+}
+
+fn canonicalize(name: &str) -> Option<CanonicalizedUdfPath> {
+    name.parse::<UdfPath>().ok().map(UdfPath::canonicalize)
+}
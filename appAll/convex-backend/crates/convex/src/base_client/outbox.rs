@@ -0,0 +1,384 @@
+use std::{
+    collections::BTreeMap,
+    fs::{
+        self,
+        File,
+        OpenOptions,
+    },
+    io::{
+        BufRead,
+        BufReader,
+        Write,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use anyhow::Context;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+#[cfg(doc)]
+use crate::base_client::BaseConvexClient;
+
+/// A mutation that has been queued but not yet acknowledged by the server.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutboxMutation {
+    /// Position of the mutation in the outbox. Ids increase in the order
+    /// mutations were queued.
+    pub id: u64,
+    /// The path of the mutation function.
+    pub udf_path: String,
+    /// The mutation's arguments object, in Convex JSON format.
+    pub args: serde_json::Value,
+}
+
+/// Durable storage for mutations that haven't been acknowledged by the server
+/// yet, so they can be replayed after a restart.
+///
+/// See [`BaseConvexClient::with_outbox`]. A mutation is removed once the
+/// server responds to it, so a mutation that was executed right before the
+/// process exited may be replayed (at-least-once delivery).
+pub trait MutationOutbox: Send {
+    /// Return every mutation in the outbox, ordered by id.
+    fn load(&mut self) -> anyhow::Result<Vec<OutboxMutation>>;
+
+    /// Durably add a mutation to the end of the outbox.
+    fn push(&mut self, mutation: &OutboxMutation) -> anyhow::Result<()>;
+
+    /// Remove the mutation with the given id.
+    fn remove(&mut self, id: u64) -> anyhow::Result<()>;
+}
+
+/// A [`MutationOutbox`] stored in a local append-only log with one JSON entry
+/// per line.
+///
+/// Pushes append the mutation and removals append a tombstone, each synced to
+/// disk. The log is compacted through a temporary file when it's opened and
+/// once tombstones outnumber the pending mutations.
+pub struct FileMutationOutbox {
+    path: PathBuf,
+    pending: BTreeMap<u64, OutboxMutation>,
+    num_tombstones: usize,
+}
+
+/// Don't bother compacting logs with fewer tombstones than this.
+const MIN_TOMBSTONES_TO_COMPACT: usize = 64;
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum OutboxEntry {
+    Mutation(OutboxMutation),
+    #[serde(rename_all = "camelCase")]
+    Removed {
+        removed_id: u64,
+    },
+}
+
+impl FileMutationOutbox {
+    /// Open the outbox at `path`, creating an empty one if it doesn't exist.
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut pending = BTreeMap::new();
+        match File::open(&path) {
+            Ok(file) => {
+                let lines = BufReader::new(file)
+                    .lines()
+                    .collect::<Result<Vec<_>, _>>()
+                    .with_context(|| format!("Failed to read outbox {}", path.display()))?;
+                let num_lines = lines.len();
+                for (i, line) in lines.into_iter().enumerate() {
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    match serde_json::from_str(&line) {
+                        Ok(OutboxEntry::Mutation(mutation)) => {
+                            pending.insert(mutation.id, mutation);
+                        },
+                        Ok(OutboxEntry::Removed { removed_id }) => {
+                            pending.remove(&removed_id);
+                        },
+                        // The process may have exited in the middle of appending the last line.
+                        // A truncated mutation was never sent, and a truncated tombstone means
+                        // the mutation is replayed, which the outbox allows.
+                        Err(e) if i == num_lines - 1 => {
+                            tracing::warn!("Ignoring truncated outbox entry: {e}");
+                        },
+                        Err(e) => {
+                            return Err(e).with_context(|| {
+                                format!("Corrupt entry in outbox {}", path.display())
+                            })
+                        },
+                    }
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {},
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to open outbox {}", path.display()))
+            },
+        }
+        let mut outbox = Self {
+            path,
+            pending,
+            num_tombstones: 0,
+        };
+        // Drop tombstones and any truncated entry from disk before appending after
+        // them.
+        outbox.compact()?;
+        Ok(outbox)
+    }
+
+    fn compact(&mut self) -> anyhow::Result<()> {
+        let tmp_path = self.path.with_extension("tmp");
+        let mut file = File::create(&tmp_path)?;
+        for mutation in self.pending.values() {
+            serde_json::to_writer(&mut file, mutation)?;
+            file.write_all(b"\n")?;
+        }
+        file.sync_all()?;
+        fs::rename(&tmp_path, &self.path)?;
+        self.num_tombstones = 0;
+        Ok(())
+    }
+
+    fn append(&self, entry: &OutboxEntry) -> anyhow::Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        let mut file = OpenOptions::new().append(true).open(&self.path)?;
+        file.write_all(&line)?;
+        file.sync_data()?;
+        Ok(())
+    }
+}
+
+impl MutationOutbox for FileMutationOutbox {
+    fn load(&mut self) -> anyhow::Result<Vec<OutboxMutation>> {
+        Ok(self.pending.values().cloned().collect())
+    }
+
+    fn push(&mut self, mutation: &OutboxMutation) -> anyhow::Result<()> {
+        self.append(&OutboxEntry::Mutation(mutation.clone()))?;
+        self.pending.insert(mutation.id, mutation.clone());
+        Ok(())
+    }
+
+    fn remove(&mut self, id: u64) -> anyhow::Result<()> {
+        if !self.pending.contains_key(&id) {
+            return Ok(());
+        }
+        self.append(&OutboxEntry::Removed { removed_id: id })?;
+        self.pending.remove(&id);
+        self.num_tombstones += 1;
+        if self.num_tombstones >= MIN_TOMBSTONES_TO_COMPACT
+            && self.num_tombstones > self.pending.len()
+        {
+            self.compact()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{
+            self,
+            OpenOptions,
+        },
+        io::Write,
+    };
+
+    use convex_sync_types::{
+        ClientMessage,
+        LogLinesMessage,
+    };
+    use serde_json::json;
+
+    use super::{
+        FileMutationOutbox,
+        MutationOutbox,
+        OutboxMutation,
+        MIN_TOMBSTONES_TO_COMPACT,
+    };
+    use crate::{
+        base_client::BaseConvexClient,
+        sync::ServerMessage,
+        Value,
+    };
+
+    fn mutation(id: u64) -> OutboxMutation {
+        OutboxMutation {
+            id,
+            udf_path: "messages:send".into(),
+            args: json!({"body": format!("message {id}")}),
+        }
+    }
+
+    #[test]
+    fn test_file_outbox_survives_reopen() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("outbox.jsonl");
+
+        let mut outbox = FileMutationOutbox::open(&path)?;
+        assert_eq!(outbox.load()?, vec![]);
+        outbox.push(&mutation(0))?;
+        outbox.push(&mutation(1))?;
+        outbox.push(&mutation(2))?;
+        outbox.remove(1)?;
+        drop(outbox);
+
+        let mut outbox = FileMutationOutbox::open(&path)?;
+        assert_eq!(outbox.load()?, vec![mutation(0), mutation(2)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_outbox_compacts_tombstones() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("outbox.jsonl");
+
+        let mut outbox = FileMutationOutbox::open(&path)?;
+        let num_mutations = 2 * MIN_TOMBSTONES_TO_COMPACT as u64;
+        for id in 0..num_mutations {
+            outbox.push(&mutation(id))?;
+        }
+        // Removals only append until tombstones outnumber pending mutations.
+        for id in 0..MIN_TOMBSTONES_TO_COMPACT as u64 {
+            outbox.remove(id)?;
+        }
+        let num_lines = || anyhow::Ok(fs::read_to_string(&path)?.lines().count());
+        assert_eq!(
+            num_lines()?,
+            num_mutations as usize + MIN_TOMBSTONES_TO_COMPACT
+        );
+        outbox.remove(MIN_TOMBSTONES_TO_COMPACT as u64)?;
+        assert_eq!(num_lines()?, MIN_TOMBSTONES_TO_COMPACT - 1);
+        // Removing an unknown id is a no-op.
+        outbox.remove(0)?;
+        assert_eq!(num_lines()?, MIN_TOMBSTONES_TO_COMPACT - 1);
+        outbox.remove(num_mutations - 1)?;
+        drop(outbox);
+
+        let mut outbox = FileMutationOutbox::open(&path)?;
+        assert_eq!(
+            outbox.load()?,
+            (MIN_TOMBSTONES_TO_COMPACT as u64 + 1..num_mutations - 1)
+                .map(mutation)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(num_lines()?, MIN_TOMBSTONES_TO_COMPACT - 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_outbox_ignores_truncated_entry() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("outbox.jsonl");
+
+        let mut outbox = FileMutationOutbox::open(&path)?;
+        outbox.push(&mutation(0))?;
+        drop(outbox);
+        OpenOptions::new()
+            .append(true)
+            .open(&path)?
+            .write_all(b"{\"id\":1,\"udfPa")?;
+
+        let mut outbox = FileMutationOutbox::open(&path)?;
+        outbox.push(&mutation(1))?;
+        drop(outbox);
+        let mut outbox = FileMutationOutbox::open(&path)?;
+        assert_eq!(outbox.load()?, vec![mutation(0), mutation(1)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_base_client_replays_outbox() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("outbox.jsonl");
+
+        let mut outbox = FileMutationOutbox::open(&path)?;
+        outbox.push(&mutation(0))?;
+        outbox.push(&mutation(1))?;
+        drop(outbox);
+
+        let mut client = BaseConvexClient::with_outbox(Box::new(FileMutationOutbox::open(&path)?))?;
+        let mut sent = vec![];
+        while let Some(message) = client.pop_next_message() {
+            sent.push(message);
+        }
+        assert_eq!(
+            sent,
+            vec![
+                ClientMessage::Mutation {
+                    request_id: 0,
+                    udf_path: "messages:send".parse()?,
+                    args: vec![json!({"body": "message 0"})],
+                    component_path: None,
+                },
+                ClientMessage::Mutation {
+                    request_id: 1,
+                    udf_path: "messages:send".parse()?,
+                    args: vec![json!({"body": "message 1"})],
+                    component_path: None,
+                },
+            ]
+        );
+
+        // New mutations are recorded after the replayed ones.
+        let _result = client.mutation("messages:send".parse()?, Default::default())?;
+        client
+            .receive_message(ServerMessage::MutationResponse {
+                request_id: 0,
+                result: Ok(Value::Null),
+                ts: None,
+                log_lines: LogLinesMessage(vec![]),
+            })
+            .map_err(anyhow::Error::msg)?;
+        drop(client);
+
+        let mut outbox = FileMutationOutbox::open(&path)?;
+        assert_eq!(
+            outbox.load()?,
+            vec![
+                mutation(1),
+                OutboxMutation {
+                    id: 2,
+                    udf_path: "messages.js:send".into(),
+                    args: json!({}),
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    struct FailingOutbox;
+
+    impl MutationOutbox for FailingOutbox {
+        fn load(&mut self) -> anyhow::Result<Vec<OutboxMutation>> {
+            Ok(vec![])
+        }
+
+        fn push(&mut self, _mutation: &OutboxMutation) -> anyhow::Result<()> {
+            anyhow::bail!("disk full")
+        }
+
+        fn remove(&mut self, _id: u64) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_base_client_doesnt_send_unrecorded_mutation() -> anyhow::Result<()> {
+        let mut client = BaseConvexClient::with_outbox(Box::new(FailingOutbox))?;
+        assert!(client
+            .mutation("messages:send".parse()?, Default::default())
+            .is_err());
+        assert_eq!(client.pop_next_message(), None);
+        Ok(())
+    }
+}
@@ -29,9 +29,12 @@ use crate::SubscriberId;
 use crate::{
     base_client::{
         BaseConvexClient,
+        MutationOutbox,
+        OptimisticLocalStore,
         QueryResults,
     },
     client::{
+        pagination::PaginatedQuerySubscription,
         subscription::{
             QuerySetSubscription,
             QuerySubscription,
@@ -53,10 +56,11 @@ use crate::{
     FunctionResult,
 };
 
+pub mod pagination;
 pub mod subscription;
 mod worker;
 
-const VERSION: Option<&str> = option_env!("CARGO_PKG_VERSION");
+pub(crate) const VERSION: Option<&str> = option_env!("CARGO_PKG_VERSION");
 
 /// An asynchronous client to interact with a specific project to perform
 /// mutations and manage query subscriptions using [`tokio`].
@@ -147,7 +151,10 @@ impl ConvexClient {
         // Listener for when each transaction completes
         let (watch_sender, watch_receiver) = broadcast::channel(1);
 
-        let base_client = BaseConvexClient::new();
+        let base_client = match builder.outbox {
+            Some(outbox) => BaseConvexClient::with_outbox(outbox)?,
+            None => BaseConvexClient::new(),
+        };
 
         let protocol = WebSocketManager::open(
             ws_url,
@@ -244,6 +251,42 @@ impl ConvexClient {
             .expect("INTERNAL BUG: Convex Client dropped prematurely."))
     }
 
+    /// Subscribe to a paginated query `name` with `args`, loading the first
+    /// `initial_num_items` items.
+    ///
+    /// The query must take a `paginationOpts` argument (which is filled in by
+    /// the client, so leave it out of `args`) and return the result of
+    /// `.paginate()`. Load further pages with
+    /// [`PaginatedQuerySubscription::load_more`].
+    ///
+    /// ```no_run
+    /// # use convex::ConvexClient;
+    /// # use convex::PaginationStatus;
+    /// # #[tokio::main]
+    /// # async fn main() -> anyhow::Result<()> {
+    /// let mut client = ConvexClient::new("https://cool-music-123.convex.cloud").await?;
+    /// let mut sub = client
+    ///     .subscribe_paginated("listMessages", maplit::btreemap!{}, 10)
+    ///     .await?;
+    /// while let Some(result) = sub.next().await {
+    ///     let result = result?;
+    ///     println!("{:?}", result.results);
+    ///     if result.status == PaginationStatus::CanLoadMore {
+    ///         sub.load_more(10).await?;
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn subscribe_paginated(
+        &mut self,
+        name: &str,
+        args: BTreeMap<String, Value>,
+        initial_num_items: usize,
+    ) -> anyhow::Result<PaginatedQuerySubscription> {
+        PaginatedQuerySubscription::new(self.clone(), name, args, initial_num_items).await
+    }
+
     /// Perform a mutation `name` with `args` and return a future
     /// containing the return value of the mutation once it completes.
     ///
@@ -268,12 +311,67 @@ impl ConvexClient {
         let (tx, rx) = oneshot::channel();
 
         let udf_path: UdfPath = name.parse()?;
-        let request = MutationRequest { udf_path, args };
+        let request = MutationRequest {
+            udf_path,
+            args,
+            optimistic_update: None,
+        };
 
         self.request_sender
             .send(ClientRequest::Mutation(request, tx))?;
 
-        let res = rx.await?;
+        let res = rx.await??;
+        Ok(res.await?)
+    }
+
+    /// Perform a mutation `name` with `args`, like
+    /// [`mutation`](Self::mutation), while applying `update` to the local
+    /// query results until the server reflects the mutation.
+    ///
+    /// Subscriptions see the optimistic results right away. They're rolled
+    /// back if the mutation fails.
+    ///
+    /// ```no_run
+    /// # use convex::ConvexClient;
+    /// # use convex::Value;
+    /// # #[tokio::main]
+    /// # async fn main() -> anyhow::Result<()> {
+    /// let mut client = ConvexClient::new("https://cool-music-123.convex.cloud").await?;
+    /// let result = client
+    ///     .mutation_with_optimistic_update(
+    ///         "sendMessage",
+    ///         maplit::btreemap! {"body".into() => "Let it be.".into()},
+    ///         |store| {
+    ///             let args = maplit::btreemap! {};
+    ///             if let Some(Value::Array(mut messages)) = store.get_query("listMessages", &args) {
+    ///                 messages.push("Let it be.".into());
+    ///                 store.set_query("listMessages", &args, Some(Value::Array(messages)));
+    ///             }
+    ///         },
+    ///     )
+    ///     .await?;
+    /// println!("{result:?}");
+    /// # Ok(())
+    /// # }
+    pub async fn mutation_with_optimistic_update(
+        &mut self,
+        name: &str,
+        args: BTreeMap<String, Value>,
+        update: impl Fn(&mut OptimisticLocalStore<'_>) + Send + Sync + 'static,
+    ) -> anyhow::Result<FunctionResult> {
+        let (tx, rx) = oneshot::channel();
+
+        let udf_path: UdfPath = name.parse()?;
+        let request = MutationRequest {
+            udf_path,
+            args,
+            optimistic_update: Some(Box::new(update)),
+        };
+
+        self.request_sender
+            .send(ClientRequest::Mutation(request, tx))?;
+
+        let res = rx.await??;
         Ok(res.await?)
     }
 
@@ -400,6 +498,7 @@ pub struct ConvexClientBuilder {
     deployment_url: String,
     client_id: Option<String>,
     on_state_change: Option<mpsc::Sender<WebSocketState>>,
+    outbox: Option<Box<dyn MutationOutbox>>,
 }
 
 impl ConvexClientBuilder {
@@ -409,6 +508,7 @@ impl ConvexClientBuilder {
             deployment_url: deployment_url.to_string(),
             client_id: None,
             on_state_change: None,
+            outbox: None,
         }
     }
 
@@ -425,6 +525,26 @@ impl ConvexClientBuilder {
         self
     }
 
+    /// Record mutations in `outbox` until the server responds to them, and
+    /// replay any mutations left in it by a previous run when the client
+    /// starts.
+    ///
+    /// ```no_run
+    /// # use convex::{base_client::FileMutationOutbox, ConvexClientBuilder};
+    /// # #[tokio::main]
+    /// # async fn main() -> anyhow::Result<()> {
+    /// let client = ConvexClientBuilder::new("https://cool-music-123.convex.cloud")
+    ///     .with_mutation_outbox(FileMutationOutbox::open("outbox.jsonl")?)
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_mutation_outbox(mut self, outbox: impl MutationOutbox + 'static) -> Self {
+        self.outbox = Some(Box::new(outbox));
+        self
+    }
+
     /// Build the [`ConvexClient`] with the configured options.
     ///
     /// ```no_run
@@ -472,9 +592,18 @@ pub mod tests {
 
     use super::ConvexClient;
     use crate::{
-        base_client::FunctionResult,
+        base_client::{
+            FileMutationOutbox,
+            FunctionResult,
+            MutationOutbox,
+            OutboxMutation,
+        },
         client::{
             deployment_to_ws_url,
+            pagination::{
+                PaginatedQueryResult,
+                PaginationStatus,
+            },
             worker::worker,
             BaseConvexClient,
         },
@@ -488,6 +617,12 @@ pub mod tests {
 
     impl ConvexClient {
         pub async fn with_test_protocol() -> anyhow::Result<(Self, TestProtocolManager)> {
+            Self::with_test_protocol_and_base_client(BaseConvexClient::new()).await
+        }
+
+        pub async fn with_test_protocol_and_base_client(
+            base_client: BaseConvexClient,
+        ) -> anyhow::Result<(Self, TestProtocolManager)> {
             let _ = tracing_subscriber::fmt()
                 .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
                 .try_init();
@@ -506,7 +641,6 @@ pub mod tests {
                 "rust-0.0.1",
             )
             .await?;
-            let base_client = BaseConvexClient::new();
 
             let listen_handle = tokio::spawn(worker(
                 response_receiver,
@@ -963,6 +1097,350 @@ pub mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_optimistic_update_rolled_back_on_failure() -> anyhow::Result<()> {
+        let (mut client, mut test_protocol) = ConvexClient::with_test_protocol().await?;
+
+        let mut subscription = client.subscribe("getValue1", btreemap! {}).await?;
+        test_protocol
+            .fake_server_response(
+                fake_transition(
+                    StateVersion::initial(),
+                    vec![(subscription.query_id(), 10.into())],
+                )
+                .0,
+            )
+            .await?;
+        assert_eq!(
+            subscription.next().await,
+            Some(FunctionResult::Value(10.into()))
+        );
+        test_protocol.take_sent().await;
+
+        let res = tokio::spawn(async move {
+            client
+                .mutation_with_optimistic_update("incrementCounter", btreemap! {}, |store| {
+                    if let Some(Value::Int64(value)) = store.get_query("getValue1", &btreemap! {}) {
+                        store.set_query("getValue1", &btreemap! {}, Some((value + 1).into()));
+                    }
+                })
+                .await
+        });
+        assert_eq!(
+            subscription.next().await,
+            Some(FunctionResult::Value(11.into()))
+        );
+
+        let mutation_result = FunctionResult::ErrorMessage("JEEPERS".into());
+        let (mut_resp, _transition) = fake_mutation_response(mutation_result.clone());
+        test_protocol.wait_until_n_messages_sent(1).await;
+        test_protocol.fake_server_response(mut_resp).await?;
+        assert_eq!(res.await??, mutation_result);
+        assert_eq!(
+            subscription.next().await,
+            Some(FunctionResult::Value(10.into()))
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_optimistic_update_replaced_by_server_value() -> anyhow::Result<()> {
+        let (mut client, mut test_protocol) = ConvexClient::with_test_protocol().await?;
+
+        let mut subscription = client.subscribe("getValue1", btreemap! {}).await?;
+        let query_id = subscription.query_id();
+        let (transition, version) =
+            fake_transition(StateVersion::initial(), vec![(query_id, 10.into())]);
+        test_protocol.fake_server_response(transition).await?;
+        assert_eq!(
+            subscription.next().await,
+            Some(FunctionResult::Value(10.into()))
+        );
+        test_protocol.take_sent().await;
+
+        let res = tokio::spawn(async move {
+            client
+                .mutation_with_optimistic_update("incrementCounter", btreemap! {}, |store| {
+                    if let Some(Value::Int64(value)) = store.get_query("getValue1", &btreemap! {}) {
+                        store.set_query("getValue1", &btreemap! {}, Some((value + 1).into()));
+                    }
+                })
+                .await
+        });
+        assert_eq!(
+            subscription.next().await,
+            Some(FunctionResult::Value(11.into()))
+        );
+
+        // Another client's write landed first, so the server's value differs from
+        // the optimistic one. It replaces it instead of having the update applied
+        // on top.
+        test_protocol.wait_until_n_messages_sent(1).await;
+        let (transition, version) = fake_transition(version, vec![(query_id, 12.into())]);
+        test_protocol
+            .fake_server_response(ServerMessage::MutationResponse {
+                request_id: 0,
+                result: Ok(Value::Null),
+                ts: Some(version.ts),
+                log_lines: LogLinesMessage(vec![]),
+            })
+            .await?;
+        test_protocol.fake_server_response(transition).await?;
+        assert_eq!(res.await??, FunctionResult::Value(Value::Null));
+        assert_eq!(
+            subscription.next().await,
+            Some(FunctionResult::Value(12.into()))
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_outbox_replayed_on_start() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("outbox.jsonl");
+        let mut outbox = FileMutationOutbox::open(&path)?;
+        outbox.push(&OutboxMutation {
+            id: 0,
+            udf_path: "incrementCounter".into(),
+            args: json!({"body": "hello"}),
+        })?;
+        drop(outbox);
+
+        let base_client =
+            BaseConvexClient::with_outbox(Box::new(FileMutationOutbox::open(&path)?))?;
+        let (mut client, mut test_protocol) =
+            ConvexClient::with_test_protocol_and_base_client(base_client).await?;
+        test_protocol.wait_until_n_messages_sent(2).await;
+        assert_eq!(
+            test_protocol.take_sent().await[1..],
+            [ClientMessage::Mutation {
+                request_id: 0,
+                udf_path: UdfPath::from_str("incrementCounter")?,
+                args: vec![json!({"body": "hello"})],
+                component_path: None,
+            }]
+        );
+
+        // New mutations are sent after the replayed one.
+        let res =
+            tokio::spawn(async move { client.mutation("incrementCounter", btreemap! {}).await });
+        test_protocol.wait_until_n_messages_sent(1).await;
+        assert_eq!(
+            test_protocol.take_sent().await,
+            vec![ClientMessage::Mutation {
+                request_id: 1,
+                udf_path: UdfPath::from_str("incrementCounter")?,
+                args: vec![json!({})],
+                component_path: None,
+            }]
+        );
+
+        let (transition, version) = fake_transition(StateVersion::initial(), vec![]);
+        for request_id in [0, 1] {
+            test_protocol
+                .fake_server_response(ServerMessage::MutationResponse {
+                    request_id,
+                    result: Ok(Value::Null),
+                    ts: Some(version.ts),
+                    log_lines: LogLinesMessage(vec![]),
+                })
+                .await?;
+        }
+        test_protocol.fake_server_response(transition).await?;
+        assert_eq!(res.await??, FunctionResult::Value(Value::Null));
+
+        // Both mutations were acknowledged, so there's nothing left to replay.
+        assert_eq!(FileMutationOutbox::open(&path)?.load()?, vec![]);
+        Ok(())
+    }
+
+    fn added_queries(messages: Vec<ClientMessage>) -> Vec<(QueryId, serde_json::Value)> {
+        messages
+            .into_iter()
+            .flat_map(|message| match message {
+                ClientMessage::ModifyQuerySet { modifications, .. } => modifications,
+                _ => vec![],
+            })
+            .filter_map(|modification| match modification {
+                QuerySetModification::Add(query) => Some((query.query_id, query.args[0].clone())),
+                QuerySetModification::Remove { .. } => None,
+            })
+            .collect()
+    }
+
+    fn fake_page(items: Vec<&str>, is_done: bool, continue_cursor: &str) -> anyhow::Result<Value> {
+        Value::try_from(json!({
+            "page": items,
+            "isDone": is_done,
+            "continueCursor": continue_cursor,
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_paginated_subscription() -> anyhow::Result<()> {
+        let (mut client, mut test_protocol) = ConvexClient::with_test_protocol().await?;
+
+        let mut subscription = client
+            .subscribe_paginated("listMessages", btreemap! {}, 2)
+            .await?;
+        let added = added_queries(test_protocol.take_sent().await);
+        assert_eq!(added.len(), 1);
+        let (first_page, first_args) = added[0].clone();
+        let pagination_id = first_args["paginationOpts"]["id"].clone();
+        assert_eq!(
+            first_args,
+            json!({"paginationOpts": {"numItems": 2.0, "cursor": null, "id": pagination_id}})
+        );
+        assert_eq!(
+            subscription.next().await.unwrap()?,
+            PaginatedQueryResult {
+                results: vec![],
+                status: PaginationStatus::LoadingFirstPage,
+            }
+        );
+
+        let (transition, version) = fake_transition(
+            StateVersion::initial(),
+            vec![(first_page, fake_page(vec!["a", "b"], false, "c1")?)],
+        );
+        test_protocol.fake_server_response(transition).await?;
+        assert_eq!(
+            subscription.next().await.unwrap()?,
+            PaginatedQueryResult {
+                results: vec!["a".into(), "b".into()],
+                status: PaginationStatus::CanLoadMore,
+            }
+        );
+
+        // Loading more pins the first page to end at its continue cursor.
+        assert!(subscription.load_more(2).await?);
+        let added = added_queries(test_protocol.take_sent().await);
+        assert_eq!(
+            added
+                .iter()
+                .map(|(_, args)| args.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                json!({"paginationOpts": {
+                    "numItems": 2.0,
+                    "cursor": null,
+                    "endCursor": "c1",
+                    "id": pagination_id,
+                }}),
+                json!({"paginationOpts": {"numItems": 2.0, "cursor": "c1", "id": pagination_id}}),
+            ]
+        );
+        let (pinned_page, second_page) = (added[0].0, added[1].0);
+        assert_eq!(
+            subscription.next().await.unwrap()?,
+            PaginatedQueryResult {
+                results: vec!["a".into(), "b".into()],
+                status: PaginationStatus::LoadingMore,
+            }
+        );
+
+        let (transition, _) = fake_transition(
+            version,
+            vec![
+                (pinned_page, fake_page(vec!["a", "b"], false, "c1")?),
+                (second_page, fake_page(vec!["c"], true, "c2")?),
+            ],
+        );
+        test_protocol.fake_server_response(transition).await?;
+        assert_eq!(
+            subscription.next().await.unwrap()?,
+            PaginatedQueryResult {
+                results: vec!["a".into(), "b".into(), "c".into()],
+                status: PaginationStatus::Exhausted,
+            }
+        );
+        assert!(!subscription.load_more(2).await?);
+
+        // Swapping in the pinned page unsubscribes the original one.
+        test_protocol.wait_until_n_messages_sent(1).await;
+        assert_eq!(
+            test_protocol.take_sent().await,
+            vec![ClientMessage::ModifyQuerySet {
+                base_version: 3,
+                new_version: 4,
+                modifications: vec![QuerySetModification::Remove {
+                    query_id: first_page
+                }],
+            }]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_paginated_subscription_split() -> anyhow::Result<()> {
+        let (mut client, mut test_protocol) = ConvexClient::with_test_protocol().await?;
+
+        let mut subscription = client
+            .subscribe_paginated("listMessages", btreemap! {}, 1)
+            .await?;
+        let (first_page, _) = added_queries(test_protocol.take_sent().await)[0].clone();
+        subscription.next().await.unwrap()?;
+
+        let mut page = fake_page(vec!["a", "b", "c"], false, "c3")?;
+        if let Value::Object(fields) = &mut page {
+            fields.insert("splitCursor".into(), "c1".into());
+            fields.insert("pageStatus".into(), "SplitRecommended".into());
+        }
+        let (transition, version) =
+            fake_transition(StateVersion::initial(), vec![(first_page, page)]);
+        test_protocol.fake_server_response(transition).await?;
+        assert_eq!(
+            subscription.next().await.unwrap()?,
+            PaginatedQueryResult {
+                results: vec!["a".into(), "b".into(), "c".into()],
+                status: PaginationStatus::CanLoadMore,
+            }
+        );
+
+        // The oversized page is split into two halves, ending where it ended.
+        let added = added_queries(test_protocol.take_sent().await);
+        let pagination_opts: Vec<_> = added
+            .iter()
+            .map(|(_, args)| {
+                (
+                    args["paginationOpts"]["cursor"].clone(),
+                    args["paginationOpts"]["endCursor"].clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            pagination_opts,
+            vec![(json!(null), json!("c1")), (json!("c1"), json!("c3")),]
+        );
+
+        // The merged results don't change once the halves are swapped in.
+        let (transition, _) = fake_transition(
+            version,
+            vec![
+                (added[0].0, fake_page(vec!["a"], false, "c1")?),
+                (added[1].0, fake_page(vec!["b", "c"], false, "c3")?),
+            ],
+        );
+        test_protocol.fake_server_response(transition).await?;
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), subscription.next())
+                .await
+                .is_err()
+        );
+        test_protocol.wait_until_n_messages_sent(1).await;
+        assert_eq!(
+            test_protocol.take_sent().await,
+            vec![ClientMessage::ModifyQuerySet {
+                base_version: 3,
+                new_version: 4,
+                modifications: vec![QuerySetModification::Remove {
+                    query_id: first_page
+                }],
+            }]
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_deployment_url() -> anyhow::Result<()> {
         assert_eq!(
@@ -0,0 +1,400 @@
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use futures::StreamExt;
+
+use crate::{
+    base_client::{
+        FunctionResult,
+        QueryResults,
+    },
+    client::{
+        subscription::{
+            QuerySetSubscription,
+            QuerySubscription,
+        },
+        ConvexClient,
+    },
+    value::Value,
+};
+
+/// The loading state of a [`PaginatedQuerySubscription`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaginationStatus {
+    /// The first page hasn't loaded yet.
+    LoadingFirstPage,
+    /// All loaded pages are available and there are more results after the
+    /// last one.
+    CanLoadMore,
+    /// A page requested with
+    /// [`load_more`](PaginatedQuerySubscription::load_more) (or a page being
+    /// split by the server) hasn't loaded yet.
+    LoadingMore,
+    /// All loaded pages are available and the last one reached the end of the
+    /// query.
+    Exhausted,
+}
+
+/// A consistent view of all the pages loaded by a
+/// [`PaginatedQuerySubscription`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct PaginatedQueryResult {
+    /// The concatenated items of every loaded page, in order.
+    pub results: Vec<Value>,
+    /// Whether more pages are loading or can be loaded.
+    pub status: PaginationStatus,
+}
+
+/// A reactive subscription to a paginated query, i.e. a query that takes a
+/// `paginationOpts` argument and returns the result of `.paginate()`.
+///
+/// It is returned by [`ConvexClient::subscribe_paginated`]. Pages are loaded
+/// on demand with [`load_more`](Self::load_more), and every loaded page stays
+/// subscribed, so [`next`](Self::next) yields a new
+/// [`PaginatedQueryResult`] whenever any of them changes. All pages are read
+/// from the same consistent snapshot of the query set.
+///
+/// Each page after the first starts at the previous page's continue cursor,
+/// and the previous page is pinned to end there, so pages never overlap or
+/// leave gaps, even across reconnects. When the server reports that a page has
+/// grown too large to keep reading it (via `splitCursor` and `pageStatus`),
+/// the page is split in two and the halves are swapped in once both have
+/// loaded.
+///
+/// The pages are unsubscribed when this subscription is dropped.
+pub struct PaginatedQuerySubscription {
+    client: ConvexClient,
+    name: String,
+    args: BTreeMap<String, Value>,
+    initial_num_items: usize,
+    pagination_id: u32,
+    pages: Vec<Page>,
+    watch: QuerySetSubscription,
+    last_emitted: Option<Result<PaginatedQueryResult, String>>,
+}
+
+/// A single subscribed page query.
+struct PageQuery {
+    subscription: QuerySubscription,
+    cursor: Option<String>,
+    end_cursor: Option<String>,
+    num_items: usize,
+    result: Option<FunctionResult>,
+}
+
+/// A loaded page, along with the queries that will replace it once they all
+/// have results. Replacements are used for pinning a page's end cursor and
+/// for splitting a page that has grown too large.
+struct Page {
+    query: PageQuery,
+    replacement: Vec<PageQuery>,
+}
+
+/// The fields of a `PaginationResult` we care about.
+struct PaginationResult {
+    page: Vec<Value>,
+    is_done: bool,
+    continue_cursor: String,
+    split_cursor: Option<String>,
+    page_status: Option<String>,
+}
+
+impl PaginationResult {
+    fn parse(value: &Value) -> anyhow::Result<Self> {
+        let Value::Object(fields) = value else {
+            anyhow::bail!("Paginated query didn't return an object: {value:?}");
+        };
+        let page = match fields.get("page") {
+            Some(Value::Array(page)) => page.clone(),
+            _ => anyhow::bail!("Paginated query result is missing `page`"),
+        };
+        let is_done = match fields.get("isDone") {
+            Some(Value::Boolean(is_done)) => *is_done,
+            _ => anyhow::bail!("Paginated query result is missing `isDone`"),
+        };
+        let continue_cursor = match fields.get("continueCursor") {
+            Some(Value::String(cursor)) => cursor.clone(),
+            _ => anyhow::bail!("Paginated query result is missing `continueCursor`"),
+        };
+        let split_cursor = match fields.get("splitCursor") {
+            Some(Value::String(cursor)) => Some(cursor.clone()),
+            _ => None,
+        };
+        let page_status = match fields.get("pageStatus") {
+            Some(Value::String(status)) => Some(status.clone()),
+            _ => None,
+        };
+        Ok(Self {
+            page,
+            is_done,
+            continue_cursor,
+            split_cursor,
+            page_status,
+        })
+    }
+
+    fn split_required(&self) -> bool {
+        self.page_status.as_deref() == Some("SplitRequired")
+    }
+
+    fn should_split(&self, num_items: usize) -> bool {
+        self.split_cursor.is_some()
+            && (self.split_required()
+                || self.page_status.as_deref() == Some("SplitRecommended")
+                || self.page.len() > num_items * 2)
+    }
+}
+
+impl PaginatedQuerySubscription {
+    pub(super) async fn new(
+        client: ConvexClient,
+        name: &str,
+        args: BTreeMap<String, Value>,
+        initial_num_items: usize,
+    ) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            !args.contains_key("paginationOpts"),
+            "Paginated query args must not contain `paginationOpts`"
+        );
+        // Subscribe to the query set before the first page so we can't miss its
+        // first result.
+        let watch = client.watch_all();
+        let mut subscription = Self {
+            client,
+            name: name.to_string(),
+            args,
+            initial_num_items,
+            pagination_id: rand::random(),
+            pages: vec![],
+            watch,
+            last_emitted: None,
+        };
+        let first_page = subscription
+            .subscribe_page(None, None, initial_num_items)
+            .await?;
+        subscription.pages.push(Page::new(first_page));
+        Ok(subscription)
+    }
+
+    /// Wait for the next change to the loaded pages and return their merged
+    /// results.
+    ///
+    /// Returns an error if any page query fails, and `None` once the client
+    /// has shut down.
+    pub async fn next(&mut self) -> Option<anyhow::Result<PaginatedQueryResult>> {
+        loop {
+            if let Err(e) = self.maintain_pages().await {
+                return Some(Err(e));
+            }
+            let merged = self.merged_result();
+            let emitted = merged.as_ref().map_err(|e| e.to_string()).cloned();
+            if self.last_emitted.as_ref() != Some(&emitted) {
+                self.last_emitted = Some(emitted);
+                return Some(merged);
+            }
+            let results = self.watch.next().await?;
+            self.update_results(&results);
+        }
+    }
+
+    /// Request another page of `num_items` items after the last loaded page.
+    ///
+    /// Returns `false` without doing anything if the last page hasn't loaded
+    /// yet or the query is exhausted.
+    pub async fn load_more(&mut self, num_items: usize) -> anyhow::Result<bool> {
+        let last = self.pages.last().context("Paginated query has no pages")?;
+        let Some(FunctionResult::Value(value)) = &last.query.result else {
+            return Ok(false);
+        };
+        let result = PaginationResult::parse(value)?;
+        if result.is_done {
+            return Ok(false);
+        }
+        let continue_cursor = result.continue_cursor;
+        // Pin the previous page to end where the new one starts, so that it can't
+        // grow into (or shrink away from) the new page.
+        if last.query.end_cursor.is_none() && last.replacement.is_empty() {
+            let cursor = last.query.cursor.clone();
+            let page_num_items = last.query.num_items;
+            let pinned = self
+                .subscribe_page(cursor, Some(continue_cursor.clone()), page_num_items)
+                .await?;
+            self.pages
+                .last_mut()
+                .expect("Checked above")
+                .replacement
+                .push(pinned);
+        }
+        let next_page = self
+            .subscribe_page(Some(continue_cursor), None, num_items)
+            .await?;
+        self.pages.push(Page::new(next_page));
+        Ok(true)
+    }
+
+    async fn subscribe_page(
+        &mut self,
+        cursor: Option<String>,
+        end_cursor: Option<String>,
+        num_items: usize,
+    ) -> anyhow::Result<PageQuery> {
+        let mut pagination_opts = BTreeMap::new();
+        pagination_opts.insert("numItems".to_string(), Value::Float64(num_items as f64));
+        pagination_opts.insert("cursor".to_string(), cursor.clone().into());
+        if let Some(end_cursor) = &end_cursor {
+            pagination_opts.insert("endCursor".to_string(), end_cursor.clone().into());
+        }
+        pagination_opts.insert("id".to_string(), Value::Float64(self.pagination_id as f64));
+        let mut args = self.args.clone();
+        args.insert("paginationOpts".to_string(), Value::Object(pagination_opts));
+
+        let mut subscription = self.client.subscribe(&self.name, args).await?;
+        let result = subscription.initial.take();
+        Ok(PageQuery {
+            subscription,
+            cursor,
+            end_cursor,
+            num_items,
+            result,
+        })
+    }
+
+    fn update_results(&mut self, results: &QueryResults) {
+        for page in &mut self.pages {
+            for query in std::iter::once(&mut page.query).chain(page.replacement.iter_mut()) {
+                if let Some(result) = results.get(query.subscription.id()) {
+                    query.result = Some(result.clone());
+                }
+            }
+        }
+    }
+
+    /// Restart from the first page if a cursor became invalid, swap in
+    /// replacements that have fully loaded, and start splitting pages that
+    /// have grown too large.
+    async fn maintain_pages(&mut self) -> anyhow::Result<()> {
+        let invalid_cursor = self.pages.iter().any(|page| {
+            matches!(
+                &page.query.result,
+                Some(FunctionResult::ErrorMessage(message)) if message.contains("InvalidCursor")
+            )
+        });
+        if invalid_cursor {
+            tracing::warn!(
+                "Paginated query {} hit an invalid cursor, restarting from the first page",
+                self.name
+            );
+            self.pages.clear();
+            self.pagination_id = rand::random();
+            let first_page = self
+                .subscribe_page(None, None, self.initial_num_items)
+                .await?;
+            self.pages.push(Page::new(first_page));
+            return Ok(());
+        }
+
+        let mut i = 0;
+        while i < self.pages.len() {
+            let page = &self.pages[i];
+            let replacement_loaded = !page.replacement.is_empty()
+                && page
+                    .replacement
+                    .iter()
+                    .all(|query| matches!(query.result, Some(FunctionResult::Value(_))));
+            if replacement_loaded {
+                let replacement = std::mem::take(&mut self.pages[i].replacement);
+                let num_replacements = replacement.len();
+                self.pages
+                    .splice(i..=i, replacement.into_iter().map(Page::new));
+                i += num_replacements;
+                continue;
+            }
+            if page.replacement.is_empty() {
+                if let Some(FunctionResult::Value(value)) = &page.query.result {
+                    let result = PaginationResult::parse(value)?;
+                    if result.should_split(page.query.num_items) {
+                        let split_cursor = result.split_cursor.expect("Checked by should_split");
+                        let cursor = page.query.cursor.clone();
+                        let end_cursor = page
+                            .query
+                            .end_cursor
+                            .clone()
+                            .unwrap_or(result.continue_cursor);
+                        let num_items = page.query.num_items;
+                        tracing::debug!("Splitting page {i} of paginated query {}", self.name);
+                        let first_half = self
+                            .subscribe_page(cursor, Some(split_cursor.clone()), num_items)
+                            .await?;
+                        let second_half = self
+                            .subscribe_page(Some(split_cursor), Some(end_cursor), num_items)
+                            .await?;
+                        self.pages[i].replacement = vec![first_half, second_half];
+                    }
+                }
+            }
+            i += 1;
+        }
+        Ok(())
+    }
+
+    fn merged_result(&self) -> anyhow::Result<PaginatedQueryResult> {
+        let mut results = vec![];
+        for (i, page) in self.pages.iter().enumerate() {
+            let loading = if i == 0 {
+                PaginationStatus::LoadingFirstPage
+            } else {
+                PaginationStatus::LoadingMore
+            };
+            let value = match &page.query.result {
+                None => {
+                    return Ok(PaginatedQueryResult {
+                        results,
+                        status: loading,
+                    })
+                },
+                Some(FunctionResult::Value(value)) => value,
+                Some(FunctionResult::ErrorMessage(message)) => anyhow::bail!(message.clone()),
+                Some(FunctionResult::ConvexError(error)) => return Err(error.clone().into()),
+            };
+            let result = PaginationResult::parse(value)?;
+            if result.split_required() {
+                // The page stopped early; wait for its halves to load.
+                return Ok(PaginatedQueryResult {
+                    results,
+                    status: loading,
+                });
+            }
+            results.extend(result.page);
+            if i == self.pages.len() - 1 {
+                let status = if result.is_done {
+                    PaginationStatus::Exhausted
+                } else {
+                    PaginationStatus::CanLoadMore
+                };
+                return Ok(PaginatedQueryResult { results, status });
+            }
+        }
+        Ok(PaginatedQueryResult {
+            results,
+            status: PaginationStatus::LoadingFirstPage,
+        })
+    }
+}
+
+impl Page {
+    fn new(query: PageQuery) -> Self {
+        Self {
+            query,
+            replacement: vec![],
+        }
+    }
+}
+
+impl std::fmt::Debug for PaginatedQuerySubscription {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PaginatedQuerySubscription")
+            .field("name", &self.name)
+            .field("num_pages", &self.pages.len())
+            .finish()
+    }
+}
@@ -27,6 +27,7 @@ use tokio_stream::wrappers::{
 use crate::{
     base_client::{
         BaseConvexClient,
+        OptimisticUpdate,
         SubscriberId,
     },
     client::{
@@ -49,7 +50,7 @@ const MAX_BACKOFF: Duration = Duration::from_secs(15);
 pub enum ClientRequest {
     Mutation(
         MutationRequest,
-        oneshot::Sender<oneshot::Receiver<FunctionResult>>,
+        oneshot::Sender<anyhow::Result<oneshot::Receiver<FunctionResult>>>,
     ),
     Action(
         ActionRequest,
@@ -67,6 +68,7 @@ pub enum ClientRequest {
 pub struct MutationRequest {
     pub udf_path: UdfPath,
     pub args: BTreeMap<String, Value>,
+    pub optimistic_update: Option<OptimisticUpdate>,
 }
 
 pub struct ActionRequest {
@@ -98,6 +100,9 @@ pub async fn worker<T: SyncProtocol>(
     let mut backoff = Backoff::new(INITIAL_BACKOFF, MAX_BACKOFF);
     let mut protocol_response_stream = ReceiverStream::new(protocol_response_receiver).fuse();
     let mut client_request_stream = UnboundedReceiverStream::new(client_request_receiver).fuse();
+    // Send any mutations the base client queued up before we started, e.g.
+    // ones replayed from its outbox.
+    flush_messages(&mut base_client, &mut protocol_manager).await;
     loop {
         let e = loop {
             match _worker_once(
@@ -182,9 +187,20 @@ async fn _worker_once<T: SyncProtocol>(
                     let MutationRequest {
                         udf_path,
                         args,
+                        optimistic_update,
                     } = mutation;
-                    let result_receiver = base_client
-                        .mutation(udf_path, args);
+                    let result_receiver = match optimistic_update {
+                        Some(update) => {
+                            let result_receiver = base_client
+                                .mutation_with_optimistic_update(udf_path, args, update);
+                            if result_receiver.is_ok() {
+                                // Notify watchers of the optimistic query results
+                                let _ = watch_sender.send(base_client.latest_results().clone());
+                            }
+                            result_receiver
+                        },
+                        None => base_client.mutation(udf_path, args),
+                    };
                     flush_messages(base_client, protocol_manager).await;
                     let _ = tx.send(result_receiver);
                 },
@@ -0,0 +1,411 @@
+use std::{
+    collections::{
+        BTreeMap,
+        BTreeSet,
+    },
+    fmt::Write,
+};
+
+use super::validator::{
+    Field,
+    Literal,
+    Validator,
+};
+
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let",
+    "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return",
+    "static", "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use",
+    "virtual", "where", "while", "yield",
+];
+
+/// Split a JS identifier into lowercase words at `_`, `-`, `.` and case
+/// boundaries, e.g. `getHTTPValue` into `get`, `http`, `value`.
+fn words(s: &str) -> Vec<String> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut words = vec![];
+    let mut current = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if !c.is_ascii_alphanumeric() {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+        if c.is_ascii_uppercase() && !current.is_empty() {
+            let prev = chars[i - 1];
+            let next_is_lower = chars.get(i + 1).is_some_and(|n| n.is_ascii_lowercase());
+            if prev.is_ascii_lowercase()
+                || prev.is_ascii_digit()
+                || (prev.is_ascii_uppercase() && next_is_lower)
+            {
+                words.push(std::mem::take(&mut current));
+            }
+        }
+        current.push(c.to_ascii_lowercase());
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+/// A `snake_case` identifier for a JS field, function or module name.
+pub(crate) fn snake_ident(s: &str) -> String {
+    let mut ident = words(s).join("_");
+    if ident.is_empty() {
+        ident = "_".to_string();
+    }
+    if ident.starts_with(|c: char| c.is_ascii_digit()) {
+        ident.insert(0, '_');
+    }
+    match ident.as_str() {
+        "self" | "super" | "crate" | "_" => format!("{ident}_"),
+        _ if KEYWORDS.contains(&ident.as_str()) => format!("r#{ident}"),
+        _ => ident,
+    }
+}
+
+/// A `PascalCase` type or variant name.
+pub(crate) fn pascal_ident(s: &str) -> String {
+    let mut ident: String = words(s)
+        .into_iter()
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect();
+    if ident.is_empty() || ident.starts_with(|c: char| c.is_ascii_digit()) {
+        ident.insert(0, 'V');
+    }
+    if ident == "Self" {
+        ident.push('_');
+    }
+    ident
+}
+
+fn literal_type(literal: &Literal) -> &'static str {
+    match literal {
+        Literal::String(_) => "String",
+        Literal::Float64(_) => "f64",
+        Literal::Int64 => "i64",
+        Literal::Boolean(_) => "bool",
+    }
+}
+
+/// An expression for a literal's value, for literals we can check.
+fn literal_value(literal: &Literal) -> Option<String> {
+    match literal {
+        Literal::String(s) => Some(format!("::convex::Value::String({s:?}.to_string())")),
+        Literal::Float64(f) => Some(format!("::convex::Value::Float64({f:?})")),
+        Literal::Boolean(b) => Some(format!("::convex::Value::Boolean({b})")),
+        Literal::Int64 => None,
+    }
+}
+
+/// A type that already exists elsewhere in the generated code, like a table's
+/// document struct, and can be reused instead of generating a new one.
+pub(crate) struct KnownType {
+    pub validator: Validator,
+    pub path: String,
+}
+
+/// Collects the type definitions for one generated Rust module.
+pub(crate) struct ModuleEmitter<'a> {
+    known_types: &'a [KnownType],
+    type_names: BTreeSet<String>,
+    pub items: Vec<String>,
+}
+
+impl<'a> ModuleEmitter<'a> {
+    pub fn new(known_types: &'a [KnownType]) -> Self {
+        Self {
+            known_types,
+            type_names: BTreeSet::new(),
+            items: vec![],
+        }
+    }
+
+    fn reserve_name(&mut self, name: &str) -> String {
+        let mut candidate = name.to_string();
+        let mut suffix = 2;
+        while !self.type_names.insert(candidate.clone()) {
+            candidate = format!("{name}{suffix}");
+            suffix += 1;
+        }
+        candidate
+    }
+
+    /// Return the Rust type for `validator`, defining any structs or enums it
+    /// needs with names derived from `name`.
+    pub fn rust_type(&mut self, validator: &Validator, name: &str) -> String {
+        if let Some(known) = self.known_types.iter().find(|k| &k.validator == validator) {
+            return known.path.clone();
+        }
+        match validator {
+            Validator::Id(_) | Validator::String => "String".to_string(),
+            Validator::Null => "()".to_string(),
+            Validator::Float64 => "f64".to_string(),
+            Validator::Int64 => "i64".to_string(),
+            Validator::Boolean => "bool".to_string(),
+            Validator::Bytes => "Vec<u8>".to_string(),
+            Validator::Any => "::convex::Value".to_string(),
+            Validator::Literal(literal) => literal_type(literal).to_string(),
+            Validator::Array(item) => {
+                format!("Vec<{}>", self.rust_type(item, &format!("{name}Item")))
+            },
+            Validator::Record(value) => format!(
+                "::std::collections::BTreeMap<String, {}>",
+                self.rust_type(value, &format!("{name}Value"))
+            ),
+            Validator::Object(fields) => self.emit_struct(name, fields),
+            Validator::Union(members) => {
+                let non_null: Vec<&Validator> =
+                    members.iter().filter(|m| **m != Validator::Null).collect();
+                if non_null.len() < members.len() {
+                    let inner = match &non_null[..] {
+                        [] => return "()".to_string(),
+                        [member] => self.rust_type(member, name),
+                        _ => self.rust_type(
+                            &Validator::Union(non_null.into_iter().cloned().collect()),
+                            name,
+                        ),
+                    };
+                    return format!("Option<{inner}>");
+                }
+                let string_literals: Option<Vec<&str>> = members
+                    .iter()
+                    .map(|m| match m {
+                        Validator::Literal(Literal::String(s)) => Some(s.as_str()),
+                        _ => None,
+                    })
+                    .collect();
+                match (string_literals, &members[..]) {
+                    (_, [member]) => self.rust_type(member, name),
+                    (Some(literals), _) => self.emit_string_enum(name, &literals),
+                    (None, _) => self.emit_union_enum(name, members),
+                }
+            },
+        }
+    }
+
+    pub fn emit_struct(&mut self, name: &str, fields: &BTreeMap<String, Field>) -> String {
+        let name = self.reserve_name(name);
+        let mut field_idents = BTreeSet::new();
+        let mut definition = String::new();
+        let mut into_value = String::new();
+        let mut from_value = String::new();
+        for (field_name, field) in fields {
+            let mut ident = snake_ident(field_name.trim_start_matches('_'));
+            while !field_idents.insert(ident.clone()) {
+                ident.push('_');
+            }
+            let ty = self.rust_type(
+                &field.validator,
+                &format!("{name}{}", pascal_ident(field_name)),
+            );
+            if field.optional {
+                writeln!(definition, "    pub {ident}: Option<{ty}>,").unwrap();
+                writeln!(
+                    into_value,
+                    "        if let Some(value) = self.{ident} {{\n            \
+                     fields.insert({field_name:?}.to_string(), \
+                     ::convex::typed::ConvexType::into_value(value));\n        }}"
+                )
+                .unwrap();
+                writeln!(
+                    from_value,
+                    "            {ident}: ::convex::typed::optional_field(&mut fields, \
+                     {field_name:?})?,"
+                )
+                .unwrap();
+            } else {
+                writeln!(definition, "    pub {ident}: {ty},").unwrap();
+                writeln!(
+                    into_value,
+                    "        fields.insert({field_name:?}.to_string(), \
+                     ::convex::typed::ConvexType::into_value(self.{ident}));"
+                )
+                .unwrap();
+                // Literal fields are checked so that unions of objects tagged with a
+                // literal decode into the right variant.
+                let decode = match &field.validator {
+                    Validator::Literal(literal) => match literal_value(literal) {
+                        Some(expected) => format!(
+                            "::convex::typed::literal_field(&mut fields, {field_name:?}, \
+                             {expected})?"
+                        ),
+                        None => format!("::convex::typed::field(&mut fields, {field_name:?})?"),
+                    },
+                    _ => format!("::convex::typed::field(&mut fields, {field_name:?})?"),
+                };
+                writeln!(from_value, "            {ident}: {decode},").unwrap();
+            }
+        }
+        self.items.push(format!(
+            "#[derive(Clone, Debug, PartialEq)]
+pub struct {name} {{
+{definition}}}
+
+impl ::convex::typed::ConvexType for {name} {{
+    #[allow(unused_mut)]
+    fn into_value(self) -> ::convex::Value {{
+        let mut fields = ::std::collections::BTreeMap::new();
+{into_value}        ::convex::Value::Object(fields)
+    }}
+
+    #[allow(unused_mut, unused_variables)]
+    fn from_value(value: ::convex::Value) -> ::convex::typed::Result<Self> {{
+        let mut fields = ::convex::typed::object_fields(value, {name:?})?;
+        Ok(Self {{
+{from_value}        }})
+    }}
+}}
+"
+        ));
+        name
+    }
+
+    fn emit_string_enum(&mut self, name: &str, literals: &[&str]) -> String {
+        let name = self.reserve_name(name);
+        let mut variant_names = BTreeSet::new();
+        let mut definition = String::new();
+        let mut into_value = String::new();
+        let mut from_value = String::new();
+        for literal in literals {
+            let mut variant = pascal_ident(literal);
+            while !variant_names.insert(variant.clone()) {
+                variant.push('_');
+            }
+            writeln!(definition, "    {variant},").unwrap();
+            writeln!(into_value, "            {name}::{variant} => {literal:?},").unwrap();
+            writeln!(
+                from_value,
+                "            {literal:?} => Ok({name}::{variant}),"
+            )
+            .unwrap();
+        }
+        self.items.push(format!(
+            "#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum {name} {{
+{definition}}}
+
+impl ::convex::typed::ConvexType for {name} {{
+    fn into_value(self) -> ::convex::Value {{
+        let value = match self {{
+{into_value}        }};
+        ::convex::Value::String(value.to_string())
+    }}
+
+    fn from_value(value: ::convex::Value) -> ::convex::typed::Result<Self> {{
+        let value: String = ::convex::typed::ConvexType::from_value(value)?;
+        match value.as_str() {{
+{from_value}            _ => Err(::convex::typed::unknown_variant({name:?}, value)),
+        }}
+    }}
+}}
+"
+        ));
+        name
+    }
+
+    fn emit_union_enum(&mut self, name: &str, members: &[Validator]) -> String {
+        let name = self.reserve_name(name);
+        let mut variant_names = BTreeSet::new();
+        let mut definition = String::new();
+        let mut into_value = String::new();
+        let mut from_value = String::new();
+        for (i, member) in members.iter().enumerate() {
+            let mut variant = variant_name(member).unwrap_or_else(|| format!("Variant{i}"));
+            while !variant_names.insert(variant.clone()) {
+                variant.push('_');
+            }
+            let ty = self.rust_type(member, &format!("{name}{variant}"));
+            writeln!(definition, "    {variant}({ty}),").unwrap();
+            writeln!(
+                into_value,
+                "            {name}::{variant}(value) => \
+                 ::convex::typed::ConvexType::into_value(value),"
+            )
+            .unwrap();
+            writeln!(
+                from_value,
+                "        if let Ok(value) = \
+                 ::convex::typed::ConvexType::from_value(value.clone()) {{\n            return \
+                 Ok({name}::{variant}(value));\n        }}"
+            )
+            .unwrap();
+        }
+        self.items.push(format!(
+            "#[derive(Clone, Debug, PartialEq)]
+pub enum {name} {{
+{definition}}}
+
+impl ::convex::typed::ConvexType for {name} {{
+    fn into_value(self) -> ::convex::Value {{
+        match self {{
+{into_value}        }}
+    }}
+
+    fn from_value(value: ::convex::Value) -> ::convex::typed::Result<Self> {{
+{from_value}        Err(::convex::typed::unknown_variant({name:?}, value))
+    }}
+}}
+"
+        ));
+        name
+    }
+}
+
+/// Name a union variant after its type, or after the value of its first
+/// string literal field for tagged objects like `{ kind: "text", ... }`.
+fn variant_name(member: &Validator) -> Option<String> {
+    let name = match member {
+        Validator::Id(_) => "Id",
+        Validator::Null => "Null",
+        Validator::Float64 => "Float64",
+        Validator::Int64 => "Int64",
+        Validator::Boolean => "Boolean",
+        Validator::String => "String",
+        Validator::Bytes => "Bytes",
+        Validator::Array(_) => "Array",
+        Validator::Record(_) => "Record",
+        Validator::Any => "Any",
+        Validator::Literal(Literal::String(s)) => return Some(pascal_ident(s)),
+        Validator::Literal(_) => return None,
+        Validator::Union(_) => return None,
+        Validator::Object(fields) => {
+            return fields.iter().find_map(|(_, field)| match &field.validator {
+                Validator::Literal(Literal::String(s)) if !field.optional => Some(pascal_ident(s)),
+                _ => None,
+            })
+        },
+    };
+    Some(name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        pascal_ident,
+        snake_ident,
+    };
+
+    #[test]
+    fn test_idents() {
+        assert_eq!(snake_ident("sendMessage"), "send_message");
+        assert_eq!(snake_ident("getHTTPValue"), "get_http_value");
+        assert_eq!(snake_ident("creationTime"), "creation_time");
+        assert_eq!(snake_ident("type"), "r#type");
+        assert_eq!(snake_ident("self"), "self_");
+        assert_eq!(snake_ident("2fa"), "_2fa");
+        assert_eq!(pascal_ident("messages"), "Messages");
+        assert_eq!(pascal_ident("user_profiles"), "UserProfiles");
+        assert_eq!(pascal_ident("in-progress"), "InProgress");
+        assert_eq!(pascal_ident("404"), "V404");
+    }
+}
@@ -0,0 +1,716 @@
+//! Generate typed Rust bindings for a Convex deployment.
+//!
+//! The generator reads the deployment's function signatures (the output of
+//! `npx convex function-spec`) and, optionally, its schema, and emits:
+//! - a `schema` module with a struct (or enum) for each table's documents, and
+//! - an `api` module mirroring the `convex/` directory, with argument and
+//!   result types and a wrapper around [`ConvexClient`] for each public
+//!   function.
+//!
+//! All generated types implement [`ConvexType`](crate::typed::ConvexType), so
+//! they convert to and from [`Value`](crate::Value) without any
+//! `BTreeMap<String, Value>` plumbing.
+//!
+//! The simplest way to use it is from a build script:
+//! ```no_run
+//! // build.rs
+//! fn main() -> anyhow::Result<()> {
+//!     convex::codegen::Builder::from_function_spec_file("convex_function_spec.json")
+//!         .schema_file("convex_schema.json")
+//!         .generate()?;
+//!     Ok(())
+//! }
+//! ```
+//! and then include the output in your crate:
+//! ```ignore
+//! include!(concat!(env!("OUT_DIR"), "/convex_api.rs"));
+//!
+//! async fn send(client: &mut convex::ConvexClient) -> anyhow::Result<()> {
+//!     let message = api::messages::send(
+//!         client,
+//!         api::messages::SendArgs {
+//!             author: "The Beatles".into(),
+//!             body: "Let it be.".into(),
+//!         },
+//!     )
+//!     .await?;
+//!     Ok(())
+//! }
+//! ```
+use std::{
+    collections::BTreeMap,
+    env,
+    fmt::Write,
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use anyhow::Context;
+use serde_json::Value as JsonValue;
+
+#[cfg(doc)]
+use crate::ConvexClient;
+use crate::ConvexHttpClient;
+
+mod emit;
+mod validator;
+
+use self::{
+    emit::{
+        pascal_ident,
+        snake_ident,
+        KnownType,
+        ModuleEmitter,
+    },
+    validator::{
+        Field,
+        Validator,
+    },
+};
+
+/// The default file name the [`Builder`] writes to in `OUT_DIR`.
+pub const DEFAULT_OUT_FILE: &str = "convex_api.rs";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum FunctionType {
+    Query,
+    Mutation,
+    Action,
+}
+
+struct FunctionSpec {
+    /// The module path, e.g. `messages.js`.
+    module_path: String,
+    /// The exported function name, e.g. `list`.
+    name: String,
+    function_type: FunctionType,
+    args: Validator,
+    returns: Validator,
+}
+
+/// Parse the output of `npx convex function-spec` (or the bare function list
+/// from the `_system/cli/modules:apiSpec` query), keeping public functions.
+fn parse_function_spec(spec: &JsonValue) -> anyhow::Result<Vec<FunctionSpec>> {
+    let functions = match spec {
+        JsonValue::Array(functions) => functions,
+        spec => spec
+            .get("functions")
+            .and_then(JsonValue::as_array)
+            .context("Function spec must contain a `functions` array")?,
+    };
+    let mut result = vec![];
+    for function in functions {
+        let function_type = match function.get("functionType").and_then(JsonValue::as_str) {
+            Some("Query" | "query") => FunctionType::Query,
+            Some("Mutation" | "mutation") => FunctionType::Mutation,
+            Some("Action" | "action") => FunctionType::Action,
+            // HTTP actions aren't called through the client.
+            _ => continue,
+        };
+        let visibility = function
+            .get("visibility")
+            .and_then(|v| v.get("kind"))
+            .and_then(JsonValue::as_str)
+            .unwrap_or("public");
+        if visibility != "public" {
+            continue;
+        }
+        let identifier = function
+            .get("identifier")
+            .and_then(JsonValue::as_str)
+            .context("Function is missing an identifier")?;
+        let (module_path, name) = identifier
+            .rsplit_once(':')
+            .with_context(|| format!("Invalid function identifier {identifier}"))?;
+        let args = match function.get("args") {
+            Some(args) => Validator::try_from(args)
+                .with_context(|| format!("Invalid args validator for {identifier}"))?,
+            None => Validator::Any,
+        };
+        let returns = match function.get("returns") {
+            Some(JsonValue::Null) | None => Validator::Any,
+            Some(returns) => Validator::try_from(returns)
+                .with_context(|| format!("Invalid returns validator for {identifier}"))?,
+        };
+        result.push(FunctionSpec {
+            module_path: module_path.to_string(),
+            name: name.to_string(),
+            function_type,
+            args,
+            returns,
+        });
+    }
+    Ok(result)
+}
+
+/// Parse a schema (`{"tables": [...]}`) into each table's document validator,
+/// including system fields.
+fn parse_schema(schema: &JsonValue) -> anyhow::Result<Vec<(String, Validator)>> {
+    let tables = schema
+        .get("tables")
+        .and_then(JsonValue::as_array)
+        .context("Schema must contain a `tables` array")?;
+    let mut result = vec![];
+    for table in tables {
+        let table_name = table
+            .get("tableName")
+            .and_then(JsonValue::as_str)
+            .context("Table is missing a tableName")?;
+        let document_type = match table.get("documentType") {
+            Some(JsonValue::Null) | None => Validator::Any,
+            Some(document_type) => Validator::try_from(document_type)
+                .with_context(|| format!("Invalid document type for table {table_name}"))?,
+        };
+        result.push((
+            table_name.to_string(),
+            with_system_fields(document_type, table_name),
+        ));
+    }
+    Ok(result)
+}
+
+fn with_system_fields(validator: Validator, table_name: &str) -> Validator {
+    match validator {
+        Validator::Object(mut fields) => {
+            fields.insert(
+                "_id".to_string(),
+                Field {
+                    validator: Validator::Id(table_name.to_string()),
+                    optional: false,
+                },
+            );
+            fields.insert(
+                "_creationTime".to_string(),
+                Field {
+                    validator: Validator::Float64,
+                    optional: false,
+                },
+            );
+            Validator::Object(fields)
+        },
+        Validator::Union(members) => Validator::Union(
+            members
+                .into_iter()
+                .map(|member| with_system_fields(member, table_name))
+                .collect(),
+        ),
+        validator => validator,
+    }
+}
+
+/// Functions grouped by module, mirroring the `convex/` directory.
+#[derive(Default)]
+struct ModuleTree {
+    functions: Vec<FunctionSpec>,
+    children: BTreeMap<String, ModuleTree>,
+}
+
+impl ModuleTree {
+    fn insert(&mut self, function: FunctionSpec) {
+        let path = function
+            .module_path
+            .trim_end_matches(".js")
+            .trim_end_matches(".ts")
+            .to_string();
+        let mut node = self;
+        for segment in path.split('/') {
+            node = node.children.entry(snake_ident(segment)).or_default();
+        }
+        node.functions.push(function);
+    }
+
+    fn emit(&self, out: &mut String, depth: usize, known_types: &[KnownType]) {
+        let indent = "    ".repeat(depth);
+        // Document types live in the top-level `schema` module.
+        let schema_prefix = "super::".repeat(depth);
+        let local_known_types: Vec<KnownType> = known_types
+            .iter()
+            .map(|known| KnownType {
+                validator: known.validator.clone(),
+                path: format!("{schema_prefix}{}", known.path),
+            })
+            .collect();
+        let mut emitter = ModuleEmitter::new(&local_known_types);
+        let mut wrappers = vec![];
+        for function in &self.functions {
+            wrappers.push(emit_wrapper(&mut emitter, function));
+        }
+        for item in emitter.items.iter().chain(wrappers.iter()) {
+            for line in item.lines() {
+                if line.is_empty() {
+                    out.push('\n');
+                } else {
+                    writeln!(out, "{indent}{line}").unwrap();
+                }
+            }
+            out.push('\n');
+        }
+        for (name, child) in &self.children {
+            writeln!(out, "{indent}pub mod {name} {{").unwrap();
+            child.emit(out, depth + 1, known_types);
+            writeln!(out, "{indent}}}\n").unwrap();
+        }
+    }
+}
+
+fn emit_wrapper(emitter: &mut ModuleEmitter<'_>, function: &FunctionSpec) -> String {
+    let type_prefix = pascal_ident(&function.name);
+    let args_type = match &function.args {
+        Validator::Object(fields) => emitter.emit_struct(&format!("{type_prefix}Args"), fields),
+        _ => "::std::collections::BTreeMap<String, ::convex::Value>".to_string(),
+    };
+    let returns_type = emitter.rust_type(&function.returns, &format!("{type_prefix}Result"));
+    let fn_name = snake_ident(&function.name);
+    let identifier = format!("{}:{}", function.module_path, function.name);
+    let (kind, method) = match function.function_type {
+        FunctionType::Query => ("query", "query"),
+        FunctionType::Mutation => ("mutation", "mutation"),
+        FunctionType::Action => ("action", "action"),
+    };
+    let mut wrapper = format!(
+        "/// Run the {kind} `{identifier}`.
+pub async fn {fn_name}(
+    client: &mut ::convex::ConvexClient,
+    args: {args_type},
+) -> ::convex::typed::Result<{returns_type}> {{
+    let args = ::convex::typed::args_object(args)?;
+    ::convex::typed::function_result(client.{method}({identifier:?}, args).await?)
+}}
+"
+    );
+    if function.function_type == FunctionType::Query {
+        let subscribe_name = snake_ident(&format!("subscribe_{}", function.name));
+        write!(
+            wrapper,
+            "
+/// Subscribe to the query `{identifier}`.
+pub async fn {subscribe_name}(
+    client: &mut ::convex::ConvexClient,
+    args: {args_type},
+) -> ::convex::typed::Result<::convex::typed::TypedSubscription<{returns_type}>> {{
+    let args = ::convex::typed::args_object(args)?;
+    Ok(::convex::typed::TypedSubscription::new(
+        client.subscribe({identifier:?}, args).await?,
+    ))
+}}
+"
+        )
+        .unwrap();
+    }
+    wrapper
+}
+
+/// Generate Rust bindings from a function spec and an optional schema, both in
+/// the JSON formats used by deployments. Returns the source of a file meant to
+/// be `include!`d into a crate that depends on `convex`.
+pub fn generate(function_spec: &JsonValue, schema: Option<&JsonValue>) -> anyhow::Result<String> {
+    let functions = parse_function_spec(function_spec)?;
+    let tables = match schema {
+        Some(schema) => parse_schema(schema)?,
+        None => vec![],
+    };
+
+    let mut out = String::from("// @generated by `convex::codegen`. Do not edit.\n\n");
+
+    out.push_str("pub mod schema {\n");
+    let mut known_types = vec![];
+    let mut schema_emitter = ModuleEmitter::new(&[]);
+    for (table_name, document_type) in &tables {
+        let name = format!("{}Document", pascal_ident(table_name));
+        let ty = match document_type {
+            Validator::Object(fields) => schema_emitter.emit_struct(&name, fields),
+            validator => {
+                let ty = schema_emitter.rust_type(validator, &name);
+                if ty != name {
+                    schema_emitter
+                        .items
+                        .push(format!("pub type {name} = {ty};\n"));
+                }
+                name.clone()
+            },
+        };
+        known_types.push(KnownType {
+            validator: document_type.clone(),
+            path: format!("schema::{ty}"),
+        });
+    }
+    for item in &schema_emitter.items {
+        for line in item.lines() {
+            if line.is_empty() {
+                out.push('\n');
+            } else {
+                writeln!(out, "    {line}").unwrap();
+            }
+        }
+        out.push('\n');
+    }
+    out.push_str("}\n\n");
+
+    let mut api = ModuleTree::default();
+    for function in functions {
+        api.insert(function);
+    }
+    out.push_str("pub mod api {\n");
+    api.emit(&mut out, 1, &known_types);
+    out.push_str("}\n");
+    Ok(out)
+}
+
+/// Fetch the function spec and active schema of a deployment, in the formats
+/// accepted by [`generate`]. Requires an admin or deploy key.
+pub async fn fetch_deployment_metadata(
+    deployment_url: &str,
+    admin_key: &str,
+) -> anyhow::Result<(JsonValue, Option<JsonValue>)> {
+    let mut client = ConvexHttpClient::new(deployment_url)?;
+    client.set_admin_auth(admin_key.to_string(), None)?;
+
+    let functions = crate::typed::function_result::<crate::Value>(
+        client
+            .query("_system/cli/modules:apiSpec", BTreeMap::new())
+            .await?,
+    )
+    .context("Failed to fetch the function spec")?;
+    let schemas = crate::typed::function_result::<crate::Value>(
+        client
+            .query("_system/frontend/getSchemas", BTreeMap::new())
+            .await?,
+    )
+    .context("Failed to fetch the schema")?;
+    let schema = match schemas {
+        crate::Value::Object(mut fields) => match fields.remove("active") {
+            Some(crate::Value::String(schema)) => {
+                Some(serde_json::from_str(&schema).context("Invalid schema JSON")?)
+            },
+            _ => None,
+        },
+        _ => None,
+    };
+    Ok((JsonValue::from(functions), schema))
+}
+
+enum Source {
+    Files {
+        function_spec: PathBuf,
+        schema: Option<PathBuf>,
+    },
+    Deployment {
+        url: String,
+        admin_key: String,
+    },
+}
+
+/// Generates bindings from a build script.
+///
+/// By default the output is written to `$OUT_DIR/convex_api.rs`.
+pub struct Builder {
+    source: Source,
+    out_file: Option<PathBuf>,
+}
+
+impl Builder {
+    /// Generate from a function spec file written by
+    /// `npx convex function-spec --file`.
+    pub fn from_function_spec_file(path: impl AsRef<Path>) -> Self {
+        Self {
+            source: Source::Files {
+                function_spec: path.as_ref().to_path_buf(),
+                schema: None,
+            },
+            out_file: None,
+        }
+    }
+
+    /// Also generate document types from a schema JSON file. Ignored when
+    /// generating from a deployment, which provides its own schema.
+    pub fn schema_file(mut self, path: impl AsRef<Path>) -> Self {
+        if let Source::Files { schema, .. } = &mut self.source {
+            *schema = Some(path.as_ref().to_path_buf());
+        }
+        self
+    }
+
+    /// Generate from a live deployment's function spec and active schema,
+    /// using an admin or deploy key.
+    pub fn from_deployment(deployment_url: &str, admin_key: &str) -> Self {
+        Self {
+            source: Source::Deployment {
+                url: deployment_url.to_string(),
+                admin_key: admin_key.to_string(),
+            },
+            out_file: None,
+        }
+    }
+
+    /// Write the output to `path` instead of `$OUT_DIR/convex_api.rs`.
+    pub fn out_file(mut self, path: impl AsRef<Path>) -> Self {
+        self.out_file = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Generate the bindings and write them out, returning the output path.
+    ///
+    /// When generating from files, this tells Cargo to rerun the build
+    /// script if they change.
+    pub fn generate(self) -> anyhow::Result<PathBuf> {
+        let (function_spec, schema) = match &self.source {
+            Source::Files {
+                function_spec,
+                schema,
+            } => {
+                println!("cargo:rerun-if-changed={}", function_spec.display());
+                let function_spec = read_json(function_spec)?;
+                let schema = match schema {
+                    Some(schema) => {
+                        println!("cargo:rerun-if-changed={}", schema.display());
+                        Some(read_json(schema)?)
+                    },
+                    None => None,
+                };
+                (function_spec, schema)
+            },
+            Source::Deployment { url, admin_key } => tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()?
+                .block_on(fetch_deployment_metadata(url, admin_key))?,
+        };
+        let source = generate(&function_spec, schema.as_ref())?;
+        let out_file = match self.out_file {
+            Some(out_file) => out_file,
+            None => {
+                PathBuf::from(env::var_os("OUT_DIR").context("OUT_DIR is not set; use `out_file`")?)
+                    .join(DEFAULT_OUT_FILE)
+            },
+        };
+        fs::write(&out_file, source)
+            .with_context(|| format!("Failed to write {}", out_file.display()))?;
+        Ok(out_file)
+    }
+}
+
+fn read_json(path: &Path) -> anyhow::Result<JsonValue> {
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&contents).with_context(|| format!("Invalid JSON in {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use convex_sync_types::{
+        ClientMessage,
+        LogLinesMessage,
+        QuerySetModification,
+        StateModification,
+        StateVersion,
+        UdfPath,
+    };
+    use maplit::btreemap;
+    use pretty_assertions::assert_eq;
+    use serde_json::{
+        json,
+        Value as JsonValue,
+    };
+
+    use super::generate;
+    use crate::{
+        sync::ServerMessage,
+        ConvexClient,
+        Value,
+    };
+
+    // The output of `generate` for `test_spec`, compiled as part of the tests.
+    #[allow(dead_code)]
+    mod generated {
+        include!("testdata/convex_api.rs");
+    }
+
+    fn test_spec() -> (JsonValue, JsonValue) {
+        let message_fields = json!({
+            "author": {"fieldType": {"type": "id", "tableName": "users"}, "optional": false},
+            "body": {"fieldType": {"type": "string"}, "optional": false},
+        });
+        let message_document = json!({
+            "type": "object",
+            "value": {
+                "_id": {"fieldType": {"type": "id", "tableName": "messages"}, "optional": false},
+                "_creationTime": {"fieldType": {"type": "number"}, "optional": false},
+                "author": {"fieldType": {"type": "id", "tableName": "users"}, "optional": false},
+                "body": {"fieldType": {"type": "string"}, "optional": false},
+            },
+        });
+        let function_spec = json!({
+            "url": "https://cool-music-123.convex.cloud",
+            "functions": [
+                {
+                    "identifier": "messages.js:list",
+                    "functionType": "Query",
+                    "visibility": {"kind": "public"},
+                    "args": {"type": "object", "value": {
+                        "limit": {"fieldType": {"type": "number"}, "optional": true},
+                    }},
+                    "returns": {"type": "array", "value": message_document},
+                },
+                {
+                    "identifier": "messages.js:send",
+                    "functionType": "Mutation",
+                    "visibility": {"kind": "public"},
+                    "args": {"type": "object", "value": message_fields},
+                    "returns": {"type": "null"},
+                },
+                {
+                    "identifier": "admin/cleanup.js:run",
+                    "functionType": "Action",
+                    "visibility": {"kind": "internal"},
+                    "args": {"type": "any"},
+                    "returns": {"type": "any"},
+                },
+                {"functionType": "HttpAction", "method": "GET", "path": "/hello"},
+            ],
+        });
+        let schema = json!({
+            "tables": [{
+                "tableName": "messages",
+                "indexes": [],
+                "documentType": {"type": "object", "value": message_fields},
+            }],
+            "schemaValidation": true,
+        });
+        (function_spec, schema)
+    }
+
+    #[test]
+    fn test_generate() -> anyhow::Result<()> {
+        let (function_spec, schema) = test_spec();
+        let source = generate(&function_spec, Some(&schema))?;
+
+        assert!(source.contains("pub struct MessagesDocument {"));
+        assert!(source.contains("    pub id: String,"));
+        assert!(source.contains("    pub creation_time: f64,"));
+        // The query's result reuses the document struct.
+        assert!(source.contains(
+            ") -> ::convex::typed::Result<Vec<super::super::schema::MessagesDocument>> {"
+        ));
+        assert!(source.contains("pub struct ListArgs {"));
+        assert!(source.contains("pub limit: Option<f64>,"));
+        assert!(source.contains("pub async fn subscribe_list("));
+        assert!(source.contains("client.mutation(\"messages.js:send\", args)"));
+        // Internal functions and HTTP actions are skipped.
+        assert!(!source.contains("cleanup"));
+
+        // Keep the compiled copy in sync with the generator.
+        assert_eq!(
+            source,
+            include_str!("testdata/convex_api.rs"),
+            "Generated bindings changed, update src/codegen/testdata/convex_api.rs"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generated_wrappers() -> anyhow::Result<()> {
+        use generated::{
+            api::messages,
+            schema::MessagesDocument,
+        };
+
+        let (mut client, mut test_protocol) = ConvexClient::with_test_protocol().await?;
+        test_protocol.take_sent().await;
+
+        let mut query_client = client.clone();
+        let list = tokio::spawn(async move {
+            messages::list(&mut query_client, messages::ListArgs { limit: Some(10.) }).await
+        });
+        test_protocol.wait_until_n_messages_sent(1).await;
+        let ClientMessage::ModifyQuerySet { modifications, .. } =
+            test_protocol.take_sent().await.remove(0)
+        else {
+            anyhow::bail!("Expected a query set modification");
+        };
+        let [QuerySetModification::Add(query)] = &modifications[..] else {
+            anyhow::bail!("Expected one added query, got {modifications:?}");
+        };
+        assert_eq!(query.udf_path, UdfPath::from_str("messages.js:list")?);
+        assert_eq!(query.args, vec![json!({"limit": 10.0})]);
+
+        let start_version = StateVersion::initial();
+        let end_version = StateVersion {
+            ts: start_version.ts.succ().expect("Succ failed"),
+            ..start_version
+        };
+        test_protocol
+            .fake_server_response(ServerMessage::Transition {
+                start_version,
+                end_version,
+                modifications: vec![StateModification::QueryUpdated {
+                    query_id: query.query_id,
+                    value: Value::Array(vec![Value::Object(btreemap! {
+                        "_id".into() => "m1".into(),
+                        "_creationTime".into() => Value::Float64(1.),
+                        "author".into() => "u1".into(),
+                        "body".into() => "Let it be.".into(),
+                    })]),
+                    journal: None,
+                    log_lines: LogLinesMessage(vec![]),
+                }],
+            })
+            .await?;
+        assert_eq!(
+            list.await??,
+            vec![MessagesDocument {
+                id: "m1".into(),
+                creation_time: 1.,
+                author: "u1".into(),
+                body: "Let it be.".into(),
+            }]
+        );
+
+        let send = tokio::spawn(async move {
+            messages::send(
+                &mut client,
+                messages::SendArgs {
+                    author: "u1".into(),
+                    body: "Here comes the sun.".into(),
+                },
+            )
+            .await
+        });
+        // `list` unsubscribes from the query once it has a result.
+        test_protocol.wait_until_n_messages_sent(2).await;
+        assert_eq!(
+            test_protocol.take_sent().await[1..],
+            [ClientMessage::Mutation {
+                request_id: 0,
+                udf_path: UdfPath::from_str("messages.js:send")?,
+                args: vec![json!({"author": "u1", "body": "Here comes the sun."})],
+                component_path: None,
+            }]
+        );
+        // The mutation's result is delivered once a transition includes it.
+        let next_version = StateVersion {
+            ts: end_version.ts.succ().expect("Succ failed"),
+            ..end_version
+        };
+        test_protocol
+            .fake_server_response(ServerMessage::MutationResponse {
+                request_id: 0,
+                result: Ok(Value::Null),
+                ts: Some(next_version.ts),
+                log_lines: LogLinesMessage(vec![]),
+            })
+            .await?;
+        test_protocol
+            .fake_server_response(ServerMessage::Transition {
+                start_version: end_version,
+                end_version: next_version,
+                modifications: vec![],
+            })
+            .await?;
+        send.await??;
+        Ok(())
+    }
+}
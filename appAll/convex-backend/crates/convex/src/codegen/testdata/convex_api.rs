@@ -0,0 +1,119 @@
+// @generated by `convex::codegen`. Do not edit.
+
+pub mod schema {
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct MessagesDocument {
+        pub creation_time: f64,
+        pub id: String,
+        pub author: String,
+        pub body: String,
+    }
+
+    impl ::convex::typed::ConvexType for MessagesDocument {
+        #[allow(unused_mut)]
+        fn into_value(self) -> ::convex::Value {
+            let mut fields = ::std::collections::BTreeMap::new();
+            fields.insert("_creationTime".to_string(), ::convex::typed::ConvexType::into_value(self.creation_time));
+            fields.insert("_id".to_string(), ::convex::typed::ConvexType::into_value(self.id));
+            fields.insert("author".to_string(), ::convex::typed::ConvexType::into_value(self.author));
+            fields.insert("body".to_string(), ::convex::typed::ConvexType::into_value(self.body));
+            ::convex::Value::Object(fields)
+        }
+
+        #[allow(unused_mut, unused_variables)]
+        fn from_value(value: ::convex::Value) -> ::convex::typed::Result<Self> {
+            let mut fields = ::convex::typed::object_fields(value, "MessagesDocument")?;
+            Ok(Self {
+                creation_time: ::convex::typed::field(&mut fields, "_creationTime")?,
+                id: ::convex::typed::field(&mut fields, "_id")?,
+                author: ::convex::typed::field(&mut fields, "author")?,
+                body: ::convex::typed::field(&mut fields, "body")?,
+            })
+        }
+    }
+
+}
+
+pub mod api {
+    pub mod messages {
+        #[derive(Clone, Debug, PartialEq)]
+        pub struct ListArgs {
+            pub limit: Option<f64>,
+        }
+
+        impl ::convex::typed::ConvexType for ListArgs {
+            #[allow(unused_mut)]
+            fn into_value(self) -> ::convex::Value {
+                let mut fields = ::std::collections::BTreeMap::new();
+                if let Some(value) = self.limit {
+                    fields.insert("limit".to_string(), ::convex::typed::ConvexType::into_value(value));
+                }
+                ::convex::Value::Object(fields)
+            }
+
+            #[allow(unused_mut, unused_variables)]
+            fn from_value(value: ::convex::Value) -> ::convex::typed::Result<Self> {
+                let mut fields = ::convex::typed::object_fields(value, "ListArgs")?;
+                Ok(Self {
+                    limit: ::convex::typed::optional_field(&mut fields, "limit")?,
+                })
+            }
+        }
+
+        #[derive(Clone, Debug, PartialEq)]
+        pub struct SendArgs {
+            pub author: String,
+            pub body: String,
+        }
+
+        impl ::convex::typed::ConvexType for SendArgs {
+            #[allow(unused_mut)]
+            fn into_value(self) -> ::convex::Value {
+                let mut fields = ::std::collections::BTreeMap::new();
+                fields.insert("author".to_string(), ::convex::typed::ConvexType::into_value(self.author));
+                fields.insert("body".to_string(), ::convex::typed::ConvexType::into_value(self.body));
+                ::convex::Value::Object(fields)
+            }
+
+            #[allow(unused_mut, unused_variables)]
+            fn from_value(value: ::convex::Value) -> ::convex::typed::Result<Self> {
+                let mut fields = ::convex::typed::object_fields(value, "SendArgs")?;
+                Ok(Self {
+                    author: ::convex::typed::field(&mut fields, "author")?,
+                    body: ::convex::typed::field(&mut fields, "body")?,
+                })
+            }
+        }
+
+        /// Run the query `messages.js:list`.
+        pub async fn list(
+            client: &mut ::convex::ConvexClient,
+            args: ListArgs,
+        ) -> ::convex::typed::Result<Vec<super::super::schema::MessagesDocument>> {
+            let args = ::convex::typed::args_object(args)?;
+            ::convex::typed::function_result(client.query("messages.js:list", args).await?)
+        }
+
+        /// Subscribe to the query `messages.js:list`.
+        pub async fn subscribe_list(
+            client: &mut ::convex::ConvexClient,
+            args: ListArgs,
+        ) -> ::convex::typed::Result<::convex::typed::TypedSubscription<Vec<super::super::schema::MessagesDocument>>> {
+            let args = ::convex::typed::args_object(args)?;
+            Ok(::convex::typed::TypedSubscription::new(
+                client.subscribe("messages.js:list", args).await?,
+            ))
+        }
+
+        /// Run the mutation `messages.js:send`.
+        pub async fn send(
+            client: &mut ::convex::ConvexClient,
+            args: SendArgs,
+        ) -> ::convex::typed::Result<()> {
+            let args = ::convex::typed::args_object(args)?;
+            ::convex::typed::function_result(client.mutation("messages.js:send", args).await?)
+        }
+
+    }
+
+}
@@ -0,0 +1,182 @@
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use serde_json::Value as JsonValue;
+
+/// A validator, parsed from the JSON format that deployments use for schemas
+/// and function signatures.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Validator {
+    Id(String),
+    Null,
+    Float64,
+    Int64,
+    Boolean,
+    String,
+    Bytes,
+    Literal(Literal),
+    Array(Box<Validator>),
+    Record(Box<Validator>),
+    Object(BTreeMap<String, Field>),
+    Union(Vec<Validator>),
+    Any,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Literal {
+    String(String),
+    Float64(f64),
+    Int64,
+    Boolean(bool),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Field {
+    pub validator: Validator,
+    pub optional: bool,
+}
+
+impl TryFrom<&JsonValue> for Validator {
+    type Error = anyhow::Error;
+
+    fn try_from(json: &JsonValue) -> anyhow::Result<Self> {
+        let ty = json
+            .get("type")
+            .and_then(JsonValue::as_str)
+            .with_context(|| format!("Validator is missing a type: {json}"))?;
+        let value = || {
+            json.get("value")
+                .with_context(|| format!("{ty} validator is missing a value"))
+        };
+        let validator = match ty {
+            "null" => Validator::Null,
+            "number" => Validator::Float64,
+            "bigint" => Validator::Int64,
+            "boolean" => Validator::Boolean,
+            "string" => Validator::String,
+            "bytes" => Validator::Bytes,
+            "any" => Validator::Any,
+            "id" => Validator::Id(
+                json.get("tableName")
+                    .and_then(JsonValue::as_str)
+                    .context("id validator is missing a tableName")?
+                    .to_string(),
+            ),
+            "literal" => Validator::Literal(match value()? {
+                JsonValue::String(s) => Literal::String(s.clone()),
+                JsonValue::Bool(b) => Literal::Boolean(*b),
+                JsonValue::Number(n) => {
+                    Literal::Float64(n.as_f64().context("Invalid number literal")?)
+                },
+                JsonValue::Object(o) if o.contains_key("$integer") => Literal::Int64,
+                v => anyhow::bail!("Unsupported literal {v}"),
+            }),
+            "array" => Validator::Array(Box::new(Validator::try_from(value()?)?)),
+            "record" => {
+                let values = json
+                    .get("values")
+                    .context("record validator is missing values")?;
+                Validator::Record(Box::new(Field::try_from(values)?.validator))
+            },
+            "object" => {
+                let fields = value()?
+                    .as_object()
+                    .context("object validator value must be an object")?;
+                Validator::Object(
+                    fields
+                        .iter()
+                        .map(|(name, field)| Ok((name.clone(), Field::try_from(field)?)))
+                        .collect::<anyhow::Result<_>>()?,
+                )
+            },
+            "union" => Validator::Union(
+                value()?
+                    .as_array()
+                    .context("union validator value must be an array")?
+                    .iter()
+                    .map(Validator::try_from)
+                    .collect::<anyhow::Result<_>>()?,
+            ),
+            // Sets and maps are deprecated and have no natural Rust equivalent
+            // that round-trips through `Value`.
+            "set" | "map" => Validator::Any,
+            ty => anyhow::bail!("Unknown validator type {ty}"),
+        };
+        Ok(validator)
+    }
+}
+
+impl TryFrom<&JsonValue> for Field {
+    type Error = anyhow::Error;
+
+    fn try_from(json: &JsonValue) -> anyhow::Result<Self> {
+        Ok(Field {
+            validator: Validator::try_from(
+                json.get("fieldType")
+                    .context("Field is missing a fieldType")?,
+            )?,
+            optional: json
+                .get("optional")
+                .and_then(JsonValue::as_bool)
+                .unwrap_or(false),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use maplit::btreemap;
+    use serde_json::json;
+
+    use super::{
+        Field,
+        Literal,
+        Validator,
+    };
+
+    #[test]
+    fn test_parse_validator() -> anyhow::Result<()> {
+        let json = json!({
+            "type": "object",
+            "value": {
+                "author": {"fieldType": {"type": "id", "tableName": "users"}, "optional": false},
+                "tags": {
+                    "fieldType": {
+                        "type": "record",
+                        "keys": {"type": "string"},
+                        "values": {"fieldType": {"type": "bigint"}, "optional": false},
+                    },
+                    "optional": true,
+                },
+                "kind": {
+                    "fieldType": {"type": "union", "value": [
+                        {"type": "literal", "value": "text"},
+                        {"type": "null"},
+                    ]},
+                    "optional": false,
+                },
+            },
+        });
+        assert_eq!(
+            Validator::try_from(&json)?,
+            Validator::Object(btreemap! {
+                "author".into() => Field {
+                    validator: Validator::Id("users".into()),
+                    optional: false,
+                },
+                "tags".into() => Field {
+                    validator: Validator::Record(Box::new(Validator::Int64)),
+                    optional: true,
+                },
+                "kind".into() => Field {
+                    validator: Validator::Union(vec![
+                        Validator::Literal(Literal::String("text".into())),
+                        Validator::Null,
+                    ]),
+                    optional: false,
+                },
+            })
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,419 @@
+//! A client for calling Convex functions over plain HTTP.
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use convex_sync_types::{
+    Timestamp,
+    UdfPath,
+    UserIdentityAttributes,
+};
+use reqwest::header::AUTHORIZATION;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use serde_json::Value as JsonValue;
+use url::Url;
+
+use crate::{
+    client::VERSION,
+    convex_logs,
+    value::Value,
+    ConvexError,
+    FunctionResult,
+};
+
+/// Ask the backend for values in the encoding that round trips every
+/// [`Value`], including integers and bytes.
+const VALUE_FORMAT: &str = "convex_encoded_json";
+
+const CONVEX_CLIENT_HEADER: &str = "Convex-Client";
+
+/// A client that calls Convex functions with one HTTP request per call.
+///
+/// Unlike [`ConvexClient`](crate::ConvexClient), it doesn't keep a WebSocket
+/// connection or a background task around, so it is a better fit for CLIs,
+/// serverless functions and batch jobs that make a few calls and exit. It
+/// can't subscribe to queries.
+///
+/// ```no_run
+/// use convex::ConvexHttpClient;
+///
+/// #[tokio::main]
+/// async fn main() -> anyhow::Result<()> {
+///     let client = ConvexHttpClient::new("https://cool-music-123.convex.cloud")?;
+///     let result = client.query("listMessages", maplit::btreemap!{}).await?;
+///     println!("{result:?}");
+///     Ok(())
+/// }
+/// ```
+///
+/// Cloning a [`ConvexHttpClient`] is cheap and shares its connection pool.
+#[derive(Clone)]
+pub struct ConvexHttpClient {
+    http_client: reqwest::Client,
+    deployment_url: Url,
+    client_id: String,
+    authorization: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FunctionRequest<'a> {
+    path: &'a str,
+    args: JsonValue,
+    format: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    component_path: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ts: Option<String>,
+}
+
+#[derive(Serialize)]
+struct QueryBatchRequest<'a> {
+    queries: Vec<FunctionRequest<'a>>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "status", rename_all = "camelCase")]
+enum FunctionResponse {
+    #[serde(rename_all = "camelCase")]
+    Success {
+        value: JsonValue,
+        #[serde(default)]
+        log_lines: Vec<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Error {
+        error_message: String,
+        error_data: Option<JsonValue>,
+        #[serde(default)]
+        log_lines: Vec<String>,
+    },
+}
+
+#[derive(Deserialize)]
+struct QueryBatchResponse {
+    results: Vec<FunctionResponse>,
+}
+
+#[derive(Deserialize)]
+struct QueryTsResponse {
+    ts: String,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    code: String,
+    message: String,
+}
+
+impl ConvexHttpClient {
+    /// Constructs a new client for calling functions on `deployment_url`.
+    pub fn new(deployment_url: &str) -> anyhow::Result<Self> {
+        Self::new_with_client_id(
+            deployment_url,
+            &format!("rust-{}", VERSION.unwrap_or("unknown")),
+        )
+    }
+
+    #[doc(hidden)]
+    pub fn new_with_client_id(deployment_url: &str, client_id: &str) -> anyhow::Result<Self> {
+        let deployment_url: Url = deployment_url.parse()?;
+        anyhow::ensure!(
+            matches!(deployment_url.scheme(), "http" | "https"),
+            "Unknown scheme {}. Expected http or https.",
+            deployment_url.scheme()
+        );
+        Ok(Self {
+            http_client: reqwest::Client::new(),
+            deployment_url,
+            client_id: client_id.to_string(),
+            authorization: None,
+        })
+    }
+
+    /// Set auth for use when calling Convex functions.
+    ///
+    /// Set it with a token that you get from your auth provider via their login
+    /// flow. If `None` is passed as the token, then auth is unset (logging
+    /// out).
+    pub fn set_auth(&mut self, token: Option<String>) {
+        self.authorization = token.map(|token| format!("Bearer {token}"));
+    }
+
+    /// Set admin auth for use when calling Convex functions as a deployment
+    /// admin. Required for [`ConvexHttpClient::component_function`].
+    ///
+    /// You can get a deploy_key from the Convex dashboard's deployment settings
+    /// page. Deployment admins can act as users as part of their
+    /// development flow to see how a function would act.
+    pub fn set_admin_auth(
+        &mut self,
+        deploy_key: String,
+        acting_as: Option<UserIdentityAttributes>,
+    ) -> anyhow::Result<()> {
+        self.authorization = Some(admin_authorization(deploy_key, acting_as)?);
+        Ok(())
+    }
+
+    /// Run query `name` with `args` at the latest timestamp.
+    pub async fn query(
+        &self,
+        name: &str,
+        args: BTreeMap<String, Value>,
+    ) -> anyhow::Result<FunctionResult> {
+        self.call("query", &function_request(name, args, None)?)
+            .await
+    }
+
+    /// Run query `name` with `args` at `ts`, for reading several queries from
+    /// the same snapshot. Get a timestamp with
+    /// [`ConvexHttpClient::query_ts`].
+    pub async fn query_at_ts(
+        &self,
+        name: &str,
+        args: BTreeMap<String, Value>,
+        ts: Timestamp,
+    ) -> anyhow::Result<FunctionResult> {
+        let mut request = function_request(name, args, None)?;
+        request.ts = Some(serialize_ts(ts));
+        self.call("query_at_ts", &request).await
+    }
+
+    /// The latest timestamp queries can run at.
+    pub async fn query_ts(&self) -> anyhow::Result<Timestamp> {
+        let response: QueryTsResponse = self.post("query_ts", &()).await?;
+        deserialize_ts(&response.ts)
+    }
+
+    /// Run several queries at the same timestamp, returning their results in
+    /// the same order.
+    pub async fn query_batch(
+        &self,
+        queries: Vec<(&str, BTreeMap<String, Value>)>,
+    ) -> anyhow::Result<Vec<FunctionResult>> {
+        let queries = queries
+            .into_iter()
+            .map(|(name, args)| function_request(name, args, None))
+            .collect::<anyhow::Result<_>>()?;
+        let response: QueryBatchResponse = self
+            .post("query_batch", &QueryBatchRequest { queries })
+            .await?;
+        response
+            .results
+            .into_iter()
+            .map(FunctionResult::try_from)
+            .collect()
+    }
+
+    /// Run mutation `name` with `args`.
+    pub async fn mutation(
+        &self,
+        name: &str,
+        args: BTreeMap<String, Value>,
+    ) -> anyhow::Result<FunctionResult> {
+        self.call("mutation", &function_request(name, args, None)?)
+            .await
+    }
+
+    /// Run action `name` with `args`.
+    pub async fn action(
+        &self,
+        name: &str,
+        args: BTreeMap<String, Value>,
+    ) -> anyhow::Result<FunctionResult> {
+        self.call("action", &function_request(name, args, None)?)
+            .await
+    }
+
+    /// Run the query, mutation or action `name` in the component mounted at
+    /// `component_path`, like `"widget"` or `"widget/ratelimiter"`. Requires
+    /// admin auth.
+    pub async fn component_function(
+        &self,
+        component_path: &str,
+        name: &str,
+        args: BTreeMap<String, Value>,
+    ) -> anyhow::Result<FunctionResult> {
+        self.call(
+            "function",
+            &function_request(name, args, Some(component_path))?,
+        )
+        .await
+    }
+
+    async fn call(
+        &self,
+        endpoint: &str,
+        request: &FunctionRequest<'_>,
+    ) -> anyhow::Result<FunctionResult> {
+        let response: FunctionResponse = self.post(endpoint, request).await?;
+        response.try_into()
+    }
+
+    async fn post<T: for<'de> Deserialize<'de>>(
+        &self,
+        endpoint: &str,
+        body: &impl Serialize,
+    ) -> anyhow::Result<T> {
+        let mut url = self.deployment_url.clone();
+        url.set_path(&format!("api/{endpoint}"));
+        let mut request = self
+            .http_client
+            .post(url)
+            .header(CONVEX_CLIENT_HEADER, &self.client_id)
+            .json(body);
+        if let Some(authorization) = &self.authorization {
+            request = request.header(AUTHORIZATION, authorization);
+        }
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await?;
+            // Errors outside of the function itself, like a malformed request
+            // or bad auth, come back as an error status.
+            return Err(match serde_json::from_str::<ErrorResponse>(&body) {
+                Ok(ErrorResponse { code, message }) => {
+                    anyhow::anyhow!("{code}: {message}").context(status)
+                },
+                Err(_) => anyhow::anyhow!("{body}").context(status),
+            });
+        }
+        Ok(response.json().await?)
+    }
+}
+
+fn function_request<'a>(
+    name: &'a str,
+    args: BTreeMap<String, Value>,
+    component_path: Option<&'a str>,
+) -> anyhow::Result<FunctionRequest<'a>> {
+    // Fail early on malformed names, like the WebSocket client does.
+    let _: UdfPath = name.parse()?;
+    Ok(FunctionRequest {
+        path: name,
+        args: Value::Object(args).into(),
+        format: VALUE_FORMAT,
+        component_path,
+        ts: None,
+    })
+}
+
+impl TryFrom<FunctionResponse> for FunctionResult {
+    type Error = anyhow::Error;
+
+    fn try_from(response: FunctionResponse) -> anyhow::Result<Self> {
+        match response {
+            FunctionResponse::Success { value, log_lines } => {
+                for log_line in log_lines {
+                    convex_logs!("{}", log_line);
+                }
+                Ok(FunctionResult::Value(value.try_into()?))
+            },
+            FunctionResponse::Error {
+                error_message,
+                error_data,
+                log_lines,
+            } => {
+                for log_line in log_lines {
+                    convex_logs!("{}", log_line);
+                }
+                Ok(match error_data {
+                    Some(data) => FunctionResult::ConvexError(ConvexError {
+                        message: error_message,
+                        data: data.try_into()?,
+                    }),
+                    None => FunctionResult::ErrorMessage(error_message),
+                })
+            },
+        }
+    }
+}
+
+/// The backend reads `Convex <key>:<base64 user attributes>` as an admin
+/// acting as that user.
+fn admin_authorization(
+    deploy_key: String,
+    acting_as: Option<UserIdentityAttributes>,
+) -> anyhow::Result<String> {
+    Ok(match acting_as {
+        None => format!("Convex {deploy_key}"),
+        Some(attributes) => {
+            let attributes = JsonValue::try_from(attributes)?;
+            let attributes = base64::encode(serde_json::to_vec(&attributes)?);
+            format!("Convex {deploy_key}:{attributes}")
+        },
+    })
+}
+
+/// Timestamps are sent as the base64 of their little endian bytes.
+fn serialize_ts(ts: Timestamp) -> String {
+    base64::encode(u64::from(ts).to_le_bytes())
+}
+
+fn deserialize_ts(ts: &str) -> anyhow::Result<Timestamp> {
+    let bytes: [u8; 8] = base64::decode(ts)?
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("Wrong number of bytes for timestamp {ts}"))?;
+    Timestamp::try_from(u64::from_le_bytes(bytes)).context("Invalid timestamp")
+}
+
+#[cfg(test)]
+mod tests {
+    use convex_sync_types::Timestamp;
+    use maplit::btreemap;
+    use serde_json::json;
+
+    use super::{
+        deserialize_ts,
+        serialize_ts,
+        FunctionResponse,
+    };
+    use crate::{
+        ConvexError,
+        FunctionResult,
+        Value,
+    };
+
+    fn decode(response: serde_json::Value) -> anyhow::Result<FunctionResult> {
+        serde_json::from_value::<FunctionResponse>(response)?.try_into()
+    }
+
+    #[test]
+    fn test_decode_function_responses() -> anyhow::Result<()> {
+        assert_eq!(
+            decode(json!({
+                "status": "success",
+                "value": { "count": { "$integer": "AQAAAAAAAAA=" } },
+                "logLines": ["[LOG] hello"],
+            }))?,
+            FunctionResult::Value(Value::Object(btreemap! {
+                "count".to_string() => Value::Int64(1),
+            })),
+        );
+        assert_eq!(
+            decode(json!({ "status": "error", "errorMessage": "Uncaught Error: oops" }))?,
+            FunctionResult::ErrorMessage("Uncaught Error: oops".to_string()),
+        );
+        assert_eq!(
+            decode(json!({
+                "status": "error",
+                "errorMessage": "Uncaught ConvexError: nope",
+                "errorData": "nope",
+            }))?,
+            FunctionResult::ConvexError(ConvexError {
+                message: "Uncaught ConvexError: nope".to_string(),
+                data: Value::String("nope".to_string()),
+            }),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_ts_roundtrip() -> anyhow::Result<()> {
+        let ts = Timestamp::try_from(1_700_000_000_000_000_000u64)?;
+        assert_eq!(deserialize_ts(&serialize_ts(ts))?, ts);
+        Ok(())
+    }
+}
@@ -31,6 +31,11 @@
 //! }
 //! ```
 //!
+//! ## One-off calls over HTTP
+//! Programs that only make a few calls, like CLIs or serverless functions, can
+//! use [`ConvexHttpClient`] instead. It sends each call as its own HTTP request
+//! and doesn't keep a connection or background task around.
+//!
 //! ## Extending client for other programming languages or frameworks.
 //! To extend Convex into non-[`tokio`] frameworks,
 //! you can use the [`base_client::BaseConvexClient`] to build something similar
@@ -41,6 +46,11 @@
 #![cfg_attr(not(test), warn(missing_docs))]
 #![warn(rustdoc::missing_crate_level_docs)]
 
+// Lets tests compile code generated by `codegen`, which refers to this crate
+// as `::convex`.
+#[cfg(test)]
+extern crate self as convex;
+
 mod value;
 #[cfg(any(test, feature = "testing"))]
 pub use value::export::roundtrip::ExportContext;
@@ -51,6 +61,11 @@ pub use value::{
 
 mod client;
 pub use client::{
+    pagination::{
+        PaginatedQueryResult,
+        PaginatedQuerySubscription,
+        PaginationStatus,
+    },
     subscription::{
         QuerySetSubscription,
         QuerySubscription,
@@ -60,6 +75,12 @@ pub use client::{
 };
 pub use sync::WebSocketState;
 
+mod http_client;
+pub use http_client::ConvexHttpClient;
+
+pub mod codegen;
+pub mod typed;
+
 pub mod base_client;
 #[doc(inline)]
 pub use base_client::{
@@ -0,0 +1,233 @@
+//! Conversions between Rust types and [`Value`], used by the code emitted by
+//! [`codegen`](crate::codegen).
+//!
+//! Generated document structs, argument structs and enums implement
+//! [`ConvexType`], and the generated function wrappers use the helpers in this
+//! module to encode arguments and decode results.
+use std::{
+    collections::BTreeMap,
+    marker::PhantomData,
+    pin::Pin,
+};
+
+use futures::{
+    task,
+    Stream,
+    StreamExt,
+};
+
+use crate::{
+    FunctionResult,
+    QuerySubscription,
+    Value,
+};
+
+/// Result type used by generated code, so that it doesn't need to depend on
+/// `anyhow` directly.
+pub type Result<T> = anyhow::Result<T>;
+
+/// A Rust type with a fixed mapping to and from Convex values.
+pub trait ConvexType: Sized {
+    /// Convert `self` into a Convex value.
+    fn into_value(self) -> Value;
+
+    /// Convert a Convex value into `Self`, failing if it has the wrong shape.
+    fn from_value(value: Value) -> Result<Self>;
+}
+
+impl ConvexType for Value {
+    fn into_value(self) -> Value {
+        self
+    }
+
+    fn from_value(value: Value) -> Result<Self> {
+        Ok(value)
+    }
+}
+
+impl ConvexType for () {
+    fn into_value(self) -> Value {
+        Value::Null
+    }
+
+    fn from_value(value: Value) -> Result<Self> {
+        match value {
+            Value::Null => Ok(()),
+            v => anyhow::bail!("Expected null, got {v:?}"),
+        }
+    }
+}
+
+macro_rules! impl_convex_type {
+    ($ty:ty, $variant:ident, $expected:literal) => {
+        impl ConvexType for $ty {
+            fn into_value(self) -> Value {
+                Value::$variant(self)
+            }
+
+            fn from_value(value: Value) -> Result<Self> {
+                match value {
+                    Value::$variant(v) => Ok(v),
+                    v => anyhow::bail!("Expected {}, got {v:?}", $expected),
+                }
+            }
+        }
+    };
+}
+
+impl_convex_type!(f64, Float64, "a number");
+impl_convex_type!(i64, Int64, "a bigint");
+impl_convex_type!(bool, Boolean, "a boolean");
+impl_convex_type!(String, String, "a string");
+impl_convex_type!(Vec<u8>, Bytes, "bytes");
+
+/// `Option<T>` corresponds to `T | null`.
+impl<T: ConvexType> ConvexType for Option<T> {
+    fn into_value(self) -> Value {
+        match self {
+            Some(v) => v.into_value(),
+            None => Value::Null,
+        }
+    }
+
+    fn from_value(value: Value) -> Result<Self> {
+        match value {
+            Value::Null => Ok(None),
+            v => Ok(Some(T::from_value(v)?)),
+        }
+    }
+}
+
+impl<T: ConvexType> ConvexType for Vec<T> {
+    fn into_value(self) -> Value {
+        Value::Array(self.into_iter().map(T::into_value).collect())
+    }
+
+    fn from_value(value: Value) -> Result<Self> {
+        match value {
+            Value::Array(values) => values.into_iter().map(T::from_value).collect(),
+            v => anyhow::bail!("Expected an array, got {v:?}"),
+        }
+    }
+}
+
+/// `BTreeMap<String, T>` corresponds to `Record<string, T>`.
+impl<T: ConvexType> ConvexType for BTreeMap<String, T> {
+    fn into_value(self) -> Value {
+        Value::Object(self.into_iter().map(|(k, v)| (k, v.into_value())).collect())
+    }
+
+    fn from_value(value: Value) -> Result<Self> {
+        match value {
+            Value::Object(fields) => fields
+                .into_iter()
+                .map(|(k, v)| Ok((k, T::from_value(v)?)))
+                .collect(),
+            v => anyhow::bail!("Expected an object, got {v:?}"),
+        }
+    }
+}
+
+/// Return the fields of an object value, for decoding the struct `type_name`.
+pub fn object_fields(value: Value, type_name: &str) -> Result<BTreeMap<String, Value>> {
+    match value {
+        Value::Object(fields) => Ok(fields),
+        v => anyhow::bail!("Expected an object for {type_name}, got {v:?}"),
+    }
+}
+
+/// Decode the required field `name`.
+pub fn field<T: ConvexType>(fields: &mut BTreeMap<String, Value>, name: &str) -> Result<T> {
+    let value = fields
+        .remove(name)
+        .ok_or_else(|| anyhow::anyhow!("Missing field {name}"))?;
+    T::from_value(value).map_err(|e| e.context(format!("In field {name}")))
+}
+
+/// Decode the required field `name`, which must be equal to `expected`.
+pub fn literal_field<T: ConvexType>(
+    fields: &mut BTreeMap<String, Value>,
+    name: &str,
+    expected: Value,
+) -> Result<T> {
+    let value = fields
+        .remove(name)
+        .ok_or_else(|| anyhow::anyhow!("Missing field {name}"))?;
+    anyhow::ensure!(
+        value == expected,
+        "Expected field {name} to be {expected:?}, got {value:?}"
+    );
+    T::from_value(value)
+}
+
+/// Decode the optional field `name`, which may be missing.
+pub fn optional_field<T: ConvexType>(
+    fields: &mut BTreeMap<String, Value>,
+    name: &str,
+) -> Result<Option<T>> {
+    fields
+        .remove(name)
+        .map(|value| T::from_value(value).map_err(|e| e.context(format!("In field {name}"))))
+        .transpose()
+}
+
+/// The error for a value that doesn't match any variant of the enum
+/// `type_name`.
+pub fn unknown_variant(type_name: &str, value: impl std::fmt::Debug) -> anyhow::Error {
+    anyhow::anyhow!("{value:?} doesn't match any variant of {type_name}")
+}
+
+/// Encode function arguments, which must be an object.
+pub fn args_object<T: ConvexType>(args: T) -> Result<BTreeMap<String, Value>> {
+    match args.into_value() {
+        Value::Object(fields) => Ok(fields),
+        v => anyhow::bail!("Function arguments must be an object, got {v:?}"),
+    }
+}
+
+/// Decode the result of a function call, turning function errors into
+/// [`anyhow::Error`]s. [`ConvexError`](crate::ConvexError)s can be recovered
+/// with [`anyhow::Error::downcast`].
+pub fn function_result<T: ConvexType>(result: FunctionResult) -> Result<T> {
+    match result {
+        FunctionResult::Value(value) => T::from_value(value),
+        FunctionResult::ErrorMessage(message) => Err(anyhow::anyhow!(message)),
+        FunctionResult::ConvexError(error) => Err(error.into()),
+    }
+}
+
+/// A [`QuerySubscription`] whose results are decoded into `T`.
+///
+/// Implements [`Stream`]<[`Result<T>`]>.
+pub struct TypedSubscription<T> {
+    subscription: QuerySubscription,
+    _result: PhantomData<fn() -> T>,
+}
+
+impl<T> TypedSubscription<T> {
+    /// Wrap an untyped subscription.
+    pub fn new(subscription: QuerySubscription) -> Self {
+        Self {
+            subscription,
+            _result: PhantomData,
+        }
+    }
+
+    /// Return the underlying untyped subscription.
+    pub fn into_inner(self) -> QuerySubscription {
+        self.subscription
+    }
+}
+
+impl<T: ConvexType> Stream for TypedSubscription<T> {
+    type Item = Result<T>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<Option<Self::Item>> {
+        self.subscription
+            .poll_next_unpin(cx)
+            .map(|result| result.map(function_result))
+    }
+}
@@ -271,7 +271,7 @@ impl<RT: Runtime> TransactionalFileStorage<RT> {
         })
     }
 
-    fn track_stream_usage(
+    pub(crate) fn track_stream_usage(
         component_path: ComponentPath,
         stream: BoxStream<'static, futures::io::Result<bytes::Bytes>>,
         get_file_type: GetFileType,
@@ -423,6 +423,21 @@ impl<RT: Runtime> FileStorage<RT> {
         namespace: TableNamespace,
         entry: FileStorageEntry,
         usage_tracker: &dyn StorageUsageTracker,
+    ) -> anyhow::Result<DeveloperDocumentId> {
+        // Start/Complete transaction after the slow upload process
+        // to avoid OCC risk.
+        let tx = self.database.begin(Identity::system()).await?;
+        self.store_entry_in_tx(tx, namespace, entry, usage_tracker)
+            .await
+    }
+
+    /// Like `store_entry`, but commits `tx` along with the new entry.
+    pub(crate) async fn store_entry_in_tx(
+        &self,
+        mut tx: Transaction<RT>,
+        namespace: TableNamespace,
+        entry: FileStorageEntry,
+        usage_tracker: &dyn StorageUsageTracker,
     ) -> anyhow::Result<DeveloperDocumentId> {
         let storage_id = entry.storage_id.clone();
         let size = entry.size;
@@ -433,9 +448,6 @@ impl<RT: Runtime> FileStorage<RT> {
             .transpose()?;
         let sha256 = entry.sha256.clone();
 
-        // Start/Complete transaction after the slow upload process
-        // to avoid OCC risk.
-        let mut tx = self.database.begin(Identity::system()).await?;
         let virtual_id = self
             .transactional_file_storage
             .store_file_entry(&mut tx, namespace, entry)
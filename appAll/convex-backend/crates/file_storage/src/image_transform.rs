@@ -0,0 +1,505 @@
+//! Server-side transformations of images in file storage.
+//!
+//! Transformed images are stored as derived objects in the files `Storage` and
+//! recorded in `_file_storage_image_variants`, keyed by the source object and
+//! a canonical description of the transform, so each variant is only computed
+//! once.
+
+use std::{
+    fmt,
+    io::Cursor,
+    str::FromStr,
+};
+
+use anyhow::Context;
+use bytes::Bytes;
+use common::{
+    components::ComponentId,
+    knobs::{
+        IMAGE_TRANSFORM_MAX_SOURCE_BYTES,
+        IMAGE_TRANSFORM_MAX_VARIANTS_PER_FILE,
+    },
+    runtime::Runtime,
+    types::ObjectKey,
+};
+use errors::ErrorMetadata;
+use futures::{
+    stream,
+    TryStreamExt,
+};
+use headers::{
+    ContentLength,
+    ContentType,
+};
+use image::{
+    codecs::{
+        jpeg::JpegEncoder,
+        webp::WebPEncoder,
+    },
+    imageops::FilterType,
+    DynamicImage,
+    ImageFormat,
+    ImageReader,
+    Limits,
+};
+use keybroker::Identity;
+use model::file_storage::{
+    image_variants::FileStorageImageVariantsModel,
+    types::{
+        FileStorageEntry,
+        FileStorageImageVariant,
+    },
+    FileStorageId,
+};
+use storage::{
+    StorageExt,
+    UploadExt,
+};
+use usage_tracking::StorageUsageTracker;
+
+use crate::{
+    metrics::GetFileType,
+    FileStorage,
+    FileStream,
+    TransactionalFileStorage,
+};
+
+/// Largest width or height, in pixels, of a source or transformed image.
+pub const MAX_IMAGE_DIMENSION: u32 = 8192;
+const DEFAULT_JPEG_QUALITY: u8 = 80;
+
+fn invalid_transform(msg: impl Into<String>) -> ErrorMetadata {
+    ErrorMetadata::bad_request("InvalidImageTransform", msg.into())
+}
+
+/// How the image is fit into the requested width and height when both are
+/// given.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ImageFit {
+    /// Scale to fit within the box, preserving the aspect ratio.
+    #[default]
+    Contain,
+    /// Scale to cover the box, preserving the aspect ratio, and crop the
+    /// overflow from the center.
+    Cover,
+    /// Stretch to exactly the requested size.
+    Fill,
+}
+
+impl FromStr for ImageFit {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "contain" => Ok(Self::Contain),
+            "cover" => Ok(Self::Cover),
+            "fill" => Ok(Self::Fill),
+            _ => anyhow::bail!(invalid_transform(format!(
+                "Invalid fit \"{s}\". Expected one of contain, cover or fill"
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for ImageFit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Contain => write!(f, "contain"),
+            Self::Cover => write!(f, "cover"),
+            Self::Fill => write!(f, "fill"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageOutputFormat {
+    Jpeg,
+    Png,
+    Webp,
+}
+
+impl ImageOutputFormat {
+    /// The format to keep when no output format is requested. Sources in a
+    /// format we can't encode are converted to PNG.
+    fn for_content_type(content_type: Option<&str>) -> Self {
+        match content_type {
+            Some("image/jpeg") => Self::Jpeg,
+            Some("image/webp") => Self::Webp,
+            _ => Self::Png,
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Jpeg => "image/jpeg",
+            Self::Png => "image/png",
+            Self::Webp => "image/webp",
+        }
+    }
+}
+
+impl FromStr for ImageOutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "jpeg" | "jpg" => Ok(Self::Jpeg),
+            "png" => Ok(Self::Png),
+            "webp" => Ok(Self::Webp),
+            _ => anyhow::bail!(invalid_transform(format!(
+                "Invalid format \"{s}\". Expected one of jpeg, png or webp"
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for ImageOutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Jpeg => write!(f, "jpeg"),
+            Self::Png => write!(f, "png"),
+            Self::Webp => write!(f, "webp"),
+        }
+    }
+}
+
+/// A rectangle of the source image to keep, applied before resizing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ImageCrop {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FromStr for ImageCrop {
+    type Err = anyhow::Error;
+
+    /// Parses `x,y,width,height`.
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let parts = s
+            .split(',')
+            .map(|part| part.trim().parse::<u32>())
+            .collect::<Result<Vec<_>, _>>()
+            .ok();
+        let Some([x, y, width, height]) = parts.as_deref() else {
+            anyhow::bail!(invalid_transform(format!(
+                "Invalid crop \"{s}\". Expected x,y,width,height"
+            )));
+        };
+        Ok(Self {
+            x: *x,
+            y: *y,
+            width: *width,
+            height: *height,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ImageTransform {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub fit: ImageFit,
+    /// Defaults to the format of the source image.
+    pub format: Option<ImageOutputFormat>,
+    /// JPEG quality from 1 to 100. PNG and WebP output is lossless, so it's
+    /// rejected for those formats.
+    pub quality: Option<u8>,
+    pub crop: Option<ImageCrop>,
+}
+
+impl ImageTransform {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        for (name, dimension) in [("width", self.width), ("height", self.height)] {
+            if let Some(dimension) = dimension {
+                anyhow::ensure!(
+                    (1..=MAX_IMAGE_DIMENSION).contains(&dimension),
+                    invalid_transform(format!(
+                        "Image {name} must be between 1 and {MAX_IMAGE_DIMENSION}"
+                    ))
+                );
+            }
+        }
+        if let Some(quality) = self.quality {
+            anyhow::ensure!(
+                (1..=100).contains(&quality),
+                invalid_transform("Image quality must be between 1 and 100")
+            );
+        }
+        if let Some(crop) = self.crop {
+            anyhow::ensure!(
+                crop.width > 0 && crop.height > 0,
+                invalid_transform("Crop width and height must be positive")
+            );
+        }
+        Ok(())
+    }
+
+    fn output_format(
+        &self,
+        source_content_type: Option<&str>,
+    ) -> anyhow::Result<ImageOutputFormat> {
+        let format = self
+            .format
+            .unwrap_or_else(|| ImageOutputFormat::for_content_type(source_content_type));
+        anyhow::ensure!(
+            self.quality.is_none() || format == ImageOutputFormat::Jpeg,
+            invalid_transform(format!(
+                "Image quality is only supported for jpeg output, not {format}"
+            ))
+        );
+        Ok(format)
+    }
+
+    /// A canonical string identifying the output of this transform for a given
+    /// output format. Equivalent transforms map to the same key.
+    fn cache_key(&self, format: ImageOutputFormat) -> String {
+        let mut key = String::new();
+        if let Some(crop) = self.crop {
+            key.push_str(&format!(
+                "crop={},{},{},{};",
+                crop.x, crop.y, crop.width, crop.height
+            ));
+        }
+        if let Some(width) = self.width {
+            key.push_str(&format!("w={width};"));
+        }
+        if let Some(height) = self.height {
+            key.push_str(&format!("h={height};"));
+        }
+        // Fit only matters when both dimensions are constrained.
+        if self.width.is_some() && self.height.is_some() {
+            key.push_str(&format!("fit={};", self.fit));
+        }
+        key.push_str(&format!("f={format}"));
+        if format == ImageOutputFormat::Jpeg {
+            key.push_str(&format!(
+                ";q={}",
+                self.quality.unwrap_or(DEFAULT_JPEG_QUALITY)
+            ));
+        }
+        key
+    }
+
+    /// Decode `source`, apply the transform and encode it as `format`. This is
+    /// CPU bound and should be run on a blocking thread.
+    fn apply(&self, source: &[u8], format: ImageOutputFormat) -> anyhow::Result<Vec<u8>> {
+        let mut reader = ImageReader::new(Cursor::new(source)).with_guessed_format()?;
+        let mut limits = Limits::default();
+        limits.max_image_width = Some(MAX_IMAGE_DIMENSION);
+        limits.max_image_height = Some(MAX_IMAGE_DIMENSION);
+        reader.limits(limits);
+        let mut image = reader.decode().map_err(|e| {
+            ErrorMetadata::bad_request(
+                "InvalidImage",
+                format!("Stored file could not be decoded as an image: {e}"),
+            )
+        })?;
+
+        if let Some(crop) = self.crop {
+            anyhow::ensure!(
+                crop.x.saturating_add(crop.width) <= image.width()
+                    && crop.y.saturating_add(crop.height) <= image.height(),
+                invalid_transform(format!(
+                    "Crop is outside of the {}x{} image",
+                    image.width(),
+                    image.height()
+                ))
+            );
+            image = image.crop_imm(crop.x, crop.y, crop.width, crop.height);
+        }
+
+        image = match (self.width, self.height) {
+            (None, None) => image,
+            (Some(width), None) => image.resize(width, MAX_IMAGE_DIMENSION, FilterType::Lanczos3),
+            (None, Some(height)) => image.resize(MAX_IMAGE_DIMENSION, height, FilterType::Lanczos3),
+            (Some(width), Some(height)) => match self.fit {
+                ImageFit::Contain => image.resize(width, height, FilterType::Lanczos3),
+                ImageFit::Cover => image.resize_to_fill(width, height, FilterType::Lanczos3),
+                ImageFit::Fill => image.resize_exact(width, height, FilterType::Lanczos3),
+            },
+        };
+
+        let mut output = Cursor::new(Vec::new());
+        match format {
+            ImageOutputFormat::Jpeg => {
+                // JPEG has no alpha channel.
+                let encoder = JpegEncoder::new_with_quality(
+                    &mut output,
+                    self.quality.unwrap_or(DEFAULT_JPEG_QUALITY),
+                );
+                DynamicImage::ImageRgb8(image.into_rgb8()).write_with_encoder(encoder)?;
+            },
+            ImageOutputFormat::Png => image.write_to(&mut output, ImageFormat::Png)?,
+            ImageOutputFormat::Webp => {
+                let encoder = WebPEncoder::new_lossless(&mut output);
+                DynamicImage::ImageRgba8(image.into_rgba8()).write_with_encoder(encoder)?;
+            },
+        }
+        Ok(output.into_inner())
+    }
+}
+
+/// Every distinct transform of a file is stored, so the number of variants per
+/// file is capped rather than letting requests for arbitrary sizes grow
+/// storage without bound.
+async fn ensure_variant_capacity<RT: Runtime>(
+    model: &mut FileStorageImageVariantsModel<'_, RT>,
+    source_storage_key: &ObjectKey,
+) -> anyhow::Result<()> {
+    let max_variants = *IMAGE_TRANSFORM_MAX_VARIANTS_PER_FILE;
+    anyhow::ensure!(
+        model.count_for_source(source_storage_key).await? < max_variants,
+        ErrorMetadata::bad_request(
+            "TooManyImageVariants",
+            format!(
+                "This image already has {max_variants} transformed variants. Reuse one of the \
+                 existing sizes and formats instead."
+            ),
+        )
+    );
+    Ok(())
+}
+
+impl<RT: Runtime> FileStorage<RT> {
+    /// Stream `transform` applied to the image stored at `storage_id`,
+    /// computing and caching the variant if this is the first request for it.
+    pub async fn get_transformed_image(
+        &self,
+        component: ComponentId,
+        storage_id: FileStorageId,
+        transform: ImageTransform,
+        usage_tracker: impl StorageUsageTracker + Clone + 'static,
+    ) -> anyhow::Result<FileStream> {
+        transform.validate()?;
+        let mut tx = self.database.begin(Identity::system()).await?;
+        let Some(entry) = self
+            .transactional_file_storage
+            .get_file_entry(&mut tx, component.into(), storage_id.clone())
+            .await?
+        else {
+            anyhow::bail!(ErrorMetadata::not_found(
+                "FileNotFound",
+                format!("File {storage_id} not found"),
+            ));
+        };
+        let Some(component_path) = tx.get_component_path(component) else {
+            anyhow::bail!(ErrorMetadata::not_found(
+                "FileNotFound",
+                format!("Component {component:?} not found"),
+            ));
+        };
+        let format = transform.output_format(entry.content_type.as_deref())?;
+        let transform_key = transform.cache_key(format);
+        let mut model = FileStorageImageVariantsModel::new(&mut tx);
+        let cached = model.get(&entry.storage_key, &transform_key).await?;
+        if cached.is_none() {
+            // Fail before doing the transform if it couldn't be stored anyway.
+            ensure_variant_capacity(&mut model, &entry.storage_key).await?;
+        }
+        drop(tx);
+
+        let variant = match cached {
+            Some(variant) => variant,
+            None => {
+                self.create_image_variant(&entry, &transform, format, transform_key)
+                    .await?
+            },
+        };
+
+        let storage_get_stream = self
+            .transactional_file_storage
+            .storage
+            .get(&variant.storage_key)
+            .await?
+            .with_context(|| format!("object {:?} not found", variant.storage_key))?;
+        let content_type: ContentType = variant.content_type.parse()?;
+        let call_tracker = usage_tracker.track_storage_call(
+            component_path.clone(),
+            "image transform",
+            entry.storage_id,
+            Some(content_type.clone()),
+            variant.sha256.clone(),
+        );
+        Ok(FileStream {
+            sha256: variant.sha256,
+            content_length: ContentLength(storage_get_stream.content_length as u64),
+            content_type: Some(content_type),
+            stream: TransactionalFileStorage::<RT>::track_stream_usage(
+                component_path,
+                storage_get_stream.stream,
+                GetFileType::ImageTransform,
+                call_tracker,
+            ),
+        })
+    }
+
+    async fn create_image_variant(
+        &self,
+        entry: &FileStorageEntry,
+        transform: &ImageTransform,
+        format: ImageOutputFormat,
+        transform_key: String,
+    ) -> anyhow::Result<FileStorageImageVariant> {
+        anyhow::ensure!(
+            entry.size as usize <= *IMAGE_TRANSFORM_MAX_SOURCE_BYTES,
+            ErrorMetadata::bad_request(
+                "ImageTooLarge",
+                format!(
+                    "Images larger than {} bytes can't be transformed",
+                    *IMAGE_TRANSFORM_MAX_SOURCE_BYTES
+                ),
+            )
+        );
+        let storage = &self.transactional_file_storage.storage;
+        let source: Vec<u8> = storage
+            .get(&entry.storage_key)
+            .await?
+            .with_context(|| format!("object {:?} not found", entry.storage_key))?
+            .stream
+            .map_ok(|chunk| chunk.to_vec())
+            .try_concat()
+            .await?;
+
+        let transform_ = transform.clone();
+        let output =
+            tokio::task::spawn_blocking(move || transform_.apply(&source, format)).await??;
+
+        let mut upload = storage.start_upload().await?;
+        let (size, sha256) = upload
+            .try_write_parallel_and_hash(stream::iter([Ok(Bytes::from(output))]))
+            .await?;
+        let storage_key = upload.complete().await?;
+        let variant = FileStorageImageVariant {
+            source_storage_key: entry.storage_key.clone(),
+            transform: transform_key,
+            storage_key,
+            sha256,
+            size: size.try_into()?,
+            content_type: format.content_type().to_string(),
+        };
+
+        let mut tx = self.database.begin(Identity::system()).await?;
+        let mut model = FileStorageImageVariantsModel::new(&mut tx);
+        if let Some(existing) = model
+            .get(&variant.source_storage_key, &variant.transform)
+            .await?
+        {
+            // A concurrent request for the same variant won the race.
+            drop(tx);
+            storage.delete_object(&variant.storage_key).await?;
+            return Ok(existing);
+        }
+        if let Err(e) = ensure_variant_capacity(&mut model, &variant.source_storage_key).await {
+            drop(tx);
+            storage.delete_object(&variant.storage_key).await?;
+            return Err(e);
+        }
+        model.insert(variant.clone()).await?;
+        self.database
+            .commit_with_write_source(tx, "file_storage_create_image_variant")
+            .await?;
+        Ok(variant)
+    }
+}
@@ -15,8 +15,17 @@ use headers::{
 };
 use storage::Storage;
 
+pub use crate::image_transform::{
+    ImageCrop,
+    ImageFit,
+    ImageOutputFormat,
+    ImageTransform,
+};
+
 mod core;
+mod image_transform;
 mod metrics;
+mod resumable;
 #[cfg(test)]
 mod tests;
 
@@ -36,6 +45,15 @@ pub struct FileRangeStream {
     pub stream: BoxStream<'static, futures::io::Result<bytes::Bytes>>,
 }
 
+/// Where a resumable upload currently stands. A client that lost its
+/// connection resumes by sending the next part at `offset`.
+#[derive(Debug, PartialEq)]
+pub struct ResumableUploadProgress {
+    pub upload_id: String,
+    pub offset: u64,
+    pub num_parts: usize,
+}
+
 #[derive(Clone)]
 pub struct FileStorage<RT: Runtime> {
     pub database: Database<RT>,
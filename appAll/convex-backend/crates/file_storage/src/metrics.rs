@@ -23,6 +23,8 @@ pub(crate) enum GetFileType {
     All,
     // If a range is specified in the request, even if the range is 0-
     Range,
+    /// A transformed variant of a stored image
+    ImageTransform,
 }
 
 impl GetFileType {
@@ -30,6 +32,9 @@ impl GetFileType {
         match self {
             GetFileType::All => StaticMetricLabel::new(GET_FILE_TYPE_LABEL, "all"),
             GetFileType::Range => StaticMetricLabel::new(GET_FILE_TYPE_LABEL, "range"),
+            GetFileType::ImageTransform => {
+                StaticMetricLabel::new(GET_FILE_TYPE_LABEL, "image_transform")
+            },
         }
     }
 }
@@ -0,0 +1,397 @@
+use bytes::Bytes;
+use common::{
+    components::ComponentId,
+    document::ParsedDocument,
+    knobs::{
+        RESUMABLE_UPLOAD_CLAIM_TIMEOUT,
+        RESUMABLE_UPLOAD_MAX_AGE,
+    },
+    runtime::Runtime,
+    sha256::{
+        Sha256,
+        Sha256Digest,
+    },
+    types::StorageUuid,
+};
+use database::Transaction;
+use errors::ErrorMetadata;
+use futures::TryStreamExt;
+use headers::ContentType;
+use keybroker::Identity;
+use model::file_storage::{
+    types::{
+        FileStorageEntry,
+        FileStorageUpload,
+        FileStorageUploadClaim,
+    },
+    uploads::FileStorageUploadsModel,
+};
+use storage::{
+    ClientDrivenUploadPartToken,
+    ClientDrivenUploadToken,
+    StorageExt,
+    MAX_NUM_PARTS,
+    MIN_CLIENT_DRIVEN_PART_SIZE,
+};
+use usage_tracking::StorageUsageTracker;
+use value::id_v6::DeveloperDocumentId;
+
+use crate::{
+    FileStorage,
+    ResumableUploadProgress,
+};
+
+fn upload_not_found(upload_id: &str) -> ErrorMetadata {
+    ErrorMetadata::not_found(
+        "UploadNotFound",
+        format!("Upload {upload_id} not found. It may have expired or already been finished."),
+    )
+}
+
+impl<RT: Runtime> FileStorage<RT> {
+    /// Start a resumable upload. Parts are appended with
+    /// `upload_resumable_part` and the file only becomes visible in `_storage`
+    /// once `finish_resumable_upload` is called.
+    pub async fn start_resumable_upload(
+        &self,
+        component: ComponentId,
+        content_type: Option<ContentType>,
+    ) -> anyhow::Result<ResumableUploadProgress> {
+        let storage = &self.transactional_file_storage.storage;
+        let upload_token = storage.start_client_driven_upload().await?;
+        let upload_id = self.transactional_file_storage.rt.new_uuid_v4().to_string();
+
+        let mut tx = self.database.begin(Identity::system()).await?;
+        let expiration_ts = tx.begin_timestamp().add(*RESUMABLE_UPLOAD_MAX_AGE)?;
+        FileStorageUploadsModel::new(&mut tx)
+            .insert(FileStorageUpload {
+                upload_id: upload_id.clone(),
+                component,
+                upload_token: upload_token.0,
+                content_type: content_type.map(|ct| ct.to_string()),
+                offset: 0,
+                part_tokens: vec![],
+                last_part_size: 0,
+                expiration_ts,
+                claim: None,
+            })
+            .await?;
+        self.database
+            .commit_with_write_source(tx, "file_storage_start_resumable_upload")
+            .await?;
+        Ok(ResumableUploadProgress {
+            upload_id,
+            offset: 0,
+            num_parts: 0,
+        })
+    }
+
+    /// Append `part` to the upload. `offset` must match the number of bytes
+    /// received so far, which a client can find with
+    /// `resumable_upload_progress` after a dropped connection.
+    ///
+    /// Parts must be sent one at a time: the upload is claimed while a part is
+    /// written, and a part sent meanwhile (e.g. a retry after a timeout) is
+    /// rejected. Every part except the last must be at least the minimum part
+    /// size of the underlying storage (5MiB), so once a smaller part has been
+    /// received the upload can only be finished.
+    pub async fn upload_resumable_part(
+        &self,
+        upload_id: &str,
+        offset: u64,
+        part: Bytes,
+    ) -> anyhow::Result<ResumableUploadProgress> {
+        anyhow::ensure!(
+            !part.is_empty(),
+            ErrorMetadata::bad_request("EmptyUploadPart", "Upload parts must not be empty")
+        );
+        let mut tx = self.database.begin(Identity::system()).await?;
+        let upload = self.get_upload(&mut tx, upload_id, offset).await?;
+        anyhow::ensure!(
+            upload.part_tokens.is_empty()
+                || upload.last_part_size >= MIN_CLIENT_DRIVEN_PART_SIZE as i64,
+            ErrorMetadata::bad_request(
+                "UploadPartTooSmall",
+                format!(
+                    "Upload {upload_id} already received a final part smaller than \
+                     {MIN_CLIENT_DRIVEN_PART_SIZE} bytes. Every part except the last must be at \
+                     least {MIN_CLIENT_DRIVEN_PART_SIZE} bytes."
+                ),
+            )
+        );
+        let part_number = upload.part_tokens.len() + 1;
+        anyhow::ensure!(
+            part_number <= MAX_NUM_PARTS,
+            ErrorMetadata::bad_request(
+                "TooManyUploadParts",
+                format!("Uploads can have at most {MAX_NUM_PARTS} parts"),
+            )
+        );
+        // Commit the claim before writing the part, so no other request can
+        // write a part with the same number.
+        let (upload, claim_id) = self.claim_upload(&mut tx, upload_id, upload, false).await?;
+        self.database
+            .commit_with_write_source(tx, "file_storage_claim_resumable_part")
+            .await?;
+
+        let part_size = part.len() as i64;
+        let part_token = match self
+            .transactional_file_storage
+            .storage
+            .upload_part(
+                ClientDrivenUploadToken(upload.upload_token),
+                part_number.try_into()?,
+                part,
+            )
+            .await
+        {
+            Ok(part_token) => part_token,
+            Err(e) => {
+                self.release_claim(upload_id, &claim_id).await;
+                return Err(e);
+            },
+        };
+
+        let mut tx = self.database.begin(Identity::system()).await?;
+        let upload = self.get_upload(&mut tx, upload_id, offset).await?;
+        ensure_claimed(&upload, upload_id, &claim_id)?;
+        let upload = FileStorageUploadsModel::new(&mut tx)
+            .record_part(upload, part_size, part_token.0)
+            .await?;
+        self.database
+            .commit_with_write_source(tx, "file_storage_upload_resumable_part")
+            .await?;
+        Ok(ResumableUploadProgress {
+            upload_id: upload.upload_id,
+            offset: upload.offset as u64,
+            num_parts: upload.part_tokens.len(),
+        })
+    }
+
+    pub async fn resumable_upload_progress(
+        &self,
+        upload_id: &str,
+    ) -> anyhow::Result<ResumableUploadProgress> {
+        let mut tx = self.database.begin(Identity::system()).await?;
+        let upload = FileStorageUploadsModel::new(&mut tx)
+            .get(upload_id)
+            .await?
+            .ok_or_else(|| upload_not_found(upload_id))?;
+        Ok(ResumableUploadProgress {
+            upload_id: upload_id.to_string(),
+            offset: upload.offset as u64,
+            num_parts: upload.part_tokens.len(),
+        })
+    }
+
+    /// Complete the upload and store it as a `_storage` entry in the component
+    /// the upload was started for.
+    pub async fn finish_resumable_upload(
+        &self,
+        upload_id: &str,
+        expected_sha256: Option<Sha256Digest>,
+        usage_tracker: &dyn StorageUsageTracker,
+    ) -> anyhow::Result<DeveloperDocumentId> {
+        // Claim the upload first so a concurrent finish fails here rather than
+        // completing the upload in storage a second time.
+        let mut tx = self.database.begin(Identity::system()).await?;
+        let upload = FileStorageUploadsModel::new(&mut tx)
+            .get(upload_id)
+            .await?
+            .ok_or_else(|| upload_not_found(upload_id))?;
+        let (upload, claim_id) = self.claim_upload(&mut tx, upload_id, upload, true).await?;
+        self.database
+            .commit_with_write_source(tx, "file_storage_claim_resumable_finish")
+            .await?;
+
+        let result = self
+            .finish_claimed_upload(upload_id, upload, &claim_id, expected_sha256, usage_tracker)
+            .await;
+        if result.is_err() {
+            self.release_claim(upload_id, &claim_id).await;
+        }
+        result
+    }
+
+    async fn finish_claimed_upload(
+        &self,
+        upload_id: &str,
+        upload: FileStorageUpload,
+        claim_id: &str,
+        expected_sha256: Option<Sha256Digest>,
+        usage_tracker: &dyn StorageUsageTracker,
+    ) -> anyhow::Result<DeveloperDocumentId> {
+        let storage = &self.transactional_file_storage.storage;
+        let storage_key = storage
+            .finish_client_driven_upload(
+                ClientDrivenUploadToken(upload.upload_token),
+                upload
+                    .part_tokens
+                    .into_iter()
+                    .map(ClientDrivenUploadPartToken)
+                    .collect(),
+            )
+            .await?;
+
+        // Parts arrive in separate requests, so the hash is computed once over
+        // the assembled object.
+        let mut hasher = Sha256::new();
+        let mut size = 0;
+        let mut stream = storage
+            .get(&storage_key)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Finished upload {storage_key:?} not found"))?
+            .stream;
+        while let Some(chunk) = stream.try_next().await? {
+            size += chunk.len() as i64;
+            hasher.update(&chunk);
+        }
+        let sha256 = hasher.finalize();
+        anyhow::ensure!(
+            size == upload.offset,
+            "Upload {upload_id} has {size} bytes in storage but received {}",
+            upload.offset
+        );
+        if let Some(expected_sha256) = expected_sha256
+            && expected_sha256 != sha256
+        {
+            anyhow::bail!(ErrorMetadata::bad_request(
+                "Sha256Mismatch",
+                format!(
+                    "Sha256 mismatch. Expected: {} Actual: {}",
+                    expected_sha256.as_base64(),
+                    sha256.as_base64()
+                ),
+            ));
+        }
+
+        let entry = FileStorageEntry {
+            storage_id: StorageUuid::from(self.transactional_file_storage.rt.new_uuid_v4()),
+            storage_key,
+            sha256,
+            size,
+            content_type: upload.content_type,
+        };
+
+        // Remove the upload in the same transaction that creates the entry so a
+        // retried finish can't create a second `_storage` document.
+        let mut tx = self.database.begin(Identity::system()).await?;
+        let upload_doc = FileStorageUploadsModel::new(&mut tx)
+            .get(upload_id)
+            .await?
+            .ok_or_else(|| upload_not_found(upload_id))?;
+        ensure_claimed(&upload_doc, upload_id, claim_id)?;
+        FileStorageUploadsModel::new(&mut tx)
+            .delete(upload_doc.id())
+            .await?;
+        self.store_entry_in_tx(tx, upload.component.into(), entry, usage_tracker)
+            .await
+    }
+
+    /// Claim the upload for this request, unless another request holds an
+    /// unexpired claim. The claim only takes effect once `tx` commits.
+    async fn claim_upload(
+        &self,
+        tx: &mut Transaction<RT>,
+        upload_id: &str,
+        upload: ParsedDocument<FileStorageUpload>,
+        finishing: bool,
+    ) -> anyhow::Result<(FileStorageUpload, String)> {
+        if let Some(claim) = &upload.claim
+            && claim.expiration_ts >= *tx.begin_timestamp()
+        {
+            anyhow::bail!(if claim.finishing {
+                ErrorMetadata::bad_request(
+                    "UploadFinishing",
+                    format!("Upload {upload_id} is already being finished"),
+                )
+            } else {
+                ErrorMetadata::bad_request(
+                    "UploadPartInProgress",
+                    format!(
+                        "Upload {upload_id} is still writing the part at offset {}. Check its \
+                         progress and retry once the part has been received.",
+                        upload.offset
+                    ),
+                )
+            });
+        }
+        let claim_id = self.transactional_file_storage.rt.new_uuid_v4().to_string();
+        let claim = FileStorageUploadClaim {
+            claim_id: claim_id.clone(),
+            finishing,
+            expiration_ts: tx.begin_timestamp().add(*RESUMABLE_UPLOAD_CLAIM_TIMEOUT)?,
+        };
+        let upload = FileStorageUploadsModel::new(tx)
+            .set_claim(upload, Some(claim))
+            .await?;
+        Ok((upload, claim_id))
+    }
+
+    /// Release this request's claim after it failed, so the client can retry
+    /// straight away.
+    async fn release_claim(&self, upload_id: &str, claim_id: &str) {
+        let result = async {
+            let mut tx = self.database.begin(Identity::system()).await?;
+            let mut model = FileStorageUploadsModel::new(&mut tx);
+            if let Some(upload) = model.get(upload_id).await?
+                && ensure_claimed(&upload, upload_id, claim_id).is_ok()
+            {
+                model.set_claim(upload, None).await?;
+                self.database
+                    .commit_with_write_source(tx, "file_storage_release_resumable_upload")
+                    .await?;
+            }
+            anyhow::Ok(())
+        }
+        .await;
+        if let Err(e) = result {
+            tracing::warn!("Failed to release claim on upload {upload_id}: {e:#}");
+        }
+    }
+
+    async fn get_upload(
+        &self,
+        tx: &mut Transaction<RT>,
+        upload_id: &str,
+        offset: u64,
+    ) -> anyhow::Result<ParsedDocument<FileStorageUpload>> {
+        let upload = FileStorageUploadsModel::new(tx)
+            .get(upload_id)
+            .await?
+            .ok_or_else(|| upload_not_found(upload_id))?;
+        anyhow::ensure!(
+            upload.offset as u64 == offset,
+            ErrorMetadata::bad_request(
+                "UploadOffsetMismatch",
+                format!(
+                    "Upload {upload_id} expected a part at offset {}, not {offset}",
+                    upload.offset
+                ),
+            )
+        );
+        Ok(upload)
+    }
+}
+
+fn ensure_claimed(
+    upload: &FileStorageUpload,
+    upload_id: &str,
+    claim_id: &str,
+) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        upload
+            .claim
+            .as_ref()
+            .is_some_and(|claim| claim.claim_id == claim_id),
+        ErrorMetadata::bad_request(
+            "UploadClaimExpired",
+            format!(
+                "Upload {upload_id} was taken over by another request after this one took longer \
+                 than {:?}. Check its progress before retrying.",
+                *RESUMABLE_UPLOAD_CLAIM_TIMEOUT
+            ),
+        )
+    );
+    Ok(())
+}
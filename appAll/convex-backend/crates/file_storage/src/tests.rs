@@ -1,6 +1,16 @@
-use std::sync::Arc;
+use std::{
+    sync::Arc,
+    time::Duration,
+};
 
+use bytes::Bytes;
 use common::{
+    components::ComponentId,
+    knobs::{
+        IMAGE_TRANSFORM_MAX_VARIANTS_PER_FILE,
+        RESUMABLE_UPLOAD_CLAIM_TIMEOUT,
+        RESUMABLE_UPLOAD_MAX_AGE,
+    },
     runtime::Runtime,
     sha256::Sha256,
 };
@@ -16,19 +26,41 @@ use errors::{
     ErrorMetadata,
 };
 use events::usage::NoOpUsageEventLogger;
-use futures::stream;
+use futures::{
+    future,
+    stream,
+    TryStreamExt,
+};
+use headers::ContentType;
 use keybroker::Identity;
 use model::{
-    file_storage::FileStorageId,
+    file_storage::{
+        blobs::FileStorageBlobsModel,
+        image_variants::FileStorageImageVariantsModel,
+        types::FileStorageUploadClaim,
+        uploads::FileStorageUploadsModel,
+        FileStorageId,
+        FileStorageModel,
+    },
     test_helpers::DbFixturesWithModel,
 };
 use runtime::testing::TestRuntime;
-use storage::LocalDirStorage;
+use storage::{
+    ClientDrivenUploadToken,
+    LocalDirStorage,
+    MIN_CLIENT_DRIVEN_PART_SIZE,
+};
 use usage_tracking::UsageCounter;
 use value::TableNamespace;
 
 use super::FileStorage;
-use crate::TransactionalFileStorage;
+use crate::{
+    ImageFit,
+    ImageOutputFormat,
+    ImageTransform,
+    ResumableUploadProgress,
+    TransactionalFileStorage,
+};
 
 fn setup_file_storage(
     rt: TestRuntime,
@@ -84,3 +116,555 @@ async fn test_store_file_sha_mismatch(rt: TestRuntime) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[convex_macro::test_runtime]
+async fn test_store_file_dedup(rt: TestRuntime) -> anyhow::Result<()> {
+    let database = DbFixtures::new_with_model(&rt).await?.db;
+    let file_storage = setup_file_storage(rt, &database)?;
+    let usage_tracker = UsageCounter::new(Arc::new(NoOpUsageEventLogger));
+    let namespace = TableNamespace::test_user();
+
+    let mut ids = vec![];
+    for _ in 0..2 {
+        let id = file_storage
+            .store_file(
+                namespace,
+                None,
+                None,
+                stream::iter([Ok(b"avatar".to_vec())]),
+                None,
+                &usage_tracker,
+            )
+            .await?;
+        ids.push(FileStorageId::DocumentId(id));
+    }
+
+    let mut tx = database.begin(Identity::system()).await?;
+    let mut entries = vec![];
+    for id in &ids {
+        entries.push(
+            file_storage
+                .transactional_file_storage
+                .get_file_entry(&mut tx, namespace, id.clone())
+                .await?
+                .unwrap(),
+        );
+    }
+    assert_ne!(entries[0].storage_id, entries[1].storage_id);
+    assert_eq!(entries[0].storage_key, entries[1].storage_key);
+    let shared_key = entries[0].storage_key.clone();
+
+    // Only the duplicate upload is unreferenced.
+    let unreferenced = FileStorageBlobsModel::new(&mut tx)
+        .cleanup_unreferenced(10)
+        .await?;
+    assert_eq!(unreferenced.len(), 1);
+    assert_ne!(unreferenced[0], shared_key);
+    database.commit(tx).await?;
+
+    // Deleting one entry keeps the shared object alive.
+    let mut tx = database.begin(Identity::system()).await?;
+    file_storage
+        .transactional_file_storage
+        .delete(&mut tx, namespace, ids[0].clone())
+        .await?;
+    assert!(FileStorageBlobsModel::new(&mut tx)
+        .cleanup_unreferenced(10)
+        .await?
+        .is_empty());
+    database.commit(tx).await?;
+
+    // Deleting the last entry makes it collectable.
+    let mut tx = database.begin(Identity::system()).await?;
+    file_storage
+        .transactional_file_storage
+        .delete(&mut tx, namespace, ids[1].clone())
+        .await?;
+    let unreferenced = FileStorageBlobsModel::new(&mut tx)
+        .cleanup_unreferenced(10)
+        .await?;
+    assert_eq!(unreferenced, vec![shared_key]);
+    database.commit(tx).await?;
+
+    Ok(())
+}
+
+#[convex_macro::test_runtime]
+async fn test_remove_all_references(rt: TestRuntime) -> anyhow::Result<()> {
+    let database = DbFixtures::new_with_model(&rt).await?.db;
+    let file_storage = setup_file_storage(rt, &database)?;
+    let usage_tracker = UsageCounter::new(Arc::new(NoOpUsageEventLogger));
+    let namespace = TableNamespace::test_user();
+
+    for _ in 0..2 {
+        file_storage
+            .store_file(
+                namespace,
+                None,
+                None,
+                stream::iter([Ok(b"avatar".to_vec())]),
+                None,
+                &usage_tracker,
+            )
+            .await?;
+    }
+    let mut tx = database.begin(Identity::system()).await?;
+    // Drop the duplicate upload so only the shared object is left.
+    assert_eq!(
+        FileStorageBlobsModel::new(&mut tx)
+            .cleanup_unreferenced(10)
+            .await?
+            .len(),
+        1
+    );
+    database.commit(tx).await?;
+
+    // Dropping every entry's reference, as deleting the `_file_storage` table
+    // does, makes the shared object collectable.
+    let mut tx = database.begin(Identity::system()).await?;
+    FileStorageModel::new(&mut tx, namespace)
+        .remove_all_references()
+        .await?;
+    assert_eq!(
+        FileStorageBlobsModel::new(&mut tx)
+            .cleanup_unreferenced(10)
+            .await?
+            .len(),
+        1
+    );
+    database.commit(tx).await?;
+
+    Ok(())
+}
+
+#[convex_macro::test_runtime]
+async fn test_resumable_upload(rt: TestRuntime) -> anyhow::Result<()> {
+    let database = DbFixtures::new_with_model(&rt).await?.db;
+    let file_storage = setup_file_storage(rt, &database)?;
+    let usage_tracker = UsageCounter::new(Arc::new(NoOpUsageEventLogger));
+    let first_part = Bytes::from(vec![b'a'; MIN_CLIENT_DRIVEN_PART_SIZE]);
+    let last_part = Bytes::from_static(b"tail");
+    let mut hasher = Sha256::new();
+    hasher.update(&first_part);
+    hasher.update(&last_part);
+    let sha256 = hasher.finalize();
+
+    let ResumableUploadProgress { upload_id, .. } = file_storage
+        .start_resumable_upload(ComponentId::test_user(), None)
+        .await?;
+    let progress = file_storage
+        .upload_resumable_part(&upload_id, 0, first_part.clone())
+        .await?;
+    let first_offset = MIN_CLIENT_DRIVEN_PART_SIZE as u64;
+    assert_eq!(
+        progress,
+        ResumableUploadProgress {
+            upload_id: upload_id.clone(),
+            offset: first_offset,
+            num_parts: 1,
+        }
+    );
+
+    // A retried part at a stale offset is rejected without being appended.
+    let err: ErrorMetadata = file_storage
+        .upload_resumable_part(&upload_id, 0, first_part)
+        .await
+        .unwrap_err()
+        .downcast()?;
+    assert_eq!(err.short_msg, "UploadOffsetMismatch");
+    assert_eq!(
+        file_storage.resumable_upload_progress(&upload_id).await?,
+        progress
+    );
+
+    file_storage
+        .upload_resumable_part(&upload_id, first_offset, last_part.clone())
+        .await?;
+    // Only the last part may be smaller than the minimum part size, so nothing
+    // can follow it.
+    let err: ErrorMetadata = file_storage
+        .upload_resumable_part(
+            &upload_id,
+            first_offset + last_part.len() as u64,
+            last_part.clone(),
+        )
+        .await
+        .unwrap_err()
+        .downcast()?;
+    assert_eq!(err.short_msg, "UploadPartTooSmall");
+
+    let id = file_storage
+        .finish_resumable_upload(&upload_id, Some(sha256.clone()), &usage_tracker)
+        .await?;
+
+    let mut tx = database.begin(Identity::system()).await?;
+    let entry = file_storage
+        .transactional_file_storage
+        .get_file_entry(
+            &mut tx,
+            TableNamespace::test_user(),
+            FileStorageId::DocumentId(id),
+        )
+        .await?
+        .unwrap();
+    assert_eq!(
+        entry.size as usize,
+        MIN_CLIENT_DRIVEN_PART_SIZE + last_part.len()
+    );
+    assert_eq!(entry.sha256, sha256);
+
+    // The upload can't be finished twice.
+    let err: ErrorMetadata = file_storage
+        .finish_resumable_upload(&upload_id, None, &usage_tracker)
+        .await
+        .unwrap_err()
+        .downcast()?;
+    assert_eq!(err.code, ErrorCode::NotFound);
+
+    Ok(())
+}
+
+#[convex_macro::test_runtime]
+async fn test_resumable_upload_expired(rt: TestRuntime) -> anyhow::Result<()> {
+    let database = DbFixtures::new_with_model(&rt).await?.db;
+    let file_storage = setup_file_storage(rt.clone(), &database)?;
+    let storage = file_storage.transactional_file_storage.storage.clone();
+
+    let ResumableUploadProgress { upload_id, .. } = file_storage
+        .start_resumable_upload(ComponentId::test_user(), None)
+        .await?;
+    file_storage
+        .upload_resumable_part(
+            &upload_id,
+            0,
+            Bytes::from(vec![b'a'; MIN_CLIENT_DRIVEN_PART_SIZE]),
+        )
+        .await?;
+    assert_eq!(
+        storage.list_objects().try_collect::<Vec<_>>().await?.len(),
+        1
+    );
+
+    rt.advance_time(*RESUMABLE_UPLOAD_MAX_AGE + Duration::from_secs(1))
+        .await;
+    database.bump_max_repeatable_ts().await?;
+
+    // Expired uploads can't be continued.
+    let err: ErrorMetadata = file_storage
+        .upload_resumable_part(
+            &upload_id,
+            MIN_CLIENT_DRIVEN_PART_SIZE as u64,
+            Bytes::from_static(b"tail"),
+        )
+        .await
+        .unwrap_err()
+        .downcast()?;
+    assert_eq!(err.code, ErrorCode::NotFound);
+
+    // Cleaning them up hands back the tokens needed to discard the staged parts.
+    let mut tx = database.begin(Identity::system()).await?;
+    let upload_tokens = FileStorageUploadsModel::new(&mut tx)
+        .cleanup_expired(10)
+        .await?;
+    assert_eq!(upload_tokens.len(), 1);
+    database.commit(tx).await?;
+    for upload_token in upload_tokens {
+        storage
+            .abort_client_driven_upload(ClientDrivenUploadToken(upload_token))
+            .await?;
+    }
+    assert!(storage
+        .list_objects()
+        .try_collect::<Vec<_>>()
+        .await?
+        .is_empty());
+
+    Ok(())
+}
+
+#[convex_macro::test_runtime]
+async fn test_resumable_upload_concurrent_parts(rt: TestRuntime) -> anyhow::Result<()> {
+    let database = DbFixtures::new_with_model(&rt).await?.db;
+    let file_storage = setup_file_storage(rt, &database)?;
+    let usage_tracker = UsageCounter::new(Arc::new(NoOpUsageEventLogger));
+    let parts = [
+        Bytes::from(vec![b'a'; MIN_CLIENT_DRIVEN_PART_SIZE]),
+        Bytes::from(vec![b'b'; MIN_CLIENT_DRIVEN_PART_SIZE]),
+    ];
+
+    let ResumableUploadProgress { upload_id, .. } = file_storage
+        .start_resumable_upload(ComponentId::test_user(), None)
+        .await?;
+    // A retry racing the original request for the same offset: only one of
+    // them may write the part.
+    let (first, second) = future::join(
+        file_storage.upload_resumable_part(&upload_id, 0, parts[0].clone()),
+        file_storage.upload_resumable_part(&upload_id, 0, parts[1].clone()),
+    )
+    .await;
+    let winner = match (first, second) {
+        (Ok(_), Err(_)) => &parts[0],
+        (Err(_), Ok(_)) => &parts[1],
+        (first, second) => anyhow::bail!("Expected one part to win: {first:?}, {second:?}"),
+    };
+
+    // Both finishes race too, and only one completes the upload.
+    let (first, second) = future::join(
+        file_storage.finish_resumable_upload(&upload_id, None, &usage_tracker),
+        file_storage.finish_resumable_upload(&upload_id, None, &usage_tracker),
+    )
+    .await;
+    let id = match (first, second) {
+        (Ok(id), Err(_)) | (Err(_), Ok(id)) => id,
+        (first, second) => anyhow::bail!("Expected one finish to win: {first:?}, {second:?}"),
+    };
+
+    // The stored file is exactly the winning part.
+    let mut tx = database.begin(Identity::system()).await?;
+    let entry = file_storage
+        .transactional_file_storage
+        .get_file_entry(
+            &mut tx,
+            TableNamespace::test_user(),
+            FileStorageId::DocumentId(id),
+        )
+        .await?
+        .unwrap();
+    let mut hasher = Sha256::new();
+    hasher.update(winner);
+    assert_eq!(entry.sha256, hasher.finalize());
+    assert_eq!(entry.size as usize, MIN_CLIENT_DRIVEN_PART_SIZE);
+
+    Ok(())
+}
+
+async fn claim_for_other_request(
+    database: &Database<TestRuntime>,
+    upload_id: &str,
+    finishing: bool,
+) -> anyhow::Result<()> {
+    let mut tx = database.begin(Identity::system()).await?;
+    let expiration_ts = tx.begin_timestamp().add(*RESUMABLE_UPLOAD_CLAIM_TIMEOUT)?;
+    let mut model = FileStorageUploadsModel::new(&mut tx);
+    let upload = model.get(upload_id).await?.unwrap();
+    model
+        .set_claim(
+            upload,
+            Some(FileStorageUploadClaim {
+                claim_id: "other-request".to_string(),
+                finishing,
+                expiration_ts,
+            }),
+        )
+        .await?;
+    database.commit(tx).await?;
+    Ok(())
+}
+
+#[convex_macro::test_runtime]
+async fn test_resumable_upload_claimed(rt: TestRuntime) -> anyhow::Result<()> {
+    let database = DbFixtures::new_with_model(&rt).await?.db;
+    let file_storage = setup_file_storage(rt.clone(), &database)?;
+    let usage_tracker = UsageCounter::new(Arc::new(NoOpUsageEventLogger));
+    let ResumableUploadProgress { upload_id, .. } = file_storage
+        .start_resumable_upload(ComponentId::test_user(), None)
+        .await?;
+
+    // A request still writing a part blocks other parts and finishing.
+    claim_for_other_request(&database, &upload_id, false).await?;
+    let err: ErrorMetadata = file_storage
+        .upload_resumable_part(&upload_id, 0, Bytes::from_static(b"tail"))
+        .await
+        .unwrap_err()
+        .downcast()?;
+    assert_eq!(err.short_msg, "UploadPartInProgress");
+    let err: ErrorMetadata = file_storage
+        .finish_resumable_upload(&upload_id, None, &usage_tracker)
+        .await
+        .unwrap_err()
+        .downcast()?;
+    assert_eq!(err.short_msg, "UploadPartInProgress");
+
+    // A request that's finishing the upload makes others fail fast.
+    claim_for_other_request(&database, &upload_id, true).await?;
+    let err: ErrorMetadata = file_storage
+        .finish_resumable_upload(&upload_id, None, &usage_tracker)
+        .await
+        .unwrap_err()
+        .downcast()?;
+    assert_eq!(err.short_msg, "UploadFinishing");
+
+    // Claims of requests that died lapse.
+    rt.advance_time(*RESUMABLE_UPLOAD_CLAIM_TIMEOUT + Duration::from_secs(1))
+        .await;
+    database.bump_max_repeatable_ts().await?;
+    let progress = file_storage
+        .upload_resumable_part(&upload_id, 0, Bytes::from_static(b"tail"))
+        .await?;
+    assert_eq!(progress.offset, 4);
+
+    Ok(())
+}
+
+#[convex_macro::test_runtime]
+async fn test_image_transform(rt: TestRuntime) -> anyhow::Result<()> {
+    let database = DbFixtures::new_with_model(&rt).await?.db;
+    let file_storage = setup_file_storage(rt, &database)?;
+    let usage_tracker = UsageCounter::new(Arc::new(NoOpUsageEventLogger));
+
+    let mut png = std::io::Cursor::new(vec![]);
+    image::RgbImage::from_pixel(40, 20, image::Rgb([255, 0, 0]))
+        .write_to(&mut png, image::ImageFormat::Png)?;
+    let id = file_storage
+        .store_file(
+            TableNamespace::test_user(),
+            None,
+            Some(ContentType::png()),
+            stream::iter(vec![Ok(png.into_inner())]),
+            None,
+            &usage_tracker,
+        )
+        .await?;
+    let storage_id = FileStorageId::DocumentId(id);
+
+    let transform = ImageTransform {
+        width: Some(10),
+        format: Some(ImageOutputFormat::Jpeg),
+        ..Default::default()
+    };
+    let file_stream = file_storage
+        .get_transformed_image(
+            ComponentId::test_user(),
+            storage_id.clone(),
+            transform.clone(),
+            usage_tracker.clone(),
+        )
+        .await?;
+    assert_eq!(file_stream.content_type, Some(ContentType::jpeg()));
+    let output: Vec<u8> = file_stream
+        .stream
+        .map_ok(|chunk| chunk.to_vec())
+        .try_concat()
+        .await?;
+    let output = image::load_from_memory(&output)?;
+    assert_eq!((output.width(), output.height()), (10, 5));
+
+    // The variant is cached and served again on the next request.
+    let mut tx = database.begin(Identity::system()).await?;
+    let entry = file_storage
+        .transactional_file_storage
+        .get_file_entry(&mut tx, TableNamespace::test_user(), storage_id.clone())
+        .await?
+        .unwrap();
+    let variant = FileStorageImageVariantsModel::new(&mut tx)
+        .get(&entry.storage_key, "w=10;f=jpeg;q=80")
+        .await?
+        .unwrap();
+    let file_stream = file_storage
+        .get_transformed_image(
+            ComponentId::test_user(),
+            storage_id.clone(),
+            transform,
+            usage_tracker.clone(),
+        )
+        .await?;
+    assert_eq!(file_stream.sha256, variant.sha256);
+
+    // Crops outside the source image are rejected.
+    let err: ErrorMetadata = file_storage
+        .get_transformed_image(
+            ComponentId::test_user(),
+            storage_id.clone(),
+            ImageTransform {
+                width: Some(10),
+                height: Some(10),
+                fit: ImageFit::Cover,
+                crop: Some("30,0,20,20".parse()?),
+                ..Default::default()
+            },
+            usage_tracker.clone(),
+        )
+        .await
+        .unwrap_err()
+        .downcast()?;
+    assert_eq!(err.short_msg, "InvalidImageTransform");
+
+    // WebP output is lossless, so a quality can't be honored.
+    let err: ErrorMetadata = file_storage
+        .get_transformed_image(
+            ComponentId::test_user(),
+            storage_id,
+            ImageTransform {
+                format: Some(ImageOutputFormat::Webp),
+                quality: Some(50),
+                ..Default::default()
+            },
+            usage_tracker,
+        )
+        .await
+        .unwrap_err()
+        .downcast()?;
+    assert_eq!(err.short_msg, "InvalidImageTransform");
+
+    Ok(())
+}
+
+#[convex_macro::test_runtime]
+async fn test_image_variants_per_file_limit(rt: TestRuntime) -> anyhow::Result<()> {
+    let database = DbFixtures::new_with_model(&rt).await?.db;
+    let file_storage = setup_file_storage(rt, &database)?;
+    let usage_tracker = UsageCounter::new(Arc::new(NoOpUsageEventLogger));
+
+    let mut png = std::io::Cursor::new(vec![]);
+    image::RgbImage::from_pixel(4, 4, image::Rgb([255, 0, 0]))
+        .write_to(&mut png, image::ImageFormat::Png)?;
+    let id = file_storage
+        .store_file(
+            TableNamespace::test_user(),
+            None,
+            Some(ContentType::png()),
+            stream::iter(vec![Ok(png.into_inner())]),
+            None,
+            &usage_tracker,
+        )
+        .await?;
+    let storage_id = FileStorageId::DocumentId(id);
+    let max_variants = *IMAGE_TRANSFORM_MAX_VARIANTS_PER_FILE as u32;
+    let transform = |width| ImageTransform {
+        width: Some(width),
+        ..Default::default()
+    };
+
+    for width in 1..=max_variants {
+        file_storage
+            .get_transformed_image(
+                ComponentId::test_user(),
+                storage_id.clone(),
+                transform(width),
+                usage_tracker.clone(),
+            )
+            .await?;
+    }
+    // Existing variants are still served, but no new ones are created.
+    file_storage
+        .get_transformed_image(
+            ComponentId::test_user(),
+            storage_id.clone(),
+            transform(1),
+            usage_tracker.clone(),
+        )
+        .await?;
+    let err: ErrorMetadata = file_storage
+        .get_transformed_image(
+            ComponentId::test_user(),
+            storage_id,
+            transform(max_variants + 1),
+            usage_tracker,
+        )
+        .await
+        .unwrap_err()
+        .downcast()?;
+    assert_eq!(err.short_msg, "TooManyImageVariants");
+
+    Ok(())
+}
@@ -487,6 +487,43 @@ impl<RT: Runtime> Request<RT> {
         }
     }
 
+    /// Span properties identifying the function this request runs, if any.
+    fn trace_properties(&self) -> Vec<(&'static str, String)> {
+        let (udf_type, path, context) = match &self.inner {
+            RequestType::Udf { request, .. } => (
+                request.udf_type.to_lowercase_string(),
+                request.path_and_args.path(),
+                &request.context,
+            ),
+            RequestType::Action { request, .. } => (
+                "action",
+                request.params.path_and_args.path(),
+                &request.context,
+            ),
+            RequestType::HttpAction { request, .. } => (
+                "http_action",
+                request.http_module_path.path(),
+                &request.context,
+            ),
+            RequestType::Analyze { .. }
+            | RequestType::EvaluateSchema { .. }
+            | RequestType::EvaluateAuthConfig { .. }
+            | RequestType::EvaluateAppDefinitions { .. }
+            | RequestType::EvaluateComponentInitializer { .. } => return vec![],
+        };
+        let mut properties = vec![
+            ("udf_type", udf_type.to_string()),
+            ("udf_path", path.udf_path.to_string()),
+            ("request_id", context.request_id.to_string()),
+        ];
+        if let Some(component_path) = &path.component_path
+            && !component_path.is_root()
+        {
+            properties.push(("component_path", component_path.to_string()));
+        }
+        properties
+    }
+
     fn reject(self) {
         let error =
             ErrorMetadata::rejected_before_execution("WorkerOverloaded", NO_AVAILABLE_WORKERS)
@@ -1382,7 +1419,8 @@ pub trait IsolateWorker<RT: Runtime>: Clone + Send + 'static {
                     let Some((req, done, done_token)) = req else {
                         return;
                     };
-                    let root = initialize_root_from_parent(func_path!(),req.parent_trace.clone());
+                    let root = initialize_root_from_parent(func_path!(),req.parent_trace.clone())
+                        .with_properties(|| req.trace_properties());
                     // If we receive a request from a different client (i.e. a different backend),
                     // recreate the isolate. We don't allow an isolate to be reused
                     // across clients for security isolation.
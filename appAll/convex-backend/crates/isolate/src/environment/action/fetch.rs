@@ -2,13 +2,27 @@ use std::time::Duration;
 
 use ::metrics::StatusTimer;
 use common::{
+    fastrace_helpers::{
+        SPAN_KIND_CLIENT,
+        SPAN_KIND_PROPERTY,
+    },
     http::{
         HttpRequestStream,
         HttpResponseStream,
+        TRACEPARENT_HEADER,
     },
     runtime::Runtime,
 };
 use errors::ErrorMetadata;
+use fastrace::{
+    collector::SpanContext,
+    future::FutureExt as _,
+    Span,
+};
+use http::{
+    HeaderName,
+    HeaderValue,
+};
 
 use super::task_executor::TaskExecutor;
 use crate::{
@@ -64,9 +78,27 @@ impl<RT: Runtime> TaskExecutor<RT> {
     #[convex_macro::instrument_future]
     async fn run_fetch_inner(
         &self,
-        request: HttpRequestStream,
+        mut request: HttpRequestStream,
     ) -> anyhow::Result<HttpResponseStream> {
-        self.fetch_client.fetch(request).await
+        let span = Span::enter_with_local_parent("fetch").with_properties(|| {
+            [
+                (SPAN_KIND_PROPERTY, SPAN_KIND_CLIENT.to_string()),
+                ("http.method", request.method.to_string()),
+                ("http.origin", request.url.origin().unicode_serialization()),
+            ]
+        });
+        // Continue the trace in the service being called, unless the developer
+        // is already propagating their own.
+        let traceparent = HeaderName::from_static(TRACEPARENT_HEADER);
+        if let Some(span_ctx) = SpanContext::from_span(&span)
+            && !request.headers.contains_key(&traceparent)
+        {
+            request.headers.insert(
+                traceparent,
+                HeaderValue::from_str(&span_ctx.encode_w3c_traceparent())?,
+            );
+        }
+        self.fetch_client.fetch(request).in_span(span).await
     }
 
     fn log_fetch_request(
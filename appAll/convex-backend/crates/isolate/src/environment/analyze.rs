@@ -54,6 +54,10 @@ use model::{
             ArgsValidator,
             ReturnsValidator,
         },
+        http_route_policy::{
+            HttpRoutePolicy,
+            SerializedHttpRoutePolicy,
+        },
         module_versions::{
             invalid_function_name_error,
             AnalyzedFunction,
@@ -955,12 +959,31 @@ fn http_analyze<RT: Runtime>(
         if source_pos.is_none() {
             tracing::warn!("Failed to resolve {module_path:?}:{path}");
         }
+
+        // Newer routers add the route's CORS and rate limit policy as JSON.
+        let policy = match entry.get_index(scope, 3) {
+            Some(policy) if policy.is_string() => {
+                let policy = policy.to_rust_string_lossy(scope);
+                let policy = serde_json::from_str::<SerializedHttpRoutePolicy>(&policy)
+                    .map_err(anyhow::Error::from)
+                    .and_then(HttpRoutePolicy::try_from);
+                match policy {
+                    Ok(policy) => policy,
+                    Err(e) => {
+                        let message = format!("Invalid options for route {method} {path}: {e}");
+                        return Ok(Err(JsError::from_message(message)));
+                    },
+                }
+            },
+            _ => HttpRoutePolicy::default(),
+        };
         http_routes.push(AnalyzedHttpRoute {
             route: HttpActionRoute {
                 path: path.clone(),
                 method,
             },
             pos: source_pos,
+            policy,
         });
     }
 
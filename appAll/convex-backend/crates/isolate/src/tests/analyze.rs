@@ -109,11 +109,15 @@ async fn test_analyze_module(rt: TestRuntime) -> anyhow::Result<()> {
     assert_eq!(module.functions.len(), expected.len());
     let expected_routes_unmapped = vec![
         ("/imported", RoutableMethod::Get),
+        ("/stream_count", RoutableMethod::Get),
+        ("/web_socket_count", RoutableMethod::Get),
         ("/separate_function", RoutableMethod::Get),
         ("/inline", RoutableMethod::Get),
     ];
     let expected_routes_mapped = vec![
         ("/imported", RoutableMethod::Get, None),
+        ("/stream_count", RoutableMethod::Get, None),
+        ("/web_socket_count", RoutableMethod::Get, None),
         (
             "/separate_function",
             RoutableMethod::Get,
@@ -64,6 +64,7 @@ pub fn http_request(path: &str) -> HttpActionRequest {
             method: Method::GET,
         },
         body: None,
+        client_address: None,
     }
 }
 
@@ -75,6 +76,7 @@ pub fn http_post_request(path: &str, body: Vec<u8>) -> HttpActionRequest {
             method: Method::POST,
         },
         body: Some(stream::once(async move { Ok(body.into()) }).boxed()),
+        client_address: None,
     }
 }
 
@@ -0,0 +1,130 @@
+//! Copies a self-hosted deployment's persistence from one database driver to
+//! another, e.g. when moving off SQLite:
+//!
+//! ```sh
+//! migrate_persistence --instance-name convex-self-hosted \
+//!     --source-db sqlite --source-db-spec convex_local_backend.sqlite3 \
+//!     --target-db postgres-v5 --target-db-spec postgresql://user@host:5432
+//! ```
+//!
+//! The backend must be stopped while this runs. If the migration is
+//! interrupted, run it again with the same flags to pick up where it left off.
+//! File storage isn't touched, so point the new backend at the same storage.
+
+use std::sync::Arc;
+
+use clap::Parser;
+use clusters::DbDriverTag;
+use cmd_util::env::config_service;
+use common::{
+    errors::MainError,
+    shutdown::ShutdownSignal,
+};
+use keybroker::DEV_INSTANCE_NAME;
+use local_backend::{
+    persistence::connect_persistence,
+    persistence_migration::PersistenceMigration,
+};
+use runtime::prod::ProdRuntime;
+
+#[derive(Parser)]
+struct Args {
+    /// Instance name of the deployment. Postgres and MySQL derive the database
+    /// name from it.
+    #[clap(long, default_value = DEV_INSTANCE_NAME)]
+    instance_name: String,
+
+    /// Database driver to migrate from.
+    #[clap(long, value_enum)]
+    source_db: DbDriverTag,
+
+    /// File path for SQLite or server URL for Postgres/MySQL to migrate from.
+    #[clap(long)]
+    source_db_spec: String,
+
+    /// Postgres schema of the source deployment, see `--postgres-schema` on
+    /// the backend.
+    #[clap(long)]
+    source_postgres_schema: Option<String>,
+
+    /// MySQL table prefix of the source deployment, see
+    /// `--mysql-table-prefix` on the backend.
+    #[clap(long, default_value = "")]
+    source_mysql_table_prefix: String,
+
+    /// Database driver to migrate to.
+    #[clap(long, value_enum)]
+    target_db: DbDriverTag,
+
+    /// File path for SQLite or server URL for Postgres/MySQL to migrate to.
+    #[clap(long)]
+    target_db_spec: String,
+
+    /// Postgres schema for the target deployment.
+    #[clap(long)]
+    target_postgres_schema: Option<String>,
+
+    /// MySQL table prefix for the target deployment.
+    #[clap(long, default_value = "")]
+    target_mysql_table_prefix: String,
+
+    /// Number of document revisions or index entries written per transaction.
+    #[clap(long, default_value_t = 1024)]
+    chunk_size: usize,
+
+    /// Don't require SSL when talking to either database.
+    #[clap(long)]
+    do_not_require_ssl: bool,
+}
+
+async fn run(runtime: ProdRuntime, args: Args) -> anyhow::Result<()> {
+    // Connecting takes the lease on both sides, so a backend that is still
+    // running against either database will lose it rather than keep writing.
+    let source = connect_persistence(
+        args.source_db,
+        &args.source_db_spec,
+        args.do_not_require_ssl,
+        args.source_postgres_schema,
+        None,
+        args.source_mysql_table_prefix,
+        &args.instance_name,
+        runtime.clone(),
+        ShutdownSignal::panic(),
+    )
+    .await?;
+    let target = connect_persistence(
+        args.target_db,
+        &args.target_db_spec,
+        args.do_not_require_ssl,
+        args.target_postgres_schema,
+        None,
+        args.target_mysql_table_prefix,
+        &args.instance_name,
+        runtime.clone(),
+        ShutdownSignal::panic(),
+    )
+    .await?;
+    let migration = PersistenceMigration::new(source.clone(), target.clone(), args.chunk_size)?;
+    let summary = migration.run(&runtime).await?;
+    tracing::info!(
+        "Migrated {} document revisions and {} index entries as of {}",
+        summary.documents_verified,
+        summary.index_entries_verified,
+        summary.snapshot_ts
+    );
+    source.shutdown().await?;
+    target.shutdown().await?;
+    Ok(())
+}
+
+fn main() -> Result<(), MainError> {
+    let _guard = config_service();
+    let args = Args::parse();
+    let tokio = ProdRuntime::init_tokio()?;
+    let runtime = ProdRuntime::new(&tokio);
+    let runtime_ = runtime.clone();
+    runtime.block_on("migrate_persistence", async move {
+        run(runtime_, args).await?;
+        Ok(())
+    })
+}
@@ -0,0 +1,79 @@
+//! Re-encrypts every object in the local storage directory with the current
+//! storage encryption key. Run it with the same flags as the backend after
+//! enabling `--storage-encryption` or bumping
+//! `--storage-encryption-key-version`; once it has finished, retired instance
+//! secrets are no longer needed.
+//!
+//! The backend must be stopped while this runs, since objects that are still
+//! being uploaded would otherwise be replaced mid-write.
+
+use std::{
+    fs,
+    path::Path,
+};
+
+use anyhow::Context;
+use clap::Parser;
+use local_backend::config::LocalConfig;
+use storage::{
+    reencrypt_local_object,
+    LocalObjectRotation,
+    StorageKeyring,
+    StorageUseCase,
+};
+
+#[derive(Default, Debug)]
+struct RotationSummary {
+    unchanged: usize,
+    rewrapped: usize,
+    encrypted: usize,
+}
+
+fn rotate_dir(
+    keyring: &StorageKeyring,
+    dir: &Path,
+    summary: &mut RotationSummary,
+) -> anyhow::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            rotate_dir(keyring, &path, summary)?;
+            continue;
+        }
+        if path.extension().is_none_or(|ext| ext != "blob") {
+            continue;
+        }
+        let rotation = reencrypt_local_object(keyring, &path)
+            .with_context(|| format!("Failed to rotate {}", path.display()))?;
+        match rotation {
+            LocalObjectRotation::Unchanged => summary.unchanged += 1,
+            LocalObjectRotation::Rewrapped => summary.rewrapped += 1,
+            LocalObjectRotation::Encrypted => summary.encrypted += 1,
+        }
+    }
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let config = LocalConfig::parse();
+    sodiumoxide::init().map_err(|()| anyhow::anyhow!("sodiumoxide initialization failed"))?;
+    let keyring = config
+        .storage_keyring()?
+        .context("--storage-encryption must be set to rotate storage keys")?;
+    for use_case in [
+        StorageUseCase::Files,
+        StorageUseCase::Modules,
+        StorageUseCase::Exports,
+        StorageUseCase::SnapshotImports,
+        StorageUseCase::SearchIndexes,
+    ] {
+        let dir = config.storage_dir().join(use_case.to_string());
+        if !dir.exists() {
+            continue;
+        }
+        let mut summary = RotationSummary::default();
+        rotate_dir(&keyring, &dir, &mut summary)?;
+        println!("{use_case}: {summary:?}");
+    }
+    Ok(())
+}
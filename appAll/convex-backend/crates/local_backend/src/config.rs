@@ -1,6 +1,8 @@
 use std::{
     fmt,
     path::PathBuf,
+    sync::Arc,
+    time::Duration,
 };
 
 use clap::Parser;
@@ -16,12 +18,15 @@ use keybroker::{
     DEV_SECRET,
 };
 use metrics::SERVER_VERSION_STR;
+use node_executor::NodeSandboxConfig;
+use storage::StorageKeyring;
 use url::Url;
 
 #[derive(Parser, Clone)]
 #[clap(version = &**SERVER_VERSION_STR, author = "Convex, Inc. <no-reply@convex.dev>")]
 pub struct LocalConfig {
-    /// File path for SQLite, the file path; for postgres, a server URL.
+    /// For SQLite, the file path; for RocksDB, the directory; for postgres, a
+    /// server URL.
     #[clap(default_value = "convex_local_backend.sqlite3")]
     pub db_spec: String,
 
@@ -97,6 +102,22 @@ pub struct LocalConfig {
     #[clap(long)]
     pub do_not_require_ssl: bool,
 
+    /// Postgres schema to store this deployment's tables in, so several
+    /// deployments can share one database. Created if it doesn't exist.
+    #[clap(long)]
+    pub postgres_schema: Option<String>,
+
+    /// Server URL of a Postgres read replica, in the same format as the
+    /// primary's. Snapshot reads the replica has caught up to are served from
+    /// it.
+    #[clap(long)]
+    pub postgres_replica_url: Option<String>,
+
+    /// Prefix for the names of this deployment's MySQL tables, so several
+    /// deployments can share one database.
+    #[clap(long, default_value = "")]
+    pub mysql_table_prefix: String,
+
     /// self-hosted Convex will periodically communicate with a remote beacon
     /// server. This is to help Convex understand and improve the product.
     /// If set, the self-host beacon will not be sent.
@@ -115,6 +136,88 @@ pub struct LocalConfig {
     /// reach the client for debugging purposes.
     #[clap(long, default_value = "false")]
     pub redact_logs_to_client: bool,
+
+    /// If set, user files, modules, exports, snapshot imports and search
+    /// indexes are encrypted at rest with keys derived from the instance
+    /// secret. "use node" actions decrypt their source packages into the
+    /// worker's private temp directory for the duration of a request. Files
+    /// stored before this was enabled stay readable, and can be encrypted with
+    /// the `rotate_storage_key` tool.
+    #[clap(long)]
+    pub storage_encryption: bool,
+
+    /// Version of the key used to encrypt new objects in storage. To rotate
+    /// keys, bump the version, restart the backend and then run
+    /// `rotate_storage_key` with the same flags.
+    #[clap(long, default_value = "0", requires = "storage_encryption")]
+    pub storage_encryption_key_version: u64,
+
+    /// Instance secrets used before the current one. Objects encrypted with
+    /// keys derived from them stay readable until they're rotated.
+    #[clap(long, requires = "storage_encryption")]
+    pub retired_instance_secret: Vec<String>,
+
+    /// Directories to look for Node.js binaries in when running "use node"
+    /// actions, in order of preference. Each can be an nvm-style versions
+    /// directory (containing e.g. `v20.11.1/bin/node`) or a directory
+    /// containing `node` or `node20` binaries. Defaults to
+    /// `~/.nvm/versions/node`. The `node` on the `PATH` is always tried last.
+    #[clap(long)]
+    pub node_search_path: Vec<PathBuf>,
+
+    /// Memory limit in MB for each Node.js worker running "use node"
+    /// actions. Exceeding it fails the action.
+    #[clap(long)]
+    pub node_memory_limit_mb: Option<u64>,
+
+    /// CPU time in seconds a single "use node" action may use. Requires
+    /// `--node-cgroup-root`.
+    #[clap(long, requires = "node_cgroup_root")]
+    pub node_cpu_time_limit_secs: Option<u64>,
+
+    /// Share of a CPU each Node.js worker may use, in thousandths of a core.
+    /// Requires `--node-cgroup-root`.
+    #[clap(long, requires = "node_cgroup_root")]
+    pub node_cpu_quota_millicores: Option<u64>,
+
+    /// Maximum number of processes and threads in each Node.js worker.
+    /// Requires `--node-cgroup-root`.
+    #[clap(long, requires = "node_cgroup_root")]
+    pub node_max_processes: Option<u64>,
+
+    /// Largest file in MB a "use node" action may write.
+    #[clap(long)]
+    pub node_max_file_size_mb: Option<u64>,
+
+    /// A cgroup v2 directory the backend may create child cgroups in, used to
+    /// enforce memory, CPU and process limits on Node.js workers.
+    #[clap(long)]
+    pub node_cgroup_root: Option<PathBuf>,
+
+    /// If set, Node.js workers see a read-only filesystem, except for a private
+    /// temp directory. Requires `bwrap` on the `PATH`.
+    #[clap(long)]
+    pub node_read_only_filesystem: bool,
+
+    /// Comma-separated hosts "use node" actions may connect to, in addition to
+    /// the backend itself. Pass the flag with no hosts to block all outbound
+    /// connections. By default all hosts are allowed. Workers get their own
+    /// network namespace, so this requires `bwrap` on the `PATH`. Include
+    /// `registry.npmjs.org` to install external packages.
+    #[clap(long, num_args = 0.., value_delimiter = ',')]
+    pub node_allowed_hosts: Option<Vec<String>>,
+
+    /// Base URL of an OpenTelemetry collector to export traces to over
+    /// OTLP/HTTP, e.g. http://localhost:4318. Traces are posted to
+    /// `/v1/traces` under it.
+    #[clap(long)]
+    pub otlp_endpoint: Option<Url>,
+
+    /// Fraction of requests, function calls and background jobs to trace when
+    /// `--otlp-endpoint` is set. Requests with a sampled `traceparent` header
+    /// are always traced.
+    #[clap(long, default_value = "1.0", requires = "otlp_endpoint")]
+    pub otlp_trace_sample_ratio: f64,
 }
 
 impl fmt::Debug for LocalConfig {
@@ -193,6 +296,35 @@ impl LocalConfig {
         )
     }
 
+    pub fn storage_keyring(&self) -> anyhow::Result<Option<Arc<StorageKeyring>>> {
+        if !self.storage_encryption {
+            return Ok(None);
+        }
+        let retired_secrets = self
+            .retired_instance_secret
+            .iter()
+            .map(|secret| Ok(*InstanceSecret::try_from(secret.as_str())?.as_bytes()))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(Some(Arc::new(StorageKeyring::derive(
+            self.secret()?.as_bytes(),
+            self.storage_encryption_key_version,
+            &retired_secrets,
+        )?)))
+    }
+
+    pub fn node_sandbox_config(&self) -> NodeSandboxConfig {
+        NodeSandboxConfig {
+            memory_limit_mb: self.node_memory_limit_mb,
+            cpu_time_limit: self.node_cpu_time_limit_secs.map(Duration::from_secs),
+            cpu_quota_millicores: self.node_cpu_quota_millicores,
+            max_processes: self.node_max_processes,
+            max_file_size_mb: self.node_max_file_size_mb,
+            cgroup_root: self.node_cgroup_root.clone(),
+            read_only_filesystem: self.node_read_only_filesystem,
+            allowed_hosts: self.node_allowed_hosts.clone(),
+        }
+    }
+
     pub fn storage_dir(&self) -> PathBuf {
         self.local_storage.clone().into()
     }
@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use anyhow::Context;
 use application::{
     deploy_config::ModuleJson,
@@ -21,6 +23,7 @@ use common::{
     },
     shapes::{
         dashboard_shape_json,
+        inferred_database_schema,
         reduced::ReducedShape,
     },
     types::FunctionCaller,
@@ -37,7 +40,9 @@ use serde::{
     Deserialize,
     Serialize,
 };
+use serde_json::Value as JsonValue;
 use value::{
+    NamespacedTableMapping,
     TableName,
     TableNamespace,
 };
@@ -76,19 +81,14 @@ pub struct ShapesArgs {
     component: Option<String>,
 }
 
-#[debug_handler]
-pub async fn shapes2(
-    State(st): State<LocalAppState>,
-    ExtractIdentity(identity): ExtractIdentity,
-    Query(ShapesArgs { component }): Query<ShapesArgs>,
-) -> Result<impl IntoResponse, HttpResponseError> {
-    let mut out = serde_json::Map::new();
-
-    must_be_admin_member(&identity)?;
-    let component = ComponentId::deserialize_from_string(component.as_deref())?;
+/// The inferred shape of every user table in `component`.
+fn user_table_shapes(
+    st: &LocalAppState,
+    component: ComponentId,
+) -> anyhow::Result<(BTreeMap<TableName, ReducedShape>, NamespacedTableMapping)> {
     let snapshot = st.application.latest_snapshot()?;
     let mapping = snapshot.table_mapping().namespace(component.into());
-
+    let mut shapes = BTreeMap::new();
     for (namespace, table_name) in snapshot.table_registry.user_table_names() {
         if TableNamespace::from(component) != namespace {
             continue;
@@ -103,12 +103,56 @@ pub async fn shapes2(
             // Table summaries are still bootstrapping, use `Unknown` in the meantime
             None => ReducedShape::Unknown,
         };
+        shapes.insert(table_name.clone(), shape);
+    }
+    Ok((shapes, mapping))
+}
+
+#[debug_handler]
+pub async fn shapes2(
+    State(st): State<LocalAppState>,
+    ExtractIdentity(identity): ExtractIdentity,
+    Query(ShapesArgs { component }): Query<ShapesArgs>,
+) -> Result<impl IntoResponse, HttpResponseError> {
+    let mut out = serde_json::Map::new();
+
+    must_be_admin_member(&identity)?;
+    let component = ComponentId::deserialize_from_string(component.as_deref())?;
+    let (shapes, mapping) = user_table_shapes(&st, component)?;
+    for (table_name, shape) in shapes {
         let json = dashboard_shape_json(&shape, &mapping, virtual_system_mapping())?;
-        out.insert(String::from(table_name.clone()), json);
+        out.insert(String::from(table_name), json);
     }
     Ok(Json(out))
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct InferSchemaResponse {
+    /// The proposed schema, in the JSON format that schema pushes use.
+    schema: JsonValue,
+    /// The proposed schema as the source of `convex/schema.ts`.
+    schema_ts: String,
+}
+
+/// Propose a schema that matches the documents currently in the component's
+/// tables, as a starting point for adding a schema to a schemaless deployment.
+#[debug_handler]
+pub async fn infer_schema(
+    State(st): State<LocalAppState>,
+    ExtractIdentity(identity): ExtractIdentity,
+    Query(ShapesArgs { component }): Query<ShapesArgs>,
+) -> Result<impl IntoResponse, HttpResponseError> {
+    must_be_admin_member(&identity)?;
+    let component = ComponentId::deserialize_from_string(component.as_deref())?;
+    let (shapes, mapping) = user_table_shapes(&st, component)?;
+    let schema = inferred_database_schema(shapes, &mapping, virtual_system_mapping());
+    Ok(Json(InferSchemaResponse {
+        schema_ts: schema.to_schema_ts(),
+        schema: JsonValue::try_from(schema)?,
+    }))
+}
+
 #[debug_handler]
 pub async fn delete_tables(
     State(st): State<LocalAppState>,
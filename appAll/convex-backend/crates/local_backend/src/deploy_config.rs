@@ -314,13 +314,18 @@ pub async fn push_config_handler(
         ErrorMetadata::bad_request("InvalidVersion", "The function version is invalid"),
     )?;
 
+    let node_version = config.config.node_version.unwrap_or_default();
     let begin_build_external_deps = Instant::now();
     // Upload external node dependencies separately
     let external_deps_id_and_pkg = if let Some(deps) = config.node_dependencies
         && !deps.is_empty()
     {
         let deps: Vec<_> = deps.into_iter().map(NodeDependency::from).collect();
-        Some(application.build_external_node_deps(deps).await?)
+        Some(
+            application
+                .build_external_node_deps(deps, node_version)
+                .await?,
+        )
     } else {
         None
     };
@@ -331,7 +336,7 @@ pub async fn push_config_handler(
         .unwrap_or(PackageSize::default());
 
     let source_package = application
-        .upload_package(&modules, external_deps_id_and_pkg)
+        .upload_package(&modules, external_deps_id_and_pkg, node_version)
         .await?;
     let end_upload_source_package = Instant::now();
     // Verify that we have not exceeded the max zipped or unzipped file size
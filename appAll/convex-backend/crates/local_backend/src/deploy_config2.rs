@@ -48,6 +48,7 @@ use model::{
     },
     external_packages::types::ExternalDepsPackageId,
     modules::module_versions::SerializedAnalyzedModule,
+    push_history::PushHistoryModel,
     source_packages::types::SourcePackage,
 };
 use serde::{
@@ -63,9 +64,12 @@ use value::{
 
 use crate::{
     admin::{
+        must_be_admin,
         must_be_admin_from_key,
         must_be_admin_from_key_with_write_access,
+        must_be_admin_with_write_access,
     },
+    authentication::ExtractIdentity,
     LocalAppState,
 };
 
@@ -288,6 +292,86 @@ pub async fn finish_push(
     Ok(Json(SerializedFinishPushDiff::try_from(resp)?))
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PushHistoryItem {
+    id: String,
+    creation_time: Option<f64>,
+    /// The earlier push this one rolled back to, if it was a rollback.
+    rollback_of: Option<String>,
+    component_definitions: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ListPushHistoryResponse {
+    pushes: Vec<PushHistoryItem>,
+}
+
+/// List the pushes that can be rolled back to, newest first.
+#[debug_handler]
+pub async fn list_push_history(
+    State(st): State<LocalAppState>,
+    ExtractIdentity(identity): ExtractIdentity,
+) -> Result<impl IntoResponse, HttpResponseError> {
+    must_be_admin(&identity)?;
+    let mut tx = st.application.begin(identity).await?;
+    let pushes = PushHistoryModel::new(&mut tx)
+        .list()
+        .await?
+        .into_iter()
+        .map(|entry| PushHistoryItem {
+            id: entry.developer_id().to_string(),
+            creation_time: entry.creation_time().map(f64::from),
+            rollback_of: entry.rollback_of.map(|id| id.to_string()),
+            component_definitions: entry
+                .definitions
+                .keys()
+                .cloned()
+                .map(String::from)
+                .collect(),
+        })
+        .collect();
+    Ok(Json(ListPushHistoryResponse { pushes }))
+}
+
+const DEFAULT_ROLLBACK_SCHEMA_TIMEOUT_MS: u32 = 60_000;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RollbackPushRequest {
+    push_id: String,
+    schema_timeout_ms: Option<u32>,
+}
+
+/// Re-apply a push from the push history.
+#[debug_handler]
+pub async fn rollback_push(
+    State(st): State<LocalAppState>,
+    ExtractIdentity(identity): ExtractIdentity,
+    Json(req): Json<RollbackPushRequest>,
+) -> Result<impl IntoResponse, HttpResponseError> {
+    must_be_admin_with_write_access(&identity)?;
+    let push_id: DeveloperDocumentId = req.push_id.parse().map_err(|_| {
+        anyhow::Error::new(ErrorMetadata::bad_request(
+            "InvalidPushId",
+            format!("Invalid push ID: {}", req.push_id),
+        ))
+    })?;
+    let schema_timeout = Duration::from_millis(
+        req.schema_timeout_ms
+            .unwrap_or(DEFAULT_ROLLBACK_SCHEMA_TIMEOUT_MS) as u64,
+    );
+    let resp = st
+        .application
+        .rollback_push(identity, push_id, schema_timeout)
+        .await
+        .map_err(|e| {
+            e.wrap_error_message(|msg| format!("Hit an error while rolling back:\n{msg}"))
+        })?;
+    Ok(Json(SerializedFinishPushDiff::try_from(resp)?))
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ReportPushCompletedRequest {
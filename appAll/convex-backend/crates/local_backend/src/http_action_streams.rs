@@ -0,0 +1,622 @@
+//! Long-lived responses an HTTP action can hand off to the backend: a stream
+//! of Server-Sent Events carrying a query's result, or a WebSocket connection.
+//!
+//! The action returns a response marked with [`CONVEX_STREAM_HEADER`] whose
+//! body describes the stream (see `querySubscriptionResponse` and
+//! `webSocketResponse` in `convex/server`). The action finishes as usual and
+//! the backend serves the stream, so the connection can outlive the action's
+//! execution time limit. It can't outlive the request's auth token though:
+//! the stream is closed when the token expires, and the client has to
+//! reconnect with a fresh one.
+
+use std::{
+    future,
+    sync::Arc,
+    time::SystemTime,
+};
+
+use anyhow::Context;
+use application::{
+    api::{
+        ApplicationApi,
+        ExecuteQueryTimestamp,
+    },
+    redaction::RedactedJsError,
+};
+use axum::{
+    extract::ws::{
+        close_code,
+        CloseFrame,
+        Message,
+        WebSocket,
+        WebSocketUpgrade,
+    },
+    response::{
+        sse::{
+            Event,
+            KeepAlive,
+            Sse,
+        },
+        IntoResponse,
+        Response,
+    },
+};
+use common::{
+    components::{
+        CanonicalizedComponentFunctionPath,
+        ComponentPath,
+        ExportPath,
+    },
+    errors::report_error,
+    http::ResolvedHostname,
+    types::FunctionCaller,
+    ws::is_connection_closed_error,
+    RequestId,
+};
+use errors::ErrorMetadata;
+use futures::{
+    future::BoxFuture,
+    select_biased,
+    stream::{
+        self,
+        BoxStream,
+    },
+    FutureExt,
+    SinkExt,
+    StreamExt,
+};
+use futures_async_stream::try_stream;
+use http::{
+    header::{
+        CONTENT_LENGTH,
+        CONTENT_TYPE,
+    },
+    HeaderMap,
+};
+use keybroker::Identity;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use serde_json::{
+    json,
+    Value as JsonValue,
+};
+use udf::HttpActionResponsePart;
+use value::export::ValueFormat;
+
+use crate::parse::{
+    parse_export_path,
+    parse_udf_path,
+};
+
+/// Response header an HTTP action sets to hand its response off to the
+/// backend as a stream.
+pub const CONVEX_STREAM_HEADER: &str = "convex-stream";
+
+/// Stream descriptions are small JSON objects, so refuse anything larger.
+const MAX_STREAM_SPEC_SIZE: usize = 1 << 16;
+
+const TOKEN_EXPIRED_MESSAGE: &str = "Convex token identity expired";
+
+#[derive(Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+enum HttpStreamSpec {
+    #[serde(rename_all = "camelCase")]
+    QuerySubscription {
+        query: QuerySpec,
+        format: Option<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    WebSocket {
+        on_message: Option<String>,
+        subscribe: Option<QuerySpec>,
+        format: Option<String>,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct QuerySpec {
+    path: String,
+    args: JsonValue,
+}
+
+/// The result of one run of a subscribed query.
+enum QueryUpdate {
+    Value(JsonValue),
+    Error {
+        message: String,
+        data: Option<JsonValue>,
+    },
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum WebSocketServerMessage {
+    Update {
+        value: JsonValue,
+    },
+    QueryError {
+        message: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        data: Option<JsonValue>,
+    },
+    Result {
+        value: JsonValue,
+    },
+    Error {
+        message: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        data: Option<JsonValue>,
+    },
+}
+
+/// Serve the stream described by the rest of an HTTP action's response.
+/// `headers` are the action's response headers, which are passed through
+/// apart from the ones describing the body.
+pub async fn stream_response(
+    api: Arc<dyn ApplicationApi>,
+    host: ResolvedHostname,
+    identity: Identity,
+    mut headers: HeaderMap,
+    mut body: BoxStream<'static, anyhow::Result<HttpActionResponsePart>>,
+    ws: Option<WebSocketUpgrade>,
+) -> anyhow::Result<Response> {
+    let mut spec = vec![];
+    while let Some(part) = body.next().await {
+        let HttpActionResponsePart::BodyChunk(bytes) = part? else {
+            anyhow::bail!("Unexpected element in HTTP response stream");
+        };
+        spec.extend_from_slice(&bytes);
+        anyhow::ensure!(
+            spec.len() <= MAX_STREAM_SPEC_SIZE,
+            ErrorMetadata::bad_request(
+                "InvalidStreamResponse",
+                "The stream description returned by the HTTP action is too large",
+            )
+        );
+    }
+    let spec: HttpStreamSpec =
+        serde_json::from_slice(&spec).context(ErrorMetadata::bad_request(
+            "InvalidStreamResponse",
+            "The HTTP action returned an invalid stream description",
+        ))?;
+    headers.remove(CONVEX_STREAM_HEADER);
+    headers.remove(CONTENT_TYPE);
+    headers.remove(CONTENT_LENGTH);
+
+    match spec {
+        HttpStreamSpec::QuerySubscription { query, format } => {
+            let path = parse_export_path(&query.path)?;
+            let format = parse_format(format)?;
+            let expired = identity_expired(&identity);
+            let events = query_updates(api, host, identity, path, query.args, format)
+                .map(|update| {
+                    let event = match update? {
+                        QueryUpdate::Value(value) => {
+                            Event::default().event("update").data(value.to_string())
+                        },
+                        QueryUpdate::Error { message, data } => Event::default()
+                            .event("queryError")
+                            .data(json!({ "message": message, "data": data }).to_string()),
+                    };
+                    anyhow::Ok(event)
+                })
+                .take_until(expired)
+                .chain(stream::once(async {
+                    Ok(Event::default()
+                        .event("tokenExpired")
+                        .data(json!({ "message": TOKEN_EXPIRED_MESSAGE }).to_string()))
+                }));
+            let sse = Sse::new(events).keep_alive(KeepAlive::default());
+            Ok((headers, sse).into_response())
+        },
+        HttpStreamSpec::WebSocket {
+            on_message,
+            subscribe,
+            format,
+        } => {
+            let Some(ws) = ws else {
+                anyhow::bail!(ErrorMetadata::bad_request(
+                    "WebSocketUpgradeRequired",
+                    "This route only accepts WebSocket connections",
+                ));
+            };
+            let on_message = on_message
+                .map(|path| {
+                    anyhow::Ok(CanonicalizedComponentFunctionPath {
+                        component: ComponentPath::root(),
+                        udf_path: parse_udf_path(&path)?,
+                    })
+                })
+                .transpose()?;
+            let subscribe = subscribe
+                .map(|query| anyhow::Ok((parse_export_path(&query.path)?, query.args)))
+                .transpose()?;
+            let format = parse_format(format)?;
+            let response = ws.on_upgrade(move |socket| async move {
+                if let Err(mut e) =
+                    run_web_socket(socket, api, host, identity, on_message, subscribe, format).await
+                {
+                    report_error(&mut e).await;
+                }
+            });
+            Ok((headers, response).into_response())
+        },
+    }
+}
+
+/// Resolves when `identity`'s auth token expires, or never for identities
+/// that don't expire.
+fn identity_expired(identity: &Identity) -> BoxFuture<'static, ()> {
+    let Identity::User(user) = identity else {
+        return future::pending().boxed();
+    };
+    match user.expiration.duration_since(SystemTime::now()) {
+        Ok(remaining) => tokio::time::sleep(remaining).boxed(),
+        Err(_) => future::ready(()).boxed(),
+    }
+}
+
+fn parse_format(format: Option<String>) -> anyhow::Result<ValueFormat> {
+    Ok(format
+        .map(|f| f.parse())
+        .transpose()?
+        .unwrap_or(ValueFormat::ConvexCleanJSON))
+}
+
+/// Run `query` and run it again each time its result may have changed.
+#[try_stream(ok = QueryUpdate, error = anyhow::Error, boxed)]
+async fn query_updates(
+    api: Arc<dyn ApplicationApi>,
+    host: ResolvedHostname,
+    identity: Identity,
+    path: ExportPath,
+    args: JsonValue,
+    format: ValueFormat,
+) {
+    let args = vec![args];
+    let subscription_client = api.subscription_client(&host).await?;
+    let mut journal = None;
+    loop {
+        let query_return = api
+            .execute_public_query(
+                &host,
+                RequestId::new(),
+                identity.clone(),
+                path.clone(),
+                args.clone(),
+                FunctionCaller::HttpEndpoint,
+                ExecuteQueryTimestamp::Latest,
+                journal,
+            )
+            .await?;
+        yield match query_return.result {
+            Ok(value) => QueryUpdate::Value(value.export(format)),
+            Err(error) => {
+                let (message, data) = export_error(error, format);
+                QueryUpdate::Error { message, data }
+            },
+        };
+        journal = Some(query_return.journal);
+        let subscription = subscription_client.subscribe(query_return.token).await?;
+        subscription.wait_for_invalidation().await?;
+    }
+}
+
+fn export_error(error: RedactedJsError, format: ValueFormat) -> (String, Option<JsonValue>) {
+    let message = format!("{error}");
+    let data = error.custom_data_if_any().map(|data| data.export(format));
+    (message, data)
+}
+
+/// Pass each message from the client to `on_message` and send back what it
+/// returns, while sending the results of `subscribe` as they change.
+async fn run_web_socket(
+    socket: WebSocket,
+    api: Arc<dyn ApplicationApi>,
+    host: ResolvedHostname,
+    identity: Identity,
+    on_message: Option<CanonicalizedComponentFunctionPath>,
+    subscribe: Option<(ExportPath, JsonValue)>,
+    format: ValueFormat,
+) -> anyhow::Result<()> {
+    let (mut sender, receiver) = socket.split();
+    let mut receiver = receiver.fuse();
+    let mut expired = identity_expired(&identity).fuse();
+    let mut updates = match subscribe {
+        Some((path, args)) => query_updates(
+            api.clone(),
+            host.clone(),
+            identity.clone(),
+            path,
+            args,
+            format,
+        ),
+        None => stream::pending().boxed(),
+    }
+    .fuse();
+    loop {
+        let reply = select_biased! {
+            () = expired => {
+                let close = Message::Close(Some(CloseFrame {
+                    code: close_code::POLICY,
+                    reason: TOKEN_EXPIRED_MESSAGE.into(),
+                }));
+                if let Err(e) = sender.send(close).await
+                    && !is_connection_closed_error(&e)
+                {
+                    return Err(e.into());
+                }
+                break;
+            },
+            message = receiver.next() => {
+                let message = match message {
+                    None | Some(Ok(Message::Close(_))) => break,
+                    Some(Ok(Message::Text(text))) => JsonValue::String(text),
+                    Some(Ok(Message::Binary(bytes))) => {
+                        json!({ "$bytes": base64::encode(bytes) })
+                    },
+                    // Pings are answered by axum.
+                    Some(Ok(Message::Ping(_) | Message::Pong(_))) => continue,
+                    Some(Err(e)) if is_connection_closed_error(&e) => break,
+                    Some(Err(e)) => return Err(e.into()),
+                };
+                let Some(ref path) = on_message else {
+                    continue;
+                };
+                let result = api
+                    .execute_any_function(
+                        &host,
+                        RequestId::new(),
+                        identity.clone(),
+                        path.clone(),
+                        vec![json!({ "message": message })],
+                        FunctionCaller::HttpEndpoint,
+                    )
+                    .await?;
+                match result {
+                    Ok(function_return) => WebSocketServerMessage::Result {
+                        value: function_return.value.export(format),
+                    },
+                    Err(function_error) => {
+                        let (message, data) = export_error(function_error.error, format);
+                        WebSocketServerMessage::Error { message, data }
+                    },
+                }
+            },
+            update = updates.next() => {
+                let Some(update) = update else {
+                    break;
+                };
+                match update? {
+                    QueryUpdate::Value(value) => WebSocketServerMessage::Update { value },
+                    QueryUpdate::Error { message, data } => {
+                        WebSocketServerMessage::QueryError { message, data }
+                    },
+                }
+            },
+        };
+        if let Err(e) = sender
+            .send(Message::Text(serde_json::to_string(&reply)?))
+            .await
+        {
+            if is_connection_closed_error(&e) {
+                break;
+            }
+            return Err(e.into());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::Arc,
+        time::{
+            Duration,
+            SystemTime,
+        },
+    };
+
+    use application::test_helpers::ApplicationTestExt;
+    use axum::body::{
+        Body,
+        BodyDataStream,
+    };
+    use common::http::{
+        RequestDestination,
+        ResolvedHostname,
+    };
+    use futures::{
+        stream,
+        SinkExt,
+        Stream,
+        StreamExt,
+    };
+    use http::{
+        HeaderMap,
+        Request,
+        StatusCode,
+    };
+    use keybroker::{
+        testing::TestUserIdentity,
+        Identity,
+        UserIdentity,
+    };
+    use runtime::prod::ProdRuntime;
+    use serde_json::{
+        json,
+        Value as JsonValue,
+    };
+    use tokio_tungstenite::{
+        connect_async,
+        tungstenite::{
+            self,
+            Message,
+        },
+    };
+    use udf::HttpActionResponsePart;
+
+    use super::{
+        stream_response,
+        TOKEN_EXPIRED_MESSAGE,
+    };
+    use crate::test_helpers::setup_backend_for_test;
+
+    /// Reads Server-Sent Events off a response body.
+    struct SseEvents {
+        body: BodyDataStream,
+        buffer: String,
+    }
+
+    impl SseEvents {
+        fn new(body: Body) -> Self {
+            Self {
+                body: body.into_data_stream(),
+                buffer: String::new(),
+            }
+        }
+
+        /// The next event's name and JSON data, or `None` if the stream ended.
+        async fn next(&mut self) -> anyhow::Result<Option<(String, JsonValue)>> {
+            loop {
+                if let Some((event, rest)) = self.buffer.split_once("\n\n") {
+                    let (event, rest) = (event.to_string(), rest.to_string());
+                    self.buffer = rest;
+                    let mut name = None;
+                    let mut data = String::new();
+                    for line in event.lines() {
+                        if let Some(value) = line.strip_prefix("event:") {
+                            name = Some(value.trim_start().to_string());
+                        } else if let Some(value) = line.strip_prefix("data:") {
+                            data.push_str(value.trim_start());
+                        }
+                    }
+                    // Skip keep-alive comments.
+                    let Some(name) = name else {
+                        continue;
+                    };
+                    return Ok(Some((name, serde_json::from_str(&data)?)));
+                }
+                let Some(chunk) = self.body.next().await else {
+                    return Ok(None);
+                };
+                self.buffer.push_str(std::str::from_utf8(&chunk?)?);
+            }
+        }
+    }
+
+    async fn next_ws_message(
+        websocket: &mut (impl Stream<Item = Result<Message, tungstenite::Error>> + Unpin),
+    ) -> anyhow::Result<JsonValue> {
+        let message = websocket
+            .next()
+            .await
+            .ok_or_else(|| anyhow::anyhow!("WebSocket closed"))??;
+        Ok(serde_json::from_str(message.to_text()?)?)
+    }
+
+    #[convex_macro::prod_rt_test]
+    async fn test_query_subscription_updates(rt: ProdRuntime) -> anyhow::Result<()> {
+        let backend = setup_backend_for_test(rt).await?;
+        backend.st.application.load_udf_tests_modules().await?;
+        let req = Request::builder()
+            .uri("/http/stream_count")
+            .method("GET")
+            .header("Host", "localhost")
+            .body(Body::empty())?;
+        let response = backend.send(req).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        let mut events = SseEvents::new(response.into_body());
+        assert_eq!(
+            events.next().await?,
+            Some(("update".to_string(), json!(0.0)))
+        );
+
+        let req = Request::builder()
+            .uri("/api/mutation")
+            .method("POST")
+            .header("Content-Type", "application/json")
+            .header("Host", "localhost")
+            .body(Body::from(serde_json::to_vec(&json!({
+                "path": "basic:insertObject",
+                "args": { "foo": "bar" },
+            }))?))?;
+        let _: JsonValue = backend.expect_success(req).await?;
+        assert_eq!(
+            events.next().await?,
+            Some(("update".to_string(), json!(1.0)))
+        );
+        Ok(())
+    }
+
+    #[convex_macro::prod_rt_test]
+    async fn test_query_subscription_token_expired(rt: ProdRuntime) -> anyhow::Result<()> {
+        let backend = setup_backend_for_test(rt).await?;
+        backend.st.application.load_udf_tests_modules().await?;
+        let mut user = UserIdentity::test();
+        user.expiration = SystemTime::now() - Duration::from_secs(1);
+        let spec = json!({
+            "kind": "querySubscription",
+            "query": { "path": "basic:count", "args": {} },
+        });
+        let body = stream::once(async move {
+            anyhow::Ok(HttpActionResponsePart::BodyChunk(
+                serde_json::to_vec(&spec)?.into(),
+            ))
+        })
+        .boxed();
+        let response = stream_response(
+            Arc::new(backend.st.application.clone()),
+            ResolvedHostname {
+                instance_name: "carnitas".to_string(),
+                destination: RequestDestination::ConvexCloud,
+            },
+            Identity::user(user),
+            HeaderMap::new(),
+            body,
+            None,
+        )
+        .await?;
+        let mut events = SseEvents::new(response.into_body());
+        assert_eq!(
+            events.next().await?,
+            Some((
+                "tokenExpired".to_string(),
+                json!({ "message": TOKEN_EXPIRED_MESSAGE })
+            ))
+        );
+        assert_eq!(events.next().await?, None);
+        Ok(())
+    }
+
+    #[convex_macro::prod_rt_test]
+    async fn test_web_socket(rt: ProdRuntime) -> anyhow::Result<()> {
+        let backend = setup_backend_for_test(rt).await?;
+        backend.st.application.load_udf_tests_modules().await?;
+        let (addr, _shutdown) = backend.serve().await?;
+        let (mut websocket, _) =
+            connect_async(format!("ws://{addr}/http/web_socket_count")).await?;
+        assert_eq!(
+            next_ws_message(&mut websocket).await?,
+            json!({ "type": "update", "value": 0.0 })
+        );
+
+        // Each message runs the `onMessage` mutation, whose write updates the
+        // subscribed query.
+        websocket.send(Message::Text("hi".into())).await?;
+        assert_eq!(
+            next_ws_message(&mut websocket).await?,
+            json!({ "type": "result", "value": 1.0 })
+        );
+        assert_eq!(
+            next_ws_message(&mut websocket).await?,
+            json!({ "type": "update", "value": 1.0 })
+        );
+        websocket.close(None).await?;
+        Ok(())
+    }
+}
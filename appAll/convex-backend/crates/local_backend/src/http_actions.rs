@@ -10,6 +10,7 @@ use axum::{
     },
     debug_handler,
     extract::{
+        ws::WebSocketUpgrade,
         FromRequest,
         Host,
         State,
@@ -26,6 +27,7 @@ use axum::{
 };
 use common::{
     http::{
+        client_address,
         ExtractRequestId,
         ExtractResolvedHostname,
         HttpResponseError,
@@ -64,6 +66,10 @@ use url::Url;
 
 use crate::{
     authentication::TryExtractIdentity,
+    http_action_streams::{
+        self,
+        CONVEX_STREAM_HEADER,
+    },
     RouterState,
 };
 
@@ -123,6 +129,7 @@ impl FromRequest<RouterState, axum::body::Body> for ExtractHttpRequestMetadata {
             .unwrap_or_else(|| axum::Extension(OriginalHttpUri(req.uri().clone())));
         let headers = req.headers().clone();
         let method = req.method().clone();
+        let client_address = client_address(req.headers(), req.extensions());
 
         // Construct the URL we provide in the HTTP request object.
         let url = Url::parse(&format!("{scheme}://{host}{uri}")).context("Invalid URL")?;
@@ -135,6 +142,7 @@ impl FromRequest<RouterState, axum::body::Body> for ExtractHttpRequestMetadata {
                     method,
                 },
                 body: None,
+                client_address,
             }));
         }
 
@@ -147,6 +155,7 @@ impl FromRequest<RouterState, axum::body::Body> for ExtractHttpRequestMetadata {
                 method,
             },
             body: Some(Box::pin(body.into_data_stream().map_err(|e| e.into()))),
+            client_address,
         }))
     }
 }
@@ -158,19 +167,37 @@ pub async fn http_any_method(
     TryExtractIdentity(identity_result): TryExtractIdentity,
     ExtractRequestId(request_id): ExtractRequestId,
     ExtractResolvedHostname(host): ExtractResolvedHostname,
+    ws: Option<WebSocketUpgrade>,
     ExtractHttpRequestMetadata(http_request_metadata): ExtractHttpRequestMetadata,
-) -> Result<impl IntoResponse, HttpResponseError> {
+) -> Result<Response, HttpResponseError> {
+    // The `Authorization` header for the request may contain a token corresponding
+    // to Convex auth, or it could be something separate managed by the developer.
+    // Try to extract the identity based on the Convex auth, but allow the request
+    // to go through if the header does not seem to specify Convex auth.
+    let identity = identity_result.unwrap_or(Identity::Unknown);
     let mut http_response_stream = stream_http_response(
-        host,
+        host.clone(),
         request_id,
         http_request_metadata,
-        identity_result,
+        identity.clone(),
         st.api.clone(),
     );
     let head = http_response_stream.try_next().await?;
     let Some(HttpActionResponsePart::Head(response_head)) = head else {
         return Err(anyhow::anyhow!("Did not receive HTTP response head first").into());
     };
+    if response_head.headers.contains_key(CONVEX_STREAM_HEADER) {
+        let response = http_action_streams::stream_response(
+            st.api.clone(),
+            host,
+            identity,
+            response_head.headers,
+            http_response_stream,
+            ws,
+        )
+        .await?;
+        return Ok(response);
+    }
     let body = http_response_stream.map(|p| match p {
         Ok(HttpActionResponsePart::BodyChunk(bytes)) => Ok(bytes),
         Err(e) => Err(e),
@@ -183,7 +210,8 @@ pub async fn http_any_method(
         status: response_head.status,
         headers: response_head.headers,
         body: Box::pin(body),
-    })
+    }
+    .into_response())
 }
 
 #[try_stream(ok=HttpActionResponsePart, error=anyhow::Error, boxed)]
@@ -191,14 +219,9 @@ async fn stream_http_response(
     host: ResolvedHostname,
     request_id: RequestId,
     http_request_metadata: HttpActionRequest,
-    identity_result: anyhow::Result<Identity>,
+    identity: Identity,
     application: Arc<dyn ApplicationApi>,
 ) {
-    // The `Authorization` header for the request may contain a token corresponding
-    // to Convex auth, or it could be something separate managed by the developer.
-    // Try to extract the identity based on the Convex auth, but allow the request
-    // to go through if the header does not seem to specify Convex auth.
-    let identity = identity_result.unwrap_or(Identity::Unknown);
     let (http_response_sender, http_response_receiver) = mpsc::unbounded_channel();
 
     tokio::pin! {
@@ -14,16 +14,20 @@ use ::authentication::{
     application_auth::ApplicationAuth,
 };
 use ::storage::{
+    EncryptedStorage,
     LocalDirStorage,
+    Storage,
     StorageUseCase,
 };
 use application::{
     api::ApplicationApi,
+    application_function_runner::FunctionQuotaLimits,
     log_visibility::RedactLogsToClient,
     Application,
     QueryCache,
 };
 use common::{
+    fastrace_helpers::set_sampling_config,
     http::{
         fetch::ProxiedFetchClient,
         RouteMapper,
@@ -43,7 +47,6 @@ use common::{
 };
 use config::LocalConfig;
 use database::Database;
-use events::usage::NoOpUsageEventLogger;
 use file_storage::{
     FileStorage,
     TransactionalFileStorage,
@@ -61,6 +64,7 @@ use node_executor::{
     local::LocalNodeExecutor,
     Actions,
 };
+use otlp::OtlpExporter;
 use runtime::prod::ProdRuntime;
 use search::{
     searcher::InProcessSearcher,
@@ -68,6 +72,7 @@ use search::{
     SegmentTermMetadataFetcher,
 };
 use serde::Serialize;
+use usage::LocalUsageLog;
 
 pub mod admin;
 mod app_metrics;
@@ -81,11 +86,14 @@ pub mod dashboard;
 pub mod deploy_config;
 pub mod deploy_config2;
 pub mod environment_variables;
+mod http_action_streams;
 pub mod http_actions;
 pub mod logs;
 pub mod node_action_callbacks;
+pub mod otlp;
 pub mod parse;
 pub mod persistence;
+pub mod persistence_migration;
 pub mod proxy;
 pub mod public_api;
 pub mod router;
@@ -98,6 +106,7 @@ pub mod streaming_import;
 pub mod subs;
 #[cfg(test)]
 mod test_helpers;
+pub mod usage;
 
 pub const MAX_CONCURRENT_REQUESTS: usize = 128;
 
@@ -110,12 +119,15 @@ pub struct LocalAppState {
     // Name of the instance. (e.g. crazy-giraffe-123)
     pub instance_name: String,
     pub application: Application<ProdRuntime>,
+    pub usage_log: LocalUsageLog<ProdRuntime>,
     pub zombify_rx: async_broadcast::Receiver<()>,
 }
 
 impl LocalAppState {
     pub async fn shutdown(self) -> anyhow::Result<()> {
         self.application.shutdown().await?;
+        self.usage_log.flush().await?;
+        fastrace::flush();
 
         Ok(())
     }
@@ -146,43 +158,37 @@ pub async fn make_app(
     // TODO(CX-6572) Separate `SegmentMetadataFetcher` from `SearcherImpl`
     let segment_metadata_fetcher: Arc<dyn SegmentTermMetadataFetcher> =
         Arc::new(in_process_searcher);
+    let usage_log = LocalUsageLog::new(runtime.clone(), config.storage_dir().join("usage"))?;
+    runtime.spawn("local_usage_log", usage_log.clone().go());
     let database = Database::load(
         persistence.clone(),
         runtime.clone(),
         searcher.clone(),
         preempt_tx,
         virtual_system_mapping().clone(),
-        Arc::new(NoOpUsageEventLogger),
+        Arc::new(usage_log.clone()),
     )
     .await?;
     initialize_application_system_tables(&database).await?;
-    let files_storage = Arc::new(LocalDirStorage::for_use_case(
-        runtime.clone(),
-        &config.storage_dir().to_string_lossy(),
-        StorageUseCase::Files,
-    )?);
-    let modules_storage = Arc::new(LocalDirStorage::for_use_case(
-        runtime.clone(),
-        &config.storage_dir().to_string_lossy(),
-        StorageUseCase::Modules,
-    )?);
-    let search_storage = Arc::new(LocalDirStorage::for_use_case(
-        runtime.clone(),
-        &config.storage_dir().to_string_lossy(),
-        StorageUseCase::SearchIndexes,
-    )?);
+    let storage_keyring = config.storage_keyring()?;
+    let local_storage = |use_case: StorageUseCase| -> anyhow::Result<Arc<dyn Storage>> {
+        let storage: Arc<dyn Storage> = Arc::new(LocalDirStorage::for_use_case(
+            runtime.clone(),
+            &config.storage_dir().to_string_lossy(),
+            use_case,
+        )?);
+        match &storage_keyring {
+            Some(keyring) => Ok(Arc::new(EncryptedStorage::new(storage, keyring.clone()))),
+            None => Ok(storage),
+        }
+    };
+    let files_storage = local_storage(StorageUseCase::Files)?;
+    let modules_storage = local_storage(StorageUseCase::Modules)?;
+    let search_storage = local_storage(StorageUseCase::SearchIndexes)?;
     // Search storage needs to be set for Database to be fully initialized
     database.set_search_storage(search_storage.clone());
-    let exports_storage = Arc::new(LocalDirStorage::for_use_case(
-        runtime.clone(),
-        &config.storage_dir().to_string_lossy(),
-        StorageUseCase::Exports,
-    )?);
-    let snapshot_imports_storage = Arc::new(LocalDirStorage::for_use_case(
-        runtime.clone(),
-        &config.storage_dir().to_string_lossy(),
-        StorageUseCase::SnapshotImports,
-    )?);
+    let exports_storage = local_storage(StorageUseCase::Exports)?;
+    let snapshot_imports_storage = local_storage(StorageUseCase::SnapshotImports)?;
 
     let file_storage = FileStorage {
         transactional_file_storage: TransactionalFileStorage::new(
@@ -194,7 +200,14 @@ pub async fn make_app(
     };
 
     let node_process_timeout = *ACTION_USER_TIMEOUT + Duration::from_secs(5);
-    let node_executor = Arc::new(LocalNodeExecutor::new(node_process_timeout)?);
+    let node_executor = Arc::new(
+        LocalNodeExecutor::new(
+            node_process_timeout,
+            config.node_search_path.clone(),
+            config.node_sandbox_config(),
+        )?
+        .with_package_storage(modules_storage.clone()),
+    );
     let actions = Actions::new(
         node_executor,
         config.convex_origin_url()?,
@@ -254,6 +267,7 @@ pub async fn make_app(
             Arc::new(NullAccessTokenAuth),
         )),
         QueryCache::new(*UDF_CACHE_MAX_SIZE),
+        FunctionQuotaLimits::from_knobs(),
     )
     .await?;
 
@@ -266,11 +280,24 @@ pub async fn make_app(
         runtime.spawn("beacon_worker", beacon_future);
     }
 
+    if let Some(otlp_endpoint) = config.otlp_endpoint.clone() {
+        let sample_ratio = config.otlp_trace_sample_ratio;
+        anyhow::ensure!(
+            (0.0..=1.0).contains(&sample_ratio),
+            "--otlp-trace-sample-ratio must be between 0 and 1, got {sample_ratio}"
+        );
+        let (reporter, exporter) = OtlpExporter::new(otlp_endpoint, instance_name.clone());
+        fastrace::set_reporter(reporter, fastrace::collector::Config::default());
+        set_sampling_config(&sample_ratio.to_string());
+        runtime.spawn("otlp_exporter", exporter.go());
+    }
+
     let app_state = LocalAppState {
         origin,
         site_origin: config.convex_site_url()?,
         instance_name,
         application,
+        usage_log,
         zombify_rx,
     };
 
@@ -107,6 +107,9 @@ async fn run_server_inner(runtime: ProdRuntime, config: LocalConfig) -> anyhow::
         config.db,
         &config.db_spec,
         config.do_not_require_ssl,
+        config.postgres_schema.clone(),
+        config.postgres_replica_url.as_deref(),
+        config.mysql_table_prefix.clone(),
         &config.name(),
         runtime.clone(),
         preempt_signal.clone(),
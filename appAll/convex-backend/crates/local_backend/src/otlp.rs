@@ -0,0 +1,441 @@
+//! Exports the backend's `fastrace` spans to an OpenTelemetry collector over
+//! OTLP/HTTP with JSON bodies.
+//!
+//! `fastrace` hands finished spans to [`OtlpReporter`] on its own collector
+//! thread, which passes them to [`OtlpExporter`] to be posted from the async
+//! runtime. Spans are dropped rather than queued without bound when the
+//! collector can't keep up.
+
+use std::borrow::Cow;
+
+use common::{
+    fastrace_helpers::{
+        SPAN_KIND_CLIENT,
+        SPAN_KIND_PROPERTY,
+        SPAN_KIND_SERVER,
+    },
+    version::SERVER_VERSION_STR,
+};
+use fastrace::collector::{
+    EventRecord,
+    Reporter,
+    SpanRecord,
+};
+use reqwest::Client;
+use serde::Serialize;
+use tokio::sync::mpsc;
+use url::Url;
+
+/// Number of batches of spans from `fastrace` to buffer while an export is in
+/// flight.
+const REPORT_QUEUE_SIZE: usize = 256;
+
+/// Most spans to send to the collector in one request.
+const MAX_SPANS_PER_EXPORT: usize = 4096;
+
+const SERVICE_NAME: &str = "convex-backend";
+
+// `SpanKind` values.
+const OTLP_SPAN_KIND_INTERNAL: u32 = 1;
+const OTLP_SPAN_KIND_SERVER: u32 = 2;
+const OTLP_SPAN_KIND_CLIENT: u32 = 3;
+
+/// Passed to `fastrace::set_reporter`.
+pub struct OtlpReporter {
+    tx: mpsc::Sender<Vec<SpanRecord>>,
+}
+
+impl Reporter for OtlpReporter {
+    fn report(&mut self, spans: Vec<SpanRecord>) {
+        if spans.is_empty() {
+            return;
+        }
+        let num_spans = spans.len();
+        if self.tx.try_send(spans).is_err() {
+            tracing::warn!("OTLP exporter is behind, dropping {num_spans} spans");
+        }
+    }
+}
+
+pub struct OtlpExporter {
+    client: Client,
+    traces_url: Url,
+    resource: OtlpResource,
+    rx: mpsc::Receiver<Vec<SpanRecord>>,
+}
+
+impl OtlpExporter {
+    /// `endpoint` is the collector's base URL, e.g. `http://localhost:4318`.
+    /// Spans are posted to `/v1/traces` under it.
+    pub fn new(endpoint: Url, instance_name: String) -> (OtlpReporter, Self) {
+        let mut traces_url = endpoint;
+        let path = format!("{}/v1/traces", traces_url.path().trim_end_matches('/'));
+        traces_url.set_path(&path);
+        let (tx, rx) = mpsc::channel(REPORT_QUEUE_SIZE);
+        let resource = OtlpResource {
+            attributes: vec![
+                OtlpKeyValue::new("service.name", SERVICE_NAME),
+                OtlpKeyValue::new("service.version", SERVER_VERSION_STR.as_str()),
+                OtlpKeyValue::new("service.instance.id", instance_name),
+            ],
+        };
+        let exporter = Self {
+            client: Client::new(),
+            traces_url,
+            resource,
+            rx,
+        };
+        (OtlpReporter { tx }, exporter)
+    }
+
+    pub async fn go(mut self) {
+        tracing::info!("Exporting traces to {}", self.traces_url);
+        while let Some(mut spans) = self.rx.recv().await {
+            while spans.len() < MAX_SPANS_PER_EXPORT
+                && let Ok(more) = self.rx.try_recv()
+            {
+                spans.extend(more);
+            }
+            let num_spans = spans.len();
+            if let Err(e) = self.export(spans).await {
+                // The collector being down shouldn't page anyone, so don't
+                // report to Sentry.
+                tracing::warn!("Failed to export {num_spans} spans to OTLP collector: {e:#}");
+            }
+        }
+    }
+
+    async fn export(&self, spans: Vec<SpanRecord>) -> anyhow::Result<()> {
+        let request = OtlpExportTraceRequest {
+            resource_spans: vec![OtlpResourceSpans {
+                resource: &self.resource,
+                scope_spans: vec![OtlpScopeSpans {
+                    scope: OtlpScope {
+                        name: SERVICE_NAME,
+                        version: SERVER_VERSION_STR.as_str(),
+                    },
+                    spans: spans.into_iter().map(OtlpSpan::from).collect(),
+                }],
+            }],
+        };
+        self.client
+            .post(self.traces_url.clone())
+            .json(&request)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+// The OTLP/JSON encoding of `ExportTraceServiceRequest`. Note that trace and
+// span IDs are hex rather than base64 encoded, unlike the usual protobuf JSON
+// mapping.
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct OtlpExportTraceRequest<'a> {
+    resource_spans: Vec<OtlpResourceSpans<'a>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct OtlpResourceSpans<'a> {
+    resource: &'a OtlpResource,
+    scope_spans: Vec<OtlpScopeSpans>,
+}
+
+#[derive(Serialize)]
+struct OtlpResource {
+    attributes: Vec<OtlpKeyValue>,
+}
+
+#[derive(Serialize)]
+struct OtlpScopeSpans {
+    scope: OtlpScope,
+    spans: Vec<OtlpSpan>,
+}
+
+#[derive(Serialize)]
+struct OtlpScope {
+    name: &'static str,
+    version: &'static str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct OtlpSpan {
+    trace_id: String,
+    span_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parent_span_id: Option<String>,
+    name: Cow<'static, str>,
+    kind: u32,
+    start_time_unix_nano: String,
+    end_time_unix_nano: String,
+    attributes: Vec<OtlpKeyValue>,
+    events: Vec<OtlpEvent>,
+}
+
+impl From<SpanRecord> for OtlpSpan {
+    fn from(span: SpanRecord) -> Self {
+        // The kind is a field of its own rather than an attribute.
+        let mut kind = OTLP_SPAN_KIND_INTERNAL;
+        let attributes = span
+            .properties
+            .into_iter()
+            .filter_map(|(k, v)| {
+                if k != SPAN_KIND_PROPERTY {
+                    return Some(OtlpKeyValue::new(k, v));
+                }
+                kind = match &*v {
+                    SPAN_KIND_SERVER => OTLP_SPAN_KIND_SERVER,
+                    SPAN_KIND_CLIENT => OTLP_SPAN_KIND_CLIENT,
+                    _ => OTLP_SPAN_KIND_INTERNAL,
+                };
+                None
+            })
+            .collect();
+        Self {
+            trace_id: format!("{:032x}", span.trace_id.0),
+            span_id: format!("{:016x}", span.span_id.0),
+            // Roots of traces started here have no parent, but roots continuing
+            // a `traceparent` have the remote span as theirs.
+            parent_span_id: (span.parent_id.0 != 0).then(|| format!("{:016x}", span.parent_id.0)),
+            name: span.name,
+            kind,
+            start_time_unix_nano: span.begin_time_unix_ns.to_string(),
+            end_time_unix_nano: (span.begin_time_unix_ns + span.duration_ns).to_string(),
+            attributes,
+            events: span.events.into_iter().map(OtlpEvent::from).collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct OtlpEvent {
+    time_unix_nano: String,
+    name: Cow<'static, str>,
+    attributes: Vec<OtlpKeyValue>,
+}
+
+impl From<EventRecord> for OtlpEvent {
+    fn from(event: EventRecord) -> Self {
+        Self {
+            time_unix_nano: event.timestamp_unix_ns.to_string(),
+            name: event.name,
+            attributes: event
+                .properties
+                .into_iter()
+                .map(|(k, v)| OtlpKeyValue::new(k, v))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct OtlpKeyValue {
+    key: Cow<'static, str>,
+    value: OtlpAnyValue,
+}
+
+impl OtlpKeyValue {
+    fn new(key: impl Into<Cow<'static, str>>, value: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            value: OtlpAnyValue {
+                string_value: value.into(),
+            },
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct OtlpAnyValue {
+    string_value: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        extract::State,
+        routing::post,
+        Json,
+        Router,
+    };
+    use common::http::ConvexHttpService;
+    use fastrace::collector::{
+        EventRecord,
+        Reporter,
+        SpanContext,
+        SpanId,
+        SpanRecord,
+        TraceId,
+    };
+    use serde_json::{
+        json,
+        Value as JsonValue,
+    };
+    use tokio::{
+        net::TcpStream,
+        sync::{
+            mpsc,
+            oneshot,
+        },
+    };
+
+    use super::OtlpExporter;
+
+    fn span(
+        trace_id: u128,
+        span_id: u64,
+        parent_id: u64,
+        name: &'static str,
+        properties: Vec<(&'static str, &'static str)>,
+    ) -> SpanRecord {
+        SpanRecord {
+            trace_id: TraceId(trace_id),
+            span_id: SpanId(span_id),
+            parent_id: SpanId(parent_id),
+            begin_time_unix_ns: 1_000,
+            duration_ns: 500,
+            name: name.into(),
+            properties: properties
+                .into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+            events: vec![],
+        }
+    }
+
+    /// Export spans to a stub collector and check they arrive as OTLP/JSON.
+    #[tokio::test]
+    async fn test_export_to_collector() -> anyhow::Result<()> {
+        let (body_tx, mut body_rx) = mpsc::channel(8);
+        async fn collect(
+            State(body_tx): State<mpsc::Sender<JsonValue>>,
+            Json(body): Json<JsonValue>,
+        ) -> Json<JsonValue> {
+            body_tx.send(body).await.unwrap();
+            Json(json!({}))
+        }
+        let collector = ConvexHttpService::new_for_test(
+            Router::new()
+                .route("/otlp/v1/traces", post(collect))
+                .with_state(body_tx),
+        );
+        let port = portpicker::pick_unused_port().expect("No ports free");
+        let addr = format!("127.0.0.1:{port}").parse()?;
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+        let collector = tokio::spawn(collector.serve(addr, async move {
+            shutdown_rx.await.unwrap();
+        }));
+        // Can take a moment after the server spawn to connect to it.
+        while TcpStream::connect(addr).await.is_err() {
+            tokio::task::yield_now().await;
+        }
+
+        let (mut reporter, exporter) = OtlpExporter::new(
+            format!("http://127.0.0.1:{port}/otlp/").parse()?,
+            "carnitas".to_string(),
+        );
+        let exporter = tokio::spawn(exporter.go());
+
+        // A trace continuing one from an incoming `traceparent`.
+        let parent = SpanContext::decode_w3c_traceparent(
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+        )
+        .unwrap();
+        let mut root = span(
+            parent.trace_id.0,
+            1,
+            parent.span_id.0,
+            "/http/hello",
+            vec![("span.kind", "server"), ("request_id", "abc123")],
+        );
+        root.events.push(EventRecord {
+            name: "retry".into(),
+            timestamp_unix_ns: 1_200,
+            properties: vec![("attempt".into(), "1".into())],
+        });
+        let child = span(
+            parent.trace_id.0,
+            2,
+            1,
+            "IsolateWorker::handle_request",
+            vec![("udf_path", "messages.js:send"), ("request_id", "abc123")],
+        );
+        let fetch = span(
+            parent.trace_id.0,
+            3,
+            2,
+            "fetch",
+            vec![("span.kind", "client"), ("http.method", "GET")],
+        );
+
+        reporter.report(vec![root, child, fetch]);
+        let body = body_rx.recv().await.unwrap();
+
+        let resource_spans = &body["resourceSpans"][0];
+        let resource_attributes = resource_spans["resource"]["attributes"].as_array().unwrap();
+        assert!(resource_attributes.contains(&json!({
+            "key": "service.name",
+            "value": { "stringValue": "convex-backend" },
+        })));
+        assert!(resource_attributes.contains(&json!({
+            "key": "service.instance.id",
+            "value": { "stringValue": "carnitas" },
+        })));
+        let spans = resource_spans["scopeSpans"][0]["spans"].as_array().unwrap();
+        assert_eq!(
+            spans[0],
+            json!({
+                "traceId": "0af7651916cd43dd8448eb211c80319c",
+                "spanId": "0000000000000001",
+                "parentSpanId": "b7ad6b7169203331",
+                "name": "/http/hello",
+                "kind": 2,
+                "startTimeUnixNano": "1000",
+                "endTimeUnixNano": "1500",
+                "attributes": [
+                    { "key": "request_id", "value": { "stringValue": "abc123" } },
+                ],
+                "events": [{
+                    "timeUnixNano": "1200",
+                    "name": "retry",
+                    "attributes": [
+                        { "key": "attempt", "value": { "stringValue": "1" } },
+                    ],
+                }],
+            })
+        );
+        assert_eq!(spans[1]["traceId"], "0af7651916cd43dd8448eb211c80319c");
+        assert_eq!(spans[1]["parentSpanId"], "0000000000000001");
+        assert_eq!(spans[1]["kind"], 1);
+        assert_eq!(
+            spans[1]["attributes"][0],
+            json!({ "key": "udf_path", "value": { "stringValue": "messages.js:send" } })
+        );
+        assert_eq!(spans[2]["kind"], 3);
+        assert_eq!(
+            spans[2]["attributes"],
+            json!([{ "key": "http.method", "value": { "stringValue": "GET" } }])
+        );
+
+        // Roots of traces started by the backend have no parent.
+        reporter.report(vec![span(7, 8, 0, "sync-worker/mutation", vec![])]);
+        let body = body_rx.recv().await.unwrap();
+        let span = &body["resourceSpans"][0]["scopeSpans"][0]["spans"][0];
+        assert_eq!(span["name"], "sync-worker/mutation");
+        assert_eq!(span["kind"], 1);
+        assert!(span.get("parentSpanId").is_none());
+
+        drop(reporter);
+        exporter.await?;
+        shutdown_tx.send(()).unwrap();
+        collector.await??;
+        Ok(())
+    }
+}
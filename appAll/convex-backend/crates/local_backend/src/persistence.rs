@@ -5,8 +5,13 @@ use clusters::{
     DbDriverTag,
 };
 use common::{
-    knobs::DATABASE_USE_PREPARED_STATEMENTS,
+    knobs::{
+        DATABASE_USE_PREPARED_STATEMENTS,
+        PERSISTENCE_DOCUMENT_COMPRESSION_TRAINING_INTERVAL,
+    },
     persistence::Persistence,
+    persistence_compression::DocumentCompressor,
+    runtime::Runtime,
     shutdown::ShutdownSignal,
 };
 use mysql::{
@@ -18,6 +23,7 @@ use postgres::{
     PostgresOptions,
     PostgresPersistence,
 };
+use rocksdb_persistence::RocksDbPersistence;
 use runtime::prod::ProdRuntime;
 use sqlite::SqlitePersistence;
 
@@ -25,6 +31,9 @@ pub async fn connect_persistence(
     db: DbDriverTag,
     db_spec: &str,
     do_not_require_ssl: bool,
+    postgres_schema: Option<String>,
+    postgres_replica_url: Option<&str>,
+    mysql_table_prefix: String,
     instance_name: &str,
     runtime: ProdRuntime,
     shutdown_signal: ShutdownSignal,
@@ -36,17 +45,40 @@ pub async fn connect_persistence(
             tracing::info!("Connected to SQLite at {db_spec}");
             persistence
         },
+        DbDriverTag::RocksDb => {
+            let persistence = Arc::new(RocksDbPersistence::new(db_spec, false)?);
+            tracing::info!("Opened RocksDB at {db_spec}");
+            persistence
+        },
         DbDriverTag::Postgres(version) | DbDriverTag::PostgresAwsIam(version) => {
-            let options = PostgresOptions {
-                allow_read_only: false,
-                version,
-            };
             let args = persistence_args_from_cluster_url(
                 instance_name,
                 db_spec.parse()?,
                 db,
                 require_ssl,
             )?;
+            // The replica holds the same database as the primary, so its URL
+            // gets the same database name.
+            let replica_url = postgres_replica_url
+                .map(|url| {
+                    anyhow::Ok(
+                        persistence_args_from_cluster_url(
+                            instance_name,
+                            url.parse()?,
+                            db,
+                            require_ssl,
+                        )?
+                        .url
+                        .to_string(),
+                    )
+                })
+                .transpose()?;
+            let options = PostgresOptions {
+                allow_read_only: false,
+                version,
+                schema: postgres_schema,
+                replica_url,
+            };
             let persistence = Arc::new(PostgresPersistence::new(args.url.as_str(), options).await?);
             tracing::info!("Connected to Postgres database: {} ", args.db_name);
             persistence
@@ -56,6 +88,7 @@ pub async fn connect_persistence(
                 allow_read_only: false,
                 version,
                 use_prepared_statements: *DATABASE_USE_PREPARED_STATEMENTS,
+                table_prefix: mysql_table_prefix,
             };
             let args = persistence_args_from_cluster_url(
                 instance_name,
@@ -68,7 +101,7 @@ pub async fn connect_persistence(
                     Arc::new(ConvexMySqlPool::new(
                         &args.url,
                         options.use_prepared_statements,
-                        Some(runtime),
+                        Some(runtime.clone()),
                     )?),
                     args.db_name.clone(),
                     options,
@@ -80,5 +113,33 @@ pub async fn connect_persistence(
             persistence
         },
     };
+    if let Some(compressor) = persistence.document_compressor() {
+        compressor.initialize(persistence.as_ref()).await?;
+        if compressor.needs_dictionary() {
+            runtime.spawn(
+                "document_compression_training",
+                train_compression_dictionary(runtime.clone(), compressor, persistence.clone()),
+            );
+        }
+    }
     Ok(persistence)
 }
+
+/// Keep checking whether a new deployment has enough documents to train its
+/// compression dictionary on, instead of only trying at startup.
+async fn train_compression_dictionary(
+    runtime: ProdRuntime,
+    compressor: Arc<DocumentCompressor>,
+    persistence: Arc<dyn Persistence>,
+) {
+    loop {
+        runtime
+            .wait(*PERSISTENCE_DOCUMENT_COMPRESSION_TRAINING_INTERVAL)
+            .await;
+        match compressor.train_dictionary(persistence.as_ref()).await {
+            Ok(true) => return,
+            Ok(false) => (),
+            Err(e) => tracing::warn!("Failed to train document compression dictionary: {e:#}"),
+        }
+    }
+}
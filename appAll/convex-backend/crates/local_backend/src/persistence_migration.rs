@@ -0,0 +1,523 @@
+//! Copies a deployment's persistence from one database driver to another, e.g.
+//! from SQLite to Postgres, without going through a snapshot export.
+//!
+//! The whole document log is copied with its original timestamps, so document
+//! history, creation times and references (like `_storage` ids) are kept as
+//! is. Database indexes are copied as of the snapshot the migration runs at,
+//! which is all the target needs to serve reads once it starts up, and the
+//! target's index retention is moved up to that snapshot so nothing reads
+//! earlier index history.
+//!
+//! Both sides are written in chunks that are safe to replay, so an interrupted
+//! migration can simply be run again. Neither deployment may be running while
+//! this happens.
+
+use std::{
+    cmp,
+    collections::BTreeSet,
+    hash::Hash,
+    sync::Arc,
+};
+
+use common::{
+    bootstrap_model::index::{
+        database_index::{
+            DeveloperDatabaseIndexConfig,
+            IndexedFields,
+        },
+        IndexConfig,
+        TabletIndexMetadata,
+    },
+    interval::Interval,
+    knobs::DEFAULT_DOCUMENTS_PAGE_SIZE,
+    persistence::{
+        new_idle_repeatable_ts,
+        ConflictStrategy,
+        NoopRetentionValidator,
+        Persistence,
+        PersistenceGlobalKey,
+        PersistenceReader,
+        RepeatablePersistence,
+        RetentionValidator,
+        TimestampRange,
+    },
+    query::Order,
+    runtime::Runtime,
+    sha256::{
+        Sha256,
+        Sha256Digest,
+    },
+    try_chunks::TryChunksExt,
+    types::{
+        DatabaseIndexUpdate,
+        DatabaseIndexValue,
+        IndexId,
+        RepeatableTimestamp,
+        Timestamp,
+    },
+};
+use database::DatabaseSnapshot;
+use futures::{
+    pin_mut,
+    TryStreamExt,
+};
+use value::{
+    ConvexValue,
+    TabletId,
+};
+
+/// The largest chunk any persistence accepts in a single write.
+const MAX_CHUNK_SIZE: usize = 16384;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MigrationSummary {
+    pub snapshot_ts: Timestamp,
+    /// Revisions copied by this run, which excludes those copied by earlier,
+    /// interrupted runs.
+    pub documents_copied: usize,
+    pub index_entries_copied: usize,
+    /// Revisions and index entries present on both sides after the migration.
+    pub documents_verified: usize,
+    pub index_entries_verified: usize,
+}
+
+struct DatabaseIndex {
+    index_id: IndexId,
+    tablet_id: TabletId,
+    name: String,
+    fields: IndexedFields,
+    is_system_index: bool,
+}
+
+pub struct PersistenceMigration {
+    source: Arc<dyn Persistence>,
+    target: Arc<dyn Persistence>,
+    chunk_size: usize,
+    // Neither side is serving traffic, so there's no retention to race with.
+    retention_validator: Arc<dyn RetentionValidator>,
+}
+
+impl PersistenceMigration {
+    pub fn new(
+        source: Arc<dyn Persistence>,
+        target: Arc<dyn Persistence>,
+        chunk_size: usize,
+    ) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            (1..=MAX_CHUNK_SIZE).contains(&chunk_size),
+            "Chunk size must be between 1 and {MAX_CHUNK_SIZE}"
+        );
+        Ok(Self {
+            source,
+            target,
+            chunk_size,
+            retention_validator: Arc::new(NoopRetentionValidator),
+        })
+    }
+
+    pub async fn run<RT: Runtime>(&self, rt: &RT) -> anyhow::Result<MigrationSummary> {
+        self.check_target_matches_source().await?;
+        let snapshot_ts = new_idle_repeatable_ts(self.source.as_ref(), rt).await?;
+        tracing::info!("Migrating persistence as of {}", *snapshot_ts);
+
+        let documents_copied = self.copy_documents(snapshot_ts).await?;
+        let indexes = self.database_indexes::<RT>(snapshot_ts).await?;
+        let mut index_entries_copied = 0;
+        for index in &indexes {
+            index_entries_copied += self.copy_index(snapshot_ts, index).await?;
+        }
+        self.copy_persistence_globals(snapshot_ts).await?;
+
+        let documents_verified = self.verify_documents(snapshot_ts).await?;
+        let mut index_entries_verified = 0;
+        for index in &indexes {
+            index_entries_verified += self.verify_index(snapshot_ts, index).await?;
+        }
+        Ok(MigrationSummary {
+            snapshot_ts: *snapshot_ts,
+            documents_copied,
+            index_entries_copied,
+            documents_verified,
+            index_entries_verified,
+        })
+    }
+
+    /// A non-empty target must be the result of an earlier run from the same
+    /// source, not some other deployment.
+    async fn check_target_matches_source(&self) -> anyhow::Result<()> {
+        let first_document = |reader: Arc<dyn PersistenceReader>| {
+            let retention_validator = self.retention_validator.clone();
+            async move {
+                reader
+                    .load_documents(TimestampRange::all(), Order::Asc, 1, retention_validator)
+                    .try_next()
+                    .await
+            }
+        };
+        let Some(target_first) = first_document(self.target.reader()).await? else {
+            return Ok(());
+        };
+        let source_first = first_document(self.source.reader()).await?;
+        anyhow::ensure!(
+            source_first.as_ref() == Some(&target_first),
+            "Target persistence already holds a different deployment"
+        );
+        tracing::info!("Target persistence isn't empty, resuming migration");
+        Ok(())
+    }
+
+    async fn copy_documents(&self, snapshot_ts: RepeatableTimestamp) -> anyhow::Result<usize> {
+        // Everything before the target's latest revision has already been
+        // copied. Revisions at that timestamp may have been split across chunks,
+        // so copy them again.
+        let resume_ts = self
+            .target
+            .reader()
+            .load_documents(
+                TimestampRange::all(),
+                Order::Desc,
+                1,
+                self.retention_validator.clone(),
+            )
+            .try_next()
+            .await?
+            .map(|entry| entry.ts);
+        let range = match resume_ts {
+            Some(ts) => TimestampRange::new(ts..=*snapshot_ts)?,
+            None => TimestampRange::snapshot(*snapshot_ts),
+        };
+        let source = self.source.reader();
+        let chunks = source
+            .load_documents(
+                range,
+                Order::Asc,
+                *DEFAULT_DOCUMENTS_PAGE_SIZE,
+                self.retention_validator.clone(),
+            )
+            .try_chunks2(self.chunk_size);
+        pin_mut!(chunks);
+        let mut copied = 0;
+        while let Some(chunk) = chunks.try_next().await? {
+            let chunk_len = chunk.len();
+            let last_ts = chunk.last().map(|entry| entry.ts);
+            self.target
+                .write(chunk, BTreeSet::new(), ConflictStrategy::Overwrite)
+                .await?;
+            copied += chunk_len;
+            if let Some(last_ts) = last_ts {
+                tracing::info!("Copied {copied} document revisions, up to {last_ts}");
+            }
+        }
+        Ok(copied)
+    }
+
+    async fn database_indexes<RT: Runtime>(
+        &self,
+        snapshot_ts: RepeatableTimestamp,
+    ) -> anyhow::Result<Vec<DatabaseIndex>> {
+        let snapshot = RepeatablePersistence::new(
+            self.source.reader(),
+            snapshot_ts,
+            self.retention_validator.clone(),
+        )
+        .read_snapshot(snapshot_ts)?;
+        let (_, _, _, index_documents, _) =
+            DatabaseSnapshot::<RT>::load_table_and_index_metadata(&snapshot).await?;
+        let mut indexes = vec![];
+        for (id, (_, document)) in index_documents {
+            let metadata = TabletIndexMetadata::from_document(document)?;
+            // Text and vector indexes live in search storage, not persistence.
+            let IndexConfig::Database {
+                developer_config: DeveloperDatabaseIndexConfig { fields },
+                ..
+            } = metadata.config
+            else {
+                continue;
+            };
+            indexes.push(DatabaseIndex {
+                index_id: id.internal_id(),
+                tablet_id: *metadata.name.table(),
+                name: metadata.name.to_string(),
+                fields,
+                is_system_index: metadata.name.descriptor().is_reserved(),
+            });
+        }
+        Ok(indexes)
+    }
+
+    async fn copy_index(
+        &self,
+        snapshot_ts: RepeatableTimestamp,
+        index: &DatabaseIndex,
+    ) -> anyhow::Result<usize> {
+        // Entries are written in key order, so everything up to the target's
+        // last key has already been copied.
+        let target = self.target.reader();
+        let last_copied = target
+            .index_scan(
+                index.index_id,
+                index.tablet_id,
+                *snapshot_ts,
+                &Interval::all(),
+                Order::Desc,
+                1,
+                self.retention_validator.clone(),
+            )
+            .try_next()
+            .await?;
+        let interval = match last_copied {
+            Some((key, _)) => Interval::all().split_after(key, Order::Asc).1,
+            None => Interval::all(),
+        };
+
+        let version = self.source.reader().version();
+        let source = self.source.reader();
+        let chunks = source
+            .index_scan(
+                index.index_id,
+                index.tablet_id,
+                *snapshot_ts,
+                &interval,
+                Order::Asc,
+                self.chunk_size,
+                self.retention_validator.clone(),
+            )
+            .try_chunks2(self.chunk_size);
+        pin_mut!(chunks);
+        let mut copied = 0;
+        while let Some(chunk) = chunks.try_next().await? {
+            let mut updates = BTreeSet::new();
+            for (key, revision) in chunk {
+                let index_key = revision.value.index_key(&index.fields[..], version);
+                anyhow::ensure!(
+                    index_key.clone().into_bytes() == key,
+                    "Index key for {} in {} doesn't match the stored key",
+                    revision.value.id(),
+                    index.name,
+                );
+                updates.insert((
+                    revision.ts,
+                    DatabaseIndexUpdate {
+                        index_id: index.index_id,
+                        key: index_key,
+                        value: DatabaseIndexValue::NonClustered(revision.value.id()),
+                        is_system_index: index.is_system_index,
+                    },
+                ));
+            }
+            copied += updates.len();
+            self.target
+                .write(vec![], updates, ConflictStrategy::Overwrite)
+                .await?;
+        }
+        tracing::info!("Copied {copied} entries of index {}", index.name);
+        Ok(copied)
+    }
+
+    async fn copy_persistence_globals(
+        &self,
+        snapshot_ts: RepeatableTimestamp,
+    ) -> anyhow::Result<()> {
+        let source = self.source.reader();
+        for key in PersistenceGlobalKey::all_keys() {
+            let mut value = source.get_persistence_global(key).await?;
+            // Index history before the snapshot wasn't copied.
+            if matches!(
+                key,
+                PersistenceGlobalKey::RetentionMinSnapshotTimestamp
+                    | PersistenceGlobalKey::RetentionConfirmedDeletedTimestamp
+            ) {
+                let ts = value.map(Timestamp::try_from).transpose()?;
+                value = Some(cmp::max(ts.unwrap_or(Timestamp::MIN), *snapshot_ts).into());
+            }
+            if let Some(value) = value {
+                self.target.write_persistence_global(key, value).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn verify_documents(&self, snapshot_ts: RepeatableTimestamp) -> anyhow::Result<usize> {
+        let checksum = |reader: Arc<dyn PersistenceReader>| {
+            let retention_validator = self.retention_validator.clone();
+            async move {
+                let stream = reader.load_documents(
+                    TimestampRange::snapshot(*snapshot_ts),
+                    Order::Asc,
+                    *DEFAULT_DOCUMENTS_PAGE_SIZE,
+                    retention_validator,
+                );
+                pin_mut!(stream);
+                let mut checksum = Checksum::new();
+                while let Some(entry) = stream.try_next().await? {
+                    checksum.add((
+                        entry.ts,
+                        entry.id,
+                        entry.prev_ts,
+                        entry
+                            .value
+                            .map(|document| ConvexValue::Object(document.into_value().0)),
+                    ));
+                }
+                anyhow::Ok(checksum.finish())
+            }
+        };
+        let (source, target) = futures::try_join!(
+            checksum(self.source.reader()),
+            checksum(self.target.reader())
+        )?;
+        anyhow::ensure!(
+            source == target,
+            "Document log mismatch after migration: source has {} revisions ({:?}), target has {} \
+             ({:?})",
+            source.0,
+            source.1,
+            target.0,
+            target.1,
+        );
+        tracing::info!("Verified {} document revisions", source.0);
+        Ok(source.0)
+    }
+
+    async fn verify_index(
+        &self,
+        snapshot_ts: RepeatableTimestamp,
+        index: &DatabaseIndex,
+    ) -> anyhow::Result<usize> {
+        let checksum = |reader: Arc<dyn PersistenceReader>| {
+            let retention_validator = self.retention_validator.clone();
+            async move {
+                let stream = reader.index_scan(
+                    index.index_id,
+                    index.tablet_id,
+                    *snapshot_ts,
+                    &Interval::all(),
+                    Order::Asc,
+                    self.chunk_size,
+                    retention_validator,
+                );
+                pin_mut!(stream);
+                let mut checksum = Checksum::new();
+                while let Some((key, revision)) = stream.try_next().await? {
+                    checksum.add((key, revision.ts, revision.value.id()));
+                }
+                anyhow::Ok(checksum.finish())
+            }
+        };
+        let (source, target) = futures::try_join!(
+            checksum(self.source.reader()),
+            checksum(self.target.reader())
+        )?;
+        anyhow::ensure!(
+            source == target,
+            "Index {} mismatch after migration: source has {} entries ({:?}), target has {} ({:?})",
+            index.name,
+            source.0,
+            source.1,
+            target.0,
+            target.1,
+        );
+        Ok(source.0)
+    }
+}
+
+/// Count and running hash of a sequence of rows.
+struct Checksum {
+    count: usize,
+    hasher: Sha256,
+}
+
+impl Checksum {
+    fn new() -> Self {
+        Self {
+            count: 0,
+            hasher: Sha256::new(),
+        }
+    }
+
+    fn add(&mut self, row: impl Hash) {
+        self.count += 1;
+        row.hash(&mut self.hasher);
+    }
+
+    fn finish(self) -> (usize, Sha256Digest) {
+        (self.count, self.hasher.finalize())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use common::{
+        assert_obj,
+        persistence::Persistence,
+        testing::TestPersistence,
+        types::TableName,
+    };
+    use database::{
+        test_helpers::{
+            DbFixtures,
+            DbFixturesArgs,
+        },
+        TestFacingModel,
+    };
+    use keybroker::Identity;
+    use runtime::testing::TestRuntime;
+    use value::ConvexValue;
+
+    use super::PersistenceMigration;
+
+    #[convex_macro::test_runtime]
+    async fn test_migrate_persistence(rt: TestRuntime) -> anyhow::Result<()> {
+        let source: Arc<dyn Persistence> = Arc::new(TestPersistence::new());
+        let DbFixtures { db, .. } = DbFixtures::new_with_args(
+            &rt,
+            DbFixturesArgs {
+                tp: Some(source.clone()),
+                ..Default::default()
+            },
+        )
+        .await?;
+        let table: TableName = "messages".parse()?;
+        let mut tx = db.begin(Identity::system()).await?;
+        let id = TestFacingModel::new(&mut tx)
+            .insert(&table, assert_obj!("body" => "hello"))
+            .await?;
+        db.commit(tx).await?;
+        let mut tx = db.begin(Identity::system()).await?;
+        TestFacingModel::new(&mut tx)
+            .replace(id, assert_obj!("body" => "hello again"))
+            .await?;
+        db.commit(tx).await?;
+        db.shutdown().await?;
+
+        let target: Arc<dyn Persistence> = Arc::new(TestPersistence::new());
+        let migration = PersistenceMigration::new(source.clone(), target.clone(), 3)?;
+        let summary = migration.run(&rt).await?;
+        assert!(summary.documents_copied > 0);
+        assert_eq!(summary.documents_copied, summary.documents_verified);
+        assert_eq!(summary.index_entries_copied, summary.index_entries_verified);
+
+        // Running again only replays the last timestamp.
+        let summary = migration.run(&rt).await?;
+        assert!(summary.documents_copied < summary.documents_verified);
+        assert_eq!(summary.index_entries_copied, 0);
+
+        let DbFixtures { db, .. } = DbFixtures::new_with_args(
+            &rt,
+            DbFixturesArgs {
+                tp: Some(target),
+                ..Default::default()
+            },
+        )
+        .await?;
+        let mut tx = db.begin(Identity::system()).await?;
+        let document = tx.get(id).await?.unwrap();
+        assert_eq!(
+            document.value().get("body"),
+            Some(&ConvexValue::try_from("hello again")?)
+        );
+        Ok(())
+    }
+}
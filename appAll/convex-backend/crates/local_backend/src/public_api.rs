@@ -1,3 +1,5 @@
+use std::net::IpAddr;
+
 use application::{
     api::ExecuteQueryTimestamp,
     redaction::{
@@ -20,10 +22,12 @@ use common::{
             Path,
             Query,
         },
+        ExtractClientAddress,
         ExtractClientVersion,
         ExtractRequestId,
         ExtractResolvedHostname,
         HttpResponseError,
+        ResolvedHostname,
     },
     types::FunctionCaller,
     version::ClientVersion,
@@ -180,6 +184,23 @@ impl UdfResponse {
     }
 }
 
+/// Count a request against its client's rate limit.
+async fn check_client_rate_limit(
+    st: &RouterState,
+    host: &ResolvedHostname,
+    client_address: Option<IpAddr>,
+    requests: u32,
+) -> anyhow::Result<()> {
+    match client_address {
+        Some(client_address) => {
+            st.api
+                .check_client_rate_limit(host, client_address, requests)
+                .await
+        },
+        None => Ok(()),
+    }
+}
+
 /// Executes an arbitrary query/mutation/action from its name.
 pub async fn public_function_post(
     State(st): State<RouterState>,
@@ -187,8 +208,10 @@ pub async fn public_function_post(
     ExtractRequestId(request_id): ExtractRequestId,
     ExtractAuthenticationToken(auth_token): ExtractAuthenticationToken,
     ExtractClientVersion(client_version): ExtractClientVersion,
+    ExtractClientAddress(client_address): ExtractClientAddress,
     Json(req): Json<UdfPostRequestWithComponent>,
 ) -> Result<impl IntoResponse, HttpResponseError> {
+    check_client_rate_limit(&st, &host, client_address, 1).await?;
     // NOTE: We could coalesce authenticating and executing the query into one
     // rpc but we keep things simple by reusing the same method as the sync worker.
     // Round trip latency between Usher and Backend is much smaller than between
@@ -247,8 +270,10 @@ pub async fn public_function_post_with_path(
     ExtractRequestId(request_id): ExtractRequestId,
     ExtractAuthenticationToken(auth_token): ExtractAuthenticationToken,
     ExtractClientVersion(client_version): ExtractClientVersion,
+    ExtractClientAddress(client_address): ExtractClientAddress,
     Json(req): Json<UdfPostRequestArgsOnly>,
 ) -> Result<impl IntoResponse, HttpResponseError> {
+    check_client_rate_limit(&st, &host, client_address, 1).await?;
     // NOTE: We could coalesce authenticating and executing the query into one
     // rpc but we keep things simple by reusing the same method as the sync worker.
     // Round trip latency between Usher and Backend is much smaller than between
@@ -333,7 +358,9 @@ pub async fn public_query_get(
     ExtractRequestId(request_id): ExtractRequestId,
     ExtractAuthenticationToken(auth_token): ExtractAuthenticationToken,
     ExtractClientVersion(client_version): ExtractClientVersion,
+    ExtractClientAddress(client_address): ExtractClientAddress,
 ) -> Result<impl IntoResponse, HttpResponseError> {
+    check_client_rate_limit(&st, &host, client_address, 1).await?;
     let export_path = parse_export_path(&req.path)?;
     let args = req.args.into_arg_vec();
     let journal = None;
@@ -377,8 +404,10 @@ pub async fn public_query_post(
     ExtractRequestId(request_id): ExtractRequestId,
     ExtractAuthenticationToken(auth_token): ExtractAuthenticationToken,
     ExtractClientVersion(client_version): ExtractClientVersion,
+    ExtractClientAddress(client_address): ExtractClientAddress,
     Json(req): Json<UdfPostRequest>,
 ) -> Result<impl IntoResponse, HttpResponseError> {
+    check_client_rate_limit(&st, &host, client_address, 1).await?;
     let udf_path = parse_export_path(&req.path)?;
     let journal = None;
     // NOTE: We could coalesce authenticating and executing the query into one
@@ -431,8 +460,10 @@ pub async fn public_query_at_ts_post(
     ExtractRequestId(request_id): ExtractRequestId,
     ExtractAuthenticationToken(auth_token): ExtractAuthenticationToken,
     ExtractClientVersion(client_version): ExtractClientVersion,
+    ExtractClientAddress(client_address): ExtractClientAddress,
     Json(req): Json<UdfPostWithTsRequest>,
 ) -> Result<impl IntoResponse, HttpResponseError> {
+    check_client_rate_limit(&st, &host, client_address, 1).await?;
     let export_path = parse_export_path(&req.path)?;
     let journal = None;
     // NOTE: We could coalesce authenticating and executing the query into one
@@ -486,8 +517,11 @@ pub async fn public_query_batch_post(
     ExtractRequestId(request_id): ExtractRequestId,
     ExtractAuthenticationToken(auth_token): ExtractAuthenticationToken,
     ExtractClientVersion(client_version): ExtractClientVersion,
+    ExtractClientAddress(client_address): ExtractClientAddress,
     Json(req_batch): Json<QueryBatchArgs>,
 ) -> Result<impl IntoResponse, HttpResponseError> {
+    let requests = req_batch.queries.len().try_into().unwrap_or(u32::MAX);
+    check_client_rate_limit(&st, &host, client_address, requests).await?;
     let mut results = vec![];
     // All queries execute at the same timestamp.
     let ts = st.api.latest_timestamp(&host, request_id.clone()).await?;
@@ -535,8 +569,10 @@ pub async fn public_mutation_post(
     ExtractRequestId(request_id): ExtractRequestId,
     ExtractAuthenticationToken(auth_token): ExtractAuthenticationToken,
     ExtractClientVersion(client_version): ExtractClientVersion,
+    ExtractClientAddress(client_address): ExtractClientAddress,
     Json(req): Json<UdfPostRequest>,
 ) -> Result<impl IntoResponse, HttpResponseError> {
+    check_client_rate_limit(&st, &host, client_address, 1).await?;
     let export_path = parse_export_path(&req.path)?;
     // NOTE: We could coalesce authenticating and executing the query into one
     // rpc but we keep things simple by reusing the same method as the sync worker.
@@ -581,8 +617,10 @@ pub async fn public_action_post(
     ExtractRequestId(request_id): ExtractRequestId,
     ExtractAuthenticationToken(auth_token): ExtractAuthenticationToken,
     ExtractClientVersion(client_version): ExtractClientVersion,
+    ExtractClientAddress(client_address): ExtractClientAddress,
     Json(req): Json<UdfPostRequest>,
 ) -> Result<impl IntoResponse, HttpResponseError> {
+    check_client_rate_limit(&st, &host, client_address, 1).await?;
     let export_path = parse_export_path(&req.path)?;
 
     // NOTE: We could coalesce authenticating and executing the query into one
@@ -9,8 +9,11 @@ use axum::{
     extract::{
         DefaultBodyLimit,
         FromRef,
+        MatchedPath,
         State,
     },
+    middleware::Next,
+    response::Response,
     routing::{
         get,
         post,
@@ -19,8 +22,15 @@ use axum::{
     Router,
 };
 use common::{
+    fastrace_helpers::{
+        get_sampled_span,
+        SPAN_KIND_PROPERTY,
+        SPAN_KIND_SERVER,
+    },
     http::{
         cli_cors,
+        ExtractRequestId,
+        ExtractResolvedHostname,
         CONVEX_CLIENT_HEADER,
     },
     knobs::{
@@ -31,6 +41,11 @@ use common::{
         MAX_PUSH_BYTES,
     },
 };
+use fastrace::{
+    collector::SpanContext,
+    future::FutureExt as _,
+    Span,
+};
 use http::{
     header::{
         ACCEPT,
@@ -45,6 +60,7 @@ use http::{
     Method,
     StatusCode,
 };
+use maplit::btreemap;
 use metrics::SERVER_VERSION_STR;
 use tower::ServiceBuilder;
 use tower_http::{
@@ -72,6 +88,7 @@ use crate::{
         delete_tables,
         get_indexes,
         get_source_code,
+        infer_schema,
         run_test_function,
         shapes2,
     },
@@ -133,8 +150,13 @@ use crate::{
         perform_import,
     },
     storage::{
+        storage_finish_resumable_upload,
         storage_get,
+        storage_get_image,
+        storage_resumable_upload_progress,
+        storage_start_resumable_upload,
         storage_upload,
+        storage_upload_resumable_part,
     },
     streaming_import::{
         add_primary_key_indexes,
@@ -147,6 +169,7 @@ use crate::{
         replace_tables,
     },
     subs::sync,
+    usage,
     LocalAppState,
     RouterState,
 };
@@ -162,6 +185,38 @@ where
     request
 }
 
+/// Traces requests without a `traceparent` header, sampled according to
+/// `REQUEST_TRACE_SAMPLE_CONFIG` or `--otlp-trace-sample-ratio`. Requests with
+/// one are already traced by `stats_middleware`, so they just get a child span
+/// here to carry the request ID.
+///
+/// This runs in front of every route, so it must never reject a request: a
+/// malformed `Convex-Request-Id` header is left for the handler to report.
+pub async fn trace_request(
+    matched_path: Option<MatchedPath>,
+    request_id: Option<ExtractRequestId>,
+    ExtractResolvedHostname(host): ExtractResolvedHostname,
+    req: axum::extract::Request,
+    next: Next,
+) -> Response {
+    let mut properties = btreemap! {
+        "http.method".to_string() => req.method().to_string(),
+    };
+    if let Some(ExtractRequestId(request_id)) = request_id {
+        properties.insert("request_id".to_string(), request_id.to_string());
+    }
+    let span = if SpanContext::current_local_parent().is_some() {
+        Span::enter_with_local_parent("handle_request").with_properties(|| properties)
+    } else {
+        let route = matched_path
+            .as_ref()
+            .map_or("unknown", |path| path.as_str());
+        get_sampled_span(&host.instance_name, route, &mut rand::rng(), properties)
+            .with_property(|| (SPAN_KIND_PROPERTY, SPAN_KIND_SERVER))
+    };
+    next.run(req).in_span(span).await
+}
+
 pub fn router(st: LocalAppState) -> Router {
     let browser_routes = Router::new()
         // Called by the browser (and optionally authenticated by a cookie or `Authorization`
@@ -176,6 +231,11 @@ pub fn router(st: LocalAppState) -> Router {
         .route("/update_environment_variables", post(update_environment_variables))
         // Canonical URL routes
         .route("/update_canonical_url", post(update_canonical_url))
+        // Push history routes
+        .route("/push_history", get(deploy_config2::list_push_history))
+        .route("/rollback_push", post(deploy_config2::rollback_push))
+        // Usage accounting routes
+        .route("/usage", get(usage::usage))
         // Administrative routes for the dashboard
         .layer(ServiceBuilder::new());
 
@@ -251,6 +311,7 @@ pub fn router(st: LocalAppState) -> Router {
         .layer(cors())
         .with_state(st)
         .merge(migrated)
+        .layer(axum::middleware::from_fn(trace_request))
 }
 
 pub fn public_api_routes() -> Router<RouterState> {
@@ -271,7 +332,17 @@ pub fn public_api_routes() -> Router<RouterState> {
 pub fn storage_api_routes() -> Router<RouterState> {
     Router::new()
         .route("/upload", post(storage_upload))
+        .route("/upload/resumable", post(storage_start_resumable_upload))
+        .route(
+            "/upload/resumable/:upload_id",
+            get(storage_resumable_upload_progress).put(storage_upload_resumable_part),
+        )
+        .route(
+            "/upload/resumable/:upload_id/finish",
+            post(storage_finish_resumable_upload),
+        )
         .route("/:storage_id", get(storage_get))
+        .route("/:storage_id/image", get(storage_get_image))
 }
 
 // IMPORTANT NOTE: Those routes are proxied by Usher. Any changes to the router,
@@ -349,6 +420,7 @@ where
 {
     Router::new()
         .route("/shapes2", get(shapes2))
+        .route("/infer_schema", get(infer_schema))
         .route("/get_indexes", get(get_indexes))
         .route("/delete_tables", post(delete_tables))
         .route("/delete_component", post(delete_component))
@@ -446,3 +518,30 @@ pub fn cors() -> CorsLayer {
         )
         .max_age(Duration::from_secs(86400))
 }
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use common::http::CONVEX_REQUEST_ID_HEADER;
+    use http::{
+        HeaderValue,
+        Request,
+        StatusCode,
+    };
+    use runtime::prod::ProdRuntime;
+
+    use crate::test_helpers::setup_backend_for_test;
+
+    /// Tracing a request mustn't reject it, whatever its request ID header.
+    #[convex_macro::prod_rt_test]
+    async fn test_trace_request_invalid_request_id(rt: ProdRuntime) -> anyhow::Result<()> {
+        let backend = setup_backend_for_test(rt).await?;
+        let req = Request::builder()
+            .uri("/instance_version")
+            .header(CONVEX_REQUEST_ID_HEADER, HeaderValue::from_bytes(b"\xff")?)
+            .body(Body::empty())?;
+        let response = backend.send(req).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        Ok(())
+    }
+}
@@ -43,12 +43,18 @@ use common::{
         ExtractResolvedHostname,
         HttpResponseError,
     },
+    knobs::STORAGE_MAX_INTERMEDIATE_PART_SIZE,
     sha256::DigestHeader,
 };
 use errors::ErrorMetadata;
 use file_storage::{
     FileRangeStream,
     FileStream,
+    ImageCrop,
+    ImageFit,
+    ImageOutputFormat,
+    ImageTransform,
+    ResumableUploadProgress,
 };
 use futures::StreamExt;
 use http::StatusCode;
@@ -136,6 +142,119 @@ pub async fn storage_upload(
     }))
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResumableUploadResponse {
+    upload_id: String,
+    offset: u64,
+    num_parts: usize,
+}
+
+impl From<ResumableUploadProgress> for ResumableUploadResponse {
+    fn from(progress: ResumableUploadProgress) -> Self {
+        Self {
+            upload_id: progress.upload_id,
+            offset: progress.offset,
+            num_parts: progress.num_parts,
+        }
+    }
+}
+
+/// Start a resumable upload. The token is the same one used for single
+/// request uploads; the returned `uploadId` authorizes the rest of the upload.
+#[debug_handler]
+pub async fn storage_start_resumable_upload(
+    State(st): State<RouterState>,
+    Query(QueryParams { token }): Query<QueryParams>,
+    content_type: Result<TypedHeader<ContentType>, TypedHeaderRejection>,
+    ExtractResolvedHostname(host): ExtractResolvedHostname,
+    ExtractRequestId(request_id): ExtractRequestId,
+) -> Result<impl IntoResponse, HttpResponseError> {
+    let component = st
+        .api
+        .check_store_file_authorization(
+            &host,
+            request_id.clone(),
+            &token,
+            STORE_FILE_AUTHORIZATION_VALIDITY,
+        )
+        .await?;
+    let content_type = map_header_err(content_type)?;
+    let progress = st
+        .api
+        .start_resumable_upload(&host, request_id, component, content_type)
+        .await?;
+    Ok(Json(ResumableUploadResponse::from(progress)))
+}
+
+#[debug_handler]
+pub async fn storage_resumable_upload_progress(
+    State(st): State<RouterState>,
+    Path(upload_id): Path<String>,
+    ExtractResolvedHostname(host): ExtractResolvedHostname,
+    ExtractRequestId(request_id): ExtractRequestId,
+) -> Result<impl IntoResponse, HttpResponseError> {
+    let progress = st
+        .api
+        .resumable_upload_progress(&host, request_id, upload_id)
+        .await?;
+    Ok(Json(ResumableUploadResponse::from(progress)))
+}
+
+#[derive(Deserialize)]
+pub struct UploadPartQueryParams {
+    offset: u64,
+}
+
+#[debug_handler]
+pub async fn storage_upload_resumable_part(
+    State(st): State<RouterState>,
+    Path(upload_id): Path<String>,
+    Query(UploadPartQueryParams { offset }): Query<UploadPartQueryParams>,
+    ExtractResolvedHostname(host): ExtractResolvedHostname,
+    ExtractRequestId(request_id): ExtractRequestId,
+    body: Body,
+) -> Result<impl IntoResponse, HttpResponseError> {
+    let part = axum::body::to_bytes(body, *STORAGE_MAX_INTERMEDIATE_PART_SIZE)
+        .await
+        .context(ErrorMetadata::bad_request(
+            "InvalidUploadPart",
+            format!(
+                "Failed to read upload part. Parts can be at most {} bytes.",
+                *STORAGE_MAX_INTERMEDIATE_PART_SIZE
+            ),
+        ))?;
+    let progress = st
+        .api
+        .upload_resumable_part(&host, request_id, upload_id, offset, part)
+        .await?;
+    Ok(Json(ResumableUploadResponse::from(progress)))
+}
+
+#[debug_handler]
+pub async fn storage_finish_resumable_upload(
+    State(st): State<RouterState>,
+    Path(upload_id): Path<String>,
+    sha256: Result<TypedHeader<DigestHeader>, TypedHeaderRejection>,
+    ExtractResolvedHostname(host): ExtractResolvedHostname,
+    ExtractRequestId(request_id): ExtractRequestId,
+) -> Result<impl IntoResponse, HttpResponseError> {
+    let sha256 = map_header_err(sha256)?.map(|dh| dh.0);
+    let storage_id = st
+        .api
+        .finish_resumable_upload(&host, request_id, upload_id, sha256)
+        .await?;
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Response {
+        storage_id: String,
+    }
+    Ok(Json(Response {
+        storage_id: storage_id.to_string(),
+    }))
+}
+
 #[derive(Deserialize)]
 pub struct GetQueryParams {
     component: Option<String>,
@@ -235,3 +354,87 @@ pub async fn storage_get(
     )
         .into_response())
 }
+
+#[derive(Deserialize)]
+pub struct GetImageQueryParams {
+    component: Option<String>,
+    w: Option<u32>,
+    h: Option<u32>,
+    fit: Option<String>,
+    format: Option<String>,
+    q: Option<u8>,
+    crop: Option<String>,
+}
+
+/// Serve a resized, cropped or re-encoded copy of a stored image. Transformed
+/// images are cached, so only the first request for a given set of parameters
+/// pays for the transform.
+#[debug_handler]
+pub async fn storage_get_image(
+    State(st): State<RouterState>,
+    Path(uuid): Path<String>,
+    Query(GetImageQueryParams {
+        component,
+        w,
+        h,
+        fit,
+        format,
+        q,
+        crop,
+    }): Query<GetImageQueryParams>,
+    ExtractResolvedHostname(host): ExtractResolvedHostname,
+    Host(original_host): Host,
+    ExtractRequestId(request_id): ExtractRequestId,
+) -> Result<Response, HttpResponseError> {
+    let storage_uuid = uuid.parse().context(ErrorMetadata::bad_request(
+        "InvalidStoragePath",
+        format!("Invalid storage path: \"{uuid}\". Please use `storage.getUrl()` to generate a valid URL to retrieve files. See https://docs.convex.dev/file-storage/serve-files for more details"),
+    ))?;
+    let file_storage_id = FileStorageId::LegacyStorageId(storage_uuid);
+    let component = ComponentId::deserialize_from_string(component.as_deref())?;
+    let origin = original_host.into();
+    let transform = ImageTransform {
+        width: w,
+        height: h,
+        fit: fit
+            .as_deref()
+            .map(str::parse::<ImageFit>)
+            .transpose()?
+            .unwrap_or_default(),
+        format: format
+            .as_deref()
+            .map(str::parse::<ImageOutputFormat>)
+            .transpose()?,
+        quality: q,
+        crop: crop.as_deref().map(str::parse::<ImageCrop>).transpose()?,
+    };
+
+    let FileStream {
+        sha256,
+        content_type,
+        content_length,
+        stream,
+    } = st
+        .api
+        .get_transformed_image(
+            &host,
+            request_id,
+            origin,
+            component,
+            file_storage_id,
+            transform,
+        )
+        .await?;
+    Ok((
+        TypedHeader(DigestHeader(sha256)),
+        content_type.map(TypedHeader),
+        TypedHeader(content_length),
+        TypedHeader(
+            CacheControl::new()
+                .with_private()
+                .with_max_age(MAX_CACHE_AGE),
+        ),
+        Body::from_stream(stream),
+    )
+        .into_response())
+}
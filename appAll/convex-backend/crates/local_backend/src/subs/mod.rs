@@ -1,6 +1,9 @@
-use std::time::{
-    Duration,
-    Instant,
+use std::{
+    net::IpAddr,
+    time::{
+        Duration,
+        Instant,
+    },
 };
 
 use ::errors::{
@@ -26,6 +29,7 @@ use common::{
         report_error_sync,
     },
     http::{
+        ExtractClientAddress,
         ExtractClientVersion,
         ExtractResolvedHostname,
         HttpResponseError,
@@ -337,18 +341,25 @@ async fn run_sync_socket(
     log_websocket_closed();
 }
 
-fn new_sync_worker_config(client_version: ClientVersion) -> anyhow::Result<SyncWorkerConfig> {
-    Ok(SyncWorkerConfig { client_version })
+fn new_sync_worker_config(
+    client_version: ClientVersion,
+    client_address: Option<IpAddr>,
+) -> anyhow::Result<SyncWorkerConfig> {
+    Ok(SyncWorkerConfig {
+        client_version,
+        client_address,
+    })
 }
 
 pub async fn sync_handler(
     st: RouterState,
     host: ResolvedHostname,
     client_version: ClientVersion,
+    client_address: Option<IpAddr>,
     ws: WebSocketUpgrade,
     on_connect: Box<dyn FnOnce(SessionId) + Send>,
 ) -> Result<impl IntoResponse, HttpResponseError> {
-    let config = new_sync_worker_config(client_version)?;
+    let config = new_sync_worker_config(client_version, client_address)?;
     // Make a copy of the Sentry scope, which contains the request metadata.
     let sentry_scope = sentry::configure_scope(move |s| s.clone());
 
@@ -367,9 +378,18 @@ pub async fn sync(
     State(st): State<RouterState>,
     ExtractResolvedHostname(host): ExtractResolvedHostname,
     ExtractClientVersion(client_version): ExtractClientVersion,
+    ExtractClientAddress(client_address): ExtractClientAddress,
     ws: WebSocketUpgrade,
 ) -> Result<impl IntoResponse, HttpResponseError> {
-    sync_handler(st, host, client_version, ws, Box::new(|_session_id| ())).await
+    sync_handler(
+        st,
+        host,
+        client_version,
+        client_address,
+        ws,
+        Box::new(|_session_id| ()),
+    )
+    .await
 }
 
 #[cfg(test)]
@@ -1,4 +1,5 @@
 use std::{
+    net::SocketAddr,
     sync::Arc,
     time::Duration,
 };
@@ -11,6 +12,7 @@ use application::{
     },
     RedactedQueryReturn,
 };
+use axum::response::Response;
 use axum_extra::headers::Authorization;
 use common::{
     components::{
@@ -46,6 +48,10 @@ use sync_types::{
     headers::ConvexAdminAuthorization,
     CanonicalizedUdfPath,
 };
+use tokio::{
+    net::TcpStream,
+    sync::oneshot,
+};
 use tower::ServiceExt;
 
 use crate::{
@@ -96,6 +102,30 @@ pub async fn setup_backend_for_test(runtime: ProdRuntime) -> anyhow::Result<Test
 }
 
 impl TestLocalBackend {
+    /// Serve the backend on a free local port, for tests that need a real
+    /// connection (e.g. WebSockets). The server stops when the returned sender
+    /// is dropped.
+    pub async fn serve(&self) -> anyhow::Result<(SocketAddr, oneshot::Sender<()>)> {
+        let app = ConvexHttpService::new_for_test(self.app.router());
+        let port = portpicker::pick_unused_port().expect("No ports free");
+        let addr = format!("127.0.0.1:{port}").parse()?;
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+        tokio::spawn(app.serve(addr, async move {
+            let _ = shutdown_rx.await;
+        }));
+        // Can take a moment after the server spawn to connect to it.
+        while TcpStream::connect(addr).await.is_err() {
+            tokio::task::yield_now().await;
+        }
+        Ok((addr, shutdown_tx))
+    }
+
+    /// Send `req` and return the response, whose body may still be streaming.
+    pub async fn send(&self, req: Request<axum::body::Body>) -> anyhow::Result<Response> {
+        tracing::info!("Sending req {req:?}");
+        Ok(self.app.router().clone().oneshot(req).await?)
+    }
+
     pub async fn expect_success<T: DeserializeOwned>(
         &self,
         req: Request<axum::body::Body>,
@@ -0,0 +1,604 @@
+//! Usage accounting for self-hosted deployments.
+//!
+//! [`LocalUsageLog`] receives the usage events the backend emits as functions
+//! run and adds them up into hourly rollups by function, table and component.
+//! The rollups are kept in a JSON file per hour under the storage directory,
+//! and `GET /api/usage` reports them over a time range, e.g. to split the cost
+//! of a shared deployment between the teams using it.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    io,
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use axum::{
+    extract::State,
+    response::IntoResponse,
+};
+use common::{
+    errors::report_error,
+    http::{
+        extract::{
+            Json,
+            Query,
+        },
+        HttpResponseError,
+    },
+    knobs::{
+        LOCAL_USAGE_FLUSH_INTERVAL,
+        LOCAL_USAGE_RETENTION,
+    },
+    runtime::{
+        Runtime,
+        UnixTimestamp,
+    },
+    types::ModuleEnvironment,
+};
+use errors::ErrorMetadata;
+use events::usage::{
+    UsageEvent,
+    UsageEventLogger,
+};
+use parking_lot::Mutex;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use crate::{
+    admin::must_be_admin,
+    authentication::ExtractIdentity,
+    LocalAppState,
+};
+
+const HOUR_SECS: u64 = 60 * 60;
+
+/// Usage added up over some set of events. Which fields apply depends on what
+/// the usage is grouped by: tables only have database and vector bandwidth.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct UsageTotals {
+    pub calls: u64,
+    /// Action memory in megabytes multiplied by execution time in
+    /// milliseconds.
+    pub v8_action_compute_mb_ms: u64,
+    pub node_action_compute_mb_ms: u64,
+    pub database_ingress_bytes: u64,
+    pub database_egress_bytes: u64,
+    pub database_egress_rows: u64,
+    pub vector_ingress_bytes: u64,
+    pub vector_egress_bytes: u64,
+    pub storage_calls: u64,
+    pub storage_ingress_bytes: u64,
+    pub storage_egress_bytes: u64,
+}
+
+impl UsageTotals {
+    fn add(&mut self, other: &UsageTotals) {
+        self.calls += other.calls;
+        self.v8_action_compute_mb_ms += other.v8_action_compute_mb_ms;
+        self.node_action_compute_mb_ms += other.node_action_compute_mb_ms;
+        self.database_ingress_bytes += other.database_ingress_bytes;
+        self.database_egress_bytes += other.database_egress_bytes;
+        self.database_egress_rows += other.database_egress_rows;
+        self.vector_ingress_bytes += other.vector_ingress_bytes;
+        self.vector_egress_bytes += other.vector_egress_bytes;
+        self.storage_calls += other.storage_calls;
+        self.storage_ingress_bytes += other.storage_ingress_bytes;
+        self.storage_egress_bytes += other.storage_egress_bytes;
+    }
+}
+
+/// The usage in one hour, as stored on disk. Component paths are empty for
+/// the root component.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct HourlyUsage {
+    /// Keyed by component path, then function path.
+    functions: BTreeMap<String, BTreeMap<String, UsageTotals>>,
+    /// Keyed by component path, then table name.
+    tables: BTreeMap<String, BTreeMap<String, UsageTotals>>,
+    /// Everything in each component, including storage used outside of
+    /// functions, e.g. by snapshot imports and exports.
+    components: BTreeMap<String, UsageTotals>,
+}
+
+impl HourlyUsage {
+    fn record(&mut self, event: UsageEvent) {
+        let mut usage = UsageTotals::default();
+        let (component_path, function, table) = match event {
+            UsageEvent::FunctionCall { fields } => {
+                // System functions and CLI calls aren't counted as calls.
+                if !fields.is_tracked {
+                    return;
+                }
+                usage.calls = 1;
+                let compute = fields.duration_millis * fields.memory_megabytes;
+                if fields.environment == ModuleEnvironment::Isolate.to_string() {
+                    usage.v8_action_compute_mb_ms = compute;
+                } else if fields.environment == ModuleEnvironment::Node.to_string() {
+                    usage.node_action_compute_mb_ms = compute;
+                }
+                (fields.component_path, Some(fields.udf_id), None)
+            },
+            UsageEvent::FunctionStorageCalls {
+                component_path,
+                udf_id,
+                count,
+                ..
+            } => {
+                usage.storage_calls = count;
+                (component_path, Some(udf_id), None)
+            },
+            UsageEvent::FunctionStorageBandwidth {
+                component_path,
+                udf_id,
+                ingress,
+                egress,
+                ..
+            } => {
+                usage.storage_ingress_bytes = ingress;
+                usage.storage_egress_bytes = egress;
+                (component_path, Some(udf_id), None)
+            },
+            UsageEvent::StorageCall { component_path, .. } => {
+                usage.storage_calls = 1;
+                (component_path, None, None)
+            },
+            UsageEvent::StorageBandwidth {
+                component_path,
+                ingress,
+                egress,
+                ..
+            } => {
+                usage.storage_ingress_bytes = ingress;
+                usage.storage_egress_bytes = egress;
+                (component_path, None, None)
+            },
+            UsageEvent::DatabaseBandwidth {
+                component_path,
+                udf_id,
+                table_name,
+                ingress,
+                egress,
+                egress_rows,
+                ..
+            } => {
+                usage.database_ingress_bytes = ingress;
+                usage.database_egress_bytes = egress;
+                usage.database_egress_rows = egress_rows;
+                (component_path, Some(udf_id), Some(table_name))
+            },
+            UsageEvent::VectorBandwidth {
+                component_path,
+                udf_id,
+                table_name,
+                ingress,
+                egress,
+                ..
+            } => {
+                usage.vector_ingress_bytes = ingress;
+                usage.vector_egress_bytes = egress;
+                (component_path, Some(udf_id), Some(table_name))
+            },
+            // These are point-in-time sizes rather than usage over time.
+            UsageEvent::CurrentVectorStorage { .. }
+            | UsageEvent::CurrentDatabaseStorage { .. }
+            | UsageEvent::CurrentFileStorage { .. }
+            | UsageEvent::CurrentDocumentCounts { .. } => return,
+        };
+        let component_path = component_path.unwrap_or_default();
+        if let Some(function) = function {
+            self.functions
+                .entry(component_path.clone())
+                .or_default()
+                .entry(function)
+                .or_default()
+                .add(&usage);
+        }
+        if let Some(table) = table {
+            self.tables
+                .entry(component_path.clone())
+                .or_default()
+                .entry(table)
+                .or_default()
+                .add(&usage);
+        }
+        self.components
+            .entry(component_path)
+            .or_default()
+            .add(&usage);
+    }
+
+    fn merge(&mut self, other: &HourlyUsage) {
+        for (grouped, other_grouped) in [
+            (&mut self.functions, &other.functions),
+            (&mut self.tables, &other.tables),
+        ] {
+            for (component_path, usage_by_name) in other_grouped {
+                let grouped = grouped.entry(component_path.clone()).or_default();
+                for (name, usage) in usage_by_name {
+                    grouped.entry(name.clone()).or_default().add(usage);
+                }
+            }
+        }
+        for (component_path, usage) in &other.components {
+            self.components
+                .entry(component_path.clone())
+                .or_default()
+                .add(usage);
+        }
+    }
+
+    fn rows(self, group_by: UsageGroupBy) -> Vec<UsageRow> {
+        let grouped = match group_by {
+            UsageGroupBy::Function => self.functions,
+            UsageGroupBy::Table => self.tables,
+            UsageGroupBy::Component => {
+                return self
+                    .components
+                    .into_iter()
+                    .map(|(component_path, usage)| UsageRow {
+                        component_path,
+                        name: None,
+                        usage,
+                    })
+                    .collect();
+            },
+        };
+        grouped
+            .into_iter()
+            .flat_map(|(component_path, usage_by_name)| {
+                usage_by_name
+                    .into_iter()
+                    .map(move |(name, usage)| UsageRow {
+                        component_path: component_path.clone(),
+                        name: Some(name),
+                        usage,
+                    })
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UsageGroupBy {
+    Function,
+    Table,
+    Component,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageRow {
+    pub component_path: String,
+    /// The function path or table name, unless grouping by component.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub usage: UsageTotals,
+}
+
+/// Adds up usage events into hourly rollups and writes them to `dir`.
+#[derive(Clone)]
+pub struct LocalUsageLog<RT: Runtime> {
+    runtime: RT,
+    dir: PathBuf,
+    /// Usage recorded since the last flush, keyed by the start of its hour in
+    /// seconds since the epoch.
+    pending: Arc<Mutex<BTreeMap<u64, HourlyUsage>>>,
+    /// Held while reading or writing rollup files, so reports don't see usage
+    /// that's been taken from `pending` but not written yet.
+    files: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl<RT: Runtime> fmt::Debug for LocalUsageLog<RT> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LocalUsageLog")
+            .field("dir", &self.dir)
+            .finish()
+    }
+}
+
+impl<RT: Runtime> LocalUsageLog<RT> {
+    pub fn new(runtime: RT, dir: PathBuf) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self {
+            runtime,
+            dir,
+            pending: Arc::new(Mutex::new(BTreeMap::new())),
+            files: Arc::new(tokio::sync::Mutex::new(())),
+        })
+    }
+
+    /// Flush rollups to disk every `LOCAL_USAGE_FLUSH_INTERVAL`.
+    pub async fn go(self) {
+        loop {
+            self.runtime.wait(*LOCAL_USAGE_FLUSH_INTERVAL).await;
+            if let Err(mut e) = self.flush().await {
+                report_error(&mut e).await;
+            }
+        }
+    }
+
+    /// Add the usage recorded since the last flush to the rollup files, and
+    /// delete rollups older than `LOCAL_USAGE_RETENTION`.
+    pub async fn flush(&self) -> anyhow::Result<()> {
+        let _files = self.files.lock().await;
+        let pending = std::mem::take(&mut *self.pending.lock());
+        let mut result = Ok(());
+        for (hour, usage) in pending {
+            let flushed: anyhow::Result<()> = try {
+                let mut rollup = self.read_hour(hour).await?.unwrap_or_default();
+                rollup.merge(&usage);
+                self.write_hour(hour, &rollup).await?;
+            };
+            if let Err(e) = flushed {
+                // Keep the usage to try again next time.
+                self.pending.lock().entry(hour).or_default().merge(&usage);
+                result = Err(e);
+            }
+        }
+        let now = self.runtime.unix_timestamp().as_secs();
+        let expired_before = now.saturating_sub(LOCAL_USAGE_RETENTION.as_secs());
+        for hour in self.stored_hours().await? {
+            if hour + HOUR_SECS <= expired_before {
+                tokio::fs::remove_file(self.hour_path(hour)).await?;
+            }
+        }
+        result
+    }
+
+    /// Usage in the hours overlapping `[start, end]`, grouped by `group_by`.
+    pub async fn report(
+        &self,
+        start: UnixTimestamp,
+        end: UnixTimestamp,
+        group_by: UsageGroupBy,
+    ) -> anyhow::Result<Vec<UsageRow>> {
+        let (first_hour, last_hour) = (hour_start(start), hour_start(end));
+        let in_range = |hour: u64| hour >= first_hour && hour <= last_hour;
+        let _files = self.files.lock().await;
+        let mut total = HourlyUsage::default();
+        for hour in self.stored_hours().await? {
+            if in_range(hour)
+                && let Some(rollup) = self.read_hour(hour).await?
+            {
+                total.merge(&rollup);
+            }
+        }
+        for (hour, usage) in self.pending.lock().iter() {
+            if in_range(*hour) {
+                total.merge(usage);
+            }
+        }
+        Ok(total.rows(group_by))
+    }
+
+    fn hour_path(&self, hour: u64) -> PathBuf {
+        self.dir.join(format!("{hour}.json"))
+    }
+
+    async fn stored_hours(&self) -> anyhow::Result<Vec<u64>> {
+        let mut hours = vec![];
+        let mut entries = tokio::fs::read_dir(&self.dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let file_name = entry.file_name();
+            if let Some(hour) = file_name
+                .to_str()
+                .and_then(|name| name.strip_suffix(".json"))
+                .and_then(|hour| hour.parse().ok())
+            {
+                hours.push(hour);
+            }
+        }
+        Ok(hours)
+    }
+
+    async fn read_hour(&self, hour: u64) -> anyhow::Result<Option<HourlyUsage>> {
+        match tokio::fs::read(self.hour_path(hour)).await {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn write_hour(&self, hour: u64, rollup: &HourlyUsage) -> anyhow::Result<()> {
+        // Write to a temporary file first so a crash can't leave a partial
+        // rollup behind.
+        let tmp_path = self.dir.join(format!("{hour}.json.tmp"));
+        tokio::fs::write(&tmp_path, serde_json::to_vec(rollup)?).await?;
+        tokio::fs::rename(&tmp_path, self.hour_path(hour)).await?;
+        Ok(())
+    }
+}
+
+fn hour_start(ts: UnixTimestamp) -> u64 {
+    ts.as_secs() / HOUR_SECS * HOUR_SECS
+}
+
+#[async_trait]
+impl<RT: Runtime> UsageEventLogger for LocalUsageLog<RT> {
+    fn record(&self, events: Vec<UsageEvent>) {
+        let hour = hour_start(self.runtime.unix_timestamp());
+        let mut pending = self.pending.lock();
+        let usage = pending.entry(hour).or_default();
+        for event in events {
+            usage.record(event);
+        }
+    }
+
+    async fn record_async(&self, events: Vec<UsageEvent>) {
+        self.record(events)
+    }
+
+    async fn shutdown(&self) -> anyhow::Result<()> {
+        self.flush().await
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageQueryArgs {
+    /// Start of the range in milliseconds since the epoch.
+    start_ms: u64,
+    /// End of the range in milliseconds since the epoch. Defaults to now.
+    end_ms: Option<u64>,
+    group_by: UsageGroupBy,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageResponse {
+    start_ms: u64,
+    end_ms: u64,
+    group_by: UsageGroupBy,
+    usage: Vec<UsageRow>,
+}
+
+/// Report usage by function, table or component over a time range. Usage is
+/// kept by the hour, so the range is widened to whole hours.
+pub async fn usage(
+    State(st): State<LocalAppState>,
+    ExtractIdentity(identity): ExtractIdentity,
+    Query(UsageQueryArgs {
+        start_ms,
+        end_ms,
+        group_by,
+    }): Query<UsageQueryArgs>,
+) -> Result<impl IntoResponse, HttpResponseError> {
+    must_be_admin(&identity)?;
+    let end_ms = match end_ms {
+        Some(end_ms) => end_ms,
+        None => st.usage_log.runtime.unix_timestamp().as_ms_since_epoch()?,
+    };
+    if start_ms > end_ms {
+        return Err(anyhow::anyhow!(ErrorMetadata::bad_request(
+            "InvalidUsageRange",
+            "`startMs` must not be after `endMs`",
+        ))
+        .into());
+    }
+    let start = UnixTimestamp::from_millis(start_ms);
+    let end = UnixTimestamp::from_millis(end_ms);
+    let start_ms = Duration::from_secs(hour_start(start)).as_millis() as u64;
+    let end_ms = Duration::from_secs(hour_start(end) + HOUR_SECS).as_millis() as u64;
+    let usage = st.usage_log.report(start, end, group_by).await?;
+    Ok(Json(UsageResponse {
+        start_ms,
+        end_ms,
+        group_by,
+        usage,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use common::runtime::Runtime;
+    use events::usage::{
+        FunctionCallUsageFields,
+        UsageEvent,
+        UsageEventLogger,
+    };
+    use runtime::testing::TestRuntime;
+
+    use super::{
+        LocalUsageLog,
+        UsageGroupBy,
+        UsageRow,
+        UsageTotals,
+    };
+
+    fn function_call(component_path: Option<&str>, udf_id: &str) -> UsageEvent {
+        UsageEvent::FunctionCall {
+            fields: FunctionCallUsageFields {
+                id: "id".to_string(),
+                request_id: "request_id".to_string(),
+                status: "success".to_string(),
+                component_path: component_path.map(str::to_string),
+                udf_id: udf_id.to_string(),
+                udf_id_type: "function".to_string(),
+                tag: "action".to_string(),
+                memory_megabytes: 512,
+                duration_millis: 10,
+                environment: "node".to_string(),
+                is_tracked: true,
+                response_sha256: None,
+                is_occ: false,
+                occ_table_name: None,
+                occ_document_id: None,
+                occ_write_source: None,
+                occ_retry_count: None,
+            },
+        }
+    }
+
+    fn database_bandwidth(udf_id: &str, table_name: &str, egress: u64) -> UsageEvent {
+        UsageEvent::DatabaseBandwidth {
+            id: "id".to_string(),
+            request_id: "request_id".to_string(),
+            component_path: None,
+            udf_id: udf_id.to_string(),
+            table_name: table_name.to_string(),
+            ingress: 0,
+            egress,
+            egress_rows: 1,
+        }
+    }
+
+    #[convex_macro::test_runtime]
+    async fn test_usage_rollups(rt: TestRuntime) -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let usage_log = LocalUsageLog::new(rt.clone(), dir.path().to_owned())?;
+        let start = rt.unix_timestamp();
+        usage_log.record(vec![
+            function_call(None, "messages:list"),
+            database_bandwidth("messages:list", "messages", 100),
+        ]);
+        usage_log.flush().await?;
+        // Usage from after a restart is added to what's on disk.
+        let usage_log = LocalUsageLog::new(rt.clone(), dir.path().to_owned())?;
+        usage_log.record(vec![
+            function_call(Some("billing"), "invoices:send"),
+            database_bandwidth("messages:list", "messages", 50),
+        ]);
+        let end = rt.unix_timestamp();
+
+        let by_table = usage_log.report(start, end, UsageGroupBy::Table).await?;
+        assert_eq!(
+            by_table,
+            vec![UsageRow {
+                component_path: "".to_string(),
+                name: Some("messages".to_string()),
+                usage: UsageTotals {
+                    database_egress_bytes: 150,
+                    database_egress_rows: 2,
+                    ..Default::default()
+                },
+            }]
+        );
+        let by_component = usage_log
+            .report(start, end, UsageGroupBy::Component)
+            .await?;
+        assert_eq!(by_component.len(), 2);
+        assert_eq!(by_component[1].component_path, "billing");
+        assert_eq!(by_component[1].usage.node_action_compute_mb_ms, 5120);
+
+        // Earlier hours aren't included.
+        rt.advance_time(Duration::from_secs(2 * 60 * 60)).await;
+        let later = rt.unix_timestamp();
+        assert!(usage_log
+            .report(later, later, UsageGroupBy::Function)
+            .await?
+            .is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,458 @@
+//! Counters and histograms that carry exemplars: labels such as a trace ID
+//! from one of the events counted, so a dashboard can jump from a latency
+//! spike to a request that caused it.
+//!
+//! The `prometheus` crate can't attach exemplars, so these are kept here and
+//! written out alongside
+//! [`CONVEX_METRICS_REGISTRY`](crate::CONVEX_METRICS_REGISTRY)
+//! by [`encode_metrics`](crate::encode_metrics). Exemplars are only part of
+//! the OpenMetrics format; the plain Prometheus text format leaves them out.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        Arc,
+        LazyLock,
+    },
+    time::{
+        SystemTime,
+        UNIX_EPOCH,
+    },
+};
+
+use parking_lot::{
+    Mutex,
+    RwLock,
+};
+
+use crate::{
+    labels::Labels,
+    log_invalid_metric,
+    metrics::{
+        CONVEX_METRICS_CONST_LABELS,
+        SERVICE_NAME,
+    },
+    MetricHelp,
+    MetricName,
+};
+
+/// All the exemplar metrics that have been registered, in registration order.
+static EXEMPLAR_METRICS: LazyLock<RwLock<Vec<Arc<ExemplarMetric>>>> =
+    LazyLock::new(Default::default);
+
+/// Labels identifying one of the events a metric counted.
+#[derive(Clone, Debug)]
+pub struct Exemplar {
+    labels: Vec<(&'static str, String)>,
+    timestamp: SystemTime,
+}
+
+impl Exemplar {
+    pub fn new(labels: Vec<(&'static str, String)>, timestamp: SystemTime) -> Self {
+        Self { labels, timestamp }
+    }
+}
+
+/// A counter with labels whose series keep the latest exemplar.
+#[derive(Clone)]
+pub struct ExemplarCounterVec(Arc<ExemplarMetric>);
+
+impl ExemplarCounterVec {
+    pub fn register(
+        name: MetricName,
+        help: MetricHelp,
+        label_names: &'static [&'static str],
+    ) -> Self {
+        assert!(
+            name.ends_with("_total"),
+            "Exemplar counters must end in `_total`"
+        );
+        Self(ExemplarMetric::register(name, help, label_names, None))
+    }
+}
+
+/// A histogram with labels whose series keep the latest exemplar in each
+/// bucket.
+#[derive(Clone)]
+pub struct ExemplarHistogramVec(Arc<ExemplarMetric>);
+
+impl ExemplarHistogramVec {
+    /// `buckets` are the upper bounds of the buckets, in increasing order. A
+    /// `+Inf` bucket is always added.
+    pub fn register(
+        name: MetricName,
+        help: MetricHelp,
+        label_names: &'static [&'static str],
+        buckets: &'static [f64],
+    ) -> Self {
+        assert!(
+            buckets.is_sorted_by(|a, b| a < b),
+            "Histogram buckets must be increasing"
+        );
+        Self(ExemplarMetric::register(
+            name,
+            help,
+            label_names,
+            Some(buckets),
+        ))
+    }
+}
+
+pub fn log_counter_with_exemplar(
+    counter: &ExemplarCounterVec,
+    increment: u64,
+    labels: Labels<'_>,
+    exemplar: Option<Exemplar>,
+) {
+    counter.0.observe(increment as f64, labels, exemplar);
+}
+
+pub fn log_distribution_with_exemplar(
+    histogram: &ExemplarHistogramVec,
+    value: f64,
+    labels: Labels<'_>,
+    exemplar: Option<Exemplar>,
+) {
+    histogram.0.observe(value, labels, exemplar);
+}
+
+struct ExemplarMetric {
+    name: String,
+    help: MetricHelp,
+    label_names: &'static [&'static str],
+    /// `None` for counters.
+    buckets: Option<&'static [f64]>,
+    /// Labels added to every series, sorted by name.
+    const_labels: Vec<(String, String)>,
+    series: Mutex<BTreeMap<Vec<String>, Series>>,
+}
+
+#[derive(Default)]
+struct Series {
+    count: u64,
+    sum: f64,
+    /// Non-cumulative counts for each bucket, then `+Inf`.
+    bucket_counts: Vec<u64>,
+    /// The latest exemplar for each bucket, then `+Inf`. Counters only have
+    /// one.
+    exemplars: Vec<Option<(f64, Exemplar)>>,
+}
+
+impl ExemplarMetric {
+    fn register(
+        name: MetricName,
+        help: MetricHelp,
+        label_names: &'static [&'static str],
+        buckets: Option<&'static [f64]>,
+    ) -> Arc<Self> {
+        let mut const_labels: Vec<_> = CONVEX_METRICS_CONST_LABELS
+            .iter()
+            .flatten()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        const_labels.sort();
+        let metric = Arc::new(Self::new(
+            format!("{}_{}", *SERVICE_NAME, &*name),
+            help,
+            label_names,
+            buckets,
+            const_labels,
+        ));
+        EXEMPLAR_METRICS.write().push(metric.clone());
+        metric
+    }
+
+    fn new(
+        name: String,
+        help: MetricHelp,
+        label_names: &'static [&'static str],
+        buckets: Option<&'static [f64]>,
+        const_labels: Vec<(String, String)>,
+    ) -> Self {
+        Self {
+            name,
+            help,
+            label_names,
+            buckets,
+            const_labels,
+            series: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    fn observe(&self, value: f64, labels: Labels<'_>, exemplar: Option<Exemplar>) {
+        let Some(label_values) = self
+            .label_names
+            .iter()
+            .map(|name| {
+                labels
+                    .iter()
+                    .find(|label| label.key == *name)
+                    .map(|label| label.value.to_string())
+            })
+            .collect::<Option<Vec<_>>>()
+            .filter(|_| labels.len() == self.label_names.len())
+        else {
+            log_invalid_metric(
+                self.name.clone(),
+                prometheus::Error::InconsistentCardinality {
+                    expect: self.label_names.len(),
+                    got: labels.len(),
+                },
+            );
+            return;
+        };
+        let num_buckets = self.buckets.map_or(1, |buckets| buckets.len() + 1);
+        let mut series = self.series.lock();
+        let series = series.entry(label_values).or_insert_with(|| Series {
+            bucket_counts: vec![0; num_buckets],
+            exemplars: vec![None; num_buckets],
+            ..Default::default()
+        });
+        series.count += 1;
+        series.sum += value;
+        let bucket = match self.buckets {
+            Some(buckets) => buckets.partition_point(|upper_bound| *upper_bound < value),
+            None => 0,
+        };
+        series.bucket_counts[bucket] += 1;
+        if let Some(exemplar) = exemplar {
+            series.exemplars[bucket] = Some((value, exemplar));
+        }
+    }
+
+    fn encode(&self, out: &mut String, openmetrics: bool) -> std::fmt::Result {
+        let series = self.series.lock();
+        if series.is_empty() {
+            return Ok(());
+        }
+        let help = escape_help(&self.help);
+        match self.buckets {
+            None => {
+                let family = if openmetrics {
+                    self.name.strip_suffix("_total").unwrap_or(&self.name)
+                } else {
+                    &self.name
+                };
+                writeln!(out, "# HELP {family} {help}")?;
+                writeln!(out, "# TYPE {family} counter")?;
+                for (label_values, series) in series.iter() {
+                    let labels = self.labels(label_values, None);
+                    write!(out, "{}{labels} {}", self.name, format_float(series.sum))?;
+                    if openmetrics {
+                        write_exemplar(out, &series.exemplars[0])?;
+                    }
+                    writeln!(out)?;
+                }
+            },
+            Some(buckets) => {
+                let name = &self.name;
+                writeln!(out, "# HELP {name} {help}")?;
+                writeln!(out, "# TYPE {name} histogram")?;
+                for (label_values, series) in series.iter() {
+                    let mut cumulative_count = 0;
+                    for (i, count) in series.bucket_counts.iter().enumerate() {
+                        cumulative_count += count;
+                        let le = buckets
+                            .get(i)
+                            .map_or_else(|| "+Inf".to_string(), |b| format_float(*b));
+                        let labels = self.labels(label_values, Some(&le));
+                        write!(out, "{name}_bucket{labels} {cumulative_count}")?;
+                        if openmetrics {
+                            write_exemplar(out, &series.exemplars[i])?;
+                        }
+                        writeln!(out)?;
+                    }
+                    let labels = self.labels(label_values, None);
+                    writeln!(out, "{name}_sum{labels} {}", format_float(series.sum))?;
+                    writeln!(out, "{name}_count{labels} {}", series.count)?;
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn labels(&self, label_values: &[String], le: Option<&str>) -> String {
+        let labels = self
+            .const_labels
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .chain(
+                self.label_names
+                    .iter()
+                    .copied()
+                    .zip(label_values.iter().map(String::as_str)),
+            )
+            .chain(le.map(|le| ("le", le)));
+        format_labels(labels)
+    }
+}
+
+/// Write out the exemplar metrics that have been recorded at least once.
+pub(crate) fn encode_exemplar_metrics(out: &mut String, openmetrics: bool) -> std::fmt::Result {
+    for metric in EXEMPLAR_METRICS.read().iter() {
+        metric.encode(out, openmetrics)?;
+    }
+    Ok(())
+}
+
+fn write_exemplar(out: &mut String, exemplar: &Option<(f64, Exemplar)>) -> std::fmt::Result {
+    let Some((value, exemplar)) = exemplar else {
+        return Ok(());
+    };
+    let labels = format_labels(
+        exemplar
+            .labels
+            .iter()
+            .map(|(name, value)| (*name, value.as_str())),
+    );
+    let timestamp = exemplar
+        .timestamp
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64();
+    write!(out, " # {labels} {} {timestamp:.3}", format_float(*value))
+}
+
+pub(crate) fn format_labels<'a>(labels: impl Iterator<Item = (&'a str, &'a str)>) -> String {
+    let labels: Vec<_> = labels
+        .map(|(name, value)| format!("{name}=\"{}\"", escape_label_value(value)))
+        .collect();
+    if labels.is_empty() {
+        return String::new();
+    }
+    format!("{{{}}}", labels.join(","))
+}
+
+pub(crate) fn format_float(value: f64) -> String {
+    if value == f64::INFINITY {
+        "+Inf".to_string()
+    } else if value == f64::NEG_INFINITY {
+        "-Inf".to_string()
+    } else if value.is_nan() {
+        "NaN".to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+pub(crate) fn escape_help(help: &str) -> String {
+    help.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{
+        Duration,
+        UNIX_EPOCH,
+    };
+
+    use super::{
+        Exemplar,
+        ExemplarMetric,
+    };
+    use crate::{
+        MetricHelp,
+        MetricLabel,
+    };
+
+    fn const_labels() -> Vec<(String, String)> {
+        vec![("instance_name".to_string(), "carnitas".to_string())]
+    }
+
+    fn trace(trace_id: &str, secs: f64) -> Option<Exemplar> {
+        Some(Exemplar::new(
+            vec![("trace_id", trace_id.to_string())],
+            UNIX_EPOCH + Duration::from_secs_f64(secs),
+        ))
+    }
+
+    fn encode(metric: &ExemplarMetric, openmetrics: bool) -> String {
+        let mut out = String::new();
+        metric.encode(&mut out, openmetrics).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_encode_counter() {
+        let metric = ExemplarMetric::new(
+            "test_requests_total".to_string(),
+            MetricHelp::new("Requests served,\nby status"),
+            &["status"],
+            None,
+            const_labels(),
+        );
+        assert_eq!(encode(&metric, true), "");
+
+        metric.observe(1., vec![MetricLabel::new("status", "ok")], None);
+        metric.observe(
+            2.,
+            vec![MetricLabel::new("status", "ok")],
+            trace("abc", 1.5),
+        );
+        metric.observe(1., vec![MetricLabel::new("status", "a\"b\\c\n")], None);
+
+        assert_eq!(
+            encode(&metric, true),
+            r#"# HELP test_requests Requests served,\nby status
+# TYPE test_requests counter
+test_requests_total{instance_name="carnitas",status="a\"b\\c\n"} 1
+test_requests_total{instance_name="carnitas",status="ok"} 3 # {trace_id="abc"} 2 1.500
+"#
+        );
+        assert_eq!(
+            encode(&metric, false),
+            r#"# HELP test_requests_total Requests served,\nby status
+# TYPE test_requests_total counter
+test_requests_total{instance_name="carnitas",status="a\"b\\c\n"} 1
+test_requests_total{instance_name="carnitas",status="ok"} 3
+"#
+        );
+    }
+
+    #[test]
+    fn test_encode_histogram() {
+        let metric = ExemplarMetric::new(
+            "test_latency_seconds".to_string(),
+            MetricHelp::new("Request latency"),
+            &["path"],
+            Some(&[0.5, 1.]),
+            const_labels(),
+        );
+        let labels = || vec![MetricLabel::new("path", "/api")];
+        metric.observe(0.25, labels(), trace("t1", 2.));
+        metric.observe(0.75, labels(), None);
+        metric.observe(1., labels(), None);
+        metric.observe(4., labels(), trace("t2", 3.25));
+
+        assert_eq!(
+            encode(&metric, true),
+            r#"# HELP test_latency_seconds Request latency
+# TYPE test_latency_seconds histogram
+test_latency_seconds_bucket{instance_name="carnitas",path="/api",le="0.5"} 1 # {trace_id="t1"} 0.25 2.000
+test_latency_seconds_bucket{instance_name="carnitas",path="/api",le="1"} 3
+test_latency_seconds_bucket{instance_name="carnitas",path="/api",le="+Inf"} 4 # {trace_id="t2"} 4 3.250
+test_latency_seconds_sum{instance_name="carnitas",path="/api"} 6
+test_latency_seconds_count{instance_name="carnitas",path="/api"} 4
+"#
+        );
+        assert_eq!(
+            encode(&metric, false),
+            r#"# HELP test_latency_seconds Request latency
+# TYPE test_latency_seconds histogram
+test_latency_seconds_bucket{instance_name="carnitas",path="/api",le="0.5"} 1
+test_latency_seconds_bucket{instance_name="carnitas",path="/api",le="1"} 3
+test_latency_seconds_bucket{instance_name="carnitas",path="/api",le="+Inf"} 4
+test_latency_seconds_sum{instance_name="carnitas",path="/api"} 6
+test_latency_seconds_count{instance_name="carnitas",path="/api"} 4
+"#
+        );
+    }
+}
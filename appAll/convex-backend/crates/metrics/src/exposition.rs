@@ -0,0 +1,246 @@
+//! Writing out all metrics for a Prometheus scrape.
+
+use std::fmt::Write;
+
+use prometheus::{
+    proto::{
+        MetricFamily,
+        MetricType,
+    },
+    Encoder,
+    TextEncoder,
+};
+
+use crate::{
+    exemplars::{
+        encode_exemplar_metrics,
+        escape_help,
+        format_float,
+        format_labels,
+    },
+    CONVEX_METRICS_REGISTRY,
+};
+
+pub const OPENMETRICS_CONTENT_TYPE: &str =
+    "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExpositionFormat {
+    /// The Prometheus text format, version 0.0.4.
+    Prometheus,
+    /// The OpenMetrics text format, which includes exemplars.
+    OpenMetrics,
+}
+
+impl ExpositionFormat {
+    /// The format to use for a scrape with the given `Accept` header.
+    pub fn from_accept_header(accept: Option<&str>) -> Self {
+        match accept {
+            Some(accept) if accept.contains("application/openmetrics-text") => Self::OpenMetrics,
+            _ => Self::Prometheus,
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Prometheus => prometheus::TEXT_FORMAT,
+            Self::OpenMetrics => OPENMETRICS_CONTENT_TYPE,
+        }
+    }
+}
+
+/// Encode every metric that has been recorded at least once.
+pub fn encode_metrics(format: ExpositionFormat) -> anyhow::Result<String> {
+    let families = CONVEX_METRICS_REGISTRY.gather();
+    let mut out = match format {
+        ExpositionFormat::Prometheus => {
+            let mut buf = vec![];
+            TextEncoder::new().encode(&families, &mut buf)?;
+            String::from_utf8(buf)?
+        },
+        ExpositionFormat::OpenMetrics => {
+            let mut out = String::new();
+            for family in &families {
+                encode_openmetrics_family(&mut out, family)?;
+            }
+            out
+        },
+    };
+    encode_exemplar_metrics(&mut out, format == ExpositionFormat::OpenMetrics)?;
+    if format == ExpositionFormat::OpenMetrics {
+        out.push_str("# EOF\n");
+    }
+    Ok(out)
+}
+
+fn encode_openmetrics_family(out: &mut String, family: &MetricFamily) -> std::fmt::Result {
+    let name = family.get_name();
+    let help = escape_help(family.get_help());
+    let (family_name, type_name) = match family.get_field_type() {
+        // OpenMetrics counters are named without the `_total` their samples
+        // have. Counters that don't end in `_total` can't be written as
+        // counters at all.
+        MetricType::COUNTER => match name.strip_suffix("_total") {
+            Some(family_name) => (family_name, "counter"),
+            None => (name, "unknown"),
+        },
+        MetricType::GAUGE => (name, "gauge"),
+        MetricType::SUMMARY => (name, "summary"),
+        MetricType::HISTOGRAM => (name, "histogram"),
+        MetricType::UNTYPED => (name, "unknown"),
+    };
+    writeln!(out, "# HELP {family_name} {help}")?;
+    writeln!(out, "# TYPE {family_name} {type_name}")?;
+    for metric in family.get_metric() {
+        let label_pairs: Vec<_> = metric
+            .get_label()
+            .iter()
+            .map(|pair| (pair.get_name(), pair.get_value()))
+            .collect();
+        let labels = format_labels(label_pairs.iter().copied());
+        match family.get_field_type() {
+            MetricType::COUNTER => {
+                let value = format_float(metric.get_counter().get_value());
+                writeln!(out, "{name}{labels} {value}")?;
+            },
+            MetricType::GAUGE => {
+                let value = format_float(metric.get_gauge().get_value());
+                writeln!(out, "{name}{labels} {value}")?;
+            },
+            MetricType::UNTYPED => {
+                let value = format_float(metric.get_untyped().get_value());
+                writeln!(out, "{name}{labels} {value}")?;
+            },
+            MetricType::SUMMARY => {
+                let summary = metric.get_summary();
+                for quantile in summary.get_quantile() {
+                    let q = format_float(quantile.get_quantile());
+                    let labels = format_labels(
+                        label_pairs
+                            .iter()
+                            .copied()
+                            .chain([("quantile", q.as_str())]),
+                    );
+                    let value = format_float(quantile.get_value());
+                    writeln!(out, "{name}{labels} {value}")?;
+                }
+                let sum = format_float(summary.get_sample_sum());
+                writeln!(out, "{name}_sum{labels} {sum}")?;
+                writeln!(out, "{name}_count{labels} {}", summary.get_sample_count())?;
+            },
+            MetricType::HISTOGRAM => {
+                let histogram = metric.get_histogram();
+                let mut has_inf_bucket = false;
+                for bucket in histogram.get_bucket() {
+                    has_inf_bucket |= bucket.get_upper_bound() == f64::INFINITY;
+                    let le = format_float(bucket.get_upper_bound());
+                    let labels =
+                        format_labels(label_pairs.iter().copied().chain([("le", le.as_str())]));
+                    writeln!(
+                        out,
+                        "{name}_bucket{labels} {}",
+                        bucket.get_cumulative_count()
+                    )?;
+                }
+                if !has_inf_bucket {
+                    let labels = format_labels(label_pairs.iter().copied().chain([("le", "+Inf")]));
+                    writeln!(
+                        out,
+                        "{name}_bucket{labels} {}",
+                        histogram.get_sample_count()
+                    )?;
+                }
+                let sum = format_float(histogram.get_sample_sum());
+                writeln!(out, "{name}_sum{labels} {sum}")?;
+                writeln!(out, "{name}_count{labels} {}", histogram.get_sample_count())?;
+            },
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use prometheus::{
+        Counter,
+        CounterVec,
+        Gauge,
+        Histogram,
+        HistogramOpts,
+        Opts,
+        Registry,
+    };
+
+    use super::{
+        encode_metrics,
+        encode_openmetrics_family,
+        ExpositionFormat,
+    };
+
+    #[test]
+    fn test_encode_openmetrics() -> anyhow::Result<()> {
+        let registry = Registry::new_custom(
+            None,
+            Some(HashMap::from([(
+                "instance_name".to_string(),
+                "carnitas".to_string(),
+            )])),
+        )?;
+        let requests = CounterVec::new(
+            Opts::new("test_requests_total", "Requests \"served\"\nby status"),
+            &["status"],
+        )?;
+        registry.register(Box::new(requests.clone()))?;
+        let retries = Counter::new("test_retries", "Retries")?;
+        registry.register(Box::new(retries.clone()))?;
+        let queue_length = Gauge::new("test_queue_length", "Queue length")?;
+        registry.register(Box::new(queue_length.clone()))?;
+        let latency = Histogram::with_opts(
+            HistogramOpts::new("test_latency_seconds", "Request latency").buckets(vec![0.5, 1.]),
+        )?;
+        registry.register(Box::new(latency.clone()))?;
+
+        requests.with_label_values(&["a\"b\\c\n"]).inc();
+        requests.with_label_values(&["ok"]).inc_by(3.);
+        retries.inc_by(2.);
+        queue_length.set(5.);
+        latency.observe(0.25);
+        latency.observe(4.);
+
+        let mut out = String::new();
+        for family in &registry.gather() {
+            encode_openmetrics_family(&mut out, family)?;
+        }
+        assert_eq!(
+            out,
+            r#"# HELP test_latency_seconds Request latency
+# TYPE test_latency_seconds histogram
+test_latency_seconds_bucket{instance_name="carnitas",le="0.5"} 1
+test_latency_seconds_bucket{instance_name="carnitas",le="1"} 1
+test_latency_seconds_bucket{instance_name="carnitas",le="+Inf"} 2
+test_latency_seconds_sum{instance_name="carnitas"} 4.25
+test_latency_seconds_count{instance_name="carnitas"} 2
+# HELP test_queue_length Queue length
+# TYPE test_queue_length gauge
+test_queue_length{instance_name="carnitas"} 5
+# HELP test_requests Requests "served"\nby status
+# TYPE test_requests counter
+test_requests_total{instance_name="carnitas",status="a\"b\\c\n"} 1
+test_requests_total{instance_name="carnitas",status="ok"} 3
+# HELP test_retries Retries
+# TYPE test_retries unknown
+test_retries{instance_name="carnitas"} 2
+"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_metrics_eof() -> anyhow::Result<()> {
+        assert!(encode_metrics(ExpositionFormat::OpenMetrics)?.ends_with("# EOF\n"));
+        assert!(!encode_metrics(ExpositionFormat::Prometheus)?.contains("# EOF"));
+        Ok(())
+    }
+}
@@ -12,6 +12,8 @@ use std::{
     },
 };
 
+mod exemplars;
+mod exposition;
 mod labels;
 mod macros;
 mod metrics;
@@ -20,6 +22,17 @@ mod reporting;
 mod timer;
 
 pub use crate::{
+    exemplars::{
+        log_counter_with_exemplar,
+        log_distribution_with_exemplar,
+        Exemplar,
+        ExemplarCounterVec,
+        ExemplarHistogramVec,
+    },
+    exposition::{
+        encode_metrics,
+        ExpositionFormat,
+    },
     labels::*,
     macros::*,
     metrics::*,
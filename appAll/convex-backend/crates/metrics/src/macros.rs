@@ -180,3 +180,43 @@ macro_rules! register_convex_gauge_owned {
         .expect("Metric initialization failed")
     }};
 }
+
+/// Register a counter whose series keep an exemplar and store it in a static
+/// variable. The third argument gives the labels for this metric.
+/// The reported metric name will be the lower_snake_case version of the
+/// declared variable name, which must end in `_total`.
+#[macro_export]
+macro_rules! register_convex_exemplar_counter {
+    ($VIS:vis $NAME:ident, $HELP:literal, $LABELS:expr $(,)?) => {
+        $VIS static $NAME: std::sync::LazyLock<$crate::ExemplarCounterVec> =
+            std::sync::LazyLock::new(|| {
+                $crate::paste! {
+                    let name = $crate::metric_name!(stringify!([<$NAME:lower>]));
+                }
+                $crate::ExemplarCounterVec::register(name, $crate::metric_help!($HELP), $LABELS)
+            });
+    };
+}
+
+/// Register a histogram whose buckets keep an exemplar and store it in a
+/// static variable. The third argument gives the labels for this metric and
+/// the fourth the upper bounds of its buckets.
+/// The reported metric name will be the lower_snake_case version of the
+/// declared variable name.
+#[macro_export]
+macro_rules! register_convex_exemplar_histogram {
+    ($VIS:vis $NAME:ident, $HELP:literal, $LABELS:expr, $BUCKETS:expr $(,)?) => {
+        $VIS static $NAME: std::sync::LazyLock<$crate::ExemplarHistogramVec> =
+            std::sync::LazyLock::new(|| {
+                $crate::paste! {
+                    let name = $crate::metric_name!(stringify!([<$NAME:lower>]));
+                }
+                $crate::ExemplarHistogramVec::register(
+                    name,
+                    $crate::metric_help!($HELP),
+                    $LABELS,
+                    $BUCKETS,
+                )
+            });
+    };
+}
@@ -20,7 +20,10 @@
 //!    module.
 use std::{
     borrow::Cow,
-    collections::HashSet,
+    collections::{
+        HashMap,
+        HashSet,
+    },
     env,
     ops::Deref,
     sync::LazyLock,
@@ -83,14 +86,22 @@ pub static SERVICE_NAME: LazyLock<String> = LazyLock::new(|| {
         .replace('-', "_")
 });
 
-pub static CONVEX_METRICS_REGISTRY: LazyLock<Registry> = LazyLock::new(|| {
-    let labels = env::var("CONVEX_SITE").ok().map(|instance_name| {
-        [("instance_name".to_owned(), instance_name)]
-            .into_iter()
-            .collect()
+/// Labels added to every metric.
+pub(crate) static CONVEX_METRICS_CONST_LABELS: LazyLock<Option<HashMap<String, String>>> =
+    LazyLock::new(|| {
+        env::var("CONVEX_SITE").ok().map(|instance_name| {
+            [("instance_name".to_owned(), instance_name)]
+                .into_iter()
+                .collect()
+        })
     });
-    Registry::new_custom(Some(SERVICE_NAME.clone()), labels)
-        .expect("Failed to initialize Prometheus metrics registry")
+
+pub static CONVEX_METRICS_REGISTRY: LazyLock<Registry> = LazyLock::new(|| {
+    Registry::new_custom(
+        Some(SERVICE_NAME.clone()),
+        CONVEX_METRICS_CONST_LABELS.clone(),
+    )
+    .expect("Failed to initialize Prometheus metrics registry")
 });
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
@@ -3,7 +3,10 @@ use std::{
     fmt::Formatter,
 };
 
-use common::document::ParsedDocument;
+use common::{
+    document::ParsedDocument,
+    types::NodeVersion,
+};
 use value::{
     id_v6::DeveloperDocumentId,
     obj,
@@ -34,6 +37,14 @@ pub struct AwsLambdaConfig {
     pub vpc_security_group_ids: Vec<String>,
 }
 
+impl AwsLambdaConfig {
+    /// The Node.js version of the Lambda's runtime, if it's a Node.js runtime
+    /// we support.
+    pub fn node_version(&self) -> Option<NodeVersion> {
+        NodeVersion::from_aws_lambda_runtime(&self.runtime)
+    }
+}
+
 #[cfg_attr(any(test, feature = "testing"), derive(proptest_derive::Arbitrary))]
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct AwsLambdaVersion {
@@ -69,6 +69,7 @@ use crate::{
         AuditLogIndexDiff,
         SerializedIndexDiff,
     },
+    file_storage::FileStorageModel,
     initialize_application_system_table,
     modules::{
         module_versions::AnalyzedModule,
@@ -727,6 +728,12 @@ impl<'a, RT: Runtime> ComponentConfigModel<'a, RT> {
                 .delete_table(namespace, SCHEMAS_TABLE.clone())
                 .await?;
 
+            // release the component's references to shared file storage objects
+            // before its `_file_storage` table goes away
+            FileStorageModel::new(self.tx, namespace)
+                .remove_all_references()
+                .await?;
+
             // then delete all tables, including system tables
             let namespaced_table_mapping = self.tx.table_mapping().namespace(namespace);
             for (_, _, table_name) in namespaced_table_mapping.iter() {
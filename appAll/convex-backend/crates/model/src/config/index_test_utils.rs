@@ -171,6 +171,7 @@ pub async fn apply_config(
     let config_metadata = ConfigMetadata {
         functions: "convex/".to_string(),
         auth_info: vec![],
+        node_version: None,
     };
 
     let mut tx = db.begin_system().await?;
@@ -86,6 +86,7 @@ async fn test_config(rt: TestRuntime) -> anyhow::Result<()> {
                 sha256,
                 external_deps_package_id: None,
                 package_size,
+                node_version: None,
             }),
             btreemap! {
                 p1 => AnalyzedModule {
@@ -172,6 +173,7 @@ async fn test_config_large_modules(rt: TestRuntime) -> anyhow::Result<()> {
                 sha256,
                 external_deps_package_id: None,
                 package_size,
+                node_version: None,
             }),
             analyzed_result,
             None,
@@ -217,6 +219,7 @@ async fn test_config_delete_auth_info(rt: TestRuntime) -> anyhow::Result<()> {
             ConfigMetadata {
                 functions: "convex/".to_string(),
                 auth_info: vec![],
+                node_version: None,
             },
             vec![],
             UdfConfig::new_for_test(&rt, "1000.0.0".parse()?),
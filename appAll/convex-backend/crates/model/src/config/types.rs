@@ -14,7 +14,10 @@ use std::{
 use common::{
     auth::AuthInfo,
     obj,
-    types::ModuleEnvironment,
+    types::{
+        ModuleEnvironment,
+        NodeVersion,
+    },
 };
 use database::{
     LegacyIndexDiff,
@@ -110,6 +113,8 @@ pub struct ConfigMetadata {
         )
     )]
     pub auth_info: Vec<AuthInfo>,
+    /// Node.js version for "use node" actions. `None` uses the default.
+    pub node_version: Option<NodeVersion>,
 }
 
 impl ConfigMetadata {
@@ -118,6 +123,7 @@ impl ConfigMetadata {
         Self {
             functions: "convex/".to_string(),
             auth_info: vec![],
+            node_version: None,
         }
     }
 
@@ -126,6 +132,7 @@ impl ConfigMetadata {
         Self {
             functions: "convex/".to_string(),
             auth_info: vec![AuthInfo::test_example()],
+            node_version: None,
         }
     }
 
@@ -133,6 +140,7 @@ impl ConfigMetadata {
         Self {
             functions: file.functions,
             auth_info,
+            node_version: file.node_version,
         }
     }
 }
@@ -143,6 +151,7 @@ pub struct ConfigFile {
     pub functions: String,
     // Deprecated, moved to AuthConfig.providers
     pub auth_info: Option<Vec<AuthInfo>>,
+    pub node_version: Option<NodeVersion>,
 }
 
 impl TryFrom<ConfigMetadata> for ConvexObject {
@@ -163,6 +172,12 @@ impl TryFrom<ConfigMetadata> for ConvexObject {
                 .try_into()?;
             config.insert("authInfo".parse()?, auth_info);
         }
+        if let Some(node_version) = m.node_version {
+            config.insert(
+                "nodeVersion".parse()?,
+                ConvexValue::try_from(node_version.to_string())?,
+            );
+        }
         config.try_into()
     }
 }
@@ -197,9 +212,15 @@ impl TryFrom<ConvexObject> for ConfigMetadata {
                 .collect::<anyhow::Result<Vec<AuthInfo>>>()?,
             _ => vec![],
         };
+        let node_version = match fields.remove("nodeVersion") {
+            Some(ConvexValue::String(s)) => Some(s.parse()?),
+            None => None,
+            _ => anyhow::bail!("Invalid nodeVersion field for ConfigMetadata: {fields:?}"),
+        };
         Ok(Self {
             functions,
             auth_info,
+            node_version,
         })
     }
 }
@@ -44,6 +44,7 @@ use value::{
     val,
     ConvexObject,
     ConvexValue,
+    DeveloperDocumentId,
     TableName,
 };
 
@@ -139,6 +140,11 @@ pub enum DeploymentAuditLogEvent {
     PushConfigWithComponents {
         diffs: PushComponentDiffs,
     },
+    /// Re-applied the configuration recorded in `_push_history` as `push_id`.
+    RollbackPush {
+        push_id: DeveloperDocumentId,
+        diffs: PushComponentDiffs,
+    },
     BuildIndexes {
         #[cfg_attr(
             any(test, feature = "testing"),
@@ -218,6 +224,7 @@ impl DeploymentAuditLogEvent {
             DeploymentAuditLogEvent::PushConfigWithComponents { .. } => {
                 "push_config_with_components"
             },
+            DeploymentAuditLogEvent::RollbackPush { .. } => "rollback_push",
             DeploymentAuditLogEvent::BuildIndexes { .. } => "build_indexes",
             DeploymentAuditLogEvent::ChangeDeploymentState { .. } => "change_deployment_state",
             DeploymentAuditLogEvent::SnapshotImport { .. } => "snapshot_import",
@@ -253,6 +260,10 @@ impl DeploymentAuditLogEvent {
                 ConvexObject::try_from(config_diff)
             },
             DeploymentAuditLogEvent::PushConfigWithComponents { diffs } => diffs.try_into(),
+            DeploymentAuditLogEvent::RollbackPush { push_id, diffs } => {
+                ConvexObject::try_from(diffs)?
+                    .shallow_merge(obj!("push_id" => push_id.to_string())?)
+            },
             DeploymentAuditLogEvent::BuildIndexes {
                 added_indexes,
                 removed_indexes,
@@ -425,6 +436,10 @@ impl TryFrom<ConvexObject> for DeploymentAuditLogEvent {
             "push_config_with_components" => DeploymentAuditLogEvent::PushConfigWithComponents {
                 diffs: ConvexObject::try_from(fields)?.try_into()?,
             },
+            "rollback_push" => DeploymentAuditLogEvent::RollbackPush {
+                push_id: remove_string(&mut fields, "push_id")?.parse()?,
+                diffs: ConvexObject::try_from(fields)?.try_into()?,
+            },
             "build_indexes" => {
                 let added_indexes = remove_vec(&mut fields, "added_indexes")?
                     .into_iter()
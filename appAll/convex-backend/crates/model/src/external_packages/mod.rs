@@ -18,6 +18,7 @@ use common::{
     types::{
         IndexName,
         NodeDependency,
+        NodeVersion,
     },
 };
 use database::{
@@ -108,6 +109,7 @@ impl<'a, RT: Runtime> ExternalPackagesModel<'a, RT> {
     pub async fn get_cached_package_match(
         &mut self,
         deps: Vec<NodeDependency>,
+        node_version: NodeVersion,
     ) -> anyhow::Result<Option<(ExternalDepsPackageId, ExternalDepsPackage)>> {
         let index_query = Query::index_range(IndexRange {
             index_name: IndexName::by_creation_time(EXTERNAL_PACKAGES_TABLE.clone()),
@@ -134,7 +136,8 @@ impl<'a, RT: Runtime> ExternalPackagesModel<'a, RT> {
                 .into_iter()
                 .map(|dep| (dep.package, dep.version))
                 .collect();
-            if pkg_deps_map.eq(&deps_map) {
+            // Native modules are built against a specific Node.js version.
+            if pkg_deps_map.eq(&deps_map) && pkg.node_version.unwrap_or_default() == node_version {
                 return Ok(Some((DeveloperDocumentId::from(id).into(), pkg)));
             }
 
@@ -0,0 +1,282 @@
+//! Content-addressed deduplication for file storage.
+//!
+//! Every object written to the files `Storage` on behalf of a `_storage` entry
+//! gets a row in `_file_storage_blobs`. When a new upload has the same sha256
+//! and size as a live blob, the `_storage` entry is pointed at the existing
+//! object and the duplicate upload is recorded with a zero reference count.
+//! Deleting a `_storage` entry only decrements the count; objects are removed
+//! from `Storage` once nothing references them anymore (see
+//! [`FileStorageBlobsModel::cleanup_unreferenced`]).
+//!
+//! Entries written before deduplication existed have no blob row. Their
+//! objects are never shared and deleting them leaves the row set untouched.
+
+use std::sync::LazyLock;
+
+use common::{
+    document::{
+        ParsedDocument,
+        ResolvedDocument,
+        CREATION_TIME_FIELD_PATH,
+    },
+    query::{
+        IndexRange,
+        IndexRangeExpression,
+        Order,
+        Query,
+    },
+    runtime::Runtime,
+    types::{
+        IndexName,
+        ObjectKey,
+    },
+};
+use database::{
+    defaults::system_index,
+    ResolvedQuery,
+    SystemMetadataModel,
+    Transaction,
+};
+use value::{
+    sha256::Sha256Digest,
+    ConvexValue,
+    FieldPath,
+    TableName,
+    TableNamespace,
+};
+
+use crate::{
+    file_storage::{
+        image_variants::FileStorageImageVariantsModel,
+        types::{
+            FileStorageBlob,
+            FileStorageEntry,
+        },
+    },
+    SystemIndex,
+    SystemTable,
+};
+
+pub static FILE_STORAGE_BLOBS_TABLE: LazyLock<TableName> = LazyLock::new(|| {
+    "_file_storage_blobs"
+        .parse()
+        .expect("invalid built-in file storage blobs table")
+});
+
+pub static FILE_STORAGE_BLOBS_INDEX_BY_SHA256: LazyLock<IndexName> =
+    LazyLock::new(|| system_index(&FILE_STORAGE_BLOBS_TABLE, "by_sha256"));
+pub static FILE_STORAGE_BLOBS_INDEX_BY_STORAGE_KEY: LazyLock<IndexName> =
+    LazyLock::new(|| system_index(&FILE_STORAGE_BLOBS_TABLE, "by_storage_key"));
+pub static FILE_STORAGE_BLOBS_INDEX_BY_REF_COUNT: LazyLock<IndexName> =
+    LazyLock::new(|| system_index(&FILE_STORAGE_BLOBS_TABLE, "by_ref_count"));
+
+static SHA256_FIELD: LazyLock<FieldPath> =
+    LazyLock::new(|| "sha256".parse().expect("invalid sha256 field"));
+static SIZE_FIELD: LazyLock<FieldPath> =
+    LazyLock::new(|| "size".parse().expect("invalid size field"));
+static STORAGE_KEY_FIELD: LazyLock<FieldPath> =
+    LazyLock::new(|| "storageKey".parse().expect("invalid storageKey field"));
+static REF_COUNT_FIELD: LazyLock<FieldPath> =
+    LazyLock::new(|| "refCount".parse().expect("invalid refCount field"));
+
+pub struct FileStorageBlobsTable;
+impl SystemTable for FileStorageBlobsTable {
+    fn table_name(&self) -> &'static TableName {
+        &FILE_STORAGE_BLOBS_TABLE
+    }
+
+    fn indexes(&self) -> Vec<SystemIndex> {
+        vec![
+            SystemIndex {
+                name: FILE_STORAGE_BLOBS_INDEX_BY_SHA256.clone(),
+                fields: vec![
+                    SHA256_FIELD.clone(),
+                    SIZE_FIELD.clone(),
+                    CREATION_TIME_FIELD_PATH.clone(),
+                ]
+                .try_into()
+                .unwrap(),
+            },
+            SystemIndex {
+                name: FILE_STORAGE_BLOBS_INDEX_BY_STORAGE_KEY.clone(),
+                fields: vec![STORAGE_KEY_FIELD.clone(), CREATION_TIME_FIELD_PATH.clone()]
+                    .try_into()
+                    .unwrap(),
+            },
+            SystemIndex {
+                name: FILE_STORAGE_BLOBS_INDEX_BY_REF_COUNT.clone(),
+                fields: vec![REF_COUNT_FIELD.clone(), CREATION_TIME_FIELD_PATH.clone()]
+                    .try_into()
+                    .unwrap(),
+            },
+        ]
+    }
+
+    fn validate_document(&self, document: ResolvedDocument) -> anyhow::Result<()> {
+        ParsedDocument::<FileStorageBlob>::try_from(document).map(|_| ())
+    }
+}
+
+pub struct FileStorageBlobsModel<'a, RT: Runtime> {
+    tx: &'a mut Transaction<RT>,
+}
+
+impl<'a, RT: Runtime> FileStorageBlobsModel<'a, RT> {
+    pub fn new(tx: &'a mut Transaction<RT>) -> Self {
+        Self { tx }
+    }
+
+    /// Record a new reference to the contents of `entry`.
+    ///
+    /// If a live blob with the same sha256 and size already exists,
+    /// `entry.storage_key` is rewritten to point at it, and the object that
+    /// was just uploaded under the old key is recorded as unreferenced so
+    /// garbage collection can reclaim it.
+    pub async fn add_reference(&mut self, entry: &mut FileStorageEntry) -> anyhow::Result<()> {
+        if let Some(blob) = self.get_by_storage_key(&entry.storage_key).await? {
+            // The object is already tracked (e.g. the same upload is being stored
+            // again), so just bump its count.
+            return self.set_ref_count(blob, |count| count + 1).await;
+        }
+        match self.get_live_by_contents(&entry.sha256, entry.size).await? {
+            Some(existing) => {
+                let duplicate = FileStorageBlob {
+                    storage_key: entry.storage_key.clone(),
+                    sha256: entry.sha256.clone(),
+                    size: entry.size,
+                    ref_count: 0,
+                };
+                SystemMetadataModel::new_global(self.tx)
+                    .insert(&FILE_STORAGE_BLOBS_TABLE, duplicate.try_into()?)
+                    .await?;
+                entry.storage_key = existing.storage_key.clone();
+                self.set_ref_count(existing, |count| count + 1).await?;
+            },
+            None => {
+                let blob = FileStorageBlob {
+                    storage_key: entry.storage_key.clone(),
+                    sha256: entry.sha256.clone(),
+                    size: entry.size,
+                    ref_count: 1,
+                };
+                SystemMetadataModel::new_global(self.tx)
+                    .insert(&FILE_STORAGE_BLOBS_TABLE, blob.try_into()?)
+                    .await?;
+            },
+        }
+        Ok(())
+    }
+
+    /// Drop a reference to the object at `storage_key`. The bytes stay in
+    /// `Storage` until [`Self::cleanup_unreferenced`] runs.
+    pub async fn remove_reference(&mut self, storage_key: &ObjectKey) -> anyhow::Result<()> {
+        let Some(blob) = self.get_by_storage_key(storage_key).await? else {
+            // Entries stored before deduplication aren't tracked.
+            return Ok(());
+        };
+        anyhow::ensure!(
+            blob.ref_count > 0,
+            "File storage blob {storage_key:?} is already unreferenced"
+        );
+        self.set_ref_count(blob, |count| count - 1).await
+    }
+
+    /// Delete up to `limit` unreferenced blob rows, returning the object keys
+    /// that should be removed from `Storage` once this transaction commits.
+    /// This includes the keys of any cached image variants of those blobs.
+    pub async fn cleanup_unreferenced(&mut self, limit: usize) -> anyhow::Result<Vec<ObjectKey>> {
+        let index_range = IndexRange {
+            index_name: FILE_STORAGE_BLOBS_INDEX_BY_REF_COUNT.clone(),
+            range: vec![IndexRangeExpression::Eq(
+                REF_COUNT_FIELD.clone(),
+                ConvexValue::from(0i64).into(),
+            )],
+            order: Order::Asc,
+        };
+        let query = Query::index_range(index_range).limit(limit);
+        let mut query_stream = ResolvedQuery::new(self.tx, TableNamespace::Global, query)?;
+        let mut object_keys = vec![];
+        while let Some(doc) = query_stream.next(self.tx, None).await? {
+            let blob: ParsedDocument<FileStorageBlob> = doc.try_into()?;
+            SystemMetadataModel::new_global(self.tx)
+                .delete(blob.id())
+                .await?;
+            let storage_key = blob.into_value().storage_key;
+            object_keys.extend(
+                FileStorageImageVariantsModel::new(self.tx)
+                    .delete_for_source(&storage_key)
+                    .await?,
+            );
+            object_keys.push(storage_key);
+        }
+        Ok(object_keys)
+    }
+
+    pub async fn get_by_storage_key(
+        &mut self,
+        storage_key: &ObjectKey,
+    ) -> anyhow::Result<Option<ParsedDocument<FileStorageBlob>>> {
+        let index_range = IndexRange {
+            index_name: FILE_STORAGE_BLOBS_INDEX_BY_STORAGE_KEY.clone(),
+            range: vec![IndexRangeExpression::Eq(
+                STORAGE_KEY_FIELD.clone(),
+                ConvexValue::try_from(String::from(storage_key.clone()))?.into(),
+            )],
+            order: Order::Asc,
+        };
+        let mut query_stream = ResolvedQuery::new(
+            self.tx,
+            TableNamespace::Global,
+            Query::index_range(index_range),
+        )?;
+        query_stream
+            .expect_at_most_one(self.tx)
+            .await?
+            .map(|doc| doc.try_into())
+            .transpose()
+    }
+
+    /// Find the oldest blob with the given contents that is still referenced.
+    async fn get_live_by_contents(
+        &mut self,
+        sha256: &Sha256Digest,
+        size: i64,
+    ) -> anyhow::Result<Option<ParsedDocument<FileStorageBlob>>> {
+        let index_range = IndexRange {
+            index_name: FILE_STORAGE_BLOBS_INDEX_BY_SHA256.clone(),
+            range: vec![
+                IndexRangeExpression::Eq(
+                    SHA256_FIELD.clone(),
+                    ConvexValue::try_from(sha256.clone())?.into(),
+                ),
+                IndexRangeExpression::Eq(SIZE_FIELD.clone(), ConvexValue::from(size).into()),
+            ],
+            order: Order::Asc,
+        };
+        let mut query_stream = ResolvedQuery::new(
+            self.tx,
+            TableNamespace::Global,
+            Query::index_range(index_range),
+        )?;
+        while let Some(doc) = query_stream.next(self.tx, None).await? {
+            let blob: ParsedDocument<FileStorageBlob> = doc.try_into()?;
+            if blob.ref_count > 0 {
+                return Ok(Some(blob));
+            }
+        }
+        Ok(None)
+    }
+
+    async fn set_ref_count(
+        &mut self,
+        blob: ParsedDocument<FileStorageBlob>,
+        f: impl FnOnce(i64) -> i64,
+    ) -> anyhow::Result<()> {
+        let (id, mut blob) = blob.into_id_and_value();
+        blob.ref_count = f(blob.ref_count);
+        SystemMetadataModel::new_global(self.tx)
+            .replace(id, blob.try_into()?)
+            .await?;
+        Ok(())
+    }
+}
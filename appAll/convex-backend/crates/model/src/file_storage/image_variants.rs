@@ -0,0 +1,195 @@
+//! Cache of transformed images derived from objects in file storage.
+//!
+//! Variants are keyed by the object key of their source, so every `_storage`
+//! entry sharing a deduplicated blob also shares its variants. They are
+//! deleted along with the source blob (see
+//! [`crate::file_storage::blobs::FileStorageBlobsModel::cleanup_unreferenced`]).
+
+use std::sync::LazyLock;
+
+use common::{
+    document::{
+        ParsedDocument,
+        ResolvedDocument,
+        CREATION_TIME_FIELD_PATH,
+    },
+    query::{
+        IndexRange,
+        IndexRangeExpression,
+        Order,
+        Query,
+    },
+    runtime::Runtime,
+    types::{
+        IndexName,
+        ObjectKey,
+    },
+};
+use database::{
+    defaults::system_index,
+    ResolvedQuery,
+    SystemMetadataModel,
+    Transaction,
+};
+use value::{
+    ConvexValue,
+    FieldPath,
+    ResolvedDocumentId,
+    TableName,
+    TableNamespace,
+};
+
+use crate::{
+    file_storage::types::FileStorageImageVariant,
+    SystemIndex,
+    SystemTable,
+};
+
+pub static FILE_STORAGE_IMAGE_VARIANTS_TABLE: LazyLock<TableName> = LazyLock::new(|| {
+    "_file_storage_image_variants"
+        .parse()
+        .expect("invalid built-in file storage image variants table")
+});
+
+pub static FILE_STORAGE_IMAGE_VARIANTS_INDEX_BY_SOURCE: LazyLock<IndexName> =
+    LazyLock::new(|| system_index(&FILE_STORAGE_IMAGE_VARIANTS_TABLE, "by_source"));
+
+static SOURCE_STORAGE_KEY_FIELD: LazyLock<FieldPath> = LazyLock::new(|| {
+    "sourceStorageKey"
+        .parse()
+        .expect("invalid sourceStorageKey field")
+});
+static TRANSFORM_FIELD: LazyLock<FieldPath> =
+    LazyLock::new(|| "transform".parse().expect("invalid transform field"));
+
+pub struct FileStorageImageVariantsTable;
+impl SystemTable for FileStorageImageVariantsTable {
+    fn table_name(&self) -> &'static TableName {
+        &FILE_STORAGE_IMAGE_VARIANTS_TABLE
+    }
+
+    fn indexes(&self) -> Vec<SystemIndex> {
+        vec![SystemIndex {
+            name: FILE_STORAGE_IMAGE_VARIANTS_INDEX_BY_SOURCE.clone(),
+            fields: vec![
+                SOURCE_STORAGE_KEY_FIELD.clone(),
+                TRANSFORM_FIELD.clone(),
+                CREATION_TIME_FIELD_PATH.clone(),
+            ]
+            .try_into()
+            .unwrap(),
+        }]
+    }
+
+    fn validate_document(&self, document: ResolvedDocument) -> anyhow::Result<()> {
+        ParsedDocument::<FileStorageImageVariant>::try_from(document).map(|_| ())
+    }
+}
+
+pub struct FileStorageImageVariantsModel<'a, RT: Runtime> {
+    tx: &'a mut Transaction<RT>,
+}
+
+impl<'a, RT: Runtime> FileStorageImageVariantsModel<'a, RT> {
+    pub fn new(tx: &'a mut Transaction<RT>) -> Self {
+        Self { tx }
+    }
+
+    pub async fn get(
+        &mut self,
+        source_storage_key: &ObjectKey,
+        transform: &str,
+    ) -> anyhow::Result<Option<FileStorageImageVariant>> {
+        let index_range = IndexRange {
+            index_name: FILE_STORAGE_IMAGE_VARIANTS_INDEX_BY_SOURCE.clone(),
+            range: vec![
+                IndexRangeExpression::Eq(
+                    SOURCE_STORAGE_KEY_FIELD.clone(),
+                    ConvexValue::try_from(String::from(source_storage_key.clone()))?.into(),
+                ),
+                IndexRangeExpression::Eq(
+                    TRANSFORM_FIELD.clone(),
+                    ConvexValue::try_from(transform)?.into(),
+                ),
+            ],
+            order: Order::Asc,
+        };
+        let mut query_stream = ResolvedQuery::new(
+            self.tx,
+            TableNamespace::Global,
+            Query::index_range(index_range),
+        )?;
+        query_stream
+            .expect_at_most_one(self.tx)
+            .await?
+            .map(|doc| {
+                let variant: ParsedDocument<FileStorageImageVariant> = doc.try_into()?;
+                anyhow::Ok(variant.into_value())
+            })
+            .transpose()
+    }
+
+    /// Number of variants derived from `source_storage_key`.
+    pub async fn count_for_source(
+        &mut self,
+        source_storage_key: &ObjectKey,
+    ) -> anyhow::Result<usize> {
+        let index_range = IndexRange {
+            index_name: FILE_STORAGE_IMAGE_VARIANTS_INDEX_BY_SOURCE.clone(),
+            range: vec![IndexRangeExpression::Eq(
+                SOURCE_STORAGE_KEY_FIELD.clone(),
+                ConvexValue::try_from(String::from(source_storage_key.clone()))?.into(),
+            )],
+            order: Order::Asc,
+        };
+        let mut query_stream = ResolvedQuery::new(
+            self.tx,
+            TableNamespace::Global,
+            Query::index_range(index_range),
+        )?;
+        let mut count = 0;
+        while query_stream.next(self.tx, None).await?.is_some() {
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    pub async fn insert(
+        &mut self,
+        variant: FileStorageImageVariant,
+    ) -> anyhow::Result<ResolvedDocumentId> {
+        SystemMetadataModel::new_global(self.tx)
+            .insert(&FILE_STORAGE_IMAGE_VARIANTS_TABLE, variant.try_into()?)
+            .await
+    }
+
+    /// Delete every variant derived from `source_storage_key`, returning the
+    /// object keys to remove from `Storage` once this transaction commits.
+    pub async fn delete_for_source(
+        &mut self,
+        source_storage_key: &ObjectKey,
+    ) -> anyhow::Result<Vec<ObjectKey>> {
+        let index_range = IndexRange {
+            index_name: FILE_STORAGE_IMAGE_VARIANTS_INDEX_BY_SOURCE.clone(),
+            range: vec![IndexRangeExpression::Eq(
+                SOURCE_STORAGE_KEY_FIELD.clone(),
+                ConvexValue::try_from(String::from(source_storage_key.clone()))?.into(),
+            )],
+            order: Order::Asc,
+        };
+        let mut query_stream = ResolvedQuery::new(
+            self.tx,
+            TableNamespace::Global,
+            Query::index_range(index_range),
+        )?;
+        let mut object_keys = vec![];
+        while let Some(doc) = query_stream.next(self.tx, None).await? {
+            let variant: ParsedDocument<FileStorageImageVariant> = doc.try_into()?;
+            SystemMetadataModel::new_global(self.tx)
+                .delete(variant.id())
+                .await?;
+            object_keys.push(variant.into_value().storage_key);
+        }
+        Ok(object_keys)
+    }
+}
@@ -55,14 +55,20 @@ use value::{
     TableNamespace,
 };
 
-use self::virtual_table::FileStorageDocMapper;
+use self::{
+    blobs::FileStorageBlobsModel,
+    virtual_table::FileStorageDocMapper,
+};
 use crate::{
     file_storage::types::FileStorageEntry,
     SystemIndex,
     SystemTable,
 };
 
+pub mod blobs;
+pub mod image_variants;
 pub mod types;
+pub mod uploads;
 pub mod virtual_table;
 
 pub type BatchKey = usize;
@@ -201,8 +207,12 @@ impl<'a, RT: Runtime> FileStorageModel<'a, RT> {
 
     pub async fn store_file(
         &mut self,
-        entry: FileStorageEntry,
+        mut entry: FileStorageEntry,
     ) -> anyhow::Result<ResolvedDocumentId> {
+        // Point the entry at an existing object with the same contents, if any.
+        FileStorageBlobsModel::new(self.tx)
+            .add_reference(&mut entry)
+            .await?;
         // Call insert_metadata rather than insert because we already
         // did access check on `identity` rather than `self.identity`
         SystemMetadataModel::new(self.tx, self.namespace)
@@ -325,7 +335,35 @@ impl<'a, RT: Runtime> FileStorageModel<'a, RT> {
         SystemMetadataModel::new(self.tx, self.namespace)
             .delete(document_id)
             .await?;
-        Ok(Some(entry.into_value()))
+        let entry = entry.into_value();
+        // The underlying object may still be shared with other entries, so it's
+        // only removed from storage once its reference count drops to zero.
+        FileStorageBlobsModel::new(self.tx)
+            .remove_reference(&entry.storage_key)
+            .await?;
+        Ok(Some(entry))
+    }
+
+    /// Drop the blob references held by every entry in this namespace. Must be
+    /// called in the same transaction that deletes the namespace's
+    /// `_file_storage` table, otherwise the shared objects are never
+    /// collected.
+    pub async fn remove_all_references(&mut self) -> anyhow::Result<()> {
+        if !TableModel::new(self.tx).table_exists(self.namespace, &FILE_STORAGE_TABLE) {
+            return Ok(());
+        }
+        let query = Query::full_table_scan(FILE_STORAGE_TABLE.clone(), Order::Asc);
+        let mut query_stream = ResolvedQuery::new(self.tx, self.namespace, query)?;
+        let mut storage_keys = vec![];
+        while let Some(storage_document) = query_stream.next(self.tx, None).await? {
+            let storage_entry: ParsedDocument<FileStorageEntry> = storage_document.try_into()?;
+            storage_keys.push(storage_entry.into_value().storage_key);
+        }
+        let mut blobs_model = FileStorageBlobsModel::new(self.tx);
+        for storage_key in storage_keys {
+            blobs_model.remove_reference(&storage_key).await?;
+        }
+        Ok(())
     }
 
     pub async fn get_total_storage_count(&mut self) -> anyhow::Result<u64> {
@@ -2,6 +2,7 @@ use std::collections::BTreeMap;
 
 use anyhow::Context;
 use common::{
+    components::ComponentId,
     obj,
     types::{
         ObjectKey,
@@ -9,7 +10,13 @@ use common::{
     },
 };
 use pb::storage::FileStorageEntry as FileStorageEntryProto;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use sync_types::Timestamp;
 use value::{
+    codegen_convex_serialization,
     sha256::Sha256Digest,
     ConvexObject,
     ConvexValue,
@@ -124,6 +131,221 @@ impl From<FileStorageEntry> for FileStorageEntryProto {
     }
 }
 
+/// A single object in the backing store that may be shared by several
+/// `_storage` entries with identical contents.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(any(test, feature = "testing"), derive(proptest_derive::Arbitrary))]
+pub struct FileStorageBlob {
+    pub storage_key: ObjectKey,
+    pub sha256: Sha256Digest,
+    pub size: i64,
+    /// Number of `_storage` entries pointing at `storage_key`. Blobs with a
+    /// zero reference count are never handed out again and are reclaimed by
+    /// garbage collection.
+    pub ref_count: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SerializedFileStorageBlob {
+    storage_key: String,
+    #[serde(with = "serde_bytes")]
+    sha256: Vec<u8>,
+    size: i64,
+    ref_count: i64,
+}
+
+impl From<FileStorageBlob> for SerializedFileStorageBlob {
+    fn from(blob: FileStorageBlob) -> Self {
+        Self {
+            storage_key: blob.storage_key.into(),
+            sha256: blob.sha256.to_vec(),
+            size: blob.size,
+            ref_count: blob.ref_count,
+        }
+    }
+}
+
+impl TryFrom<SerializedFileStorageBlob> for FileStorageBlob {
+    type Error = anyhow::Error;
+
+    fn try_from(blob: SerializedFileStorageBlob) -> anyhow::Result<Self> {
+        Ok(Self {
+            storage_key: blob.storage_key.try_into()?,
+            sha256: blob.sha256.try_into()?,
+            size: blob.size,
+            ref_count: blob.ref_count,
+        })
+    }
+}
+
+codegen_convex_serialization!(FileStorageBlob, SerializedFileStorageBlob);
+
+/// A resumable upload of a user file that hasn't been finalized into a
+/// `_storage` entry yet.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(any(test, feature = "testing"), derive(proptest_derive::Arbitrary))]
+pub struct FileStorageUpload {
+    /// Unguessable id handed to the client. Knowing it is sufficient to
+    /// continue the upload, so it is never exposed to other clients.
+    pub upload_id: String,
+    pub component: ComponentId,
+    /// Opaque `ClientDrivenUploadToken` for the backing `Storage`.
+    pub upload_token: String,
+    pub content_type: Option<String>,
+    /// Number of bytes received so far. The next part must start here.
+    pub offset: i64,
+    /// Opaque `ClientDrivenUploadPartToken`s, in part order.
+    pub part_tokens: Vec<String>,
+    /// Size of the most recent part. Only the final part may be smaller than
+    /// the storage's minimum part size, so a small part ends the upload.
+    pub last_part_size: i64,
+    pub expiration_ts: Timestamp,
+    /// Set while a request is writing the part at `offset` or finishing the
+    /// upload, so a retried request can't write the same part concurrently.
+    pub claim: Option<FileStorageUploadClaim>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(any(test, feature = "testing"), derive(proptest_derive::Arbitrary))]
+pub struct FileStorageUploadClaim {
+    /// Unique to the request holding the claim, so a request whose claim
+    /// lapsed can't record its part over another's.
+    pub claim_id: String,
+    /// Whether the upload is being finished rather than receiving a part.
+    pub finishing: bool,
+    /// A request that dies holding a claim releases it at this timestamp.
+    pub expiration_ts: Timestamp,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SerializedFileStorageUpload {
+    upload_id: String,
+    component: Option<String>,
+    upload_token: String,
+    content_type: Option<String>,
+    offset: i64,
+    part_tokens: Vec<String>,
+    last_part_size: i64,
+    expiration_ts: i64,
+    claim: Option<SerializedFileStorageUploadClaim>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SerializedFileStorageUploadClaim {
+    claim_id: String,
+    finishing: bool,
+    expiration_ts: i64,
+}
+
+impl From<FileStorageUpload> for SerializedFileStorageUpload {
+    fn from(upload: FileStorageUpload) -> Self {
+        Self {
+            upload_id: upload.upload_id,
+            component: upload.component.serialize_to_string(),
+            upload_token: upload.upload_token,
+            content_type: upload.content_type,
+            offset: upload.offset,
+            part_tokens: upload.part_tokens,
+            last_part_size: upload.last_part_size,
+            expiration_ts: upload.expiration_ts.into(),
+            claim: upload.claim.map(|claim| SerializedFileStorageUploadClaim {
+                claim_id: claim.claim_id,
+                finishing: claim.finishing,
+                expiration_ts: claim.expiration_ts.into(),
+            }),
+        }
+    }
+}
+
+impl TryFrom<SerializedFileStorageUpload> for FileStorageUpload {
+    type Error = anyhow::Error;
+
+    fn try_from(upload: SerializedFileStorageUpload) -> anyhow::Result<Self> {
+        Ok(Self {
+            upload_id: upload.upload_id,
+            component: ComponentId::deserialize_from_string(upload.component.as_deref())?,
+            upload_token: upload.upload_token,
+            content_type: upload.content_type,
+            offset: upload.offset,
+            part_tokens: upload.part_tokens,
+            last_part_size: upload.last_part_size,
+            expiration_ts: upload.expiration_ts.try_into()?,
+            claim: upload
+                .claim
+                .map(|claim| {
+                    anyhow::Ok(FileStorageUploadClaim {
+                        claim_id: claim.claim_id,
+                        finishing: claim.finishing,
+                        expiration_ts: claim.expiration_ts.try_into()?,
+                    })
+                })
+                .transpose()?,
+        })
+    }
+}
+
+codegen_convex_serialization!(FileStorageUpload, SerializedFileStorageUpload);
+
+/// A transformed copy of an image in file storage, cached so the transform
+/// only runs once per source object.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(any(test, feature = "testing"), derive(proptest_derive::Arbitrary))]
+pub struct FileStorageImageVariant {
+    /// Object key of the original image.
+    pub source_storage_key: ObjectKey,
+    /// Canonical description of the transform, e.g. `w=200,fit=cover`.
+    pub transform: String,
+    pub storage_key: ObjectKey,
+    pub sha256: Sha256Digest,
+    pub size: i64,
+    pub content_type: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SerializedFileStorageImageVariant {
+    source_storage_key: String,
+    transform: String,
+    storage_key: String,
+    #[serde(with = "serde_bytes")]
+    sha256: Vec<u8>,
+    size: i64,
+    content_type: String,
+}
+
+impl From<FileStorageImageVariant> for SerializedFileStorageImageVariant {
+    fn from(variant: FileStorageImageVariant) -> Self {
+        Self {
+            source_storage_key: variant.source_storage_key.into(),
+            transform: variant.transform,
+            storage_key: variant.storage_key.into(),
+            sha256: variant.sha256.to_vec(),
+            size: variant.size,
+            content_type: variant.content_type,
+        }
+    }
+}
+
+impl TryFrom<SerializedFileStorageImageVariant> for FileStorageImageVariant {
+    type Error = anyhow::Error;
+
+    fn try_from(variant: SerializedFileStorageImageVariant) -> anyhow::Result<Self> {
+        Ok(Self {
+            source_storage_key: variant.source_storage_key.try_into()?,
+            transform: variant.transform,
+            storage_key: variant.storage_key.try_into()?,
+            sha256: variant.sha256.try_into()?,
+            size: variant.size,
+            content_type: variant.content_type,
+        })
+    }
+}
+
+codegen_convex_serialization!(FileStorageImageVariant, SerializedFileStorageImageVariant);
+
 #[cfg(test)]
 mod tests {
     use cmd_util::env::env_config;
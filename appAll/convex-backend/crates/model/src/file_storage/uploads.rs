@@ -0,0 +1,203 @@
+//! State for resumable uploads of user files.
+//!
+//! A resumable upload is backed by a client-driven multipart upload in the
+//! files `Storage`. Each row in `_file_storage_uploads` tracks how many bytes
+//! have been received so a client on a flaky connection can ask where to
+//! resume, and the part tokens needed to finish the upload.
+
+use std::sync::LazyLock;
+
+use common::{
+    document::{
+        ParsedDocument,
+        ResolvedDocument,
+        CREATION_TIME_FIELD_PATH,
+    },
+    query::{
+        IndexRange,
+        IndexRangeExpression,
+        Order,
+        Query,
+    },
+    runtime::Runtime,
+    types::IndexName,
+};
+use database::{
+    defaults::system_index,
+    ResolvedQuery,
+    SystemMetadataModel,
+    Transaction,
+};
+use value::{
+    ConvexValue,
+    FieldPath,
+    ResolvedDocumentId,
+    TableName,
+    TableNamespace,
+};
+
+use crate::{
+    file_storage::types::{
+        FileStorageUpload,
+        FileStorageUploadClaim,
+    },
+    SystemIndex,
+    SystemTable,
+};
+
+pub static FILE_STORAGE_UPLOADS_TABLE: LazyLock<TableName> = LazyLock::new(|| {
+    "_file_storage_uploads"
+        .parse()
+        .expect("invalid built-in file storage uploads table")
+});
+
+pub static FILE_STORAGE_UPLOADS_INDEX_BY_UPLOAD_ID: LazyLock<IndexName> =
+    LazyLock::new(|| system_index(&FILE_STORAGE_UPLOADS_TABLE, "by_upload_id"));
+pub static FILE_STORAGE_UPLOADS_INDEX_BY_EXPIRATION_TS: LazyLock<IndexName> =
+    LazyLock::new(|| system_index(&FILE_STORAGE_UPLOADS_TABLE, "by_expiration_ts"));
+
+static UPLOAD_ID_FIELD: LazyLock<FieldPath> =
+    LazyLock::new(|| "uploadId".parse().expect("invalid uploadId field"));
+static EXPIRATION_TS_FIELD: LazyLock<FieldPath> =
+    LazyLock::new(|| "expirationTs".parse().expect("invalid expirationTs field"));
+
+pub struct FileStorageUploadsTable;
+impl SystemTable for FileStorageUploadsTable {
+    fn table_name(&self) -> &'static TableName {
+        &FILE_STORAGE_UPLOADS_TABLE
+    }
+
+    fn indexes(&self) -> Vec<SystemIndex> {
+        vec![
+            SystemIndex {
+                name: FILE_STORAGE_UPLOADS_INDEX_BY_UPLOAD_ID.clone(),
+                fields: vec![UPLOAD_ID_FIELD.clone(), CREATION_TIME_FIELD_PATH.clone()]
+                    .try_into()
+                    .unwrap(),
+            },
+            SystemIndex {
+                name: FILE_STORAGE_UPLOADS_INDEX_BY_EXPIRATION_TS.clone(),
+                fields: vec![
+                    EXPIRATION_TS_FIELD.clone(),
+                    CREATION_TIME_FIELD_PATH.clone(),
+                ]
+                .try_into()
+                .unwrap(),
+            },
+        ]
+    }
+
+    fn validate_document(&self, document: ResolvedDocument) -> anyhow::Result<()> {
+        ParsedDocument::<FileStorageUpload>::try_from(document).map(|_| ())
+    }
+}
+
+pub struct FileStorageUploadsModel<'a, RT: Runtime> {
+    tx: &'a mut Transaction<RT>,
+}
+
+impl<'a, RT: Runtime> FileStorageUploadsModel<'a, RT> {
+    pub fn new(tx: &'a mut Transaction<RT>) -> Self {
+        Self { tx }
+    }
+
+    pub async fn insert(
+        &mut self,
+        upload: FileStorageUpload,
+    ) -> anyhow::Result<ResolvedDocumentId> {
+        SystemMetadataModel::new_global(self.tx)
+            .insert(&FILE_STORAGE_UPLOADS_TABLE, upload.try_into()?)
+            .await
+    }
+
+    /// Look up an upload that hasn't expired yet.
+    pub async fn get(
+        &mut self,
+        upload_id: &str,
+    ) -> anyhow::Result<Option<ParsedDocument<FileStorageUpload>>> {
+        let index_range = IndexRange {
+            index_name: FILE_STORAGE_UPLOADS_INDEX_BY_UPLOAD_ID.clone(),
+            range: vec![IndexRangeExpression::Eq(
+                UPLOAD_ID_FIELD.clone(),
+                ConvexValue::try_from(upload_id)?.into(),
+            )],
+            order: Order::Asc,
+        };
+        let mut query_stream = ResolvedQuery::new(
+            self.tx,
+            TableNamespace::Global,
+            Query::index_range(index_range),
+        )?;
+        let Some(doc) = query_stream.expect_at_most_one(self.tx).await? else {
+            return Ok(None);
+        };
+        let upload: ParsedDocument<FileStorageUpload> = doc.try_into()?;
+        if upload.expiration_ts < *self.tx.begin_timestamp() {
+            return Ok(None);
+        }
+        Ok(Some(upload))
+    }
+
+    /// Set or clear the request holding `upload`'s claim.
+    pub async fn set_claim(
+        &mut self,
+        upload: ParsedDocument<FileStorageUpload>,
+        claim: Option<FileStorageUploadClaim>,
+    ) -> anyhow::Result<FileStorageUpload> {
+        let (id, mut upload) = upload.into_id_and_value();
+        upload.claim = claim;
+        SystemMetadataModel::new_global(self.tx)
+            .replace(id, upload.clone().try_into()?)
+            .await?;
+        Ok(upload)
+    }
+
+    /// Record a part that was written at `upload.offset`, releasing the claim
+    /// the part was written under.
+    pub async fn record_part(
+        &mut self,
+        upload: ParsedDocument<FileStorageUpload>,
+        part_size: i64,
+        part_token: String,
+    ) -> anyhow::Result<FileStorageUpload> {
+        let (id, mut upload) = upload.into_id_and_value();
+        upload.offset += part_size;
+        upload.part_tokens.push(part_token);
+        upload.last_part_size = part_size;
+        upload.claim = None;
+        SystemMetadataModel::new_global(self.tx)
+            .replace(id, upload.clone().try_into()?)
+            .await?;
+        Ok(upload)
+    }
+
+    pub async fn delete(&mut self, id: ResolvedDocumentId) -> anyhow::Result<()> {
+        SystemMetadataModel::new_global(self.tx).delete(id).await?;
+        Ok(())
+    }
+
+    /// Delete up to `limit` uploads that expired before being finished,
+    /// returning their `ClientDrivenUploadToken`s so the staged parts can be
+    /// aborted once this transaction commits.
+    pub async fn cleanup_expired(&mut self, limit: usize) -> anyhow::Result<Vec<String>> {
+        let index_range = IndexRange {
+            index_name: FILE_STORAGE_UPLOADS_INDEX_BY_EXPIRATION_TS.clone(),
+            range: vec![IndexRangeExpression::Lt(
+                EXPIRATION_TS_FIELD.clone(),
+                ConvexValue::from(i64::from(*self.tx.begin_timestamp())).into(),
+            )],
+            order: Order::Asc,
+        };
+        let query = Query::index_range(index_range).limit(limit);
+        let mut query_stream = ResolvedQuery::new(self.tx, TableNamespace::Global, query)?;
+        let mut upload_tokens = vec![];
+        while let Some(doc) = query_stream.next(self.tx, None).await? {
+            let upload: ParsedDocument<FileStorageUpload> = doc.try_into()?;
+            SystemMetadataModel::new_global(self.tx)
+                .delete(upload.id())
+                .await?;
+            upload_tokens.push(upload.into_value().upload_token);
+        }
+        Ok(upload_tokens)
+    }
+}
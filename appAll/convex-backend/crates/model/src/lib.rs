@@ -102,7 +102,12 @@ use environment_variables::{
     ENVIRONMENT_VARIABLES_TABLE,
 };
 use exports::EXPORTS_BY_STATE_AND_TS_INDEX;
-use file_storage::FILE_STORAGE_ID_INDEX;
+use file_storage::{
+    blobs::FileStorageBlobsTable,
+    image_variants::FileStorageImageVariantsTable,
+    uploads::FileStorageUploadsTable,
+    FILE_STORAGE_ID_INDEX,
+};
 use keybroker::Identity;
 use log_sinks::LogSinksTable;
 use maplit::btreeset;
@@ -140,6 +145,7 @@ use crate::{
     external_packages::ExternalPackagesTable,
     file_storage::FileStorageTable,
     modules::ModulesTable,
+    push_history::PushHistoryTable,
     scheduled_jobs::ScheduledJobsTable,
     session_requests::SessionRequestsTable,
     snapshot_imports::SnapshotImportsTable,
@@ -167,6 +173,7 @@ pub mod log_sinks;
 mod metrics;
 pub mod migrations;
 pub mod modules;
+pub mod push_history;
 pub mod scheduled_jobs;
 pub mod session_requests;
 pub mod snapshot_imports;
@@ -209,9 +216,13 @@ enum DefaultTableNumber {
     ComponentsTable = 32,
     FunctionHandlesTable = 33,
     CanonicalUrls = 34,
+    FileStorageBlobs = 35,
+    FileStorageUploads = 36,
+    FileStorageImageVariants = 37,
+    PushHistory = 38,
     // Keep this number and your user name up to date. The number makes it easy to know
     // what to use next. The username on the same line detects merge conflicts
-    // Next Number - 35 - lee
+    // Next Number - 39 - agent
 }
 
 impl From<DefaultTableNumber> for TableNumber {
@@ -252,6 +263,10 @@ impl From<DefaultTableNumber> for &'static dyn SystemTable {
             DefaultTableNumber::ComponentsTable => &ComponentsTable,
             DefaultTableNumber::FunctionHandlesTable => &FunctionHandlesTable,
             DefaultTableNumber::CanonicalUrls => &CanonicalUrlsTable,
+            DefaultTableNumber::FileStorageBlobs => &FileStorageBlobsTable,
+            DefaultTableNumber::FileStorageUploads => &FileStorageUploadsTable,
+            DefaultTableNumber::FileStorageImageVariants => &FileStorageImageVariantsTable,
+            DefaultTableNumber::PushHistory => &PushHistoryTable,
         }
     }
 }
@@ -453,6 +468,10 @@ pub fn app_system_tables() -> Vec<&'static dyn SystemTable> {
         &SnapshotImportsTable,
         &FunctionHandlesTable,
         &CanonicalUrlsTable,
+        &FileStorageBlobsTable,
+        &FileStorageUploadsTable,
+        &FileStorageImageVariantsTable,
+        &PushHistoryTable,
         &LogSinksTable,
         &AwsLambdaVersionsTable,
         &BackendInfoTable,
@@ -48,7 +48,13 @@ use crate::{
         ExportsModel,
         EXPORTS_TABLE,
     },
+    file_storage::{
+        blobs::FILE_STORAGE_BLOBS_TABLE,
+        image_variants::FILE_STORAGE_IMAGE_VARIANTS_TABLE,
+        uploads::FILE_STORAGE_UPLOADS_TABLE,
+    },
     metrics::log_migration_worker_failed,
+    push_history::PUSH_HISTORY_TABLE,
     snapshot_imports::SnapshotImportModel,
 };
 
@@ -78,7 +84,7 @@ impl fmt::Display for MigrationCompletionCriterion {
 // migrations unless explicitly dropping support.
 // Add a user name next to the version when you make a change to highlight merge
 // conflicts.
-pub const DATABASE_VERSION: DatabaseVersion = 116; // lee
+pub const DATABASE_VERSION: DatabaseVersion = 120; // agent
 
 pub struct MigrationWorker<RT: Runtime> {
     rt: RT,
@@ -376,6 +382,22 @@ impl<RT: Runtime> MigrationWorker<RT> {
             116 => MigrationCompletionCriterion::LogLine(
                 format!("Created system table: {}", *CANONICAL_URLS_TABLE).into(),
             ),
+            117 => MigrationCompletionCriterion::LogLine(
+                format!("Created system table: {}", *FILE_STORAGE_BLOBS_TABLE).into(),
+            ),
+            118 => MigrationCompletionCriterion::LogLine(
+                format!("Created system table: {}", *FILE_STORAGE_UPLOADS_TABLE).into(),
+            ),
+            119 => MigrationCompletionCriterion::LogLine(
+                format!(
+                    "Created system table: {}",
+                    *FILE_STORAGE_IMAGE_VARIANTS_TABLE
+                )
+                .into(),
+            ),
+            120 => MigrationCompletionCriterion::LogLine(
+                format!("Created system table: {}", *PUSH_HISTORY_TABLE).into(),
+            ),
             // NOTE: Make sure to increase DATABASE_VERSION when adding new migrations.
             _ => anyhow::bail!("Version did not define a migration! {}", to_version),
         };
@@ -0,0 +1,190 @@
+//! Policies a route in `http.js` can declare, which the backend enforces
+//! before running the route's HTTP action.
+
+use std::time::Duration;
+
+use errors::ErrorMetadata;
+#[cfg(any(test, feature = "testing"))]
+use proptest::prelude::*;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use value::heap_size::HeapSize;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(any(test, feature = "testing"), derive(proptest_derive::Arbitrary))]
+pub struct HttpRoutePolicy {
+    pub cors: Option<CorsPolicy>,
+    pub rate_limit: Option<HttpRateLimit>,
+}
+
+impl HttpRoutePolicy {
+    pub fn is_empty(&self) -> bool {
+        self.cors.is_none() && self.rate_limit.is_none()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(any(test, feature = "testing"), derive(proptest_derive::Arbitrary))]
+#[cfg_attr(
+    any(test, feature = "testing"),
+    proptest(filter = "|cors| !(cors.allow_credentials && cors.allowed_origins.is_none())")
+)]
+pub struct CorsPolicy {
+    /// Origins allowed to read responses, or `None` to allow any origin.
+    pub allowed_origins: Option<Vec<String>>,
+    /// Request headers allowed in preflight requests. If empty, whatever the
+    /// browser asks for is allowed.
+    pub allowed_headers: Vec<String>,
+    pub exposed_headers: Vec<String>,
+    pub allow_credentials: bool,
+    pub max_age_secs: Option<u32>,
+}
+
+impl CorsPolicy {
+    pub fn allows_origin(&self, origin: &str) -> bool {
+        match self.allowed_origins {
+            None => true,
+            Some(ref origins) => origins.iter().any(|o| o == origin),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(any(test, feature = "testing"), derive(proptest_derive::Arbitrary))]
+pub enum RateLimitKey {
+    /// Limit each client address separately.
+    Ip,
+    /// Limit each authenticated user separately. Unauthenticated requests
+    /// are limited by client address.
+    Identity,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(any(test, feature = "testing"), derive(proptest_derive::Arbitrary))]
+pub struct HttpRateLimit {
+    pub key: RateLimitKey,
+    /// Requests allowed per `period`, which is also the largest burst.
+    #[cfg_attr(any(test, feature = "testing"), proptest(strategy = "1..1000u32"))]
+    pub requests: u32,
+    #[cfg_attr(
+        any(test, feature = "testing"),
+        proptest(strategy = "(1..3_600_000u64).prop_map(Duration::from_millis)")
+    )]
+    pub period: Duration,
+}
+
+impl HeapSize for HttpRoutePolicy {
+    fn heap_size(&self) -> usize {
+        self.cors.heap_size()
+    }
+}
+
+impl HeapSize for CorsPolicy {
+    fn heap_size(&self) -> usize {
+        self.allowed_origins.heap_size()
+            + self.allowed_headers.heap_size()
+            + self.exposed_headers.heap_size()
+    }
+}
+
+/// The policy as `HttpRouter.getRoutes()` returns it, which is also how it's
+/// stored with the analyzed `http.js` module.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SerializedHttpRoutePolicy {
+    cors: Option<SerializedCorsPolicy>,
+    rate_limit: Option<SerializedHttpRateLimit>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SerializedCorsPolicy {
+    allowed_origins: Option<Vec<String>>,
+    allowed_headers: Option<Vec<String>>,
+    exposed_headers: Option<Vec<String>>,
+    allow_credentials: Option<bool>,
+    max_age_secs: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SerializedHttpRateLimit {
+    by: String,
+    requests: u32,
+    period_ms: u64,
+}
+
+impl From<HttpRoutePolicy> for SerializedHttpRoutePolicy {
+    fn from(policy: HttpRoutePolicy) -> Self {
+        Self {
+            cors: policy.cors.map(|cors| SerializedCorsPolicy {
+                allowed_origins: cors.allowed_origins,
+                allowed_headers: Some(cors.allowed_headers),
+                exposed_headers: Some(cors.exposed_headers),
+                allow_credentials: Some(cors.allow_credentials),
+                max_age_secs: cors.max_age_secs,
+            }),
+            rate_limit: policy.rate_limit.map(|limit| SerializedHttpRateLimit {
+                by: match limit.key {
+                    RateLimitKey::Ip => "ip",
+                    RateLimitKey::Identity => "identity",
+                }
+                .to_string(),
+                requests: limit.requests,
+                period_ms: limit.period.as_millis() as u64,
+            }),
+        }
+    }
+}
+
+impl TryFrom<SerializedHttpRoutePolicy> for HttpRoutePolicy {
+    type Error = anyhow::Error;
+
+    fn try_from(policy: SerializedHttpRoutePolicy) -> anyhow::Result<Self> {
+        let cors = policy
+            .cors
+            .map(|cors| {
+                let allow_credentials = cors.allow_credentials.unwrap_or(false);
+                anyhow::ensure!(
+                    !(allow_credentials && cors.allowed_origins.is_none()),
+                    invalid_policy("`cors.allowCredentials` requires `cors.allowedOrigins`")
+                );
+                Ok(CorsPolicy {
+                    allowed_origins: cors.allowed_origins,
+                    allowed_headers: cors.allowed_headers.unwrap_or_default(),
+                    exposed_headers: cors.exposed_headers.unwrap_or_default(),
+                    allow_credentials,
+                    max_age_secs: cors.max_age_secs,
+                })
+            })
+            .transpose()?;
+        let rate_limit = policy
+            .rate_limit
+            .map(|limit| {
+                let key = match &limit.by[..] {
+                    "ip" => RateLimitKey::Ip,
+                    "identity" => RateLimitKey::Identity,
+                    by => anyhow::bail!(invalid_policy(format!(
+                        "`rateLimit.by` must be \"ip\" or \"identity\", not {by:?}"
+                    ))),
+                };
+                anyhow::ensure!(
+                    limit.requests > 0 && limit.period_ms > 0,
+                    invalid_policy("`rateLimit.requests` and `rateLimit.period` must be positive")
+                );
+                Ok(HttpRateLimit {
+                    key,
+                    requests: limit.requests,
+                    period: Duration::from_millis(limit.period_ms),
+                })
+            })
+            .transpose()?;
+        Ok(Self { cors, rate_limit })
+    }
+}
+
+fn invalid_policy(msg: impl Into<String>) -> ErrorMetadata {
+    ErrorMetadata::bad_request("InvalidHttpRoutePolicy", msg.into())
+}
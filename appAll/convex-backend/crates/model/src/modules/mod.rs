@@ -81,6 +81,7 @@ use crate::{
 };
 
 pub mod function_validators;
+pub mod http_route_policy;
 mod metrics;
 pub mod module_versions;
 pub mod types;
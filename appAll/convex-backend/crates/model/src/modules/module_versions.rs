@@ -31,9 +31,15 @@ use value::heap_size::{
     WithHeapSize,
 };
 
-use super::function_validators::{
-    ArgsValidator,
-    ReturnsValidator,
+use super::{
+    function_validators::{
+        ArgsValidator,
+        ReturnsValidator,
+    },
+    http_route_policy::{
+        HttpRoutePolicy,
+        SerializedHttpRoutePolicy,
+    },
 };
 use crate::cron_jobs::types::{
     CronIdentifier,
@@ -403,6 +409,7 @@ impl TryFrom<SerializedHttpActionRoute> for HttpActionRoute {
 pub struct AnalyzedHttpRoute {
     pub route: HttpActionRoute,
     pub pos: Option<AnalyzedSourcePosition>,
+    pub policy: HttpRoutePolicy,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -410,11 +417,12 @@ pub struct AnalyzedHttpRoute {
 struct SerializedAnalyzedHttpRoute {
     route: SerializedHttpActionRoute,
     pos: Option<SerializedAnalyzedSourcePosition>,
+    policy: Option<SerializedHttpRoutePolicy>,
 }
 
 impl HeapSize for AnalyzedHttpRoute {
     fn heap_size(&self) -> usize {
-        self.route.heap_size() + self.pos.heap_size()
+        self.route.heap_size() + self.pos.heap_size() + self.policy.heap_size()
     }
 }
 
@@ -425,6 +433,7 @@ impl TryFrom<AnalyzedHttpRoute> for SerializedAnalyzedHttpRoute {
         Ok(Self {
             route: SerializedHttpActionRoute::try_from(r.route)?,
             pos: r.pos.map(TryFrom::try_from).transpose()?,
+            policy: (!r.policy.is_empty()).then(|| r.policy.into()),
         })
     }
 }
@@ -436,6 +445,11 @@ impl TryFrom<SerializedAnalyzedHttpRoute> for AnalyzedHttpRoute {
         Ok(Self {
             route: HttpActionRoute::try_from(r.route)?,
             pos: r.pos.map(AnalyzedSourcePosition::try_from).transpose()?,
+            policy: r
+                .policy
+                .map(HttpRoutePolicy::try_from)
+                .transpose()?
+                .unwrap_or_default(),
         })
     }
 }
@@ -460,8 +474,8 @@ impl AnalyzedHttpRoutes {
         }
     }
 
-    pub fn route_exact(&self, path: &str, method: RoutableMethod) -> bool {
-        self.routes.iter().any(|AnalyzedHttpRoute { route, .. }| {
+    pub fn route_exact(&self, path: &str, method: RoutableMethod) -> Option<&AnalyzedHttpRoute> {
+        self.routes.iter().find(|AnalyzedHttpRoute { route, .. }| {
             if route.path.ends_with('*') {
                 return false;
             }
@@ -473,9 +487,10 @@ impl AnalyzedHttpRoutes {
         &self,
         path: &RoutedHttpPath,
         method: RoutableMethod,
-    ) -> Option<RoutedHttpPath> {
-        let mut longest_match: Option<RoutedHttpPath> = None;
-        for AnalyzedHttpRoute { route, .. } in &self.routes {
+    ) -> Option<(RoutedHttpPath, &AnalyzedHttpRoute)> {
+        let mut longest_match: Option<(RoutedHttpPath, &AnalyzedHttpRoute)> = None;
+        for analyzed_route in &self.routes {
+            let route = &analyzed_route.route;
             if route.method != method {
                 continue;
             }
@@ -489,14 +504,14 @@ impl AnalyzedHttpRoutes {
                 continue;
             };
             let new_match = RoutedHttpPath(format!("/{match_suffix}"));
-            if let Some(ref existing_suffix) = longest_match {
+            if let Some((ref existing_suffix, _)) = longest_match {
                 // If the existing longest match has a shorter suffix, then it
                 // matches a longer prefix.
                 if existing_suffix.len() < match_suffix.len() {
                     continue;
                 }
             }
-            longest_match = Some(new_match);
+            longest_match = Some((new_match, analyzed_route));
         }
         longest_match
     }
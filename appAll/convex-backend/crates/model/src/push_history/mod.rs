@@ -0,0 +1,112 @@
+//! A bounded history of the configurations pushed to a deployment, so a bad
+//! push can be rolled back to an earlier one without rebuilding it.
+
+use std::sync::LazyLock;
+
+use common::{
+    document::{
+        ParsedDocument,
+        ResolvedDocument,
+    },
+    knobs::PUSH_HISTORY_MAX_ENTRIES,
+    query::{
+        Order,
+        Query,
+    },
+    runtime::Runtime,
+};
+use database::{
+    ResolvedQuery,
+    SystemMetadataModel,
+    Transaction,
+};
+use value::{
+    DeveloperDocumentId,
+    ResolvedDocumentId,
+    TableName,
+    TableNamespace,
+};
+
+use crate::{
+    push_history::types::PushHistoryEntry,
+    SystemIndex,
+    SystemTable,
+};
+
+pub mod types;
+
+/// Module paths the CLI gives a component's bundled `convex.config.ts` and
+/// `schema.ts` in its source package.
+pub const COMPONENT_DEFINITION_FILE_NAME: &str = "convex.config.js";
+pub const SCHEMA_FILE_NAME: &str = "schema.js";
+
+pub static PUSH_HISTORY_TABLE: LazyLock<TableName> = LazyLock::new(|| {
+    "_push_history"
+        .parse()
+        .expect("invalid built-in push history table")
+});
+
+pub struct PushHistoryTable;
+impl SystemTable for PushHistoryTable {
+    fn table_name(&self) -> &'static TableName {
+        &PUSH_HISTORY_TABLE
+    }
+
+    fn indexes(&self) -> Vec<SystemIndex> {
+        vec![]
+    }
+
+    fn validate_document(&self, document: ResolvedDocument) -> anyhow::Result<()> {
+        ParsedDocument::<PushHistoryEntry>::try_from(document).map(|_| ())
+    }
+}
+
+pub struct PushHistoryModel<'a, RT: Runtime> {
+    tx: &'a mut Transaction<RT>,
+}
+
+impl<'a, RT: Runtime> PushHistoryModel<'a, RT> {
+    pub fn new(tx: &'a mut Transaction<RT>) -> Self {
+        Self { tx }
+    }
+
+    /// Record a push, dropping the oldest entries beyond
+    /// [`PUSH_HISTORY_MAX_ENTRIES`].
+    pub async fn record(&mut self, entry: PushHistoryEntry) -> anyhow::Result<ResolvedDocumentId> {
+        let id = SystemMetadataModel::new_global(self.tx)
+            .insert(&PUSH_HISTORY_TABLE, entry.try_into()?)
+            .await?;
+        let entries = self.list().await?;
+        for entry in entries.into_iter().skip(*PUSH_HISTORY_MAX_ENTRIES) {
+            SystemMetadataModel::new_global(self.tx)
+                .delete(entry.id())
+                .await?;
+        }
+        Ok(id)
+    }
+
+    /// All recorded pushes, newest first.
+    pub async fn list(&mut self) -> anyhow::Result<Vec<ParsedDocument<PushHistoryEntry>>> {
+        // The table is small, so a full scan in creation order is fine.
+        let query = Query::full_table_scan(PUSH_HISTORY_TABLE.clone(), Order::Desc);
+        let mut query_stream = ResolvedQuery::new(self.tx, TableNamespace::Global, query)?;
+        let mut entries = vec![];
+        while let Some(doc) = query_stream.next(self.tx, None).await? {
+            entries.push(doc.try_into()?);
+        }
+        Ok(entries)
+    }
+
+    pub async fn get(
+        &mut self,
+        id: DeveloperDocumentId,
+    ) -> anyhow::Result<Option<ParsedDocument<PushHistoryEntry>>> {
+        let query = Query::get(PUSH_HISTORY_TABLE.clone(), id);
+        let mut query_stream = ResolvedQuery::new(self.tx, TableNamespace::Global, query)?;
+        query_stream
+            .expect_at_most_one(self.tx)
+            .await?
+            .map(|doc| doc.try_into())
+            .transpose()
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::{
+    BTreeMap,
+    BTreeSet,
+};
+
+use common::{
+    auth::AuthInfo,
+    components::ComponentDefinitionPath,
+};
+use semver::Version;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use value::{
+    codegen_convex_serialization,
+    ConvexObject,
+    DeveloperDocumentId,
+};
+
+use crate::{
+    auth::types::AuthInfoPersisted,
+    external_packages::types::ExternalDepsPackageId,
+    source_packages::types::SourcePackage,
+};
+
+/// A configuration pushed to the deployment, with everything needed to push
+/// it again. The modules themselves live in the source packages, which are
+/// never deleted from storage.
+#[derive(Clone, Debug)]
+#[cfg_attr(
+    any(test, feature = "testing"),
+    derive(proptest_derive::Arbitrary, PartialEq)
+)]
+pub struct PushHistoryEntry {
+    /// The earlier entry this push rolled back to, if it was a rollback.
+    pub rollback_of: Option<DeveloperDocumentId>,
+    pub external_deps_id: Option<ExternalDepsPackageId>,
+    #[cfg_attr(
+        any(test, feature = "testing"),
+        proptest(
+            strategy = "proptest::collection::btree_map(proptest::prelude::any::<ComponentDefinitionPath>(), proptest::prelude::any::<PushedComponentDefinition>(), 0..3)"
+        )
+    )]
+    pub definitions: BTreeMap<ComponentDefinitionPath, PushedComponentDefinition>,
+    #[cfg_attr(
+        any(test, feature = "testing"),
+        proptest(
+            strategy = "proptest::collection::vec(proptest::prelude::any::<AuthInfo>(), 0..2)"
+        )
+    )]
+    pub auth_info: Vec<AuthInfo>,
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(
+    any(test, feature = "testing"),
+    derive(proptest_derive::Arbitrary, PartialEq)
+)]
+pub struct PushedComponentDefinition {
+    pub source_package: SourcePackage,
+    pub dependencies: BTreeSet<ComponentDefinitionPath>,
+    #[cfg_attr(
+        any(test, feature = "testing"),
+        proptest(value = "Version::new(1, 17, 0)")
+    )]
+    pub udf_server_version: Version,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SerializedPushHistoryEntry {
+    rollback_of: Option<String>,
+    external_deps_id: Option<String>,
+    definitions: Vec<SerializedPushedComponentDefinition>,
+    auth_info: Vec<ConvexObject>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SerializedPushedComponentDefinition {
+    definition_path: String,
+    source_package: ConvexObject,
+    dependencies: Vec<String>,
+    udf_server_version: String,
+}
+
+impl TryFrom<PushHistoryEntry> for SerializedPushHistoryEntry {
+    type Error = anyhow::Error;
+
+    fn try_from(value: PushHistoryEntry) -> Result<Self, Self::Error> {
+        Ok(Self {
+            rollback_of: value.rollback_of.map(String::from),
+            external_deps_id: value
+                .external_deps_id
+                .map(|id| String::from(DeveloperDocumentId::from(id))),
+            definitions: value
+                .definitions
+                .into_iter()
+                .map(|(definition_path, definition)| {
+                    Ok(SerializedPushedComponentDefinition {
+                        definition_path: String::from(definition_path),
+                        source_package: definition.source_package.try_into()?,
+                        dependencies: definition
+                            .dependencies
+                            .into_iter()
+                            .map(String::from)
+                            .collect(),
+                        udf_server_version: definition.udf_server_version.to_string(),
+                    })
+                })
+                .collect::<anyhow::Result<_>>()?,
+            auth_info: value
+                .auth_info
+                .into_iter()
+                .map(|info| ConvexObject::try_from(AuthInfoPersisted(info)))
+                .collect::<anyhow::Result<_>>()?,
+        })
+    }
+}
+
+impl TryFrom<SerializedPushHistoryEntry> for PushHistoryEntry {
+    type Error = anyhow::Error;
+
+    fn try_from(value: SerializedPushHistoryEntry) -> Result<Self, Self::Error> {
+        Ok(Self {
+            rollback_of: value.rollback_of.map(|id| id.parse()).transpose()?,
+            external_deps_id: value
+                .external_deps_id
+                .map(|id| anyhow::Ok(id.parse::<DeveloperDocumentId>()?.into()))
+                .transpose()?,
+            definitions: value
+                .definitions
+                .into_iter()
+                .map(|definition| {
+                    Ok((
+                        definition.definition_path.parse()?,
+                        PushedComponentDefinition {
+                            source_package: definition.source_package.try_into()?,
+                            dependencies: definition
+                                .dependencies
+                                .into_iter()
+                                .map(|path| path.parse())
+                                .collect::<anyhow::Result<_>>()?,
+                            udf_server_version: definition.udf_server_version.parse()?,
+                        },
+                    ))
+                })
+                .collect::<anyhow::Result<_>>()?,
+            auth_info: value
+                .auth_info
+                .into_iter()
+                .map(|info| Ok(AuthInfoPersisted::try_from(info)?.0))
+                .collect::<anyhow::Result<_>>()?,
+        })
+    }
+}
+
+codegen_convex_serialization!(
+    PushHistoryEntry,
+    SerializedPushHistoryEntry,
+    test_cases = 64
+);
@@ -5,7 +5,10 @@ use std::{
 
 use common::{
     obj,
-    types::ObjectKey,
+    types::{
+        NodeVersion,
+        ObjectKey,
+    },
 };
 use errors::ErrorMetadata;
 use humansize::{
@@ -29,6 +32,9 @@ pub struct SourcePackage {
     pub sha256: Sha256Digest,
     pub external_deps_package_id: Option<ExternalDepsPackageId>,
     pub package_size: PackageSize,
+    /// Node.js version chosen by the project config when this package was
+    /// pushed. `None` for packages pushed before it was configurable.
+    pub node_version: Option<NodeVersion>,
 }
 
 #[cfg_attr(any(test, feature = "testing"), derive(proptest_derive::Arbitrary))]
@@ -174,6 +180,7 @@ impl TryFrom<SourcePackage> for ConvexObject {
             sha256,
             external_deps_package_id,
             package_size,
+            node_version,
         }: SourcePackage,
     ) -> Result<Self, Self::Error> {
         let storage_key: String = storage_key.into();
@@ -185,6 +192,10 @@ impl TryFrom<SourcePackage> for ConvexObject {
                 .transpose()?
                 .unwrap_or(ConvexValue::Null),
             "packageSize" => ConvexValue::Object(package_size.try_into()?),
+            "nodeVersion" => match node_version {
+                Some(node_version) => ConvexValue::try_from(node_version.to_string())?,
+                None => ConvexValue::Null,
+            },
         )
     }
 }
@@ -213,11 +224,17 @@ impl TryFrom<ConvexObject> for SourcePackage {
             None => PackageSize::default(),
             _ => anyhow::bail!("Invalid 'packageSize' in {object_fields:?}"),
         };
+        let node_version = match object_fields.remove("nodeVersion") {
+            Some(ConvexValue::Null) | None => None,
+            Some(ConvexValue::String(s)) => Some(s.parse()?),
+            _ => anyhow::bail!("Invalid 'nodeVersion' in {object_fields:?}"),
+        };
         Ok(Self {
             storage_key,
             sha256,
             external_deps_package_id: external_package_id,
             package_size,
+            node_version,
         })
     }
 }
@@ -113,24 +113,53 @@ where
 
 struct MySQLFormatArguments<'a> {
     db_name: &'a str,
+    /// `db_name.table_prefix`, substituted for `@db_name.` in front of table
+    /// names.
+    table_qualifier: String,
     params: Vec<String>,
 }
 
+impl<'a> MySQLFormatArguments<'a> {
+    fn new(db_name: &'a str, table_prefix: &str, params: Vec<String>) -> Self {
+        Self {
+            db_name,
+            table_qualifier: format!("{db_name}.{table_prefix}"),
+            params,
+        }
+    }
+}
+
 impl FormatArgs for MySQLFormatArguments<'_> {
     fn get_index(&self, index: usize) -> Result<Option<dynfmt::Argument<'_>>, ()> {
         self.params.get_index(index)
     }
 
     fn get_key(&self, key: &str) -> Result<Option<dynfmt::Argument<'_>>, ()> {
-        if key != "db_name" {
-            panic!("Unexpected named argument {key}");
+        match key {
+            "db_name" => Ok(Some(&self.db_name)),
+            "table_qualifier" => Ok(Some(&self.table_qualifier)),
+            _ => panic!("Unexpected named argument {key}"),
         }
-        Ok(Some(&self.db_name))
     }
 }
 
 const DB_NAME_ARGUMENT_PATTERN: &str = "@db_name";
 
+// Finds @db_name arguments. When followed by a `.` the argument qualifies a
+// table name, so it also covers the deployment's table prefix.
+fn db_name_args(format: &str) -> impl Iterator<Item = Result<ArgumentSpec<'_>, Error<'_>>> {
+    format
+        .match_indices(DB_NAME_ARGUMENT_PATTERN)
+        .map(move |(index, _)| {
+            let end = index + DB_NAME_ARGUMENT_PATTERN.len();
+            Ok(if format[end..].starts_with('.') {
+                ArgumentSpec::new(index, end + 1).with_position(Position::Key("table_qualifier"))
+            } else {
+                ArgumentSpec::new(index, end).with_position(Position::Key("db_name"))
+            })
+        })
+}
+
 // Formats both @db_name and ?
 struct MySQLRawStatementFormat;
 
@@ -138,14 +167,7 @@ impl<'f> Format<'f> for MySQLRawStatementFormat {
     type Iter = impl Iterator<Item = Result<ArgumentSpec<'f>, Error<'f>>>;
 
     fn iter_args(&self, format: &'f str) -> Result<Self::Iter, Error<'f>> {
-        let db_name_iter = format
-            .match_indices(DB_NAME_ARGUMENT_PATTERN)
-            .map(|(index, _)| {
-                Ok(
-                    ArgumentSpec::new(index, index + DB_NAME_ARGUMENT_PATTERN.len())
-                        .with_position(Position::Key("db_name")),
-                )
-            });
+        let db_name_iter = db_name_args(format);
         let args_iter = format
             .match_indices('?')
             .map(|(index, _)| Ok(ArgumentSpec::new(index, index + 1)));
@@ -163,13 +185,15 @@ impl<'f> Format<'f> for MySQLRawStatementFormat {
 // used with the text protocol.
 fn format_mysql_text_protocol(
     db_name: &str,
+    table_prefix: &str,
     statement: &'static str,
     params: Vec<MySqlValue>,
     labels: &[StaticMetricLabel],
 ) -> anyhow::Result<String> {
-    let args = MySQLFormatArguments {
+    let args = MySQLFormatArguments::new(
         db_name,
-        params: params
+        table_prefix,
+        params
             .into_iter()
             .map(|p| match p {
                 MySqlValue::NULL => "NULL".to_owned(),
@@ -183,7 +207,7 @@ fn format_mysql_text_protocol(
                 MySqlValue::Time(..) => panic!("Time MySQL argument not supported"),
             })
             .collect(),
-    };
+    );
     let result = MySQLRawStatementFormat.format(statement, args)?.to_string();
     if result.len() > LARGE_STATEMENT_THRESHOLD {
         log_large_statement(labels.to_vec());
@@ -198,26 +222,19 @@ impl<'f> Format<'f> for MySQLPreparedStatementFormat {
     type Iter = impl Iterator<Item = Result<ArgumentSpec<'f>, Error<'f>>>;
 
     fn iter_args(&self, format: &'f str) -> Result<Self::Iter, Error<'f>> {
-        Ok::<Self::Iter, _>(
-            format
-                .match_indices(DB_NAME_ARGUMENT_PATTERN)
-                .map(|(index, _)| {
-                    Ok(
-                        ArgumentSpec::new(index, index + DB_NAME_ARGUMENT_PATTERN.len())
-                            .with_position(Position::Key("db_name")),
-                    )
-                }),
-        )
+        Ok::<Self::Iter, _>(db_name_args(format))
     }
 }
 
 // Formats a MySQL query by only replacing the @db_name but leaves positional
 // arguments alone. To be used with MySQL binary protocol.
-fn format_mysql_binary_protocol(db_name: &str, statement: &'static str) -> anyhow::Result<String> {
-    let args = MySQLFormatArguments {
-        db_name,
-        params: vec![], // No positional arguments.
-    };
+fn format_mysql_binary_protocol(
+    db_name: &str,
+    table_prefix: &str,
+    statement: &'static str,
+) -> anyhow::Result<String> {
+    // No positional arguments.
+    let args = MySQLFormatArguments::new(db_name, table_prefix, vec![]);
     Ok(MySQLPreparedStatementFormat
         .format(statement, args)?
         .to_string())
@@ -228,6 +245,7 @@ pub(crate) struct MySqlConnection<'a> {
     labels: Vec<StaticMetricLabel>,
     use_prepared_statements: bool,
     db_name: &'a str,
+    table_prefix: &'a str,
     _tracker: ConnectionTracker,
     _timer: Timer<VMHistogramVec>,
 }
@@ -237,7 +255,13 @@ impl MySqlConnection<'_> {
     #[fastrace::trace]
     pub async fn execute_many(&mut self, query: &'static str) -> anyhow::Result<()> {
         log_execute(self.labels.clone());
-        let statement = format_mysql_text_protocol(self.db_name, query, vec![], &self.labels)?;
+        let statement = format_mysql_text_protocol(
+            self.db_name,
+            self.table_prefix,
+            query,
+            vec![],
+            &self.labels,
+        )?;
         with_timeout(self.conn.query_iter(statement)).await?;
         Ok(())
     }
@@ -251,11 +275,17 @@ impl MySqlConnection<'_> {
     ) -> anyhow::Result<Option<Row>> {
         log_query(self.labels.clone());
         let future = if self.use_prepared_statements {
-            let statement = format_mysql_binary_protocol(self.db_name, statement)?;
+            let statement =
+                format_mysql_binary_protocol(self.db_name, self.table_prefix, statement)?;
             self.conn.exec_first(statement, params)
         } else {
-            let statement =
-                format_mysql_text_protocol(self.db_name, statement, params, &self.labels)?;
+            let statement = format_mysql_text_protocol(
+                self.db_name,
+                self.table_prefix,
+                statement,
+                params,
+                &self.labels,
+            )?;
             self.conn.query_first(statement)
         };
         let row = with_timeout(future).await?;
@@ -282,13 +312,19 @@ impl MySqlConnection<'_> {
         let progress_counter = query_progress_counter(size_hint, labels.clone());
         log_query(labels.clone());
         let stream = if self.use_prepared_statements {
-            let statement = format_mysql_binary_protocol(self.db_name, statement)?;
+            let statement =
+                format_mysql_binary_protocol(self.db_name, self.table_prefix, statement)?;
             with_timeout(self.conn.exec_stream(statement, Params::Positional(params)))
                 .await?
                 .boxed()
         } else {
-            let statement =
-                format_mysql_text_protocol(self.db_name, statement, params, &self.labels)?;
+            let statement = format_mysql_text_protocol(
+                self.db_name,
+                self.table_prefix,
+                statement,
+                params,
+                &self.labels,
+            )?;
             with_timeout(self.conn.query_stream(statement))
                 .await?
                 .boxed()
@@ -328,13 +364,19 @@ impl MySqlConnection<'_> {
     ) -> anyhow::Result<u64> {
         log_execute(self.labels.clone());
         let affected_rows = if self.use_prepared_statements {
-            let statement = format_mysql_binary_protocol(self.db_name, statement)?;
+            let statement =
+                format_mysql_binary_protocol(self.db_name, self.table_prefix, statement)?;
             with_timeout(self.conn.exec_iter(statement, Params::Positional(params)))
                 .await?
                 .affected_rows()
         } else {
-            let statement =
-                format_mysql_text_protocol(self.db_name, statement, params, &self.labels)?;
+            let statement = format_mysql_text_protocol(
+                self.db_name,
+                self.table_prefix,
+                statement,
+                params,
+                &self.labels,
+            )?;
             with_timeout(self.conn.query_iter(statement))
                 .await?
                 .affected_rows()
@@ -355,6 +397,7 @@ impl MySqlConnection<'_> {
             inner,
             use_prepared_statements: self.use_prepared_statements,
             db_name: self.db_name,
+            table_prefix: self.table_prefix,
             labels: &self.labels,
         })
     }
@@ -364,6 +407,7 @@ pub(crate) struct MySqlTransaction<'a> {
     inner: mysql_async::Transaction<'a>,
     use_prepared_statements: bool,
     db_name: &'a str,
+    table_prefix: &'a str,
     labels: &'a [StaticMetricLabel],
 }
 
@@ -376,11 +420,17 @@ impl MySqlTransaction<'_> {
         params: Vec<MySqlValue>,
     ) -> anyhow::Result<Option<Row>> {
         let future = if self.use_prepared_statements {
-            let statement = format_mysql_binary_protocol(self.db_name, statement)?;
+            let statement =
+                format_mysql_binary_protocol(self.db_name, self.table_prefix, statement)?;
             self.inner.exec_first(statement, Params::Positional(params))
         } else {
-            let statement =
-                format_mysql_text_protocol(self.db_name, statement, params, self.labels)?;
+            let statement = format_mysql_text_protocol(
+                self.db_name,
+                self.table_prefix,
+                statement,
+                params,
+                self.labels,
+            )?;
             self.inner.query_first(statement)
         };
         with_timeout(future).await
@@ -393,11 +443,17 @@ impl MySqlTransaction<'_> {
         params: Vec<MySqlValue>,
     ) -> anyhow::Result<()> {
         let future = if self.use_prepared_statements {
-            let statement = format_mysql_binary_protocol(self.db_name, statement)?;
+            let statement =
+                format_mysql_binary_protocol(self.db_name, self.table_prefix, statement)?;
             self.inner.exec_drop(statement, Params::Positional(params))
         } else {
-            let statement =
-                format_mysql_text_protocol(self.db_name, statement, params, self.labels)?;
+            let statement = format_mysql_text_protocol(
+                self.db_name,
+                self.table_prefix,
+                statement,
+                params,
+                self.labels,
+            )?;
             self.inner.query_drop(statement)
         };
         with_timeout(future).await
@@ -410,13 +466,19 @@ impl MySqlTransaction<'_> {
         params: Vec<MySqlValue>,
     ) -> anyhow::Result<u64> {
         let affected_rows = if self.use_prepared_statements {
-            let statement = format_mysql_binary_protocol(self.db_name, statement)?;
+            let statement =
+                format_mysql_binary_protocol(self.db_name, self.table_prefix, statement)?;
             with_timeout(self.inner.exec_iter(statement, Params::Positional(params)))
                 .await?
                 .affected_rows()
         } else {
-            let statement =
-                format_mysql_text_protocol(self.db_name, statement, params, self.labels)?;
+            let statement = format_mysql_text_protocol(
+                self.db_name,
+                self.table_prefix,
+                statement,
+                params,
+                self.labels,
+            )?;
             with_timeout(self.inner.query_iter(statement))
                 .await?
                 .affected_rows()
@@ -489,6 +551,7 @@ impl<RT: Runtime> ConvexMySqlPool<RT> {
         &self,
         name: &'static str,
         db_name: &'a str,
+        table_prefix: &'a str,
     ) -> anyhow::Result<MySqlConnection<'a>> {
         let pool_get_timer = get_connection_timer(&self.cluster_name);
         let conn = with_timeout(self.pool.get_conn()).await;
@@ -501,6 +564,7 @@ impl<RT: Runtime> ConvexMySqlPool<RT> {
             ],
             use_prepared_statements: self.use_prepared_statements,
             db_name,
+            table_prefix,
             _tracker: ConnectionTracker::new(&self.stats),
             _timer: connection_lifetime_timer(name, &self.cluster_name),
         })
@@ -544,6 +608,7 @@ mod tests {
     fn test_format_mysql_text_protocol() -> anyhow::Result<()> {
         let encoded = format_mysql_text_protocol(
             "presley_db",
+            "",
             r#"
     SELECT * FROM @db_name.indexes
     WHERE (key, value) IN (?, ?)
@@ -567,6 +632,7 @@ mod tests {
     fn test_format_mysql_binary_protocol() -> anyhow::Result<()> {
         let encoded = format_mysql_binary_protocol(
             "presley_db",
+            "",
             r#"
     SELECT * FROM @db_name.indexes
     WHERE (key, value) IN (?, ?)
@@ -584,6 +650,32 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_format_mysql_table_prefix() -> anyhow::Result<()> {
+        let statement = r#"
+    SELECT COUNT(1) FROM INFORMATION_SCHEMA.TABLES WHERE TABLE_SCHEMA = '@db_name';
+    SELECT * FROM @db_name.indexes WHERE key = ?
+"#;
+        let expected = r#"
+    SELECT COUNT(1) FROM INFORMATION_SCHEMA.TABLES WHERE TABLE_SCHEMA = 'presley_db';
+    SELECT * FROM presley_db.staging_indexes WHERE key = "#;
+        assert_eq!(
+            format_mysql_text_protocol(
+                "presley_db",
+                "staging_",
+                statement,
+                vec![MySqlValue::from(1)],
+                &[]
+            )?,
+            format!("{expected}1\n")
+        );
+        assert_eq!(
+            format_mysql_binary_protocol("presley_db", "staging_", statement)?,
+            format!("{expected}?\n")
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_derive_cluster_name() -> anyhow::Result<()> {
         assert_eq!(
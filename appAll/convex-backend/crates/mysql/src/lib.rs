@@ -20,6 +20,7 @@ use std::{
     },
     fmt::Write,
     future::Future,
+    iter,
     ops::Bound,
     pin::Pin,
     sync::{
@@ -79,6 +80,7 @@ use common::{
         RetentionValidator,
         TimestampRange,
     },
+    persistence_compression::DocumentCompressor,
     query::Order,
     runtime::Runtime,
     sha256::Sha256,
@@ -136,6 +138,8 @@ pub struct MySqlPersistence<RT: Runtime> {
     // Used by the reader.
     read_pool: Arc<ConvexMySqlPool<RT>>,
     db_name: String,
+    table_prefix: String,
+    compressor: Arc<DocumentCompressor>,
     version: PersistenceVersion,
 }
 
@@ -152,11 +156,15 @@ pub struct MySqlOptions {
     pub allow_read_only: bool,
     pub version: PersistenceVersion,
     pub use_prepared_statements: bool,
+    /// Prepended to the name of every table, so several deployments can share
+    /// a database. Empty by default.
+    pub table_prefix: String,
 }
 
 pub struct MySqlReaderOptions {
     pub db_should_be_leader: bool,
     pub version: PersistenceVersion,
+    pub table_prefix: String,
 }
 
 impl<RT: Runtime> MySqlPersistence<RT> {
@@ -166,10 +174,12 @@ impl<RT: Runtime> MySqlPersistence<RT> {
         options: MySqlOptions,
         lease_lost_shutdown: ShutdownSignal,
     ) -> Result<Self, ConnectError> {
+        validate_table_prefix(&options.table_prefix)?;
+        let table_prefix = options.table_prefix;
         let newly_created = {
-            let mut client = pool.acquire("init_sql", &db_name).await?;
+            let mut client = pool.acquire("init_sql", &db_name, &table_prefix).await?;
             let table_count: usize = client
-                .query_optional(GET_TABLE_COUNT, vec![])
+                .query_optional(GET_TABLE_COUNT, persistence_table_params(&table_prefix))
                 .await
                 .map_err(Into::<anyhow::Error>::into)?
                 .context("GET_TABLE_COUNT query returned no rows?")?
@@ -189,17 +199,25 @@ impl<RT: Runtime> MySqlPersistence<RT> {
             }
             Self::check_newly_created(&mut client).await?
         };
-        let mut client = pool.acquire("read_only", &db_name).await?;
+        let mut client = pool.acquire("read_only", &db_name, &table_prefix).await?;
         if !options.allow_read_only && Self::is_read_only(&mut client).await? {
             return Err(ConnectError::ReadOnly);
         }
 
-        let lease = Lease::acquire(pool.clone(), db_name.clone(), lease_lost_shutdown).await?;
+        let lease = Lease::acquire(
+            pool.clone(),
+            db_name.clone(),
+            table_prefix.clone(),
+            lease_lost_shutdown,
+        )
+        .await?;
         Ok(Self {
             newly_created: newly_created.into(),
             lease,
             read_pool: pool,
             db_name,
+            table_prefix,
+            compressor: Arc::new(DocumentCompressor::new()),
             version: options.version,
         })
     }
@@ -208,13 +226,17 @@ impl<RT: Runtime> MySqlPersistence<RT> {
         pool: Arc<ConvexMySqlPool<RT>>,
         db_name: String,
         options: MySqlReaderOptions,
-    ) -> MySqlReader<RT> {
-        MySqlReader {
+    ) -> anyhow::Result<MySqlReader<RT>> {
+        validate_table_prefix(&options.table_prefix)?;
+        Ok(MySqlReader {
             db_name,
+            table_prefix: options.table_prefix,
             read_pool: pool,
+            // Dictionaries are loaded the first time a document needs one.
+            compressor: Arc::new(DocumentCompressor::new()),
             db_should_be_leader: options.db_should_be_leader,
             version: options.version,
-        }
+        })
     }
 
     async fn is_read_only(client: &mut MySqlConnection<'_>) -> anyhow::Result<bool> {
@@ -235,10 +257,13 @@ impl<RT: Runtime> MySqlPersistence<RT> {
     pub(crate) async fn get_table_count(&self) -> anyhow::Result<usize> {
         let mut client = self
             .read_pool
-            .acquire("get_table_count", &self.db_name)
+            .acquire("get_table_count", &self.db_name, &self.table_prefix)
             .await?;
         client
-            .query_optional(GET_TABLE_COUNT, vec![])
+            .query_optional(
+                GET_TABLE_COUNT,
+                persistence_table_params(&self.table_prefix),
+            )
             .await
             .map_err(Into::<anyhow::Error>::into)?
             .context("GET_TABLE_COUNT query returned no rows?")?
@@ -256,7 +281,9 @@ impl<RT: Runtime> Persistence for MySqlPersistence<RT> {
     fn reader(&self) -> Arc<dyn PersistenceReader> {
         Arc::new(MySqlReader {
             db_name: self.db_name.clone(),
+            table_prefix: self.table_prefix.clone(),
             read_pool: self.read_pool.clone(),
+            compressor: self.compressor.clone(),
             db_should_be_leader: true,
             version: self.version,
         })
@@ -298,6 +325,7 @@ impl<RT: Runtime> Persistence for MySqlPersistence<RT> {
         // True, the below might end up failing and not changing anything.
         self.newly_created.store(false, SeqCst);
         let cluster_name = self.read_pool.cluster_name().to_owned();
+        let compressor = self.compressor.clone();
         self.lease
             .transact(move |tx| {
                 async move {
@@ -316,12 +344,13 @@ impl<RT: Runtime> Persistence for MySqlPersistence<RT> {
                             let mut insert_document_chunk = vec![];
                             for update in chunk {
                                 insert_document_chunk = document_params(
+                                    &compressor,
                                     insert_document_chunk,
                                     update.ts,
                                     update.id,
                                     update.value.clone(),
                                     update.prev_ts,
-                                );
+                                )?;
                             }
                             let future = async {
                                 let timer =
@@ -448,7 +477,7 @@ impl<RT: Runtime> Persistence for MySqlPersistence<RT> {
     ) -> anyhow::Result<Vec<IndexEntry>> {
         let mut client = self
             .read_pool
-            .acquire("load_index_chunk", &self.db_name)
+            .acquire("load_index_chunk", &self.db_name, &self.table_prefix)
             .await?;
         let stmt = LOAD_INDEXES_PAGE;
         let mut params = MySqlReader::<RT>::_index_cursor_params(cursor.as_ref());
@@ -512,12 +541,18 @@ impl<RT: Runtime> Persistence for MySqlPersistence<RT> {
     async fn shutdown(&self) -> anyhow::Result<()> {
         self.read_pool.clone().shutdown().await
     }
+
+    fn document_compressor(&self) -> Option<Arc<DocumentCompressor>> {
+        Some(self.compressor.clone())
+    }
 }
 
 #[derive(Clone)]
 pub struct MySqlReader<RT: Runtime> {
     read_pool: Arc<ConvexMySqlPool<RT>>,
     db_name: String,
+    table_prefix: String,
+    compressor: Arc<DocumentCompressor>,
     /// Set `db_should_be_leader` if this PostgresReader should be connected
     /// to the database leader. In particular, we protect against heterogenous
     /// connection pools where one connection is to the leader and another is to
@@ -535,7 +570,7 @@ impl<RT: Runtime> MySqlReader<RT> {
         }
     }
 
-    fn row_to_document(
+    async fn row_to_document(
         &self,
         row: Row,
     ) -> anyhow::Result<(
@@ -544,11 +579,11 @@ impl<RT: Runtime> MySqlReader<RT> {
         Option<ResolvedDocument>,
         Option<Timestamp>,
     )> {
-        let (ts, id, doc, prev_ts) = self.row_to_document_inner(row)?;
+        let (ts, id, doc, prev_ts) = self.row_to_document_inner(row).await?;
         Ok((ts, id, doc, prev_ts))
     }
 
-    fn row_to_document_inner(
+    async fn row_to_document_inner(
         &self,
         row: Row,
     ) -> anyhow::Result<(
@@ -563,6 +598,10 @@ impl<RT: Runtime> MySqlReader<RT> {
         let ts = Timestamp::try_from(ts)?;
         let table_b: Vec<u8> = row.get(2).unwrap();
         let json_value: Vec<u8> = row.get(3).unwrap();
+        let json_value = self
+            .compressor
+            .decode_with_reader(self, &json_value)
+            .await?;
         let json_value: JsonValue = serde_json::from_slice(&json_value)?;
         let deleted: bool = row.get(4).unwrap();
         let table = TabletId(table_b.try_into()?);
@@ -595,7 +634,7 @@ impl<RT: Runtime> MySqlReader<RT> {
         let timer = metrics::load_documents_timer(self.read_pool.cluster_name());
         let mut client = self
             .read_pool
-            .acquire("load_documents", &self.db_name)
+            .acquire("load_documents", &self.db_name, &self.table_prefix)
             .await?;
         let mut num_returned = 0;
         let mut num_skipped_by_table = 0;
@@ -633,7 +672,7 @@ impl<RT: Runtime> MySqlReader<RT> {
             futures::pin_mut!(row_stream);
 
             while let Some(row) = row_stream.try_next().await? {
-                let (ts, document_id, document, prev_ts) = self.row_to_document(row)?;
+                let (ts, document_id, document, prev_ts) = self.row_to_document(row).await?;
                 rows_loaded += 1;
                 last_ts = ts;
                 last_tablet_id_param = internal_id_param(document_id.table().0);
@@ -736,7 +775,10 @@ impl<RT: Runtime> MySqlReader<RT> {
                 let mut to_yield = vec![];
                 // Avoid holding connections across yield points, to limit lifetime
                 // and improve fairness.
-                let mut client = self.read_pool.acquire("index_scan", &self.db_name).await?;
+                let mut client = self
+                    .read_pool
+                    .acquire("index_scan", &self.db_name, &self.table_prefix)
+                    .await?;
                 stats.sql_statements += 1;
                 let (query, params) = index_query(
                     index_id,
@@ -819,6 +861,10 @@ impl<RT: Runtime> MySqlReader<RT> {
                         anyhow::anyhow!("Dangling index reference for {:?} {:?}", key, ts)
                     })?;
                     let json_value: Vec<u8> = row.get(8).unwrap();
+                    let json_value = self
+                        .compressor
+                        .decode_with_reader(self, &json_value)
+                        .await?;
                     let json_value: JsonValue = serde_json::from_slice(&json_value)?;
                     anyhow::ensure!(
                         json_value != serde_json::Value::Null,
@@ -987,7 +1033,11 @@ impl<RT: Runtime> PersistenceReader for MySqlReader<RT> {
 
         let mut client = self
             .read_pool
-            .acquire("previous_revisions_of_documents", &self.db_name)
+            .acquire(
+                "previous_revisions_of_documents",
+                &self.db_name,
+                &self.table_prefix,
+            )
             .await?;
         let ids: Vec<_> = ids.into_iter().collect();
 
@@ -1013,7 +1063,7 @@ impl<RT: Runtime> PersistenceReader for MySqlReader<RT> {
         for row in results.into_iter() {
             let ts: i64 = row.get(6).unwrap();
             let ts = Timestamp::try_from(ts)?;
-            let (prev_ts, id, maybe_doc, prev_prev_ts) = self.row_to_document(row)?;
+            let (prev_ts, id, maybe_doc, prev_prev_ts) = self.row_to_document(row).await?;
             anyhow::ensure!(result
                 .insert(
                     DocumentPrevTsQuery { id, ts, prev_ts },
@@ -1047,7 +1097,7 @@ impl<RT: Runtime> PersistenceReader for MySqlReader<RT> {
 
         let mut client = self
             .read_pool
-            .acquire("previous_revisions", &self.db_name)
+            .acquire("previous_revisions", &self.db_name, &self.table_prefix)
             .await?;
         let ids: Vec<_> = ids.into_iter().collect();
 
@@ -1076,7 +1126,7 @@ impl<RT: Runtime> PersistenceReader for MySqlReader<RT> {
         for row in results.into_iter() {
             let ts: i64 = row.get(6).unwrap();
             let ts = Timestamp::try_from(ts)?;
-            let (prev_ts, id, maybe_doc, prev_prev_ts) = self.row_to_document(row)?;
+            let (prev_ts, id, maybe_doc, prev_prev_ts) = self.row_to_document(row).await?;
             anyhow::ensure!(result
                 .insert(
                     (id, ts),
@@ -1126,7 +1176,7 @@ impl<RT: Runtime> PersistenceReader for MySqlReader<RT> {
     ) -> anyhow::Result<Option<JsonValue>> {
         let mut client = self
             .read_pool
-            .acquire("get_persistence_global", &self.db_name)
+            .acquire("get_persistence_global", &self.db_name, &self.table_prefix)
             .await?;
         let params = vec![String::from(key).into()];
         let row_stream = client
@@ -1155,23 +1205,35 @@ impl<RT: Runtime> PersistenceReader for MySqlReader<RT> {
     async fn table_size_stats(&self) -> anyhow::Result<Vec<PersistenceTableSize>> {
         let mut client = self
             .read_pool
-            .acquire("table_size_stats", &self.db_name)
+            .acquire("table_size_stats", &self.db_name, &self.table_prefix)
             .await?;
-        let stats = client
-            .query_stream(TABLE_SIZE_QUERY, vec![self.db_name.clone().into()], 5)
+        // MySQL has no cheap way to sample rows, so we don't report
+        // `compressed_row_count` rather than scanning the documents table.
+        client
+            .query_stream(
+                TABLE_SIZE_QUERY,
+                iter::once(self.db_name.clone().into())
+                    .chain(persistence_table_params(&self.table_prefix))
+                    .collect(),
+                5,
+            )
             .await?
             .map(|row| {
                 let row = row?;
+                let table_name: String = row.get_opt(0).unwrap()?;
                 anyhow::Ok(PersistenceTableSize {
-                    table_name: row.get_opt(0).unwrap()?,
+                    table_name: table_name
+                        .strip_prefix(&self.table_prefix)
+                        .unwrap_or(&table_name)
+                        .to_owned(),
                     data_bytes: row.get_opt(1).unwrap()?,
                     index_bytes: row.get_opt(2).unwrap()?,
                     row_count: row.get_opt(3).unwrap()?,
+                    compressed_row_count: None,
                 })
             })
             .try_collect()
-            .await?;
-        Ok(stats)
+            .await
     }
 }
 
@@ -1190,6 +1252,7 @@ impl<RT: Runtime> PersistenceReader for MySqlReader<RT> {
 struct Lease<RT: Runtime> {
     pool: Arc<ConvexMySqlPool<RT>>,
     db_name: String,
+    table_prefix: String,
     lease_ts: i64,
     lease_lost_shutdown: ShutdownSignal,
 }
@@ -1200,10 +1263,13 @@ impl<RT: Runtime> Lease<RT> {
     async fn acquire(
         pool: Arc<ConvexMySqlPool<RT>>,
         db_name: String,
+        table_prefix: String,
         lease_lost_shutdown: ShutdownSignal,
     ) -> anyhow::Result<Self> {
         let timer = metrics::lease_acquire_timer(pool.cluster_name());
-        let mut client = pool.acquire("lease_acquire", &db_name).await?;
+        let mut client = pool
+            .acquire("lease_acquire", &db_name, &table_prefix)
+            .await?;
         let ts = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .expect("before 1970")
@@ -1222,6 +1288,7 @@ impl<RT: Runtime> Lease<RT> {
         timer.finish();
         Ok(Self {
             db_name,
+            table_prefix,
             pool,
             lease_ts: ts,
             lease_lost_shutdown,
@@ -1243,7 +1310,10 @@ impl<RT: Runtime> Lease<RT> {
             &'b mut MySqlTransaction<'_>,
         ) -> Pin<Box<dyn Future<Output = anyhow::Result<T>> + Send + 'b>>,
     {
-        let mut client = self.pool.acquire("transact", &self.db_name).await?;
+        let mut client = self
+            .pool
+            .acquire("transact", &self.db_name, &self.table_prefix)
+            .await?;
         let mut tx = client.transaction(&self.db_name).await?;
 
         let timer = metrics::lease_precond_timer(self.pool.cluster_name());
@@ -1276,16 +1346,18 @@ impl<RT: Runtime> Lease<RT> {
 }
 
 fn document_params(
+    compressor: &DocumentCompressor,
     mut query: Vec<mysql_async::Value>,
     ts: Timestamp,
     id: InternalDocumentId,
     maybe_doc: Option<ResolvedDocument>,
     prev_ts: Option<Timestamp>,
-) -> Vec<mysql_async::Value> {
+) -> anyhow::Result<Vec<mysql_async::Value>> {
     let (json_value, deleted) = match maybe_doc {
         Some(document) => (document.value().0.clone().into(), false),
         None => (serde_json::Value::Null, true),
     };
+    let json_value = compressor.encode(serde_json::to_vec(&json_value)?)?;
 
     query.push(internal_doc_id_param(id).into());
     query.push(i64::from(ts).into());
@@ -1293,7 +1365,7 @@ fn document_params(
     query.push(json_value.into());
     query.push(deleted.into());
     query.push(prev_ts.map(i64::from).into());
-    query
+    Ok(query)
 }
 
 fn internal_id_param(id: InternalId) -> Vec<u8> {
@@ -1336,12 +1408,42 @@ fn index_params(query: &mut Vec<mysql_async::Value>, ts: Timestamp, update: Data
     query.push(doc_id.into());
 }
 
+// Tables created by INIT_SQL, before the deployment's table prefix is applied.
+const PERSISTENCE_TABLES: [&str; 5] = [
+    "documents",
+    "indexes",
+    "leases",
+    "read_only",
+    "persistence_globals",
+];
+
+fn persistence_table_params(table_prefix: &str) -> Vec<mysql_async::Value> {
+    PERSISTENCE_TABLES
+        .iter()
+        .map(|table| format!("{table_prefix}{table}").into())
+        .collect()
+}
+
+/// Table prefixes are interpolated into SQL, so only allow characters that
+/// are valid in unquoted identifiers.
+fn validate_table_prefix(table_prefix: &str) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        table_prefix
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        "Invalid MySQL table prefix {table_prefix:?}: only letters, digits and underscores are \
+         allowed"
+    );
+    Ok(())
+}
+
 const GET_TABLE_COUNT: &str = r#"
-    SELECT COUNT(1) FROM INFORMATION_SCHEMA.TABLES WHERE TABLE_SCHEMA = '@db_name';
+    SELECT COUNT(1) FROM INFORMATION_SCHEMA.TABLES WHERE TABLE_SCHEMA = '@db_name'
+    AND TABLE_NAME IN (?, ?, ?, ?, ?);
 "#;
 
 // Expected table count after INIT_SQL is ran.
-const EXPECTED_TABLE_COUNT: usize = 5;
+const EXPECTED_TABLE_COUNT: usize = PERSISTENCE_TABLES.len();
 
 // This runs (currently) every time a MySqlPersistence is created, so it
 // needs to not only be idempotent but not to affect any already-resident data.
@@ -1771,7 +1873,7 @@ fn prev_rev_chunk(chunk_size: usize) -> &'static str {
 const TABLE_SIZE_QUERY: &str = "
 SELECT table_name, data_length, index_length, table_rows
 FROM information_schema.tables
-WHERE table_schema = ?
+WHERE table_schema = ? AND table_name IN (?, ?, ?, ?, ?)
 ";
 
 const MIN_SHA256: [u8; 32] = [0; 32];
@@ -59,6 +59,7 @@ run_persistence_test_suite!(
             allow_read_only: false,
             version: PersistenceVersion::V5,
             use_prepared_statements: true,
+            table_prefix: String::new(),
         },
         ShutdownSignal::panic(),
     )
@@ -74,6 +75,7 @@ run_persistence_test_suite!(
             allow_read_only: true,
             version: PersistenceVersion::V5,
             use_prepared_statements: true,
+            table_prefix: String::new(),
         },
         ShutdownSignal::panic(),
     )
@@ -98,6 +100,7 @@ mod raw_statements {
                 allow_read_only: false,
                 version: PersistenceVersion::V5,
                 use_prepared_statements: false,
+                table_prefix: String::new(),
             },
             ShutdownSignal::panic()
         )
@@ -113,6 +116,7 @@ mod raw_statements {
                 allow_read_only: true,
                 version: PersistenceVersion::V5,
                 use_prepared_statements: false,
+                table_prefix: String::new(),
             },
             ShutdownSignal::panic(),
         )
@@ -126,6 +130,7 @@ async fn test_loading_locally() -> anyhow::Result<()> {
         allow_read_only: false,
         version: PersistenceVersion::V5,
         use_prepared_statements: false,
+        table_prefix: String::new(),
     };
     let opts = crate::itest::new_db_opts().await?;
     let persistence = MySqlPersistence::new(
@@ -174,6 +179,7 @@ async fn test_writing_locally() -> anyhow::Result<()> {
         allow_read_only: false,
         version: PersistenceVersion::V5,
         use_prepared_statements: false,
+        table_prefix: String::new(),
     };
     let opts = crate::itest::new_db_opts().await?;
     let persistence = MySqlPersistence::new(
@@ -237,6 +243,7 @@ async fn test_lease_preempt() -> anyhow::Result<()> {
         allow_read_only: false,
         version: PersistenceVersion::V5,
         use_prepared_statements: false,
+        table_prefix: String::new(),
     };
     let p1 = Arc::new(
         MySqlPersistence::new(
@@ -279,6 +286,7 @@ async fn test_lease_preempt() -> anyhow::Result<()> {
         allow_read_only: false,
         version: PersistenceVersion::V5,
         use_prepared_statements: false,
+        table_prefix: String::new(),
     };
     let p2 = Arc::new(
         MySqlPersistence::new(
@@ -340,6 +348,7 @@ async fn test_table_count() -> anyhow::Result<()> {
         allow_read_only: false,
         version: PersistenceVersion::V5,
         use_prepared_statements: false,
+        table_prefix: String::new(),
     };
     let opts = crate::itest::new_db_opts().await?;
     let persistence = MySqlPersistence::new(
@@ -362,3 +371,76 @@ async fn test_table_count() -> anyhow::Result<()> {
     );
     Ok(())
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_table_prefixes_are_isolated() -> anyhow::Result<()> {
+    let opts = crate::itest::new_db_opts().await?;
+    let pool = Arc::new(ConvexMySqlPool::new(
+        &opts.url.clone(),
+        false,
+        Option::<ProdRuntime>::None,
+    )?);
+    let options = |table_prefix: &str| MySqlOptions {
+        allow_read_only: false,
+        version: PersistenceVersion::V5,
+        use_prepared_statements: false,
+        table_prefix: table_prefix.to_owned(),
+    };
+    let p1 = Arc::new(
+        MySqlPersistence::new(
+            pool.clone(),
+            opts.db_name.clone(),
+            options("dev_"),
+            ShutdownSignal::panic(),
+        )
+        .await?,
+    );
+    let p2 = Arc::new(
+        MySqlPersistence::new(
+            pool.clone(),
+            opts.db_name.clone(),
+            options("staging_"),
+            ShutdownSignal::panic(),
+        )
+        .await?,
+    );
+    assert_eq!(p1.get_table_count().await?, EXPECTED_TABLE_COUNT);
+    assert_eq!(p2.get_table_count().await?, EXPECTED_TABLE_COUNT);
+
+    let mut id_generator = TestIdGenerator::new();
+    let table: TableName = str::parse("table")?;
+    let doc_id = id_generator.user_generate(&table);
+    id_generator.write_tables(p1.clone()).await?;
+    let doc = ResolvedDocument::new(doc_id, CreationTime::ONE, ConvexObject::empty())?;
+
+    // Each deployment holds the lease for its own tables, so both can write.
+    for p in [&p1, &p2] {
+        p.write(
+            vec![DocumentLogEntry {
+                ts: Timestamp::must(1),
+                id: doc.id_with_table_id(),
+                value: Some(doc.clone()),
+                prev_ts: None,
+            }],
+            BTreeSet::new(),
+            ConflictStrategy::Error,
+        )
+        .await?;
+    }
+
+    // The table metadata written through `p1` isn't visible to `p2`.
+    let p1_documents: Vec<_> = p1.reader().load_all_documents().try_collect().await?;
+    let p2_documents: Vec<_> = p2.reader().load_all_documents().try_collect().await?;
+    assert!(p1_documents.len() > p2_documents.len());
+    assert_eq!(p2_documents.len(), 1);
+
+    assert!(MySqlPersistence::new(
+        pool,
+        opts.db_name,
+        options("x; DROP TABLE documents; --"),
+        ShutdownSignal::panic(),
+    )
+    .await
+    .is_err());
+    Ok(())
+}
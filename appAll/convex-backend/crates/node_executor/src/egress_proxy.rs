@@ -0,0 +1,250 @@
+//! An HTTP `CONNECT` proxy that is the only way out of a sandboxed Node.js
+//! worker's network namespace.
+//!
+//! When outbound hosts are restricted, workers run with `bwrap --unshare-net`
+//! and have no network interface besides loopback. The proxy listens on a Unix
+//! socket in the worker's temp directory, which is bound into the sandbox, and
+//! only opens tunnels to the deployment's allowed hosts plus the backend and
+//! storage hosts of the request the worker is currently running.
+use std::{
+    collections::BTreeSet,
+    path::Path,
+    sync::Arc,
+};
+
+use anyhow::Context;
+use common::runtime::tokio_spawn;
+use parking_lot::Mutex;
+use serde_json::Value as JsonValue;
+use tokio::{
+    io::{
+        AsyncBufReadExt,
+        AsyncReadExt,
+        AsyncWriteExt,
+        BufReader,
+    },
+    net::{
+        TcpStream,
+        UnixListener,
+        UnixStream,
+    },
+    task::JoinHandle,
+};
+use url::Url;
+
+use crate::sandbox::REQUEST_URL_POINTERS;
+
+/// Longest request head the proxy will read before giving up on a client.
+const MAX_REQUEST_HEAD_BYTES: u64 = 8 << 10;
+
+pub struct EgressProxy {
+    request_hosts: Arc<Mutex<BTreeSet<String>>>,
+    accept_loop: JoinHandle<()>,
+}
+
+impl EgressProxy {
+    pub fn start(socket_path: &Path, allowed_hosts: &[String]) -> anyhow::Result<Self> {
+        let listener = UnixListener::bind(socket_path)
+            .with_context(|| format!("Failed to bind egress proxy to {}", socket_path.display()))?;
+        let allowed_hosts: Arc<BTreeSet<String>> = Arc::new(
+            allowed_hosts
+                .iter()
+                .map(|host| normalize_host(host))
+                .collect(),
+        );
+        let request_hosts = Arc::new(Mutex::new(BTreeSet::new()));
+        let accept_loop = tokio_spawn("node_egress_proxy", {
+            let request_hosts = request_hosts.clone();
+            async move {
+                loop {
+                    let stream = match listener.accept().await {
+                        Ok((stream, _)) => stream,
+                        Err(e) => {
+                            tracing::warn!("Egress proxy failed to accept a connection: {e}");
+                            continue;
+                        },
+                    };
+                    let allowed_hosts = allowed_hosts.clone();
+                    let request_hosts = request_hosts.clone();
+                    tokio_spawn("node_egress_tunnel", async move {
+                        if let Err(e) = tunnel(stream, &allowed_hosts, &request_hosts).await {
+                            tracing::debug!("Egress tunnel failed: {e:#}");
+                        }
+                    });
+                }
+            }
+        });
+        Ok(Self {
+            request_hosts,
+            accept_loop,
+        })
+    }
+
+    /// Allow tunnels to the hosts `request` points the worker at, replacing
+    /// those of the previous request.
+    pub fn grant_request_hosts(&self, request: &JsonValue) {
+        *self.request_hosts.lock() = request_hosts(request);
+    }
+
+    pub fn revoke_request_hosts(&self) {
+        self.request_hosts.lock().clear();
+    }
+}
+
+impl Drop for EgressProxy {
+    fn drop(&mut self) {
+        self.accept_loop.abort();
+    }
+}
+
+fn normalize_host(host: &str) -> String {
+    let host = host.to_lowercase();
+    match host.strip_prefix('[').and_then(|h| h.strip_suffix(']')) {
+        Some(host) => host.to_string(),
+        None => host,
+    }
+}
+
+fn request_hosts(request: &JsonValue) -> BTreeSet<String> {
+    REQUEST_URL_POINTERS
+        .iter()
+        .filter_map(|pointer| request.pointer(pointer)?.as_str())
+        .filter_map(|url| Url::parse(url).ok())
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .filter_map(|url| url.host_str().map(normalize_host))
+        .collect()
+}
+
+async fn tunnel(
+    stream: UnixStream,
+    allowed_hosts: &BTreeSet<String>,
+    request_hosts: &Mutex<BTreeSet<String>>,
+) -> anyhow::Result<()> {
+    let mut stream = BufReader::new(stream);
+    let mut head = (&mut stream).take(MAX_REQUEST_HEAD_BYTES);
+    let mut request_line = String::new();
+    head.read_line(&mut request_line).await?;
+    // Skip the headers, there's nothing in them the proxy needs.
+    loop {
+        let mut line = String::new();
+        anyhow::ensure!(
+            head.read_line(&mut line).await? > 0,
+            "Connection closed during request head"
+        );
+        if line.trim_end().is_empty() {
+            break;
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (Some("CONNECT"), Some(authority)) = (parts.next(), parts.next()) else {
+        stream
+            .write_all(b"HTTP/1.1 405 Method Not Allowed\r\nContent-Length: 0\r\n\r\n")
+            .await?;
+        return Ok(());
+    };
+    let (host, port) = authority
+        .rsplit_once(':')
+        .and_then(|(host, port)| Some((normalize_host(host), port.parse::<u16>().ok()?)))
+        .with_context(|| format!("Invalid CONNECT authority {authority:?}"))?;
+    if !allowed_hosts.contains(&host) && !request_hosts.lock().contains(&host) {
+        stream
+            .write_all(b"HTTP/1.1 403 Forbidden\r\nContent-Length: 0\r\n\r\n")
+            .await?;
+        return Ok(());
+    }
+    let mut upstream = match TcpStream::connect((host.as_str(), port)).await {
+        Ok(upstream) => upstream,
+        Err(e) => {
+            stream
+                .write_all(b"HTTP/1.1 502 Bad Gateway\r\nContent-Length: 0\r\n\r\n")
+                .await?;
+            return Err(e).context(format!("Failed to connect to {host}:{port}"));
+        },
+    };
+    stream
+        .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+        .await?;
+    tokio::io::copy_bidirectional(&mut stream, &mut upstream).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use runtime::prod::ProdRuntime;
+    use serde_json::json;
+    use tempfile::TempDir;
+    use tokio::{
+        io::{
+            AsyncReadExt,
+            AsyncWriteExt,
+        },
+        net::{
+            TcpListener,
+            UnixStream,
+        },
+    };
+
+    use super::EgressProxy;
+
+    async fn connect(socket_path: &std::path::Path, authority: &str) -> anyhow::Result<String> {
+        let mut stream = UnixStream::connect(socket_path).await?;
+        stream
+            .write_all(
+                format!("CONNECT {authority} HTTP/1.1\r\nHost: {authority}\r\n\r\n").as_bytes(),
+            )
+            .await?;
+        let mut response = vec![0; 1024];
+        let n = stream.read(&mut response).await?;
+        let response = String::from_utf8(response[..n].to_vec())?;
+        if response.starts_with("HTTP/1.1 200") {
+            stream.write_all(b"ping").await?;
+            let mut pong = [0; 4];
+            stream.read_exact(&mut pong).await?;
+            assert_eq!(&pong, b"pong");
+        }
+        Ok(response.lines().next().unwrap_or_default().to_string())
+    }
+
+    #[convex_macro::prod_rt_test]
+    async fn test_egress_proxy(_rt: ProdRuntime) -> anyhow::Result<()> {
+        let upstream = TcpListener::bind("127.0.0.1:0").await?;
+        let port = upstream.local_addr()?.port();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = upstream.accept().await {
+                let mut ping = [0; 4];
+                if stream.read_exact(&mut ping).await.is_ok() {
+                    let _ = stream.write_all(b"pong").await;
+                }
+            }
+        });
+
+        let dir = TempDir::new()?;
+        let socket_path = dir.path().join("egress.sock");
+        let proxy = EgressProxy::start(&socket_path, &["LOCALHOST".to_string()])?;
+        assert_eq!(
+            connect(&socket_path, &format!("localhost:{port}")).await?,
+            "HTTP/1.1 200 Connection Established"
+        );
+        assert_eq!(
+            connect(&socket_path, &format!("127.0.0.1:{port}")).await?,
+            "HTTP/1.1 403 Forbidden"
+        );
+
+        // Hosts from a request are only allowed until they're revoked.
+        proxy.grant_request_hosts(&json!({
+            "backendAddress": format!("http://127.0.0.1:{port}"),
+            "sourcePackage": { "uri": "file://localhost/tmp/package.zip" },
+        }));
+        assert_eq!(
+            connect(&socket_path, &format!("127.0.0.1:{port}")).await?,
+            "HTTP/1.1 200 Connection Established"
+        );
+        proxy.revoke_request_hosts();
+        assert_eq!(
+            connect(&socket_path, &format!("127.0.0.1:{port}")).await?,
+            "HTTP/1.1 403 Forbidden"
+        );
+        Ok(())
+    }
+}
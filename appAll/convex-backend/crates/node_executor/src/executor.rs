@@ -28,11 +28,15 @@ use common::{
         ActionCallbackToken,
         ConvexOrigin,
         NodeDependency,
+        NodeVersion,
         ObjectKey,
         UdfType,
     },
 };
-use errors::ErrorMetadataAnyhowExt;
+use errors::{
+    ErrorMetadata,
+    ErrorMetadataAnyhowExt,
+};
 use http::Uri;
 use isolate::{
     deserialize_udf_custom_error,
@@ -122,6 +126,12 @@ const NODE_ANALYZE_MAX_BACKOFF: Duration = Duration::from_secs(5);
 #[async_trait]
 pub trait NodeExecutor: Sync + Send {
     fn enable(&self) -> anyhow::Result<()>;
+    /// Whether this executor can run actions on `node_version`. Executors
+    /// with a single provisioned runtime, like AWS Lambda, only run the
+    /// default version.
+    fn supports_node_version(&self, node_version: NodeVersion) -> bool {
+        node_version == NodeVersion::default()
+    }
     async fn invoke(
         &self,
         request: ExecutorRequest,
@@ -208,6 +218,21 @@ impl<RT: Runtime> Actions<RT> {
         self.executor.enable()
     }
 
+    pub fn check_node_version(&self, node_version: NodeVersion) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.executor.supports_node_version(node_version),
+            ErrorMetadata::bad_request(
+                "UnsupportedNodeVersion",
+                format!(
+                    "Node.js {node_version} isn't available in this deployment. Use Node.js {} \
+                     instead.",
+                    NodeVersion::default()
+                ),
+            )
+        );
+        Ok(())
+    }
+
     pub fn shutdown(&self) {
         self.executor.shutdown()
     }
@@ -530,6 +555,17 @@ pub enum ExecutorRequest {
     BuildDeps(BuildDepsRequest),
 }
 
+impl ExecutorRequest {
+    /// The Node.js version the request must run on.
+    pub fn node_version(&self) -> NodeVersion {
+        match self {
+            ExecutorRequest::Execute { request, .. } => request.source_package.node_version,
+            ExecutorRequest::Analyze(request) => request.source_package.node_version,
+            ExecutorRequest::BuildDeps(request) => request.node_version,
+        }
+    }
+}
+
 impl TryFrom<ExecutorRequest> for JsonValue {
     type Error = anyhow::Error;
 
@@ -612,6 +648,8 @@ pub struct SourcePackage {
 
     // Info of external package if external dependencies were specified.
     pub external_deps: Option<Package>,
+
+    pub node_version: NodeVersion,
 }
 
 impl From<SourcePackage> for JsonValue {
@@ -851,6 +889,7 @@ pub struct AnalyzedNodeFunction {
 pub struct BuildDepsRequest {
     pub deps: Vec<NodeDependency>,
     pub upload_url: Uri,
+    pub node_version: NodeVersion,
 }
 
 #[derive(Debug, Deserialize, PartialEq)]
@@ -6,25 +6,31 @@
 #![feature(stmt_expr_attributes)]
 #![feature(try_blocks)]
 
+mod egress_proxy;
 mod executor;
 pub mod local;
 mod metrics;
+mod sandbox;
 pub mod source_package;
+mod worker_pool;
 
-pub use crate::executor::{
-    error_response_json,
-    parse_streamed_response,
-    Actions,
-    AnalyzeRequest,
-    AnalyzeResponse,
-    BuildDepsRequest,
-    ExecuteRequest,
-    ExecutorRequest,
-    InvokeResponse,
-    NodeActionOutcome,
-    NodeExecutor,
-    Package,
-    ResponsePart,
-    SourcePackage,
-    EXECUTE_TIMEOUT_RESPONSE_JSON,
+pub use crate::{
+    executor::{
+        error_response_json,
+        parse_streamed_response,
+        Actions,
+        AnalyzeRequest,
+        AnalyzeResponse,
+        BuildDepsRequest,
+        ExecuteRequest,
+        ExecutorRequest,
+        InvokeResponse,
+        NodeActionOutcome,
+        NodeExecutor,
+        Package,
+        ResponsePart,
+        SourcePackage,
+        EXECUTE_TIMEOUT_RESPONSE_JSON,
+    },
+    sandbox::NodeSandboxConfig,
 };
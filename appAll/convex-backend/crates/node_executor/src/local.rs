@@ -1,52 +1,75 @@
 use std::{
+    collections::BTreeMap,
     fs,
-    path::PathBuf,
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::Arc,
     time::Duration,
 };
 
 use anyhow::Context;
 use async_trait::async_trait;
-use common::log_lines::LogLine;
-use errors::ErrorMetadata;
-use futures::{
-    select_biased,
-    FutureExt,
-    StreamExt,
+use common::{
+    log_lines::LogLine,
+    types::NodeVersion,
 };
+use errors::ErrorMetadata;
 use isolate::bundled_js::node_executor_file;
 use serde_json::Value as JsonValue;
+use storage::Storage;
 use tempfile::TempDir;
 use tokio::{
     process::Command as TokioCommand,
-    sync::mpsc,
-};
-use tokio_process_stream::{
-    Item,
-    ProcessLineStream,
+    sync::{
+        mpsc,
+        OnceCell,
+    },
 };
 
-use crate::executor::{
-    parse_streamed_response,
-    ExecutorRequest,
-    InvokeResponse,
-    NodeExecutor,
-    ResponsePart,
-    EXECUTE_TIMEOUT_RESPONSE_JSON,
+use crate::{
+    executor::{
+        ExecutorRequest,
+        InvokeResponse,
+        NodeExecutor,
+        EXECUTE_TIMEOUT_RESPONSE_JSON,
+    },
+    metrics::log_local_worker_retired,
+    sandbox::NodeSandboxConfig,
+    worker_pool::NodeWorkerPool,
 };
 
-/// Always use node version specified in .nvmrc for lambda execution, even if
-/// we're using older version for CLI.
-const NODE_VERSION: &str = include_str!("../../../.nvmrc");
-
 pub struct LocalNodeExecutor {
     _source_dir: TempDir,
     source_path: PathBuf,
-    node_path: String,
+    node_search_path: Vec<PathBuf>,
     node_process_timeout: Duration,
+    sandbox: Arc<NodeSandboxConfig>,
+    // Where to read source packages that can't be fetched by URL.
+    package_storage: Option<Arc<dyn Storage>>,
+    // Resolved the first time a request needs that version.
+    runtimes: BTreeMap<NodeVersion, OnceCell<NodeRuntime>>,
+}
+
+struct NodeRuntime {
+    node_path: String,
+    pool: NodeWorkerPool,
 }
 
 impl LocalNodeExecutor {
-    pub fn new(node_process_timeout: Duration) -> anyhow::Result<Self> {
+    /// `node_search_path` lists directories to look for Node.js binaries in,
+    /// in order of preference. Each can be an nvm-style versions directory
+    /// (containing e.g. `v20.11.1/bin/node`) or a directory containing `node`
+    /// or `node20` binaries. When empty, `~/.nvm/versions/node` is used. The
+    /// `node` on the `PATH` is always tried last.
+    ///
+    /// Workers are started with the limits in `sandbox`.
+    pub fn new(
+        node_process_timeout: Duration,
+        node_search_path: Vec<PathBuf>,
+        sandbox: NodeSandboxConfig,
+    ) -> anyhow::Result<Self> {
         // Write the source of local.cjs to a temp file.
         let source_dir = TempDir::new()?;
         let (source, source_map) =
@@ -60,111 +83,170 @@ impl LocalNodeExecutor {
             "Using local node executor. Source: {}",
             source_path.to_str().expect("Path is not UTF-8 string?"),
         );
-        let node_version = NODE_VERSION.trim();
-
-        // Look for node in a few places.
-        let possible_path = home::home_dir()
-            .unwrap()
-            .join(".nvm")
-            .join(format!("versions/node/v{node_version}/bin/node"));
-        let node_path = if possible_path.exists() {
-            possible_path.to_string_lossy().to_string()
+
+        let node_search_path = if node_search_path.is_empty() {
+            home::home_dir()
+                .map(|home| home.join(".nvm/versions/node"))
+                .into_iter()
+                .collect()
         } else {
-            "node".to_string()
+            node_search_path
         };
 
         Ok(Self {
             _source_dir: source_dir,
             source_path,
-            node_path,
+            node_search_path,
             node_process_timeout,
+            sandbox: Arc::new(sandbox),
+            package_storage: None,
+            runtimes: NodeVersion::ALL
+                .into_iter()
+                .map(|version| (version, OnceCell::new()))
+                .collect(),
         })
     }
 
-    async fn check_version(&self) -> anyhow::Result<()> {
-        let cmd = TokioCommand::new(&self.node_path)
-            .arg("--version")
-            .output()
-            .await?;
-        let version = String::from_utf8_lossy(&cmd.stdout);
-
-        if !version.starts_with("v18.") {
-            anyhow::bail!(ErrorMetadata::bad_request(
-                "DeploymentNotConfiguredForNodeActions",
-                "Deployment is not configured to deploy \"use node\" actions. \
-                 Node.js v18 is not installed. \
-                 Install Node.js 18 with nvm (https://github.com/nvm-sh/nvm) \
-                 to deploy Node.js actions."
-            ))
+    /// Read source packages stored in an
+    /// [`EncryptedStorage`](storage::EncryptedStorage) from `package_storage`.
+    /// Their URLs can't be fetched, so each is decrypted into the private temp
+    /// directory of the worker running the request, and removed afterwards.
+    pub fn with_package_storage(mut self, package_storage: Arc<dyn Storage>) -> Self {
+        self.package_storage = Some(package_storage);
+        self
+    }
+
+    async fn runtime(&self, node_version: NodeVersion) -> anyhow::Result<&NodeRuntime> {
+        self.runtimes[&node_version]
+            .get_or_try_init(|| async {
+                let node_path = self.find_node(node_version).await?;
+                tracing::info!("Using {node_path} for Node.js {node_version} actions");
+                Ok(NodeRuntime {
+                    pool: NodeWorkerPool::new(
+                        node_path.clone(),
+                        self.source_path.clone(),
+                        self.sandbox.clone(),
+                    ),
+                    node_path,
+                })
+            })
+            .await
+    }
+
+    async fn find_node(&self, node_version: NodeVersion) -> anyhow::Result<String> {
+        let candidates = self
+            .node_search_path
+            .iter()
+            .flat_map(|dir| node_candidates(dir, node_version))
+            .filter(|path| path.is_file())
+            .map(|path| path.to_string_lossy().to_string())
+            .chain(["node".to_string()]);
+        let prefix = format!("v{}.", node_version.major());
+        for candidate in candidates {
+            let Ok(output) = TokioCommand::new(&candidate)
+                .arg("--version")
+                .output()
+                .await
+            else {
+                continue;
+            };
+            let version = String::from_utf8_lossy(&output.stdout);
+            if version.starts_with(&prefix) {
+                return Ok(candidate);
+            }
+            tracing::debug!("Skipping {candidate}: {}", version.trim());
         }
-        Ok(())
+        anyhow::bail!(ErrorMetadata::bad_request(
+            "DeploymentNotConfiguredForNodeActions",
+            format!(
+                "Deployment is not configured to deploy \"use node\" actions. Node.js \
+                 {node_version} is not installed. Install Node.js {node_version} with nvm \
+                 (https://github.com/nvm-sh/nvm) to deploy Node.js actions. Searched: {}",
+                self.node_search_path
+                    .iter()
+                    .map(|dir| dir.display().to_string())
+                    .chain(["PATH".to_string()])
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        ))
     }
 }
 
+/// Possible locations of a `node_version` binary in `dir`, best first.
+fn node_candidates(dir: &Path, node_version: NodeVersion) -> Vec<PathBuf> {
+    let major = node_version.major();
+    // nvm-style versions directory, e.g. `v20.11.1/bin/node`. Prefer the newest
+    // release.
+    let mut releases: Vec<(Vec<u32>, PathBuf)> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let name = entry.file_name().into_string().ok()?;
+            let release = name.strip_prefix('v')?;
+            let release: Vec<u32> = release.split('.').map(str::parse).try_collect().ok()?;
+            (release.first() == Some(&major)).then(|| (release, entry.path().join("bin/node")))
+        })
+        .collect();
+    releases.sort();
+    let mut candidates: Vec<_> = releases.into_iter().rev().map(|(_, path)| path).collect();
+    // A directory of binaries, e.g. `/usr/local/bin`.
+    candidates.push(dir.join(format!("node{major}")));
+    candidates.push(dir.join("node"));
+    candidates
+}
+
 #[async_trait]
 impl NodeExecutor for LocalNodeExecutor {
     fn enable(&self) -> anyhow::Result<()> {
         Ok(())
     }
 
+    fn supports_node_version(&self, _node_version: NodeVersion) -> bool {
+        true
+    }
+
     async fn invoke(
         &self,
         request: ExecutorRequest,
         log_line_sender: mpsc::UnboundedSender<LogLine>,
     ) -> anyhow::Result<InvokeResponse> {
+        let runtime = self.runtime(request.node_version()).await?;
         let request = JsonValue::try_from(request)?;
-        self.check_version().await?;
+        let source_package_key = request
+            .pointer("/sourcePackage/key")
+            .and_then(JsonValue::as_str)
+            .map(str::to_owned);
         let request = serde_json::to_string(&request)?;
         tracing::info!(
-            "{} {} --request='{}'",
-            &self.node_path,
+            "{} {} --worker <<< '{}'",
+            &runtime.node_path,
             self.source_path.to_str().expect("Must be utf-8"),
             &request,
         );
-        let mut _cmd = TokioCommand::new(&self.node_path);
-        let cmd = _cmd
-            .arg(&self.source_path)
-            .arg("--request")
-            .arg(request)
-            .kill_on_drop(true);
-        let mut result_values = vec![];
-        let mut err_lines = vec![];
-
-        let mut procstream = ProcessLineStream::try_from(cmd)?.fuse();
-
-        let response = loop {
-            select_biased! {
-                item = procstream.select_next_some() => {
-                    match item {
-                        Item::Stdout(line) => {
-                            let parts = parse_streamed_response(&line)?;
-                            for part in parts {
-                                match part {
-                                    ResponsePart::LogLine(log_line) => {
-                                        log_line_sender.send(log_line)?;
-                                    },
-                                    ResponsePart::Result(result) => result_values.push(result)
-                                }
-                            }
-                        },
-                        Item::Done(status) => {
-                            if !status?.success() {
-                                for line in err_lines {
-                                    tracing::error!("{line}");
-                                }
-                                anyhow::bail!("Local process did not exit successfully");
-                            }
-                            anyhow::ensure!(result_values.len() <= 1, "Received more than one result from lambda response");
-                            let value = result_values.pop().ok_or_else(|| anyhow::anyhow!("Received no result from lambda response"))?;
-                            break value;
-                        }
-                        Item::Stderr(line) => err_lines.push(line),
-                    }
-                },
-                _ = tokio::time::sleep(self.node_process_timeout).fuse() => {
-                    break EXECUTE_TIMEOUT_RESPONSE_JSON.clone();
-                },
-            }
+        let mut worker = runtime.pool.checkout(source_package_key.as_deref())?;
+        let result = tokio::time::timeout(
+            self.node_process_timeout,
+            worker.run(
+                &request,
+                source_package_key.as_deref(),
+                self.package_storage.as_ref(),
+                &log_line_sender,
+            ),
+        )
+        .await;
+        let response = match result {
+            Ok(response) => {
+                let response = response?;
+                runtime.pool.checkin(worker);
+                response
+            },
+            // Dropping the worker kills it, along with whatever it was still running.
+            Err(_) => {
+                log_local_worker_retired("timeout");
+                EXECUTE_TIMEOUT_RESPONSE_JSON.clone()
+            },
         };
         Ok(InvokeResponse {
             response,
@@ -174,13 +256,18 @@ impl NodeExecutor for LocalNodeExecutor {
         })
     }
 
-    fn shutdown(&self) {}
+    fn shutdown(&self) {
+        for runtime in self.runtimes.values().filter_map(OnceCell::get) {
+            runtime.pool.clear();
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use std::{
         collections::BTreeMap,
+        fs,
         future::Future,
         sync::{
             Arc,
@@ -201,6 +288,7 @@ mod tests {
         runtime::Runtime,
         types::{
             ModuleEnvironment,
+            NodeVersion,
             UdfType,
         },
         value::ConvexValue,
@@ -233,14 +321,23 @@ mod tests {
     use runtime::prod::ProdRuntime;
     use serde_json::json;
     use storage::{
+        EncryptedStorage,
         LocalDirStorage,
         Storage,
+        StorageKeyring,
     };
     use sync_types::{
         CanonicalizedModulePath,
         ModulePath,
     };
-    use tokio::sync::mpsc;
+    use tempfile::TempDir;
+    use tokio::{
+        io::{
+            AsyncReadExt,
+            AsyncWriteExt,
+        },
+        sync::mpsc,
+    };
     use udf::validation::ValidatedPathAndArgs;
     use value::{
         array,
@@ -249,12 +346,16 @@ mod tests {
         ConvexObject,
     };
 
-    use super::LocalNodeExecutor;
+    use super::{
+        node_candidates,
+        LocalNodeExecutor,
+    };
     use crate::{
         executor::{
             NodeActionOutcome,
             Package,
         },
+        sandbox::NodeSandboxConfig,
         Actions,
         AnalyzeRequest,
         ExecuteRequest,
@@ -285,6 +386,7 @@ mod tests {
         Ok(SourcePackage {
             bundled_source: Package { uri, key, sha256 },
             external_deps: None,
+            node_version: NodeVersion::default(),
         })
     }
 
@@ -332,8 +434,12 @@ mod tests {
     }
 
     fn create_actions<RT: Runtime>(rt: RT) -> Actions<RT> {
+        create_sandboxed_actions(rt, NodeSandboxConfig::default())
+    }
+
+    fn create_sandboxed_actions<RT: Runtime>(rt: RT, sandbox: NodeSandboxConfig) -> Actions<RT> {
         Actions::new(
-            Arc::new(LocalNodeExecutor::new(TEST_NODE_PROCESS_TIMEOUT).unwrap()),
+            Arc::new(LocalNodeExecutor::new(TEST_NODE_PROCESS_TIMEOUT, vec![], sandbox).unwrap()),
             TEST_BACKEND_ADDRESS.into(),
             TEST_USER_TIMEOUT,
             rt,
@@ -370,6 +476,147 @@ mod tests {
         Ok(())
     }
 
+    #[convex_macro::prod_rt_test]
+    async fn test_encrypted_package(rt: ProdRuntime) -> anyhow::Result<()> {
+        let storage: Arc<dyn Storage> = Arc::new(EncryptedStorage::new(
+            Arc::new(LocalDirStorage::new(rt.clone())?),
+            Arc::new(StorageKeyring::derive(&[7; 32], 0, &[])?),
+        ));
+        let source_package = upload_modules(storage.clone(), TEST_SOURCE.clone()).await?;
+        assert_eq!(
+            source_package.bundled_source.uri.scheme_str(),
+            Some("convex-encrypted")
+        );
+        let actions = Actions::new(
+            Arc::new(
+                LocalNodeExecutor::new(
+                    TEST_NODE_PROCESS_TIMEOUT,
+                    vec![],
+                    NodeSandboxConfig::default(),
+                )?
+                .with_package_storage(storage),
+            ),
+            TEST_BACKEND_ADDRESS.into(),
+            TEST_USER_TIMEOUT,
+            rt,
+        );
+
+        let numbers: ConvexArray = array![1f64.into(), 7f64.into()]?;
+        let args = create_args(assert_obj!("numbers" => ConvexValue::Array(numbers)))?;
+        let path_and_args = ValidatedPathAndArgs::new_for_tests(
+            "node_actions.js:addNumbers".parse()?,
+            args,
+            VERSION.clone(),
+        );
+        let (response, _log_lines) = execute(
+            &actions,
+            execute_request(path_and_args, source_package),
+            empty_source_maps_callback(),
+        )
+        .await?;
+
+        assert_eq!(response.result?, ConvexValue::from(8.));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_node_candidates() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        for release in ["v18.20.6", "v20.9.0", "v20.11.1", "v22.1.0", "v20-nightly"] {
+            fs::create_dir_all(dir.path().join(release).join("bin"))?;
+        }
+        assert_eq!(
+            node_candidates(dir.path(), NodeVersion::V20),
+            vec![
+                dir.path().join("v20.11.1/bin/node"),
+                dir.path().join("v20.9.0/bin/node"),
+                dir.path().join("node20"),
+                dir.path().join("node"),
+            ]
+        );
+        Ok(())
+    }
+
+    #[convex_macro::prod_rt_test]
+    async fn test_worker_reused(rt: ProdRuntime) -> anyhow::Result<()> {
+        let storage = Arc::new(LocalDirStorage::new(rt.clone())?);
+        let executor = Arc::new(LocalNodeExecutor::new(
+            TEST_NODE_PROCESS_TIMEOUT,
+            vec![],
+            NodeSandboxConfig::default(),
+        )?);
+        let actions = Actions::new(
+            executor.clone(),
+            TEST_BACKEND_ADDRESS.into(),
+            TEST_USER_TIMEOUT,
+            rt,
+        );
+        let source_package = upload_modules(storage.clone(), TEST_SOURCE.clone()).await?;
+
+        for i in 0..3 {
+            let numbers: ConvexArray = array![(i as f64).into(), 7f64.into()]?;
+            let args = create_args(assert_obj!("numbers" => ConvexValue::Array(numbers)))?;
+            let path_and_args = ValidatedPathAndArgs::new_for_tests(
+                "node_actions.js:addNumbers".parse()?,
+                args,
+                VERSION.clone(),
+            );
+            let (response, _log_lines) = execute(
+                &actions,
+                execute_request(path_and_args, source_package.clone()),
+                empty_source_maps_callback(),
+            )
+            .await?;
+            assert_eq!(response.result?, ConvexValue::from(i as f64 + 7.));
+            // Requests run one at a time, so they should all share one worker.
+            let runtime = executor.runtime(NodeVersion::default()).await?;
+            assert_eq!(runtime.pool.num_idle(), 1);
+        }
+
+        Ok(())
+    }
+
+    #[convex_macro::prod_rt_test]
+    async fn test_worker_state_reset(rt: ProdRuntime) -> anyhow::Result<()> {
+        let storage = Arc::new(LocalDirStorage::new(rt.clone())?);
+        let executor = Arc::new(LocalNodeExecutor::new(
+            TEST_NODE_PROCESS_TIMEOUT,
+            vec![],
+            NodeSandboxConfig::default(),
+        )?);
+        let actions = Actions::new(
+            executor.clone(),
+            TEST_BACKEND_ADDRESS.into(),
+            TEST_USER_TIMEOUT,
+            rt,
+        );
+        let source_package = upload_modules(storage.clone(), TEST_SOURCE.clone()).await?;
+        let runtime = executor.runtime(NodeVersion::default()).await?;
+
+        for (leave_timer, num_idle) in [(false, 1), (false, 1), (true, 0)] {
+            let args = create_args(assert_obj!("leaveTimer" => leave_timer))?;
+            let path_and_args = ValidatedPathAndArgs::new_for_tests(
+                "node_actions.js:leakState".parse()?,
+                args,
+                VERSION.clone(),
+            );
+            let (response, _log_lines) = execute(
+                &actions,
+                execute_request(path_and_args, source_package.clone()),
+                empty_source_maps_callback(),
+            )
+            .await?;
+            // The forged `WorkerDone` line didn't end the request, and the previous
+            // request's global was cleared.
+            assert_eq!(response.result?, ConvexValue::Null);
+            // A worker with a timer still running is recycled.
+            assert_eq!(runtime.pool.num_idle(), num_idle);
+        }
+
+        Ok(())
+    }
+
     #[convex_macro::prod_rt_test]
     async fn test_log_lines(rt: ProdRuntime) -> anyhow::Result<()> {
         let storage = Arc::new(LocalDirStorage::new(rt.clone())?);
@@ -438,7 +685,11 @@ mod tests {
     async fn test_query_syscall(rt: ProdRuntime) -> anyhow::Result<()> {
         let storage = Arc::new(LocalDirStorage::new(rt.clone())?);
         let actions = Actions::new(
-            Arc::new(LocalNodeExecutor::new(TEST_NODE_PROCESS_TIMEOUT)?),
+            Arc::new(LocalNodeExecutor::new(
+                TEST_NODE_PROCESS_TIMEOUT,
+                vec![],
+                NodeSandboxConfig::default(),
+            )?),
             "http://localhost:8719".into(),
             TEST_USER_TIMEOUT,
             rt,
@@ -474,7 +725,11 @@ mod tests {
     async fn test_schedule_syscall(rt: ProdRuntime) -> anyhow::Result<()> {
         let storage = Arc::new(LocalDirStorage::new(rt.clone())?);
         let actions = Actions::new(
-            Arc::new(LocalNodeExecutor::new(TEST_NODE_PROCESS_TIMEOUT)?),
+            Arc::new(LocalNodeExecutor::new(
+                TEST_NODE_PROCESS_TIMEOUT,
+                vec![],
+                NodeSandboxConfig::default(),
+            )?),
             "http://localhost:8719".into(),
             TEST_USER_TIMEOUT,
             rt,
@@ -742,6 +997,101 @@ mod tests {
         Ok(())
     }
 
+    #[convex_macro::prod_rt_test]
+    async fn test_sandbox_memory_limit(rt: ProdRuntime) -> anyhow::Result<()> {
+        let storage = Arc::new(LocalDirStorage::new(rt.clone())?);
+        let actions = create_sandboxed_actions(
+            rt,
+            NodeSandboxConfig {
+                memory_limit_mb: Some(64),
+                ..Default::default()
+            },
+        );
+        let source_package = upload_modules(storage.clone(), TEST_SOURCE.clone()).await?;
+        let path_and_args = ValidatedPathAndArgs::new_for_tests(
+            "node_actions.js:allocateForever".parse()?,
+            array![],
+            VERSION.clone(),
+        );
+        let (response, _log_lines) = execute(
+            &actions,
+            execute_request(path_and_args, source_package),
+            empty_source_maps_callback(),
+        )
+        .await?;
+        let error = response.result.unwrap_err();
+        assert!(
+            error
+                .message
+                .contains("Function execution exceeded the memory limit of 64 MB"),
+            "{error:?}"
+        );
+
+        Ok(())
+    }
+
+    #[convex_macro::prod_rt_test]
+    async fn test_sandbox_network_allowlist(rt: ProdRuntime) -> anyhow::Result<()> {
+        // The allowlist is enforced with a network namespace.
+        if std::process::Command::new("bwrap")
+            .arg("--version")
+            .output()
+            .is_err()
+        {
+            tracing::warn!("Skipping test_sandbox_network_allowlist without bwrap");
+            return Ok(());
+        }
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let port = listener.local_addr()?.port();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request).await;
+                let _ = stream
+                    .write_all(
+                        b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+                    )
+                    .await;
+            }
+        });
+        let storage = Arc::new(LocalDirStorage::new(rt.clone())?);
+        let actions = create_sandboxed_actions(
+            rt,
+            NodeSandboxConfig {
+                allowed_hosts: Some(vec!["localhost".to_string()]),
+                ..Default::default()
+            },
+        );
+        let source_package = upload_modules(storage.clone(), TEST_SOURCE.clone()).await?;
+        for (host, allowed) in [("localhost", true), ("127.0.0.2", false)] {
+            let args = create_args(assert_obj!("url" => format!("http://{host}:{port}/")))?;
+            let path_and_args = ValidatedPathAndArgs::new_for_tests(
+                "node_actions.js:httpGet".parse()?,
+                args,
+                VERSION.clone(),
+            );
+            let (response, _log_lines) = execute(
+                &actions,
+                execute_request(path_and_args, source_package.clone()),
+                empty_source_maps_callback(),
+            )
+            .await?;
+            if allowed {
+                assert_eq!(response.result?, ConvexValue::try_from("hello")?);
+            } else {
+                let error = response.result.unwrap_err();
+                assert!(
+                    error
+                        .message
+                        .contains("Outbound network access to 127.0.0.2 is not allowed"),
+                    "{error:?}"
+                );
+            }
+        }
+
+        Ok(())
+    }
+
     #[convex_macro::prod_rt_test]
     async fn test_deadlock(rt: ProdRuntime) -> anyhow::Result<()> {
         let storage = Arc::new(LocalDirStorage::new(rt.clone())?);
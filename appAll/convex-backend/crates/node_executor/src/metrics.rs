@@ -2,6 +2,7 @@ use std::time::Duration;
 
 use metrics::{
     log_counter,
+    log_counter_with_labels,
     log_distribution,
     log_distribution_with_labels,
     register_convex_counter,
@@ -118,3 +119,24 @@ pub fn log_external_deps_size_bytes_total(pkg_size: PackageSize) {
         vec![unzipped_label],
     );
 }
+
+register_convex_counter!(
+    NODE_EXECUTOR_LOCAL_WORKER_STARTED_TOTAL,
+    "Number of Node.js worker processes started by the local node executor"
+);
+pub fn log_local_worker_started() {
+    log_counter(&NODE_EXECUTOR_LOCAL_WORKER_STARTED_TOTAL, 1);
+}
+
+register_convex_counter!(
+    NODE_EXECUTOR_LOCAL_WORKER_RETIRED_TOTAL,
+    "Number of Node.js worker processes retired by the local node executor",
+    &["reason"]
+);
+pub fn log_local_worker_retired(reason: &'static str) {
+    log_counter_with_labels(
+        &NODE_EXECUTOR_LOCAL_WORKER_RETIRED_TOTAL,
+        1,
+        vec![StaticMetricLabel::new("reason", reason)],
+    );
+}
@@ -0,0 +1,532 @@
+//! Resource limits and isolation for the Node.js workers started by
+//! [`LocalNodeExecutor`](crate::local::LocalNodeExecutor).
+//!
+//! Limits are enforced with Linux primitives: memory, CPU and process count
+//! with a cgroup v2 per worker, file sizes with `RLIMIT_FSIZE`, and the
+//! filesystem, process and network isolation with
+//! [bubblewrap](https://github.com/containers/bubblewrap). A worker sees only
+//! its Node.js installation, the executor bundle, system libraries and its
+//! private temp directory. When outbound hosts are restricted, it also gets an
+//! empty network namespace whose only way out is an
+//! [`EgressProxy`](crate::egress_proxy::EgressProxy) running in the backend.
+use std::{
+    collections::BTreeMap,
+    ffi::CString,
+    fs,
+    os::unix::{
+        ffi::OsStrExt,
+        process::ExitStatusExt,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+    process::ExitStatus,
+    sync::{
+        atomic::{
+            AtomicU64,
+            Ordering,
+        },
+        Arc,
+    },
+    time::Duration,
+};
+
+use anyhow::Context;
+use futures::TryStreamExt;
+use serde_json::{
+    json,
+    Value as JsonValue,
+};
+use storage::{
+    encrypted_object_key,
+    Storage,
+    StorageExt,
+};
+use tokio::{
+    io::AsyncWriteExt,
+    process::Command as TokioCommand,
+};
+use url::Url;
+
+#[derive(Clone, Debug, Default)]
+pub struct NodeSandboxConfig {
+    /// Memory limit for each worker. Enforced with the worker's cgroup when
+    /// `cgroup_root` is set, and always with V8's heap limit.
+    pub memory_limit_mb: Option<u64>,
+    /// CPU time a single request may use. Requires `cgroup_root`.
+    pub cpu_time_limit: Option<Duration>,
+    /// Share of a CPU each worker may use, in thousandths of a core. Requires
+    /// `cgroup_root`.
+    pub cpu_quota_millicores: Option<u64>,
+    /// Maximum number of processes and threads in each worker. Requires
+    /// `cgroup_root`.
+    pub max_processes: Option<u64>,
+    /// Largest file a worker may write.
+    pub max_file_size_mb: Option<u64>,
+    /// A cgroup v2 directory delegated to the backend. Each worker runs in its
+    /// own child cgroup.
+    pub cgroup_root: Option<PathBuf>,
+    /// Run workers with a read-only view of the filesystem, except for their
+    /// private temp directory.
+    pub read_only_filesystem: bool,
+    /// Hosts actions may connect to, in addition to the backend itself. `None`
+    /// allows all outbound connections. Restricting hosts also isolates the
+    /// filesystem.
+    pub allowed_hosts: Option<Vec<String>>,
+}
+
+/// Fields of an executor request holding URLs the worker itself must reach.
+pub const REQUEST_URL_POINTERS: [&str; 5] = [
+    "/backendAddress",
+    "/uploadUrl",
+    "/sourcePackage/uri",
+    "/sourcePackage/bundled_source/uri",
+    "/sourcePackage/external_deps/uri",
+];
+
+/// Environment variables workers inherit from the backend. Everything else,
+/// like credentials the backend was started with, is withheld.
+const WORKER_ENV_ALLOWLIST: [&str; 4] = ["PATH", "LANG", "NODE_PATH", "TZ"];
+
+/// Directories and files from the host a bubblewrapped worker needs besides
+/// Node.js itself: shared libraries, and a shell and `env` for running `npm`.
+const SYSTEM_PATHS: [&str; 7] = [
+    "/lib",
+    "/lib64",
+    "/usr/lib",
+    "/usr/lib64",
+    "/etc/ld.so.cache",
+    "/bin/sh",
+    "/usr/bin/env",
+];
+
+/// The ways a worker can break out of its limits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SandboxViolation {
+    MemoryLimit,
+    CpuTimeLimit,
+    FileSizeLimit,
+}
+
+impl SandboxViolation {
+    pub fn metric_label(&self) -> &'static str {
+        match self {
+            SandboxViolation::MemoryLimit => "memory_limit",
+            SandboxViolation::CpuTimeLimit => "cpu_time_limit",
+            SandboxViolation::FileSizeLimit => "file_size_limit",
+        }
+    }
+
+    /// An error response in the format the executor returns, so the violation
+    /// surfaces as an action error.
+    pub fn response_json(&self, config: &NodeSandboxConfig) -> JsonValue {
+        let (name, message) = match self {
+            SandboxViolation::MemoryLimit => (
+                "MemoryLimitExceeded",
+                format!(
+                    "Function execution exceeded the memory limit of {} MB and was stopped.",
+                    config.memory_limit_mb.unwrap_or_default()
+                ),
+            ),
+            SandboxViolation::CpuTimeLimit => (
+                "CpuTimeLimitExceeded",
+                format!(
+                    "Function execution used more than {:?} of CPU time and was stopped.",
+                    config.cpu_time_limit.unwrap_or_default()
+                ),
+            ),
+            SandboxViolation::FileSizeLimit => (
+                "FileSizeLimitExceeded",
+                format!(
+                    "Function execution tried to write a file larger than {} MB and was stopped.",
+                    config.max_file_size_mb.unwrap_or_default()
+                ),
+            ),
+        };
+        json!({
+            "type": "error",
+            "name": name,
+            "message": message,
+        })
+    }
+}
+
+impl NodeSandboxConfig {
+    /// Whether workers run inside bubblewrap.
+    pub fn uses_bwrap(&self) -> bool {
+        self.read_only_filesystem || self.allowed_hosts.is_some()
+    }
+
+    /// Build the command that starts a worker, wrapping `node` in bubblewrap if
+    /// the filesystem or network should be isolated. `egress_socket` is the
+    /// [`EgressProxy`](crate::egress_proxy::EgressProxy) socket for a worker
+    /// with restricted outbound hosts.
+    pub fn worker_command(
+        &self,
+        node_path: &str,
+        source_path: &Path,
+        tmp_dir: &Path,
+        egress_socket: Option<&Path>,
+        cgroup: Option<&WorkerCgroup>,
+    ) -> anyhow::Result<TokioCommand> {
+        let (mut cmd, sandbox_path) = if self.uses_bwrap() {
+            let node_path = resolve_program(node_path)?;
+            // Bind the whole installation, so `npm` is available to build
+            // dependencies.
+            let node_prefix = fs::canonicalize(&node_path)?
+                .parent()
+                .and_then(Path::parent)
+                .context("Node.js binary must be in a `bin` directory")?
+                .to_owned();
+            let source_dir = source_path
+                .parent()
+                .context("Executor bundle must be in a directory")?;
+            let mut cmd = TokioCommand::new(resolve_program("bwrap")?);
+            for path in SYSTEM_PATHS {
+                cmd.args(["--ro-bind-try", path, path]);
+            }
+            for path in [node_prefix.as_path(), node_path.as_path(), source_dir] {
+                cmd.arg("--ro-bind").arg(path).arg(path);
+            }
+            cmd.arg("--bind")
+                .arg(tmp_dir)
+                .arg(tmp_dir)
+                .arg("--chdir")
+                .arg(tmp_dir)
+                .args(["--dev", "/dev", "--proc", "/proc"])
+                .args(["--unshare-ipc", "--unshare-pid", "--unshare-uts"]);
+            if self.allowed_hosts.is_some() {
+                cmd.arg("--unshare-net");
+            }
+            cmd.args(["--new-session", "--die-with-parent", "--"])
+                .arg(&node_path);
+            let sandbox_path = format!("{}:/usr/bin:/bin", node_prefix.join("bin").display());
+            (cmd, Some(sandbox_path))
+        } else {
+            (TokioCommand::new(node_path), None)
+        };
+        if let Some(memory_limit_mb) = self.memory_limit_mb {
+            cmd.arg(format!("--max-old-space-size={memory_limit_mb}"));
+        }
+        cmd.arg(source_path).arg("--worker");
+        if let Some(egress_socket) = egress_socket {
+            cmd.arg(format!("--egress-proxy={}", egress_socket.display()));
+        }
+
+        cmd.env_clear();
+        for name in WORKER_ENV_ALLOWLIST {
+            if let Some(value) = std::env::var_os(name) {
+                cmd.env(name, value);
+            }
+        }
+        // Only the host's paths that are bound into the sandbox are useful.
+        if let Some(sandbox_path) = sandbox_path {
+            cmd.env("PATH", sandbox_path);
+        }
+        cmd.env("TMPDIR", tmp_dir).env("HOME", tmp_dir);
+
+        let max_file_size = self.max_file_size_mb.map(|mb| mb << 20);
+        let cgroup_procs = cgroup
+            .map(|cgroup| CString::new(cgroup.path.join("cgroup.procs").as_os_str().as_bytes()))
+            .transpose()?;
+        // SAFETY: The closure only makes async-signal-safe system calls and
+        // doesn't allocate.
+        unsafe {
+            cmd.pre_exec(move || {
+                // Join the worker's cgroup before exec, so everything the worker
+                // starts is accounted to it.
+                if let Some(cgroup_procs) = &cgroup_procs {
+                    let fd = libc::open(cgroup_procs.as_ptr(), libc::O_WRONLY);
+                    if fd < 0 || libc::write(fd, b"0".as_ptr().cast(), 1) != 1 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                    libc::close(fd);
+                }
+                if let Some(max_file_size) = max_file_size {
+                    let limit = libc::rlimit {
+                        rlim_cur: max_file_size,
+                        rlim_max: max_file_size,
+                    };
+                    if libc::setrlimit(libc::RLIMIT_FSIZE, &limit) != 0 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                }
+                Ok(())
+            });
+        }
+        Ok(cmd)
+    }
+
+    /// Create a cgroup for a new worker if limits need one.
+    pub fn create_cgroup(&self) -> anyhow::Result<Option<WorkerCgroup>> {
+        let Some(cgroup_root) = &self.cgroup_root else {
+            return Ok(None);
+        };
+        static NEXT_WORKER_ID: AtomicU64 = AtomicU64::new(0);
+        let path = cgroup_root.join(format!(
+            "node-worker-{}-{}",
+            std::process::id(),
+            NEXT_WORKER_ID.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir(&path)
+            .with_context(|| format!("Failed to create cgroup {}", path.display()))?;
+        let cgroup = WorkerCgroup { path };
+        if let Some(memory_limit_mb) = self.memory_limit_mb {
+            cgroup.write("memory.max", &(memory_limit_mb << 20).to_string())?;
+            cgroup.write("memory.swap.max", "0")?;
+        }
+        if let Some(millicores) = self.cpu_quota_millicores {
+            // Quota and period in microseconds.
+            cgroup.write("cpu.max", &format!("{} 100000", millicores * 100))?;
+        }
+        if let Some(max_processes) = self.max_processes {
+            cgroup.write("pids.max", &max_processes.to_string())?;
+        }
+        Ok(Some(cgroup))
+    }
+
+    /// Work out which limit, if any, caused a worker to exit.
+    pub fn exit_violation(
+        &self,
+        status: ExitStatus,
+        cgroup: Option<&WorkerCgroup>,
+        stderr: &[String],
+    ) -> Option<SandboxViolation> {
+        if let Some(cgroup) = cgroup
+            && cgroup.oom_kills() > 0
+        {
+            return Some(SandboxViolation::MemoryLimit);
+        }
+        if self.memory_limit_mb.is_some()
+            && stderr
+                .iter()
+                .any(|line| line.contains("JavaScript heap out of memory"))
+        {
+            return Some(SandboxViolation::MemoryLimit);
+        }
+        if self.max_file_size_mb.is_some() && status.signal() == Some(libc::SIGXFSZ) {
+            return Some(SandboxViolation::FileSizeLimit);
+        }
+        None
+    }
+}
+
+/// Copy the local files `request` points at, like source packages in
+/// [`LocalDirStorage`](storage::LocalDirStorage), into the worker's temp
+/// directory so a bubblewrapped worker can read them, and point `request` at
+/// the copies. Returns the copies so they can be removed after the request.
+pub async fn stage_local_files(
+    request: &mut JsonValue,
+    tmp_dir: &Path,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let mut staged = vec![];
+    for pointer in REQUEST_URL_POINTERS {
+        let Some(value) = request.pointer_mut(pointer) else {
+            continue;
+        };
+        let Some(path) = value
+            .as_str()
+            .and_then(|url| Url::parse(url).ok())
+            .filter(|url| url.scheme() == "file")
+            .and_then(|url| url.to_file_path().ok())
+        else {
+            continue;
+        };
+        // Already in the sandbox, like packages from `decrypt_packages`.
+        if path.starts_with(tmp_dir) {
+            continue;
+        }
+        let file_name = path.file_name().context("Missing file name")?;
+        let copy = tmp_dir.join(format!(
+            "staged-{}-{}",
+            staged.len(),
+            file_name.to_string_lossy()
+        ));
+        tokio::fs::copy(&path, &copy)
+            .await
+            .with_context(|| format!("Failed to copy {} into the sandbox", path.display()))?;
+        *value = JsonValue::from(
+            Url::from_file_path(&copy)
+                .map_err(|()| anyhow::anyhow!("Invalid path {}", copy.display()))?
+                .to_string(),
+        );
+        staged.push(copy);
+    }
+    Ok(staged)
+}
+
+/// Decrypt the source packages `request` points at with
+/// [`EncryptedStorage`](storage::EncryptedStorage) URIs into the worker's
+/// private temp directory, and point `request` at the plaintext copies.
+/// Returns the copies so they can be removed after the request.
+pub async fn decrypt_packages(
+    request: &mut JsonValue,
+    tmp_dir: &Path,
+    package_storage: &Arc<dyn Storage>,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let mut paths = vec![];
+    // The bundled source is referenced twice, so only decrypt it once.
+    let mut urls: BTreeMap<_, String> = BTreeMap::new();
+    for pointer in REQUEST_URL_POINTERS {
+        let Some(value) = request.pointer_mut(pointer) else {
+            continue;
+        };
+        let Some(key) = value.as_str().and_then(encrypted_object_key) else {
+            continue;
+        };
+        if let Some(url) = urls.get(&key) {
+            *value = JsonValue::from(url.clone());
+            continue;
+        }
+        let path = tmp_dir.join(format!("package-{}.zip", paths.len()));
+        let mut stream = package_storage
+            .get(&key)
+            .await?
+            .with_context(|| format!("Source package {key:?} not found"))?
+            .stream;
+        let mut file = tokio::fs::File::create(&path).await?;
+        while let Some(chunk) = stream.try_next().await? {
+            file.write_all(&chunk).await?;
+        }
+        file.flush().await?;
+        let url = Url::from_file_path(&path)
+            .map_err(|()| anyhow::anyhow!("Invalid path {}", path.display()))?
+            .to_string();
+        *value = JsonValue::from(url.clone());
+        urls.insert(key, url);
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// Find `program` on the backend's `PATH` unless it's already a path.
+fn resolve_program(program: &str) -> anyhow::Result<PathBuf> {
+    if program.contains('/') {
+        return Ok(PathBuf::from(program));
+    }
+    std::env::var_os("PATH")
+        .iter()
+        .flat_map(std::env::split_paths)
+        .map(|dir| dir.join(program))
+        .find(|path| path.is_file())
+        .with_context(|| format!("{program} isn't on the PATH"))
+}
+
+/// A cgroup holding one worker. It's removed when dropped.
+pub struct WorkerCgroup {
+    path: PathBuf,
+}
+
+impl WorkerCgroup {
+    fn write(&self, file: &str, value: &str) -> anyhow::Result<()> {
+        fs::write(self.path.join(file), value)
+            .with_context(|| format!("Failed to set {file} of cgroup {}", self.path.display()))
+    }
+
+    fn read_key(&self, file: &str, key: &str) -> Option<u64> {
+        let contents = fs::read_to_string(self.path.join(file)).ok()?;
+        contents.lines().find_map(|line| {
+            let (k, v) = line.split_once(' ')?;
+            if k == key {
+                v.parse().ok()
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Total CPU time used by the worker so far.
+    pub fn cpu_usage(&self) -> Option<Duration> {
+        self.read_key("cpu.stat", "usage_usec")
+            .map(Duration::from_micros)
+    }
+
+    fn oom_kills(&self) -> u64 {
+        self.read_key("memory.events", "oom_kill").unwrap_or(0)
+    }
+}
+
+impl Drop for WorkerCgroup {
+    fn drop(&mut self) {
+        // Make sure nothing is left running so the cgroup can be removed.
+        let _ = fs::write(self.path.join("cgroup.kill"), "1");
+        if let Err(e) = fs::remove_dir(&self.path) {
+            tracing::warn!("Failed to remove cgroup {}: {e}", self.path.display());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        os::unix::process::ExitStatusExt,
+        process::ExitStatus,
+    };
+
+    use runtime::prod::ProdRuntime;
+    use tempfile::TempDir;
+
+    use super::{
+        NodeSandboxConfig,
+        SandboxViolation,
+    };
+
+    #[convex_macro::prod_rt_test]
+    async fn test_worker_env_allowlist(_rt: ProdRuntime) -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let script = dir.path().join("print_env.sh");
+        fs::write(&script, "env > \"$TMPDIR/env\"\n")?;
+        // Stand in for `node` with a shell that dumps the worker's environment.
+        let status = NodeSandboxConfig::default()
+            .worker_command("/bin/sh", &script, dir.path(), None, None)?
+            .status()
+            .await?;
+        assert!(status.success());
+        let env = fs::read_to_string(dir.path().join("env"))?;
+        let names: Vec<_> = env
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .map(|(name, _)| name)
+            .collect();
+        assert!(names.contains(&"TMPDIR"), "{names:?}");
+        // Set by `cargo test`, but not allowlisted.
+        assert!(!names.contains(&"CARGO_PKG_NAME"), "{names:?}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_exit_violation() {
+        let config = NodeSandboxConfig {
+            memory_limit_mb: Some(128),
+            max_file_size_mb: Some(1),
+            ..Default::default()
+        };
+        let heap_oom = vec![
+            "FATAL ERROR: Reached heap limit Allocation failed - JavaScript heap out of memory"
+                .to_string(),
+        ];
+        assert_eq!(
+            config.exit_violation(ExitStatus::from_raw(libc::SIGABRT), None, &heap_oom),
+            Some(SandboxViolation::MemoryLimit)
+        );
+        assert_eq!(
+            config.exit_violation(ExitStatus::from_raw(libc::SIGXFSZ), None, &[]),
+            Some(SandboxViolation::FileSizeLimit)
+        );
+        assert_eq!(
+            config.exit_violation(ExitStatus::from_raw(libc::SIGSEGV), None, &[]),
+            None
+        );
+        // Without limits configured, crashes aren't attributed to them.
+        assert_eq!(
+            NodeSandboxConfig::default().exit_violation(
+                ExitStatus::from_raw(libc::SIGXFSZ),
+                None,
+                &heap_oom
+            ),
+            None
+        );
+    }
+}
@@ -0,0 +1,405 @@
+//! A pool of long-lived `local.cjs --worker` processes for
+//! [`LocalNodeExecutor`](crate::local::LocalNodeExecutor).
+//!
+//! A worker reads one JSON `{nonce, request}` per line from stdin and writes
+//! the same newline-delimited [`ResponsePart`]s to stdout as a one-off process
+//! would, followed by a `WorkerDone` line echoing the nonce and reporting its
+//! resident memory. User code shares stdout, so only a line with the right
+//! nonce ends the request. Workers keep the source packages they have
+//! downloaded, so requests are routed to an idle worker that last ran the same
+//! source package when there is one.
+//!
+//! Workers undo changes to globals after each request. A request that leaves
+//! timers, sockets or other handles running gets its worker recycled, so they
+//! can't leak into later requests. A worker with restricted outbound hosts
+//! has its own [`EgressProxy`], which only lets it reach the current request's
+//! backend and storage hosts while that request runs.
+use std::{
+    future,
+    path::{
+        Path,
+        PathBuf,
+    },
+    process::Stdio,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::Context;
+use common::{
+    knobs::{
+        NODE_WORKER_MAX_REQUESTS,
+        NODE_WORKER_MAX_RSS_MB,
+        NODE_WORKER_POOL_MAX_IDLE,
+    },
+    log_lines::LogLine,
+    runtime::tokio_spawn,
+};
+use parking_lot::Mutex;
+use serde::Deserialize;
+use serde_json::{
+    json,
+    Value as JsonValue,
+};
+use storage::Storage;
+use tempfile::TempDir;
+use tokio::{
+    io::{
+        AsyncBufReadExt,
+        AsyncWriteExt,
+        BufReader,
+        Lines,
+    },
+    process::{
+        Child,
+        ChildStdin,
+        ChildStdout,
+    },
+    sync::mpsc,
+};
+
+use crate::{
+    egress_proxy::EgressProxy,
+    executor::{
+        parse_streamed_response,
+        ResponsePart,
+    },
+    metrics::{
+        log_local_worker_retired,
+        log_local_worker_started,
+    },
+    sandbox::{
+        decrypt_packages,
+        stage_local_files,
+        NodeSandboxConfig,
+        SandboxViolation,
+        WorkerCgroup,
+    },
+};
+
+/// How often to check a request's CPU usage when it has a CPU time limit.
+const CPU_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+pub struct NodeWorkerPool {
+    node_path: String,
+    source_path: PathBuf,
+    sandbox: Arc<NodeSandboxConfig>,
+    // Most recently used last.
+    idle: Mutex<Vec<NodeWorker>>,
+}
+
+impl NodeWorkerPool {
+    pub fn new(node_path: String, source_path: PathBuf, sandbox: Arc<NodeSandboxConfig>) -> Self {
+        Self {
+            node_path,
+            source_path,
+            sandbox,
+            idle: Mutex::new(vec![]),
+        }
+    }
+
+    /// Take an idle worker, preferring one that has already loaded
+    /// `source_package_key`, or start a new one.
+    pub fn checkout(&self, source_package_key: Option<&str>) -> anyhow::Result<NodeWorker> {
+        let mut idle = self.idle.lock();
+        // Skip workers that exited while idle.
+        idle.retain_mut(|worker| matches!(worker.child.try_wait(), Ok(None)));
+        let cached = source_package_key.and_then(|key| {
+            idle.iter()
+                .rposition(|worker| worker.source_package_key.as_deref() == Some(key))
+        });
+        let worker = match cached {
+            Some(i) => Some(idle.remove(i)),
+            None => idle.pop(),
+        };
+        drop(idle);
+        match worker {
+            Some(worker) => Ok(worker),
+            None => NodeWorker::start(&self.node_path, &self.source_path, self.sandbox.clone()),
+        }
+    }
+
+    /// Return a worker that finished its request to the pool, unless it's due
+    /// to be recycled.
+    pub fn checkin(&self, worker: NodeWorker) {
+        if let Some(violation) = worker.violation {
+            log_local_worker_retired(violation.metric_label());
+            return;
+        }
+        if !worker.clean {
+            log_local_worker_retired("dangling_handles");
+            return;
+        }
+        if worker.num_requests >= *NODE_WORKER_MAX_REQUESTS {
+            log_local_worker_retired("max_requests");
+            return;
+        }
+        if worker.rss_bytes > *NODE_WORKER_MAX_RSS_MB * (1 << 20) {
+            tracing::info!(
+                "Retiring Node.js worker using {} MB",
+                worker.rss_bytes >> 20
+            );
+            log_local_worker_retired("memory");
+            return;
+        }
+        let mut idle = self.idle.lock();
+        idle.push(worker);
+        while idle.len() > *NODE_WORKER_POOL_MAX_IDLE {
+            idle.remove(0);
+            log_local_worker_retired("idle");
+        }
+    }
+
+    /// Stop all idle workers.
+    pub fn clear(&self) {
+        self.idle.lock().clear();
+    }
+
+    #[cfg(test)]
+    pub fn num_idle(&self) -> usize {
+        self.idle.lock().len()
+    }
+}
+
+/// A running worker process. It's killed when dropped.
+pub struct NodeWorker {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: Lines<BufReader<ChildStdout>>,
+    stderr: mpsc::UnboundedReceiver<String>,
+    num_requests: usize,
+    rss_bytes: u64,
+    // Whether the last request left no handles running.
+    clean: bool,
+    source_package_key: Option<String>,
+    sandbox: Arc<NodeSandboxConfig>,
+    // Set when the worker broke one of its limits and must not be reused.
+    violation: Option<SandboxViolation>,
+    // Dropped after `child`, so the worker is killed before they're cleaned up.
+    egress_proxy: Option<EgressProxy>,
+    cgroup: Option<WorkerCgroup>,
+    tmp_dir: TempDir,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WorkerDone {
+    kind: String,
+    nonce: String,
+    rss_bytes: u64,
+    clean: bool,
+}
+
+enum WorkerOutput {
+    Done {
+        result_values: Vec<JsonValue>,
+        rss_bytes: u64,
+        clean: bool,
+    },
+    Exited,
+}
+
+impl NodeWorker {
+    fn start(
+        node_path: &str,
+        source_path: &Path,
+        sandbox: Arc<NodeSandboxConfig>,
+    ) -> anyhow::Result<Self> {
+        let tmp_dir = TempDir::new()?;
+        let cgroup = sandbox.create_cgroup()?;
+        let egress_socket = tmp_dir.path().join("egress.sock");
+        let egress_proxy = sandbox
+            .allowed_hosts
+            .as_ref()
+            .map(|allowed_hosts| EgressProxy::start(&egress_socket, allowed_hosts))
+            .transpose()?;
+        let mut child = sandbox
+            .worker_command(
+                node_path,
+                source_path,
+                tmp_dir.path(),
+                egress_proxy.is_some().then_some(egress_socket.as_path()),
+                cgroup.as_ref(),
+            )?
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .context("Failed to start Node.js worker")?;
+        let stdin = child.stdin.take().context("Missing worker stdin")?;
+        let stdout = BufReader::new(child.stdout.take().context("Missing worker stdout")?).lines();
+        let mut stderr_lines =
+            BufReader::new(child.stderr.take().context("Missing worker stderr")?).lines();
+        // Keep draining stderr so a chatty worker can't block on a full pipe.
+        let (stderr_sender, stderr) = mpsc::unbounded_channel();
+        tokio_spawn("node_worker_stderr", async move {
+            while let Ok(Some(line)) = stderr_lines.next_line().await {
+                if stderr_sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        log_local_worker_started();
+        Ok(Self {
+            child,
+            stdin,
+            stdout,
+            stderr,
+            num_requests: 0,
+            rss_bytes: 0,
+            clean: true,
+            source_package_key: None,
+            sandbox,
+            violation: None,
+            egress_proxy,
+            cgroup,
+            tmp_dir,
+        })
+    }
+
+    /// Send `request` to the worker and wait for its result, forwarding log
+    /// lines as they arrive. If the worker breaks one of its sandbox limits,
+    /// the result is an error response describing the violation. Encrypted
+    /// source packages are read from `package_storage`.
+    ///
+    /// The worker must not be reused if this fails or is cancelled.
+    pub async fn run(
+        &mut self,
+        request: &str,
+        source_package_key: Option<&str>,
+        package_storage: Option<&Arc<dyn Storage>>,
+        log_line_sender: &mpsc::UnboundedSender<LogLine>,
+    ) -> anyhow::Result<JsonValue> {
+        let mut request = JsonValue::from_str(request)?;
+        let mut staged_files = match package_storage {
+            Some(package_storage) => {
+                decrypt_packages(&mut request, self.tmp_dir.path(), package_storage).await?
+            },
+            None => vec![],
+        };
+        if self.sandbox.uses_bwrap() {
+            staged_files.extend(stage_local_files(&mut request, self.tmp_dir.path()).await?);
+        }
+        if let Some(egress_proxy) = &self.egress_proxy {
+            egress_proxy.grant_request_hosts(&request);
+        }
+        let nonce = format!("{:032x}", rand::random::<u128>());
+        let mut line = serde_json::to_vec(&json!({
+            "nonce": nonce,
+            "request": request,
+        }))?;
+        line.push(b'\n');
+        self.stdin.write_all(&line).await?;
+        self.stdin.flush().await?;
+        self.num_requests += 1;
+        if source_package_key.is_some() {
+            self.source_package_key = source_package_key.map(str::to_owned);
+        }
+
+        let cpu_deadline = match (&self.cgroup, self.sandbox.cpu_time_limit) {
+            (Some(cgroup), Some(limit)) => cgroup.cpu_usage().map(|usage| (cgroup, usage + limit)),
+            _ => None,
+        };
+        let cpu_watchdog = async {
+            let Some((cgroup, deadline)) = cpu_deadline else {
+                return future::pending().await;
+            };
+            loop {
+                tokio::time::sleep(CPU_POLL_INTERVAL).await;
+                if cgroup.cpu_usage().is_some_and(|usage| usage >= deadline) {
+                    return;
+                }
+            }
+        };
+        let output = tokio::select! {
+            output = read_output(&mut self.stdout, &nonce, log_line_sender) => Some(output?),
+            () = cpu_watchdog => None,
+        };
+        if let Some(egress_proxy) = &self.egress_proxy {
+            egress_proxy.revoke_request_hosts();
+        }
+        for path in staged_files {
+            tokio::fs::remove_file(path).await?;
+        }
+        let (mut result_values, rss_bytes, clean) = match output {
+            Some(WorkerOutput::Done {
+                result_values,
+                rss_bytes,
+                clean,
+            }) => (result_values, rss_bytes, clean),
+            Some(WorkerOutput::Exited) => {
+                let status = self.child.wait().await?;
+                let mut stderr = vec![];
+                while let Some(line) = self.stderr.recv().await {
+                    stderr.push(line);
+                }
+                if let Some(violation) =
+                    self.sandbox
+                        .exit_violation(status, self.cgroup.as_ref(), &stderr)
+                {
+                    return Ok(self.violated(violation));
+                }
+                for line in stderr {
+                    tracing::error!("{line}");
+                }
+                log_local_worker_retired("error");
+                anyhow::bail!("Local process did not exit successfully: {status}");
+            },
+            None => return Ok(self.violated(SandboxViolation::CpuTimeLimit)),
+        };
+        self.rss_bytes = rss_bytes;
+        self.clean = clean;
+        // Stderr output is only interesting when the worker fails.
+        while self.stderr.try_recv().is_ok() {}
+
+        anyhow::ensure!(
+            result_values.len() <= 1,
+            "Received more than one result from lambda response"
+        );
+        result_values
+            .pop()
+            .ok_or_else(|| anyhow::anyhow!("Received no result from lambda response"))
+    }
+
+    fn violated(&mut self, violation: SandboxViolation) -> JsonValue {
+        tracing::warn!("Node.js worker exceeded its {violation:?}");
+        self.violation = Some(violation);
+        violation.response_json(&self.sandbox)
+    }
+}
+
+/// Read the worker's response to a request, up to its `WorkerDone` line for
+/// `nonce`.
+async fn read_output(
+    stdout: &mut Lines<BufReader<ChildStdout>>,
+    nonce: &str,
+    log_line_sender: &mpsc::UnboundedSender<LogLine>,
+) -> anyhow::Result<WorkerOutput> {
+    let mut result_values = vec![];
+    while let Some(line) = stdout.next_line().await? {
+        if let Ok(done) = serde_json::from_str::<WorkerDone>(&line)
+            && done.kind == "WorkerDone"
+        {
+            if done.nonce != nonce {
+                tracing::warn!("Ignoring forged WorkerDone line from Node.js worker");
+                continue;
+            }
+            return Ok(WorkerOutput::Done {
+                result_values,
+                rss_bytes: done.rss_bytes,
+                clean: done.clean,
+            });
+        }
+        for part in parse_streamed_response(&line)? {
+            match part {
+                ResponsePart::LogLine(log_line) => {
+                    log_line_sender.send(log_line)?;
+                },
+                ResponsePart::Result(result) => result_values.push(result),
+            }
+        }
+    }
+    Ok(WorkerOutput::Exited)
+}
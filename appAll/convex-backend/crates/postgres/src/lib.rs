@@ -29,6 +29,8 @@ use std::{
         LazyLock,
     },
     time::{
+        Duration,
+        Instant,
         SystemTime,
         UNIX_EPOCH,
     },
@@ -65,9 +67,11 @@ use common::{
         Persistence,
         PersistenceGlobalKey,
         PersistenceReader,
+        PersistenceTableSize,
         RetentionValidator,
         TimestampRange,
     },
+    persistence_compression::DocumentCompressor,
     query::Order,
     sha256::Sha256,
     types::{
@@ -111,6 +115,7 @@ use itertools::{
     Itertools,
 };
 use native_tls::TlsConnector;
+use parking_lot::Mutex;
 use postgres_native_tls::MakeTlsConnector;
 use serde::Deserialize as _;
 use serde_json::Value as JsonValue;
@@ -132,6 +137,8 @@ pub struct PostgresPersistence {
 
     // Used by the reader.
     read_pool: Arc<ConvexPgPool>,
+    replica: Option<Arc<PostgresReplica>>,
+    compressor: Arc<DocumentCompressor>,
     version: PersistenceVersion,
 }
 
@@ -143,22 +150,38 @@ pub enum ConnectError {
     Other(#[from] anyhow::Error),
 }
 
-#[derive(Copy, Clone)]
+#[derive(Clone)]
 pub struct PostgresOptions {
     pub allow_read_only: bool,
     pub version: PersistenceVersion,
+    /// Schema holding this deployment's tables, so several deployments can
+    /// share a database. It's created if it doesn't exist. Defaults to the
+    /// connection's search path (usually `public`).
+    pub schema: Option<String>,
+    /// Read replica (hot standby) of the same database. Snapshot reads that
+    /// the replica has already replayed are served from it, everything else
+    /// goes to the primary.
+    pub replica_url: Option<String>,
 }
 
 pub struct PostgresReaderOptions {
     pub db_should_be_leader: bool,
     pub version: PersistenceVersion,
+    pub schema: Option<String>,
+    pub replica_url: Option<String>,
 }
 
 impl PostgresPersistence {
     pub async fn new(url: &str, options: PostgresOptions) -> Result<Self, ConnectError> {
-        let pool = Self::create_pool(url)?;
+        let pool = Self::create_pool(url, options.schema.as_deref())?;
         let newly_created = {
             let client = pool.get_connection("init_sql").await?;
+            if let Some(schema) = &options.schema {
+                client
+                    .batch_execute(&format!("CREATE SCHEMA IF NOT EXISTS {schema};"))
+                    .await
+                    .map_err(Into::<anyhow::Error>::into)?;
+            }
             client
                 .batch_execute(INIT_SQL)
                 .await
@@ -174,29 +197,62 @@ impl PostgresPersistence {
             pool.status().max_size
         );
 
+        let replica = Self::create_replica(options.replica_url.as_deref(), &options.schema)?;
+
         let lease = Lease::acquire(pool.clone()).await?;
         Ok(Self {
             newly_created: newly_created.into(),
             lease,
             read_pool: Arc::new(pool),
+            replica,
+            compressor: Arc::new(DocumentCompressor::new()),
             version: options.version,
         })
     }
 
     pub fn new_reader(url: &str, options: PostgresReaderOptions) -> anyhow::Result<PostgresReader> {
         Ok(PostgresReader {
-            read_pool: Arc::new(Self::create_pool(url)?),
+            read_pool: Arc::new(Self::create_pool(url, options.schema.as_deref())?),
+            replica: Self::create_replica(options.replica_url.as_deref(), &options.schema)?,
+            // Dictionaries are loaded the first time a document needs one.
+            compressor: Arc::new(DocumentCompressor::new()),
             db_should_be_leader: options.db_should_be_leader,
             version: options.version,
         })
     }
 
+    fn create_replica(
+        replica_url: Option<&str>,
+        schema: &Option<String>,
+    ) -> anyhow::Result<Option<Arc<PostgresReplica>>> {
+        let Some(replica_url) = replica_url else {
+            return Ok(None);
+        };
+        // The replica's schema is created by replaying the primary, so unlike
+        // the primary there is nothing to initialize here.
+        let pool = Self::create_pool(replica_url, schema.as_deref())?;
+        tracing::info!("Serving caught up snapshot reads from a Postgres replica");
+        Ok(Some(Arc::new(PostgresReplica::new(pool))))
+    }
+
     async fn is_read_only(client: &PostgresConnection) -> anyhow::Result<bool> {
         Ok(client.query_opt(CHECK_IS_READ_ONLY, &[]).await?.is_some())
     }
 
-    fn create_pool(url: &str) -> anyhow::Result<ConvexPgPool> {
-        let pg_config = tokio_postgres::Config::from_str(url)?;
+    fn create_pool(url: &str, schema: Option<&str>) -> anyhow::Result<ConvexPgPool> {
+        let mut pg_config = tokio_postgres::Config::from_str(url)?;
+        if let Some(schema) = schema {
+            validate_schema_name(schema)?;
+            // All statements, including lease handling, use unqualified table
+            // names, so pinning the search path for every connection in the pool
+            // is enough to keep deployments apart.
+            let search_path = format!("-c search_path={schema}");
+            let options = match pg_config.get_options() {
+                Some(options) => format!("{options} {search_path}"),
+                None => search_path,
+            };
+            pg_config.options(&options);
+        }
         let connector = TlsConnector::builder().build()?;
         let connector = MakeTlsConnector::new(connector);
 
@@ -220,6 +276,8 @@ impl Persistence for PostgresPersistence {
     fn reader(&self) -> Arc<dyn PersistenceReader> {
         Arc::new(PostgresReader {
             read_pool: self.read_pool.clone(),
+            replica: self.replica.clone(),
+            compressor: self.compressor.clone(),
             db_should_be_leader: true,
             version: self.version,
         })
@@ -239,6 +297,21 @@ impl Persistence for PostgresPersistence {
             }
         }));
 
+        // Encode documents up front so compressing them doesn't hold the
+        // transaction open.
+        let documents = documents
+            .iter()
+            .map(|update| {
+                document_params(
+                    &self.compressor,
+                    update.ts,
+                    update.id,
+                    &update.value,
+                    update.prev_ts,
+                )
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
         // True, the below might end up failing and not changing anything.
         self.newly_created.store(false, SeqCst);
         self.lease
@@ -284,13 +357,8 @@ impl Persistence for PostgresPersistence {
                         let mut document_chunks = documents.chunks_exact(CHUNK_SIZE);
                         for chunk in &mut document_chunks {
                             let mut params = Vec::with_capacity(chunk.len() * NUM_DOCUMENT_PARAMS);
-                            for update in chunk {
-                                params.extend(document_params(
-                                    update.ts,
-                                    update.id,
-                                    &update.value,
-                                    update.prev_ts,
-                                ));
+                            for document in chunk {
+                                params.extend(document.iter().cloned());
                             }
                             let future = async {
                                 let timer = metrics::insert_document_chunk_timer();
@@ -302,13 +370,8 @@ impl Persistence for PostgresPersistence {
                         }
 
                         // After we've inserted all the full document chunks, drain the remainder.
-                        for update in document_chunks.remainder() {
-                            let params = document_params(
-                                update.ts,
-                                update.id,
-                                &update.value,
-                                update.prev_ts,
-                            );
+                        for document in document_chunks.remainder() {
+                            let params = document.clone();
                             let future = async {
                                 let timer = metrics::insert_one_document_timer();
                                 tx.execute_raw(&insert_document, params).await?;
@@ -475,11 +538,95 @@ impl Persistence for PostgresPersistence {
             })
             .await
     }
+
+    fn document_compressor(&self) -> Option<Arc<DocumentCompressor>> {
+        Some(self.compressor.clone())
+    }
+}
+
+/// A streaming replica of the primary database.
+///
+/// The replica is only used for reads at timestamps it has already replayed.
+/// The committer writes `max_repeatable_ts` after every commit at or below it
+/// has landed, and the replica replays the primary's WAL in order, so once
+/// the replica shows a `max_repeatable_ts` it also has all commits up to it.
+struct PostgresReplica {
+    pool: ConvexPgPool,
+    watermark: Mutex<ReplicaWatermark>,
+}
+
+#[derive(Default)]
+struct ReplicaWatermark {
+    /// The highest `max_repeatable_ts` seen on the replica.
+    ts: Option<Timestamp>,
+    last_checked: Option<Instant>,
+}
+
+impl PostgresReplica {
+    fn new(pool: ConvexPgPool) -> Self {
+        Self {
+            pool,
+            watermark: Mutex::new(ReplicaWatermark::default()),
+        }
+    }
+
+    /// Returns a connection to the replica if it has replayed every commit
+    /// at or before `ts`, or `None` if it is lagging behind.
+    async fn get_connection_at(
+        &self,
+        name: &'static str,
+        ts: Timestamp,
+    ) -> anyhow::Result<Option<PostgresConnection>> {
+        let caught_up = {
+            let mut watermark = self.watermark.lock();
+            if watermark.ts.is_some_and(|watermark_ts| ts <= watermark_ts) {
+                true
+            } else {
+                // Don't look at the replica again on every read of a recent
+                // snapshot, it won't have caught up that quickly. This also keeps
+                // an unreachable replica from slowing down every read.
+                if watermark
+                    .last_checked
+                    .is_some_and(|t| t.elapsed() < *POSTGRES_REPLICA_LAG_CHECK_INTERVAL)
+                {
+                    return Ok(None);
+                }
+                watermark.last_checked = Some(Instant::now());
+                false
+            }
+        };
+        if caught_up {
+            return Ok(Some(self.pool.get_connection(name).await?));
+        }
+        let client = self.pool.get_connection(name).await?;
+        let replica_ts =
+            query_persistence_global(&client, PersistenceGlobalKey::MaxRepeatableTimestamp)
+                .await?
+                .map(Timestamp::try_from)
+                .transpose()?;
+        let watermark_ts = {
+            let mut watermark = self.watermark.lock();
+            watermark.ts = cmp::max(watermark.ts, replica_ts);
+            watermark.ts
+        };
+        if let Some(watermark_ts) = watermark_ts {
+            metrics::log_replica_lag(ts, watermark_ts);
+        }
+        if watermark_ts.is_some_and(|watermark_ts| ts <= watermark_ts) {
+            Ok(Some(client))
+        } else {
+            Ok(None)
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct PostgresReader {
     read_pool: Arc<ConvexPgPool>,
+    /// Reads at timestamps the replica has caught up to go here instead of
+    /// `read_pool`.
+    replica: Option<Arc<PostgresReplica>>,
+    compressor: Arc<DocumentCompressor>,
     /// Set `db_should_be_leader` if this PostgresReader should be connected
     /// to the database leader. In particular, we protect against heterogenous
     /// connection pools where one connection is to the leader and another is to
@@ -489,6 +636,30 @@ pub struct PostgresReader {
 }
 
 impl PostgresReader {
+    /// Returns a connection for reading a snapshot at `ts`, and whether it is
+    /// to the replica. Falls back to the primary if there is no replica, it
+    /// hasn't replayed `ts` yet or it can't be reached.
+    async fn get_connection_at(
+        &self,
+        name: &'static str,
+        ts: Timestamp,
+    ) -> anyhow::Result<(PostgresConnection, bool)> {
+        if let Some(replica) = &self.replica {
+            match replica.get_connection_at(name, ts).await {
+                Ok(Some(client)) => {
+                    metrics::log_replica_read(name, true);
+                    return Ok((client, true));
+                },
+                Ok(None) => {},
+                Err(e) => {
+                    tracing::warn!("Reading {name} from the primary, replica failed: {e:#}");
+                },
+            }
+            metrics::log_replica_read(name, false);
+        }
+        Ok((self.read_pool.get_connection(name).await?, false))
+    }
+
     fn initial_id_param(order: Order) -> Param {
         Param::Bytes(match order {
             Order::Asc => InternalId::BEFORE_ALL_BYTES.to_vec(),
@@ -496,7 +667,7 @@ impl PostgresReader {
         })
     }
 
-    fn row_to_document(
+    async fn row_to_document(
         &self,
         row: Row,
     ) -> anyhow::Result<(
@@ -505,11 +676,11 @@ impl PostgresReader {
         Option<ResolvedDocument>,
         Option<Timestamp>,
     )> {
-        let (ts, id, doc, prev_ts) = self.row_to_document_inner(row)?;
+        let (ts, id, doc, prev_ts) = self.row_to_document_inner(row).await?;
         Ok((ts, id, doc, prev_ts))
     }
 
-    fn row_to_document_inner(
+    async fn row_to_document_inner(
         &self,
         row: Row,
     ) -> anyhow::Result<(
@@ -524,6 +695,10 @@ impl PostgresReader {
         let ts = Timestamp::try_from(ts)?;
         let tablet_id_bytes: Vec<u8> = row.get(2);
         let binary_value: Vec<u8> = row.get(3);
+        let binary_value = self
+            .compressor
+            .decode_with_reader(self, &binary_value)
+            .await?;
         let json_value: JsonValue = serde_json::from_slice(&binary_value)
             .context("Failed to deserialize database value")?;
 
@@ -557,7 +732,12 @@ impl PostgresReader {
         retention_validator: Arc<dyn RetentionValidator>,
     ) {
         let timer = metrics::load_documents_timer();
-        let client = self.read_pool.get_connection("load_documents").await?;
+        // Unbounded ranges (e.g. tailing the log) always end up on the primary.
+        let max_ts = range
+            .max_timestamp_exclusive()
+            .pred()
+            .unwrap_or(Timestamp::MIN);
+        let (client, _) = self.get_connection_at("load_documents", max_ts).await?;
         let mut num_returned = 0;
         let mut last_ts = match order {
             Order::Asc => Timestamp::MIN,
@@ -590,7 +770,7 @@ impl PostgresReader {
             futures::pin_mut!(row_stream);
 
             while let Some(row) = row_stream.try_next().await? {
-                let (ts, document_id, document, prev_ts) = self.row_to_document(row)?;
+                let (ts, document_id, document, prev_ts) = self.row_to_document(row).await?;
                 rows_loaded += 1;
                 last_ts = ts;
                 last_tablet_id_param = Param::TableId(document_id.table());
@@ -693,7 +873,7 @@ impl PostgresReader {
         let _timer = metrics::query_index_timer();
         let (mut lower, mut upper) = to_sql_bounds(interval.clone());
 
-        let client = self.read_pool.get_connection("index_scan").await?;
+        let (client, on_replica) = self.get_connection_at("index_scan", read_timestamp).await?;
         let mut stats = QueryIndexStats::new();
 
         // We use the size_hint to determine the batch size. This means in the
@@ -717,7 +897,13 @@ impl PostgresReader {
                 order,
                 batch_size,
             );
-            let check_db_leader = self.check_db_leader(&client).await?;
+            // A replica is never the leader, but it is only picked once it has
+            // replayed `read_timestamp`.
+            let check_db_leader = if on_replica {
+                None
+            } else {
+                self.check_db_leader(&client).await?
+            };
 
             let prepare_timer = metrics::query_index_sql_prepare_timer();
             let stmt = client.prepare_cached(query).await?;
@@ -798,6 +984,10 @@ impl PostgresReader {
                     anyhow::anyhow!("Dangling index reference for {:?} {:?}", key, ts)
                 })?;
                 let binary_value: Vec<u8> = row.get(8);
+                let binary_value = self
+                    .compressor
+                    .decode_with_reader(self, &binary_value)
+                    .await?;
                 let json_value: JsonValue = serde_json::from_slice(&binary_value)
                     .context("Failed to deserialize database value")?;
                 anyhow::ensure!(
@@ -873,6 +1063,29 @@ impl PostgresReader {
     }
 }
 
+async fn query_persistence_global(
+    client: &PostgresConnection,
+    key: PersistenceGlobalKey,
+) -> anyhow::Result<Option<JsonValue>> {
+    let stmt = client.prepare_cached(GET_PERSISTENCE_GLOBAL).await?;
+    let params = vec![Param::PersistenceGlobalKey(key)];
+    let row_stream = client.query_raw(&stmt, params).await?;
+    futures::pin_mut!(row_stream);
+
+    let row = row_stream.try_next().await?;
+    let value = row.map(|r| -> anyhow::Result<JsonValue> {
+        let binary_value: Vec<u8> = r.get(0);
+        let mut json_deserializer = serde_json::Deserializer::from_slice(&binary_value);
+        // XXX: this is bad, but shapes can get much more nested than convex values
+        json_deserializer.disable_recursion_limit();
+        let json_value = JsonValue::deserialize(&mut json_deserializer)
+            .with_context(|| format!("Invalid JSON at persistence key {key:?}"))?;
+        json_deserializer.end()?;
+        Ok(json_value)
+    });
+    value.transpose()
+}
+
 fn parse_row(row: &Row) -> anyhow::Result<IndexEntry> {
     let bytes: Vec<u8> = row.get(0);
     let index_id =
@@ -914,7 +1127,12 @@ impl PersistenceReader for PostgresReader {
     ) -> anyhow::Result<BTreeMap<(InternalDocumentId, Timestamp), DocumentLogEntry>> {
         let timer = metrics::prev_revisions_timer();
 
-        let client = self.read_pool.get_connection("previous_revisions").await?;
+        let max_ts = ids
+            .iter()
+            .map(|(_, ts)| *ts)
+            .max()
+            .unwrap_or(Timestamp::MIN);
+        let (client, _) = self.get_connection_at("previous_revisions", max_ts).await?;
         let (prev_rev_chunk, prev_rev) = try_join!(
             client.prepare_cached(PREV_REV_CHUNK),
             client.prepare_cached(PREV_REV)
@@ -957,7 +1175,7 @@ impl PersistenceReader for PostgresReader {
             while let Some(row) = row_stream.try_next().await? {
                 let ts: i64 = row.get(6);
                 let ts = Timestamp::try_from(ts)?;
-                let (prev_ts, id, maybe_doc, prev_prev_ts) = self.row_to_document(row)?;
+                let (prev_ts, id, maybe_doc, prev_prev_ts) = self.row_to_document(row).await?;
                 min_ts = cmp::min(ts, min_ts);
                 anyhow::ensure!(result
                     .insert(
@@ -987,9 +1205,13 @@ impl PersistenceReader for PostgresReader {
     ) -> anyhow::Result<BTreeMap<DocumentPrevTsQuery, DocumentLogEntry>> {
         let timer = metrics::previous_revisions_of_documents_timer();
 
-        let client = self
-            .read_pool
-            .get_connection("previous_revisions_of_documents")
+        let max_ts = ids
+            .iter()
+            .map(|DocumentPrevTsQuery { ts, .. }| *ts)
+            .max()
+            .unwrap_or(Timestamp::MIN);
+        let (client, _) = self
+            .get_connection_at("previous_revisions_of_documents", max_ts)
             .await?;
         let (exact_rev_chunk, exact_rev) = try_join!(
             client.prepare_cached(EXACT_REV_CHUNK),
@@ -1028,7 +1250,7 @@ impl PersistenceReader for PostgresReader {
             while let Some(row) = row_stream.try_next().await? {
                 let ts: i64 = row.get(6);
                 let ts = Timestamp::try_from(ts)?;
-                let (prev_ts, id, maybe_doc, prev_prev_ts) = self.row_to_document(row)?;
+                let (prev_ts, id, maybe_doc, prev_prev_ts) = self.row_to_document(row).await?;
                 anyhow::ensure!(result
                     .insert(
                         DocumentPrevTsQuery { id, ts, prev_ts },
@@ -1080,32 +1302,57 @@ impl PersistenceReader for PostgresReader {
         &self,
         key: PersistenceGlobalKey,
     ) -> anyhow::Result<Option<JsonValue>> {
+        // Globals aren't versioned by timestamp, so always read the latest
+        // value from the primary.
         let client = self
             .read_pool
             .get_connection("get_persistence_global")
             .await?;
-        let stmt = client.prepare_cached(GET_PERSISTENCE_GLOBAL).await?;
-        let params = vec![Param::PersistenceGlobalKey(key)];
-        let row_stream = client.query_raw(&stmt, params).await?;
-        futures::pin_mut!(row_stream);
-
-        let row = row_stream.try_next().await?;
-        let value = row.map(|r| -> anyhow::Result<JsonValue> {
-            let binary_value: Vec<u8> = r.get(0);
-            let mut json_deserializer = serde_json::Deserializer::from_slice(&binary_value);
-            // XXX: this is bad, but shapes can get much more nested than convex values
-            json_deserializer.disable_recursion_limit();
-            let json_value = JsonValue::deserialize(&mut json_deserializer)
-                .with_context(|| format!("Invalid JSON at persistence key {key:?}"))?;
-            json_deserializer.end()?;
-            Ok(json_value)
-        });
-        value.transpose()
+        query_persistence_global(&client, key).await
     }
 
     fn version(&self) -> PersistenceVersion {
         self.version
     }
+
+    async fn table_size_stats(&self) -> anyhow::Result<Vec<PersistenceTableSize>> {
+        let client = self.read_pool.get_connection("table_size_stats").await?;
+        let (table_size, compressed_count) = try_join!(
+            client.prepare_cached(TABLE_SIZE_QUERY),
+            client.prepare_cached(COMPRESSED_DOCUMENT_FRACTION_QUERY)
+        )?;
+        let no_params: &[Param] = &[];
+        let mut stats: Vec<_> = client
+            .query_raw(&table_size, no_params)
+            .await?
+            .map(|row| {
+                let row = row?;
+                let data_bytes: i64 = row.get(1);
+                let index_bytes: i64 = row.get(2);
+                let row_count: i64 = row.get(3);
+                anyhow::Ok(PersistenceTableSize {
+                    table_name: row.get(0),
+                    data_bytes: data_bytes.try_into()?,
+                    index_bytes: index_bytes.try_into()?,
+                    row_count: row_count.try_into()?,
+                    compressed_row_count: None,
+                })
+            })
+            .try_collect()
+            .await?;
+        let compressed_rows = client.query_raw(&compressed_count, no_params).await?;
+        futures::pin_mut!(compressed_rows);
+        // The fraction is NULL if the sample didn't hit any pages.
+        let compressed_fraction: Option<f64> =
+            compressed_rows.try_next().await?.and_then(|row| row.get(0));
+        for table in &mut stats {
+            if table.table_name == "documents" {
+                table.compressed_row_count = compressed_fraction
+                    .map(|fraction| (fraction * table.row_count as f64).round() as u64);
+            }
+        }
+        Ok(stats)
+    }
 }
 
 /// A `Lease` is unique for an instance across all of the processes in the
@@ -1186,27 +1433,29 @@ impl Lease {
 }
 
 fn document_params(
+    compressor: &DocumentCompressor,
     ts: Timestamp,
     id: InternalDocumentId,
     maybe_document: &Option<ResolvedDocument>,
     prev_ts: Option<Timestamp>,
-) -> [Param; NUM_DOCUMENT_PARAMS] {
+) -> anyhow::Result<[Param; NUM_DOCUMENT_PARAMS]> {
     let (json_value, deleted) = match maybe_document {
         Some(doc) => (JsonValue::from(doc.value().clone().into_value()), false),
         None => (JsonValue::Null, true),
     };
+    let json_value = compressor.encode(serde_json::to_vec(&json_value)?)?;
 
-    [
+    Ok([
         internal_doc_id_param(id),
         Param::Ts(i64::from(ts)),
         Param::TableId(id.table()),
-        Param::JsonValue(json_value),
+        Param::Bytes(json_value),
         Param::Deleted(deleted),
         match prev_ts {
             Some(prev_ts) => Param::Ts(i64::from(prev_ts)),
             None => Param::None,
         },
-    ]
+    ])
 }
 
 fn internal_id_param(id: InternalId) -> Param {
@@ -1291,6 +1540,21 @@ impl ToSql for Param {
     }
 }
 
+/// Schema names are interpolated into SQL, so only allow unquoted identifiers.
+fn validate_schema_name(schema: &str) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        !schema.is_empty()
+            && schema.len() <= 63
+            && !schema.starts_with(|c: char| c.is_ascii_digit())
+            && schema
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_'),
+        "Invalid Postgres schema name {schema:?}: must be 1-63 lowercase letters, digits or \
+         underscores, not starting with a digit"
+    );
+    Ok(())
+}
+
 // This runs (currently) every time a PostgresPersistence is created, so it
 // needs to not only be idempotent but not to affect any already-resident data.
 // IF NOT EXISTS and ON CONFLICT are helpful.
@@ -1561,11 +1825,37 @@ const WRITE_PERSISTENCE_GLOBAL: &str = r#"INSERT INTO persistence_globals
 
 const GET_PERSISTENCE_GLOBAL: &str = "SELECT json_value FROM persistence_globals WHERE key = $1";
 
+// Sizes of the tables in the schema on the connection's search path. reltuples
+// is an estimate, and -1 for tables that were never vacuumed or analyzed.
+const TABLE_SIZE_QUERY: &str = r#"
+SELECT c.relname::TEXT, pg_relation_size(c.oid), pg_indexes_size(c.oid),
+    GREATEST(c.reltuples, 0)::BIGINT
+FROM pg_class c
+JOIN pg_namespace n ON n.oid = c.relnamespace
+WHERE n.nspname = current_schema()
+    AND c.relkind = 'r'
+    AND c.relname IN ('documents', 'indexes', 'leases', 'read_only', 'persistence_globals')
+"#;
+
+// Fraction of compressed documents in a ~1% page sample, scaled by the
+// estimated row count above so we never scan the whole table. Compressed
+// payloads start with 0xC0, see `persistence_compression`.
+const COMPRESSED_DOCUMENT_FRACTION_QUERY: &str = r#"
+SELECT (COUNT(*) FILTER (WHERE substring(json_value FROM 1 FOR 1) = '\xc0'::bytea))::FLOAT8
+    / NULLIF(COUNT(*), 0)
+FROM documents TABLESAMPLE SYSTEM (1)
+"#;
+
 const CHUNK_SIZE: usize = 8;
 const NUM_DOCUMENT_PARAMS: usize = 6;
 const NUM_INDEX_PARAMS: usize = 8;
 const MAX_INSERT_SIZE: usize = 16384;
 static PIPELINE_QUERIES: LazyLock<usize> = LazyLock::new(|| env_config("PIPELINE_QUERIES", 16));
+/// How often to re-read `max_repeatable_ts` from a replica that is behind the
+/// snapshots being read.
+static POSTGRES_REPLICA_LAG_CHECK_INTERVAL: LazyLock<Duration> = LazyLock::new(|| {
+    Duration::from_millis(env_config("POSTGRES_REPLICA_LAG_CHECK_INTERVAL_MS", 500))
+});
 
 // Gross: after initialization, the first thing database does is insert metadata
 // documents.
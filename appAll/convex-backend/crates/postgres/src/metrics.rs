@@ -1,6 +1,9 @@
 use std::ops::Deref;
 
-use common::pool_stats::ConnectionPoolStats;
+use common::{
+    pool_stats::ConnectionPoolStats,
+    types::Timestamp,
+};
 use metrics::{
     log_counter,
     log_counter_with_labels,
@@ -395,3 +398,31 @@ register_convex_counter!(
 pub fn log_transaction(labels: Vec<StaticMetricLabel>) {
     log_counter_with_labels(&POSTGRES_TRANSACTION_TOTAL, 1, labels)
 }
+
+register_convex_counter!(
+    POSTGRES_REPLICA_READS_TOTAL,
+    "Number of snapshot reads that could be routed to the read replica, by where they went",
+    &["name", "target"]
+);
+pub fn log_replica_read(name: &'static str, on_replica: bool) {
+    let target = if on_replica { "replica" } else { "primary" };
+    log_counter_with_labels(
+        &POSTGRES_REPLICA_READS_TOTAL,
+        1,
+        vec![
+            StaticMetricLabel::new("name", name),
+            StaticMetricLabel::new("target", target),
+        ],
+    );
+}
+
+register_convex_histogram!(
+    POSTGRES_REPLICA_LAG_SECONDS,
+    "How far the read replica's max_repeatable_ts is behind snapshots being read"
+);
+pub fn log_replica_lag(read_ts: Timestamp, replica_ts: Timestamp) {
+    log_distribution(
+        &POSTGRES_REPLICA_LAG_SECONDS,
+        read_ts.secs_since_f64(replica_ts).max(0.0),
+    );
+}
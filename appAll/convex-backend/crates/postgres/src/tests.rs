@@ -15,8 +15,12 @@ use common::{
     persistence::{
         ConflictStrategy,
         DocumentLogEntry,
+        NoopRetentionValidator,
         Persistence,
+        PersistenceGlobalKey,
+        TimestampRange,
     },
+    query::Order,
     run_persistence_test_suite,
     testing::{
         self,
@@ -49,6 +53,8 @@ run_persistence_test_suite!(
         PostgresOptions {
             allow_read_only: false,
             version: PersistenceVersion::V5,
+            schema: None,
+            replica_url: None,
         }
     )
     .await?,
@@ -57,6 +63,8 @@ run_persistence_test_suite!(
         PostgresOptions {
             allow_read_only: true,
             version: PersistenceVersion::V5,
+            schema: None,
+            replica_url: None,
         }
     )
     .await?
@@ -67,6 +75,8 @@ async fn test_loading_locally() -> anyhow::Result<()> {
     let options = PostgresOptions {
         allow_read_only: false,
         version: PersistenceVersion::V5,
+        schema: None,
+        replica_url: None,
     };
     let persistence =
         PostgresPersistence::new(&crate::itest::new_db_opts().await?, options).await?; // need coverage on false too.
@@ -104,6 +114,8 @@ async fn test_writing_locally() -> anyhow::Result<()> {
     let options = PostgresOptions {
         allow_read_only: false,
         version: PersistenceVersion::V5,
+        schema: None,
+        replica_url: None,
     };
     let persistence =
         PostgresPersistence::new(&crate::itest::new_db_opts().await?, options).await?;
@@ -156,6 +168,8 @@ async fn test_lease_preempt() -> anyhow::Result<()> {
     let options = PostgresOptions {
         allow_read_only: false,
         version: PersistenceVersion::default(),
+        schema: None,
+        replica_url: None,
     };
     let p1 = Arc::new(PostgresPersistence::new(&url, options).await?);
 
@@ -185,6 +199,8 @@ async fn test_lease_preempt() -> anyhow::Result<()> {
     let options = PostgresOptions {
         allow_read_only: false,
         version: PersistenceVersion::V5,
+        schema: None,
+        replica_url: None,
     };
     let p2 = PostgresPersistence::new(&url, options).await?;
 
@@ -227,3 +243,122 @@ async fn test_lease_preempt() -> anyhow::Result<()> {
     assert!(result.is_err());
     Ok(())
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_schemas_are_isolated() -> anyhow::Result<()> {
+    let url = crate::itest::new_db_opts().await?;
+    let options = |schema: &str| PostgresOptions {
+        allow_read_only: false,
+        version: PersistenceVersion::V5,
+        schema: Some(schema.to_owned()),
+        replica_url: None,
+    };
+    let p1 = Arc::new(PostgresPersistence::new(&url, options("deployment_a")).await?);
+    let p2 = Arc::new(PostgresPersistence::new(&url, options("deployment_b")).await?);
+
+    let mut id_generator = TestIdGenerator::new();
+    let table: TableName = str::parse("table")?;
+    let doc_id = id_generator.user_generate(&table);
+    id_generator.write_tables(p1.clone()).await?;
+    let doc = ResolvedDocument::new(doc_id, CreationTime::ONE, ConvexObject::empty())?;
+
+    // Each deployment holds the lease for its own schema, so both can write.
+    for p in [&p1, &p2] {
+        p.write(
+            vec![DocumentLogEntry {
+                ts: Timestamp::must(1),
+                id: doc.id_with_table_id(),
+                value: Some(doc.clone()),
+                prev_ts: None,
+            }],
+            BTreeSet::new(),
+            ConflictStrategy::Error,
+        )
+        .await?;
+    }
+
+    // The table metadata written through `p1` isn't visible to `p2`.
+    let p1_documents: Vec<_> = p1.reader().load_all_documents().try_collect().await?;
+    let p2_documents: Vec<_> = p2.reader().load_all_documents().try_collect().await?;
+    assert!(p1_documents.len() > p2_documents.len());
+    assert_eq!(p2_documents.len(), 1);
+
+    assert!(
+        PostgresPersistence::new(&url, options("Robert'); DROP TABLE documents;--"))
+            .await
+            .is_err()
+    );
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_reads_from_caught_up_replica() -> anyhow::Result<()> {
+    // Stand in for a streaming replica with a separate database, so we can tell
+    // which one served each read.
+    let primary_url = crate::itest::new_db_opts().await?;
+    let replica_url = crate::itest::new_db_opts().await?;
+    let options = |replica_url: Option<String>| PostgresOptions {
+        allow_read_only: false,
+        version: PersistenceVersion::V5,
+        schema: None,
+        replica_url,
+    };
+    let primary =
+        PostgresPersistence::new(&primary_url, options(Some(replica_url.clone()))).await?;
+    let replica = PostgresPersistence::new(&replica_url, options(None)).await?;
+
+    let mut id_generator = TestIdGenerator::new();
+    let table: TableName = str::parse("table")?;
+    let doc_id = id_generator.user_generate(&table);
+    let primary_doc =
+        ResolvedDocument::new(doc_id, CreationTime::ONE, obj!("source" => "primary")?)?;
+    let replica_doc =
+        ResolvedDocument::new(doc_id, CreationTime::ONE, obj!("source" => "replica")?)?;
+    let entry = |ts: i32, doc: &ResolvedDocument| DocumentLogEntry {
+        ts: Timestamp::must(ts),
+        id: doc.id_with_table_id(),
+        value: Some(doc.clone()),
+        prev_ts: None,
+    };
+    primary
+        .write(
+            vec![entry(1, &primary_doc), entry(2, &primary_doc)],
+            BTreeSet::new(),
+            ConflictStrategy::Error,
+        )
+        .await?;
+    // The replica has only replayed up to ts 1.
+    replica
+        .write(
+            vec![entry(1, &replica_doc)],
+            BTreeSet::new(),
+            ConflictStrategy::Error,
+        )
+        .await?;
+    replica
+        .write_persistence_global(
+            PersistenceGlobalKey::MaxRepeatableTimestamp,
+            Timestamp::must(1).into(),
+        )
+        .await?;
+
+    let reader = primary.reader();
+    let documents_at = |ts: i32| {
+        reader
+            .load_documents(
+                TimestampRange::snapshot(Timestamp::must(ts)),
+                Order::Asc,
+                10,
+                Arc::new(NoopRetentionValidator),
+            )
+            .map_ok(|entry| entry.value)
+            .try_collect::<Vec<_>>()
+    };
+    assert_eq!(documents_at(1).await?, vec![Some(replica_doc)]);
+    // The replica hasn't caught up to ts 2, so this falls back to the primary.
+    assert_eq!(
+        documents_at(2).await?,
+        vec![Some(primary_doc.clone()), Some(primary_doc)]
+    );
+    Ok(())
+}
@@ -0,0 +1,842 @@
+#![feature(coroutines)]
+#![feature(proc_macro_hygiene)]
+#![feature(stmt_expr_attributes)]
+#![feature(let_chains)]
+
+use std::{
+    cmp,
+    collections::{
+        BTreeMap,
+        BTreeSet,
+    },
+    path::Path,
+    sync::Arc,
+};
+
+use anyhow::Context as _;
+use async_trait::async_trait;
+use common::{
+    document::{
+        InternalId,
+        ResolvedDocument,
+    },
+    index::{
+        IndexEntry,
+        IndexKeyBytes,
+    },
+    interval::{
+        End,
+        Interval,
+        StartIncluded,
+    },
+    persistence::{
+        ConflictStrategy,
+        DocumentLogEntry,
+        DocumentPrevTsQuery,
+        DocumentStream,
+        IndexStream,
+        LatestDocument,
+        Persistence,
+        PersistenceGlobalKey,
+        PersistenceReader,
+        PersistenceTableSize,
+        RetentionValidator,
+        TimestampRange,
+    },
+    persistence_compression::DocumentCompressor,
+    query::Order,
+    types::{
+        DatabaseIndexUpdate,
+        DatabaseIndexValue,
+        IndexId,
+        PersistenceVersion,
+        Timestamp,
+    },
+    value::{
+        ConvexValue,
+        InternalDocumentId,
+        TabletId,
+    },
+};
+use futures::StreamExt;
+use futures_async_stream::try_stream;
+use parking_lot::Mutex;
+use rocksdb::{
+    ColumnFamily,
+    Options,
+    ReadOptions,
+    WriteBatch,
+    DB,
+};
+use serde::Deserialize as _;
+use serde_json::Value as JsonValue;
+
+const DOCUMENTS: &str = "documents";
+const DOCUMENTS_BY_ID: &str = "documents_by_id";
+const INDEXES: &str = "indexes";
+const PERSISTENCE_GLOBALS: &str = "persistence_globals";
+const COLUMN_FAMILIES: [&str; 4] = [DOCUMENTS, DOCUMENTS_BY_ID, INDEXES, PERSISTENCE_GLOBALS];
+
+/// Stored in the default column family while the database is read-only.
+const READ_ONLY_KEY: &[u8] = b"read_only";
+
+/// Stored in place of `prev_ts` for a document's first revision. Timestamps
+/// never get this large.
+const NO_PREV_TS: u64 = u64::MAX;
+
+/// Index keys are escaped so that they sort correctly with a timestamp
+/// appended: zero bytes become `0x00 0xFF` and the key ends with `0x00 0x00`.
+const ESCAPED_ZERO: u8 = 0xFF;
+const KEY_TERMINATOR: [u8; 2] = [0x00, 0x00];
+
+/// `index_scan` reads at least this many index rows at a time, even if the
+/// caller only expects a couple of results, since rows hidden by newer
+/// revisions or deletes are skipped in memory.
+const MIN_INDEX_SCAN_PAGE_SIZE: usize = 16;
+
+const LIVE_DATA_SIZE_PROPERTY: &str = "rocksdb.estimate-live-data-size";
+const NUM_KEYS_PROPERTY: &str = "rocksdb.estimate-num-keys";
+
+// Each table is a column family, keyed so that RocksDB's byte order is the
+// order we read it in:
+//
+// - documents: (ts, table_id, id) -> (prev_ts, json_value)
+// - documents_by_id: (table_id, id, ts) -> (), to find previous revisions
+// - indexes: (index_id, key, ts) -> (table_id, document_id), empty if deleted
+// - persistence_globals: key -> json_value
+//
+// Unlike SQLite, reads don't go through a shared connection and can run
+// concurrently with each other and with writes. Writes still take a lock so
+// that `ConflictStrategy::Error` can check for existing rows first.
+#[derive(Clone)]
+pub struct RocksDbPersistence {
+    db: Arc<DB>,
+    newly_created: bool,
+    write_lock: Arc<Mutex<()>>,
+    compressor: Arc<DocumentCompressor>,
+}
+
+impl RocksDbPersistence {
+    pub fn new(path: &str, allow_read_only: bool) -> anyhow::Result<Self> {
+        let newly_created = !Path::new(path).exists();
+        let mut options = Options::default();
+        options.create_if_missing(true);
+        options.create_missing_column_families(true);
+        let db = DB::open_cf(&options, path, COLUMN_FAMILIES)
+            .with_context(|| format!("Failed to open RocksDB at {path}"))?;
+        if !allow_read_only {
+            anyhow::ensure!(db.get(READ_ONLY_KEY)?.is_none(), "Persistence is read-only");
+        }
+        let persistence = Self {
+            db: Arc::new(db),
+            newly_created,
+            write_lock: Arc::new(Mutex::new(())),
+            compressor: Arc::new(DocumentCompressor::new()),
+        };
+        // RocksDB locks the directory, so this process is the only writer and the
+        // dictionaries can only change through `DocumentCompressor::initialize`
+        // on this compressor.
+        persistence
+            .compressor
+            .set_dictionaries(persistence._get_persistence_global(
+                PersistenceGlobalKey::DocumentCompressionDictionaries,
+            )?)?;
+        Ok(persistence)
+    }
+
+    fn cf(&self, name: &str) -> &ColumnFamily {
+        self.db
+            .cf_handle(name)
+            .expect("column families are created on open")
+    }
+
+    /// Reads up to `limit` rows of `cf` in `[lower, upper)`, starting after
+    /// `cursor` in the given order.
+    fn scan(
+        &self,
+        cf: &str,
+        lower: &[u8],
+        upper: Option<&[u8]>,
+        cursor: Option<&[u8]>,
+        order: Order,
+        limit: usize,
+    ) -> anyhow::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let mut read_options = ReadOptions::default();
+        read_options.set_iterate_lower_bound(lower);
+        if let Some(upper) = upper {
+            read_options.set_iterate_upper_bound(upper);
+        }
+        let mut iter = self.db.raw_iterator_cf_opt(self.cf(cf), read_options);
+        match (order, cursor) {
+            (Order::Asc, None) => iter.seek_to_first(),
+            (Order::Asc, Some(cursor)) => {
+                iter.seek(cursor);
+                if iter.key() == Some(cursor) {
+                    iter.next();
+                }
+            },
+            (Order::Desc, None) => iter.seek_to_last(),
+            (Order::Desc, Some(cursor)) => {
+                iter.seek_for_prev(cursor);
+                if iter.key() == Some(cursor) {
+                    iter.prev();
+                }
+            },
+        }
+        let mut rows = vec![];
+        while rows.len() < limit
+            && let (Some(key), Some(value)) = (iter.key(), iter.value())
+        {
+            rows.push((key.to_vec(), value.to_vec()));
+            match order {
+                Order::Asc => iter.next(),
+                Order::Desc => iter.prev(),
+            }
+        }
+        iter.status()?;
+        Ok(rows)
+    }
+
+    fn get_revision(
+        &self,
+        id: InternalDocumentId,
+        ts: Timestamp,
+    ) -> anyhow::Result<Option<DocumentLogEntry>> {
+        let Some(value) = self
+            .db
+            .get_pinned_cf(self.cf(DOCUMENTS), document_key(ts, id))?
+        else {
+            return Ok(None);
+        };
+        let (document, prev_ts) = decode_revision(&self.compressor, id.table(), &value)?;
+        Ok(Some(DocumentLogEntry {
+            ts,
+            id,
+            value: document,
+            prev_ts,
+        }))
+    }
+
+    #[allow(clippy::needless_lifetimes)]
+    #[try_stream(ok = DocumentLogEntry, error = anyhow::Error)]
+    async fn load_documents_inner(
+        &self,
+        range: TimestampRange,
+        order: Order,
+        page_size: usize,
+        retention_validator: Arc<dyn RetentionValidator>,
+    ) {
+        let lower = u64::from(range.min_timestamp_inclusive()).to_be_bytes();
+        let upper = u64::from(range.max_timestamp_exclusive()).to_be_bytes();
+        let mut cursor = None;
+        loop {
+            let page = self.scan(
+                DOCUMENTS,
+                &lower,
+                Some(&upper[..]),
+                cursor.as_deref(),
+                order,
+                page_size,
+            )?;
+            retention_validator
+                .validate_document_snapshot(range.min_timestamp_inclusive())
+                .await?;
+            for (key, value) in &page {
+                let (ts, id) = decode_document_key(key)?;
+                let (document, prev_ts) = decode_revision(&self.compressor, id.table(), value)?;
+                yield DocumentLogEntry {
+                    ts,
+                    id,
+                    value: document,
+                    prev_ts,
+                };
+            }
+            if page.len() < page_size {
+                break;
+            }
+            cursor = page.into_iter().last().map(|(key, _)| key);
+        }
+    }
+
+    #[allow(clippy::needless_lifetimes)]
+    #[try_stream(ok = (IndexKeyBytes, LatestDocument), error = anyhow::Error)]
+    async fn index_scan_inner(
+        &self,
+        index_id: IndexId,
+        tablet_id: TabletId,
+        read_timestamp: Timestamp,
+        interval: Interval,
+        order: Order,
+        size_hint: usize,
+        retention_validator: Arc<dyn RetentionValidator>,
+    ) {
+        let StartIncluded(ref start) = interval.start;
+        let lower = index_key_prefix(index_id, &start[..]);
+        let upper = match interval.end {
+            End::Excluded(ref end) => Some(index_key_prefix(index_id, &end[..])),
+            End::Unbounded => prefix_end(&index_id[..]),
+        };
+        let page_size = cmp::max(size_hint, MIN_INDEX_SCAN_PAGE_SIZE);
+        let mut cursor = None;
+        // The key we're in the middle of and its latest revision at
+        // `read_timestamp` so far. Its revisions may span several pages.
+        let mut current_key = None;
+        let mut latest: Option<(Vec<u8>, Timestamp, Vec<u8>)> = None;
+        loop {
+            let page = self.scan(
+                INDEXES,
+                &lower,
+                upper.as_deref(),
+                cursor.as_deref(),
+                order,
+                page_size,
+            )?;
+            let done = page.len() < page_size;
+            cursor = page.last().map(|(row_key, _)| row_key.clone());
+
+            let mut rows = vec![];
+            for (row_key, value) in page {
+                let (_, key, ts) = decode_index_row_key(&row_key)?;
+                if current_key.as_ref() != Some(&key) {
+                    rows.extend(latest.take());
+                    current_key = Some(key.clone());
+                }
+                if ts <= read_timestamp
+                    && latest
+                        .as_ref()
+                        .is_none_or(|(_, latest_ts, _)| *latest_ts < ts)
+                {
+                    latest = Some((key, ts, value));
+                }
+            }
+            if done {
+                rows.extend(latest.take());
+            }
+
+            let mut results = vec![];
+            for (key, ts, value) in rows {
+                // Deleted index entries have no value.
+                if value.is_empty() {
+                    continue;
+                }
+                let (table, document_id) = decode_index_value(&value)?;
+                let entry = self
+                    .get_revision(InternalDocumentId::new(table, document_id), ts)?
+                    .ok_or_else(|| {
+                        anyhow::anyhow!("Dangling index reference for {:?} {:?}", key, ts)
+                    })?;
+                let document = entry.value.ok_or_else(|| {
+                    anyhow::anyhow!("Index reference to deleted document {:?} {:?}", key, ts)
+                })?;
+                anyhow::ensure!(
+                    document.id().tablet_id == tablet_id,
+                    "Index entry {key:?} points to a document in another table"
+                );
+                results.push((
+                    IndexKeyBytes(key),
+                    LatestDocument {
+                        ts,
+                        value: document,
+                        prev_ts: entry.prev_ts,
+                    },
+                ));
+            }
+            retention_validator
+                .validate_snapshot(read_timestamp)
+                .await?;
+            for result in results {
+                yield result;
+            }
+            if done {
+                break;
+            }
+        }
+    }
+
+    fn _get_persistence_global(
+        &self,
+        key: PersistenceGlobalKey,
+    ) -> anyhow::Result<Option<JsonValue>> {
+        let Some(value) = self
+            .db
+            .get_pinned_cf(self.cf(PERSISTENCE_GLOBALS), String::from(key))?
+        else {
+            return Ok(None);
+        };
+        let mut json_deserializer = serde_json::Deserializer::from_slice(&value);
+        // XXX: this is bad, but shapes can get much more nested than convex values
+        json_deserializer.disable_recursion_limit();
+        let json_value = JsonValue::deserialize(&mut json_deserializer)
+            .with_context(|| format!("Invalid JSON at persistence key {key:?}"))?;
+        json_deserializer.end()?;
+        Ok(Some(json_value))
+    }
+}
+
+#[async_trait]
+impl Persistence for RocksDbPersistence {
+    fn is_fresh(&self) -> bool {
+        self.newly_created
+    }
+
+    fn reader(&self) -> Arc<dyn PersistenceReader> {
+        Arc::new(self.clone())
+    }
+
+    async fn write(
+        &self,
+        documents: Vec<DocumentLogEntry>,
+        indexes: BTreeSet<(Timestamp, DatabaseIndexUpdate)>,
+        conflict_strategy: ConflictStrategy,
+    ) -> anyhow::Result<()> {
+        let _write_lock = self.write_lock.lock();
+        let mut batch = WriteBatch::default();
+        for update in documents {
+            let key = document_key(update.ts, update.id);
+            if conflict_strategy == ConflictStrategy::Error
+                && self.db.get_pinned_cf(self.cf(DOCUMENTS), &key)?.is_some()
+            {
+                anyhow::bail!(
+                    "Unique constraint violated: documents already has {:?} at {}",
+                    update.id,
+                    update.ts
+                );
+            }
+            if let Some(ref document) = update.value {
+                assert_eq!(update.id, document.id_with_table_id());
+            }
+            let value = encode_revision(&self.compressor, update.value.as_ref(), update.prev_ts)?;
+            batch.put_cf(self.cf(DOCUMENTS), key, value);
+            batch.put_cf(
+                self.cf(DOCUMENTS_BY_ID),
+                document_by_id_key(update.id, Some(update.ts)),
+                b"",
+            );
+        }
+        for (ts, update) in indexes {
+            let key = index_row_key(update.index_id, &update.key.into_bytes().0, ts);
+            if conflict_strategy == ConflictStrategy::Error
+                && self.db.get_pinned_cf(self.cf(INDEXES), &key)?.is_some()
+            {
+                anyhow::bail!(
+                    "Unique constraint violated: indexes already has {:?} at {}",
+                    update.index_id,
+                    ts
+                );
+            }
+            let value = match update.value {
+                DatabaseIndexValue::Deleted => vec![],
+                DatabaseIndexValue::NonClustered(doc_id) => {
+                    [&doc_id.tablet_id.0[..], &doc_id.internal_id()[..]].concat()
+                },
+            };
+            batch.put_cf(self.cf(INDEXES), key, value);
+        }
+        self.db.write(batch)?;
+        Ok(())
+    }
+
+    async fn set_read_only(&self, read_only: bool) -> anyhow::Result<()> {
+        if read_only {
+            self.db.put(READ_ONLY_KEY, b"")?;
+        } else {
+            self.db.delete(READ_ONLY_KEY)?;
+        }
+        Ok(())
+    }
+
+    async fn write_persistence_global(
+        &self,
+        key: PersistenceGlobalKey,
+        value: JsonValue,
+    ) -> anyhow::Result<()> {
+        self.db.put_cf(
+            self.cf(PERSISTENCE_GLOBALS),
+            String::from(key),
+            serde_json::to_vec(&value)?,
+        )?;
+        Ok(())
+    }
+
+    async fn load_index_chunk(
+        &self,
+        cursor: Option<IndexEntry>,
+        chunk_size: usize,
+    ) -> anyhow::Result<Vec<IndexEntry>> {
+        let cursor = cursor.map(|entry| index_row_key(entry.index_id, &entry.key_prefix, entry.ts));
+        self.scan(
+            INDEXES,
+            &[],
+            None,
+            cursor.as_deref(),
+            Order::Asc,
+            chunk_size,
+        )?
+        .into_iter()
+        .map(|(row_key, value)| {
+            let (index_id, key, ts) = decode_index_row_key(&row_key)?;
+            Ok(IndexEntry {
+                index_id,
+                key_prefix: key.clone(),
+                key_suffix: None,
+                key_sha256: key,
+                ts,
+                deleted: value.is_empty(),
+            })
+        })
+        .collect()
+    }
+
+    async fn delete_index_entries(&self, expired_rows: Vec<IndexEntry>) -> anyhow::Result<usize> {
+        let _write_lock = self.write_lock.lock();
+        // Like the SQL implementations, delete every revision of the key up to
+        // and including `ts`.
+        let mut to_delete = BTreeSet::new();
+        for IndexEntry {
+            index_id,
+            key_prefix,
+            ts,
+            ..
+        } in expired_rows
+        {
+            let lower = index_key_prefix(index_id, &key_prefix);
+            let upper = [&index_row_key(index_id, &key_prefix, ts)[..], &[0u8][..]].concat();
+            for (row_key, _) in
+                self.scan(INDEXES, &lower, Some(&upper), None, Order::Asc, usize::MAX)?
+            {
+                to_delete.insert(row_key);
+            }
+        }
+        let mut batch = WriteBatch::default();
+        for row_key in &to_delete {
+            batch.delete_cf(self.cf(INDEXES), row_key);
+        }
+        self.db.write(batch)?;
+        Ok(to_delete.len())
+    }
+
+    async fn delete(
+        &self,
+        documents: Vec<(Timestamp, InternalDocumentId)>,
+    ) -> anyhow::Result<usize> {
+        let _write_lock = self.write_lock.lock();
+        let mut to_delete = BTreeSet::new();
+        for (ts, id) in documents {
+            let lower = document_by_id_key(id, None);
+            let upper = [&document_by_id_key(id, Some(ts))[..], &[0u8][..]].concat();
+            for (key, _) in self.scan(
+                DOCUMENTS_BY_ID,
+                &lower,
+                Some(&upper),
+                None,
+                Order::Asc,
+                usize::MAX,
+            )? {
+                to_delete.insert((decode_timestamp_suffix(&key)?, id));
+            }
+        }
+        let mut batch = WriteBatch::default();
+        for &(ts, id) in &to_delete {
+            batch.delete_cf(self.cf(DOCUMENTS), document_key(ts, id));
+            batch.delete_cf(self.cf(DOCUMENTS_BY_ID), document_by_id_key(id, Some(ts)));
+        }
+        self.db.write(batch)?;
+        Ok(to_delete.len())
+    }
+
+    fn document_compressor(&self) -> Option<Arc<DocumentCompressor>> {
+        Some(self.compressor.clone())
+    }
+}
+
+#[async_trait]
+impl PersistenceReader for RocksDbPersistence {
+    fn load_documents(
+        &self,
+        range: TimestampRange,
+        order: Order,
+        page_size: u32,
+        retention_validator: Arc<dyn RetentionValidator>,
+    ) -> DocumentStream<'_> {
+        self.load_documents_inner(
+            range,
+            order,
+            cmp::max(page_size as usize, 1),
+            retention_validator,
+        )
+        .boxed()
+    }
+
+    async fn previous_revisions(
+        &self,
+        ids: BTreeSet<(InternalDocumentId, Timestamp)>,
+        retention_validator: Arc<dyn RetentionValidator>,
+    ) -> anyhow::Result<BTreeMap<(InternalDocumentId, Timestamp), DocumentLogEntry>> {
+        let mut out = BTreeMap::new();
+        let mut min_ts = Timestamp::MAX;
+        for (id, ts) in ids {
+            min_ts = cmp::min(ts, min_ts);
+            let lower = document_by_id_key(id, None);
+            let upper = document_by_id_key(id, Some(ts));
+            let Some((key, _)) = self
+                .scan(DOCUMENTS_BY_ID, &lower, Some(&upper), None, Order::Desc, 1)?
+                .pop()
+            else {
+                continue;
+            };
+            let prev_ts = decode_timestamp_suffix(&key)?;
+            let entry = self
+                .get_revision(id, prev_ts)?
+                .with_context(|| format!("Missing revision of {id:?} at {prev_ts}"))?;
+            out.insert((id, ts), entry);
+        }
+        retention_validator
+            .validate_document_snapshot(min_ts)
+            .await?;
+        Ok(out)
+    }
+
+    async fn previous_revisions_of_documents(
+        &self,
+        ids: BTreeSet<DocumentPrevTsQuery>,
+        retention_validator: Arc<dyn RetentionValidator>,
+    ) -> anyhow::Result<BTreeMap<DocumentPrevTsQuery, DocumentLogEntry>> {
+        let min_ts = ids.iter().map(|DocumentPrevTsQuery { ts, .. }| *ts).min();
+
+        let mut out = BTreeMap::new();
+        for query in ids {
+            if let Some(entry) = self.get_revision(query.id, query.prev_ts)? {
+                out.insert(query, entry);
+            }
+        }
+        if let Some(min_ts) = min_ts {
+            retention_validator
+                .validate_document_snapshot(min_ts)
+                .await?;
+        }
+        Ok(out)
+    }
+
+    fn index_scan(
+        &self,
+        index_id: IndexId,
+        tablet_id: TabletId,
+        read_timestamp: Timestamp,
+        interval: &Interval,
+        order: Order,
+        size_hint: usize,
+        retention_validator: Arc<dyn RetentionValidator>,
+    ) -> IndexStream<'_> {
+        self.index_scan_inner(
+            index_id,
+            tablet_id,
+            read_timestamp,
+            interval.clone(),
+            order,
+            size_hint,
+            retention_validator,
+        )
+        .boxed()
+    }
+
+    async fn get_persistence_global(
+        &self,
+        key: PersistenceGlobalKey,
+    ) -> anyhow::Result<Option<JsonValue>> {
+        self._get_persistence_global(key)
+    }
+
+    fn version(&self) -> PersistenceVersion {
+        PersistenceVersion::V5
+    }
+
+    async fn table_size_stats(&self) -> anyhow::Result<Vec<PersistenceTableSize>> {
+        // These are RocksDB's estimates, which are cheap to get but can be off
+        // until compaction catches up.
+        let property = |cf: &str, name: &str| -> anyhow::Result<u64> {
+            Ok(self
+                .db
+                .property_int_value_cf(self.cf(cf), name)?
+                .unwrap_or(0))
+        };
+        Ok(vec![
+            PersistenceTableSize {
+                table_name: DOCUMENTS.to_owned(),
+                data_bytes: property(DOCUMENTS, LIVE_DATA_SIZE_PROPERTY)?,
+                index_bytes: property(DOCUMENTS_BY_ID, LIVE_DATA_SIZE_PROPERTY)?,
+                row_count: property(DOCUMENTS, NUM_KEYS_PROPERTY)?,
+                compressed_row_count: None,
+            },
+            PersistenceTableSize {
+                table_name: INDEXES.to_owned(),
+                data_bytes: property(INDEXES, LIVE_DATA_SIZE_PROPERTY)?,
+                index_bytes: 0,
+                row_count: property(INDEXES, NUM_KEYS_PROPERTY)?,
+                compressed_row_count: None,
+            },
+        ])
+    }
+}
+
+fn document_key(ts: Timestamp, id: InternalDocumentId) -> Vec<u8> {
+    [
+        &u64::from(ts).to_be_bytes()[..],
+        &id.table().0[..],
+        &id.internal_id()[..],
+    ]
+    .concat()
+}
+
+fn decode_document_key(key: &[u8]) -> anyhow::Result<(Timestamp, InternalDocumentId)> {
+    anyhow::ensure!(key.len() == 40, "Invalid document key {key:?}");
+    let ts = Timestamp::try_from(u64::from_be_bytes(key[..8].try_into()?))?;
+    let table = TabletId(InternalId(key[8..24].try_into()?));
+    let id = InternalId(key[24..].try_into()?);
+    Ok((ts, InternalDocumentId::new(table, id)))
+}
+
+/// Key of a revision in `documents_by_id`, or the prefix of all of the
+/// document's revisions if `ts` is `None`.
+fn document_by_id_key(id: InternalDocumentId, ts: Option<Timestamp>) -> Vec<u8> {
+    let mut key = [&id.table().0[..], &id.internal_id()[..]].concat();
+    if let Some(ts) = ts {
+        key.extend_from_slice(&u64::from(ts).to_be_bytes());
+    }
+    key
+}
+
+fn decode_timestamp_suffix(key: &[u8]) -> anyhow::Result<Timestamp> {
+    anyhow::ensure!(key.len() >= 8, "Invalid key {key:?}");
+    let ts = u64::from_be_bytes(key[key.len() - 8..].try_into()?);
+    Ok(Timestamp::try_from(ts)?)
+}
+
+fn encode_revision(
+    compressor: &DocumentCompressor,
+    document: Option<&ResolvedDocument>,
+    prev_ts: Option<Timestamp>,
+) -> anyhow::Result<Vec<u8>> {
+    let mut value = prev_ts.map_or(NO_PREV_TS, u64::from).to_be_bytes().to_vec();
+    // Deletes are stored without a payload.
+    if let Some(document) = document {
+        let json_value: JsonValue = document.value().0.clone().into();
+        value.extend(compressor.encode(serde_json::to_vec(&json_value)?)?);
+    }
+    Ok(value)
+}
+
+fn decode_revision(
+    compressor: &DocumentCompressor,
+    table: TabletId,
+    value: &[u8],
+) -> anyhow::Result<(Option<ResolvedDocument>, Option<Timestamp>)> {
+    anyhow::ensure!(value.len() >= 8, "Invalid document revision {value:?}");
+    let (prev_ts, json_value) = value.split_at(8);
+    let prev_ts = match u64::from_be_bytes(prev_ts.try_into()?) {
+        NO_PREV_TS => None,
+        prev_ts => Some(Timestamp::try_from(prev_ts)?),
+    };
+    if json_value.is_empty() {
+        return Ok((None, prev_ts));
+    }
+    let json_value = compressor.decode(json_value)?;
+    let json_value: JsonValue = serde_json::from_slice(&json_value)?;
+    let value: ConvexValue = json_value.try_into()?;
+    Ok((
+        Some(ResolvedDocument::from_database(table, value)?),
+        prev_ts,
+    ))
+}
+
+/// Prefix of all revisions of `key` in `index_id`. Because of the escaping, it
+/// is also where `key` starts in the index's sort order.
+fn index_key_prefix(index_id: IndexId, key: &[u8]) -> Vec<u8> {
+    let mut prefix = Vec::with_capacity(index_id.size() + key.len() + 10);
+    prefix.extend_from_slice(&index_id[..]);
+    for &byte in key {
+        prefix.push(byte);
+        if byte == 0 {
+            prefix.push(ESCAPED_ZERO);
+        }
+    }
+    prefix.extend_from_slice(&KEY_TERMINATOR);
+    prefix
+}
+
+fn index_row_key(index_id: IndexId, key: &[u8], ts: Timestamp) -> Vec<u8> {
+    let mut row_key = index_key_prefix(index_id, key);
+    row_key.extend_from_slice(&u64::from(ts).to_be_bytes());
+    row_key
+}
+
+fn decode_index_row_key(row_key: &[u8]) -> anyhow::Result<(IndexId, Vec<u8>, Timestamp)> {
+    anyhow::ensure!(row_key.len() >= 26, "Invalid index key {row_key:?}");
+    let index_id = InternalId(row_key[..16].try_into()?);
+    let escaped = &row_key[16..row_key.len() - 8];
+    let escaped = escaped
+        .strip_suffix(&KEY_TERMINATOR)
+        .with_context(|| format!("Unterminated index key {row_key:?}"))?;
+    let mut key = Vec::with_capacity(escaped.len());
+    let mut bytes = escaped.iter();
+    while let Some(&byte) = bytes.next() {
+        key.push(byte);
+        if byte == 0 {
+            anyhow::ensure!(
+                bytes.next() == Some(&ESCAPED_ZERO),
+                "Invalid escape in index key {row_key:?}"
+            );
+        }
+    }
+    Ok((index_id, key, decode_timestamp_suffix(row_key)?))
+}
+
+fn decode_index_value(value: &[u8]) -> anyhow::Result<(TabletId, InternalId)> {
+    anyhow::ensure!(value.len() == 32, "Invalid index value {value:?}");
+    Ok((
+        TabletId(InternalId(value[..16].try_into()?)),
+        InternalId(value[16..].try_into()?),
+    ))
+}
+
+/// The smallest key after every key starting with `prefix`, if there is one.
+fn prefix_end(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut end = prefix.to_vec();
+    while let Some(last) = end.pop() {
+        if last < u8::MAX {
+            end.push(last + 1);
+            return Some(end);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use common::{
+        document::InternalId,
+        types::Timestamp,
+    };
+
+    use super::{
+        decode_index_row_key,
+        index_row_key,
+    };
+
+    #[test]
+    fn test_index_keys_sort_like_raw_keys() -> anyhow::Result<()> {
+        let index_id = InternalId::MIN;
+        let keys: [&[u8]; 6] = [b"", b"\x00", b"\x00\x00", b"\x00\x01", b"a", b"a\xff"];
+        let row_keys: Vec<_> = keys
+            .iter()
+            .map(|key| index_row_key(index_id, key, Timestamp::MAX))
+            .collect();
+        assert!(row_keys.is_sorted());
+        for (key, row_key) in keys.iter().zip(&row_keys) {
+            assert_eq!(
+                decode_index_row_key(row_key)?,
+                (index_id, key.to_vec(), Timestamp::MAX)
+            );
+        }
+        Ok(())
+    }
+}
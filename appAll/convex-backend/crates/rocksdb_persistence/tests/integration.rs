@@ -0,0 +1,25 @@
+use common::{
+    run_persistence_test_suite,
+    testing::persistence_test_suite,
+};
+use rocksdb_persistence::RocksDbPersistence;
+use tempfile::TempDir;
+
+run_persistence_test_suite!(
+    db,
+    TempDir::new()?,
+    RocksDbPersistence::new(
+        db.path()
+            .join("convex_local_backend.rocksdb")
+            .to_str()
+            .unwrap(),
+        false
+    )?,
+    RocksDbPersistence::new(
+        db.path()
+            .join("convex_local_backend.rocksdb")
+            .to_str()
+            .unwrap(),
+        true
+    )?
+);
@@ -38,9 +38,11 @@ use common::{
         Persistence,
         PersistenceGlobalKey,
         PersistenceReader,
+        PersistenceTableSize,
         RetentionValidator,
         TimestampRange,
     },
+    persistence_compression::DocumentCompressor,
     query::Order,
     types::{
         DatabaseIndexUpdate,
@@ -63,7 +65,12 @@ use futures_async_stream::try_stream;
 use parking_lot::Mutex;
 use rusqlite::{
     params,
-    types::Null,
+    types::{
+        FromSqlError,
+        Null,
+        Value,
+        ValueRef,
+    },
     Connection,
     Row,
     ToSql,
@@ -75,6 +82,7 @@ use serde_json::Value as JsonValue;
 // we can't really make queries concurrent.
 pub struct SqlitePersistence {
     inner: Arc<Mutex<Inner>>,
+    compressor: Arc<DocumentCompressor>,
 }
 
 struct Inner {
@@ -95,12 +103,22 @@ impl SqlitePersistence {
             let mut stmt = connection.prepare(CHECK_IS_READ_ONLY)?;
             anyhow::ensure!(stmt.raw_query().next()?.is_none());
         }
-        Ok(Self {
+        let persistence = Self {
             inner: Arc::new(Mutex::new(Inner {
                 newly_created,
                 connection,
             })),
-        })
+            compressor: Arc::new(DocumentCompressor::new()),
+        };
+        // There's only one process writing to the file, so the dictionaries can
+        // only change through `DocumentCompressor::train_dictionary` on this
+        // compressor and don't have to be reloaded on reads.
+        persistence
+            .compressor
+            .set_dictionaries(persistence._get_persistence_global(
+                PersistenceGlobalKey::DocumentCompressionDictionaries,
+            )?)?;
+        Ok(persistence)
     }
 
     #[allow(clippy::needless_lifetimes)]
@@ -188,7 +206,7 @@ ORDER BY B.key {order}
             let ts = Timestamp::try_from(row.get::<_, u64>(1)?).expect("timestamp out of bounds");
             let document_id = row.get::<_, Vec<u8>>(2)?;
             let table: Option<Vec<u8>> = row.get(3)?;
-            let json_value: Option<String> = row.get(4)?;
+            let json_value = json_value_column(row, 4)?;
             let prev_ts: Option<Timestamp> = row
                 .get::<_, Option<u64>>(5)?
                 .map(|ts| Timestamp::try_from(ts).expect("prev_ts out of bounds"));
@@ -206,7 +224,8 @@ ORDER BY B.key {order}
             let json_value = json_value.ok_or_else(|| {
                 anyhow::anyhow!("Index reference to deleted document {:?} {:?}", key, ts)
             })?;
-            let json_value: serde_json::Value = serde_json::from_str(&json_value)?;
+            let json_value = self.compressor.decode(&json_value)?;
+            let json_value: serde_json::Value = serde_json::from_slice(&json_value)?;
             let value: ConvexValue = json_value.try_into()?;
             let document = ResolvedDocument::from_database(tablet_id, value)?;
             triples.push(Ok((
@@ -258,6 +277,7 @@ impl Persistence for SqlitePersistence {
     fn reader(&self) -> Arc<dyn PersistenceReader> {
         Arc::new(Self {
             inner: self.inner.clone(),
+            compressor: self.compressor.clone(),
         })
     }
 
@@ -278,7 +298,14 @@ impl Persistence for SqlitePersistence {
             let (json_value, deleted) = if let Some(document) = update.value {
                 assert_eq!(update.id, document.id_with_table_id());
                 let json_value: serde_json::Value = document.value().0.clone().into();
-                let json_value = serde_json::to_string(&json_value)?;
+                let json_value = self.compressor.encode(serde_json::to_vec(&json_value)?)?;
+                // Keep uncompressed documents as TEXT so the file stays readable
+                // with the sqlite3 shell.
+                let json_value = if DocumentCompressor::is_compressed(&json_value) {
+                    Value::Blob(json_value)
+                } else {
+                    Value::Text(String::from_utf8(json_value)?)
+                };
                 (Some(json_value), 0)
             } else {
                 (None, 1)
@@ -438,6 +465,10 @@ impl Persistence for SqlitePersistence {
         tx.commit()?;
         Ok(count_deleted)
     }
+
+    fn document_compressor(&self) -> Option<Arc<DocumentCompressor>> {
+        Some(self.compressor.clone())
+    }
 }
 
 #[async_trait]
@@ -456,7 +487,7 @@ impl PersistenceReader for SqlitePersistence {
 
             let mut entries = vec![];
             for row in stmt.query_map([], load_document_row)? {
-                let (document_id, ts, document, prev_ts) = row_to_document(row)?;
+                let (document_id, ts, document, prev_ts) = row_to_document(&self.compressor, row)?;
                 entries.push(Ok(DocumentLogEntry {
                     ts,
                     id: document_id,
@@ -492,7 +523,8 @@ impl PersistenceReader for SqlitePersistence {
                 let params = params![&id.table().0[..], &internal_id[..], &u64::from(ts)];
                 let mut row_iter = stmt.query_map(params, load_document_row)?;
                 if let Some(row) = row_iter.next() {
-                    let (document_id, prev_ts, document, prev_prev_ts) = row_to_document(row)?;
+                    let (document_id, prev_ts, document, prev_prev_ts) =
+                        row_to_document(&self.compressor, row)?;
                     out.insert(
                         (document_id, ts),
                         DocumentLogEntry {
@@ -528,7 +560,8 @@ impl PersistenceReader for SqlitePersistence {
                 let params = params![&id.table().0[..], &internal_id[..], &u64::from(prev_ts)];
                 let mut row_iter = stmt.query_map(params, load_document_row)?;
                 if let Some(row) = row_iter.next() {
-                    let (document_id, prev_ts, document, prev_prev_ts) = row_to_document(row)?;
+                    let (document_id, prev_ts, document, prev_prev_ts) =
+                        row_to_document(&self.compressor, row)?;
                     out.insert(
                         DocumentPrevTsQuery {
                             id: document_id,
@@ -582,6 +615,56 @@ impl PersistenceReader for SqlitePersistence {
     fn version(&self) -> PersistenceVersion {
         PersistenceVersion::V5
     }
+
+    async fn table_size_stats(&self) -> anyhow::Result<Vec<PersistenceTableSize>> {
+        let connection = &self.inner.lock().connection;
+        let (min_rowid, max_rowid): (Option<i64>, Option<i64>) =
+            connection.query_row(DOCUMENTS_ROWID_RANGE_QUERY, [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?;
+        let rowid_span = match (min_rowid, max_rowid) {
+            (Some(min_rowid), Some(max_rowid)) => max_rowid - min_rowid + 1,
+            _ => 0,
+        };
+        // Scanning the table would hold the connection for as long as it takes
+        // to read every document, so look up a bounded number of random rowids
+        // instead and scale the sample up to the whole rowid range. Deleted rows
+        // leave gaps in the range, which the sample misses at the same rate.
+        let (sampled, sizes) = if rowid_span <= DOCUMENTS_SIZE_SAMPLE_ROWS {
+            (
+                rowid_span,
+                connection.query_row(
+                    DOCUMENTS_SIZE_QUERY,
+                    params![min_rowid, max_rowid],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+                )?,
+            )
+        } else {
+            (
+                DOCUMENTS_SIZE_SAMPLE_ROWS,
+                connection.query_row(
+                    DOCUMENTS_SIZE_SAMPLE_QUERY,
+                    params![min_rowid, rowid_span, DOCUMENTS_SIZE_SAMPLE_ROWS],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+                )?,
+            )
+        };
+        let (row_count, data_bytes, compressed_row_count): (i64, i64, i64) = sizes;
+        let estimate = |sample: i64| -> u64 {
+            if sampled == 0 {
+                return 0;
+            }
+            (sample as f64 * rowid_span as f64 / sampled as f64).round() as u64
+        };
+        Ok(vec![PersistenceTableSize {
+            table_name: "documents".to_owned(),
+            data_bytes: estimate(data_bytes),
+            // SQLite doesn't report index sizes without the dbstat extension.
+            index_bytes: 0,
+            row_count: estimate(row_count),
+            compressed_row_count: Some(estimate(compressed_row_count)),
+        }])
+    }
 }
 
 const DOCUMENTS_INIT: &str = r#"
@@ -635,7 +718,8 @@ CREATE TABLE IF NOT EXISTS persistence_globals (
 "#;
 
 fn row_to_document(
-    row: rusqlite::Result<(Vec<u8>, u64, Vec<u8>, Option<String>, bool, Option<u64>)>,
+    compressor: &DocumentCompressor,
+    row: rusqlite::Result<(Vec<u8>, u64, Vec<u8>, Option<Vec<u8>>, bool, Option<u64>)>,
 ) -> anyhow::Result<(
     InternalDocumentId,
     Timestamp,
@@ -650,7 +734,8 @@ fn row_to_document(
     let document = if !deleted {
         let json_value = json_value
             .ok_or_else(|| anyhow::anyhow!("Unexpected NULL json_value at {} {}", id, prev_ts))?;
-        let json_value: serde_json::Value = serde_json::from_str(&json_value)?;
+        let json_value = compressor.decode(&json_value)?;
+        let json_value: serde_json::Value = serde_json::from_slice(&json_value)?;
         let value: ConvexValue = json_value.try_into()?;
         Some(ResolvedDocument::from_database(table, value)?)
     } else {
@@ -680,16 +765,30 @@ WHERE ts >= {} AND ts < {}
 
 fn load_document_row(
     row: &Row<'_>,
-) -> rusqlite::Result<(Vec<u8>, u64, Vec<u8>, Option<String>, bool, Option<u64>)> {
+) -> rusqlite::Result<(Vec<u8>, u64, Vec<u8>, Option<Vec<u8>>, bool, Option<u64>)> {
     let id = row.get::<_, Vec<u8>>(0)?;
     let ts = row.get::<_, u64>(1)?;
     let table: Vec<u8> = row.get(2)?;
-    let json_value: Option<String> = row.get(3)?;
+    let json_value = json_value_column(row, 3)?;
     let deleted = row.get::<_, u32>(4)? != 0;
     let prev_ts: Option<u64> = row.get(5)?;
     Ok((id, ts, table, json_value, deleted, prev_ts))
 }
 
+/// Compressed documents are stored as BLOBs and uncompressed ones as TEXT, so
+/// accept either.
+fn json_value_column(row: &Row<'_>, idx: usize) -> rusqlite::Result<Option<Vec<u8>>> {
+    match row.get_ref(idx)? {
+        ValueRef::Null => Ok(None),
+        ValueRef::Text(bytes) | ValueRef::Blob(bytes) => Ok(Some(bytes.to_vec())),
+        value => Err(rusqlite::Error::FromSqlConversionFailure(
+            idx,
+            value.data_type(),
+            Box::new(FromSqlError::InvalidType),
+        )),
+    }
+}
+
 const GET_PERSISTENCE_GLOBAL: &str = "SELECT json_value FROM persistence_globals WHERE key = ?";
 
 const INSERT_DOCUMENT: &str = "INSERT INTO documents (id, ts, table_id, json_value, deleted, \
@@ -700,6 +799,29 @@ const INSERT_INDEX: &str = "INSERT INTO indexes VALUES (?, ?, ?, ?, ?, ?)";
 const INSERT_OVERWRITE_INDEX: &str = "INSERT OR REPLACE INTO indexes VALUES (?, ?, ?, ?, ?, ?)";
 const WRITE_PERSISTENCE_GLOBAL: &str = "INSERT OR REPLACE INTO persistence_globals VALUES (?, ?)";
 
+const DOCUMENTS_ROWID_RANGE_QUERY: &str = "SELECT MIN(rowid), MAX(rowid) FROM documents";
+
+/// Number of rows `table_size_stats` looks at.
+const DOCUMENTS_SIZE_SAMPLE_ROWS: i64 = 1000;
+
+// Compressed payloads start with 0xC0, see `persistence_compression`.
+const DOCUMENTS_SIZE_QUERY: &str = "SELECT COUNT(*), COALESCE(SUM(LENGTH(CAST(json_value AS \
+                                    BLOB))), 0), COUNT(*) FILTER (WHERE substr(json_value, 1, 1) \
+                                    = X'C0') FROM documents WHERE rowid BETWEEN ?1 AND ?2";
+
+// Random rowids are drawn with replacement, so a row can be counted more than
+// once, like in any other sample.
+const DOCUMENTS_SIZE_SAMPLE_QUERY: &str = r#"
+WITH RECURSIVE sample(n, sampled_rowid) AS (
+    SELECT 1, ?1 + abs(random() % ?2)
+    UNION ALL
+    SELECT n + 1, ?1 + abs(random() % ?2) FROM sample WHERE n < ?3
+)
+SELECT COUNT(*), COALESCE(SUM(LENGTH(CAST(json_value AS BLOB))), 0),
+    COUNT(*) FILTER (WHERE substr(json_value, 1, 1) = X'C0')
+FROM sample JOIN documents ON documents.rowid = sample.sampled_rowid
+"#;
+
 const WALK_INDEXES: &str =
     "SELECT index_id, key, ts, deleted FROM indexes ORDER BY index_id ASC, key ASC, ts ASC";
 
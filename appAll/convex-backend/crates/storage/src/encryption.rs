@@ -0,0 +1,1017 @@
+//! Envelope encryption for objects at rest.
+//!
+//! Objects written through [`EncryptedStorage`] start with a fixed-size header
+//! holding a random per-object data key, sealed with a key-encryption key
+//! (KEK) derived from the instance secret. The body is split into chunks of
+//! [`ENCRYPTION_CHUNK_SIZE`] bytes that are sealed independently, so a byte
+//! range can be served by fetching and opening only the chunks it overlaps.
+//!
+//! ```text
+//! header: magic (4) | format version (1) | KEK id (8) | nonce (24) | sealed data key (48) | checksum (16)
+//! chunk:  sealed plaintext (16 + up to ENCRYPTION_CHUNK_SIZE)
+//! ```
+//!
+//! Every chunk but the last one holds exactly `ENCRYPTION_CHUNK_SIZE` bytes of
+//! plaintext, and there's always a last chunk, even for empty objects. As in
+//! the STREAM construction, each chunk's nonce is derived from its index and
+//! whether it's the last chunk, so chunks can't be reordered, dropped or cut
+//! off at a chunk boundary without failing to open.
+//!
+//! Objects written before encryption was enabled have no header and are read
+//! back as is. The header ends with a checksum of everything before it, so a
+//! plaintext object is only mistaken for an encrypted one if it happens to
+//! start with a well-formed header, not merely with the magic bytes.
+//!
+//! Rotating keys only requires resealing the header of each object, since the
+//! data keys don't change. See [`reencrypt_local_object`].
+
+use std::{
+    cmp,
+    fmt,
+    fs::{
+        self,
+        File,
+    },
+    io::{
+        self,
+        Read,
+        Write,
+    },
+    ops::Range,
+    path::Path,
+    pin::Pin,
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::Context as _;
+use async_trait::async_trait;
+use bytes::Bytes;
+use common::types::{
+    FullyQualifiedObjectKey,
+    ObjectKey,
+};
+use futures::{
+    future::BoxFuture,
+    stream::{
+        self,
+        BoxStream,
+    },
+    FutureExt,
+    Stream,
+    StreamExt,
+    TryStreamExt,
+};
+use http::Uri;
+use sodiumoxide::crypto::{
+    generichash,
+    kdf,
+    secretbox,
+};
+
+use crate::{
+    BufferedUpload,
+    ClientDrivenUploadPartToken,
+    ClientDrivenUploadToken,
+    ObjectAttributes,
+    Storage,
+    StorageCacheKey,
+    StorageGetStream,
+    StoredObject,
+    Upload,
+    DOWNLOAD_CHUNK_SIZE,
+};
+
+/// Bytes of plaintext in each sealed chunk.
+pub const ENCRYPTION_CHUNK_SIZE: usize = 64 * 1024;
+
+const MAGIC: &[u8; 4] = b"CVXE";
+const FORMAT_VERSION: u8 = 2;
+const KEK_ID_LEN: usize = 8;
+const KEK_CONTEXT: [u8; kdf::CONTEXTBYTES] = *b"cvxstore";
+const HEADER_CHECKSUM_LEN: usize = generichash::DIGEST_MIN;
+const HEADER_LEN: usize = MAGIC.len()
+    + 1
+    + KEK_ID_LEN
+    + secretbox::NONCEBYTES
+    + secretbox::MACBYTES
+    + secretbox::KEYBYTES
+    + HEADER_CHECKSUM_LEN;
+const SEALED_CHUNK_SIZE: usize = ENCRYPTION_CHUNK_SIZE + secretbox::MACBYTES;
+/// Parts of a client-driven upload are staged with random nonces, since it
+/// isn't known which part is last until the upload is finished.
+const STAGED_CHUNK_OVERHEAD: usize = secretbox::NONCEBYTES + secretbox::MACBYTES;
+const STAGED_CHUNK_SIZE: usize = ENCRYPTION_CHUNK_SIZE + STAGED_CHUNK_OVERHEAD;
+
+type KekId = [u8; KEK_ID_LEN];
+
+#[derive(Clone)]
+struct Kek {
+    id: KekId,
+    key: secretbox::Key,
+}
+
+impl Kek {
+    fn derive(secret: &[u8; 32], version: u64) -> anyhow::Result<Self> {
+        let mut key = [0; secretbox::KEYBYTES];
+        kdf::derive_from_key(&mut key, version, KEK_CONTEXT, &kdf::Key(*secret))
+            .map_err(|()| anyhow::anyhow!("Failed to derive storage encryption key"))?;
+        let digest = generichash::hash(&key, Some(generichash::DIGEST_MIN), None)
+            .map_err(|()| anyhow::anyhow!("Failed to hash storage encryption key"))?;
+        let id = digest.as_ref()[..KEK_ID_LEN].try_into()?;
+        Ok(Self {
+            id,
+            key: secretbox::Key(key),
+        })
+    }
+}
+
+/// The key-encryption keys known to a deployment. New objects are sealed with
+/// the current key; objects sealed with a retired key stay readable until
+/// they're rotated.
+#[derive(Clone)]
+pub struct StorageKeyring {
+    current: Kek,
+    retired: Vec<Kek>,
+}
+
+impl fmt::Debug for StorageKeyring {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StorageKeyring")
+            .field("current", &hex::encode(self.current.id))
+            .field("num_retired", &self.retired.len())
+            .finish()
+    }
+}
+
+impl StorageKeyring {
+    /// Derive the keyring from the instance secret. Version `current_version`
+    /// is used for new objects, and all earlier versions of `secret` as well
+    /// as versions up to `current_version` of each of `retired_secrets` are
+    /// kept for reading.
+    pub fn derive(
+        secret: &[u8; 32],
+        current_version: u64,
+        retired_secrets: &[[u8; 32]],
+    ) -> anyhow::Result<Self> {
+        let current = Kek::derive(secret, current_version)?;
+        let mut retired = vec![];
+        for version in 0..current_version {
+            retired.push(Kek::derive(secret, version)?);
+        }
+        for retired_secret in retired_secrets {
+            for version in 0..=current_version {
+                retired.push(Kek::derive(retired_secret, version)?);
+            }
+        }
+        Ok(Self { current, retired })
+    }
+
+    fn get(&self, id: &KekId) -> Option<&Kek> {
+        std::iter::once(&self.current)
+            .chain(&self.retired)
+            .find(|kek| &kek.id == id)
+    }
+
+    /// Generate a new data key and the header that stores it.
+    fn new_header(&self) -> (Vec<u8>, secretbox::Key) {
+        let data_key = secretbox::gen_key();
+        (self.seal_header(&data_key), data_key)
+    }
+
+    fn seal_header(&self, data_key: &secretbox::Key) -> Vec<u8> {
+        let nonce = secretbox::gen_nonce();
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        header.extend_from_slice(MAGIC);
+        header.push(FORMAT_VERSION);
+        header.extend_from_slice(&self.current.id);
+        header.extend_from_slice(&nonce.0);
+        header.extend_from_slice(&secretbox::seal(&data_key.0, &nonce, &self.current.key));
+        let checksum = header_checksum(&header);
+        header.extend_from_slice(&checksum);
+        header
+    }
+
+    /// Returns the KEK id and data key stored in `header`.
+    fn open_header(&self, header: &[u8]) -> anyhow::Result<(KekId, secretbox::Key)> {
+        anyhow::ensure!(is_encrypted(header), "Invalid encrypted object header");
+        let rest = &header[MAGIC.len() + 1..HEADER_LEN - HEADER_CHECKSUM_LEN];
+        let (kek_id, rest) = rest.split_at(KEK_ID_LEN);
+        let kek_id: KekId = kek_id.try_into()?;
+        let (nonce, sealed_key) = rest.split_at(secretbox::NONCEBYTES);
+        let kek = self.get(&kek_id).with_context(|| {
+            format!(
+                "Object is encrypted with unknown key {}. Was a retired instance secret removed?",
+                hex::encode(kek_id)
+            )
+        })?;
+        let nonce = secretbox::Nonce::from_slice(nonce).context("Invalid header nonce")?;
+        let data_key = secretbox::open(sealed_key, &nonce, &kek.key)
+            .map_err(|()| anyhow::anyhow!("Failed to decrypt object data key"))?;
+        let data_key = secretbox::Key::from_slice(&data_key).context("Invalid data key")?;
+        Ok((kek_id, data_key))
+    }
+}
+
+fn header_checksum(header_without_checksum: &[u8]) -> [u8; HEADER_CHECKSUM_LEN] {
+    let digest = generichash::hash(header_without_checksum, Some(HEADER_CHECKSUM_LEN), None)
+        .expect("checksum length is valid");
+    digest
+        .as_ref()
+        .try_into()
+        .expect("digest has the requested length")
+}
+
+/// Whether `header` is a complete, well-formed header of the current version.
+fn is_encrypted(header: &[u8]) -> bool {
+    header.len() == HEADER_LEN
+        && header.starts_with(MAGIC)
+        && header[MAGIC.len()] == FORMAT_VERSION
+        && header[HEADER_LEN - HEADER_CHECKSUM_LEN..]
+            == header_checksum(&header[..HEADER_LEN - HEADER_CHECKSUM_LEN])
+}
+
+/// Chunks are sealed with the object's own data key, so a nonce only has to be
+/// unique within the object. Deriving it from the chunk index and whether
+/// it's the last chunk is what ties each chunk to its position.
+fn chunk_nonce(index: u64, last: bool) -> secretbox::Nonce {
+    let mut nonce = [0; secretbox::NONCEBYTES];
+    nonce[..8].copy_from_slice(&index.to_be_bytes());
+    nonce[8] = last as u8;
+    secretbox::Nonce(nonce)
+}
+
+fn seal_chunk(chunk: &[u8], index: u64, last: bool, data_key: &secretbox::Key, out: &mut Vec<u8>) {
+    out.extend_from_slice(&secretbox::seal(chunk, &chunk_nonce(index, last), data_key));
+}
+
+fn open_chunk(
+    sealed: &[u8],
+    index: u64,
+    last: bool,
+    data_key: &secretbox::Key,
+) -> anyhow::Result<Vec<u8>> {
+    anyhow::ensure!(
+        sealed.len() >= secretbox::MACBYTES,
+        "Truncated encrypted chunk"
+    );
+    secretbox::open(sealed, &chunk_nonce(index, last), data_key).map_err(|()| {
+        anyhow::anyhow!("Failed to decrypt object chunk {index}. Was the object modified?")
+    })
+}
+
+fn seal_staged_chunk(chunk: &[u8], data_key: &secretbox::Key, out: &mut Vec<u8>) {
+    let nonce = secretbox::gen_nonce();
+    out.extend_from_slice(&nonce.0);
+    out.extend_from_slice(&secretbox::seal(chunk, &nonce, data_key));
+}
+
+fn open_staged_chunk(sealed: &[u8], data_key: &secretbox::Key) -> anyhow::Result<Vec<u8>> {
+    anyhow::ensure!(
+        sealed.len() > STAGED_CHUNK_OVERHEAD,
+        "Truncated staged chunk"
+    );
+    let (nonce, sealed) = sealed.split_at(secretbox::NONCEBYTES);
+    let nonce = secretbox::Nonce::from_slice(nonce).context("Invalid chunk nonce")?;
+    secretbox::open(sealed, &nonce, data_key)
+        .map_err(|()| anyhow::anyhow!("Failed to decrypt staged upload chunk"))
+}
+
+/// Size of a `part_len` byte part of a client-driven upload once staged.
+fn staged_len(part_len: u64) -> u64 {
+    part_len + part_len.div_ceil(ENCRYPTION_CHUNK_SIZE as u64) * STAGED_CHUNK_OVERHEAD as u64
+}
+
+/// The number of chunks in an encrypted object that is `object_len` bytes,
+/// and the size of its plaintext.
+fn chunk_layout(object_len: u64) -> anyhow::Result<(u64, u64)> {
+    let body_len = object_len
+        .checked_sub(HEADER_LEN as u64)
+        .context("Truncated encrypted object")?;
+    let num_chunks = body_len.div_ceil(SEALED_CHUNK_SIZE as u64);
+    let last_chunk_len = body_len - num_chunks.saturating_sub(1) * SEALED_CHUNK_SIZE as u64;
+    anyhow::ensure!(
+        num_chunks > 0 && last_chunk_len >= secretbox::MACBYTES as u64,
+        "Truncated encrypted object"
+    );
+    let plaintext_len = (num_chunks - 1) * ENCRYPTION_CHUNK_SIZE as u64 + last_chunk_len
+        - secretbox::MACBYTES as u64;
+    Ok((num_chunks, plaintext_len))
+}
+
+/// Splits a stream of plaintext into sealed chunks.
+struct ChunkSealer {
+    data_key: secretbox::Key,
+    pending: Vec<u8>,
+    next_index: u64,
+}
+
+impl ChunkSealer {
+    fn new(data_key: secretbox::Key) -> Self {
+        Self {
+            data_key,
+            pending: Vec::with_capacity(ENCRYPTION_CHUNK_SIZE),
+            next_index: 0,
+        }
+    }
+
+    fn seal_pending(&mut self, last: bool, out: &mut Vec<u8>) {
+        seal_chunk(&self.pending, self.next_index, last, &self.data_key, out);
+        self.pending.clear();
+        self.next_index += 1;
+    }
+
+    /// Appends the sealed form of every chunk completed by `data` to `out`. A
+    /// full chunk is held back until more data arrives, since it might turn
+    /// out to be the last one.
+    fn push(&mut self, mut data: &[u8], out: &mut Vec<u8>) {
+        while !data.is_empty() {
+            if self.pending.len() == ENCRYPTION_CHUNK_SIZE {
+                self.seal_pending(false, out);
+            }
+            let n = cmp::min(ENCRYPTION_CHUNK_SIZE - self.pending.len(), data.len());
+            self.pending.extend_from_slice(&data[..n]);
+            data = &data[n..];
+        }
+    }
+
+    /// Seals the last, possibly short or empty, chunk.
+    fn finish(&mut self, out: &mut Vec<u8>) {
+        self.seal_pending(true, out);
+    }
+}
+
+/// A `Storage` that encrypts everything written to an inner `Storage`.
+///
+/// Signed URLs hand out direct access to the inner storage, so they aren't
+/// supported. Use case storages that rely on them (e.g. modules, which are
+/// fetched by the Node executor) should not be wrapped.
+#[derive(Clone, Debug)]
+pub struct EncryptedStorage {
+    inner: Arc<dyn Storage>,
+    keyring: Arc<StorageKeyring>,
+}
+
+impl EncryptedStorage {
+    pub fn new(inner: Arc<dyn Storage>, keyring: Arc<StorageKeyring>) -> Self {
+        Self { inner, keyring }
+    }
+
+    /// Reads the header of an object, returning `None` for plaintext objects.
+    async fn read_header(
+        inner: &Arc<dyn Storage>,
+        keyring: &StorageKeyring,
+        key: &FullyQualifiedObjectKey,
+        object_len: u64,
+    ) -> anyhow::Result<Option<secretbox::Key>> {
+        if object_len < HEADER_LEN as u64 {
+            return Ok(None);
+        }
+        let header = read_all(inner.get_small_range(key, 0..HEADER_LEN as u64).await?).await?;
+        if !is_encrypted(&header) {
+            return Ok(None);
+        }
+        let (_, data_key) = keyring.open_header(&header)?;
+        Ok(Some(data_key))
+    }
+
+    /// Parts of a client-driven upload are staged as they arrive, before it's
+    /// known which one is last, so finishing the upload reseals the staged
+    /// object in the chunk format readers expect.
+    async fn reseal_client_driven_upload(
+        &self,
+        staged_key: ObjectKey,
+        part_lens: Vec<u64>,
+    ) -> anyhow::Result<ObjectKey> {
+        let mut upload = self.start_upload().await?;
+        // The header is written with the first part, so an upload without parts
+        // has nothing staged to copy.
+        if !part_lens.is_empty() {
+            let fq_key = self.inner.fully_qualified_key(&staged_key);
+            let object_len = self
+                .inner
+                .get_fq_object_attributes(&fq_key)
+                .await?
+                .context("Finished upload not found")?
+                .size;
+            let data_key = Self::read_header(&self.inner, &self.keyring, &fq_key, object_len)
+                .await?
+                .context("Finished upload is missing its header")?;
+            let mut offset = HEADER_LEN as u64;
+            for part_len in part_lens {
+                let part_end = offset + staged_len(part_len);
+                while offset < part_end {
+                    // Read whole staged chunks, up to about DOWNLOAD_CHUNK_SIZE at a time.
+                    let batch = cmp::max(DOWNLOAD_CHUNK_SIZE / STAGED_CHUNK_SIZE as u64, 1)
+                        * STAGED_CHUNK_SIZE as u64;
+                    let end = cmp::min(offset + batch, part_end);
+                    let staged =
+                        read_all(self.inner.get_small_range(&fq_key, offset..end).await?).await?;
+                    for chunk in staged.chunks(STAGED_CHUNK_SIZE) {
+                        upload
+                            .write(open_staged_chunk(chunk, &data_key)?.into())
+                            .await?;
+                    }
+                    offset = end;
+                }
+            }
+            anyhow::ensure!(
+                offset == object_len,
+                "Finished upload is {object_len} bytes, expected {offset}"
+            );
+        }
+        let resealed = upload.complete().await?;
+        self.inner.delete_object(&staged_key).await?;
+        Ok(resealed)
+    }
+}
+
+/// Prefix of the URIs [`EncryptedStorage`] hands out in place of signed URLs.
+const ENCRYPTED_OBJECT_URI_PREFIX: &str = "convex-encrypted://object/";
+
+/// The object an [`EncryptedStorage`] URI names, if `uri` is one. Fetching the
+/// URI itself isn't possible, so whoever receives one must read the object
+/// through the `EncryptedStorage`, like the local Node executor does for source
+/// packages.
+pub fn encrypted_object_key(uri: &str) -> Option<ObjectKey> {
+    uri.strip_prefix(ENCRYPTED_OBJECT_URI_PREFIX)?
+        .to_string()
+        .try_into()
+        .ok()
+}
+
+async fn read_all(get_stream: StorageGetStream) -> anyhow::Result<Vec<u8>> {
+    let mut buf = Vec::with_capacity(get_stream.content_length as usize);
+    let mut stream = get_stream.stream;
+    while let Some(chunk) = stream.try_next().await? {
+        buf.extend_from_slice(&chunk);
+    }
+    Ok(buf)
+}
+
+fn parse_token(token: &str) -> anyhow::Result<(&str, &str)> {
+    token
+        .split_once(':')
+        .context("Invalid encrypted upload token")
+}
+
+#[async_trait]
+impl Storage for EncryptedStorage {
+    async fn start_upload(&self) -> anyhow::Result<Box<BufferedUpload>> {
+        let inner = self.inner.start_upload().await?;
+        let (header, data_key) = self.keyring.new_header();
+        let upload = EncryptedUpload {
+            inner,
+            header: Some(header),
+            sealer: ChunkSealer::new(data_key),
+        };
+        // The inner upload already buffers parts to the right size.
+        Ok(Box::new(BufferedUpload::new(upload, 0, usize::MAX)))
+    }
+
+    /// Parts are staged with their own framing and the whole object is
+    /// resealed when the upload is finished.
+    async fn start_client_driven_upload(&self) -> anyhow::Result<ClientDrivenUploadToken> {
+        let inner = self.inner.start_client_driven_upload().await?;
+        let (header, _) = self.keyring.new_header();
+        Ok(ClientDrivenUploadToken(format!(
+            "{}:{}",
+            hex::encode(header),
+            inner.0
+        )))
+    }
+
+    async fn upload_part(
+        &self,
+        token: ClientDrivenUploadToken,
+        part_number: u16,
+        part: Bytes,
+    ) -> anyhow::Result<ClientDrivenUploadPartToken> {
+        let (header, inner_token) = parse_token(&token.0)?;
+        let header = hex::decode(header)?;
+        let (_, data_key) = self.keyring.open_header(&header)?;
+        let mut sealed = if part_number == 1 { header } else { vec![] };
+        for chunk in part.chunks(ENCRYPTION_CHUNK_SIZE) {
+            seal_staged_chunk(chunk, &data_key, &mut sealed);
+        }
+        let inner_part_token = self
+            .inner
+            .upload_part(
+                ClientDrivenUploadToken(inner_token.to_string()),
+                part_number,
+                sealed.into(),
+            )
+            .await?;
+        Ok(ClientDrivenUploadPartToken(format!(
+            "{}:{}",
+            part.len(),
+            inner_part_token.0
+        )))
+    }
+
+    async fn finish_client_driven_upload(
+        &self,
+        token: ClientDrivenUploadToken,
+        part_tokens: Vec<ClientDrivenUploadPartToken>,
+    ) -> anyhow::Result<ObjectKey> {
+        let (_, inner_token) = parse_token(&token.0)?;
+        let mut part_lens = vec![];
+        let mut inner_part_tokens = vec![];
+        for part_token in part_tokens {
+            let (part_len, inner_part_token) = parse_token(&part_token.0)?;
+            part_lens.push(part_len.parse::<u64>()?);
+            inner_part_tokens.push(ClientDrivenUploadPartToken(inner_part_token.to_string()));
+        }
+        let staged_key = self
+            .inner
+            .finish_client_driven_upload(
+                ClientDrivenUploadToken(inner_token.to_string()),
+                inner_part_tokens,
+            )
+            .await?;
+        self.reseal_client_driven_upload(staged_key, part_lens)
+            .await
+    }
+
+    async fn abort_client_driven_upload(
+        &self,
+        token: ClientDrivenUploadToken,
+    ) -> anyhow::Result<()> {
+        let (_, inner_token) = parse_token(&token.0)?;
+        self.inner
+            .abort_client_driven_upload(ClientDrivenUploadToken(inner_token.to_string()))
+            .await
+    }
+
+    /// Anyone could fetch a real signed URL, so this hands out a URI that
+    /// only names the object. See [`encrypted_object_key`].
+    async fn signed_url(&self, key: ObjectKey, _expires_in: Duration) -> anyhow::Result<Uri> {
+        format!("{ENCRYPTED_OBJECT_URI_PREFIX}{}", String::from(key))
+            .parse()
+            .context("Invalid encrypted object URI")
+    }
+
+    async fn presigned_upload_url(
+        &self,
+        _expires_in: Duration,
+    ) -> anyhow::Result<(ObjectKey, Uri)> {
+        anyhow::bail!("Presigned upload URLs aren't supported for encrypted storage")
+    }
+
+    async fn get_fq_object_attributes(
+        &self,
+        key: &FullyQualifiedObjectKey,
+    ) -> anyhow::Result<Option<ObjectAttributes>> {
+        let Some(attributes) = self.inner.get_fq_object_attributes(key).await? else {
+            return Ok(None);
+        };
+        if Self::read_header(&self.inner, &self.keyring, key, attributes.size)
+            .await?
+            .is_none()
+        {
+            return Ok(Some(attributes));
+        }
+        let (_, plaintext_len) = chunk_layout(attributes.size)?;
+        Ok(Some(ObjectAttributes {
+            size: plaintext_len,
+        }))
+    }
+
+    fn get_small_range(
+        &self,
+        key: &FullyQualifiedObjectKey,
+        bytes_range: Range<u64>,
+    ) -> BoxFuture<'static, anyhow::Result<StorageGetStream>> {
+        let inner = self.inner.clone();
+        let keyring = self.keyring.clone();
+        let key = key.clone();
+        async move {
+            let object_len = inner
+                .get_fq_object_attributes(&key)
+                .await?
+                .with_context(|| format!("Object {key:?} not found"))?
+                .size;
+            let Some(data_key) = Self::read_header(&inner, &keyring, &key, object_len).await?
+            else {
+                return inner.get_small_range(&key, bytes_range).await;
+            };
+            let (num_chunks, plaintext_len) = chunk_layout(object_len)?;
+            anyhow::ensure!(
+                bytes_range.end <= plaintext_len,
+                "Range {bytes_range:?} is past the end of {key:?}"
+            );
+            let content_length = bytes_range.end - bytes_range.start;
+            if content_length == 0 {
+                return Ok(StorageGetStream {
+                    content_length: 0,
+                    stream: stream::empty().boxed(),
+                });
+            }
+            let chunk_size = ENCRYPTION_CHUNK_SIZE as u64;
+            let first_chunk = bytes_range.start / chunk_size;
+            let last_chunk = (bytes_range.end - 1) / chunk_size;
+            let sealed_start = HEADER_LEN as u64 + first_chunk * SEALED_CHUNK_SIZE as u64;
+            let sealed_end = cmp::min(
+                HEADER_LEN as u64 + (last_chunk + 1) * SEALED_CHUNK_SIZE as u64,
+                object_len,
+            );
+            let sealed = read_all(
+                inner
+                    .get_small_range(&key, sealed_start..sealed_end)
+                    .await?,
+            )
+            .await?;
+            let mut plaintext = Vec::with_capacity(sealed.len());
+            for (index, chunk) in (first_chunk..).zip(sealed.chunks(SEALED_CHUNK_SIZE)) {
+                let last = index == num_chunks - 1;
+                plaintext.extend_from_slice(&open_chunk(chunk, index, last, &data_key)?);
+            }
+            let start = (bytes_range.start - first_chunk * chunk_size) as usize;
+            let end = start + content_length as usize;
+            let buf = Bytes::from(plaintext).slice(start..end);
+            Ok(StorageGetStream {
+                content_length: content_length as i64,
+                stream: stream::once(async move { Ok(buf) }).boxed(),
+            })
+        }
+        .boxed()
+    }
+
+    fn storage_type_proto(&self) -> pb::searchlight::StorageType {
+        self.inner.storage_type_proto()
+    }
+
+    fn cache_key(&self, key: &ObjectKey) -> StorageCacheKey {
+        self.inner.cache_key(key)
+    }
+
+    fn fully_qualified_key(&self, key: &ObjectKey) -> FullyQualifiedObjectKey {
+        self.inner.fully_qualified_key(key)
+    }
+
+    fn test_only_decompose_fully_qualified_key(
+        &self,
+        key: FullyQualifiedObjectKey,
+    ) -> anyhow::Result<ObjectKey> {
+        self.inner.test_only_decompose_fully_qualified_key(key)
+    }
+
+    async fn delete_object(&self, key: &ObjectKey) -> anyhow::Result<()> {
+        self.inner.delete_object(key).await
+    }
+
+    fn list_objects(&self) -> BoxStream<'static, anyhow::Result<StoredObject>> {
+        self.inner.list_objects()
+    }
+}
+
+struct EncryptedUpload {
+    inner: Box<BufferedUpload>,
+    /// Written ahead of the first chunk.
+    header: Option<Vec<u8>>,
+    sealer: ChunkSealer,
+}
+
+impl EncryptedUpload {
+    fn seal(header: &mut Option<Vec<u8>>, sealer: &mut ChunkSealer, data: &[u8]) -> Bytes {
+        let mut out = header.take().unwrap_or_default();
+        sealer.push(data, &mut out);
+        out.into()
+    }
+}
+
+#[async_trait]
+impl Upload for EncryptedUpload {
+    async fn write(&mut self, data: Bytes) -> anyhow::Result<()> {
+        let sealed = Self::seal(&mut self.header, &mut self.sealer, &data);
+        self.inner.write(sealed).await
+    }
+
+    async fn try_write_parallel<'a>(
+        &'a mut self,
+        stream: &mut Pin<Box<dyn Stream<Item = anyhow::Result<Bytes>> + Send + 'a>>,
+    ) -> anyhow::Result<()> {
+        let Self {
+            inner,
+            header,
+            sealer,
+        } = self;
+        let mut sealed = stream
+            .map_ok(|data| Self::seal(header, sealer, &data))
+            .boxed();
+        inner.try_write_parallel(&mut sealed).await
+    }
+
+    async fn abort(self: Box<Self>) -> anyhow::Result<()> {
+        self.inner.abort().await
+    }
+
+    async fn complete(mut self: Box<Self>) -> anyhow::Result<ObjectKey> {
+        let mut sealed = self.header.take().unwrap_or_default();
+        self.sealer.finish(&mut sealed);
+        self.inner.write(sealed.into()).await?;
+        self.inner.complete().await
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum LocalObjectRotation {
+    /// The object was already sealed with the current key.
+    Unchanged,
+    /// The object's data key was resealed with the current key.
+    Rewrapped,
+    /// The object was stored as plaintext and has now been encrypted.
+    Encrypted,
+}
+
+/// Bring a `LocalDirStorage` object file up to date with `keyring`: reseal its
+/// header if it uses a retired key, or encrypt it if it's plaintext. The file
+/// is replaced atomically, so concurrent readers see either version, but it
+/// must not still be open for writing.
+pub fn reencrypt_local_object(
+    keyring: &StorageKeyring,
+    path: &Path,
+) -> anyhow::Result<LocalObjectRotation> {
+    let mut file = File::open(path)?;
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    (&mut file)
+        .take(HEADER_LEN as u64)
+        .read_to_end(&mut header)?;
+
+    let tmp_path = path.with_extension("rotating");
+    let mut tmp = File::create(&tmp_path)?;
+    let rotation = if is_encrypted(&header) {
+        let (kek_id, data_key) = keyring.open_header(&header)?;
+        if kek_id == keyring.current.id {
+            drop(tmp);
+            fs::remove_file(&tmp_path)?;
+            return Ok(LocalObjectRotation::Unchanged);
+        }
+        tmp.write_all(&keyring.seal_header(&data_key))?;
+        io::copy(&mut file, &mut tmp)?;
+        LocalObjectRotation::Rewrapped
+    } else {
+        let (new_header, data_key) = keyring.new_header();
+        tmp.write_all(&new_header)?;
+        let mut sealer = ChunkSealer::new(data_key);
+        let mut out = vec![];
+        sealer.push(&header, &mut out);
+        let mut buf = vec![0; ENCRYPTION_CHUNK_SIZE];
+        loop {
+            let n = file.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            sealer.push(&buf[..n], &mut out);
+            tmp.write_all(&out)?;
+            out.clear();
+        }
+        sealer.finish(&mut out);
+        tmp.write_all(&out)?;
+        LocalObjectRotation::Encrypted
+    };
+    tmp.sync_all()?;
+    fs::rename(&tmp_path, path)?;
+    Ok(rotation)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        ops::Bound,
+        sync::Arc,
+        time::Duration,
+    };
+
+    use bytes::Bytes;
+    use futures::stream;
+    use runtime::testing::TestRuntime;
+
+    use super::{
+        encrypted_object_key,
+        reencrypt_local_object,
+        EncryptedStorage,
+        LocalObjectRotation,
+        StorageKeyring,
+        ENCRYPTION_CHUNK_SIZE,
+        FORMAT_VERSION,
+        HEADER_LEN,
+        MAGIC,
+        SEALED_CHUNK_SIZE,
+    };
+    use crate::{
+        LocalDirStorage,
+        Storage,
+        StorageExt,
+        Upload,
+        UploadExt,
+    };
+
+    const SECRET: [u8; 32] = [7; 32];
+
+    fn test_data(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[convex_macro::test_runtime]
+    async fn test_encrypted_roundtrip_and_ranges(rt: TestRuntime) -> anyhow::Result<()> {
+        let local = LocalDirStorage::new(rt)?;
+        let dir = local.path().clone();
+        let keyring = Arc::new(StorageKeyring::derive(&SECRET, 0, &[])?);
+        let storage: Arc<dyn Storage> = Arc::new(EncryptedStorage::new(Arc::new(local), keyring));
+
+        let data = test_data(3 * ENCRYPTION_CHUNK_SIZE + 17);
+        let mut upload = storage.start_upload().await?;
+        upload
+            .write_parallel(stream::iter(
+                data.chunks(10_000)
+                    .map(Bytes::copy_from_slice)
+                    .collect::<Vec<_>>(),
+            ))
+            .await?;
+        let key = upload.complete().await?;
+
+        // Nothing on disk should contain the plaintext.
+        let on_disk = std::fs::read(dir.join(format!("{key}.blob")))?;
+        assert!(!on_disk
+            .windows(64)
+            .any(|window| window == &data[1000..1064]));
+
+        let all = storage.get(&key).await?.unwrap().collect_as_bytes().await?;
+        assert_eq!(&all[..], &data[..]);
+        for (start, end) in [
+            (0, 1),
+            (ENCRYPTION_CHUNK_SIZE - 3, ENCRYPTION_CHUNK_SIZE + 3),
+            (100, 2 * ENCRYPTION_CHUNK_SIZE + 5),
+            (3 * ENCRYPTION_CHUNK_SIZE, data.len()),
+        ] {
+            let range = storage
+                .get_range(
+                    &key,
+                    (Bound::Included(start as u64), Bound::Excluded(end as u64)),
+                )
+                .await?
+                .unwrap()
+                .collect_as_bytes()
+                .await?;
+            assert_eq!(&range[..], &data[start..end]);
+        }
+
+        // "Signed" URLs only name the object.
+        let uri = storage
+            .signed_url(key.clone(), Duration::from_secs(60))
+            .await?;
+        assert_eq!(encrypted_object_key(&uri.to_string()), Some(key));
+        assert_eq!(encrypted_object_key("file:///tmp/package.zip"), None);
+        Ok(())
+    }
+
+    #[convex_macro::test_runtime]
+    async fn test_encrypted_client_driven_upload(rt: TestRuntime) -> anyhow::Result<()> {
+        let keyring = Arc::new(StorageKeyring::derive(&SECRET, 0, &[])?);
+        let storage: Arc<dyn Storage> = Arc::new(EncryptedStorage::new(
+            Arc::new(LocalDirStorage::new(rt)?),
+            keyring,
+        ));
+
+        // Parts are resealed on finish, whether or not they line up with chunk
+        // boundaries.
+        let data = test_data(2 * ENCRYPTION_CHUNK_SIZE + 5);
+        for split in [ENCRYPTION_CHUNK_SIZE, ENCRYPTION_CHUNK_SIZE + 100] {
+            let token = storage.start_client_driven_upload().await?;
+            let mut part_tokens = vec![];
+            for (i, part) in [&data[..split], &data[split..]].into_iter().enumerate() {
+                part_tokens.push(
+                    storage
+                        .upload_part(token.clone(), i as u16 + 1, Bytes::copy_from_slice(part))
+                        .await?,
+                );
+            }
+            let key = storage
+                .finish_client_driven_upload(token, part_tokens)
+                .await?;
+            let all = storage.get(&key).await?.unwrap().collect_as_bytes().await?;
+            assert_eq!(&all[..], &data[..]);
+        }
+        Ok(())
+    }
+
+    #[convex_macro::test_runtime]
+    async fn test_reencrypt_local_object(rt: TestRuntime) -> anyhow::Result<()> {
+        let local = Arc::new(LocalDirStorage::new(rt)?);
+        let dir = local.path().clone();
+        let old_keyring = Arc::new(StorageKeyring::derive(&SECRET, 0, &[])?);
+        let new_secret = [9; 32];
+        let new_keyring = Arc::new(StorageKeyring::derive(&new_secret, 1, &[SECRET])?);
+
+        let data = test_data(ENCRYPTION_CHUNK_SIZE + 1);
+        let old_storage: Arc<dyn Storage> =
+            Arc::new(EncryptedStorage::new(local.clone(), old_keyring.clone()));
+        let mut upload = old_storage.start_upload().await?;
+        upload.write(data.clone().into()).await?;
+        let encrypted_key = upload.complete().await?;
+
+        let plaintext_storage: Arc<dyn Storage> = local.clone();
+        let mut upload = plaintext_storage.start_upload().await?;
+        upload.write(data.clone().into()).await?;
+        let plaintext_key = upload.complete().await?;
+
+        for (key, expected) in [
+            (&encrypted_key, LocalObjectRotation::Rewrapped),
+            (&plaintext_key, LocalObjectRotation::Encrypted),
+        ] {
+            let path = dir.join(format!("{key}.blob"));
+            assert_eq!(reencrypt_local_object(&new_keyring, &path)?, expected);
+            assert_eq!(
+                reencrypt_local_object(&new_keyring, &path)?,
+                LocalObjectRotation::Unchanged
+            );
+        }
+
+        // Everything is readable with the new key alone.
+        let new_storage: Arc<dyn Storage> = Arc::new(EncryptedStorage::new(
+            local,
+            Arc::new(StorageKeyring::derive(&new_secret, 1, &[])?),
+        ));
+        for key in [&encrypted_key, &plaintext_key] {
+            let all = new_storage
+                .get(key)
+                .await?
+                .unwrap()
+                .collect_as_bytes()
+                .await?;
+            assert_eq!(&all[..], &data[..]);
+        }
+        Ok(())
+    }
+
+    #[convex_macro::test_runtime]
+    async fn test_tampered_chunks_are_rejected(rt: TestRuntime) -> anyhow::Result<()> {
+        let local = LocalDirStorage::new(rt)?;
+        let dir = local.path().clone();
+        let keyring = Arc::new(StorageKeyring::derive(&SECRET, 0, &[])?);
+        let storage: Arc<dyn Storage> = Arc::new(EncryptedStorage::new(Arc::new(local), keyring));
+
+        // Empty objects still have a final chunk.
+        let upload = storage.start_upload().await?;
+        let empty_key = upload.complete().await?;
+        let empty = storage
+            .get(&empty_key)
+            .await?
+            .unwrap()
+            .collect_as_bytes()
+            .await?;
+        assert!(empty.is_empty());
+
+        let data = test_data(3 * ENCRYPTION_CHUNK_SIZE);
+        let mut upload = storage.start_upload().await?;
+        upload.write(data.clone().into()).await?;
+        let key = upload.complete().await?;
+        let path = dir.join(format!("{key}.blob"));
+        let original = std::fs::read(&path)?;
+        let chunk =
+            |i: usize| HEADER_LEN + i * SEALED_CHUNK_SIZE..HEADER_LEN + (i + 1) * SEALED_CHUNK_SIZE;
+
+        // Swapping two chunks.
+        let mut swapped = original.clone();
+        swapped[chunk(0)].copy_from_slice(&original[chunk(1)]);
+        swapped[chunk(1)].copy_from_slice(&original[chunk(0)]);
+        // Dropping the last chunk, which leaves a whole number of chunks.
+        let truncated = original[..chunk(2).start].to_vec();
+        for tampered in [swapped, truncated] {
+            std::fs::write(&path, tampered)?;
+            let read = async { storage.get(&key).await?.unwrap().collect_as_bytes().await };
+            assert!(read.await.is_err());
+        }
+        Ok(())
+    }
+
+    #[convex_macro::test_runtime]
+    async fn test_plaintext_starting_with_magic(rt: TestRuntime) -> anyhow::Result<()> {
+        let local = Arc::new(LocalDirStorage::new(rt)?);
+        let dir = local.path().clone();
+        let keyring = Arc::new(StorageKeyring::derive(&SECRET, 0, &[])?);
+
+        // A plaintext object that looks like the start of an encrypted one.
+        let mut data = MAGIC.to_vec();
+        data.push(FORMAT_VERSION);
+        data.extend(test_data(2 * HEADER_LEN));
+        let plaintext_storage: Arc<dyn Storage> = local.clone();
+        let mut upload = plaintext_storage.start_upload().await?;
+        upload.write(data.clone().into()).await?;
+        let key = upload.complete().await?;
+
+        let storage: Arc<dyn Storage> = Arc::new(EncryptedStorage::new(local, keyring.clone()));
+        let all = storage.get(&key).await?.unwrap().collect_as_bytes().await?;
+        assert_eq!(&all[..], &data[..]);
+
+        let path = dir.join(format!("{key}.blob"));
+        assert_eq!(
+            reencrypt_local_object(&keyring, &path)?,
+            LocalObjectRotation::Encrypted
+        );
+        let all = storage.get(&key).await?.unwrap().collect_as_bytes().await?;
+        assert_eq!(&all[..], &data[..]);
+        Ok(())
+    }
+}
@@ -31,7 +31,10 @@ use std::{
         Context,
         Poll,
     },
-    time::Duration,
+    time::{
+        Duration,
+        SystemTime,
+    },
 };
 
 use anyhow::Context as _;
@@ -85,9 +88,23 @@ use value::sha256::{
     Sha256Digest,
 };
 
+mod encryption;
+
+pub use crate::encryption::{
+    encrypted_object_key,
+    reencrypt_local_object,
+    EncryptedStorage,
+    LocalObjectRotation,
+    StorageKeyring,
+    ENCRYPTION_CHUNK_SIZE,
+};
+
 pub const LOCAL_DIR_MIN_PART_SIZE: usize = 5 * (1 << 20);
 pub const LOCAL_DIR_MAX_PART_SIZE: usize = 8 * (1 << 30);
 pub const MAX_NUM_PARTS: usize = 10000;
+/// Every part of a client-driven upload except the last must be at least
+/// this large, matching S3's multipart upload limit.
+pub const MIN_CLIENT_DRIVEN_PART_SIZE: usize = 5 * (1 << 20);
 pub const MAXIMUM_PARALLEL_UPLOADS: usize = 8;
 
 #[derive(Clone, Eq, PartialEq, PartialOrd, Ord, derive_more::Display)]
@@ -165,6 +182,11 @@ pub trait Storage: Send + Sync + Debug {
         token: ClientDrivenUploadToken,
         part_tokens: Vec<ClientDrivenUploadPartToken>,
     ) -> anyhow::Result<ObjectKey>;
+    /// Abandon a client-driven upload, discarding any parts uploaded so far.
+    async fn abort_client_driven_upload(
+        &self,
+        token: ClientDrivenUploadToken,
+    ) -> anyhow::Result<()>;
 
     /// Gets a signed url for an object.
     async fn signed_url(&self, key: ObjectKey, expires_in: Duration) -> anyhow::Result<Uri>;
@@ -203,6 +225,19 @@ pub trait Storage: Send + Sync + Debug {
     ) -> anyhow::Result<ObjectKey>;
     /// Delete the given object.
     async fn delete_object(&self, key: &ObjectKey) -> anyhow::Result<()>;
+    /// List every object in this storage, in no particular order. Objects
+    /// that are still being uploaded may be included.
+    fn list_objects(&self) -> BoxStream<'static, anyhow::Result<StoredObject>>;
+}
+
+/// An object returned by [`Storage::list_objects`].
+#[derive(Debug, Clone)]
+pub struct StoredObject {
+    pub key: ObjectKey,
+    /// Size of the object as stored, which may differ from the size reported
+    /// by `get_object_attributes` if the storage transforms its contents.
+    pub size: u64,
+    pub last_modified: SystemTime,
 }
 
 pub struct ObjectAttributes {
@@ -930,6 +965,20 @@ impl<RT: Runtime> Storage for LocalDirStorage<RT> {
         Ok(object_key)
     }
 
+    async fn abort_client_driven_upload(
+        &self,
+        token: ClientDrivenUploadToken,
+    ) -> anyhow::Result<()> {
+        let ClientDrivenUpload {
+            object_key: _,
+            filepath,
+        } = token.try_into()?;
+        match fs::remove_file(filepath) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
     async fn signed_url(&self, key: ObjectKey, _expires_in: Duration) -> anyhow::Result<Uri> {
         let key = self.path_for_key(key);
         let path = self.dir.join(key);
@@ -1020,15 +1069,11 @@ impl<RT: Runtime> Storage for LocalDirStorage<RT> {
         key: &FullyQualifiedObjectKey,
     ) -> anyhow::Result<Option<ObjectAttributes>> {
         let path = Path::new(key.as_str());
-        let mut buf = vec![];
-        let result = File::open(path);
-        if result.is_err() {
+        let Ok(metadata) = fs::metadata(path) else {
             return Ok(None);
-        }
-        let mut file = result.unwrap();
-        file.read_to_end(&mut buf)?;
+        };
         Ok(Some(ObjectAttributes {
-            size: buf.len() as u64,
+            size: metadata.len(),
         }))
     }
 
@@ -1048,6 +1093,54 @@ impl<RT: Runtime> Storage for LocalDirStorage<RT> {
         fs::remove_file(path)?;
         Ok(())
     }
+
+    fn list_objects(&self) -> BoxStream<'static, anyhow::Result<StoredObject>> {
+        let dir = self.dir.clone();
+        stream::once(async move {
+            let mut objects = vec![];
+            list_local_dir_objects(&dir, &dir, &mut objects)?;
+            anyhow::Ok(stream::iter(objects.into_iter().map(Ok)))
+        })
+        .try_flatten()
+        .boxed()
+    }
+}
+
+/// Recursively collect the `.blob` files under `dir`. Keys containing "/" are
+/// stored in subdirectories, so the key is the path relative to `root`.
+fn list_local_dir_objects(
+    root: &Path,
+    dir: &Path,
+    objects: &mut Vec<StoredObject>,
+) -> anyhow::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        // Objects can be deleted while we're listing, so skip anything that's
+        // gone by the time we look at it.
+        let metadata = match entry.metadata() {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+        if metadata.is_dir() {
+            list_local_dir_objects(root, &path, objects)?;
+            continue;
+        }
+        let relative_path = path.strip_prefix(root)?;
+        let Some(key) = relative_path
+            .to_str()
+            .and_then(|path| path.strip_suffix(".blob"))
+        else {
+            continue;
+        };
+        objects.push(StoredObject {
+            key: key.replace(std::path::MAIN_SEPARATOR, "/").try_into()?,
+            size: metadata.len(),
+            last_modified: metadata.modified()?,
+        });
+    }
+    Ok(())
 }
 
 pub struct LocalDirUpload {
@@ -1257,6 +1350,7 @@ mod buffered_upload_tests {
 #[cfg(test)]
 mod local_storage_tests {
     use std::{
+        collections::BTreeSet,
         fs::File,
         io::Read,
         sync::Arc,
@@ -1424,4 +1518,25 @@ mod local_storage_tests {
         assert!(storage.get(&object_key).await?.is_none());
         Ok(())
     }
+
+    #[convex_macro::test_runtime]
+    async fn test_storage_list_objects(rt: TestRuntime) -> anyhow::Result<()> {
+        let storage: Arc<dyn Storage> = Arc::new(LocalDirStorage::new(rt)?);
+        let mut object_keys = BTreeSet::new();
+        for _ in 0..3 {
+            let mut test_upload = storage.start_upload().await?;
+            test_upload.write(vec![1, 2, 3].into()).await?;
+            object_keys.insert(test_upload.complete().await?);
+        }
+        let listed: Vec<_> = storage.list_objects().try_collect().await?;
+        assert!(listed.iter().all(|object| object.size == 3));
+        assert_eq!(
+            listed
+                .into_iter()
+                .map(|object| object.key)
+                .collect::<BTreeSet<_>>(),
+            object_keys
+        );
+        Ok(())
+    }
 }
@@ -1,10 +1,15 @@
 use std::{
     collections::BTreeMap,
     sync::Arc,
+    time::Duration,
 };
 
 use application::{
-    test_helpers::ApplicationTestExt,
+    application_function_runner::FunctionQuotaLimits,
+    test_helpers::{
+        ApplicationFixtureArgs,
+        ApplicationTestExt,
+    },
     Application,
 };
 use common::{
@@ -27,6 +32,7 @@ use common::{
     types::{
         FunctionCaller,
         MemberId,
+        NodeVersion,
         SessionId,
         SessionRequestSeqNumber,
         Timestamp,
@@ -86,7 +92,11 @@ struct SyncTest<RT: Runtime> {
 
 impl<RT: Runtime> SyncTest<RT> {
     async fn new(rt: RT) -> anyhow::Result<Self> {
-        let application = Application::new_for_tests(&rt).await?;
+        Self::new_with_args(rt, ApplicationFixtureArgs::default()).await
+    }
+
+    async fn new_with_args(rt: RT, args: ApplicationFixtureArgs) -> anyhow::Result<Self> {
+        let application = Application::new_for_tests_with_args(&rt, args).await?;
         let kb = application.key_broker().clone();
         let application_ = application.clone();
         // Populate UDFs.
@@ -94,7 +104,9 @@ impl<RT: Runtime> SyncTest<RT> {
             // Only analyze isolate modules, so sync tests don't require prod
             // runtime. Remove the filter if you need to test node modules.
             let modules = TEST_SOURCE_ISOLATE_ONLY.clone();
-            let source_package = application.upload_package(&modules, None).await?;
+            let source_package = application
+                .upload_package(&modules, None, NodeVersion::default())
+                .await?;
             let udf_config = UdfConfig::new_for_test(&rt, "1000.0.0".parse()?);
             let analyze_results = application
                 .analyze(
@@ -908,3 +920,80 @@ async fn test_max_observed_timestamp(rt: TestRuntime) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[convex_macro::test_runtime]
+async fn test_query_set_rate_limited(rt: TestRuntime) -> anyhow::Result<()> {
+    let test = SyncTest::new_with_args(
+        rt.clone(),
+        ApplicationFixtureArgs {
+            function_quota_limits: Some(FunctionQuotaLimits {
+                rate_per_function: 0,
+                rate_per_identity: 0,
+                rate_per_ip: 1,
+                max_concurrent_per_function: 0,
+                max_concurrent_per_identity: 0,
+            }),
+            ..Default::default()
+        },
+    )
+    .await?;
+    let config = SyncWorkerConfig {
+        client_address: Some("10.0.0.1".parse()?),
+        ..Default::default()
+    };
+    let mut sync_worker = test.new_worker_with_config(config, None)?;
+    let query = |i| -> anyhow::Result<Query> {
+        Ok(Query {
+            query_id: QueryId::new(i),
+            udf_path: "sync:succeed".parse()?,
+            args: vec![],
+            journal: None,
+            component_path: None,
+        })
+    };
+
+    sync_worker.send(ClientMessage::ModifyQuerySet {
+        base_version: 0,
+        new_version: 1,
+        modifications: vec![QuerySetModification::Add(query(0)?)],
+    })?;
+    must_let!(let ServerMessage::Transition { modifications, .. } = sync_worker.receive().await?);
+    assert_eq!(modifications.len(), 1);
+    must_let!(let StateModification::QueryUpdated { query_id, .. } = &modifications[0]);
+    assert_eq!(*query_id, QueryId::new(0));
+
+    // Adding another query in the same second is over the limit, which fails
+    // the query but not the connection.
+    sync_worker.send(ClientMessage::ModifyQuerySet {
+        base_version: 1,
+        new_version: 2,
+        modifications: vec![QuerySetModification::Add(query(1)?)],
+    })?;
+    must_let!(let ServerMessage::Transition {
+        end_version, modifications, ..
+    } = sync_worker.receive().await?);
+    assert_eq!(end_version.query_set, 2);
+    assert_eq!(modifications.len(), 1);
+    must_let!(let StateModification::QueryFailed {
+        query_id,
+        error_data: Some(ConvexValue::Object(data)),
+        ..
+    } = &modifications[0]);
+    assert_eq!(*query_id, QueryId::new(1));
+    assert_eq!(
+        data.get("code"),
+        Some(&ConvexValue::try_from("ClientRateLimited")?)
+    );
+    assert!(data.get("retryAfterMs").is_some());
+
+    // The query runs once the client may retry.
+    rt.advance_time(Duration::from_secs(1)).await;
+    must_let!(let ServerMessage::Transition { modifications, .. } = sync_worker.receive().await?);
+    assert_eq!(modifications.len(), 1);
+    must_let!(let StateModification::QueryUpdated { query_id, value, .. } = &modifications[0]);
+    assert_eq!(*query_id, QueryId::new(1));
+    assert_eq!(value, &ConvexValue::try_from("on my list")?);
+
+    sync_worker.shutdown().await?;
+    Ok(())
+}
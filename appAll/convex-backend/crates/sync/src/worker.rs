@@ -1,5 +1,6 @@
 use std::{
     collections::BTreeMap,
+    net::IpAddr,
     sync::{
         atomic::{
             AtomicUsize,
@@ -26,13 +27,19 @@ use application::{
     RedactedActionError,
     RedactedMutationError,
 };
+use async_trait::async_trait;
 use cmd_util::env::env_config;
 use common::{
+    assert_obj,
     components::{
         CanonicalizedComponentFunctionPath,
         ComponentPath,
         ExportPath,
     },
+    errors::{
+        JsError,
+        RetryAfter,
+    },
     fastrace_helpers::get_sampled_span,
     http::ResolvedHostname,
     knobs::SYNC_MAX_SEND_TRANSITION_COUNT,
@@ -74,6 +81,7 @@ use maplit::btreemap;
 use model::session_requests::types::SessionRequestIdentifier;
 use sync_types::{
     ClientMessage,
+    ErrorPayload,
     IdentityVersion,
     QueryId,
     QuerySetModification,
@@ -117,16 +125,62 @@ const SYNC_WORKER_PROCESS_TIMEOUT: Duration = Duration::from_secs(60);
 #[derive(Clone, Debug)]
 pub struct SyncWorkerConfig {
     pub client_version: ClientVersion,
+    /// The client's address, for per-IP rate limits.
+    pub client_address: Option<IpAddr>,
 }
 
 impl Default for SyncWorkerConfig {
     fn default() -> Self {
         Self {
             client_version: ClientVersion::unknown(),
+            client_address: None,
         }
     }
 }
 
+/// Calls rejected by the deployment's rate limits fail on their own, with the
+/// time to wait before retrying in the error's data, instead of closing the
+/// connection.
+fn rate_limited_error_payload(e: &anyhow::Error) -> Option<ErrorPayload<ConvexValue>> {
+    let RetryAfter(retry_after) = e.downcast_ref::<RetryAfter>()?;
+    let data = assert_obj!(
+        "code" => e.short_msg().to_string(),
+        "retryAfterMs" => retry_after.as_millis() as f64
+    );
+    Some(ErrorPayload::ErrorData {
+        message: e.msg().to_string(),
+        data: data.into(),
+    })
+}
+
+/// A query the client added while over its rate limit, which fails with the
+/// rate limit error until the client may retry.
+struct RateLimitedQuery {
+    error: JsError,
+    retry_at: tokio::time::Instant,
+}
+
+/// Stands in for a rate limited query's subscription. It's invalidated once
+/// the client may retry, which reruns the query.
+struct RetryAfterSubscription<RT: Runtime> {
+    rt: RT,
+    retry_at: tokio::time::Instant,
+}
+
+#[async_trait]
+impl<RT: Runtime> SubscriptionTrait for RetryAfterSubscription<RT> {
+    fn wait_for_invalidation(&self) -> BoxFuture<'static, anyhow::Result<()>> {
+        let remaining = self
+            .retry_at
+            .saturating_duration_since(self.rt.monotonic_now());
+        self.rt.wait(remaining).map(Ok).boxed()
+    }
+
+    async fn extend_validity(&self, _new_ts: Timestamp) -> anyhow::Result<bool> {
+        Ok(self.rt.monotonic_now() < self.retry_at)
+    }
+}
+
 /// Creates a channel which allows the sender to track the buffer size and
 /// opt-in to slow down if the buffer becomes too large.
 pub fn measurable_unbounded_channel() -> (SingleFlightSender, SingleFlightReceiver) {
@@ -235,6 +289,9 @@ pub struct SyncWorker<RT: Runtime> {
 
     transition_future: Option<Fuse<BoxFuture<'static, anyhow::Result<TransitionState>>>>,
 
+    // Queries added over the client's rate limit, to fail in the next transition.
+    rate_limited_queries: BTreeMap<QueryId, RateLimitedQuery>,
+
     // Has an update been scheduled for the future?
     update_scheduled: bool,
 
@@ -282,6 +339,7 @@ impl<RT: Runtime> SyncWorker<RT> {
             mutation_sender,
             action_futures: FuturesUnordered::new(),
             transition_future: None,
+            rate_limited_queries: BTreeMap::new(),
             update_scheduled: false,
             on_connect: Some((connect_timer(), on_connect)),
         }
@@ -464,6 +522,46 @@ impl<RT: Runtime> SyncWorker<RT> {
                 new_version,
                 modifications,
             } => {
+                let added = modifications
+                    .iter()
+                    .filter(|m| matches!(m, QuerySetModification::Add(_)))
+                    .count();
+                if let Some(client_address) = self.config.client_address
+                    && added > 0
+                    && let Err(e) = self
+                        .api
+                        .check_client_rate_limit(
+                            &self.host,
+                            client_address,
+                            added.try_into().unwrap_or(u32::MAX),
+                        )
+                        .await
+                {
+                    // Fail the added queries rather than the whole connection.
+                    let (
+                        Some(ErrorPayload::ErrorData { message, data }),
+                        Some(RetryAfter(retry_after)),
+                    ) = (
+                        rate_limited_error_payload(&e),
+                        e.downcast_ref::<RetryAfter>().copied(),
+                    )
+                    else {
+                        return Err(e);
+                    };
+                    let error = JsError::convex_error(message, data);
+                    let retry_at = self.rt.monotonic_now() + retry_after;
+                    for modification in &modifications {
+                        if let QuerySetModification::Add(query) = modification {
+                            self.rate_limited_queries.insert(
+                                query.query_id,
+                                RateLimitedQuery {
+                                    error: error.clone(),
+                                    retry_at,
+                                },
+                            );
+                        }
+                    }
+                }
                 self.state
                     .modify_query_set(base_version, new_version, modifications)?;
                 self.schedule_update();
@@ -491,6 +589,7 @@ impl<RT: Runtime> SyncWorker<RT> {
                     btreemap! {
                        "udf_type".into() => UdfType::Mutation.to_lowercase_string().into(),
                        "udf_path".into() => udf_path.clone().into(),
+                       "request_id".into() => server_request_id.to_string(),
                     },
                 );
                 let rt = self.rt.clone();
@@ -499,38 +598,59 @@ impl<RT: Runtime> SyncWorker<RT> {
                 let api = self.api.clone();
                 let host = self.host.clone();
                 let caller = FunctionCaller::SyncWorker(client_version);
+                let client_address = self.config.client_address;
 
                 let future = async move {
                     rt.with_timeout("mutation", SYNC_WORKER_PROCESS_TIMEOUT, async move {
                         timer.finish();
-                        let result = match component_path {
-                            None => {
-                                api.execute_public_mutation(
-                                    &host,
-                                    server_request_id,
-                                    identity,
-                                    ExportPath::from(udf_path.canonicalize()),
-                                    args,
-                                    caller,
-                                    mutation_identifier,
-                                )
-                                .in_span(root)
-                                .await?
-                            },
-                            Some(ref p) => {
-                                let path =
-                                    Self::parse_admin_component_path(p, &udf_path, &identity)?;
-                                api.execute_admin_mutation(
-                                    &host,
-                                    server_request_id,
-                                    identity,
-                                    path,
-                                    args,
-                                    caller,
-                                    mutation_identifier,
-                                )
-                                .in_span(root)
-                                .await?
+                        let result: anyhow::Result<_> = try {
+                            if let Some(client_address) = client_address {
+                                api.check_client_rate_limit(&host, client_address, 1)
+                                    .await?;
+                            }
+                            match component_path {
+                                None => {
+                                    api.execute_public_mutation(
+                                        &host,
+                                        server_request_id,
+                                        identity,
+                                        ExportPath::from(udf_path.canonicalize()),
+                                        args,
+                                        caller,
+                                        mutation_identifier,
+                                    )
+                                    .in_span(root)
+                                    .await?
+                                },
+                                Some(ref p) => {
+                                    let path =
+                                        Self::parse_admin_component_path(p, &udf_path, &identity)?;
+                                    api.execute_admin_mutation(
+                                        &host,
+                                        server_request_id,
+                                        identity,
+                                        path,
+                                        args,
+                                        caller,
+                                        mutation_identifier,
+                                    )
+                                    .in_span(root)
+                                    .await?
+                                },
+                            }
+                        };
+                        let result = match result {
+                            Ok(result) => result,
+                            Err(e) => match rate_limited_error_payload(&e) {
+                                Some(error) => {
+                                    return Ok(ServerMessage::MutationResponse {
+                                        request_id,
+                                        result: Err(error),
+                                        ts: None,
+                                        log_lines: RedactedLogLines::empty().into(),
+                                    })
+                                },
+                                None => return Err(e),
                             },
                         };
                         let response = match result {
@@ -591,35 +711,57 @@ impl<RT: Runtime> SyncWorker<RT> {
                     btreemap! {
                        "udf_type".into() => UdfType::Action.to_lowercase_string().into(),
                        "udf_path".into() => udf_path.clone().into(),
+                       "request_id".into() => server_request_id.to_string(),
                     },
                 );
+                let client_address = self.config.client_address;
                 let future = async move {
                     let caller = FunctionCaller::SyncWorker(client_version);
-                    let result = match component_path {
-                        None => {
-                            api.execute_public_action(
-                                &host,
-                                server_request_id,
-                                identity,
-                                ExportPath::from(udf_path.canonicalize()),
-                                args,
-                                caller,
-                            )
-                            .in_span(root)
-                            .await?
-                        },
-                        Some(ref p) => {
-                            let path = Self::parse_admin_component_path(p, &udf_path, &identity)?;
-                            api.execute_admin_action(
-                                &host,
-                                server_request_id,
-                                identity,
-                                path,
-                                args,
-                                caller,
-                            )
-                            .in_span(root)
-                            .await?
+                    let result: anyhow::Result<_> = try {
+                        if let Some(client_address) = client_address {
+                            api.check_client_rate_limit(&host, client_address, 1)
+                                .await?;
+                        }
+                        match component_path {
+                            None => {
+                                api.execute_public_action(
+                                    &host,
+                                    server_request_id,
+                                    identity,
+                                    ExportPath::from(udf_path.canonicalize()),
+                                    args,
+                                    caller,
+                                )
+                                .in_span(root)
+                                .await?
+                            },
+                            Some(ref p) => {
+                                let path =
+                                    Self::parse_admin_component_path(p, &udf_path, &identity)?;
+                                api.execute_admin_action(
+                                    &host,
+                                    server_request_id,
+                                    identity,
+                                    path,
+                                    args,
+                                    caller,
+                                )
+                                .in_span(root)
+                                .await?
+                            },
+                        }
+                    };
+                    let result = match result {
+                        Ok(result) => result,
+                        Err(e) => match rate_limited_error_payload(&e) {
+                            Some(error) => {
+                                return Ok(ServerMessage::ActionResponse {
+                                    request_id,
+                                    result: Err(error),
+                                    log_lines: RedactedLogLines::empty().into(),
+                                })
+                            },
+                            None => return Err(e),
                         },
                     };
                     let response = match result {
@@ -742,6 +884,7 @@ impl<RT: Runtime> SyncWorker<RT> {
                 },
                 QuerySetModification::Remove { query_id } => {
                     self.state.remove(query_id)?;
+                    self.rate_limited_queries.remove(&query_id);
                     state_modifications
                         .insert(query_id, StateModification::QueryRemoved { query_id });
                 },
@@ -755,6 +898,25 @@ impl<RT: Runtime> SyncWorker<RT> {
         // subscriptions are no longer current.
         let mut futures = vec![];
         for query in self.state.need_fetch() {
+            if let Some(RateLimitedQuery { error, retry_at }) =
+                self.rate_limited_queries.remove(&query.query_id)
+            {
+                let result = QueryResult::Rerun {
+                    result: Err(RedactedJsError::from_js_error(
+                        error,
+                        false,
+                        RequestId::new(),
+                    )),
+                    log_lines: RedactedLogLines::empty(),
+                    journal: query.journal.flatten(),
+                };
+                let subscription: Box<dyn SubscriptionTrait> = Box::new(RetryAfterSubscription {
+                    rt: self.rt.clone(),
+                    retry_at,
+                });
+                futures.push(future::ready(Ok((query.query_id, result, subscription))).boxed());
+                continue;
+            }
             let api = self.api.clone();
             let host = self.host.clone();
             let identity_ = identity.clone();
@@ -833,7 +995,7 @@ impl<RT: Runtime> SyncWorker<RT> {
                 Ok::<_, anyhow::Error>((query.query_id, query_result, subscription))
             }
             .in_span(span);
-            futures.push(future);
+            futures.push(future.boxed());
         }
         Ok(async move {
             let mut udf_results = vec![];
@@ -1,4 +1,5 @@
 use core::fmt;
+use std::net::IpAddr;
 
 use bytes::Bytes;
 use common::{
@@ -32,12 +33,16 @@ pub const HTTP_ACTION_BODY_LIMIT: usize = 20 << 20;
 pub struct HttpActionRequest {
     pub head: HttpActionRequestHead,
     pub body: Option<BoxStream<'static, anyhow::Result<bytes::Bytes>>>,
+    /// Address of the client that made the request, if known. Used for
+    /// rate limiting.
+    pub client_address: Option<IpAddr>,
 }
 
 impl fmt::Debug for HttpActionRequest {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("HttpActionRequest")
             .field("head", &self.head)
+            .field("client_address", &self.client_address)
             .finish()
     }
 }
@@ -124,7 +129,8 @@ impl proptest::arbitrary::Arbitrary for HttpActionRequest {
                         method: method.to_string().parse()?,
                         url,
                     },
-                    body: body.map(|body| stream::once(async move { Ok(body.into())}).boxed())
+                    body: body.map(|body| stream::once(async move { Ok(body.into())}).boxed()),
+                    client_address: None,
 
                 })
             }
@@ -287,6 +287,7 @@ async function startComponentsPushAndCodegen(
     appDefinition,
     componentDefinitions,
     nodeDependencies: appImplementation.externalNodeDependencies,
+    nodeVersion: projectConfig.node.nodeVersion,
   };
   if (options.writePushRequest) {
     const pushRequestPath = path.resolve(options.writePushRequest);
@@ -58,6 +58,8 @@ export interface ProjectConfig {
   functions: string;
   node: {
     externalPackages: string[];
+    // Major version of Node.js to run "use node" actions with, e.g. "20".
+    nodeVersion?: string;
   };
   generateCommonJSApi: boolean;
   // deprecated
@@ -134,6 +136,19 @@ export async function parseProjectConfig(
         "Expected `node.externalPackages` in `convex.json` to be an array of strings",
     });
   }
+  if (typeof obj.node.nodeVersion === "number") {
+    obj.node.nodeVersion = obj.node.nodeVersion.toString();
+  } else if (
+    typeof obj.node.nodeVersion !== "undefined" &&
+    typeof obj.node.nodeVersion !== "string"
+  ) {
+    return await ctx.crash({
+      exitCode: 1,
+      errorType: "invalid filesystem data",
+      printedMessage:
+        "Expected `node.nodeVersion` in `convex.json` to be a major version like \"20\"",
+    });
+  }
   if (typeof obj.generateCommonJSApi === "undefined") {
     obj.generateCommonJSApi = false;
   } else if (typeof obj.generateCommonJSApi !== "boolean") {
@@ -679,6 +694,7 @@ export function configJSON(
     teamSlug: config.projectConfig.team,
     functions: config.projectConfig.functions,
     authInfo: config.projectConfig.authInfo,
+    nodeVersion: config.projectConfig.node.nodeVersion,
   };
   return {
     config: projectConfig,
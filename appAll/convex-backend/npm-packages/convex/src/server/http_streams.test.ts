@@ -0,0 +1,44 @@
+import { expect, test } from "vitest";
+import { anyApi } from "./api.js";
+import {
+  querySubscriptionResponse,
+  webSocketResponse,
+} from "./http_streams.js";
+
+test("querySubscriptionResponse", async () => {
+  const response = querySubscriptionResponse(
+    anyApi.messages.list,
+    { channel: "general", limit: 10n },
+    { headers: { "Access-Control-Allow-Origin": "*" } },
+  );
+  expect(response.headers.get("Convex-Stream")).toEqual("1");
+  expect(response.headers.get("Access-Control-Allow-Origin")).toEqual("*");
+  expect(await response.json()).toEqual({
+    kind: "querySubscription",
+    query: {
+      path: "messages:list",
+      args: { channel: "general", limit: { $integer: "CgAAAAAAAAA=" } },
+    },
+    format: null,
+  });
+});
+
+test("webSocketResponse", async () => {
+  const response = webSocketResponse({
+    onMessage: anyApi.devices.report,
+    subscribe: { query: anyApi.devices.settings, args: { deviceId: "a" } },
+    format: "convex_encoded_json",
+  });
+  expect(response.headers.get("Convex-Stream")).toEqual("1");
+  expect(await response.json()).toEqual({
+    kind: "webSocket",
+    onMessage: "devices:report",
+    subscribe: { path: "devices:settings", args: { deviceId: "a" } },
+    format: "convex_encoded_json",
+  });
+
+  const withoutSubscription = webSocketResponse({
+    onMessage: anyApi.devices.report,
+  });
+  expect((await withoutSubscription.json()).subscribe).toBeNull();
+});
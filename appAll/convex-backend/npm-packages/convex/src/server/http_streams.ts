@@ -0,0 +1,186 @@
+import { convexToJson } from "../values/index.js";
+import { FunctionArgs, FunctionReference, getFunctionName } from "./api.js";
+
+// Marks a response as a description of a stream for the backend to serve.
+const CONVEX_STREAM_HEADER = "Convex-Stream";
+
+/**
+ * How values are encoded in a stream.
+ *
+ * `"json"` is plain JSON, which is easiest for clients that don't use Convex.
+ * `"convex_encoded_json"` keeps the types plain JSON can't represent, like
+ * `Int64` and `ArrayBuffer`, in the same encoding as the HTTP API.
+ *
+ * @public
+ */
+export type StreamValueFormat = "json" | "convex_encoded_json";
+
+/**
+ * Options for {@link querySubscriptionResponse}.
+ *
+ * @public
+ */
+export type QuerySubscriptionResponseOptions = {
+  /**
+   * Headers to add to the response, e.g. for CORS.
+   */
+  headers?: HeadersInit;
+  /**
+   * How to encode query results. Defaults to `"json"`.
+   */
+  format?: StreamValueFormat;
+};
+
+/**
+ * Respond to an HTTP request with a stream of
+ * [Server-Sent Events](https://developer.mozilla.org/en-US/docs/Web/API/Server-sent_events)
+ * carrying the result of a public query, sent again whenever it changes.
+ *
+ * Each result is an `update` event whose data is the query's return value.
+ * If the query throws, a `queryError` event is sent with data
+ * `{ message, data }`, and the stream carries on with the next result.
+ *
+ * The query runs as the identity of the HTTP request. The HTTP action
+ * returns immediately and Convex keeps the stream open until the client
+ * disconnects, or until the request's auth token expires. Then a
+ * `tokenExpired` event is sent and the stream ends, and the client should
+ * reconnect with a fresh token.
+ *
+ * ```js
+ * http.route({
+ *   path: "/messages",
+ *   method: "GET",
+ *   handler: httpAction(async (ctx, request) => {
+ *     const channel = new URL(request.url).searchParams.get("channel")!;
+ *     return querySubscriptionResponse(api.messages.list, { channel });
+ *   }),
+ * });
+ * ```
+ *
+ * @param query - A {@link FunctionReference} for the public query to run.
+ * @param args - The arguments to the query.
+ * @param options - See {@link QuerySubscriptionResponseOptions}.
+ * @returns A `Response` to return from an HTTP action.
+ *
+ * @public
+ */
+export function querySubscriptionResponse<
+  Query extends FunctionReference<"query">,
+>(
+  query: Query,
+  args: FunctionArgs<Query>,
+  options?: QuerySubscriptionResponseOptions,
+): Response {
+  return streamResponse(
+    {
+      kind: "querySubscription",
+      query: querySpec(query, args),
+      format: options?.format ?? null,
+    },
+    options?.headers,
+  );
+}
+
+/**
+ * Options for {@link webSocketResponse}.
+ *
+ * @public
+ */
+export type WebSocketResponseOptions<
+  Query extends FunctionReference<"query"> = FunctionReference<"query">,
+> = {
+  /**
+   * A public mutation or action to run for each message the client sends.
+   * It's called with `{ message }`, where `message` is a string for text
+   * messages and an `ArrayBuffer` for binary ones. Its return value is sent
+   * back as `{ type: "result", value }`, or `{ type: "error", message, data }`
+   * if it throws.
+   */
+  onMessage?: FunctionReference<
+    "mutation" | "action",
+    "public",
+    { message: string | ArrayBuffer }
+  >;
+  /**
+   * A public query whose results are sent as `{ type: "update", value }`
+   * whenever they change, or `{ type: "queryError", message, data }` if it
+   * throws.
+   */
+  subscribe?: {
+    query: Query;
+    args: FunctionArgs<Query>;
+  };
+  /**
+   * Headers to add to the upgrade response.
+   */
+  headers?: HeadersInit;
+  /**
+   * How to encode values sent to the client. Defaults to `"json"`.
+   */
+  format?: StreamValueFormat;
+};
+
+/**
+ * Accept a WebSocket connection on an HTTP route.
+ *
+ * Messages the client sends are passed to `onMessage`, and the results of
+ * the `subscribe` query are pushed to the client as they change. Everything
+ * sent to the client is a JSON text message with a `type` field. Functions
+ * run as the identity of the upgrade request. When its auth token expires,
+ * the connection is closed with code 1008, and the client should reconnect
+ * with a fresh token.
+ *
+ * Requests that aren't WebSocket upgrades get a 400 response.
+ *
+ * ```js
+ * http.route({
+ *   path: "/device",
+ *   method: "GET",
+ *   handler: httpAction(async (ctx, request) => {
+ *     const deviceId = new URL(request.url).searchParams.get("id")!;
+ *     return webSocketResponse({
+ *       onMessage: api.devices.report,
+ *       subscribe: { query: api.devices.settings, args: { deviceId } },
+ *     });
+ *   }),
+ * });
+ * ```
+ *
+ * @param options - See {@link WebSocketResponseOptions}.
+ * @returns A `Response` to return from an HTTP action.
+ *
+ * @public
+ */
+export function webSocketResponse<Query extends FunctionReference<"query">>(
+  options: WebSocketResponseOptions<Query>,
+): Response {
+  return streamResponse(
+    {
+      kind: "webSocket",
+      onMessage:
+        options.onMessage !== undefined
+          ? getFunctionName(options.onMessage)
+          : null,
+      subscribe:
+        options.subscribe !== undefined
+          ? querySpec(options.subscribe.query, options.subscribe.args)
+          : null,
+      format: options.format ?? null,
+    },
+    options.headers,
+  );
+}
+
+function querySpec(query: FunctionReference<"query">, args: any) {
+  return {
+    path: getFunctionName(query),
+    args: convexToJson(args ?? {}),
+  };
+}
+
+function streamResponse(spec: Record<string, any>, headersInit?: HeadersInit) {
+  const headers = new Headers(headersInit);
+  headers.set(CONVEX_STREAM_HEADER, "1");
+  headers.set("Content-Type", "application/json");
+  return new Response(JSON.stringify(spec), { status: 200, headers });
+}
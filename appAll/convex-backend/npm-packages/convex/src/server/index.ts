@@ -141,11 +141,20 @@ export type {
 } from "./system_fields.js";
 export { httpRouter, HttpRouter, ROUTABLE_HTTP_METHODS } from "./router.js";
 export type {
+  HttpCorsOptions,
+  HttpRateLimitOptions,
   RoutableMethod,
+  RouteOptions,
   RouteSpec,
   RouteSpecWithPath,
   RouteSpecWithPathPrefix,
 } from "./router.js";
+export { querySubscriptionResponse, webSocketResponse } from "./http_streams.js";
+export type {
+  QuerySubscriptionResponseOptions,
+  StreamValueFormat,
+  WebSocketResponseOptions,
+} from "./http_streams.js";
 export {
   anyApi,
   getFunctionName,
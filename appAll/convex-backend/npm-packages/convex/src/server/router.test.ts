@@ -133,3 +133,52 @@ test("HttpRouter pathPrefix", () => {
   // Not shadowed: last path segment is different
   http.route({ pathPrefix: "/path11/", method: "GET", handler: action1 });
 });
+
+test("HttpRouter route options", () => {
+  const http = httpRouter();
+
+  http.route({
+    path: "/api",
+    method: "POST",
+    handler: action1,
+    cors: { allowedOrigins: ["https://example.com"], maxAge: 600 },
+    rateLimit: { requests: 10, period: 60_000 },
+  });
+  http.route({ pathPrefix: "/files/", method: "GET", handler: action2 });
+
+  expect(http.getRoutes()).toEqual([
+    [
+      "/api",
+      "POST",
+      action1,
+      JSON.stringify({
+        cors: {
+          allowedOrigins: ["https://example.com"],
+          allowedHeaders: null,
+          exposedHeaders: null,
+          allowCredentials: null,
+          maxAgeSecs: 600,
+        },
+        rateLimit: { by: "ip", requests: 10, periodMs: 60_000 },
+      }),
+    ],
+    ["/files/*", "GET", action2],
+  ]);
+
+  expect(() =>
+    http.route({
+      path: "/credentials",
+      method: "GET",
+      handler: action1,
+      cors: { allowCredentials: true },
+    }),
+  ).toThrow();
+  expect(() =>
+    http.route({
+      path: "/limit",
+      method: "GET",
+      handler: action1,
+      rateLimit: { requests: 0, period: 1000 },
+    }),
+  ).toThrow();
+});
@@ -46,6 +46,82 @@ export function normalizeMethod(
  */
 export const httpRouter = () => new HttpRouter();
 
+/**
+ * Cross-Origin Resource Sharing (CORS) settings for a route.
+ *
+ * Convex answers the route's preflight `OPTIONS` requests and adds CORS
+ * headers to its responses, so the HTTP action doesn't need to.
+ *
+ * @public
+ */
+export type HttpCorsOptions = {
+  /**
+   * Origins allowed to read responses, like `"https://example.com"`. Any
+   * origin is allowed if this is omitted.
+   */
+  allowedOrigins?: string[];
+  /**
+   * Request headers allowed in requests. Defaults to whichever headers the
+   * browser asks for.
+   */
+  allowedHeaders?: string[];
+  /**
+   * Response headers that scripts in the browser can read.
+   */
+  exposedHeaders?: string[];
+  /**
+   * Whether to allow requests with credentials like cookies. Requires
+   * `allowedOrigins`.
+   */
+  allowCredentials?: boolean;
+  /**
+   * How long browsers can cache a preflight response, in seconds.
+   */
+  maxAge?: number;
+};
+
+/**
+ * A rate limit for a route, enforced by Convex before running its HTTP
+ * action. Requests over the limit get a `429 Too Many Requests` response
+ * with a `Retry-After` header.
+ *
+ * @public
+ */
+export type HttpRateLimitOptions = {
+  /**
+   * How many requests each client can make per `period`, which is also the
+   * largest burst allowed.
+   */
+  requests: number;
+  /**
+   * The period in milliseconds.
+   */
+  period: number;
+  /**
+   * How to tell clients apart: by IP address, or by authenticated user,
+   * falling back to IP address for unauthenticated requests. Defaults to
+   * `"ip"`.
+   */
+  by?: "ip" | "identity";
+};
+
+/**
+ * Options shared by every kind of {@link RouteSpec}.
+ *
+ * @public
+ */
+export type RouteOptions = {
+  /**
+   * Handle CORS for this route. See {@link HttpCorsOptions}.
+   */
+  cors?: HttpCorsOptions;
+  /**
+   * Limit how often each client can call this route. See
+   * {@link HttpRateLimitOptions}.
+   */
+  rateLimit?: HttpRateLimitOptions;
+};
+
 /**
  * A type representing a route to an HTTP action using an exact request URL path match.
  *
@@ -66,7 +142,7 @@ export type RouteSpecWithPath = {
    * The HTTP action to execute.
    */
   handler: PublicHttpAction;
-};
+} & RouteOptions;
 
 /**
  * A type representing a route to an HTTP action using a request URL path prefix match.
@@ -89,7 +165,7 @@ export type RouteSpecWithPathPrefix = {
    * The HTTP action to execute.
    */
   handler: PublicHttpAction;
-};
+} & RouteOptions;
 
 /**
  * A type representing a route to an HTTP action.
@@ -143,6 +219,9 @@ export class HttpRouter {
   exactRoutes: Map<string, Map<RoutableMethod, PublicHttpAction>> = new Map();
   prefixRoutes: Map<RoutableMethod, Map<string, PublicHttpAction>> = new Map();
   isRouter: true = true;
+  // Serialized `RouteOptions` for the routes that have any, keyed by method
+  // and routed path.
+  private routeOptions: Map<string, string> = new Map();
 
   /**
    * Specify an HttpAction to be used to respond to requests
@@ -186,6 +265,7 @@ export class HttpRouter {
           `Path '${spec.path}' for method ${method} already in use`,
         );
       }
+      this.setRouteOptions(method, spec.path, spec);
       methods.set(method, handler);
       this.exactRoutes.set(spec.path, methods);
     } else if ("pathPrefix" in spec) {
@@ -204,6 +284,7 @@ export class HttpRouter {
           `${spec.method} pathPrefix ${spec.pathPrefix} is already defined`,
         );
       }
+      this.setRouteOptions(method, `${spec.pathPrefix}*`, spec);
       prefixes.set(spec.pathPrefix, handler);
       this.prefixRoutes.set(method, prefixes);
     } else {
@@ -213,23 +294,93 @@ export class HttpRouter {
     }
   };
 
+  private setRouteOptions(
+    method: RoutableMethod,
+    routedPath: string,
+    options: RouteOptions,
+  ) {
+    const { cors, rateLimit } = options;
+    if (cors === undefined && rateLimit === undefined) {
+      return;
+    }
+    if (cors?.allowCredentials && cors.allowedOrigins === undefined) {
+      throw new Error(
+        `${method} ${routedPath}: cors.allowCredentials requires cors.allowedOrigins`,
+      );
+    }
+    if (
+      rateLimit !== undefined &&
+      !(
+        Number.isInteger(rateLimit.requests) &&
+        rateLimit.requests > 0 &&
+        Number.isInteger(rateLimit.period) &&
+        rateLimit.period > 0
+      )
+    ) {
+      throw new Error(
+        `${method} ${routedPath}: rateLimit.requests and rateLimit.period must be positive integers`,
+      );
+    }
+    // This is the shape the backend expects when analyzing `http.js`.
+    const serialized = {
+      cors:
+        cors === undefined
+          ? null
+          : {
+              allowedOrigins: cors.allowedOrigins ?? null,
+              allowedHeaders: cors.allowedHeaders ?? null,
+              exposedHeaders: cors.exposedHeaders ?? null,
+              allowCredentials: cors.allowCredentials ?? null,
+              maxAgeSecs: cors.maxAge ?? null,
+            },
+      rateLimit:
+        rateLimit === undefined
+          ? null
+          : {
+              by: rateLimit.by ?? "ip",
+              requests: rateLimit.requests,
+              periodMs: rateLimit.period,
+            },
+    };
+    this.routeOptions.set(
+      `${method} ${routedPath}`,
+      JSON.stringify(serialized),
+    );
+  }
+
+  private routeEntry(
+    path: string,
+    method: RoutableMethod,
+    handler: PublicHttpAction,
+  ) {
+    const options = this.routeOptions.get(`${method} ${path}`);
+    return options === undefined
+      ? ([path, method, handler] as const)
+      : ([path, method, handler, options] as const);
+  }
+
   /**
    * Returns a list of routed HTTP actions.
    *
    * These are used to populate the list of routes shown in the Functions page of the Convex dashboard.
    *
-   * @returns - an array of [path, method, endpoint] tuples.
+   * @returns - an array of [path, method, endpoint] tuples, followed by the
+   * route's options as JSON for routes with a `cors` or `rateLimit`.
    */
   getRoutes = (): Array<
-    Readonly<[string, RoutableMethod, PublicHttpAction]>
+    | Readonly<[string, RoutableMethod, PublicHttpAction]>
+    | Readonly<[string, RoutableMethod, PublicHttpAction, string]>
   > => {
     const exactPaths: string[] = [...this.exactRoutes.keys()].sort();
     const exact = exactPaths.flatMap((path) =>
       [...this.exactRoutes.get(path)!.keys()]
         .sort()
-        .map(
-          (method) =>
-            [path, method, this.exactRoutes.get(path)!.get(method)!] as const,
+        .map((method) =>
+          this.routeEntry(
+            path,
+            method,
+            this.exactRoutes.get(path)!.get(method)!,
+          ),
         ),
     );
 
@@ -237,13 +388,12 @@ export class HttpRouter {
     const prefixes = prefixPathMethods.flatMap((method) =>
       [...this.prefixRoutes.get(method)!.keys()]
         .sort()
-        .map(
-          (pathPrefix) =>
-            [
-              `${pathPrefix}*`,
-              method,
-              this.prefixRoutes.get(method)!.get(pathPrefix)!,
-            ] as const,
+        .map((pathPrefix) =>
+          this.routeEntry(
+            `${pathPrefix}*`,
+            method,
+            this.prefixRoutes.get(method)!.get(pathPrefix)!,
+          ),
         ),
     );
 
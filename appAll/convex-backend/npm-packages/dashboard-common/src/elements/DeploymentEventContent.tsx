@@ -49,6 +49,7 @@ export function DeploymentEventContent({
       break;
 
     case "push_config_with_components":
+    case "rollback_push":
       body = (
         <>
           {event.metadata.component_diffs.map(
@@ -178,6 +179,9 @@ export function ActionText({ event }: { event: DeploymentAuditLogEvent }) {
     case "push_config_with_components":
       return <span>deployed functions</span>;
 
+    case "rollback_push":
+      return <span>rolled back to an earlier deploy</span>;
+
     case "change_deployment_state":
       switch (event.metadata.new_state) {
         case "paused":
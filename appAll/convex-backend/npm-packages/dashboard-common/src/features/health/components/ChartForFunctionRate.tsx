@@ -31,7 +31,7 @@ export function ChartForFunctionRate({
   const [shown, setShown] = useState<string | null>(null);
   const [startDate] = useState(new Date(Date.now() - 3600 * 1000));
   const auditLogs = useDeploymentAuditLogs(startDate.getTime(), {
-    actions: ["push_config", "push_config_with_components"],
+    actions: ["push_config", "push_config_with_components", "rollback_push"],
   });
   const deploysByMinute = auditLogs?.map(
     (log) => {
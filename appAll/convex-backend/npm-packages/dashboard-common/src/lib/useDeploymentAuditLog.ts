@@ -45,6 +45,7 @@ function processDeploymentAuditLogEvent(
     case "delete_canonical_url":
     case "push_config":
     case "push_config_with_components":
+    case "rollback_push":
     case "change_deployment_state":
     case "build_indexes":
     case "clear_tables":
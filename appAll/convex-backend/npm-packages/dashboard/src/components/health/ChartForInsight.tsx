@@ -64,7 +64,7 @@ function InsightsLineChart<T extends Record<string, any>>({
 }) {
   const { from } = useInsightsPeriod();
   const auditLogs = useDeploymentAuditLogs(toNumericUTC(from), {
-    actions: ["push_config", "push_config_with_components"],
+    actions: ["push_config", "push_config_with_components", "rollback_push"],
   });
   const deploysByHour = auditLogs?.map(
     (log) => {
@@ -7,6 +7,7 @@ import fetch from "node-fetch"; // TODO(rakeeb): use native fetch instead of nod
 import path from "node:path";
 import os from "node:os";
 import { logDurationMs } from "./log";
+import { getEgressProxyUrl } from "./sandbox";
 import { Hash } from "crypto";
 
 export type BuildDepsRequest = {
@@ -106,6 +107,13 @@ async function buildDepsInner(
   // Ex. Sharp, the image-processing library, relies on using process.env.npm_config_cache
   // as a build cache directory for libvips, but will not work with process.env.NPM_CONFIG_CACHE.
   process.env.npm_config_cache = `${dir}/.npm`;
+  // Without a network of its own, npm has to reach the registry through the
+  // egress proxy.
+  const egressProxyUrl = getEgressProxyUrl();
+  if (egressProxyUrl !== null) {
+    process.env.npm_config_proxy = egressProxyUrl;
+    process.env.npm_config_https_proxy = egressProxyUrl;
+  }
 
   // Create package.json with the dependencies as entries
   const deps_json = Object.fromEntries(deps.map((v) => [v.package, v.version]));
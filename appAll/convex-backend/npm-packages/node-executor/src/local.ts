@@ -2,30 +2,41 @@ import { Command } from "commander";
 import { invoke } from "./executor";
 import { v4 as uuidv4 } from "uuid";
 import { log, setDebugLogging } from "./log";
+import { useEgressProxy } from "./sandbox";
 import os from "node:os";
 import crypto from "crypto";
 import fs from "node:fs";
+import readline from "node:readline";
 import { Writable } from "node:stream";
 
-async function main(request_str: string, debug: boolean) {
-  let request;
-  setDebugLogging(debug);
+function parseJson(json: string, what: string) {
   try {
-    request = JSON.parse(request_str);
+    return JSON.parse(json);
   } catch (err: any) {
     throw new Error(
-      `Failed to parse request json. Error: ${err.message.toString()}`,
+      `Failed to parse ${what} json. Error: ${err.message.toString()}`,
     );
   }
+}
+
+function parseRequest(request_str: string) {
+  const request = parseJson(request_str, "request");
   request.requestId = uuidv4();
+  return request;
+}
 
-  // Monkey-patch os.tmpdir to avoid filesystem write races
+// Monkey-patch os.tmpdir to avoid filesystem write races with other
+// executor processes.
+function useFreshTempdir() {
   const prevTempdir = os.tmpdir();
   const seed = crypto.randomBytes(20).toString("hex");
   const tempdir = `${prevTempdir}/${seed}`;
   fs.mkdirSync(tempdir);
   os.tmpdir = () => tempdir;
+  return tempdir;
+}
 
+async function invokeAndLog(request: any) {
   const responseStream = new Writable({
     write: (chunk, _encoding, callback) => {
       log(chunk.toString());
@@ -34,20 +45,137 @@ async function main(request_str: string, debug: boolean) {
   });
   await invoke(request, responseStream);
   responseStream.end();
+}
+
+async function main(request_str: string, debug: boolean) {
+  setDebugLogging(debug);
+  const request = parseRequest(request_str);
+  const tempdir = useFreshTempdir();
+  await invokeAndLog(request);
 
   fs.rmSync(tempdir, { recursive: true });
   // Don't wait for dangling promises. This matches AWS Lambda behavior.
   process.exit(0);
 }
 
+// The worker's state before it ran any user code.
+type WorkerBaseline = {
+  globals: Map<string | symbol, PropertyDescriptor>;
+  resources: Map<string, number>;
+};
+
+// Count the timers, sockets, servers, etc. keeping the process alive, by
+// type. Unref'd handles, like idle keep-alive sockets, aren't included.
+function countActiveResources() {
+  const counts = new Map<string, number>();
+  for (const resource of process.getActiveResourcesInfo()) {
+    counts.set(resource, (counts.get(resource) ?? 0) + 1);
+  }
+  return counts;
+}
+
+function snapshotWorkerState(): WorkerBaseline {
+  const globals = new Map<string | symbol, PropertyDescriptor>();
+  for (const key of Reflect.ownKeys(globalThis)) {
+    globals.set(key, Object.getOwnPropertyDescriptor(globalThis, key)!);
+  }
+  return { globals, resources: countActiveResources() };
+}
+
+// Undo the changes a request made to `globalThis`. Returns `false` if that
+// failed, or if the request left handles behind that would keep running
+// during later requests, in which case the worker must be recycled.
+async function resetWorkerState(baseline: WorkerBaseline) {
+  // Give callbacks that are already due a chance to run first.
+  await new Promise((resolve) => setImmediate(resolve));
+  let clean = true;
+  for (const key of Reflect.ownKeys(globalThis)) {
+    const current = Object.getOwnPropertyDescriptor(globalThis, key)!;
+    // Node defines some internals lazily as non-configurable globals, like
+    // undici's dispatcher on the first `fetch`. Nothing can remove those.
+    if (!baseline.globals.has(key) && current.configurable) {
+      clean = Reflect.deleteProperty(globalThis, key) && clean;
+    }
+  }
+  for (const [key, descriptor] of baseline.globals) {
+    const current = Object.getOwnPropertyDescriptor(globalThis, key);
+    if (
+      !Object.is(current?.value, descriptor.value) ||
+      current?.get !== descriptor.get ||
+      current?.set !== descriptor.set
+    ) {
+      clean = Reflect.defineProperty(globalThis, key, descriptor) && clean;
+    }
+  }
+  for (const [resource, count] of countActiveResources()) {
+    if (count > (baseline.resources.get(resource) ?? 0)) {
+      clean = false;
+    }
+  }
+  return clean;
+}
+
+// Serve requests from stdin, one JSON `{ nonce, request }` per line, until
+// stdin is closed. Responses are written to stdout in the same format as
+// `main`, followed by a `WorkerDone` line with the request's nonce so the
+// backend knows this process is ready for the next request. User code can
+// write to stdout too, but can't forge that line without the nonce. Like a
+// warm AWS Lambda, source packages stay cached in the temp directory between
+// requests, but changes to globals are undone.
+async function worker(debug: boolean) {
+  setDebugLogging(debug);
+  const tempdir = useFreshTempdir();
+  const lines = readline.createInterface({
+    input: process.stdin,
+    crlfDelay: Infinity,
+  });
+  let baseline: WorkerBaseline | null = null;
+  for await (const line of lines) {
+    if (line.trim() === "") {
+      continue;
+    }
+    const { nonce, request } = parseJson(line, "worker request");
+    request.requestId = uuidv4();
+    // Taken once stdin is being read, so its handle counts as the baseline.
+    baseline ??= snapshotWorkerState();
+    await invokeAndLog(request);
+    const clean = await resetWorkerState(baseline);
+    log(
+      JSON.stringify({
+        kind: "WorkerDone",
+        nonce,
+        rssBytes: process.memoryUsage().rss,
+        clean,
+      }),
+    );
+  }
+
+  fs.rmSync(tempdir, { recursive: true, force: true });
+  process.exit(0);
+}
+
 const program = new Command();
 program
   .name("node-executor")
   .description("node-executor executes a actions locally")
   .usage("command url [options]")
   .option("--debug", "print debug output", false)
-  .requiredOption("--request <json>", "json request serialized as string")
+  .option("--request <json>", "json request serialized as string")
+  .option("--worker", "serve newline-delimited json requests from stdin")
+  .option(
+    "--egress-proxy <socket>",
+    "unix socket of the proxy outbound connections must go through",
+  )
   .action(async (options) => {
-    await main(options.request, options.debug);
+    if (options.egressProxy !== undefined) {
+      await useEgressProxy(options.egressProxy);
+    }
+    if (options.worker) {
+      await worker(options.debug);
+    } else if (options.request !== undefined) {
+      await main(options.request, options.debug);
+    } else {
+      program.error("One of --request or --worker is required");
+    }
   });
 program.parseAsync(process.argv);
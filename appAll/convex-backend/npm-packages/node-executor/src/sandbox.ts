@@ -0,0 +1,85 @@
+import http from "node:http";
+import https from "node:https";
+import net from "node:net";
+import tls from "node:tls";
+import { ProxyAgent, setGlobalDispatcher } from "undici";
+
+// URL of the loopback forwarder to the backend's egress proxy, if outbound
+// connections go through one.
+let egressProxyUrl: string | null = null;
+
+export function getEgressProxyUrl() {
+  return egressProxyUrl;
+}
+
+function networkAccessDenied(host: string) {
+  const error = new Error(
+    `Outbound network access to ${host} is not allowed by this deployment`,
+  );
+  error.name = "NetworkAccessDenied";
+  return error;
+}
+
+// An agent for `http` or `https` that opens each connection through a CONNECT
+// tunnel to the egress proxy.
+function createTunnelAgent(proxyPort: number, secure: boolean) {
+  const agent: any = secure ? new https.Agent() : new http.Agent();
+  agent.createConnection = (
+    options: any,
+    callback: (err: Error | null, socket?: net.Socket) => void,
+  ) => {
+    const authority = `${options.host}:${options.port}`;
+    const request = http.request({
+      host: "127.0.0.1",
+      port: proxyPort,
+      method: "CONNECT",
+      path: authority,
+      headers: { host: authority },
+      agent: false,
+    });
+    request.once("connect", (response, socket) => {
+      if (response.statusCode !== 200) {
+        socket.destroy();
+        callback(
+          response.statusCode === 403
+            ? networkAccessDenied(options.host)
+            : new Error(`Failed to connect to ${authority}`),
+        );
+        return;
+      }
+      callback(null, secure ? tls.connect({ ...options, socket }) : socket);
+    });
+    request.once("error", (err) => callback(err));
+    request.end();
+    return undefined;
+  };
+  return agent;
+}
+
+// Route outbound connections through the backend's egress proxy at
+// `socketPath`. The worker runs in its own network namespace, so this is its
+// only way out, and the proxy decides which hosts it may reach. A loopback
+// port forwards to the proxy so `fetch`, `http`, `https` and `npm` can all
+// use it as a regular HTTP proxy.
+export async function useEgressProxy(socketPath: string) {
+  const forwarder = net.createServer((client) => {
+    const upstream = net.connect(socketPath);
+    // The forwarder's own sockets mustn't count as handles left behind by a
+    // request.
+    client.unref();
+    upstream.unref();
+    client.on("error", () => upstream.destroy());
+    upstream.on("error", () => client.destroy());
+    client.pipe(upstream).pipe(client);
+  });
+  await new Promise<void>((resolve, reject) => {
+    forwarder.once("error", reject);
+    forwarder.listen(0, "127.0.0.1", resolve);
+  });
+  forwarder.unref();
+  const { port } = forwarder.address() as net.AddressInfo;
+  egressProxyUrl = `http://127.0.0.1:${port}`;
+  setGlobalDispatcher(new ProxyAgent(egressProxyUrl));
+  http.globalAgent = createTunnelAgent(port, false);
+  https.globalAgent = createTunnelAgent(port, true);
+}
@@ -171,6 +171,21 @@ export const pushConfigWithComponents = v.object({
   }),
 });
 
+export const rollbackPush = v.object({
+  action: v.literal("rollback_push"),
+  member_id: v.int64(),
+  metadata: v.object({
+    push_id: v.string(),
+    auth_diff: v.optional(authDiff),
+    component_diffs: v.array(
+      v.object({
+        component_path: v.union(v.string(), v.null()),
+        component_diff: componentDiff,
+      }),
+    ),
+  }),
+});
+
 export const deploymentState = v.union(
   v.literal("paused"),
   v.literal("running"),
@@ -227,6 +242,7 @@ const deploymentAuditLogTable = defineTable(
     buildIndexes,
     pushConfig,
     pushConfigWithComponents,
+    rollbackPush,
     changeDeploymentState,
     clearTables,
     snapshotImport,
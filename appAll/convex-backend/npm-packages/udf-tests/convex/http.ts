@@ -1,5 +1,5 @@
 import { httpRouter } from "convex/server";
-import { imported } from "./http_no_default";
+import { imported, streamCount, webSocketCount } from "./http_no_default";
 import { httpAction, query } from "./_generated/server";
 
 // This file is used for testing the analyze logic for HTTP actions, including
@@ -38,4 +38,18 @@ export const myQuery = query((_) => {
   return "hello";
 });
 
+// Streams served by the backend, see
+// crates/local_backend/src/http_action_streams.rs.
+http.route({
+  method: "GET",
+  path: "/stream_count",
+  handler: streamCount,
+});
+
+http.route({
+  method: "GET",
+  path: "/web_socket_count",
+  handler: webSocketCount,
+});
+
 export default http;
@@ -1,4 +1,9 @@
-import { httpAction } from "./_generated/server";
+import {
+  querySubscriptionResponse,
+  webSocketResponse,
+} from "convex/server";
+import { api } from "./_generated/api";
+import { httpAction, mutation } from "./_generated/server";
 
 export const nop = null;
 
@@ -6,4 +11,22 @@ export const imported = httpAction(async (_ctx, _request: Request) => {
   return new Response("success");
 });
 
+export const streamCount = httpAction(async (_ctx, _request: Request) => {
+  return querySubscriptionResponse(api.basic.count, {});
+});
+
+export const insertMessage = mutation(
+  async ({ db }, { message }: { message: string | ArrayBuffer }) => {
+    await db.insert("objects", { message });
+    return await db.query("objects").count();
+  },
+);
+
+export const webSocketCount = httpAction(async (_ctx, _request: Request) => {
+  return webSocketResponse({
+    onMessage: api.http_no_default.insertMessage,
+    subscribe: { query: api.basic.count, args: {} },
+  });
+});
+
 export {};
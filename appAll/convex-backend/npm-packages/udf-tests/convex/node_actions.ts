@@ -5,6 +5,7 @@ import {
   internalActionGeneric,
   makeFunctionReference,
 } from "convex/server";
+import http from "node:http";
 import { api } from "./_generated/api";
 import { ActionCtx } from "./_generated/server";
 
@@ -68,6 +69,25 @@ export const workHardForAnHour = actionGeneric(async () => {
   return "I worked for a whole hour";
 });
 
+export const allocateForever = actionGeneric(async () => {
+  const chunks = [];
+  for (;;) {
+    chunks.push(new Array(1 << 20).fill(Math.random()));
+  }
+});
+
+export const httpGet = actionGeneric(async (_, { url }: { url: string }) => {
+  return await new Promise((resolve, reject) => {
+    http
+      .get(url, (response) => {
+        let body = "";
+        response.on("data", (chunk) => (body += chunk));
+        response.on("end", () => resolve(body));
+      })
+      .on("error", reject);
+  });
+});
+
 export const getTestEnvVar = actionGeneric(async () => {
   if (process.env.UNKNOWN_VAR !== undefined) {
     throw new Error("Unexpected environment variable defined for UNKNOWN_VAR");
@@ -104,3 +124,27 @@ export const actionCallsAction = actionGeneric(
 export const partialEscapeSequence = actionGeneric(async () => {
   return "\ud83c...";
 });
+
+// Leaves a global behind, and a timer running if `leaveTimer` is set, to
+// check that neither leaks into later requests on the same worker.
+export const leakState = actionGeneric(
+  async (_, { leaveTimer }: { leaveTimer: boolean }) => {
+    const previous = (globalThis as any).leakedGlobal ?? null;
+    (globalThis as any).leakedGlobal = "leaked";
+    if (leaveTimer) {
+      setInterval(() => {
+        // intentional noop
+      }, 1000);
+    }
+    // Try to end the request early by impersonating the worker.
+    process.stdout.write(
+      JSON.stringify({
+        kind: "WorkerDone",
+        nonce: "",
+        rssBytes: 0,
+        clean: true,
+      }) + "\n",
+    );
+    return previous;
+  },
+);
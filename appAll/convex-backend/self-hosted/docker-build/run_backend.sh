@@ -4,10 +4,12 @@ export DATA_DIR=${DATA_DIR:-/convex/data}
 export TMPDIR=${TMPDIR:-"$DATA_DIR/tmp"}
 export STORAGE_DIR=${STORAGE_DIR:-"$DATA_DIR/storage"}
 export SQLITE_DB=${SQLITE_DB:-"$DATA_DIR/db.sqlite3"}
+export ROCKSDB_DIR=${ROCKSDB_DIR:-"$DATA_DIR/db.rocksdb"}
 
 # Database driver flags matching DbDriverTag values
 POSTGRES_DB_FLAGS=(--db postgres-v5)
 MYSQL_DB_FLAGS=(--db mysql-v5)
+ROCKSDB_DB_FLAGS=(--db rocksdb)
 
 set -e
 mkdir -p "$TMPDIR" "$STORAGE_DIR"
@@ -25,6 +27,9 @@ elif [ -n "$DATABASE_URL" ]; then
     echo "Warning: DATABASE_URL is deprecated. Please use POSTGRES_URL for PostgreSQL or MYSQL_URL for MySQL connections instead."
     DB_SPEC="$DATABASE_URL"
     DB_FLAGS=("${POSTGRES_DB_FLAGS[@]}")  # Maintain backwards compatibility with existing DATABASE_URL behavior
+elif [ "$EMBEDDED_DB" = "rocksdb" ]; then
+    DB_SPEC="$ROCKSDB_DIR"
+    DB_FLAGS=("${ROCKSDB_DB_FLAGS[@]}")
 else
     # Otherwise fallback to SQLite
     DB_SPEC="$SQLITE_DB"
@@ -48,5 +53,10 @@ exec ./convex-local-backend "$@" \
     ${DISABLE_BEACON:+--disable-beacon} \
     ${REDACT_LOGS_TO_CLIENT:+--redact-logs-to-client} \
     ${DO_NOT_REQUIRE_SSL:+--do-not-require-ssl} \
+    ${POSTGRES_SCHEMA:+--postgres-schema "$POSTGRES_SCHEMA"} \
+    ${POSTGRES_REPLICA_URL:+--postgres-replica-url "$POSTGRES_REPLICA_URL"} \
+    ${MYSQL_TABLE_PREFIX:+--mysql-table-prefix "$MYSQL_TABLE_PREFIX"} \
+    ${OTLP_ENDPOINT:+--otlp-endpoint "$OTLP_ENDPOINT"} \
+    ${OTLP_TRACE_SAMPLE_RATIO:+--otlp-trace-sample-ratio "$OTLP_TRACE_SAMPLE_RATIO"} \
     "${DB_FLAGS[@]}" \
     "$DB_SPEC"